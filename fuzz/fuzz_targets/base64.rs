@@ -0,0 +1,17 @@
+#![no_main]
+
+use cryptopals::utils::{b64_url_to_bytes, bytes_to_b64_str, bytes_to_b64_url, decode_b64_str};
+use libfuzzer_sys::fuzz_target;
+
+// Standard base64 has one encoding for each input, so anything that decodes round trips. The
+// URL-safe decoder takes padding or not, so only the decoded bytes have to agree
+fuzz_target!(|input: &str| {
+    if let Ok(bytes) = decode_b64_str(input) {
+        assert_eq!(bytes_to_b64_str(&bytes), input);
+    }
+    if let Ok(bytes) = b64_url_to_bytes(input) {
+        for padded in [false, true] {
+            assert_eq!(b64_url_to_bytes(&bytes_to_b64_url(&bytes, padded)).unwrap(), bytes);
+        }
+    }
+});
@@ -0,0 +1,12 @@
+#![no_main]
+
+use cryptopals::set2::challenge13::{decode_kv, encode_kv};
+use libfuzzer_sys::fuzz_target;
+
+// Profiles come back from whatever an attacker decrypts, so the decoder sees arbitrary strings
+fuzz_target!(|input: &str| {
+    if let Ok(pairs) = decode_kv(input) {
+        let borrowed: Vec<(&str, &str)> = pairs.iter().map(|(k, v)| (&k[..], &v[..])).collect();
+        assert_eq!(decode_kv(&encode_kv(&borrowed)).unwrap(), pairs);
+    }
+});
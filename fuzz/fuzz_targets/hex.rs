@@ -0,0 +1,11 @@
+#![no_main]
+
+use cryptopals::utils::{bytes_to_hex, hex_to_bytes};
+use libfuzzer_sys::fuzz_target;
+
+// Whatever decodes has to encode back to the same digits, give or take case
+fuzz_target!(|input: &str| {
+    if let Ok(bytes) = hex_to_bytes(input) {
+        assert_eq!(bytes_to_hex(&bytes), input.to_ascii_lowercase());
+    }
+});
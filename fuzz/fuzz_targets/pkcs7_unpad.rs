@@ -0,0 +1,14 @@
+#![no_main]
+
+use cryptopals::set2::challenge13::pkcs7_unpad;
+use libfuzzer_sys::fuzz_target;
+
+// Padding oracles feed this whatever a tampered ciphertext decrypts to
+fuzz_target!(|bytes: &[u8]| {
+    if let Ok(unpadded) = pkcs7_unpad(bytes) {
+        let padding = bytes.len() - unpadded.len();
+        assert!(bytes.starts_with(&unpadded));
+        assert!((1..=255).contains(&padding));
+        assert!(bytes[unpadded.len()..].iter().all(|b| *b as usize == padding));
+    }
+});
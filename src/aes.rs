@@ -0,0 +1,263 @@
+//! AES block cipher, as specified in FIPS-197
+//!
+//! Nothing clever: a byte-oriented implementation with the S-boxes generated at compile time.
+//! It's far slower than openssl, but it's ours.
+#![allow(dead_code)]
+
+use anyhow::anyhow;
+
+use crate::utils::*;
+
+pub const BLOCK_SIZE: usize = 16;
+
+/// Multiplication in GF(2^8) modulo x^8 + x^4 + x^3 + x + 1
+const fn gmul(mut a: u8, mut b: u8) -> u8 {
+    let mut p = 0;
+    while b != 0 {
+        if b & 1 != 0 {
+            p ^= a;
+        }
+        let carry = a & 0x80;
+        a <<= 1;
+        if carry != 0 {
+            a ^= 0x1b;
+        }
+        b >>= 1;
+    }
+    p
+}
+
+/// Builds the S-box by walking p over the generator 3 and q over its inverse, then applying the
+/// affine transformation to q
+const fn sboxes() -> ([u8; 256], [u8; 256]) {
+    let mut sbox = [0; 256];
+    let mut inv_sbox = [0; 256];
+    let mut p: u8 = 1;
+    let mut q: u8 = 1;
+    loop {
+        p = p ^ (p << 1) ^ if p & 0x80 != 0 { 0x1b } else { 0 };
+        q ^= q << 1;
+        q ^= q << 2;
+        q ^= q << 4;
+        if q & 0x80 != 0 {
+            q ^= 0x09;
+        }
+        let x = q ^ q.rotate_left(1) ^ q.rotate_left(2) ^ q.rotate_left(3) ^ q.rotate_left(4);
+        sbox[p as usize] = x ^ 0x63;
+        inv_sbox[(x ^ 0x63) as usize] = p;
+        if p == 1 {
+            break;
+        }
+    }
+    // Zero has no inverse, so isn't reached above
+    sbox[0] = 0x63;
+    inv_sbox[0x63] = 0;
+    (sbox, inv_sbox)
+}
+
+const SBOX: [u8; 256] = sboxes().0;
+const INV_SBOX: [u8; 256] = sboxes().1;
+
+#[derive(Clone)]
+pub struct Aes {
+    round_keys: Vec<[u8; BLOCK_SIZE]>,
+}
+
+impl Aes {
+    /// Expands a 128, 192 or 256-bit key
+    pub fn new(key: &[u8]) -> Result<Self> {
+        if ![16, 24, 32].contains(&key.len()) {
+            return Err(anyhow!("Invalid AES key length: {}", key.len()));
+        }
+        let nk = key.len() / 4;
+        let rounds = nk + 6;
+
+        let mut words: Vec<[u8; 4]> = key.chunks(4).map(|w| w.try_into().unwrap()).collect();
+        let mut rcon: u8 = 1;
+        for i in nk..4 * (rounds + 1) {
+            let mut temp = words[i - 1];
+            if i % nk == 0 {
+                temp.rotate_left(1);
+                temp = temp.map(|b| SBOX[b as usize]);
+                temp[0] ^= rcon;
+                rcon = gmul(rcon, 2);
+            } else if nk > 6 && i % nk == 4 {
+                temp = temp.map(|b| SBOX[b as usize]);
+            }
+            let prev = words[i - nk];
+            words.push([
+                prev[0] ^ temp[0],
+                prev[1] ^ temp[1],
+                prev[2] ^ temp[2],
+                prev[3] ^ temp[3],
+            ]);
+        }
+
+        let round_keys = words
+            .chunks(4)
+            .map(|ws| ws.concat().try_into().unwrap())
+            .collect();
+        Ok(Self { round_keys })
+    }
+
+    pub fn encrypt_block(&self, block: &mut [u8; BLOCK_SIZE]) {
+        let rounds = self.round_keys.len() - 1;
+        add_round_key(block, &self.round_keys[0]);
+        for round in 1..rounds {
+            sub_bytes(block, &SBOX);
+            shift_rows(block);
+            mix_columns(block);
+            add_round_key(block, &self.round_keys[round]);
+        }
+        sub_bytes(block, &SBOX);
+        shift_rows(block);
+        add_round_key(block, &self.round_keys[rounds]);
+    }
+
+    pub fn decrypt_block(&self, block: &mut [u8; BLOCK_SIZE]) {
+        let rounds = self.round_keys.len() - 1;
+        add_round_key(block, &self.round_keys[rounds]);
+        for round in (1..rounds).rev() {
+            inv_shift_rows(block);
+            sub_bytes(block, &INV_SBOX);
+            add_round_key(block, &self.round_keys[round]);
+            inv_mix_columns(block);
+        }
+        inv_shift_rows(block);
+        sub_bytes(block, &INV_SBOX);
+        add_round_key(block, &self.round_keys[0]);
+    }
+}
+
+// The state is stored column by column, as in the input: byte 4c + r is row r, column c
+
+fn add_round_key(state: &mut [u8; BLOCK_SIZE], key: &[u8; BLOCK_SIZE]) {
+    for (s, k) in state.iter_mut().zip(key.iter()) {
+        *s ^= k;
+    }
+}
+
+fn sub_bytes(state: &mut [u8; BLOCK_SIZE], table: &[u8; 256]) {
+    for s in state.iter_mut() {
+        *s = table[*s as usize];
+    }
+}
+
+fn shift_rows(state: &mut [u8; BLOCK_SIZE]) {
+    let old = *state;
+    for r in 1..4 {
+        for c in 0..4 {
+            state[4 * c + r] = old[4 * ((c + r) % 4) + r];
+        }
+    }
+}
+
+fn inv_shift_rows(state: &mut [u8; BLOCK_SIZE]) {
+    let old = *state;
+    for r in 1..4 {
+        for c in 0..4 {
+            state[4 * ((c + r) % 4) + r] = old[4 * c + r];
+        }
+    }
+}
+
+fn mix_columns(state: &mut [u8; BLOCK_SIZE]) {
+    for col in state.chunks_mut(4) {
+        let [a0, a1, a2, a3] = [col[0], col[1], col[2], col[3]];
+        col[0] = gmul(a0, 2) ^ gmul(a1, 3) ^ a2 ^ a3;
+        col[1] = a0 ^ gmul(a1, 2) ^ gmul(a2, 3) ^ a3;
+        col[2] = a0 ^ a1 ^ gmul(a2, 2) ^ gmul(a3, 3);
+        col[3] = gmul(a0, 3) ^ a1 ^ a2 ^ gmul(a3, 2);
+    }
+}
+
+fn inv_mix_columns(state: &mut [u8; BLOCK_SIZE]) {
+    for col in state.chunks_mut(4) {
+        let [a0, a1, a2, a3] = [col[0], col[1], col[2], col[3]];
+        col[0] = gmul(a0, 14) ^ gmul(a1, 11) ^ gmul(a2, 13) ^ gmul(a3, 9);
+        col[1] = gmul(a0, 9) ^ gmul(a1, 14) ^ gmul(a2, 11) ^ gmul(a3, 13);
+        col[2] = gmul(a0, 13) ^ gmul(a1, 9) ^ gmul(a2, 14) ^ gmul(a3, 11);
+        col[3] = gmul(a0, 11) ^ gmul(a1, 13) ^ gmul(a2, 9) ^ gmul(a3, 14);
+    }
+}
+
+/// Decrypts whole blocks in ECB mode, leaving any padding in place
+pub fn aes_ecb_decrypt(ciphertext: &[u8], key: &[u8]) -> Result<Vec<u8>> {
+    if !ciphertext.len().is_multiple_of(BLOCK_SIZE) {
+        return Err(anyhow!(
+            "Ciphertext length {} is not a multiple of the block size",
+            ciphertext.len()
+        ));
+    }
+    let aes = Aes::new(key)?;
+    let mut plaintext = Vec::with_capacity(ciphertext.len());
+    for chunk in ciphertext.chunks(BLOCK_SIZE) {
+        let mut block: [u8; BLOCK_SIZE] = chunk.try_into().unwrap();
+        aes.decrypt_block(&mut block);
+        plaintext.extend_from_slice(&block);
+    }
+    Ok(plaintext)
+}
+
+/// Encrypts whole blocks in ECB mode, without adding any padding
+pub fn aes_ecb_encrypt(plaintext: &[u8], key: &[u8]) -> Result<Vec<u8>> {
+    if !plaintext.len().is_multiple_of(BLOCK_SIZE) {
+        return Err(anyhow!(
+            "Plaintext length {} is not a multiple of the block size",
+            plaintext.len()
+        ));
+    }
+    let aes = Aes::new(key)?;
+    let mut ciphertext = Vec::with_capacity(plaintext.len());
+    for chunk in plaintext.chunks(BLOCK_SIZE) {
+        let mut block: [u8; BLOCK_SIZE] = chunk.try_into().unwrap();
+        aes.encrypt_block(&mut block);
+        ciphertext.extend_from_slice(&block);
+    }
+    Ok(ciphertext)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sbox() {
+        assert_eq!(SBOX[0x00], 0x63);
+        assert_eq!(SBOX[0x53], 0xed);
+        assert_eq!(INV_SBOX[0xed], 0x53);
+        assert!((0..=255).all(|b| INV_SBOX[SBOX[b] as usize] == b as u8));
+    }
+
+    #[test]
+    fn fips_197_known_answers() {
+        // FIPS-197 appendix C
+        let plaintext = hex_to_bytes("00112233445566778899aabbccddeeff").unwrap();
+        let vectors = [
+            (
+                "000102030405060708090a0b0c0d0e0f",
+                "69c4e0d86a7b0430d8cdb78070b4c55a",
+            ),
+            (
+                "000102030405060708090a0b0c0d0e0f1011121314151617",
+                "dda97ca4864cdfe06eaf70a0ec0d7191",
+            ),
+            (
+                "000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f",
+                "8ea2b7ca516745bfeafc49904b496089",
+            ),
+        ];
+        for (key, ciphertext) in vectors {
+            let key = hex_to_bytes(key).unwrap();
+            let encrypted = aes_ecb_encrypt(&plaintext, &key).unwrap();
+            assert_eq!(bytes_to_hex(&encrypted), ciphertext);
+            assert_eq!(aes_ecb_decrypt(&encrypted, &key).unwrap(), plaintext);
+        }
+    }
+
+    #[test]
+    fn bad_key_length() {
+        assert!(Aes::new(&[0; 15]).is_err());
+        assert!(Aes::new(&[0; 20]).is_err());
+    }
+}
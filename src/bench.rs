@@ -0,0 +1,358 @@
+//! Timings for the kernels the slower attacks spend their time in, so that work on making them
+//! faster has numbers to go on
+//!
+//! Each kernel runs in batches big enough to time reliably, after one batch to warm up, and the
+//! median time per call across the batches is what gets reported, along with the fastest and
+//! slowest. Build with `--release` for numbers worth comparing.
+//!
+//! Whole challenges can be timed too, with [`challenge`]: it runs one over and over, and gives the
+//! least, mean and most of its time and of everything it counts, like oracle queries.
+use std::fmt;
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use anyhow::anyhow;
+use num_bigint::{BigInt, RandBigInt};
+use num_traits::One;
+
+use crate::ecdsa::Params;
+use crate::gcm::Gf;
+use crate::lattice::lll;
+use crate::runner::{self, Status};
+use crate::set7::challenge55::generate_md4_candidate_pair;
+use crate::set8::{challenge62, params};
+use crate::utils::Result;
+
+/// Everything `bench` can time
+pub const NAMES: [&str; 6] = ["modpow", "scale", "ladder", "gf-mul", "lll", "md4-massage"];
+
+/// How long each batch should take
+const BATCH: Duration = Duration::from_millis(50);
+
+/// How many batches go into the median
+const BATCHES: usize = 15;
+
+/// Median, fastest and slowest time per call over a number of batches
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Timing {
+    pub median: Duration,
+    pub min: Duration,
+    pub max: Duration,
+    /// Calls per batch
+    pub calls: u32,
+}
+
+impl fmt::Display for Timing {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:>10.3?} per call ({:.3?} to {:.3?}, {} calls a batch)",
+            self.median, self.min, self.max, self.calls
+        )
+    }
+}
+
+fn time_batch<T>(calls: u32, f: &mut impl FnMut() -> T) -> Duration {
+    let start = Instant::now();
+    for _ in 0..calls {
+        black_box(f());
+    }
+    start.elapsed()
+}
+
+/// Times `f`, doubling the batch size until a batch takes a tenth of [`BATCH`] and then scaling
+/// it up to fill one, which warms it up along the way
+pub fn measure<T>(batches: usize, mut f: impl FnMut() -> T) -> Timing {
+    let mut calls = 1;
+    let mut elapsed = time_batch(calls, &mut f);
+    while elapsed < BATCH / 10 {
+        calls *= 2;
+        elapsed = time_batch(calls, &mut f);
+    }
+    let calls = ((calls as f64) * BATCH.as_secs_f64() / elapsed.as_secs_f64()).max(1.0) as u32;
+    let mut per_call: Vec<Duration> = (0..batches.max(1))
+        .map(|_| time_batch(calls, &mut f) / calls)
+        .collect();
+    per_call.sort();
+    Timing {
+        median: per_call[per_call.len() / 2],
+        min: per_call[0],
+        max: per_call[per_call.len() - 1],
+        calls,
+    }
+}
+
+fn report<T>(label: &str, f: impl FnMut() -> T) {
+    println!("{label:<44} {}", measure(BATCHES, f));
+}
+
+/// num-bigint switches to Montgomery multiplication for odd moduli, so an even modulus of the same
+/// size shows what that buys
+fn modpow() {
+    let mut rng = crate::utils::rng();
+    let base = BigInt::from(rng.gen_biguint(2048));
+    let exponent = BigInt::from(rng.gen_biguint(2048));
+    let odd = BigInt::from(rng.gen_biguint(2048)) | BigInt::one() | (BigInt::one() << 2047);
+    let even = &odd - 1;
+    report("modpow, 2048-bit odd modulus (Montgomery)", || {
+        base.modpow(&exponent, &odd)
+    });
+    report("modpow, 2048-bit even modulus (plain)", || {
+        base.modpow(&exponent, &even)
+    });
+}
+
+fn scale() {
+    let curve = params::curve();
+    let k = crate::utils::rng().gen_bigint_range(&BigInt::one(), params::base_order());
+    report("Curve::scale, affine, 128-bit scalar", || {
+        curve.scale(params::base_point(), &k)
+    });
+}
+
+fn ladder() {
+    let curve = params::montgomery();
+    let k = crate::utils::rng().gen_bigint_range(&BigInt::one(), params::base_order());
+    report("MontgomeryCurve::ladder, 128-bit scalar", || {
+        curve.ladder(&curve.bp, &k)
+    });
+}
+
+fn gf_mul() {
+    let mut rng = crate::utils::rng();
+    let (a, b) = (Gf::random(&mut rng), Gf::random(&mut rng));
+    report("GF(2^128) multiplication", || black_box(a) * black_box(b));
+}
+
+/// The 22-dimensional lattice challenge 62 reduces, from 20 signatures with 8-bit biased nonces
+fn lattice() {
+    let params = Params::default();
+    let mut rng = crate::utils::rng();
+    let (private_key, _) = params.keypair(&mut rng);
+    let signatures = challenge62::biased_signatures(&private_key, &params, 20, 8, &mut rng);
+    let basis = challenge62::basis(&params, &signatures, 8);
+    report("LLL, challenge 62's 22-dimensional lattice", || {
+        let mut basis = basis.clone();
+        lll(&mut basis);
+        basis
+    });
+}
+
+/// Challenge 55's first and second round massaging, to the point of a candidate pair
+fn md4_massage() {
+    let mut rng = crate::utils::rng();
+    report("MD4 message massaging, one candidate pair", || {
+        generate_md4_candidate_pair(&mut rng)
+    });
+}
+
+/// Times the kernel called `name`, or all of them
+pub fn run(name: Option<&str>) -> Result<()> {
+    let names: Vec<&str> = match name {
+        Some(name) if NAMES.contains(&name) => vec![name],
+        Some(other) => {
+            return Err(anyhow!(
+                "No benchmark called {other}, try one of: {}",
+                NAMES.join(", ")
+            ))
+        }
+        None => NAMES.to_vec(),
+    };
+    if cfg!(debug_assertions) {
+        println!("This is a debug build, so expect these to be slow");
+    }
+    for name in names {
+        match name {
+            "modpow" => modpow(),
+            "scale" => scale(),
+            "ladder" => ladder(),
+            "gf-mul" => gf_mul(),
+            "lll" => lattice(),
+            "md4-massage" => md4_massage(),
+            _ => unreachable!(),
+        }
+    }
+    Ok(())
+}
+
+/// Times and counts from running a challenge several times
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Runs {
+    pub challenge: u64,
+    /// How long each run that passed took
+    pub times: Vec<Duration>,
+    /// Each count the challenge reports, by name, with its value from every run that passed
+    pub counts: Vec<(String, Vec<u64>)>,
+    /// The seed of each run that failed, and why
+    pub failures: Vec<(u64, String)>,
+}
+
+impl Runs {
+    /// Adds a run's time and counts
+    fn add(&mut self, elapsed: Duration, counts: &[(String, u64)]) {
+        self.times.push(elapsed);
+        for (name, n) in counts {
+            match self.counts.iter_mut().find(|(known, _)| known == name) {
+                Some((_, ns)) => ns.push(*n),
+                None => self.counts.push((name.clone(), vec![*n])),
+            }
+        }
+    }
+}
+
+/// The least, mean and most of `values`
+fn spread<T: Ord + fmt::Debug>(values: &[T], mean: impl FnOnce(&[T]) -> String) -> [String; 3] {
+    let min = values.iter().min().unwrap();
+    let max = values.iter().max().unwrap();
+    [format!("{min:.2?}"), mean(values), format!("{max:.2?}")]
+}
+
+impl fmt::Display for Runs {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let runs = self.times.len() + self.failures.len();
+        writeln!(
+            f,
+            "Challenge {} over {runs} runs, {} passed ({:.0}%)",
+            self.challenge,
+            self.times.len(),
+            100.0 * self.times.len() as f64 / runs.max(1) as f64
+        )?;
+        for (seed, why) in &self.failures {
+            writeln!(
+                f,
+                "Failed with --seed {seed}: {}",
+                why.lines().next().unwrap_or_default()
+            )?;
+        }
+        if self.times.is_empty() {
+            return Ok(());
+        }
+        let width = self
+            .counts
+            .iter()
+            .map(|(name, _)| name.len())
+            .fold(4, usize::max);
+        writeln!(
+            f,
+            "{:width$}  {:>10}  {:>10}  {:>10}",
+            "", "min", "mean", "max"
+        )?;
+        let time = spread(&self.times, |times| {
+            format!(
+                "{:.2?}",
+                times.iter().sum::<Duration>() / times.len() as u32
+            )
+        });
+        let mut rows = vec![("time", time)];
+        for (name, ns) in &self.counts {
+            let counts = spread(ns, |ns| {
+                format!("{:.1}", ns.iter().sum::<u64>() as f64 / ns.len() as f64)
+            });
+            rows.push((name, counts));
+        }
+        for (name, [min, mean, max]) in rows {
+            writeln!(f, "{name:width$}  {min:>10}  {mean:>10}  {max:>10}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Runs challenge `c` `iters` times, with its output held back, and prints how long it took and
+/// what it counted, failing as soon as a run does
+pub fn challenge(c: u64, iters: u32) -> Result<()> {
+    print!("{}", runs(c, iters, true)?);
+    Ok(())
+}
+
+/// Runs challenge `c` `times` times, with its output held back, carrying on past failures, and
+/// prints how often it passed, the seeds of any runs that didn't, and what the rest counted, like
+/// challenge 55's tries or challenge 60's jumps
+pub fn repeat(c: u64, times: u32) -> Result<()> {
+    print!("{}", runs(c, times, false)?);
+    Ok(())
+}
+
+/// Runs challenge `c` `iters` times, failing at the first run that does if `stop` is set, or else
+/// noting it down
+fn runs(c: u64, iters: u32, stop: bool) -> Result<Runs> {
+    runner::find(c).ok_or_else(|| anyhow!("Invalid challenge number"))?;
+    if cfg!(debug_assertions) {
+        println!("This is a debug build, so expect these to be slow");
+    }
+    let mut runs = Runs {
+        challenge: c,
+        ..Default::default()
+    };
+    for _ in 0..iters {
+        let (outcome, _) = crate::report::capture(|| runner::attempt(c));
+        let report = outcome.report.unwrap_or_default();
+        match outcome.status {
+            Status::Passed => runs.add(outcome.elapsed, &report.counts),
+            Status::Failed(why) if stop => {
+                return Err(anyhow!(
+                    "Challenge {c} failed, rerun it with --seed {}: {why}",
+                    outcome.seed
+                ))
+            }
+            Status::Failed(why) => runs.failures.push((outcome.seed, why)),
+        }
+    }
+    Ok(runs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn timings() {
+        let mut count = 0_u64;
+        let timing = measure(3, || {
+            count += 1;
+            std::thread::sleep(Duration::from_millis(1));
+        });
+        assert!(timing.min <= timing.median && timing.median <= timing.max);
+        assert!(timing.min >= Duration::from_millis(1), "{timing}");
+        // Enough calls to fill a batch, and the batches themselves on top of calibrating
+        assert!(
+            timing.calls > 1 && count >= 3 * timing.calls as u64,
+            "{timing}"
+        );
+        assert!(run(Some("nothing")).is_err());
+    }
+
+    #[test]
+    fn runs() {
+        let mut runs = Runs {
+            challenge: 57,
+            ..Default::default()
+        };
+        runs.add(
+            Duration::from_millis(200),
+            &[("residues".to_string(), 12), ("queries".to_string(), 90)],
+        );
+        runs.add(
+            Duration::from_millis(300),
+            &[("residues".to_string(), 12), ("queries".to_string(), 110)],
+        );
+        assert_eq!(
+            runs.to_string(),
+            "Challenge 57 over 2 runs, 2 passed (100%)\n\
+             \x20                min        mean         max\n\
+             time        200.00ms    250.00ms    300.00ms\n\
+             residues          12        12.0          12\n\
+             queries           90       100.0         110\n"
+        );
+
+        runs.failures
+            .push((42, "x: expected 1, got 2\nand more".to_string()));
+        runs.failures
+            .push((43, "No wild kangaroo caught".to_string()));
+        assert!(runs.to_string().starts_with(
+            "Challenge 57 over 4 runs, 2 passed (50%)\n\
+             Failed with --seed 42: x: expected 1, got 2\n\
+             Failed with --seed 43: No wild kangaroo caught\n\
+             \x20                min"
+        ));
+    }
+}
@@ -0,0 +1,189 @@
+//! Fermat's factorization of an RSA modulus whose primes are too close together
+//!
+//! Any odd n = p q is a difference of squares, a^2 - b^2 with a = (p + q) / 2 and b = (p - q) / 2.
+//! When p and q are close, a is only just above sqrt(n), so trying a = ceil(sqrt(n)), and each
+//! number after it, until a^2 - n is a square finds them quickly: after about
+//! (p - q)^2 / (8 sqrt(n)) tries, which is a single one once they're within 2 n^(1/4) of each other.
+//!
+//! Primes drawn independently at random are nowhere near that close (they're typically about
+//! 2^(bits/2 - 1) apart), so the search is cut short, and giving up is the sign of a key from normal
+//! keygen. How far it got is still worth something, as it proves how far apart p and q must be.
+
+use num_bigint::{BigInt, RandBigInt};
+use num_traits::ToPrimitive;
+use rand::Rng;
+
+use crate::rsa::{is_probable_prime, random_prime, KeyPair, Params};
+use crate::utils::*;
+
+/// How many values of a to try, unless `--arg fermat_steps=` says otherwise
+const STEPS: u64 = 1 << 20;
+/// Miller-Rabin rounds for the primes next to each other
+const ROUNDS: usize = 40;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Fermat {
+    /// n = p q, with p >= q, found on the given step
+    Factored { p: BigInt, q: BigInt, steps: u64 },
+    /// No factors after all the steps, so |p - q| is at least `min_gap`
+    GaveUp { min_gap: BigInt },
+}
+
+pub fn main() -> Result<()> {
+    let params = Params::from_options(1024, 65537)?;
+    let steps = crate::options::get().arg("fermat_steps")?.unwrap_or(STEPS);
+    let mut rng = crate::utils::rng();
+    let bits = params.bits;
+    println!("Using a {params}, and up to {steps} steps");
+
+    for gap_bits in [bits / 8, bits / 4, bits / 4 + 4, bits / 4 + 8] {
+        let KeyPair { public, .. } = close_keypair(&params, gap_bits, &mut rng);
+        print!("Primes about 2^{gap_bits} apart: ");
+        report(&fermat(&public.modulus, steps));
+    }
+
+    print!("A normal key: ");
+    let KeyPair { public, .. } = params.keypair(&mut rng);
+    report(&fermat(&public.modulus, steps));
+    Ok(())
+}
+
+fn report(result: &Fermat) {
+    match result {
+        Fermat::Factored { p, q, steps } => {
+            println!("factored on step {steps}");
+            println!("  p = {p}\n  q = {q}");
+        }
+        Fermat::GaveUp { min_gap } => {
+            println!(
+                "gave up, as |p - q| >= 2^{}, so most likely normal keygen",
+                min_gap.bits().saturating_sub(1)
+            );
+        }
+    }
+}
+
+/// A key pair whose second prime is the first prime after the first one plus a random offset of
+/// exactly `gap_bits` bits
+pub fn close_keypair<R: Rng>(params: &Params, gap_bits: u64, rng: &mut R) -> KeyPair {
+    let mut previous: Option<BigInt> = None;
+    params.keypair_from(|| match previous.take() {
+        None => {
+            let p = random_prime(params.bits / 2, &params.e, rng);
+            previous = Some(p.clone());
+            p
+        }
+        Some(p) => {
+            let mut offset = BigInt::from(rng.gen_biguint(gap_bits));
+            offset.set_bit(gap_bits - 1, true);
+            let mut q = p + offset;
+            q.set_bit(0, true);
+            while !is_probable_prime(&q, ROUNDS, rng) {
+                q += 2;
+            }
+            q
+        }
+    })
+}
+
+/// Fermat's method on odd n, for at most `steps` values of a
+pub fn fermat(n: &BigInt, steps: u64) -> Fermat {
+    let mut a = n.sqrt();
+    if &a * &a < *n {
+        a += 1;
+    }
+    // a^2 - n, kept up to date as a goes up by adding 2a + 1
+    let mut r: BigInt = &a * &a - n;
+    for step in 1..=steps {
+        if let Some(b) = square_root(&r) {
+            return Fermat::Factored {
+                p: &a + &b,
+                q: &a - &b,
+                steps: step,
+            };
+        }
+        r += 2 * &a + 1;
+        a += 1;
+    }
+    // p + q = 2 (a + b) >= 2a for the a the search would have tried next, and
+    // (p - q)^2 = (p + q)^2 - 4n
+    let min_gap_squared: BigInt = 4 * (&a * &a - n);
+    Fermat::GaveUp {
+        min_gap: min_gap_squared.sqrt(),
+    }
+}
+
+/// sqrt(x) if x is a perfect square, ruling most other numbers out with squares mod 64 and 63
+/// before taking a root
+fn square_root(x: &BigInt) -> Option<BigInt> {
+    let low = x.iter_u64_digits().next().unwrap_or(0) & 63;
+    if !matches!(low, 0 | 1 | 4 | 9 | 16 | 17 | 25 | 33 | 36 | 41 | 49 | 57) {
+        return None;
+    }
+    let r63 = (x % 63u32).to_u32().unwrap();
+    if !(0..63u32).any(|y| y * y % 63 == r63) {
+        return None;
+    }
+    let root = x.sqrt();
+    (&root * &root == *x).then_some(root)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use num_traits::Zero;
+
+    #[test]
+    fn squares() {
+        for x in 0..10_000u32 {
+            let root = (x as f64).sqrt() as u32;
+            let expected = (root * root == x).then_some(BigInt::from(root));
+            assert_eq!(square_root(&x.into()), expected, "{x}");
+        }
+    }
+
+    #[test]
+    fn close_primes() {
+        let mut rng = rand::thread_rng();
+        let params = Params::new(512, 65537);
+        let KeyPair { public, .. } = close_keypair(&params, 100, &mut rng);
+        match fermat(&public.modulus, STEPS) {
+            Fermat::Factored { p, q, steps } => {
+                assert_eq!(&p * &q, public.modulus);
+                assert!(p >= q);
+                assert_eq!(steps, 1);
+            }
+            other => panic!("{other:?}"),
+        }
+
+        // 2^136 apart takes about 2^(2 * 8 - 3) steps
+        let KeyPair { public, .. } = close_keypair(&params, 136, &mut rng);
+        assert!(matches!(
+            fermat(&public.modulus, STEPS),
+            Fermat::Factored { steps, .. } if steps > 1
+        ));
+    }
+
+    #[test]
+    fn normal_keygen() {
+        let params = Params::new(512, 65537);
+        let mut rng = rand::thread_rng();
+        for _ in 0..4 {
+            let KeyPair { public, .. } = params.keypair(&mut rng);
+            let Fermat::GaveUp { min_gap } = fermat(&public.modulus, 1 << 10) else {
+                panic!("Factored a normal key");
+            };
+            assert!(min_gap > BigInt::zero());
+        }
+
+        // The bound is a true one, on a small example: 101 * 67 = 6767, 34 apart
+        let Fermat::GaveUp { min_gap } = fermat(&6767.into(), 1) else {
+            panic!("Too few steps to factor");
+        };
+        assert!(min_gap <= 34.into());
+        assert!(matches!(
+            fermat(&6767.into(), 100),
+            Fermat::Factored { p, q, steps: 2 } if p == 101.into() && q == 67.into()
+        ));
+    }
+}
@@ -0,0 +1,22 @@
+//! Attacks which aren't among the challenges, but belong alongside them
+pub mod fermat;
+pub mod nonce_reuse;
+pub mod wiener;
+
+use crate::utils::Result;
+use anyhow::anyhow;
+
+/// Everything `bonus` can run
+pub const NAMES: [&str; 3] = ["fermat", "nonce-reuse", "wiener"];
+
+pub fn run(name: &str) -> Result<()> {
+    match name {
+        "fermat" => fermat::main(),
+        "nonce-reuse" => nonce_reuse::main(),
+        "wiener" => wiener::main(),
+        other => Err(anyhow!(
+            "No bonus called {other}, try one of: {}",
+            NAMES.join(", ")
+        )),
+    }
+}
@@ -0,0 +1,151 @@
+//! Repeated GCM nonces from end to end: captured traffic to a forged command
+//!
+//! Alice's client seals everything it sends Bob under the same nonce. Challenge 63 gets h, the
+//! authentication key, out of any two pairs of messages, and challenge 20 reads the lot, as they
+//! all share a keystream, once the start every command has picks out which message is one. With
+//! both, Eve can take Alice's command, change it to one of her own, and give it a tag Bob accepts.
+
+use anyhow::anyhow;
+use rand::{seq::SliceRandom, Rng};
+
+use crate::aes::{aes_ecb_encrypt, BLOCK_SIZE};
+use crate::data;
+use crate::gcm::{Gcm, Gf, NONCE_SIZE};
+use crate::scoring::{Language, NGram, Scorer};
+use crate::set3::challenge20::FixedNonceBreaker;
+use crate::set8::challenge63::{forge, recover_key, Sealed};
+use crate::utils::*;
+
+/// Messages Eve captures, unless `--arg messages=` says otherwise
+const MESSAGES: usize = 40;
+/// What Alice tells Bob to do in amongst the chat
+const COMMAND: &[u8] = b"Transfer $100 to Bob";
+/// What Eve would rather he did, which has to be as long
+const FORGED: &[u8] = b"Transfer $999 to Eve";
+/// Sent along with each message, unencrypted
+const HEADER: &[u8] = b"from: alice";
+
+/// How every command starts, which Eve knows from the protocol
+const CRIB: &[u8] = b"Transfer $";
+
+/// The line which, if it starts with `crib`, leaves the others reading most like English as far as
+/// it goes
+fn drag(ciphertexts: &[Vec<u8>], crib: &[u8]) -> Result<Option<usize>> {
+    let scorer = Scorer::new(&Language::English, NGram::Trigram)?;
+    let score = |keystream: &[u8]| {
+        let mut text = vec![];
+        for c in ciphertexts {
+            text.extend(xor_bytes(&c[..c.len().min(keystream.len())], keystream));
+            // Separated by junk so the lines score independently
+            text.push(0);
+        }
+        scorer.score(&text)
+    };
+    Ok(ciphertexts
+        .iter()
+        .enumerate()
+        .filter(|(_, c)| c.len() >= crib.len())
+        .map(|(i, c)| (i, score(&xor_bytes(&c[..crib.len()], crib))))
+        .min_by(|(_, a), (_, b)| a.total_cmp(b))
+        .map(|(i, _)| i))
+}
+
+/// Bob, who carries out whatever arrives with the right tag
+struct Bob {
+    gcm: Gcm,
+}
+
+impl Bob {
+    fn open(&self, sealed: &Sealed) -> Result<Vec<u8>> {
+        self.gcm
+            .open(&sealed.nonce, &sealed.ad, &sealed.ciphertext, &sealed.tag)
+    }
+}
+
+/// Alice's side of the conversation: lines of Alice in Wonderland from somewhere random, with the
+/// command in there too
+fn conversation<R: Rng>(count: usize, rng: &mut R) -> Result<Vec<Vec<u8>>> {
+    let lines: Vec<&str> = data::ALICE.lines()?.collect();
+    let start = rng.gen_range(0..lines.len() - count);
+    let mut messages: Vec<Vec<u8>> = lines[start..start + count - 1]
+        .iter()
+        .map(|l| l.as_bytes().to_vec())
+        .collect();
+    messages.insert(rng.gen_range(0..count), COMMAND.to_vec());
+    Ok(messages)
+}
+
+pub fn main() -> Result<()> {
+    let count: usize = crate::options::get().arg("messages")?.unwrap_or(MESSAGES);
+    if count < 4 {
+        return Err(anyhow!("Need at least four messages, for two pairs"));
+    }
+    let lines = data::ALICE.lines()?.count();
+    if count >= lines {
+        return Err(anyhow!(
+            "Alice only has {lines} lines to send, so fewer messages than that"
+        ));
+    }
+    let mut rng = crate::utils::rng();
+    let key: [u8; 16] = rng.gen();
+    let gcm = Gcm::new(&key)?;
+    let nonce: [u8; NONCE_SIZE] = rng.gen();
+    let captured: Vec<Sealed> = conversation(count, &mut rng)?
+        .iter()
+        .map(|plaintext| {
+            let (ciphertext, tag) = gcm.seal(&nonce, HEADER, plaintext);
+            Sealed {
+                nonce,
+                ad: HEADER.to_vec(),
+                ciphertext,
+                tag,
+            }
+        })
+        .collect();
+    let bob = Bob { gcm };
+    println!("Eve captures {count} messages, all under the same nonce");
+
+    // Any two pairs do for h
+    let mut shuffled: Vec<&Sealed> = captured.iter().collect();
+    shuffled.shuffle(&mut rng);
+    let pairs = [
+        (shuffled[0].clone(), shuffled[1].clone()),
+        (shuffled[2].clone(), shuffled[3].clone()),
+    ];
+    let h = recover_key(&pairs, |sealed| bob.open(sealed).is_ok(), &mut rng)?;
+    println!("h = {:032x}", u128::from_be_bytes(h.to_block()));
+    if h != Gf::from_block(&aes_ecb_encrypt(&[0; BLOCK_SIZE], &key)?) {
+        return Err(anyhow!("Recovered the wrong authentication key"));
+    }
+
+    // and the keystream is the same for them all, with the start of a command to go on
+    let ciphertexts: Vec<Vec<u8>> = captured.iter().map(|s| s.ciphertext.clone()).collect();
+    let line = drag(&ciphertexts, CRIB)?
+        .ok_or_else(|| anyhow!("None of the traffic reads as a command"))?;
+    let mut breaker = FixedNonceBreaker::new(ciphertexts)?;
+    breaker.pin(line, 0, CRIB)?;
+    let plaintexts = breaker.plaintexts();
+    for p in &plaintexts {
+        println!("  {}", String::from_utf8_lossy(p));
+    }
+    let (target, plaintext) = (&captured[line], &plaintexts[line]);
+    println!("Alice's command: {:?}", String::from_utf8_lossy(plaintext));
+
+    let forged = forge(h, target, &xor_bytes(plaintext, FORGED));
+    let opened = bob.open(&forged)?;
+    println!("Bob carries out {:?}", String::from_utf8_lossy(&opened));
+    if opened != FORGED {
+        return Err(anyhow!(
+            "The command wasn't read right, so the forgery isn't Eve's"
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn forged_command() {
+        super::main().unwrap();
+    }
+}
@@ -0,0 +1,174 @@
+//! Wiener's attack on RSA with a small private exponent
+//!
+//! Picking d small makes decryption quick, and picking it first (with e whatever inverse it has)
+//! is an easy way to do it. But e d = 1 + k phi(n) for some k < d, and phi(n) is close to n, so
+//! e / n is very close to k / d: close enough, when d < n^(1/4) / 3 and q < p < 2q, that k / d is
+//! one of the convergents of e / n. Each convergent gives a guess at phi(n), and the right guess
+//! gives p + q = n - phi(n) + 1, from which p and q are the roots of a quadratic.
+//!
+//! M. J. Wiener, Cryptanalysis of short RSA secret exponents, IEEE Trans. Inf. Theory 36 (1990)
+
+use anyhow::anyhow;
+use num_bigint::{BigInt, RandBigInt, Sign};
+use num_integer::Integer;
+use num_traits::{One, Zero};
+use rand::Rng;
+
+use crate::continued_fraction::convergents;
+use crate::rsa::{random_prime, Key, KeyPair, Params};
+use crate::set5::challenge39::try_invmod;
+use crate::utils::*;
+
+/// Keys to try at each size of d past the bound
+const TRIES: usize = 4;
+
+/// What the attack finds: the private key, and the factors of the modulus
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Recovered {
+    pub private_key: Key,
+    pub p: BigInt,
+    pub q: BigInt,
+}
+
+pub fn main() -> Result<()> {
+    // Only the size, as e comes from d here
+    let bits = Params::from_options(1024, 3)?.bits;
+    let mut rng = crate::utils::rng();
+    let bound = bits / 4 - 2;
+
+    let KeyPair { public, private } = weak_keypair(bits, bound, &mut rng);
+    println!(
+        "{bits}-bit modulus, with a {bound}-bit d and a {}-bit e",
+        public.key.bits()
+    );
+    let secret = b"Short exponents, short work";
+    let c = public.apply(&BigInt::from_bytes_be(Sign::Plus, secret));
+
+    let recovered = attack(&public).ok_or_else(|| anyhow!("Attack failed"))?;
+    if recovered.private_key != private {
+        return Err(anyhow!("Recovered the wrong private key"));
+    }
+    println!("Recovered d = {}", recovered.private_key.key);
+    println!("p = {}\nq = {}", recovered.p, recovered.q);
+    let m = recovered.private_key.apply(&c).to_bytes_be().1;
+    println!("Decrypted: {}", String::from_utf8_lossy(&m));
+
+    // The bound is enough, rather than necessary, so a few bits over it can still work
+    println!("\nPast the bound of about {} bits:", bits / 4);
+    for d_bits in [bits / 4, bits / 4 + 2, bits / 4 + 8] {
+        let found = (0..TRIES)
+            .filter(|_| attack(&weak_keypair(bits, d_bits, &mut rng).public).is_some())
+            .count();
+        println!("{d_bits:>5}-bit d: {found}/{TRIES} keys broken");
+    }
+    let normal = Params::new(bits, 65537).keypair(&mut rng);
+    println!(
+        "e = 65537: {}",
+        match attack(&normal.public) {
+            Some(_) => "broken",
+            None => "safe",
+        }
+    );
+
+    Ok(())
+}
+
+/// A key pair with a random `d_bits`-bit private exponent, and a public exponent to match
+pub fn weak_keypair<R: Rng>(bits: u64, d_bits: u64, rng: &mut R) -> KeyPair {
+    loop {
+        // Any p will do, without an e to be coprime to p - 1 yet
+        let p = random_prime(bits / 2, &BigInt::one(), rng);
+        let q = random_prime(bits / 2, &BigInt::one(), rng);
+        let n = &p * &q;
+        if p == q || n.bits() != bits {
+            continue;
+        }
+        let phi = (p - 1) * (q - 1);
+        let mut d = BigInt::from(rng.gen_biguint(d_bits));
+        d.set_bit(d_bits - 1, true);
+        d.set_bit(0, true);
+        let Some(e) = try_invmod(&d, &phi) else {
+            continue;
+        };
+        return KeyPair {
+            public: Key {
+                key: e,
+                modulus: n.clone(),
+            },
+            private: Key { key: d, modulus: n },
+        };
+    }
+}
+
+/// Looks for d among the denominators of the convergents of e / n, or None if it's not there
+pub fn attack(public_key: &Key) -> Option<Recovered> {
+    let Key { key: e, modulus: n } = public_key;
+    convergents(e, n).find_map(|(k, d)| {
+        if k.is_zero() {
+            return None;
+        }
+        // e d - 1 = k phi(n)
+        let (phi, r) = (e * &d - 1u32).div_rem(&k);
+        if !r.is_zero() {
+            return None;
+        }
+        // p and q are the roots of x^2 - (p + q) x + n
+        let s = n - phi + 1u32;
+        let discriminant: BigInt = &s * &s - 4u32 * n;
+        if discriminant.sign() == Sign::Minus {
+            return None;
+        }
+        let root = discriminant.sqrt();
+        if &root * &root != discriminant {
+            return None;
+        }
+        let (p, q) = ((&s + &root) / 2u32, (&s - &root) / 2u32);
+        (&p * &q == *n).then(|| Recovered {
+            private_key: Key {
+                key: d,
+                modulus: n.clone(),
+            },
+            p,
+            q,
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn textbook() {
+        // 90581 = 239 * 379
+        let public_key = Key {
+            key: 17993.into(),
+            modulus: 90581.into(),
+        };
+        let recovered = attack(&public_key).unwrap();
+        assert_eq!(recovered.private_key.key, 5.into());
+        assert_eq!((recovered.p, recovered.q), (379.into(), 239.into()));
+    }
+
+    #[test]
+    fn small_d() {
+        let mut rng = rand::thread_rng();
+        for bits in [256, 512, 1024] {
+            let KeyPair { public, private } = weak_keypair(bits, bits / 4 - 2, &mut rng);
+            let recovered = attack(&public).unwrap();
+            assert_eq!(recovered.private_key, private);
+            assert_eq!(recovered.p * recovered.q, public.modulus);
+        }
+    }
+
+    #[test]
+    fn normal_keys() {
+        let mut rng = rand::thread_rng();
+        for _ in 0..4 {
+            assert!(attack(&Params::new(512, 65537).keypair(&mut rng).public).is_none());
+        }
+        // Way past the bound
+        let weak = weak_keypair(512, 256, &mut rng);
+        assert!(attack(&weak.public).is_none());
+    }
+}
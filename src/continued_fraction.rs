@@ -0,0 +1,105 @@
+//! Continued fractions of rationals, and their convergents
+//!
+//! A rational a / b is [q_0; q_1, q_2, ...] where the q_i are the quotients Euclid's algorithm
+//! finds on the way to gcd(a, b). Cutting the expansion short gives the convergents h_i / k_i,
+//! which are the best approximations to a / b with denominators that small:
+//! h_i = q_i h_(i-1) + h_(i-2), and likewise for k_i, starting from h_(-1) / k_(-1) = 1 / 0 and
+//! h_(-2) / k_(-2) = 0 / 1.
+#![allow(dead_code)]
+
+use num_bigint::BigInt;
+use num_integer::Integer;
+use num_traits::{One, Signed, Zero};
+
+/// The quotients [q_0; q_1, ...] of `numerator` / `denominator`, for a non-negative numerator and a
+/// positive denominator
+pub fn expansion(numerator: &BigInt, denominator: &BigInt) -> Vec<BigInt> {
+    assert!(!numerator.is_negative() && denominator.is_positive());
+    let (mut a, mut b) = (numerator.clone(), denominator.clone());
+    let mut quotients = vec![];
+    while !b.is_zero() {
+        let (q, r) = a.div_rem(&b);
+        quotients.push(q);
+        (a, b) = (b, r);
+    }
+    quotients
+}
+
+/// The convergents of a continued fraction, as (numerator, denominator), each in lowest terms
+pub struct Convergents<I> {
+    quotients: I,
+    h: (BigInt, BigInt),
+    k: (BigInt, BigInt),
+}
+
+impl<I: Iterator<Item = BigInt>> Convergents<I> {
+    pub fn new(quotients: impl IntoIterator<IntoIter = I>) -> Self {
+        Self {
+            quotients: quotients.into_iter(),
+            h: (BigInt::zero(), BigInt::one()),
+            k: (BigInt::one(), BigInt::zero()),
+        }
+    }
+}
+
+impl<I: Iterator<Item = BigInt>> Iterator for Convergents<I> {
+    type Item = (BigInt, BigInt);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let q = self.quotients.next()?;
+        let h = &q * &self.h.1 + &self.h.0;
+        let k = &q * &self.k.1 + &self.k.0;
+        self.h = (std::mem::take(&mut self.h.1), h.clone());
+        self.k = (std::mem::take(&mut self.k.1), k.clone());
+        Some((h, k))
+    }
+}
+
+/// The convergents of `numerator` / `denominator`, ending with the fraction itself in lowest terms
+pub fn convergents(
+    numerator: &BigInt,
+    denominator: &BigInt,
+) -> Convergents<std::vec::IntoIter<BigInt>> {
+    Convergents::new(expansion(numerator, denominator))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn big(xs: &[i64]) -> Vec<BigInt> {
+        xs.iter().map(|x| BigInt::from(*x)).collect()
+    }
+
+    #[test]
+    fn expansions() {
+        assert_eq!(expansion(&415.into(), &93.into()), big(&[4, 2, 6, 7]));
+        assert_eq!(expansion(&93.into(), &415.into()), big(&[0, 4, 2, 6, 7]));
+        assert_eq!(expansion(&0.into(), &5.into()), big(&[0]));
+        assert_eq!(expansion(&12.into(), &4.into()), big(&[3]));
+    }
+
+    #[test]
+    fn convergent_fractions() {
+        let found: Vec<_> = convergents(&415.into(), &93.into()).collect();
+        let expected: Vec<_> = [(4, 1), (9, 2), (58, 13), (415, 93)]
+            .into_iter()
+            .map(|(h, k)| (BigInt::from(h), BigInt::from(k)))
+            .collect();
+        assert_eq!(found, expected);
+
+        // Not in lowest terms to begin with, but the last convergent is
+        let last = convergents(&6.into(), &4.into()).last().unwrap();
+        assert_eq!(last, (3.into(), 2.into()));
+
+        // Successive convergents differ by exactly 1 / (k_i k_(i+1))
+        let (a, b) = (BigInt::from(0xdeadbeef_u64), BigInt::from(0xc0ffee_u64));
+        let all: Vec<_> = convergents(&a, &b).collect();
+        for pair in all.windows(2) {
+            let [(h0, k0), (h1, k1)] = pair else {
+                unreachable!()
+            };
+            assert!((h1 * k0 - h0 * k1).abs().is_one());
+        }
+    }
+}
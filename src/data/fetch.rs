@@ -0,0 +1,225 @@
+//! Fetches the official challenge files on first use, and keeps them in a cache directory
+//!
+//! The cache lives in, in order of preference: the `--data-dir` option, `$CRYPTOPALS_DATA_DIR`,
+//! `$XDG_CACHE_HOME/cryptopals` or `~/.cache/cryptopals`. Files are checked against their SHA-256
+//! both on the way in and when read back out, so a corrupted cache gets downloaded again.
+//!
+//! There's no HTTP client among the dependencies, so this speaks just enough HTTP/1.0 over
+//! openssl's TLS to get a static file, following redirects.
+
+use std::io::{Read, Write};
+use std::net::TcpStream;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use openssl::sha::sha256;
+use openssl::ssl::{SslConnector, SslMethod};
+use thiserror::Error;
+
+use crate::utils::bytes_to_hex;
+
+const BASE_URL: &str = "https://cryptopals.com/static/challenge-data/";
+const MAX_REDIRECTS: usize = 5;
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Error)]
+pub enum FetchError {
+    #[error("Couldn't find a cache directory, set --data-dir or CRYPTOPALS_DATA_DIR")]
+    NoCacheDir,
+    #[error("Unsupported URL {0}")]
+    BadUrl(String),
+    #[error("Malformed HTTP response")]
+    Malformed,
+    #[error("HTTP status {0} fetching {1}")]
+    Status(u16, String),
+    #[error("Too many redirects fetching {0}")]
+    TooManyRedirects(String),
+    #[error("Checksum mismatch for {name}: expected {expected}, got {actual}")]
+    Checksum {
+        name: String,
+        expected: String,
+        actual: String,
+    },
+    #[error("{0} isn't UTF-8")]
+    NotText(String),
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error(transparent)]
+    Tls(#[from] openssl::error::ErrorStack),
+    #[error("TLS handshake failed: {0}")]
+    Handshake(String),
+}
+
+/// Where downloaded files are kept
+pub fn cache_dir() -> Result<PathBuf, FetchError> {
+    if let Some(dir) = &crate::options::get().data_dir {
+        return Ok(dir.clone());
+    }
+    let env = |var| {
+        std::env::var_os(var)
+            .filter(|v| !v.is_empty())
+            .map(PathBuf::from)
+    };
+    env("CRYPTOPALS_DATA_DIR")
+        .or_else(|| env("XDG_CACHE_HOME").map(|d| d.join("cryptopals")))
+        .or_else(|| env("HOME").map(|d| d.join(".cache").join("cryptopals")))
+        .ok_or(FetchError::NoCacheDir)
+}
+
+/// Gets `name` from the cache in `dir`, downloading it first if it's missing or doesn't match
+/// `sha256` (hex), when given
+pub fn cached(dir: &Path, name: &str, sha256: Option<&str>) -> Result<String, FetchError> {
+    let path = dir.join(name);
+    if let Ok(bytes) = std::fs::read(&path) {
+        if verify(name, &bytes, sha256).is_ok() {
+            return into_text(name, bytes);
+        }
+        eprintln!("Cached {} is corrupt, fetching it again", path.display());
+    }
+
+    let url = format!("{BASE_URL}{name}");
+    eprintln!("Fetching {url}");
+    let bytes = https_get(&url)?;
+    verify(name, &bytes, sha256)?;
+    std::fs::create_dir_all(dir)?;
+    // Write then rename, so that an interrupted download never looks like a cached file
+    let partial = dir.join(format!("{name}.part"));
+    std::fs::write(&partial, &bytes)?;
+    std::fs::rename(&partial, &path)?;
+    into_text(name, bytes)
+}
+
+fn verify(name: &str, bytes: &[u8], sha256_hex: Option<&str>) -> Result<(), FetchError> {
+    let Some(expected) = sha256_hex else {
+        return Ok(());
+    };
+    let actual = bytes_to_hex(&sha256(bytes));
+    match actual == expected {
+        true => Ok(()),
+        false => Err(FetchError::Checksum {
+            name: name.to_string(),
+            expected: expected.to_string(),
+            actual,
+        }),
+    }
+}
+
+fn into_text(name: &str, bytes: Vec<u8>) -> Result<String, FetchError> {
+    String::from_utf8(bytes).map_err(|_| FetchError::NotText(name.to_string()))
+}
+
+fn https_get(url: &str) -> Result<Vec<u8>, FetchError> {
+    let mut url = url.to_string();
+    for _ in 0..MAX_REDIRECTS {
+        let (host, path) = url
+            .strip_prefix("https://")
+            .map(|rest| rest.split_once('/').unwrap_or((rest, "")))
+            .ok_or_else(|| FetchError::BadUrl(url.clone()))?;
+
+        let tcp = TcpStream::connect((host, 443))?;
+        tcp.set_read_timeout(Some(TIMEOUT))?;
+        let connector = SslConnector::builder(SslMethod::tls())?.build();
+        let mut stream = connector
+            .connect(host, tcp)
+            .map_err(|e| FetchError::Handshake(e.to_string()))?;
+        write!(
+            stream,
+            "GET /{path} HTTP/1.0\r\nHost: {host}\r\nUser-Agent: cryptopals\r\n\r\n"
+        )?;
+        let mut response = vec![];
+        // Plenty of servers hang up without a TLS close_notify, so take whatever made it through
+        if let Err(e) = stream.read_to_end(&mut response) {
+            if response.is_empty() {
+                return Err(e.into());
+            }
+        }
+
+        match parse_response(&response)? {
+            Response::Body(body) => return Ok(body),
+            Response::Redirect(location) if location.starts_with('/') => {
+                url = format!("https://{host}{location}")
+            }
+            Response::Redirect(location) => url = location,
+            Response::Status(status) => return Err(FetchError::Status(status, url)),
+        }
+    }
+    Err(FetchError::TooManyRedirects(url))
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Response {
+    Body(Vec<u8>),
+    Redirect(String),
+    Status(u16),
+}
+
+/// Splits an HTTP/1.0 response into what we care about: the body if it worked, or where to go next
+fn parse_response(response: &[u8]) -> Result<Response, FetchError> {
+    let split = response
+        .windows(4)
+        .position(|w| w == b"\r\n\r\n")
+        .ok_or(FetchError::Malformed)?;
+    let head = std::str::from_utf8(&response[..split]).map_err(|_| FetchError::Malformed)?;
+    let mut lines = head.split("\r\n");
+    let status: u16 = lines
+        .next()
+        .and_then(|l| l.split_whitespace().nth(1))
+        .and_then(|s| s.parse().ok())
+        .ok_or(FetchError::Malformed)?;
+    let header = |name: &str| {
+        head.split("\r\n").skip(1).find_map(|l| {
+            let (k, v) = l.split_once(':')?;
+            k.trim()
+                .eq_ignore_ascii_case(name)
+                .then(|| v.trim().to_string())
+        })
+    };
+
+    match status {
+        200 => Ok(Response::Body(response[split + 4..].to_vec())),
+        301 | 302 | 303 | 307 | 308 => header("location")
+            .map(Response::Redirect)
+            .ok_or(FetchError::Malformed),
+        s => Ok(Response::Status(s)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn responses() {
+        let ok = b"HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\n\r\nline 1\r\nline 2\n";
+        assert_eq!(
+            parse_response(ok).unwrap(),
+            Response::Body(b"line 1\r\nline 2\n".to_vec())
+        );
+
+        let moved =
+            b"HTTP/1.0 301 Moved Permanently\r\nlocation: https://example.com/4.txt\r\n\r\n";
+        assert_eq!(
+            parse_response(moved).unwrap(),
+            Response::Redirect("https://example.com/4.txt".into())
+        );
+
+        let missing = b"HTTP/1.0 404 Not Found\r\n\r\nNope";
+        assert_eq!(parse_response(missing).unwrap(), Response::Status(404));
+        assert!(parse_response(b"HTTP/1.0 200 OK\r\n").is_err());
+    }
+
+    #[test]
+    fn cache_hits() {
+        let dir = std::env::temp_dir().join(format!("cryptopals-cache-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("4.txt"), crate::data::CHALLENGE_4.text().unwrap()).unwrap();
+
+        // A good copy is used as it is, without going anywhere near the network
+        let text = cached(&dir, "4.txt", crate::data::CHALLENGE_4.sha256()).unwrap();
+        assert_eq!(text, crate::data::CHALLENGE_4.text().unwrap());
+        assert!(verify("4.txt", b"tampered", crate::data::CHALLENGE_4.sha256()).is_err());
+        assert!(verify("4.txt", b"unchecked", None).is_ok());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
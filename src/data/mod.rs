@@ -0,0 +1,250 @@
+//! The challenge data files
+//!
+//! By default everything under `data/` is compiled in with `include_str!`, so the challenges run
+//! the same from any working directory. Building without the `embed` feature leaves the official
+//! challenge files out, and [`fetch`] downloads them on first use instead. Either way, each file is
+//! a [`DataFile`], with accessors for the ways the challenges read them: as text, line by line,
+//! base64 decoded, either as a whole or a line at a time, or hex decoded a line at a time.
+#![allow(dead_code)]
+
+pub mod fetch;
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use anyhow::{Context, Result};
+use base64::{engine::general_purpose, Engine as _};
+
+#[derive(Debug, Clone, Copy)]
+pub struct DataFile {
+    name: &'static str,
+    /// SHA-256 of the official file, in hex, where we know it
+    sha256: Option<&'static str>,
+    source: Source,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Source {
+    Embedded(&'static str),
+    /// Fetched from cryptopals.com into the cache directory
+    Download,
+}
+
+/// Files downloaded so far this run, leaked so they can be handed out like the embedded ones
+static DOWNLOADED: Mutex<Option<HashMap<&'static str, &'static str>>> = Mutex::new(None);
+
+macro_rules! embedded {
+    ($name:literal) => {
+        DataFile {
+            name: $name,
+            sha256: None,
+            source: Source::Embedded(include_str!(concat!("../../data/", $name))),
+        }
+    };
+}
+
+#[cfg(feature = "embed")]
+macro_rules! challenge_file {
+    ($name:literal, $sha256:literal) => {
+        DataFile {
+            sha256: Some($sha256),
+            ..embedded!($name)
+        }
+    };
+}
+
+#[cfg(not(feature = "embed"))]
+macro_rules! challenge_file {
+    ($name:literal, $sha256:literal) => {
+        DataFile {
+            name: $name,
+            sha256: Some($sha256),
+            source: Source::Download,
+        }
+    };
+}
+
+/// Hex-encoded lines, one of which is single-byte XOR'd English
+pub const CHALLENGE_4: DataFile = challenge_file!(
+    "4.txt",
+    "c87c921c561bf2a69cf4847dd6649f6d05430fcabae80fe5e78b56d78978a436"
+);
+/// Base64 of repeating-key XOR'd lyrics
+pub const CHALLENGE_6: DataFile = challenge_file!(
+    "6.txt",
+    "9cce7ff2a0ade90b54c0e20ee8283c0cd8caa7663f995eff2d7b9ace3bd53d8d"
+);
+/// Base64 of AES-128-ECB under "YELLOW SUBMARINE"
+pub const CHALLENGE_7: DataFile = challenge_file!(
+    "7.txt",
+    "c50fd4291beb52b9fbac8c4bddc5454c7757d0988359e5625e430bc7cdd709c3"
+);
+/// Hex-encoded ciphertexts, one of which is ECB
+pub const CHALLENGE_8: DataFile = challenge_file!(
+    "8.txt",
+    "d61d668f428e48b70c4148ba6a3201afb6d6bd8f630686f23162400683a066b7"
+);
+/// Base64 of AES-128-CBC under "YELLOW SUBMARINE" with a zero IV
+///
+/// This one never made it into `data/`, so it's always downloaded, and there's no checksum to hold
+/// it to until someone pins one taken from the real download
+pub const CHALLENGE_10: DataFile = DataFile {
+    name: "10.txt",
+    sha256: None,
+    source: Source::Download,
+};
+/// Base64 plaintexts for fixed-nonce CTR, to be cracked by substitution
+pub const CHALLENGE_19: DataFile = challenge_file!(
+    "19.txt",
+    "7456ba31d8c7b3154f5a0521f44c2f0bccc058973bcf6e956350ef804bac2291"
+);
+/// Base64 plaintexts for fixed-nonce CTR, to be cracked statistically
+pub const CHALLENGE_20: DataFile = challenge_file!(
+    "20.txt",
+    "b742803c4fa1ee6b4e400e03a081ba46f559dad7b69012fd1582cbc5f1c8b2cd"
+);
+/// Same as challenge 7, recovered again through CTR's random access
+pub const CHALLENGE_25: DataFile = challenge_file!(
+    "25.txt",
+    "c50fd4291beb52b9fbac8c4bddc5454c7757d0988359e5625e430bc7cdd709c3"
+);
+/// DSA-signed messages, four lines to a signature, some sharing a nonce
+pub const CHALLENGE_44: DataFile = challenge_file!(
+    "44.txt",
+    "d1ab3c2f18f85c38e7701a3998fe03b6087fe8a2f202ea45aa66f45314e09698"
+);
+
+// The corpora aren't cryptopals files, so there's nowhere to download them from
+
+/// War and Peace, the English corpus for scoring
+pub const WAR_AND_PEACE: DataFile = embedded!("wap.txt");
+/// Alice's Adventures in Wonderland, a smaller English corpus
+pub const ALICE: DataFile = embedded!("aiw.txt");
+/// The SOWPODS Scrabble word list, one word to a line
+pub const SOWPODS: DataFile = embedded!("sowpods.txt");
+/// Challenge 59's invalid curves, as each one's b and number of points
+pub const INVALID_CURVES: DataFile = embedded!("invalid_curves.txt");
+
+impl DataFile {
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    pub fn sha256(&self) -> Option<&'static str> {
+        self.sha256
+    }
+
+    /// The whole file, downloading it first if need be
+    pub fn text(&self) -> Result<&'static str> {
+        match self.source {
+            Source::Embedded(contents) => Ok(contents),
+            Source::Download => {
+                let mut downloaded = DOWNLOADED.lock().unwrap();
+                let downloaded = downloaded.get_or_insert_with(HashMap::new);
+                if let Some(contents) = downloaded.get(self.name) {
+                    return Ok(contents);
+                }
+                let contents = fetch::cached(&fetch::cache_dir()?, self.name, self.sha256)
+                    .with_context(|| format!("Couldn't get {}", self.name))?;
+                let contents: &'static str = Box::leak(contents.into_boxed_str());
+                downloaded.insert(self.name, contents);
+                Ok(contents)
+            }
+        }
+    }
+
+    pub fn bytes(&self) -> Result<&'static [u8]> {
+        Ok(self.text()?.as_bytes())
+    }
+
+    /// Non-empty lines, with any trailing whitespace trimmed
+    pub fn lines(&self) -> Result<impl Iterator<Item = &'static str>> {
+        Ok(self
+            .text()?
+            .lines()
+            .map(|l| l.trim_end())
+            .filter(|l| !l.is_empty()))
+    }
+
+    /// Decodes the whole file as one base64 string, split across lines
+    pub fn base64(&self) -> Result<Vec<u8>> {
+        let joined: String = self.lines()?.collect();
+        general_purpose::STANDARD
+            .decode(joined)
+            .with_context(|| format!("Invalid base64 in {}", self.name))
+    }
+
+    /// Decodes each line as its own base64 string
+    pub fn base64_lines(&self) -> Result<Vec<Vec<u8>>> {
+        self.lines()?
+            .enumerate()
+            .map(|(i, l)| {
+                general_purpose::STANDARD
+                    .decode(l)
+                    .with_context(|| format!("Invalid base64 in {} line {}", self.name, i + 1))
+            })
+            .collect()
+    }
+
+    /// Decodes each line as its own hex string
+    pub fn hex_lines(&self) -> Result<Vec<Vec<u8>>> {
+        self.lines()?
+            .enumerate()
+            .map(|(i, l)| {
+                crate::utils::hex_to_bytes(l)
+                    .with_context(|| format!("Invalid hex in {} line {}", self.name, i + 1))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_disk() {
+        for file in [CHALLENGE_4, CHALLENGE_6, CHALLENGE_19, CHALLENGE_44, ALICE] {
+            let on_disk = std::fs::read_to_string(format!("./data/{}", file.name())).unwrap();
+            assert_eq!(file.text().unwrap(), on_disk);
+        }
+        assert_eq!(
+            CHALLENGE_6.base64().unwrap(),
+            crate::utils::read_base64_file("./data/6.txt").unwrap()
+        );
+        assert_eq!(
+            CHALLENGE_20.base64_lines().unwrap(),
+            crate::utils::read_base64_lines("./data/20.txt").unwrap()
+        );
+    }
+
+    #[test]
+    fn checksums() {
+        let files = [
+            CHALLENGE_4,
+            CHALLENGE_6,
+            CHALLENGE_7,
+            CHALLENGE_8,
+            CHALLENGE_19,
+            CHALLENGE_20,
+            CHALLENGE_25,
+            CHALLENGE_44,
+        ];
+        for file in files {
+            let digest = crate::utils::bytes_to_hex(&openssl::sha::sha256(file.bytes().unwrap()));
+            assert_eq!(file.sha256(), Some(digest.as_str()), "{}", file.name());
+        }
+    }
+
+    #[test]
+    fn accessors() {
+        assert_eq!(CHALLENGE_4.lines().unwrap().count(), 327);
+        assert_eq!(CHALLENGE_19.base64_lines().unwrap().len(), 40);
+        let hex = CHALLENGE_8.hex_lines().unwrap();
+        assert_eq!(hex.len(), 204);
+        assert!(hex.iter().all(|l| l.len() == 160));
+        assert!(ALICE.hex_lines().is_err());
+        assert!(CHALLENGE_7.base64().unwrap().len().is_multiple_of(16));
+        assert_eq!(SOWPODS.lines().unwrap().next(), Some("AA"));
+    }
+}
@@ -0,0 +1,205 @@
+//! DER and PEM encodings of the keys the challenges make and forge, for outside tools to check
+//!
+//! These follow the standard structures: PKCS#1 and PKCS#8 for RSA, and SEC 1 and PKCS#8 for EC,
+//! with the curve spelt out in full (RFC 3279's explicit ECParameters), as challenge 59's curve has
+//! no name and challenge 61's forged keys come with base points of their own. The building blocks
+//! here sit on top of the DER encoder from challenge 42.
+#![allow(dead_code)]
+
+use num_bigint::BigInt;
+
+use crate::utils::bytes_to_b64_str;
+
+pub use crate::set6::challenge42::{
+    der, encode_oid, NULL, OBJECT_IDENTIFIER, OCTET_STRING, SEQUENCE,
+};
+
+pub const INTEGER: u8 = 0x02;
+pub const BIT_STRING: u8 = 0x03;
+
+/// rsaEncryption, 1.2.840.113549.1.1.1
+pub const RSA_OID: &[u64] = &[1, 2, 840, 113549, 1, 1, 1];
+/// id-ecPublicKey, 1.2.840.10045.2.1
+pub const EC_PUBLIC_KEY_OID: &[u64] = &[1, 2, 840, 10045, 2, 1];
+/// prime-field, 1.2.840.10045.1.1
+pub const PRIME_FIELD_OID: &[u64] = &[1, 2, 840, 10045, 1, 1];
+
+/// An INTEGER, in as few bytes of two's complement as it takes
+pub fn integer(n: &BigInt) -> Vec<u8> {
+    der(INTEGER, &n.to_signed_bytes_be())
+}
+
+pub fn oid(arcs: &[u64]) -> Vec<u8> {
+    der(OBJECT_IDENTIFIER, &encode_oid(arcs))
+}
+
+/// A BIT STRING of whole bytes
+pub fn bit_string(bytes: &[u8]) -> Vec<u8> {
+    der(BIT_STRING, &[&[0], bytes].concat())
+}
+
+pub fn sequence(parts: &[Vec<u8>]) -> Vec<u8> {
+    der(SEQUENCE, &parts.concat())
+}
+
+/// `contents` under the context-specific, constructed tag [`n`]
+pub fn explicit(n: u8, contents: &[u8]) -> Vec<u8> {
+    der(0xa0 | n, contents)
+}
+
+/// `n` as exactly `len` big-endian bytes, as field elements and EC private keys are written
+pub fn fixed(n: &BigInt, len: usize) -> Vec<u8> {
+    let bytes = n.to_bytes_be().1;
+    let mut fixed = vec![0; len.saturating_sub(bytes.len())];
+    fixed.extend_from_slice(&bytes);
+    fixed
+}
+
+/// `der` in base64, 64 characters to a line, between the BEGIN and END lines for `label`
+pub fn pem(label: &str, der: &[u8]) -> String {
+    let b64 = bytes_to_b64_str(der);
+    let mut pem = format!("-----BEGIN {label}-----\n");
+    for line in b64.as_bytes().chunks(64) {
+        pem.push_str(std::str::from_utf8(line).unwrap());
+        pem.push('\n');
+    }
+    pem.push_str(&format!("-----END {label}-----\n"));
+    pem
+}
+
+#[cfg(test)]
+mod tests {
+    use openssl::ecdsa::EcdsaSig;
+    use openssl::hash::MessageDigest;
+    use openssl::pkey::PKey;
+    use openssl::sign::{Signer, Verifier};
+    use rand::thread_rng;
+
+    use super::*;
+    use crate::ecdsa::{self, duplicate_key, Sig};
+    use crate::rsa::{self, KeyPair};
+    use crate::set6::challenge42::{self, verify_sloppy, verify_strict};
+    use crate::set8::challenge61::duplicate_rsa_key;
+    use crate::utils::Auth;
+
+    /// Whether openssl takes `signature` over `message` with SHA-256, for the key in `public_pem`
+    fn openssl_verifies(public_pem: &str, message: &[u8], signature: &[u8]) -> bool {
+        let key = PKey::public_key_from_pem(public_pem.as_bytes()).unwrap();
+        let mut verifier = Verifier::new(MessageDigest::sha256(), &key).unwrap();
+        verifier.verify_oneshot(signature, message).unwrap_or(false)
+    }
+
+    /// openssl's SHA-256 signature over `message`, with the key in `private_pem`
+    fn openssl_signs(private_pem: &str, message: &[u8]) -> Vec<u8> {
+        let key = PKey::private_key_from_pem(private_pem.as_bytes()).unwrap();
+        let mut signer = Signer::new(MessageDigest::sha256(), &key).unwrap();
+        signer.sign_oneshot_to_vec(message).unwrap()
+    }
+
+    #[test]
+    fn rsa_with_openssl() {
+        let mut rng = thread_rng();
+        let pair = rsa::Params::new(1024, 65537).keypair(&mut rng);
+        let message = b"Signed here, checked there";
+
+        let signature = challenge42::sign(&pair.private, message).unwrap();
+        assert!(openssl_verifies(&pair.public.to_pem(), message, &signature));
+        assert!(!openssl_verifies(
+            &pair.public.to_pem(),
+            b"Something else",
+            &signature
+        ));
+
+        let pem = pair.to_pem().unwrap();
+        let theirs = openssl_signs(&pem, message);
+        assert_eq!(verify_strict(&pair.public, message, &theirs), Auth::Valid);
+
+        // openssl writes them out just the same
+        let key = PKey::private_key_from_pem(pem.as_bytes()).unwrap();
+        let key = key.rsa().unwrap();
+        assert!(key.check_key().unwrap());
+        assert_eq!(
+            key.private_key_to_der().unwrap(),
+            pair.to_pkcs1_der().unwrap()
+        );
+        assert_eq!(
+            key.public_key_to_der_pkcs1().unwrap(),
+            pair.public.to_pkcs1_der()
+        );
+        assert_eq!(key.public_key_to_der().unwrap(), pair.public.to_spki_der());
+    }
+
+    #[test]
+    fn forged_rsa_with_openssl() {
+        let mut rng = thread_rng();
+        let message = b"hi mom";
+
+        // Challenge 42's forgery only gets past the verifier which doesn't look past the hash
+        let KeyPair { public, .. } = rsa::Params::new(2048, 3).keypair(&mut rng);
+        let forged = challenge42::forge(message, public.size());
+        assert_eq!(verify_sloppy(&public, message, &forged), Auth::Valid);
+        assert!(!openssl_verifies(&public.to_pem(), message, &forged));
+
+        // Whereas challenge 61's key really does verify Alice's signature, and Eve can sign with it
+        let KeyPair { public, private } = rsa::Params::new(1024, 65537).keypair(&mut rng);
+        let signature = challenge42::sign(&private, message).unwrap();
+        let eve = duplicate_rsa_key(&public, message, &signature, &mut rng).unwrap();
+        assert!(openssl_verifies(&eve.public.to_pem(), message, &signature));
+        let theirs = openssl_signs(&eve.to_pem().unwrap(), b"hi Eve");
+        assert!(openssl_verifies(&eve.public.to_pem(), b"hi Eve", &theirs));
+    }
+
+    #[test]
+    fn ecdsa_with_openssl() {
+        let mut rng = thread_rng();
+        let params = ecdsa::Params::default();
+        let (d, q) = params.keypair(&mut rng);
+        let message = b"Signed here, checked there";
+
+        let signature = ecdsa::sign(&d, &params, message, &mut rng);
+        let public_pem = params.public_key_pem(&q);
+        assert!(openssl_verifies(&public_pem, message, &signature.to_der()));
+        assert!(!openssl_verifies(
+            &public_pem,
+            b"Something else",
+            &signature.to_der()
+        ));
+
+        let theirs =
+            EcdsaSig::from_der(&openssl_signs(&params.private_key_pem(&d), message)).unwrap();
+        let theirs = Sig {
+            r: BigInt::from_bytes_be(num_bigint::Sign::Plus, &theirs.r().to_vec()),
+            s: BigInt::from_bytes_be(num_bigint::Sign::Plus, &theirs.s().to_vec()),
+        };
+        assert_eq!(ecdsa::verify(&q, &params, message, &theirs), Auth::Valid);
+
+        // Challenge 61's parameters, base point and all, verify Alice's signature for openssl too
+        let (eve_params, eve_d, eve_q) =
+            duplicate_key(&q, &params, message, &signature, &mut rng).unwrap();
+        let eve_pem = eve_params.public_key_pem(&eve_q);
+        assert!(openssl_verifies(&eve_pem, message, &signature.to_der()));
+        let theirs = openssl_signs(&eve_params.private_key_pem(&eve_d), b"Signed by Eve");
+        assert!(openssl_verifies(&eve_pem, b"Signed by Eve", &theirs));
+    }
+
+    #[test]
+    fn encodings() {
+        assert_eq!(integer(&0.into()), [0x02, 0x01, 0x00]);
+        assert_eq!(integer(&128.into()), [0x02, 0x02, 0x00, 0x80]);
+        assert_eq!(integer(&(-129).into()), [0x02, 0x02, 0xff, 0x7f]);
+        assert_eq!(bit_string(&[0x04]), [0x03, 0x02, 0x00, 0x04]);
+        assert_eq!(explicit(1, &[0x05, 0x00]), [0xa1, 0x02, 0x05, 0x00]);
+        assert_eq!(fixed(&0x0102.into(), 4), [0, 0, 1, 2]);
+        assert_eq!(
+            oid(RSA_OID),
+            [0x06, 0x09, 0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x01, 0x01]
+        );
+
+        let pem = pem("PUBLIC KEY", &[0xab; 60]);
+        let lines: Vec<&str> = pem.lines().collect();
+        assert_eq!(lines[0], "-----BEGIN PUBLIC KEY-----");
+        assert_eq!(lines[1].len(), 64);
+        assert_eq!(lines[2].len(), 16);
+        assert_eq!(lines[3], "-----END PUBLIC KEY-----");
+    }
+}
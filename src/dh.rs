@@ -1,8 +1,191 @@
+//! Diffie-Hellman, and the encrypted channel the set 5 protocols build on it
+//!
+//! Each side of an exchange is a [`Party`], and once they've swapped public keys each derives a
+//! [`Session`] from the shared secret: an AES-128-CBC key taken from the first 16 bytes of its
+//! SHA-256, with a random IV sent after each message.
+//!
+//! A man in the middle who can work out the secret on each side sits between the two sessions as a
+//! [`Relay`], reading everything as it goes past.
+#![allow(dead_code)]
+
+use crate::hash::sha256::sha256;
 use crate::utils::*;
-use num_bigint::{BigInt, Sign};
+use anyhow::anyhow;
+use num_bigint::{BigInt, RandBigInt, Sign};
+use rand::Rng;
 
 pub fn nist_params() -> (BigInt, BigInt) {
     let p = BigInt::from_bytes_be(Sign::Plus,&hex_to_bytes("ffffffffffffffffc90fdaa22168c234c4c6628b80dc1cd129024e088a67cc74020bbea63b139b22514a08798e3404ddef9519b3cd3a431b302b0a6df25f14374fe1356d6d51c245e485b576625e7ec6f44c42e9a637ed6b0bff5cb6f406b7edee386bfb5a899fa5ae9f24117c4b1fe649286651ece45b3dc2007cb8a163bf0598da48361c55d39a69163fa8fd24cf5f83655d23dca3ad961c62f356208552bb9ed529077096966d670c354e4abc9804f1746c08ca237327ffffffffffffffff").unwrap());
     let g: BigInt = 2.into();
     (p, g)
 }
+
+/// One side of a Diffie-Hellman exchange
+pub struct Party {
+    p: BigInt,
+    private: BigInt,
+    pub public: BigInt,
+}
+
+impl Party {
+    /// Picks a random private key and works out the public key g**x mod p to send
+    pub fn new(p: &BigInt, g: &BigInt, rng: &mut impl Rng) -> Self {
+        let private = rng.gen_bigint_range(&1.into(), p);
+        let public = g.modpow(&private, p);
+        Self {
+            p: p.clone(),
+            private,
+            public,
+        }
+    }
+
+    /// s = other**x mod p
+    pub fn shared_secret(&self, other_public: &BigInt) -> BigInt {
+        other_public.modpow(&self.private, &self.p)
+    }
+
+    pub fn session(&self, other_public: &BigInt) -> Session {
+        Session::new(&self.shared_secret(other_public))
+    }
+}
+
+/// AES-128-CBC, keyed by the shared secret
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Session {
+    key: Vec<u8>,
+}
+
+impl Session {
+    /// Takes the key from SHA-256 of the secret's big-endian bytes
+    pub fn new(secret: &BigInt) -> Self {
+        Self {
+            key: sha256(&secret.to_bytes_be().1)[..16].to_vec(),
+        }
+    }
+
+    pub fn key(&self) -> &[u8] {
+        &self.key
+    }
+
+    /// AES-CBC(key, iv=random(16), msg) + iv
+    pub fn encrypt(&self, message: &[u8], rng: &mut impl Rng) -> Result<Vec<u8>> {
+        let iv = random_key(16, rng);
+        let mut sent = cbc_encrypt(&pkcs7_pad(message, 16), &self.key, Some(&iv))?;
+        sent.extend_from_slice(&iv);
+        Ok(sent)
+    }
+
+    pub fn decrypt(&self, sent: &[u8]) -> Result<Vec<u8>> {
+        if sent.len() < 32 || !sent.len().is_multiple_of(16) {
+            return Err(anyhow!(
+                "Message of {} bytes can't be ciphertext + IV",
+                sent.len()
+            ));
+        }
+        let (ciphertext, iv) = sent.split_at(sent.len() - 16);
+        Ok(pkcs7_unpad(&cbc_decrypt(ciphertext, &self.key, Some(iv))?)?)
+    }
+}
+
+/// Which way a message went
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    AToB,
+    BToA,
+}
+
+/// Mallory, decrypting whatever A and B send each other and re-encrypting it for the other side
+///
+/// Mallory might not be sure of A's secret, just that it's one of a few, in which case the first
+/// message from A decides: the right key is the one that decrypts it to text.
+pub struct Relay {
+    a: Vec<Session>,
+    b: Session,
+    pub log: Vec<(Direction, Vec<u8>)>,
+}
+
+impl Relay {
+    pub fn new(a_secrets: &[BigInt], b_secret: &BigInt) -> Self {
+        Self {
+            a: a_secrets.iter().map(Session::new).collect(),
+            b: Session::new(b_secret),
+            log: vec![],
+        }
+    }
+
+    pub fn a_to_b(&mut self, sent: &[u8], rng: &mut impl Rng) -> Result<Vec<u8>> {
+        let (i, message) = self
+            .a
+            .iter()
+            .enumerate()
+            .find_map(|(i, s)| Some((i, s.decrypt(sent).ok().filter(|m| m.is_ascii())?)))
+            .ok_or_else(|| anyhow!("None of A's possible keys decrypt its message"))?;
+        // Settled, from now on
+        let a = self.a.swap_remove(i);
+        self.a = vec![a];
+        self.log.push((Direction::AToB, message.clone()));
+        self.b.encrypt(&message, rng)
+    }
+
+    pub fn b_to_a(&mut self, sent: &[u8], rng: &mut impl Rng) -> Result<Vec<u8>> {
+        let message = self.b.decrypt(sent)?;
+        let a = match self.a.as_slice() {
+            [a] => a,
+            _ => return Err(anyhow!("Don't know A's key until it's sent something")),
+        };
+        self.log.push((Direction::BToA, message.clone()));
+        a.encrypt(&message, rng)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::thread_rng;
+
+    #[test]
+    fn sessions() {
+        let mut rng = thread_rng();
+        let (p, g) = nist_params();
+        let (alice, bob) = (Party::new(&p, &g, &mut rng), Party::new(&p, &g, &mut rng));
+        let (to_bob, to_alice) = (alice.session(&bob.public), bob.session(&alice.public));
+        assert_eq!(to_bob, to_alice);
+
+        let sent = to_bob.encrypt(b"YELLOW SUBMARINE", &mut rng).unwrap();
+        assert_eq!(sent.len(), 48);
+        assert_eq!(to_alice.decrypt(&sent).unwrap(), b"YELLOW SUBMARINE");
+        // A fresh IV every time
+        assert_ne!(to_bob.encrypt(b"YELLOW SUBMARINE", &mut rng).unwrap(), sent);
+
+        let eve = Session::new(&BigInt::from(1));
+        assert!(eve
+            .decrypt(&sent)
+            .map_or(true, |m| m != b"YELLOW SUBMARINE"));
+        assert!(to_alice.decrypt(&sent[..16]).is_err());
+    }
+
+    #[test]
+    fn relay() {
+        let mut rng = thread_rng();
+        let (s_a, s_b) = (BigInt::from(3), BigInt::from(5));
+        let (alice, bob) = (Session::new(&s_a), Session::new(&s_b));
+        // Mallory isn't sure of A's secret until A says something
+        let mut mallory = Relay::new(&[2.into(), s_a, 4.into()], &s_b);
+        let too_soon = bob.encrypt(b"too soon", &mut rng).unwrap();
+        assert!(mallory.b_to_a(&too_soon, &mut rng).is_err());
+
+        let hello = alice.encrypt(b"hello", &mut rng).unwrap();
+        let to_bob = mallory.a_to_b(&hello, &mut rng).unwrap();
+        assert_eq!(bob.decrypt(&to_bob).unwrap(), b"hello");
+        let reply = bob.encrypt(b"hello yourself", &mut rng).unwrap();
+        let to_alice = mallory.b_to_a(&reply, &mut rng).unwrap();
+        assert_eq!(alice.decrypt(&to_alice).unwrap(), b"hello yourself");
+        assert_eq!(
+            mallory.log,
+            [
+                (Direction::AToB, b"hello".to_vec()),
+                (Direction::BToA, b"hello yourself".to_vec())
+            ]
+        );
+    }
+}
@@ -0,0 +1,273 @@
+//! DSA, as in FIPS 186-4, with SHA-1 as the hash
+//! https://csrc.nist.gov/publications/detail/fips/186/4/final
+//!
+//! The challenges come with fixed domain parameters, which are the [`Params`] default, but
+//! `--arg dsa_bits=` generates fresh ones instead, with p of that many bits and q of the size FIPS
+//! 186-4 pairs with it: first q, then a p = 1 mod q, then a generator g of the order q subgroup.
+#![allow(dead_code)]
+
+use anyhow::{anyhow, Result};
+use num_bigint::{BigInt, RandBigInt, Sign};
+use num_traits::{Num, One, Zero};
+use openssl::sha::sha1;
+use rand::Rng;
+
+use crate::rsa::is_probable_prime;
+use crate::utils::{try_invmod, Auth};
+
+/// Miller-Rabin rounds, enough for any of the sizes below
+const ROUNDS: usize = 40;
+
+/// Sizes of p and q FIPS 186-4 allows, as (L, N)
+pub const SIZES: [(u64, u64); 4] = [(1024, 160), (2048, 224), (2048, 256), (3072, 256)];
+
+/// Domain parameters: primes p and q with q dividing p - 1, and g generating the subgroup of order
+/// q mod p
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Params {
+    pub q: BigInt,
+    pub p: BigInt,
+    pub g: BigInt,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Sig {
+    pub r: BigInt,
+    pub s: BigInt,
+}
+
+impl Default for Params {
+    /// The parameters the challenges give
+    fn default() -> Self {
+        let p: BigInt = BigInt::from_str_radix(
+            "800000000000000089e1855218a0e7dac38136ffafa72eda7\
+         859f2171e25e65eac698c1702578b07dc2a1076da241c76c6\
+         2d374d8389ea5aeffd3226a0530cc565f3bf6b50929139ebe\
+         ac04f48c3c84afb796d61e5a4f9a8fda812ab59494232c7d2\
+         b4deb50aa18ee9e132bfa85ac4374d7f9091abc3d015efc87\
+         1a584471bb1",
+            16,
+        )
+        .unwrap();
+
+        let q: BigInt =
+            BigInt::from_str_radix("f4f47f05794b256174bba6e9b396a7707e563c5b", 16).unwrap();
+
+        let g: BigInt = BigInt::from_str_radix(
+            "5958c9d3898b224b12672c0b98e06c60df923cb8bc999d119\
+         458fef538b8fa4046c8db53039db620c094c9fa077ef389b5\
+         322a559946a71903f990f1f7e0e025e2d7f7cf494aff1a047\
+         0f5b64c36b625a097f1651fe775323556fe00b3608c887892\
+         878480e99041be601a62166ca6894bdd41a7054ec89f756ba\
+         9fc95302291",
+            16,
+        )
+        .unwrap();
+        Self { q, p, g }
+    }
+}
+
+impl Params {
+    /// The challenges' parameters, unless `--arg dsa_bits=` asks for new ones
+    pub fn from_options(rng: &mut impl Rng) -> Result<Self> {
+        match crate::options::get().arg::<u64>("dsa_bits")? {
+            Some(l) => {
+                let &(_, n) = SIZES
+                    .iter()
+                    .rev()
+                    .find(|(size, _)| *size == l)
+                    .ok_or_else(|| anyhow!("DSA p must be 1024, 2048 or 3072 bits, not {l}"))?;
+                Ok(Self::generate(l, n, rng))
+            }
+            None => Ok(Self::default()),
+        }
+    }
+
+    /// Generates new parameters with an `l`-bit p and an `n`-bit q, following the outline of FIPS
+    /// 186-4 appendix A.1.1.2 and A.2.1, but with the seeds drawn at random rather than from a
+    /// hash, so they can't be re-derived
+    pub fn generate(l: u64, n: u64, rng: &mut impl Rng) -> Self {
+        loop {
+            let q = random_prime(n, rng);
+            // p = X - (X mod 2q) + 1 is 1 mod 2q, so q divides p - 1, and p is odd
+            let two_q = &q << 1;
+            let p = (0..4 * l).find_map(|_| {
+                let mut x = BigInt::from(rng.gen_biguint(l));
+                x.set_bit(l - 1, true);
+                let p: BigInt = &x - (&x % &two_q) + 1;
+                (p.bits() == l && is_probable_prime(&p, ROUNDS, rng)).then_some(p)
+            });
+            // Hardly ever happens, but the standard says to start again with a new q
+            let Some(p) = p else {
+                continue;
+            };
+
+            let e = (&p - 1) / &q;
+            let g = (2_u32..)
+                .map(|h| BigInt::from(h).modpow(&e, &p))
+                .find(|g| !g.is_one())
+                .unwrap();
+            return Self { q, p, g };
+        }
+    }
+
+    /// Checks the parameters are what they claim to be, as in FIPS 186-4 appendix A.1.1.1 and A.2.2
+    pub fn validate(&self) -> Result<()> {
+        let mut rng = crate::utils::rng();
+        let Params { q, p, g } = self;
+        if !is_probable_prime(q, ROUNDS, &mut rng) || !is_probable_prime(p, ROUNDS, &mut rng) {
+            return Err(anyhow!("p and q must both be prime"));
+        }
+        if !((p - 1u32) % q).is_zero() {
+            return Err(anyhow!("q must divide p - 1"));
+        }
+        if g <= &BigInt::one() || g >= p || !g.modpow(q, p).is_one() {
+            return Err(anyhow!("g must generate the subgroup of order q"));
+        }
+        Ok(())
+    }
+
+    /// A new private key x, and the public key y = g**x mod p to go with it
+    pub fn keypair(&self, rng: &mut impl Rng) -> (BigInt, BigInt) {
+        let x = rng.gen_bigint_range(&1.into(), &self.q);
+        let y = self.g.modpow(&x, &self.p);
+        (x, y)
+    }
+}
+
+/// A random prime of exactly `bits` bits
+fn random_prime<R: Rng>(bits: u64, rng: &mut R) -> BigInt {
+    loop {
+        let mut candidate = BigInt::from(rng.gen_biguint(bits));
+        candidate.set_bit(bits - 1, true);
+        candidate.set_bit(0, true);
+        if is_probable_prime(&candidate, ROUNDS, rng) {
+            return candidate;
+        }
+    }
+}
+
+/// H(m), as an integer
+pub fn hash(message: &[u8]) -> BigInt {
+    BigInt::from_bytes_be(Sign::Plus, &sha1(message))
+}
+
+pub fn sign(private_key: &BigInt, params: &Params, message: &[u8], rng: &mut impl Rng) -> Sig {
+    let Params { q, .. } = params;
+
+    loop {
+        let k = rng.gen_bigint_range(&1.into(), q);
+        if let Some(signature) = sign_with_k(private_key, params, message, &k) {
+            return signature;
+        }
+    }
+}
+
+/// Signs with a given nonce k, or None if that k happens to give r or s = 0
+pub fn sign_with_k(
+    private_key: &BigInt,
+    params: &Params,
+    message: &[u8],
+    k: &BigInt,
+) -> Option<Sig> {
+    let Params { q, p, g } = params;
+    let h = hash(message);
+    let kinv = try_invmod(k, q)?;
+    let two: BigInt = 2.into();
+    let qm2 = q - &two;
+    let kinvprime = k.modpow(&qm2, q);
+    assert_eq!(kinv, kinvprime);
+
+    let r = g.modpow(k, p) % q;
+    let s = (kinv * (&h + private_key * &r)) % q;
+
+    match r != 0.into() && s != 0.into() {
+        true => Some(Sig { r, s }),
+        false => None,
+    }
+}
+
+pub fn verify(y: &BigInt, params: &Params, message: &[u8], signature: &Sig) -> Auth {
+    let Params { q, p, g } = params;
+    let Sig { r, s } = signature;
+    let h = hash(message);
+    let zero: BigInt = 0.into();
+
+    match &zero < r && r < q {
+        true => {}
+        false => return Auth::Invalid,
+    };
+    match &zero < s && s < q {
+        true => {}
+        false => return Auth::Invalid,
+    };
+
+    let Some(w) = try_invmod(s, q) else {
+        return Auth::Invalid;
+    };
+
+    // g**(h w) * g**(x * rw)
+    // g**(hw + xrw) mod p mod q
+    // w = s^{-1}
+    // s = (k^-1 (h+x r))
+    // w(h+r) = (h+xr) * k / (h+xr)
+    //        = k
+    // g**k mod p mod q
+    // = r
+
+    let u1: BigInt = (h * &w) % q;
+    let u2: BigInt = (r * &w) % q;
+    let v = ((g.modpow(&u1, p) * y.modpow(&u2, p)) % p) % q;
+
+    match &v == r {
+        true => Auth::Valid,
+        false => Auth::Invalid,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::thread_rng;
+
+    use super::*;
+
+    #[test]
+    fn functioning_verification() {
+        let mut rng = thread_rng();
+        let params = Params::default();
+        params.validate().unwrap();
+        let (x, y) = params.keypair(&mut rng);
+
+        let signature = sign(&x, &params, b"test message", &mut rng);
+        assert_eq!(
+            verify(&y, &params, b"test message", &signature),
+            Auth::Valid
+        );
+        assert_eq!(
+            verify(&y, &params, b"other message", &signature),
+            Auth::Invalid
+        );
+    }
+
+    #[test]
+    fn generated_params() {
+        let mut rng = thread_rng();
+        let params = Params::generate(1024, 160, &mut rng);
+        assert_eq!(params.p.bits(), 1024);
+        assert_eq!(params.q.bits(), 160);
+        params.validate().unwrap();
+
+        let (x, y) = params.keypair(&mut rng);
+        let signature = sign(&x, &params, b"test message", &mut rng);
+        assert_eq!(
+            verify(&y, &params, b"test message", &signature),
+            Auth::Valid
+        );
+
+        let broken = Params {
+            g: params.g.modpow(&2.into(), &params.p) + 1,
+            ..params
+        };
+        assert!(broken.validate().is_err());
+    }
+}
@@ -0,0 +1,302 @@
+//! ECDSA over the Weierstrass curves of challenge 59, with SHA-256 as the hash
+//!
+//! The domain parameters travel with the key, as some protocols let the signer choose them, so
+//! [`duplicate_key`] can hand over a base point of its own along with a public key that verifies
+//! someone else's signature.
+#![allow(dead_code)]
+
+use anyhow::{anyhow, Result};
+use num_bigint::{BigInt, RandBigInt, Sign};
+use num_integer::Integer;
+use num_traits::{One, Zero};
+use openssl::sha::sha256;
+use rand::Rng;
+
+use crate::der::{
+    bit_string, der, explicit, fixed, integer, oid, pem, sequence, EC_PUBLIC_KEY_OID, OCTET_STRING,
+    PRIME_FIELD_OID,
+};
+use crate::set8::{
+    challenge59::{Curve, CurveParams, Point},
+    params,
+};
+use crate::utils::{try_invmod, Auth};
+
+/// Domain parameters: a curve, a base point g on it, and the order n of g, a prime
+#[derive(Debug, Clone)]
+pub struct Params {
+    pub curve: Curve,
+    pub g: Point,
+    pub n: BigInt,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Sig {
+    pub r: BigInt,
+    pub s: BigInt,
+}
+
+impl Default for Params {
+    /// Challenge 59's curve, y^2 = x^3 - 95051 x + 11279326 over GF(p), and its base point
+    fn default() -> Self {
+        Self {
+            curve: params::curve().clone(),
+            g: params::base_point().clone(),
+            n: params::base_order().clone(),
+        }
+    }
+}
+
+impl Params {
+    /// (d, Q), with Q = d g
+    pub fn keypair(&self, rng: &mut impl Rng) -> (BigInt, Point) {
+        let d = rng.gen_bigint_range(&BigInt::one(), &self.n);
+        let q = self.curve.scale(&self.g, &d);
+        (d, q)
+    }
+
+    /// Bytes in a field element
+    fn field_size(&self) -> usize {
+        self.curve.params.p.bits().div_ceil(8) as usize
+    }
+
+    /// SEC 1's uncompressed encoding of a point, 04h x y, or a lone 00h for O
+    pub fn encode_point(&self, point: &Point) -> Vec<u8> {
+        match point {
+            Point::P { x, y } => {
+                let size = self.field_size();
+                [vec![0x04], fixed(x, size), fixed(y, size)].concat()
+            }
+            Point::O => vec![0x00],
+        }
+    }
+
+    /// RFC 3279's explicit ECParameters: the prime field, a and b, g, n and the cofactor
+    pub fn to_der(&self) -> Vec<u8> {
+        let CurveParams { a, b, p, ord, .. } = &self.curve.params;
+        let size = self.field_size();
+        sequence(&[
+            integer(&BigInt::one()),
+            sequence(&[oid(PRIME_FIELD_OID), integer(p)]),
+            sequence(&[
+                der(OCTET_STRING, &fixed(&a.mod_floor(p), size)),
+                der(OCTET_STRING, &fixed(&b.mod_floor(p), size)),
+            ]),
+            der(OCTET_STRING, &self.encode_point(&self.g)),
+            integer(&self.n),
+            integer(&(ord / &self.n)),
+        ])
+    }
+
+    /// id-ecPublicKey along with these parameters
+    fn algorithm(&self) -> Vec<u8> {
+        sequence(&[oid(EC_PUBLIC_KEY_OID), self.to_der()])
+    }
+
+    /// SubjectPublicKeyInfo for the public key Q, the `PUBLIC KEY` most tools take
+    pub fn public_key_der(&self, q: &Point) -> Vec<u8> {
+        sequence(&[self.algorithm(), bit_string(&self.encode_point(q))])
+    }
+
+    pub fn public_key_pem(&self, q: &Point) -> String {
+        pem("PUBLIC KEY", &self.public_key_der(q))
+    }
+
+    /// PKCS#8 PrivateKeyInfo for the private key d, wrapping SEC 1's ECPrivateKey (which leaves
+    /// the parameters to the PKCS#8 wrapper)
+    pub fn private_key_der(&self, d: &BigInt) -> Vec<u8> {
+        let q = self.curve.scale(&self.g, d);
+        let key = sequence(&[
+            integer(&BigInt::one()),
+            der(OCTET_STRING, &fixed(d, self.n.bits().div_ceil(8) as usize)),
+            explicit(1, &bit_string(&self.encode_point(&q))),
+        ]);
+        sequence(&[
+            integer(&BigInt::zero()),
+            self.algorithm(),
+            der(OCTET_STRING, &key),
+        ])
+    }
+
+    pub fn private_key_pem(&self, d: &BigInt) -> String {
+        pem("PRIVATE KEY", &self.private_key_der(d))
+    }
+}
+
+impl Sig {
+    /// ECDSA-Sig-Value, SEQUENCE { r, s }, as openssl and X.509 carry signatures
+    pub fn to_der(&self) -> Vec<u8> {
+        sequence(&[integer(&self.r), integer(&self.s)])
+    }
+}
+
+/// H(m), as an integer of no more bits than n
+pub fn hash(message: &[u8], n: &BigInt) -> BigInt {
+    let h = BigInt::from_bytes_be(Sign::Plus, &sha256(message));
+    h >> 256u64.saturating_sub(n.bits())
+}
+
+pub fn sign(private_key: &BigInt, params: &Params, message: &[u8], rng: &mut impl Rng) -> Sig {
+    sign_with_nonces(private_key, params, message, || {
+        rng.gen_bigint_range(&BigInt::one(), &params.n)
+    })
+}
+
+/// Signs with nonces k from `nonce`, drawing again until one gives a usable signature
+pub fn sign_with_nonces(
+    private_key: &BigInt,
+    params: &Params,
+    message: &[u8],
+    mut nonce: impl FnMut() -> BigInt,
+) -> Sig {
+    let Params { curve, g, n } = params;
+    let h = hash(message, n);
+    loop {
+        let k = nonce();
+        let Some(x) = curve.scale(g, &k).get_x() else {
+            continue;
+        };
+        let Some(kinv) = try_invmod(&k, n) else {
+            continue;
+        };
+        let r = x.mod_floor(n);
+        let s = (kinv * (&h + private_key * &r)).mod_floor(n);
+        if !r.is_zero() && !s.is_zero() {
+            return Sig { r, s };
+        }
+    }
+}
+
+/// u1 = H(m) / s and u2 = r / s
+fn coefficients(n: &BigInt, message: &[u8], signature: &Sig) -> (BigInt, BigInt) {
+    // s is in [1, n) for a signature that verifies, and n is prime
+    let w = try_invmod(&signature.s, n).unwrap();
+    let u1 = (hash(message, n) * &w).mod_floor(n);
+    let u2 = (&signature.r * &w).mod_floor(n);
+    (u1, u2)
+}
+
+/// u1 g + u2 Q, which a valid signature has r as the x of
+fn verifying_point(public_key: &Point, params: &Params, message: &[u8], signature: &Sig) -> Point {
+    let Params { curve, g, n } = params;
+    let (u1, u2) = coefficients(n, message, signature);
+    curve.add(&curve.scale(g, &u1), &curve.scale(public_key, &u2))
+}
+
+pub fn verify(public_key: &Point, params: &Params, message: &[u8], signature: &Sig) -> Auth {
+    let Sig { r, s } = signature;
+    let in_range = |x: &BigInt| x > &BigInt::zero() && x < &params.n;
+    if !in_range(r) || !in_range(s) {
+        return Auth::Invalid;
+    }
+    match verifying_point(public_key, params, message, signature).get_x() {
+        Some(x) if &x.mod_floor(&params.n) == r => Auth::Valid,
+        _ => Auth::Invalid,
+    }
+}
+
+/// New domain parameters and a key pair (d', Q') for them which verify `signature` over `message`,
+/// as well as `public_key` does under `params`
+///
+/// Verifying finds R = u1 g + u2 Q and checks its x, with u1 and u2 fixed by the message and the
+/// signature. So for any d', a base point g' = R / (u1 + u2 d') has u1 g' + u2 d' g' = R too.
+pub fn duplicate_key(
+    public_key: &Point,
+    params: &Params,
+    message: &[u8],
+    signature: &Sig,
+    rng: &mut impl Rng,
+) -> Result<(Params, BigInt, Point)> {
+    if verify(public_key, params, message, signature) == Auth::Invalid {
+        return Err(anyhow!("The signature doesn't verify in the first place"));
+    }
+    let Params { curve, n, .. } = params;
+    let r_point = verifying_point(public_key, params, message, signature);
+    let (u1, u2) = coefficients(n, message, signature);
+
+    loop {
+        let d = rng.gen_bigint_range(&BigInt::one(), n);
+        let t = (&u1 + &u2 * &d).mod_floor(n);
+        if t.is_zero() {
+            continue;
+        }
+        let g = curve.scale(&r_point, &try_invmod(&t, n).unwrap());
+        let q = curve.scale(&g, &d);
+        let params = Params {
+            g,
+            ..params.clone()
+        };
+        return Ok((params, d, q));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::thread_rng;
+
+    #[test]
+    fn functioning_verification() {
+        let mut rng = thread_rng();
+        let params = Params::default();
+        assert_eq!(params.curve.scale(&params.g, &params.n), Point::O);
+        let (d, q) = params.keypair(&mut rng);
+
+        let signature = sign(&d, &params, b"test message", &mut rng);
+        assert_eq!(
+            verify(&q, &params, b"test message", &signature),
+            Auth::Valid
+        );
+        assert_eq!(
+            verify(&q, &params, b"other message", &signature),
+            Auth::Invalid
+        );
+        let (_, other) = params.keypair(&mut rng);
+        assert_eq!(
+            verify(&other, &params, b"test message", &signature),
+            Auth::Invalid
+        );
+    }
+
+    #[test]
+    fn duplicated_key() {
+        let mut rng = thread_rng();
+        let params = Params::default();
+        let (d, q) = params.keypair(&mut rng);
+        let message = b"I, Alice, wrote this";
+        let signature = sign(&d, &params, message, &mut rng);
+
+        let (eve_params, eve_d, eve_q) =
+            duplicate_key(&q, &params, message, &signature, &mut rng).unwrap();
+        assert_eq!(
+            verify(&eve_q, &eve_params, message, &signature),
+            Auth::Valid
+        );
+        assert_ne!(eve_q, q);
+        assert_ne!(eve_d, d);
+        assert_ne!(eve_params.g, params.g);
+        // The same curve and order, and a key pair Eve can sign with too
+        assert_eq!(eve_params.n, params.n);
+        assert_eq!(
+            eve_params.curve.scale(&eve_params.g, &eve_params.n),
+            Point::O
+        );
+        assert_eq!(eve_params.curve.scale(&eve_params.g, &eve_d), eve_q);
+        let eve_signature = sign(&eve_d, &eve_params, b"I, Eve, wrote this", &mut rng);
+        assert_eq!(
+            verify(&eve_q, &eve_params, b"I, Eve, wrote this", &eve_signature),
+            Auth::Valid
+        );
+        // But only for that message
+        assert_eq!(
+            verify(&eve_q, &eve_params, b"Something else", &signature),
+            Auth::Invalid
+        );
+
+        let forged = Sig {
+            s: &signature.s + 1,
+            ..signature
+        };
+        assert!(duplicate_key(&q, &params, message, &forged, &mut rng).is_err());
+    }
+}
@@ -0,0 +1,102 @@
+//! A fast, non-cryptographic hasher for the tables the collision searches fill up
+//!
+//! This is the hash rustc uses (FxHash): each word is rotated into the state and multiplied by a
+//! constant, which is a handful of instructions against SipHash's rounds. That matters when baby
+//! steps, distinguished points and hash collisions go in and out of a map millions of times. It
+//! makes no attempt to resist keys chosen to collide, which is fine for keys that come out of the
+//! attacks themselves, but it's no replacement for the standard hasher on input from outside.
+#![allow(dead_code)]
+
+use std::collections::{HashMap, HashSet};
+use std::hash::{BuildHasherDefault, Hasher};
+
+const SEED: u64 = 0x51_7c_c1_b7_27_22_0a_95;
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct FxHasher {
+    hash: u64,
+}
+
+impl FxHasher {
+    fn add(&mut self, word: u64) {
+        self.hash = (self.hash.rotate_left(5) ^ word).wrapping_mul(SEED);
+    }
+}
+
+impl Hasher for FxHasher {
+    fn write(&mut self, bytes: &[u8]) {
+        let mut chunks = bytes.chunks_exact(8);
+        for chunk in &mut chunks {
+            self.add(u64::from_le_bytes(chunk.try_into().unwrap()));
+        }
+        let rest = chunks.remainder();
+        if !rest.is_empty() {
+            let mut word = [0; 8];
+            word[..rest.len()].copy_from_slice(rest);
+            self.add(u64::from_le_bytes(word));
+        }
+    }
+
+    fn write_u8(&mut self, n: u8) {
+        self.add(n.into());
+    }
+
+    fn write_u16(&mut self, n: u16) {
+        self.add(n.into());
+    }
+
+    fn write_u32(&mut self, n: u32) {
+        self.add(n.into());
+    }
+
+    fn write_u64(&mut self, n: u64) {
+        self.add(n);
+    }
+
+    fn write_usize(&mut self, n: usize) {
+        self.add(n as u64);
+    }
+
+    fn finish(&self) -> u64 {
+        self.hash
+    }
+}
+
+pub type FxBuildHasher = BuildHasherDefault<FxHasher>;
+pub type FxHashMap<K, V> = HashMap<K, V, FxBuildHasher>;
+pub type FxHashSet<T> = HashSet<T, FxBuildHasher>;
+
+#[cfg(test)]
+mod tests {
+    use std::hash::{BuildHasher, Hash};
+
+    use num_bigint::BigInt;
+
+    use super::*;
+    use crate::set8::challenge59::Point;
+
+    fn hash(value: impl Hash) -> u64 {
+        FxBuildHasher::default().hash_one(value)
+    }
+
+    #[test]
+    fn hashes() {
+        // Bytes a word at a time, with the tail padded out
+        assert_eq!(hash(1u64), hash(1u16));
+        assert_ne!(hash([1u8; 9].as_slice()), hash([1u8; 8].as_slice()));
+
+        let point = |x: i32, y: i32| Point::P {
+            x: BigInt::from(x) << 200,
+            y: y.into(),
+        };
+        let mut notes: FxHashMap<Point, usize> = FxHashMap::default();
+        for i in 0..1000 {
+            notes.insert(point(i, -i), i as usize);
+        }
+        notes.insert(Point::O, 1000);
+        assert_eq!(notes.len(), 1001);
+        assert_eq!(notes.get(&point(123, -123)), Some(&123));
+        assert_eq!(notes.get(&point(123, 123)), None);
+        assert_eq!(notes.get(&Point::O), Some(&1000));
+    }
+}
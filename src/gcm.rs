@@ -0,0 +1,367 @@
+//! AES-GCM, and the field GF(2^128) its MAC works in
+//!
+//! A block is a field element with its first bit as the coefficient of x^0, so [`Gf`] keeps it
+//! bit-reversed in a `u128`, with x^i as bit i, and reverses it back on the way out. Addition is
+//! XOR, and so is subtraction.
+//!
+//! Only 96-bit nonces, the kind which go straight into the first counter block.
+
+use std::ops::{Add, AddAssign, Mul, MulAssign};
+
+use anyhow::{anyhow, Result};
+use rand::Rng;
+
+use crate::aes::{Aes, BLOCK_SIZE};
+use crate::stream::{Ctr, Layout, StreamCipher};
+
+pub const NONCE_SIZE: usize = 12;
+pub const TAG_SIZE: usize = 16;
+
+/// x^128 = x^7 + x^2 + x + 1
+const REDUCTION: u128 = 0x87;
+
+/// An element of GF(2^128) = GF(2)[x] / (x^128 + x^7 + x^2 + x + 1)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, PartialOrd, Ord)]
+pub struct Gf(pub u128);
+
+impl Gf {
+    pub const ZERO: Gf = Gf(0);
+    pub const ONE: Gf = Gf(1);
+
+    /// Up to a block's worth of bytes, zero padded
+    pub fn from_block(bytes: &[u8]) -> Self {
+        let mut block = [0; BLOCK_SIZE];
+        block[..bytes.len()].copy_from_slice(bytes);
+        Self(u128::from_be_bytes(block).reverse_bits())
+    }
+
+    pub fn to_block(self) -> [u8; BLOCK_SIZE] {
+        self.0.reverse_bits().to_be_bytes()
+    }
+
+    pub fn random<R: Rng>(rng: &mut R) -> Self {
+        Self(rng.gen())
+    }
+
+    pub fn is_zero(self) -> bool {
+        self.0 == 0
+    }
+
+    pub fn pow(self, mut k: u128) -> Self {
+        let (mut base, mut acc) = (self, Gf::ONE);
+        while k > 0 {
+            if k & 1 == 1 {
+                acc *= base;
+            }
+            base *= base;
+            k >>= 1;
+        }
+        acc
+    }
+
+    /// a^(2^128 - 2), as the multiplicative group has order 2^128 - 1. Zero has none, and gets
+    /// zero back
+    pub fn inverse(self) -> Self {
+        self.pow(u128::MAX - 1)
+    }
+
+    /// The square root, a^(2^127), as squaring is a bijection here
+    pub fn sqrt(self) -> Self {
+        (0..127).fold(self, |a, _| a * a)
+    }
+}
+
+impl Add for Gf {
+    type Output = Gf;
+
+    #[allow(clippy::suspicious_arithmetic_impl)]
+    fn add(self, other: Gf) -> Gf {
+        Gf(self.0 ^ other.0)
+    }
+}
+
+impl AddAssign for Gf {
+    #[allow(clippy::suspicious_op_assign_impl)]
+    fn add_assign(&mut self, other: Gf) {
+        self.0 ^= other.0;
+    }
+}
+
+impl Mul for Gf {
+    type Output = Gf;
+
+    /// Shift and add, reducing as soon as b reaches x^128
+    fn mul(self, other: Gf) -> Gf {
+        let (mut a, mut b, mut p) = (self.0, other.0, 0);
+        while a > 0 {
+            if a & 1 == 1 {
+                p ^= b;
+            }
+            a >>= 1;
+            let carry = b >> 127;
+            b <<= 1;
+            if carry == 1 {
+                b ^= REDUCTION;
+            }
+        }
+        Gf(p)
+    }
+}
+
+impl MulAssign for Gf {
+    fn mul_assign(&mut self, other: Gf) {
+        *self = *self * other;
+    }
+}
+
+/// The blocks GHASH runs over: the associated data and the ciphertext, each zero padded to a
+/// whole number of blocks, and then their lengths in bits
+pub fn ghash_blocks(ad: &[u8], ciphertext: &[u8]) -> Vec<Gf> {
+    blocks(ad, ciphertext).collect()
+}
+
+fn blocks<'a>(ad: &'a [u8], ciphertext: &'a [u8]) -> impl Iterator<Item = Gf> + 'a {
+    let lengths = ((8 * ad.len() as u128) << 64) | (8 * ciphertext.len() as u128);
+    ad.chunks(BLOCK_SIZE)
+        .chain(ciphertext.chunks(BLOCK_SIZE))
+        .map(Gf::from_block)
+        .chain(std::iter::once(Gf::from_block(&lengths.to_be_bytes())))
+}
+
+/// g = (g + b) h for each block b, i.e. the blocks as the coefficients of a polynomial in h, with
+/// no constant term
+pub fn ghash(h: Gf, ad: &[u8], ciphertext: &[u8]) -> Gf {
+    ghash_by(|g| g * h, ad, ciphertext)
+}
+
+fn ghash_by(times_h: impl Fn(Gf) -> Gf, ad: &[u8], ciphertext: &[u8]) -> Gf {
+    blocks(ad, ciphertext).fold(Gf::ZERO, |g, b| times_h(g + b))
+}
+
+/// Multiplication by one fixed element, from its products with each byte in each position, so
+/// that a product is 16 lookups rather than 128 shifts
+struct MulTable(Vec<[Gf; 256]>);
+
+impl MulTable {
+    fn new(h: Gf) -> Self {
+        let rows = (0..16)
+            .map(|i| std::array::from_fn(|b| h * Gf((b as u128) << (8 * i))))
+            .collect();
+        Self(rows)
+    }
+
+    fn mul(&self, a: Gf) -> Gf {
+        self.0.iter().enumerate().fold(Gf::ZERO, |acc, (i, row)| {
+            acc + row[(a.0 >> (8 * i)) as u8 as usize]
+        })
+    }
+}
+
+/// AES-GCM under one key
+pub struct Gcm {
+    aes: Aes,
+    h: MulTable,
+}
+
+impl Gcm {
+    pub fn new(key: &[u8]) -> Result<Self> {
+        let aes = Aes::new(key)?;
+        let mut zero = [0; BLOCK_SIZE];
+        aes.encrypt_block(&mut zero);
+        Ok(Self {
+            aes,
+            h: MulTable::new(Gf::from_block(&zero)),
+        })
+    }
+
+    /// CTR from counter 1, with GCM's counter blocks: E(K, nonce || 1), E(K, nonce || 2), ...
+    fn ctr(&self, nonce: &[u8; NONCE_SIZE]) -> Ctr {
+        Ctr::with_layout(self.aes.clone(), nonce, Layout::GCM)
+    }
+
+    /// CTR mode from counter 2, 1 being kept for the mask
+    fn keystream(&self, nonce: &[u8; NONCE_SIZE], data: &[u8]) -> Vec<u8> {
+        let mut ctr = self.ctr(nonce);
+        ctr.seek(BLOCK_SIZE);
+        ctr.apply_keystream(data)
+    }
+
+    /// t = GHASH(h, A, C) + s, with s = E(K, nonce || 1) the mask
+    fn tag(&self, nonce: &[u8; NONCE_SIZE], ad: &[u8], ciphertext: &[u8]) -> [u8; TAG_SIZE] {
+        let mask: Vec<u8> = self.ctr(nonce).take(BLOCK_SIZE).collect();
+        let s = Gf::from_block(&mask);
+        (ghash_by(|g| self.h.mul(g), ad, ciphertext) + s).to_block()
+    }
+
+    /// (C, t)
+    pub fn seal(
+        &self,
+        nonce: &[u8; NONCE_SIZE],
+        ad: &[u8],
+        plaintext: &[u8],
+    ) -> (Vec<u8>, [u8; TAG_SIZE]) {
+        let ciphertext = self.keystream(nonce, plaintext);
+        let tag = self.tag(nonce, ad, &ciphertext);
+        (ciphertext, tag)
+    }
+
+    /// The plaintext, if the tag is right
+    pub fn open(
+        &self,
+        nonce: &[u8; NONCE_SIZE],
+        ad: &[u8],
+        ciphertext: &[u8],
+        tag: &[u8; TAG_SIZE],
+    ) -> Result<Vec<u8>> {
+        if &self.tag(nonce, ad, ciphertext) != tag {
+            return Err(anyhow!("Tag doesn't match"));
+        }
+        Ok(self.keystream(nonce, ciphertext))
+    }
+
+    /// As [`Gcm::open`], against a tag cut down to its first few bytes
+    pub fn open_truncated(
+        &self,
+        nonce: &[u8; NONCE_SIZE],
+        ad: &[u8],
+        ciphertext: &[u8],
+        tag: &[u8],
+    ) -> Result<Vec<u8>> {
+        if tag.is_empty() || tag.len() > TAG_SIZE {
+            return Err(anyhow!("A tag can't be {} bytes", tag.len()));
+        }
+        if self.tag(nonce, ad, ciphertext)[..tag.len()] != *tag {
+            return Err(anyhow!("Tag doesn't match"));
+        }
+        Ok(self.keystream(nonce, ciphertext))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::hex_to_bytes;
+    use openssl::symm::{encrypt_aead, Cipher};
+
+    #[test]
+    fn field() {
+        let mut rng = rand::thread_rng();
+        let (a, b, c) = (
+            Gf::random(&mut rng),
+            Gf::random(&mut rng),
+            Gf::random(&mut rng),
+        );
+        assert_eq!(a * (b + c), a * b + a * c);
+        assert_eq!((a * b) * c, a * (b * c));
+        assert_eq!(a * a.inverse(), Gf::ONE);
+        assert_eq!(a.sqrt() * a.sqrt(), a);
+        assert_eq!(a + a, Gf::ZERO);
+        // x^127 x = x^7 + x^2 + x + 1
+        assert_eq!(Gf(1 << 127) * Gf(2), Gf(0x87));
+        assert_eq!(Gf::from_block(&a.to_block()), a);
+        assert_eq!(Gf::from_block(&[0x80]), Gf::ONE);
+    }
+
+    /// Mostly uniform, but often zero, one, all ones or a single power of x, where carries and
+    /// reductions are most likely to go wrong
+    fn element<R: Rng>(rng: &mut R) -> Gf {
+        match rng.gen_range(0..8) {
+            0 => Gf::ZERO,
+            1 => Gf::ONE,
+            2 => Gf(u128::MAX),
+            3 => Gf(1 << rng.gen_range(0..128)),
+            _ => Gf::random(rng),
+        }
+    }
+
+    #[test]
+    fn field_axioms() {
+        let mut rng = rand::thread_rng();
+        for _ in 0..1_000 {
+            let (a, b, c) = (element(&mut rng), element(&mut rng), element(&mut rng));
+            assert_eq!(a + b, b + a, "{a:?} {b:?}");
+            assert_eq!((a + b) + c, a + (b + c), "{a:?} {b:?} {c:?}");
+            assert_eq!(a * b, b * a, "{a:?} {b:?}");
+            assert_eq!((a * b) * c, a * (b * c), "{a:?} {b:?} {c:?}");
+            assert_eq!(a * (b + c), a * b + a * c, "{a:?} {b:?} {c:?}");
+            assert_eq!(a + Gf::ZERO, a);
+            assert_eq!(a * Gf::ONE, a);
+            assert_eq!(a * Gf::ZERO, Gf::ZERO);
+            assert_eq!(a + a, Gf::ZERO);
+            if a.is_zero() {
+                assert_eq!(a.inverse(), Gf::ZERO);
+            } else {
+                assert_eq!(a * a.inverse(), Gf::ONE, "{a:?}");
+            }
+            assert_eq!(a.sqrt() * a.sqrt(), a, "{a:?}");
+            assert_eq!(Gf::from_block(&a.to_block()), a);
+        }
+    }
+
+    #[test]
+    fn known_answers() {
+        // McGrew and Viega's test case 4, AES-128 with associated data and a partial last block
+        let key = hex_to_bytes("feffe9928665731c6d6a8f9467308308").unwrap();
+        let nonce: [u8; NONCE_SIZE] = hex_to_bytes("cafebabefacedbaddecaf888")
+            .unwrap()
+            .try_into()
+            .unwrap();
+        let plaintext = hex_to_bytes(
+            "d9313225f88406e5a55909c5aff5269a86a7a9531534f7da2e4c303d8a318a72\
+             1c3c0c95956809532fcf0e2449a6b525b16aedf5aa0de657ba637b39",
+        )
+        .unwrap();
+        let ad = hex_to_bytes("feedfacedeadbeeffeedfacedeadbeefabaddad2").unwrap();
+        let gcm = Gcm::new(&key).unwrap();
+        let (ciphertext, tag) = gcm.seal(&nonce, &ad, &plaintext);
+        assert_eq!(
+            ciphertext,
+            hex_to_bytes(
+                "42831ec2217774244b7221b784d0d49ce3aa212f2c02a4e035c17e2329aca12e\
+                 21d514b25466931c7d8f6a5aac84aa051ba30b396a0aac973d58e091"
+            )
+            .unwrap()
+        );
+        assert_eq!(
+            tag.to_vec(),
+            hex_to_bytes("5bc94fbc3221a5db94fae95ae7121a47").unwrap()
+        );
+        assert_eq!(gcm.open(&nonce, &ad, &ciphertext, &tag).unwrap(), plaintext);
+
+        let mut bad = tag;
+        bad[0] ^= 1;
+        assert!(gcm.open(&nonce, &ad, &ciphertext, &bad).is_err());
+        assert!(gcm.open(&nonce, b"", &ciphertext, &tag).is_err());
+        assert!(gcm
+            .open_truncated(&nonce, &ad, &ciphertext, &tag[..4])
+            .is_ok());
+        assert!(gcm
+            .open_truncated(&nonce, &ad, &ciphertext, &bad[..4])
+            .is_err());
+    }
+
+    #[test]
+    fn against_openssl() {
+        let mut rng = rand::thread_rng();
+        for (ad_len, len) in [(0, 0), (0, 16), (3, 1), (16, 33), (40, 100)] {
+            let key: [u8; 16] = rng.gen();
+            let nonce: [u8; NONCE_SIZE] = rng.gen();
+            let ad: Vec<u8> = (0..ad_len).map(|_| rng.gen()).collect();
+            let plaintext: Vec<u8> = (0..len).map(|_| rng.gen()).collect();
+
+            let mut expected_tag = [0; TAG_SIZE];
+            let expected = encrypt_aead(
+                Cipher::aes_128_gcm(),
+                &key,
+                Some(&nonce),
+                &ad,
+                &plaintext,
+                &mut expected_tag,
+            )
+            .unwrap();
+            let (ciphertext, tag) = Gcm::new(&key).unwrap().seal(&nonce, &ad, &plaintext);
+            assert_eq!(ciphertext, expected);
+            assert_eq!(tag, expected_tag);
+        }
+    }
+}
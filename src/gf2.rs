@@ -0,0 +1,154 @@
+//! Linear algebra over GF(2): bit vectors, null spaces, and GF(2^128) seen as a 128-dimensional
+//! vector space, with linear maps on it as matrices by their columns
+//!
+//! Challenge 64's attack is all in these terms: which bits of a tag some flips of the ciphertext
+//! change is a matrix over GF(2), and the flips worth making are in its null space.
+
+use crate::gcm::Gf;
+
+/// A vector over GF(2), 64 bits to a word
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Bits(Vec<u64>);
+
+impl Bits {
+    pub fn zeros(len: usize) -> Self {
+        Self(vec![0; len.div_ceil(64)])
+    }
+
+    pub fn get(&self, i: usize) -> bool {
+        self.0[i / 64] >> (i % 64) & 1 == 1
+    }
+
+    pub fn flip(&mut self, i: usize) {
+        self.0[i / 64] ^= 1 << (i % 64);
+    }
+
+    pub fn xor(&mut self, other: &Bits) {
+        for (a, b) in self.0.iter_mut().zip(&other.0) {
+            *a ^= b;
+        }
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.0.iter().all(|w| *w == 0)
+    }
+}
+
+/// A basis for the combinations of `vectors`, each `width` bits long, which sum to zero, as bit
+/// vectors picking out which go in: the null space of the matrix with `vectors` as its columns
+///
+/// Gaussian elimination on them as rows, each with the row of the identity it started as tacked
+/// on the end. The rows which end up zero have the combinations which got them there.
+pub fn kernel(vectors: &[Bits], width: usize) -> Vec<Bits> {
+    let n = vectors.len();
+    let mut rows: Vec<Bits> = vectors
+        .iter()
+        .enumerate()
+        .map(|(i, v)| {
+            let mut row = Bits::zeros(width + n);
+            for j in (0..width).filter(|j| v.get(*j)) {
+                row.flip(j);
+            }
+            row.flip(width + i);
+            row
+        })
+        .collect();
+
+    let mut rank = 0;
+    for col in 0..width {
+        let Some(pivot) = (rank..n).find(|i| rows[*i].get(col)) else {
+            continue;
+        };
+        rows.swap(rank, pivot);
+        let pivot = rows[rank].clone();
+        for row in rows[rank + 1..].iter_mut().filter(|row| row.get(col)) {
+            row.xor(&pivot);
+        }
+        rank += 1;
+    }
+
+    rows[rank..]
+        .iter()
+        .map(|row| {
+            let mut combination = Bits::zeros(n);
+            for i in (0..n).filter(|i| row.get(width + i)) {
+                combination.flip(i);
+            }
+            combination
+        })
+        .collect()
+}
+
+/// A linear map on GF(2^128), or a 128 x m matrix over GF(2), by its columns
+pub type Columns = Vec<Gf>;
+
+/// The matrix with these columns times v
+pub fn apply(columns: &[Gf], v: Gf) -> Gf {
+    columns
+        .iter()
+        .enumerate()
+        .filter(|(k, _)| v.0 >> k & 1 == 1)
+        .fold(Gf::ZERO, |acc, (_, c)| acc + *c)
+}
+
+/// Row r of the matrix with these columns
+pub fn row(columns: &[Gf], r: usize) -> Bits {
+    let mut row = Bits::zeros(columns.len());
+    for (c, column) in columns.iter().enumerate() {
+        if column.0 >> r & 1 == 1 {
+            row.flip(c);
+        }
+    }
+    row
+}
+
+/// The inner product of a row of 128 bits with h
+pub fn dot(row: &Bits, h: Gf) -> bool {
+    (0..128)
+        .filter(|k| row.get(*k) && h.0 >> k & 1 == 1)
+        .count()
+        % 2
+        == 1
+}
+
+/// A basis for the h with K h = 0, for K with `rows` as its rows
+pub fn null_space(rows: &[Bits]) -> Columns {
+    let columns: Vec<Bits> = (0..128)
+        .map(|k| {
+            let mut column = Bits::zeros(rows.len());
+            for (i, row) in rows.iter().enumerate() {
+                if row.get(k) {
+                    column.flip(i);
+                }
+            }
+            column
+        })
+        .collect();
+    kernel(&columns, rows.len())
+        .iter()
+        .map(|v| {
+            Gf((0..128)
+                .filter(|k| v.get(*k))
+                .fold(0, |acc, k| acc | 1 << k))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn kernels() {
+        // Columns (1, 0), (0, 1) and (1, 1): only all three together sum to zero
+        let vectors: Vec<Bits> = [0b01, 0b10, 0b11].map(|w| Bits(vec![w])).to_vec();
+        assert_eq!(kernel(&vectors, 2), [Bits(vec![0b111])]);
+        assert!(kernel(&vectors[..2], 2).is_empty());
+
+        // x^0 + x^1 = 0 and x^2 = 0 leave x^0 + x^1 and everything from x^3 on
+        let rows = [Bits(vec![0b011, 0]), Bits(vec![0b100, 0])];
+        let x = null_space(&rows);
+        assert_eq!(x.len(), 126);
+        assert!(x.iter().all(|v| !dot(&rows[0], *v) && !dot(&rows[1], *v)));
+    }
+}
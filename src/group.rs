@@ -0,0 +1,583 @@
+//! Groups to do Diffie-Hellman in, as far as the set 8 attacks need them
+//!
+//! The attacks only ever scale an element by a whole number (g^k mod p, or k P on a curve), pick
+//! elements of some small order r to send, and turn elements into bytes (to send, or to key a MAC
+//! with), so that's all a [`Group`] has to do.
+//!
+//! Discrete logs need more than that, a way to combine two elements and undo one, which is what
+//! an [`Abelian`] group adds. An x-only Montgomery curve can't, so it stays a plain [`Group`].
+
+use anyhow::anyhow;
+use num_bigint::{BigInt, RandBigInt, Sign};
+use num_integer::Integer;
+use num_traits::{One, Signed, ToPrimitive, Zero};
+use rand::Rng;
+use rayon::prelude::*;
+use std::fmt::Debug;
+
+use crate::fxhash::FxHashMap;
+use crate::progress;
+use crate::trace::{event, span};
+use crate::utils::*;
+
+pub trait Group {
+    type Element: Clone + PartialEq + Debug;
+
+    /// `element` combined with itself k times
+    fn scale(&self, element: &Self::Element, k: &BigInt) -> Self::Element;
+
+    /// `element`, 2 `element`, 3 `element` and so on, for groups with a quicker way from each one
+    /// to the next than scaling every time
+    fn multiples<'a>(
+        &'a self,
+        element: &Self::Element,
+    ) -> Box<dyn Iterator<Item = Self::Element> + 'a> {
+        let element = element.clone();
+        Box::new(
+            std::iter::successors(Some(BigInt::one()), |k| Some(k + 1))
+                .map(move |k| self.scale(&element, &k)),
+        )
+    }
+
+    /// A random element of order r, for r a prime which divides the order of the group just once
+    fn element_of_order(&self, r: &BigInt, rng: &mut impl Rng) -> Self::Element;
+
+    /// The bytes a shared secret is keyed from, and which an element is sent as
+    fn encode(&self, element: &Self::Element) -> Vec<u8>;
+
+    /// The element `encode` gave `bytes` for
+    fn decode(&self, bytes: &[u8]) -> Result<Self::Element>;
+}
+
+/// A [`Group`] whose elements can be combined and inverted
+pub trait Abelian: Group {
+    /// The group operation, a b mod p or P + Q on a curve
+    fn combine(&self, a: &Self::Element, b: &Self::Element) -> Self::Element;
+
+    fn inverse(&self, element: &Self::Element) -> Self::Element;
+
+    /// a b for each pair, for groups with a quicker way to do a batch of them than one at a time
+    fn combine_many(&self, pairs: &[(&Self::Element, &Self::Element)]) -> Vec<Self::Element> {
+        pairs.iter().map(|(a, b)| self.combine(a, b)).collect()
+    }
+}
+
+/// An element's encoding read as a number, which is what the kangaroos decide where to jump by
+fn label<G: Group>(group: &G, element: &G::Element) -> BigInt {
+    BigInt::from_bytes_be(Sign::Plus, &group.encode(element))
+}
+
+/// x in [0, order) with `base` scaled by x equal to `target`, if there is one, by baby-step
+/// giant-step
+///
+/// The baby steps are `base` scaled by 0 to m - 1, for m = ceil(sqrt(order)), kept by their
+/// encoding; the giant steps take m `base` away from `target` until one lands in the table.
+pub fn discrete_log<G: Abelian>(
+    group: &G,
+    base: &G::Element,
+    target: &G::Element,
+    order: &BigInt,
+) -> Option<BigInt> {
+    let mut m = order.sqrt();
+    if &m * &m < *order {
+        m += 1;
+    }
+    let m = m.to_usize()?;
+    let zero = group.scale(base, &BigInt::zero());
+    let table: FxHashMap<Vec<u8>, usize> = std::iter::once(zero)
+        .chain(group.multiples(base))
+        .take(m)
+        .enumerate()
+        .map(|(j, element)| (group.encode(&element), j))
+        .collect();
+
+    let giant = group.inverse(&group.scale(base, &m.into()));
+    let mut gamma = target.clone();
+    for i in 0..m {
+        if let Some(j) = table.get(&group.encode(&gamma)) {
+            return Some(BigInt::from(i) * m + j);
+        }
+        gamma = group.combine(&gamma, &giant);
+    }
+    None
+}
+
+/// x mod n with `base` scaled by x equal to `target`, for `base` of order n the product of
+/// `factors` (primes, repeated as often as they divide n), by Pohlig-Hellman
+///
+/// The log mod each prime power r^e comes a digit at a time, each a [`discrete_log`] in the
+/// subgroup of order r, and CRT puts those together.
+pub fn pohlig_hellman<G: Abelian>(
+    group: &G,
+    base: &G::Element,
+    target: &G::Element,
+    factors: &[BigInt],
+) -> Option<BigInt> {
+    let n: BigInt = factors.iter().product();
+    let mut powers: Vec<(BigInt, u32)> = vec![];
+    for r in factors {
+        match powers.iter_mut().find(|(q, _)| q == r) {
+            Some((_, e)) => *e += 1,
+            None => powers.push((r.clone(), 1)),
+        }
+    }
+
+    let (mut x, mut modulus) = (BigInt::zero(), BigInt::one());
+    for (r, e) in powers {
+        // Of order r
+        let gamma = group.scale(base, &(&n / &r));
+        let mut xr = BigInt::zero();
+        let mut rk = BigInt::one();
+        for _ in 0..e {
+            // target - xr base, pushed down into the subgroup of order r, is the kth digit scaled
+            let rest = group.combine(target, &group.inverse(&group.scale(base, &xr)));
+            let h = group.scale(&rest, &(&n / (&rk * &r)));
+            xr += discrete_log(group, &gamma, &h, &r)? * &rk;
+            rk *= &r;
+        }
+        // x = x mod modulus, and xr mod r^e
+        let t = ((&xr - &x) * try_invmod(&modulus, &rk)?).mod_floor(&rk);
+        x += t * &modulus;
+        modulus *= rk;
+    }
+    Some(x)
+}
+
+/// How the kangaroos jump: f(y) = 2^(y mod k), with the tame one taking N jumps
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Jumps {
+    k: u32,
+    n: BigInt,
+}
+
+/// N as a multiple of the mean jump
+const STRETCH: u32 = 4;
+/// Times to go again with longer jumps, after the wild kangaroo gets away
+const RESTARTS: u32 = 4;
+
+impl Jumps {
+    /// For an index somewhere in a range `width` wide, a mean jump of about sqrt(width) / 2
+    ///
+    /// That's Pollard's choice: the tame kangaroo covers a stretch about as long as the range, and
+    /// the wild one catches up on it in about as many jumps again, so about 2 sqrt(width) in all.
+    fn for_range(width: &BigInt) -> Self {
+        Self::for_herd(width, 1)
+    }
+
+    /// For `herd` kangaroos of each kind, a mean jump `herd` times as long as one pair's, so that
+    /// between them they cover the range as quickly as the pair would
+    fn for_herd(width: &BigInt, herd: usize) -> Self {
+        Self::for_mean(&(herd * width.sqrt() / 2))
+    }
+
+    /// The k with the mean jump nearest `target`
+    fn for_mean(target: &BigInt) -> Self {
+        let k = (1..256)
+            .map(|k| (k, mean_jump(k)))
+            .min_by_key(|(_, mean)| (mean - target).abs())
+            .map(|(k, _)| k)
+            .unwrap();
+        Self::with_k(k)
+    }
+
+    /// f(y) = 2^(y mod k), and N the mean of f times [`STRETCH`]
+    fn with_k(k: u32) -> Self {
+        Self {
+            k,
+            n: STRETCH * mean_jump(k),
+        }
+    }
+
+    fn jump(&self, y: &BigInt) -> usize {
+        (y % self.k).to_usize().unwrap()
+    }
+
+    /// Jumps the kangaroos should take between them, give or take, to catch one in a range `width`
+    /// wide: the tame one's N, then N more for the wild one, plus however many it takes to get from
+    /// the middle of the range to where the tame one started
+    fn expected(&self, width: &BigInt) -> BigInt {
+        2 * &self.n + width / (2 * mean_jump(self.k))
+    }
+
+    /// Jumps a herd of `herd` tame and `herd` wild kangaroos should take between them, give or
+    /// take: about 2 sqrt(width) each way, to cover the range and then land on a trail, plus the
+    /// distinguished points' share, as each kind passes mean jumps' worth after a landing
+    fn expected_herd(&self, width: &BigInt, herd: usize) -> BigInt {
+        let root = width.sqrt().max(BigInt::one());
+        4 * &root + 2 * herd * mean_jump(self.k) / root
+    }
+}
+
+/// (2^k - 1) / k, the mean of 2^(y mod k)
+fn mean_jump(k: u32) -> BigInt {
+    ((BigInt::one() << k) - 1) / k
+}
+
+/// x in [0, width] with `base` scaled by x equal to `target`, if the wild kangaroo lands on the
+/// tame one's trap, by Pollard's kangaroo as challenge 58 has it, for any [`Abelian`] group
+///
+/// The jumps are 2^(e mod k), for e an element's encoding read as a number, and k goes up by one
+/// each time the wild kangaroo gets away, up to [`RESTARTS`] times.
+pub fn kangaroo<G: Abelian>(
+    group: &G,
+    base: &G::Element,
+    target: &G::Element,
+    width: &BigInt,
+) -> Option<BigInt> {
+    let _span = span!(Info, "kangaroo", bits = width.bits());
+    let mut jumps = Jumps::for_range(width);
+    for _ in 0..=RESTARTS {
+        event!(
+            Debug,
+            "kangaroos out",
+            k = jumps.k,
+            n = jumps.n,
+            expected = jumps.expected(width)
+        );
+        // base scaled by each value a jump can take
+        let powers: Vec<G::Element> = (0..jumps.k)
+            .map(|i| group.scale(base, &(BigInt::one() << i)))
+            .collect();
+        let jump = |element: &G::Element| jumps.jump(&label(group, element));
+
+        let (mut xt, mut yt) = (BigInt::zero(), group.scale(base, width));
+        let mut count = BigInt::zero();
+        while count < jumps.n {
+            let i = jump(&yt);
+            xt += BigInt::one() << i;
+            yt = group.combine(&yt, &powers[i]);
+            count += 1;
+        }
+
+        let (mut xw, mut yw) = (BigInt::zero(), target.clone());
+        let xw_max: BigInt = width + &xt;
+        let mut wild = 0u64;
+        while xw < xw_max {
+            let i = jump(&yw);
+            xw += BigInt::one() << i;
+            yw = group.combine(&yw, &powers[i]);
+            wild += 1;
+            if yw == yt {
+                event!(Debug, "caught", wild = wild);
+                return Some(xw_max - xw);
+            }
+        }
+        event!(Debug, "got away", wild = wild);
+        jumps = Jumps::with_k(jumps.k + 1);
+    }
+    None
+}
+
+/// Jumps each kangaroo in a herd takes between looking at the distinguished points
+const ROUND: u64 = 1 << 10;
+/// Jumps to give up after, as a multiple of how many the herd should need
+const PATIENCE: u64 = 32;
+
+/// One of a herd, `distance` along from where the tame ones count from (so `point` is `base`
+/// scaled by that) or from its own target, with `label` the number `point` jumps by
+#[derive(Debug, Clone)]
+struct Roo<E> {
+    kind: Option<usize>,
+    point: E,
+    label: BigInt,
+    distance: BigInt,
+}
+
+/// i and x in [0, width] with `base` scaled by x equal to `targets[i]`, by van Oorschot and
+/// Wiener's parallel kangaroos, along with the total number of jumps
+///
+/// There are `herd` tame kangaroos, and `herd` wild ones for each target, all jumping at once,
+/// with the mean jump lengthened by [`Jumps::for_herd`]. Rather than a single trap there are
+/// distinguished points, those with the low bits of their label all zero, and each kangaroo leaves
+/// a note of its distance at any it lands on. A tame and a wild note at the same point give x for
+/// the wild one's target; two of the same kind mean two kangaroos are now jumping in lockstep, so
+/// one of them starts again somewhere else.
+///
+/// Each round the kangaroos jump [`ROUND`] times, split into packs across as many threads as rayon
+/// has, with each pack's jumps done together by [`Abelian::combine_many`], and then the notes are
+/// gathered up.
+pub fn herd_kangaroo<G>(
+    group: &G,
+    base: &G::Element,
+    targets: &[G::Element],
+    width: &BigInt,
+    herd: usize,
+    rng: &mut impl Rng,
+) -> Result<(usize, BigInt, u64)>
+where
+    G: Abelian + Sync,
+    G::Element: Send + Sync,
+{
+    let _span = span!(Info, "herd_kangaroo", bits = width.bits(), herd = herd);
+    let jumps = Jumps::for_herd(width, herd);
+    let mean = mean_jump(jumps.k);
+    // base scaled by each value a jump can take
+    let powers: Vec<G::Element> = (0..jumps.k)
+        .map(|i| group.scale(base, &(BigInt::one() << i)))
+        .collect();
+    // Each kangaroo should pass a few dozen distinguished points on its way
+    let expected = jumps.expected_herd(width, herd);
+    let per_roo: BigInt = &expected / (2 * herd);
+    let mask = (BigInt::one() << (per_roo / 32u32).bits().saturating_sub(1)) - 1;
+    event!(
+        Info,
+        "herds out",
+        k = jumps.k,
+        tame = herd,
+        wild = herd * targets.len(),
+        expected = expected
+    );
+
+    let middle: BigInt = width / 2;
+    let mut start = |kind: Option<usize>| {
+        let offset = rng.gen_bigint_range(&BigInt::zero(), &mean);
+        let (point, distance) = match kind {
+            None => (group.scale(base, &(&middle + &offset)), &middle + offset),
+            Some(i) => (
+                group.combine(&targets[i], &group.scale(base, &offset)),
+                offset,
+            ),
+        };
+        Roo {
+            kind,
+            label: label(group, &point),
+            point,
+            distance,
+        }
+    };
+    let mut roos: Vec<Roo<G::Element>> = (0..herd)
+        .map(|_| None)
+        .chain((0..targets.len()).flat_map(|i| vec![Some(i); herd]))
+        .map(&mut start)
+        .collect();
+    let pack = roos.len().div_ceil(rayon::current_num_threads());
+
+    // Half the expected jumps are the tame ones', and half the wild ones' for each target
+    let limit = (PATIENCE * (targets.len() as u64 + 1) * expected / 2u32)
+        .to_u64()
+        .unwrap_or(u64::MAX);
+    let spinner = progress::spinner();
+    let mut notes: FxHashMap<BigInt, (Option<usize>, BigInt)> = FxHashMap::default();
+    let mut total = 0;
+    while total < limit {
+        // The (label, distance) of each distinguished point each kangaroo lands on
+        let landed: Vec<Vec<(BigInt, BigInt)>> = roos
+            .par_chunks_mut(pack)
+            .flat_map_iter(|pack| {
+                let mut landed = vec![vec![]; pack.len()];
+                for _ in 0..ROUND {
+                    let steps: Vec<usize> = pack.iter().map(|roo| jumps.jump(&roo.label)).collect();
+                    let pairs: Vec<(&G::Element, &G::Element)> = pack
+                        .iter()
+                        .zip(&steps)
+                        .map(|(roo, i)| (&roo.point, &powers[*i]))
+                        .collect();
+                    let points = group.combine_many(&pairs);
+                    for (((roo, landed), point), i) in
+                        pack.iter_mut().zip(&mut landed).zip(points).zip(steps)
+                    {
+                        roo.distance += BigInt::one() << i;
+                        roo.label = label(group, &point);
+                        roo.point = point;
+                        if (&roo.label & &mask).is_zero() {
+                            landed.push((roo.label.clone(), roo.distance.clone()));
+                        }
+                    }
+                }
+                landed
+            })
+            .collect();
+        total += ROUND * roos.len() as u64;
+        spinner.set_message(format!(
+            "{total} jumps, {} distinguished points",
+            notes.len()
+        ));
+        spinner.tick();
+        event!(Trace, "round", jumps = total, distinguished = notes.len());
+
+        for (i, landed) in landed.into_iter().enumerate() {
+            let kind = roos[i].kind;
+            for (point, distance) in landed {
+                match notes.get(&point) {
+                    None => {
+                        notes.insert(point, (kind, distance));
+                    }
+                    Some((other, other_distance)) if other.is_none() != kind.is_none() => {
+                        // The tame distance less the wild one is x for the wild one's target
+                        let (x, target) = match kind {
+                            None => (&distance - other_distance, other.unwrap()),
+                            Some(target) => (other_distance - &distance, target),
+                        };
+                        if !x.is_negative() && group.scale(base, &x) == targets[target] {
+                            spinner.finish();
+                            event!(Debug, "caught", jumps = total, x = x);
+                            return Ok((target, x, total));
+                        }
+                    }
+                    Some(_) => {
+                        event!(
+                            Debug,
+                            "kangaroos in lockstep, restarting one",
+                            wild = kind.is_some()
+                        );
+                        roos[i] = start(kind);
+                        break;
+                    }
+                }
+            }
+        }
+    }
+    spinner.finish();
+    Err(anyhow!("No wild kangaroo caught after {total} jumps"))
+}
+
+/// The whole multiplicative group mod a prime p, of order p - 1
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ModP {
+    pub p: BigInt,
+}
+
+impl Group for ModP {
+    type Element = BigInt;
+
+    fn scale(&self, element: &BigInt, k: &BigInt) -> BigInt {
+        element.modpow(k, &self.p)
+    }
+
+    fn multiples<'a>(&'a self, element: &BigInt) -> Box<dyn Iterator<Item = BigInt> + 'a> {
+        let element = element.clone();
+        Box::new(std::iter::successors(Some(element.clone()), move |x| {
+            Some(x * &element % &self.p)
+        }))
+    }
+
+    /// rand(1, p)^((p-1)/r) mod p, until that isn't 1
+    fn element_of_order(&self, r: &BigInt, rng: &mut impl Rng) -> BigInt {
+        let one = BigInt::one();
+        let pow = (&self.p - &one) / r;
+        loop {
+            let h = rng.gen_bigint_range(&one, &self.p).modpow(&pow, &self.p);
+            if h != one {
+                return h;
+            }
+        }
+    }
+
+    fn encode(&self, element: &BigInt) -> Vec<u8> {
+        element.to_bytes_be().1
+    }
+
+    fn decode(&self, bytes: &[u8]) -> Result<BigInt> {
+        Ok(BigInt::from_bytes_be(Sign::Plus, bytes))
+    }
+}
+
+impl Abelian for ModP {
+    fn combine(&self, a: &BigInt, b: &BigInt) -> BigInt {
+        a * b % &self.p
+    }
+
+    fn inverse(&self, element: &BigInt) -> BigInt {
+        try_invmod(element, &self.p).unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn orders() {
+        // 1019 - 1 = 2 * 509
+        let group = ModP { p: 1019.into() };
+        for r in [2, 509] {
+            let r = BigInt::from(r);
+            let h = group.element_of_order(&r, &mut rand::thread_rng());
+            assert_ne!(h, BigInt::one());
+            assert!(group.scale(&h, &r).is_one());
+        }
+
+        let g = BigInt::from(3);
+        let stepped: Vec<_> = group.multiples(&g).take(100).collect();
+        let scaled: Vec<_> = (1..=100u32).map(|k| group.scale(&g, &k.into())).collect();
+        assert_eq!(stepped, scaled);
+    }
+
+    #[test]
+    fn logs() {
+        // 2311 - 1 = 2 * 3 * 5 * 7 * 11, and 3 generates the whole group
+        let group = ModP { p: 2311.into() };
+        let g = BigInt::from(3);
+        let order = BigInt::from(2310);
+        let factors: Vec<BigInt> = [2, 3, 5, 7, 11].map(BigInt::from).to_vec();
+        for x in [0u32, 1, 2, 1000, 2309] {
+            let y = group.scale(&g, &x.into());
+            assert_eq!(discrete_log(&group, &g, &y, &order), Some(x.into()));
+            assert_eq!(pohlig_hellman(&group, &g, &y, &factors), Some(x.into()));
+        }
+
+        // 1459 - 1 = 2 * 3^6, again with 3 generating it
+        let group = ModP { p: 1459.into() };
+        let factors: Vec<BigInt> = [2, 3, 3, 3, 3, 3, 3].map(BigInt::from).to_vec();
+        for x in [5u32, 728, 1457] {
+            let y = group.scale(&g, &x.into());
+            assert_eq!(pohlig_hellman(&group, &g, &y, &factors), Some(x.into()));
+        }
+
+        // Kangaroos only look in the range they're given
+        let group = ModP {
+            p: 1_000_003.into(),
+        };
+        let g = BigInt::from(2);
+        for x in [0u32, 1, 4321, 5000] {
+            let y = group.scale(&g, &x.into());
+            assert_eq!(kangaroo(&group, &g, &y, &5000.into()), Some(x.into()));
+        }
+        let y = group.scale(&g, &500_000.into());
+        assert_eq!(kangaroo(&group, &g, &y, &5000.into()), None);
+
+        // A herd finds whichever target is in range
+        let targets = [500_000u32, 4321].map(|x| group.scale(&g, &x.into()));
+        let (i, x, _) = herd_kangaroo(
+            &group,
+            &g,
+            &targets,
+            &5000.into(),
+            2,
+            &mut rand::thread_rng(),
+        )
+        .unwrap();
+        assert_eq!((i, x), (1, 4321.into()));
+
+        // 2 only generates the quadratic residues mod 2311, so 3 isn't a power of it
+        let group = ModP { p: 2311.into() };
+        let g = BigInt::from(3);
+        assert_eq!(discrete_log(&group, &2.into(), &g, &order), None);
+    }
+
+    #[test]
+    fn jump_sizes() {
+        // Mean jumps of (2^k - 1) / k
+        assert_eq!(mean_jump(1), BigInt::one());
+        assert_eq!(mean_jump(11), 186.into());
+        assert_eq!(Jumps::with_k(11).n, 744.into());
+
+        // sqrt(2^20) / 2 = 512, nearest to k = 13's 630 (as k = 12 gives 341)
+        assert_eq!(Jumps::for_range(&(BigInt::one() << 20)).k, 13);
+        for bits in [20, 40, 60, 100] {
+            let width = BigInt::one() << bits;
+            let jumps = Jumps::for_range(&width);
+            let target: BigInt = width.sqrt() / 2;
+            assert!(mean_jump(jumps.k) <= 2 * &target, "{bits}");
+            assert!(2 * mean_jump(jumps.k) >= target, "{bits}");
+            // Neither the tame nor the wild kangaroo does much more than sqrt(width) jumps
+            assert!(jumps.expected(&width) < 20 * width.sqrt(), "{bits}");
+
+            // A herd of 8 jumps 8 times as far, and takes about as long between them
+            let herd = Jumps::for_herd(&width, 8);
+            assert!(mean_jump(herd.k) <= 16 * &target, "{bits}");
+            assert!(2 * mean_jump(herd.k) >= 8 * &target, "{bits}");
+            assert!(herd.expected_herd(&width, 8) < 20 * width.sqrt(), "{bits}");
+        }
+    }
+}
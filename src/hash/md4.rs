@@ -0,0 +1,249 @@
+//! MD4, as specified in RFC 1320
+//! https://www.rfc-editor.org/rfc/rfc1320
+//!
+//! The round functions and steps are public so that attacks on the compression function, like
+//! Wang's collisions in challenge 55, work through exactly the same code as the digest itself.
+#![allow(dead_code)]
+
+/// Initial values of the A, B, C and D registers
+pub const INIT: [u32; 4] = [0x67452301, 0xefcdab89, 0x98badcfe, 0x10325476];
+
+const ROUND2_CONSTANT: u32 = 0x5a827999;
+const ROUND3_CONSTANT: u32 = 0x6ed9eba1;
+
+pub fn f(x: u32, y: u32, z: u32) -> u32 {
+    (x & y) | ((!x) & z)
+}
+
+pub fn g(x: u32, y: u32, z: u32) -> u32 {
+    (x & y) | (x & z) | (y & z)
+}
+
+pub fn h(x: u32, y: u32, z: u32) -> u32 {
+    x ^ y ^ z
+}
+
+/// Round 1 step: a = (a + F(b,c,d) + X[k]) <<< s
+pub fn ff(a: u32, b: u32, c: u32, d: u32, xk: u32, s: u32) -> u32 {
+    a.wrapping_add(f(b, c, d)).wrapping_add(xk).rotate_left(s)
+}
+
+/// The message word which takes a round 1 step from `a` to `new_a`
+pub fn ff_inverse(new_a: u32, a: u32, b: u32, c: u32, d: u32, s: u32) -> u32 {
+    new_a
+        .rotate_right(s)
+        .wrapping_sub(a)
+        .wrapping_sub(f(b, c, d))
+}
+
+/// Round 2 step: a = (a + G(b,c,d) + X[k] + 5A827999) <<< s
+pub fn gg(a: u32, b: u32, c: u32, d: u32, xk: u32, s: u32) -> u32 {
+    a.wrapping_add(g(b, c, d))
+        .wrapping_add(xk)
+        .wrapping_add(ROUND2_CONSTANT)
+        .rotate_left(s)
+}
+
+/// Round 3 step: a = (a + H(b,c,d) + X[k] + 6ED9EBA1) <<< s
+pub fn hh(a: u32, b: u32, c: u32, d: u32, xk: u32, s: u32) -> u32 {
+    a.wrapping_add(h(b, c, d))
+        .wrapping_add(xk)
+        .wrapping_add(ROUND3_CONSTANT)
+        .rotate_left(s)
+}
+
+/// Splits a block into its sixteen little-endian words
+pub fn words(block: &[u8]) -> [u32; 16] {
+    let mut x = [0; 16];
+    for (w, chunk) in x.iter_mut().zip(block.chunks_exact(4)) {
+        *w = u32::from_le_bytes(chunk.try_into().unwrap());
+    }
+    x
+}
+
+/// Puts words back together into a block
+pub fn block(words: &[u32; 16]) -> Vec<u8> {
+    words.iter().flat_map(|w| w.to_le_bytes()).collect()
+}
+
+pub fn md4_hash(data: &[u8]) -> String {
+    let mut h = Md4Hasher::new();
+    let output = h.hash(data);
+    crate::utils::bytes_to_hex(&output)
+}
+
+pub struct Md4Hasher {
+    a: u32,
+    b: u32,
+    c: u32,
+    d: u32,
+}
+
+impl Default for Md4Hasher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Md4Hasher {
+    /// Pads `data`, with the length field claiming it was `bogus_ml` bytes long
+    pub fn prepare(data: &[u8], bogus_ml: usize) -> Vec<u8> {
+        let ml = data.len();
+        let mut data = data.to_vec();
+        // Want to pad so there are 64 bits left over
+        // Modulo is how much room there is
+        let modulo = 64 - ml % 64;
+        let pl = modulo
+            + match modulo {
+                // If very short, we need to add a whole extra block
+                0..=8 => 64,
+                9..=64 => 0,
+                _ => panic!("Modulo is invalid"),
+            }
+            - 8;
+
+        let remainder = (ml + pl) % 64;
+        // Exactly 64 bits on the end
+        assert_eq!(remainder, 64 - 8);
+
+        // Add 1 bit
+        data.extend_from_slice(&[0x80]);
+        // Add rest of padding
+        data.extend_from_slice(&vec![0; pl - 1]);
+
+        // Append length
+        data.extend_from_slice(&(8 * bogus_ml as u64).to_le_bytes());
+
+        assert_eq!(data.len() % 64, 0);
+        data
+    }
+
+    pub fn bogus_hash(&mut self, data: &[u8], ml: usize) -> Vec<u8> {
+        let data = Self::prepare(data, ml);
+
+        self.process(&data)
+    }
+
+    pub fn hash(&mut self, data: &[u8]) -> Vec<u8> {
+        let data = Self::prepare(data, data.len());
+
+        self.process(&data)
+    }
+
+    /// Runs the compression function over whole blocks, returning the digest so far
+    pub fn process(&mut self, data: &[u8]) -> Vec<u8> {
+        for chunk in data.chunks_exact(64) {
+            self.compress(&words(chunk));
+        }
+        self.digest()
+    }
+
+    pub fn compress(&mut self, x: &[u32; 16]) {
+        let [mut a, mut b, mut c, mut d] = [self.a, self.b, self.c, self.d];
+
+        // Round 1
+        for o in [0, 4, 8, 12] {
+            a = ff(a, b, c, d, x[o], 3);
+            d = ff(d, a, b, c, x[o + 1], 7);
+            c = ff(c, d, a, b, x[o + 2], 11);
+            b = ff(b, c, d, a, x[o + 3], 19);
+        }
+
+        // Round 2
+        for o in 0..4 {
+            a = gg(a, b, c, d, x[o], 3);
+            d = gg(d, a, b, c, x[o + 4], 5);
+            c = gg(c, d, a, b, x[o + 8], 9);
+            b = gg(b, c, d, a, x[o + 12], 13);
+        }
+
+        // Round 3
+        for o in [0, 2, 1, 3] {
+            a = hh(a, b, c, d, x[o], 3);
+            d = hh(d, a, b, c, x[o + 8], 9);
+            c = hh(c, d, a, b, x[o + 4], 11);
+            b = hh(b, c, d, a, x[o + 12], 15);
+        }
+
+        self.a = self.a.wrapping_add(a);
+        self.b = self.b.wrapping_add(b);
+        self.c = self.c.wrapping_add(c);
+        self.d = self.d.wrapping_add(d);
+    }
+
+    pub fn digest(&self) -> Vec<u8> {
+        [self.a, self.b, self.c, self.d]
+            .iter()
+            .flat_map(|r| r.to_le_bytes())
+            .collect()
+    }
+
+    pub fn new() -> Self {
+        let [a, b, c, d] = INIT;
+        Self { a, b, c, d }
+    }
+
+    pub fn load(digest: &[u8]) -> Self {
+        let c: Vec<u32> = digest
+            .chunks(4)
+            .map(|w| u32::from_le_bytes(w.try_into().unwrap()))
+            .collect();
+        if let &[a, b, c, d] = &c[..] {
+            Self { a, b, c, d }
+        } else {
+            panic!("Invalid digest");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::hex_to_bytes;
+
+    #[test]
+    fn test_load() {
+        // From RFC
+        let h = hex_to_bytes("31d6cfe0d16ae931b73c59d7e0c089c0").unwrap();
+        let b = b"";
+        let loader = hex_to_bytes("0123456789abcdeffedcba9876543210").unwrap();
+        let mut hasher = Md4Hasher::load(&loader);
+        assert_eq!(h, hasher.hash(b));
+    }
+
+    #[test]
+    fn test_hashes() {
+        // From RFC
+        let vectors: [(&str, &[u8]); 7] = [
+            ("31d6cfe0d16ae931b73c59d7e0c089c0", b""),
+            ("bde52cb31de33e46245e05fbdbd6fb24", b"a"),
+            ("a448017aaf21d8525fc10ae87aa6729d", b"abc"),
+            ("d9130a8164549fe818874806e1c7014b", b"message digest"),
+            (
+                "d79e1c308aa5bbcdeea8ed63df412da9",
+                b"abcdefghijklmnopqrstuvwxyz",
+            ),
+            (
+                "043f8582f241db351ce627e153e7f0e4",
+                b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789",
+            ),
+            (
+                "e33b4ddc9c38f2199c3e7b164fcc0536",
+                b"12345678901234567890123456789012345678901234567890123456789012345678901234567890",
+            ),
+        ];
+        for (h, b) in vectors {
+            assert_eq!(md4_hash(b), h);
+        }
+    }
+
+    #[test]
+    fn steps() {
+        let block: Vec<u8> = (0..64).collect();
+        assert_eq!(self::block(&words(&block)), block);
+
+        let [a, b, c, d] = INIT;
+        let xk = 0xdeadbeef;
+        assert_eq!(ff_inverse(ff(a, b, c, d, xk, 7), a, b, c, d, 7), xk);
+    }
+}
@@ -0,0 +1,198 @@
+//! SHA-256, as specified in FIPS 180-4
+//! https://csrc.nist.gov/publications/detail/fips/180/4/final
+//!
+//! Laid out like [`super::md4`], with the state loadable from a digest and the length field open
+//! to being faked, so that it can be extended just the same.
+#![allow(dead_code)]
+
+/// Initial hash value: the first 32 bits of the fractional parts of the square roots of the first
+/// eight primes
+pub const INIT: [u32; 8] = [
+    0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab, 0x5be0cd19,
+];
+
+/// Round constants: the first 32 bits of the fractional parts of the cube roots of the first
+/// sixty-four primes
+const K: [u32; 64] = [
+    0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4, 0xab1c5ed5,
+    0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174,
+    0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
+    0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967,
+    0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85,
+    0xa2bfe8a1, 0xa81a664b, 0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
+    0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+    0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2,
+];
+
+pub const BLOCK_SIZE: usize = 64;
+pub const DIGEST_SIZE: usize = 32;
+
+fn ch(x: u32, y: u32, z: u32) -> u32 {
+    (x & y) ^ (!x & z)
+}
+
+fn maj(x: u32, y: u32, z: u32) -> u32 {
+    (x & y) ^ (x & z) ^ (y & z)
+}
+
+fn big_sigma0(x: u32) -> u32 {
+    x.rotate_right(2) ^ x.rotate_right(13) ^ x.rotate_right(22)
+}
+
+fn big_sigma1(x: u32) -> u32 {
+    x.rotate_right(6) ^ x.rotate_right(11) ^ x.rotate_right(25)
+}
+
+fn small_sigma0(x: u32) -> u32 {
+    x.rotate_right(7) ^ x.rotate_right(18) ^ (x >> 3)
+}
+
+fn small_sigma1(x: u32) -> u32 {
+    x.rotate_right(17) ^ x.rotate_right(19) ^ (x >> 10)
+}
+
+/// SHA-256 of `data`, in one go
+pub fn sha256(data: &[u8]) -> Vec<u8> {
+    Sha256Hasher::new().hash(data)
+}
+
+pub struct Sha256Hasher {
+    h: [u32; 8],
+}
+
+impl Default for Sha256Hasher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Sha256Hasher {
+    pub fn new() -> Self {
+        Self { h: INIT }
+    }
+
+    /// Picks up from a digest, as if it were the state after some whole number of blocks
+    pub fn load(digest: &[u8]) -> Self {
+        let mut h = [0; 8];
+        assert_eq!(digest.len(), DIGEST_SIZE, "Invalid digest");
+        for (r, w) in h.iter_mut().zip(digest.chunks_exact(4)) {
+            *r = u32::from_be_bytes(w.try_into().unwrap());
+        }
+        Self { h }
+    }
+
+    /// Pads `data`, with the length field claiming it was `bogus_ml` bytes long
+    pub fn prepare(data: &[u8], bogus_ml: usize) -> Vec<u8> {
+        let mut data = data.to_vec();
+        data.push(0x80);
+        // Leave exactly 8 bytes in the last block for the length
+        let zeros = (2 * BLOCK_SIZE - 8 - data.len() % BLOCK_SIZE) % BLOCK_SIZE;
+        data.resize(data.len() + zeros, 0);
+        data.extend_from_slice(&(8 * bogus_ml as u64).to_be_bytes());
+        debug_assert_eq!(data.len() % BLOCK_SIZE, 0);
+        data
+    }
+
+    pub fn hash(&mut self, data: &[u8]) -> Vec<u8> {
+        self.process(&Self::prepare(data, data.len()))
+    }
+
+    pub fn bogus_hash(&mut self, data: &[u8], ml: usize) -> Vec<u8> {
+        self.process(&Self::prepare(data, ml))
+    }
+
+    /// Runs the compression function over whole blocks, returning the digest so far
+    pub fn process(&mut self, data: &[u8]) -> Vec<u8> {
+        for chunk in data.chunks_exact(BLOCK_SIZE) {
+            self.compress(chunk.try_into().unwrap());
+        }
+        self.digest()
+    }
+
+    pub fn compress(&mut self, block: &[u8; BLOCK_SIZE]) {
+        let mut w = [0; 64];
+        for (t, chunk) in block.chunks_exact(4).enumerate() {
+            w[t] = u32::from_be_bytes(chunk.try_into().unwrap());
+        }
+        for t in 16..64 {
+            w[t] = small_sigma1(w[t - 2])
+                .wrapping_add(w[t - 7])
+                .wrapping_add(small_sigma0(w[t - 15]))
+                .wrapping_add(w[t - 16]);
+        }
+
+        let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut h] = self.h;
+        for t in 0..64 {
+            let t1 = h
+                .wrapping_add(big_sigma1(e))
+                .wrapping_add(ch(e, f, g))
+                .wrapping_add(K[t])
+                .wrapping_add(w[t]);
+            let t2 = big_sigma0(a).wrapping_add(maj(a, b, c));
+            h = g;
+            g = f;
+            f = e;
+            e = d.wrapping_add(t1);
+            d = c;
+            c = b;
+            b = a;
+            a = t1.wrapping_add(t2);
+        }
+
+        for (r, v) in self.h.iter_mut().zip([a, b, c, d, e, f, g, h]) {
+            *r = r.wrapping_add(v);
+        }
+    }
+
+    pub fn digest(&self) -> Vec<u8> {
+        self.h.iter().flat_map(|r| r.to_be_bytes()).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::{bytes_to_hex, random_key};
+
+    #[test]
+    fn test_hashes() {
+        // From FIPS 180-4's examples
+        let vectors: [(&str, &[u8]); 3] = [
+            (
+                "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855",
+                b"",
+            ),
+            (
+                "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad",
+                b"abc",
+            ),
+            (
+                "248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1",
+                b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq",
+            ),
+        ];
+        for (h, b) in vectors {
+            assert_eq!(bytes_to_hex(&sha256(b)), h);
+        }
+        assert_eq!(
+            bytes_to_hex(&sha256(&[b'a'; 1_000_000])),
+            "cdc76e5c9914fb9281a1c7e284d73e67f1809a48a497200e046d39ccc7112cd0"
+        );
+
+        // Every padding case against openssl
+        let mut rng = rand::thread_rng();
+        for len in 0..200 {
+            let data = random_key(len, &mut rng);
+            assert_eq!(sha256(&data), openssl::sha::sha256(&data));
+        }
+    }
+
+    #[test]
+    fn extension() {
+        let secret = b"secret";
+        let digest = sha256(secret);
+        let glue = &Sha256Hasher::prepare(secret, secret.len())[secret.len()..];
+        let extended = Sha256Hasher::load(&digest).bogus_hash(b"more", 64 + 4);
+        assert_eq!(extended, sha256(&[&secret[..], glue, b"more"].concat()));
+    }
+}
@@ -0,0 +1,235 @@
+//! Bob, as the set 8 attacks get to see him
+//!
+//! He has a private key in some [`Group`], and will agree a key with anyone: send him a public key
+//! and he sends back a MAC over [`MESSAGE`], keyed by the secret he'd share with its owner. That
+//! and his public key are all an attack gets, whether Bob is in the same process ([`Bob`]) or at
+//! the other end of a TCP connection ([`RemoteBob`], talking to [`serve`]).
+//!
+//! On the wire, each message is its length as 4 big-endian bytes and then the bytes themselves:
+//! Bob's public key as soon as someone connects, then the public key he's sent and his MAC in reply,
+//! for as long as the connection lasts.
+
+use std::io::{ErrorKind, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::Arc;
+use std::thread;
+
+use anyhow::anyhow;
+use hmac_sha256::HMAC;
+use num_bigint::{BigInt, RandBigInt};
+use num_traits::One;
+use rand::Rng;
+
+use crate::group::Group;
+use crate::report::say;
+use crate::trace::event;
+use crate::utils::*;
+
+/// What Bob sends back, whoever he's talking to
+pub const MESSAGE: &str = "crazy flamboyant for the rap enjoyment";
+
+/// t := MAC(K, m), with K the shared secret
+pub fn mac<G: Group>(group: &G, shared: &G::Element) -> [u8; 32] {
+    HMAC::mac(MESSAGE, group.encode(shared))
+}
+
+pub trait KeyAgreementOracle<E> {
+    fn public_key(&self) -> &E;
+
+    /// Sends Bob `public` as our public key, and gets back his MAC
+    fn exchange(&self, public: &E) -> Result<[u8; 32]>;
+}
+
+/// Bob with a private key in some group, who doesn't check what he's sent
+pub struct Bob<G: Group> {
+    group: G,
+    private: BigInt,
+    public: G::Element,
+}
+
+impl<G: Group> Bob<G> {
+    /// Picks a random private key below the order of `generator`
+    pub fn new(group: G, generator: &G::Element, order: &BigInt, rng: &mut impl Rng) -> Self {
+        let private = rng.gen_bigint_range(&BigInt::one(), order);
+        let public = group.scale(generator, &private);
+        Self {
+            group,
+            private,
+            public,
+        }
+    }
+}
+
+impl<G: Group> KeyAgreementOracle<G::Element> for Bob<G> {
+    fn public_key(&self) -> &G::Element {
+        &self.public
+    }
+
+    fn exchange(&self, public: &G::Element) -> Result<[u8; 32]> {
+        Ok(mac(&self.group, &self.group.scale(public, &self.private)))
+    }
+}
+
+/// Puts `bob` on a local port, answering each connection on a thread of its own
+///
+/// The threads are detached on purpose: nothing stops a listener blocked in `accept`, so Bob stays
+/// up until the process exits, and a connection's thread ends when the other side hangs up.
+pub fn serve<G>(bob: Bob<G>) -> Result<SocketAddr>
+where
+    G: Group + Send + Sync + 'static,
+    G::Element: Send + Sync,
+{
+    let listener = TcpListener::bind(("127.0.0.1", 0))?;
+    let addr = listener.local_addr()?;
+    let bob = Arc::new(bob);
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let bob = bob.clone();
+            thread::spawn(move || {
+                if let Err(e) = answer(&bob, stream) {
+                    event!(Warn, "Bob dropped a connection", error = e);
+                }
+            });
+        }
+    });
+    Ok(addr)
+}
+
+/// One connection, from Bob's side
+fn answer<G: Group>(bob: &Bob<G>, mut stream: TcpStream) -> Result<()> {
+    send(&mut stream, &bob.group.encode(&bob.public))?;
+    while let Some(public) = receive(&mut stream)? {
+        let t = bob.exchange(&bob.group.decode(&public)?)?;
+        send(&mut stream, &t)?;
+    }
+    Ok(())
+}
+
+/// Bob at the other end of a TCP connection
+pub struct RemoteBob<G: Group> {
+    group: G,
+    stream: TcpStream,
+    public: G::Element,
+}
+
+impl<G: Group> RemoteBob<G> {
+    pub fn connect(addr: SocketAddr, group: G) -> Result<Self> {
+        let mut stream = TcpStream::connect(addr)?;
+        let public = receive(&mut stream)?.ok_or_else(|| anyhow!("Bob hung up"))?;
+        let public = group.decode(&public)?;
+        Ok(Self {
+            group,
+            stream,
+            public,
+        })
+    }
+}
+
+impl<G: Group> KeyAgreementOracle<G::Element> for RemoteBob<G> {
+    fn public_key(&self) -> &G::Element {
+        &self.public
+    }
+
+    fn exchange(&self, public: &G::Element) -> Result<[u8; 32]> {
+        let mut stream = &self.stream;
+        send(&mut stream, &self.group.encode(public))?;
+        let t = receive(&mut stream)?.ok_or_else(|| anyhow!("Bob hung up"))?;
+        t.try_into()
+            .map_err(|t: Vec<u8>| anyhow!("Expected a 32 byte MAC, got {} bytes", t.len()))
+    }
+}
+
+/// Bob, in this process unless `--arg bob=tcp` asks for him over a local TCP connection
+pub fn connect<G>(bob: Bob<G>) -> Result<Box<dyn KeyAgreementOracle<G::Element>>>
+where
+    G: Group + Clone + Send + Sync + 'static,
+    G::Element: Send + Sync,
+{
+    let how: Option<String> = crate::options::get().arg("bob")?;
+    match how.as_deref() {
+        None | Some("local") => Ok(Box::new(bob)),
+        Some("tcp") => {
+            let group = bob.group.clone();
+            let addr = serve(bob)?;
+            say!("Talking to Bob on {addr}");
+            Ok(Box::new(RemoteBob::connect(addr, group)?))
+        }
+        Some(other) => Err(anyhow!("Bob can be local or tcp, not {other:?}")),
+    }
+}
+
+/// The longest message either side will accept, well past any group element or MAC
+const MAX_MESSAGE: usize = 1 << 20;
+
+/// All in one write, so Nagle's algorithm doesn't hold the message back waiting for an ACK
+fn send(stream: &mut impl Write, message: &[u8]) -> Result<()> {
+    stream.write_all(&[&(message.len() as u32).to_be_bytes(), message].concat())?;
+    Ok(())
+}
+
+/// The next message, or None if the other side has hung up
+fn receive(stream: &mut impl Read) -> Result<Option<Vec<u8>>> {
+    let mut length = [0; 4];
+    match stream.read_exact(&mut length) {
+        Err(e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+        other => other?,
+    }
+    let length = u32::from_be_bytes(length) as usize;
+    if length > MAX_MESSAGE {
+        return Err(anyhow!(
+            "A {length} byte message is over the {MAX_MESSAGE} byte limit"
+        ));
+    }
+    let mut message = vec![0; length];
+    stream.read_exact(&mut message)?;
+    Ok(Some(message))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::group::ModP;
+
+    #[test]
+    fn local_and_remote() {
+        let group = ModP { p: 2311.into() };
+        let g = BigInt::from(3);
+        let bob = Bob::new(group.clone(), &g, &2310.into(), &mut rand::thread_rng());
+        let public = bob.public_key().clone();
+        let expected: Vec<_> = (1..20u32)
+            .map(|h| bob.exchange(&h.into()).unwrap())
+            .collect();
+
+        let addr = serve(bob).unwrap();
+        // Two connections at once, and a few exchanges on each
+        let remotes = [
+            RemoteBob::connect(addr, group.clone()).unwrap(),
+            RemoteBob::connect(addr, group.clone()).unwrap(),
+        ];
+        for remote in &remotes {
+            assert_eq!(remote.public_key(), &public);
+            for (h, t) in (1..20u32).zip(&expected) {
+                assert_eq!(&remote.exchange(&h.into()).unwrap(), t);
+            }
+        }
+
+        // Alice's side of an honest exchange
+        let a_priv = BigInt::from(1234);
+        let a_pub = group.scale(&g, &a_priv);
+        assert_eq!(
+            remotes[0].exchange(&a_pub).unwrap(),
+            mac(&group, &group.scale(&public, &a_priv))
+        );
+    }
+
+    #[test]
+    fn oversized_message() {
+        let mut wire = &[0xff, 0xff, 0xff, 0xff, 1, 2, 3][..];
+        assert!(receive(&mut wire).is_err());
+
+        let mut wire = Vec::new();
+        send(&mut wire, &[7; MAX_MESSAGE]).unwrap();
+        assert_eq!(receive(&mut &wire[..]).unwrap(), Some(vec![7; MAX_MESSAGE]));
+        assert_eq!(receive(&mut &wire[wire.len()..]).unwrap(), None);
+    }
+}
@@ -0,0 +1,584 @@
+//! Lattice basis reduction, and Coppersmith's method for small roots which is built on it
+//!
+//! [`lll`] is Nguyen and Stehlé's L² take on LLL: the basis and its Gram matrix are kept exactly,
+//! but the Gram-Schmidt coefficients are worked out afresh from the Gram matrix in floating point
+//! whenever they're needed, which is far quicker than keeping them exactly when the entries run to
+//! thousands of bits, and precise enough for the few dozen dimensions here. [`lll_exact`] is the
+//! textbook algorithm with everything kept exactly as fractions, which is too slow for challenge
+//! 62's lattice but leaves no doubt about small ones, so it's what [`lll`] is checked against.
+//!
+//! [`small_roots`] is Coppersmith's method as Howgrave-Graham put it: for a monic f of degree δ mod
+//! N with a root |x0| < X, every N^(m - i) x^j f(x)^i has x0 as a root mod N^m. LLL finds
+//! combinations of them with small enough coefficients that the root mod N^m is a root over the
+//! integers too, and then it's only a matter of solving.
+#![allow(dead_code)]
+
+use anyhow::{anyhow, Result};
+use num_bigint::BigInt;
+use num_integer::Integer;
+use num_traits::{One, Signed, ToPrimitive, Zero};
+
+use crate::poly;
+
+/// Largest m to try in Coppersmith's method, as the lattice has (m + 1) δ dimensions, and much
+/// past that the returns diminish while the time it takes doesn't
+const MAX_M: usize = 4;
+
+/// Lovász condition constant
+const DELTA: f64 = 0.99;
+/// How far off size reduction can be before it's redone, a little over a half to allow for
+/// rounding
+const ETA: f64 = 0.51;
+
+/// Reduces the rows of `basis` in place. The rows must be linearly independent.
+pub fn lll(basis: &mut [Vec<BigInt>]) {
+    let d = basis.len();
+    if d < 2 {
+        return;
+    }
+    let mut gram: Vec<Vec<BigInt>> = (0..d)
+        .map(|i| (0..d).map(|j| dot(&basis[i], &basis[j])).collect())
+        .collect();
+    let mut mu = vec![vec![Float::zero(); d]; d];
+    let mut r = vec![vec![Float::zero(); d]; d];
+    let mut s = vec![Float::zero(); d];
+    r[0][0] = Float::from(&gram[0][0]);
+
+    let mut k = 1;
+    while k < d {
+        // Size reduce b_k against the rows before it, going round again until the Gram-Schmidt
+        // coefficients worked out afresh (from the exact Gram matrix) say it's done
+        loop {
+            for j in 0..k {
+                let mut rkj = Float::from(&gram[k][j]);
+                for i in 0..j {
+                    rkj = rkj - mu[j][i] * r[k][i];
+                }
+                r[k][j] = rkj;
+                mu[k][j] = rkj / r[j][j];
+            }
+            s[0] = Float::from(&gram[k][k]);
+            for j in 1..=k {
+                s[j] = s[j - 1] - mu[k][j - 1] * r[k][j - 1];
+            }
+            r[k][k] = s[k];
+            if (0..k).all(|j| mu[k][j].abs() <= Float::from_f64(ETA)) {
+                break;
+            }
+
+            for j in (0..k).rev() {
+                let x = mu[k][j].round();
+                if x.is_zero() {
+                    continue;
+                }
+                let xf = Float::from(&x);
+                let (above, below) = mu.split_at_mut(k);
+                let mu_k = &mut below[0];
+                for (a, b) in mu_k.iter_mut().zip(&above[j]).take(j) {
+                    *a = *a - xf * *b;
+                }
+                mu_k[j] = mu_k[j] - xf;
+                let row_j = basis[j].clone();
+                for (a, b) in basis[k].iter_mut().zip(&row_j) {
+                    *a -= &x * b;
+                }
+                // <b_k - x b_j, b_k - x b_j> and <b_k - x b_j, b_i>, from what's there already
+                let gkk = &gram[k][k] - 2 * &x * &gram[k][j] + &x * &x * &gram[j][j];
+                for i in (0..d).filter(|i| *i != k) {
+                    let t = &x * &gram[j][i];
+                    gram[k][i] -= t;
+                    gram[i][k] = gram[k][i].clone();
+                }
+                gram[k][k] = gkk;
+            }
+        }
+
+        // Lovász: b*_k + μ_(k, k-1) b*_(k-1) mustn't be much shorter than b*_(k-1)
+        if Float::from_f64(DELTA) * r[k - 1][k - 1] <= s[k - 1] {
+            k += 1;
+        } else {
+            basis.swap(k - 1, k);
+            gram.swap(k - 1, k);
+            for row in gram.iter_mut() {
+                row.swap(k - 1, k);
+            }
+            k = (k - 1).max(1);
+            if k == 1 {
+                r[0][0] = Float::from(&gram[0][0]);
+            }
+        }
+    }
+}
+
+/// Reduces the rows of `basis` in place as [`lll`] does, but with the Gram-Schmidt coefficients
+/// kept as exact fractions, so that the result is size reduced to within a half and meets the
+/// Lovász condition with δ = 0.99 exactly. The rows must be linearly independent.
+pub fn lll_exact(basis: &mut [Vec<BigInt>]) {
+    let d = basis.len();
+    if d < 2 {
+        return;
+    }
+    let (mut lengths, mut mu) = gram_schmidt(basis);
+    let delta = Ratio::new(99.into(), 100.into());
+
+    let mut k = 1;
+    while k < d {
+        size_reduce(basis, &mut mu, k, k - 1);
+        let m = mu[k][k - 1].clone();
+        if lengths[k] < &(&delta - &(&m * &m)) * &lengths[k - 1] {
+            // Swap b_(k-1) and b_k, and update what depends on them as in Cohen's Algorithm 2.6.3
+            let b = &lengths[k] + &(&(&m * &m) * &lengths[k - 1]);
+            mu[k][k - 1] = &(&m * &lengths[k - 1]) / &b;
+            lengths[k] = &(&lengths[k - 1] * &lengths[k]) / &b;
+            lengths[k - 1] = b;
+            basis.swap(k - 1, k);
+            let (above, below) = mu.split_at_mut(k);
+            for (a, b) in above[k - 1].iter_mut().zip(below[0].iter_mut()).take(k - 1) {
+                std::mem::swap(a, b);
+            }
+            for i in k + 1..d {
+                let t = mu[i][k].clone();
+                mu[i][k] = &mu[i][k - 1] - &(&m * &t);
+                mu[i][k - 1] = &t + &(&mu[k][k - 1] * &mu[i][k]);
+            }
+            k = (k - 1).max(1);
+        } else {
+            for j in (0..k - 1).rev() {
+                size_reduce(basis, &mut mu, k, j);
+            }
+            k += 1;
+        }
+    }
+}
+
+/// Takes the nearest multiple of b_`j` from b_`k`, if that makes |μ_(k, j)| at most a half
+fn size_reduce(basis: &mut [Vec<BigInt>], mu: &mut [Vec<Ratio>], k: usize, j: usize) {
+    if mu[k][j].num.abs() * 2 <= mu[k][j].den {
+        return;
+    }
+    let q = mu[k][j].round();
+    let row_j = basis[j].clone();
+    for (a, b) in basis[k].iter_mut().zip(&row_j) {
+        *a -= &q * b;
+    }
+    let q = Ratio::from(q);
+    let (above, below) = mu.split_at_mut(k);
+    let mu_k = &mut below[0];
+    for (a, b) in mu_k.iter_mut().zip(&above[j]).take(j) {
+        *a = &*a - &(&q * b);
+    }
+    mu_k[j] = &mu_k[j] - &q;
+}
+
+/// The squared lengths of the Gram-Schmidt vectors b*_i, and the coefficients μ_(i, j) of b*_j in
+/// b_i, worked out exactly from the Gram matrix
+fn gram_schmidt(basis: &[Vec<BigInt>]) -> (Vec<Ratio>, Vec<Vec<Ratio>>) {
+    let d = basis.len();
+    let mut lengths: Vec<Ratio> = vec![];
+    let mut mu = vec![vec![Ratio::from(BigInt::zero()); d]; d];
+    for i in 0..d {
+        for j in 0..i {
+            let mut r = Ratio::from(dot(&basis[i], &basis[j]));
+            for l in 0..j {
+                r = &r - &(&(&mu[j][l] * &mu[i][l]) * &lengths[l]);
+            }
+            mu[i][j] = &r / &lengths[j];
+        }
+        let mut b = Ratio::from(dot(&basis[i], &basis[i]));
+        for l in 0..i {
+            b = &b - &(&(&mu[i][l] * &mu[i][l]) * &lengths[l]);
+        }
+        lengths.push(b);
+    }
+    (lengths, mu)
+}
+
+fn dot(a: &[BigInt], b: &[BigInt]) -> BigInt {
+    a.iter().zip(b).map(|(x, y)| x * y).sum()
+}
+
+/// A float with a 64-bit exponent, for Gram-Schmidt on vectors thousands of bits long: only the
+/// leading bits matter, but there are far too many of them for an f64's exponent
+#[derive(Clone, Copy, Debug)]
+struct Float {
+    /// In [0.5, 1) in size, or 0
+    mantissa: f64,
+    exponent: i64,
+}
+
+impl Float {
+    fn zero() -> Self {
+        Self {
+            mantissa: 0.0,
+            exponent: 0,
+        }
+    }
+
+    fn from_f64(x: f64) -> Self {
+        Self::normalised(x, 0)
+    }
+
+    fn normalised(mantissa: f64, exponent: i64) -> Self {
+        if mantissa == 0.0 || !mantissa.is_finite() {
+            return Self {
+                mantissa,
+                exponent: 0,
+            };
+        }
+        // IEEE 754's own exponent, less one to put the mantissa in [0.5, 1)
+        let e = ((mantissa.to_bits() >> 52) & 0x7ff) as i64 - 1022;
+        Self {
+            mantissa: mantissa * 2f64.powi(-e as i32),
+            exponent: exponent + e,
+        }
+    }
+
+    fn is_zero(&self) -> bool {
+        self.mantissa == 0.0
+    }
+
+    fn abs(self) -> Self {
+        Self {
+            mantissa: self.mantissa.abs(),
+            ..self
+        }
+    }
+
+    /// The nearest whole number
+    fn round(self) -> BigInt {
+        match self.exponent <= 53 {
+            true => BigInt::from((self.mantissa * 2f64.powi(self.exponent as i32)).round() as i64),
+            false => {
+                BigInt::from((self.mantissa * 2f64.powi(53)) as i64) << (self.exponent - 53) as u64
+            }
+        }
+    }
+}
+
+impl From<&BigInt> for Float {
+    fn from(x: &BigInt) -> Self {
+        let shift = x.bits().saturating_sub(64);
+        let top = (x >> shift).to_f64().unwrap();
+        Self::normalised(top, shift as i64)
+    }
+}
+
+impl std::ops::Mul for Float {
+    type Output = Self;
+    fn mul(self, other: Self) -> Self {
+        Self::normalised(
+            self.mantissa * other.mantissa,
+            self.exponent + other.exponent,
+        )
+    }
+}
+
+impl std::ops::Div for Float {
+    type Output = Self;
+    fn div(self, other: Self) -> Self {
+        Self::normalised(
+            self.mantissa / other.mantissa,
+            self.exponent - other.exponent,
+        )
+    }
+}
+
+impl std::ops::Sub for Float {
+    type Output = Self;
+    fn sub(self, other: Self) -> Self {
+        let other = Self {
+            mantissa: -other.mantissa,
+            ..other
+        };
+        if self.is_zero() {
+            return other;
+        }
+        if other.is_zero() {
+            return self;
+        }
+        let (big, small) = match self.exponent >= other.exponent {
+            true => (self, other),
+            false => (other, self),
+        };
+        let gap = (big.exponent - small.exponent).min(1000) as i32;
+        Self::normalised(
+            big.mantissa + small.mantissa * 2f64.powi(-gap),
+            big.exponent,
+        )
+    }
+}
+
+impl PartialEq for Float {
+    fn eq(&self, other: &Self) -> bool {
+        (*self - *other).is_zero()
+    }
+}
+
+impl PartialOrd for Float {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        (*self - *other).mantissa.partial_cmp(&0.0)
+    }
+}
+
+/// A fraction in lowest terms, with a positive denominator
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Ratio {
+    num: BigInt,
+    den: BigInt,
+}
+
+impl Ratio {
+    fn new(num: BigInt, den: BigInt) -> Self {
+        let g = num.gcd(&den);
+        let g = match den.is_negative() {
+            true => -g,
+            false => g,
+        };
+        Self {
+            num: num / &g,
+            den: den / &g,
+        }
+    }
+
+    /// The nearest whole number, rounding halves up
+    fn round(&self) -> BigInt {
+        // floor(x + 1/2)
+        let shifted: BigInt = &self.num * 2 + &self.den;
+        shifted.div_floor(&(&self.den * 2))
+    }
+}
+
+impl From<BigInt> for Ratio {
+    fn from(n: BigInt) -> Self {
+        Self {
+            num: n,
+            den: BigInt::one(),
+        }
+    }
+}
+
+impl std::ops::Add for &Ratio {
+    type Output = Ratio;
+    fn add(self, other: &Ratio) -> Ratio {
+        Ratio::new(
+            &self.num * &other.den + &other.num * &self.den,
+            &self.den * &other.den,
+        )
+    }
+}
+
+impl std::ops::Sub for &Ratio {
+    type Output = Ratio;
+    fn sub(self, other: &Ratio) -> Ratio {
+        Ratio::new(
+            &self.num * &other.den - &other.num * &self.den,
+            &self.den * &other.den,
+        )
+    }
+}
+
+impl std::ops::Mul for &Ratio {
+    type Output = Ratio;
+    fn mul(self, other: &Ratio) -> Ratio {
+        Ratio::new(&self.num * &other.num, &self.den * &other.den)
+    }
+}
+
+impl std::ops::Div for &Ratio {
+    type Output = Ratio;
+    fn div(self, other: &Ratio) -> Ratio {
+        Ratio::new(&self.num * &other.den, &self.den * &other.num)
+    }
+}
+
+impl PartialOrd for Ratio {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Ratio {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        (&self.num * &other.den).cmp(&(&other.num * &self.den))
+    }
+}
+
+/// The smallest m for which Howgrave-Graham's bound promises to find roots below `bound`, if any:
+/// LLL's first vector is at most 2^((n - 1) / 4) det^(1 / n) long, which has to be under
+/// N^m / sqrt(n) for its root mod N^m to be a root over the integers
+fn choose_m(degree: usize, modulus: &BigInt, bound: &BigInt) -> Option<usize> {
+    let (log_n, log_x) = (modulus.bits() as f64, bound.bits() as f64);
+    (1..=MAX_M).find(|m| {
+        let (m, n) = (*m as f64, ((m + 1) * degree) as f64);
+        let log_det = n * (n - 1.0) / 2.0 * log_x + degree as f64 * m * (m + 1.0) / 2.0 * log_n;
+        log_det / n + n.log2() / 2.0 + (n - 1.0) / 4.0 < m * log_n
+    })
+}
+
+/// The roots x0 of the monic `f` mod `modulus` with |x0| < `bound`, by Coppersmith's method
+pub fn small_roots(f: &[BigInt], modulus: &BigInt, bound: &BigInt) -> Result<Vec<BigInt>> {
+    let f = poly::reduce(f, modulus);
+    let delta = poly::degree(&f);
+    if delta == 0 || !f[delta].is_one() {
+        return Err(anyhow!("Polynomial must be monic, and not constant"));
+    }
+    let m = choose_m(delta, modulus, bound)
+        .ok_or_else(|| anyhow!("Roots up to {bound} are too big to find mod {modulus}"))?;
+    let n = (m + 1) * delta;
+
+    // N^(m - i) x^j f^i, with x scaled up by X, has degree i δ + j, so the basis is triangular
+    let powers: Vec<BigInt> = (0..n).map(|k| bound.pow(k as u32)).collect();
+    let mut basis: Vec<Vec<BigInt>> = (0..=m)
+        .flat_map(|i| (0..delta).map(move |j| (i, j)))
+        .map(|(i, j)| {
+            let g = poly::scale(
+                &poly::shift(&poly::pow(&f, i), j),
+                &modulus.pow((m - i) as u32),
+            );
+            let mut row: Vec<BigInt> = g.iter().zip(&powers).map(|(c, x)| c * x).collect();
+            row.resize(n, BigInt::zero());
+            row
+        })
+        .collect();
+    lll(&mut basis);
+
+    // Every row short enough has x0 as a root over the integers, and so does their gcd
+    let limit = modulus.pow(2 * m as u32);
+    let candidates: Vec<poly::Poly> = basis
+        .iter()
+        .filter(|row| dot(row, row) * n < limit)
+        .map(|row| row.iter().zip(&powers).map(|(c, x)| c / x).collect())
+        .collect();
+    let g = candidates
+        .iter()
+        .fold(vec![], |acc: poly::Poly, h| poly::gcd(&acc, h));
+    if g.is_empty() {
+        return Err(anyhow!("No vectors short enough in the reduced basis"));
+    }
+    let below = bound - 1u32;
+    Ok(poly::integer_roots(&g, &-&below, &below)
+        .into_iter()
+        .filter(|x| poly::eval(&f, x).mod_floor(modulus).is_zero())
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::Rng;
+
+    fn rows(rows: &[&[i64]]) -> Vec<Vec<BigInt>> {
+        rows.iter()
+            .map(|r| r.iter().map(|x| BigInt::from(*x)).collect())
+            .collect()
+    }
+
+    /// Whether `basis` is size reduced to within `eta` and meets the Lovász condition with `delta`,
+    /// going by its exact Gram-Schmidt coefficients
+    fn reduced(basis: &[Vec<BigInt>], eta: (i64, i64), delta: (i64, i64)) -> bool {
+        let (eta, delta) = (ratio(eta), ratio(delta));
+        let (lengths, mu) = gram_schmidt(basis);
+        (1..basis.len()).all(|i| {
+            let m = &mu[i][i - 1];
+            mu[i][..i]
+                .iter()
+                .all(|m| m.num.abs() * &eta.den <= &eta.num * &m.den)
+                && lengths[i] >= &(&delta - &(m * m)) * &lengths[i - 1]
+        })
+    }
+
+    fn ratio((num, den): (i64, i64)) -> Ratio {
+        Ratio::new(num.into(), den.into())
+    }
+
+    fn determinant(basis: &[Vec<BigInt>]) -> Ratio {
+        let (lengths, _) = gram_schmidt(basis);
+        lengths
+            .iter()
+            .fold(Ratio::from(BigInt::one()), |acc, b| &acc * b)
+    }
+
+    #[test]
+    fn reduction() {
+        let small = rows(&[&[1, 1, 1], &[-1, 0, 2], &[3, 5, 6]]);
+        // Challenge 62's example, doubled to clear the halves
+        let example = rows(&[
+            &[-4, 0, 4, 0],
+            &[1, -2, 0, 0],
+            &[-2, 0, -4, 1],
+            &[-2, 2, 2, 4],
+        ]);
+        let reduced_example = rows(&[
+            &[1, -2, 0, 0],
+            &[-2, 0, -4, 1],
+            &[-1, 0, 2, 4],
+            &[-3, -2, 4, 0],
+        ]);
+        for reduce in [lll, lll_exact] {
+            let mut basis = small.clone();
+            reduce(&mut basis);
+            assert_eq!(basis, rows(&[&[0, 1, 0], &[1, 0, 1], &[-1, 0, 2]]));
+            let mut basis = example.clone();
+            reduce(&mut basis);
+            assert_eq!(basis, reduced_example);
+        }
+
+        // For random bases, the exact reduction is reduced exactly, and the floating point one is
+        // as reduced as its slack allows when checked against exact arithmetic
+        let mut rng = rand::thread_rng();
+        for _ in 0..20 {
+            let n = rng.gen_range(2..8);
+            let basis: Vec<Vec<BigInt>> = (0..n)
+                .map(|i| {
+                    (0..n)
+                        .map(|j| match i == j {
+                            true => BigInt::from(rng.gen_range(1..1000)),
+                            false => BigInt::from(rng.gen_range(-1000..1000) * (i > j) as i64),
+                        })
+                        .collect()
+                })
+                .collect();
+            let (mut exact, mut float) = (basis.clone(), basis.clone());
+            lll_exact(&mut exact);
+            lll(&mut float);
+            // Same lattice, so the same determinant
+            assert_eq!(determinant(&exact), determinant(&basis));
+            assert_eq!(determinant(&float), determinant(&basis));
+            assert!(reduced(&exact, (1, 2), (99, 100)));
+            assert!(reduced(&float, (51, 100), (99, 100)));
+            // Each first vector is within 2^((n - 1) / 2) of the shortest in the lattice, and so of
+            // the other
+            assert!(dot(&float[0], &float[0]) <= dot(&exact[0], &exact[0]) << (n - 1));
+        }
+
+        assert_eq!(ratio((7, -2)).round(), BigInt::from(-3));
+        assert_eq!(ratio((5, 2)).round(), BigInt::from(3));
+        assert!(ratio((1, 3)) < ratio((1, 2)));
+    }
+
+    #[test]
+    fn coppersmith() {
+        // (x + 123456789)^3 - c mod a 512-bit n, with x a hundred bits or so
+        let n = crate::rsa::Params::new(512, 3)
+            .keypair(&mut rand::thread_rng())
+            .public
+            .modulus;
+        let x0 = BigInt::from(0xdeadbeef_u64).pow(3);
+        let b = BigInt::from(123456789);
+        let c = (&x0 + &b).modpow(&3.into(), &n);
+        let f = poly::add(&poly::pow(&[b.clone(), BigInt::one()], 3), &[-c]);
+        let bound = BigInt::one() << 100;
+        assert_eq!(
+            small_roots(&f, &n, &bound).unwrap(),
+            std::slice::from_ref(&x0)
+        );
+        // Negative ones too
+        let c = (&b - &x0).modpow(&3.into(), &n);
+        let f = poly::add(&poly::pow(&[b, BigInt::one()], 3), &[-c]);
+        assert_eq!(small_roots(&f, &n, &bound).unwrap(), [-x0]);
+
+        // Too big a bound, and not monic
+        assert!(small_roots(&f, &n, &(BigInt::one() << 200)).is_err());
+        assert!(small_roots(&poly::scale(&f, &2.into()), &n, &bound).is_err());
+    }
+}
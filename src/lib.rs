@@ -0,0 +1,54 @@
+//! Solutions to the cryptopals challenges, along with the primitives they're built on: the hash
+//! functions in [`hash`], written from scratch so that attacks can get at their insides, stream
+//! ciphers in [`stream`], Diffie-Hellman in [`dh`], the elliptic curves of set 8 in [`ec`], and the
+//! odds and ends in [`utils`] (XOR, padding, base64, intervals and so on). The `cryptopals` binary
+//! is just a command line over [`runner`].
+
+// Module docs are the verbatim challenge texts, so leave their indentation alone
+#![allow(clippy::doc_overindented_list_items, clippy::doc_lazy_continuation)]
+
+pub mod aes;
+pub mod bench;
+pub mod bonus;
+pub mod continued_fraction;
+pub mod data;
+pub mod der;
+pub mod dh;
+pub mod dsa;
+pub mod ecdsa;
+pub mod fxhash;
+pub mod gcm;
+pub mod gf2;
+pub mod group;
+pub mod hash;
+pub mod key_agreement;
+pub mod lattice;
+pub mod mt;
+pub mod options;
+pub mod poly;
+pub mod progress;
+pub mod report;
+pub mod rsa;
+pub mod runner;
+pub mod scoring;
+pub mod set1;
+pub mod set2;
+pub mod set3;
+pub mod set4;
+pub mod set5;
+pub mod set6;
+pub mod set7;
+pub mod set8;
+pub mod srp;
+pub mod stream;
+pub mod tool;
+pub mod trace;
+pub mod utils;
+
+/// The elliptic curves from set 8: short Weierstrass curves and their points, Montgomery curves
+/// with the single-coordinate ladder, and the challenges' curve in both forms
+pub mod ec {
+    pub use crate::set8::challenge59::{Curve, CurveParams, Point};
+    pub use crate::set8::challenge60::MontgomeryCurve;
+    pub use crate::set8::params;
+}
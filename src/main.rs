@@ -1,25 +1,101 @@
-use anyhow::{anyhow, Result};
+use anyhow::Result;
 const HELP: &str = "
 USAGE:
     -c [CHALLENGE_NUMBER]
+    --all                Runs every challenge in order, carrying on past failures, and sums
+                         up how each went
+    -s, --set [SET]      Runs every challenge in one set, the same way as --all
+    --list               Lists every challenge with its set, title, whether it's done yet, and
+                         whether it's fast or slow
+    crack-xor [FILE]     Breaks repeating-key XOR on FILE
+    bonus [NAME]         Runs an attack from outside the challenges: fermat,
+                         nonce-reuse, wiener
+    bench [NAME]         Times the kernels the slower attacks spend their time in, or just
+                         one of: modpow, scale, ladder, gf-mul, lll, md4-massage
+    tool [OPERATION] [ARGS]
+                         Runs one primitive on its own, with hex bytes in and out:
+                           hex-to-b64 HEX, b64-to-hex BASE64, xor KEY DATA, crack-byte DATA,
+                           ecb-encrypt KEY DATA, ecb-decrypt KEY DATA, cbc-encrypt KEY DATA,
+                           cbc-decrypt KEY DATA, sha1 DATA, sha256 DATA or md4 DATA
+                         DATA left off is read from stdin
+    --bench -c [N]       Runs challenge N over and over, and gives the least, mean and most time it
+                         took and of each thing it counts, like oracle queries; bench -c [N] is
+                         the same
+    --repeat [K] -c [N]  Runs challenge N K times, carrying on past failures, and gives how many
+                         passed, the seeds of those that didn't, and the same as --bench
 
 FLAGS:
     -h, --help           Prints help information
+    --alternatives       Shows runner-up candidates when a best guess is ambiguous
+    --interactive        Asks for corrections to attacks which can take them (e.g. 19, 20)
+    --mac [ALGORITHM]    MAC the timing attacks (31, 32) go after: hmac-sha1, hmac-sha256, sha1
+                         or sha256 [default: hmac-sha1]
+    --arg [NAME=VALUE]   Sets something only some challenges use, may be repeated:
+                           rsa_bits    RSA modulus size, for 39-42 and 46-48
+                           e           RSA public exponent, where the attack allows it
+    --data-dir [DIR]     Where to cache downloaded challenge files [default: $CRYPTOPALS_DATA_DIR,
+                         or ~/.cache/cryptopals]
+    --progress [STYLE]   How slower attacks show progress: bars, lines (a line on stderr every few
+                         seconds) or none [default: bars on a terminal, or else lines]
+    --no-progress        The same as --progress none
+    --format [FORMAT]    text, or json for a JSON record of each challenge on stdout, with the
+                         challenges' own output moved to stderr [default: text]
+    --seed [SEED]        Seeds each challenge's randomness, to repeat a run exactly; a failing
+                         challenge says which seed it had
+    -j, --jobs [N]       How many challenges --all and -s run at once, each one's output printed
+                         when it finishes; the timing attacks still run alone [default: 1]
+    -v, -vv, -vvv        Logs what the attacks find along the way, at info, debug or trace level,
+                         unless CRYPTOPALS_LOG says otherwise
+    -q, --quiet          Prints nothing from the challenges themselves, only failures, the summary
+                         and any JSON records
+    --quick              Gives the heavier challenges (47, 48, 58, 64) smaller moduli, groups and
+                         messages, to check they still work without the wait; --arg still wins
+    --out [DIR]          Writes what each challenge recovers to files in DIR/challengeNN, raw
+                         bytes as .bin and text and numbers as .txt, with report.json
+
+BENCH FLAGS:
+    --iters [K]          How many times --bench runs the challenge [default: 10]
+
+TOOL FLAGS:
+    --text               Plaintexts are text rather than hex, going in or coming out
+    --iv [IV]            Hex IV for cbc-encrypt and cbc-decrypt [default: all zeros]
+
+CRACK-XOR FLAGS:
+    --base64             FILE is base64 encoded, rather than raw bytes
+    --min-key [LENGTH]   Shortest key length to try [default: 1]
+    --max-key [LENGTH]   Longest key length to try [default: 40]
 ";
 
-mod dh;
-mod set1;
-mod set2;
-mod set3;
-mod set4;
-mod set5;
-mod set6;
-mod set7;
-mod set8;
-mod stream;
-mod utils;
-
-fn parse_args() -> Result<u64, pico_args::Error> {
+use cryptopals::{bench, bonus, options, progress, runner, tool, trace, utils};
+
+enum Command {
+    Challenge(u64),
+    All,
+    Set(u64),
+    List,
+    CrackXor {
+        file: String,
+        base64: bool,
+        key_len_range: std::ops::Range<usize>,
+    },
+    Bonus(String),
+    Bench(Option<String>),
+    Tool {
+        operation: String,
+        args: Vec<String>,
+        flags: tool::Flags,
+    },
+    BenchChallenge {
+        challenge: u64,
+        iters: u32,
+    },
+    Repeat {
+        challenge: u64,
+        times: u32,
+    },
+}
+
+fn parse_args() -> Result<(Command, options::Options), pico_args::Error> {
     let mut pargs = pico_args::Arguments::from_env();
 
     if pargs.contains(["-h", "--help"]) {
@@ -27,24 +103,145 @@ fn parse_args() -> Result<u64, pico_args::Error> {
         std::process::exit(0);
     }
 
-    let challenge = pargs.value_from_str("-c")?;
+    let mut verbose = 0;
+    for (flag, n) in [("-vvv", 3), ("-vv", 2), ("-v", 1)] {
+        while pargs.contains(flag) {
+            verbose += n;
+        }
+    }
+    trace::set_verbosity(verbose);
+
+    let options = options::Options {
+        alternatives: pargs.contains("--alternatives"),
+        interactive: pargs.contains("--interactive"),
+        mac: pargs.opt_value_from_str("--mac")?.unwrap_or_default(),
+        data_dir: pargs.opt_value_from_str("--data-dir")?,
+        progress: match pargs.contains("--no-progress") {
+            true => progress::Style::None,
+            false => pargs.opt_value_from_str("--progress")?.unwrap_or_default(),
+        },
+        format: pargs.opt_value_from_str("--format")?.unwrap_or_default(),
+        seed: pargs.opt_value_from_str("--seed")?,
+        jobs: pargs.opt_value_from_str(["-j", "--jobs"])?.unwrap_or(1),
+        quiet: pargs.contains(["-q", "--quiet"]),
+        profile: match pargs.contains("--quick") {
+            true => options::Profile::Quick,
+            false => options::Profile::Full,
+        },
+        out: pargs.opt_value_from_str("--out")?,
+        args: pargs.values_from_fn("--arg", options::parse_arg)?,
+    };
+    let command = match pargs.subcommand()?.as_deref() {
+        Some("crack-xor") => {
+            let base64 = pargs.contains("--base64");
+            let min_key: usize = pargs.opt_value_from_str("--min-key")?.unwrap_or(1);
+            let max_key: usize = pargs.opt_value_from_str("--max-key")?.unwrap_or(40);
+            Command::CrackXor {
+                file: pargs.free_from_str()?,
+                base64,
+                key_len_range: min_key..max_key + 1,
+            }
+        }
+        Some("bonus") => Command::Bonus(pargs.free_from_str()?),
+        Some("bench") => match pargs.opt_value_from_str("-c")? {
+            Some(challenge) => Command::BenchChallenge {
+                challenge,
+                iters: pargs.opt_value_from_str("--iters")?.unwrap_or(10),
+            },
+            None => Command::Bench(pargs.opt_free_from_str()?),
+        },
+        Some("tool") => {
+            let flags = tool::Flags {
+                text: pargs.contains("--text"),
+                iv: pargs.opt_value_from_str("--iv")?,
+            };
+            let operation = pargs.free_from_str()?;
+            let args = pargs.finish().into_iter();
+            let command = Command::Tool {
+                operation,
+                args: args.map(|a| a.to_string_lossy().into_owned()).collect(),
+                flags,
+            };
+            return Ok((command, options));
+        }
+        Some(other) => {
+            return Err(pico_args::Error::ArgumentParsingFailed {
+                cause: format!("unknown subcommand {other}"),
+            })
+        }
+        None if pargs.contains("--all") => Command::All,
+        None if pargs.contains("--list") => Command::List,
+        None if pargs.contains("--bench") => Command::BenchChallenge {
+            challenge: pargs.value_from_str("-c")?,
+            iters: pargs.opt_value_from_str("--iters")?.unwrap_or(10),
+        },
+        None => match (
+            pargs.opt_value_from_str("--repeat")?,
+            pargs.opt_value_from_str(["-s", "--set"])?,
+        ) {
+            (Some(times), _) => Command::Repeat {
+                challenge: pargs.value_from_str("-c")?,
+                times,
+            },
+            (None, Some(s)) => Command::Set(s),
+            (None, None) => Command::Challenge(pargs.value_from_str("-c")?),
+        },
+    };
 
-    Ok(challenge)
+    let unused = pargs.finish();
+    if !unused.is_empty() {
+        return Err(pico_args::Error::ArgumentParsingFailed {
+            cause: format!("unexpected arguments {unused:?}"),
+        });
+    }
+
+    Ok((command, options))
 }
 
-fn main() -> Result<()> {
-    let challenge = parse_args()?;
-
-    match challenge {
-        c @ 1..=8 => set1::run(c),
-        c @ 9..=16 => set2::run(c),
-        c @ 17..=24 => set3::run(c),
-        c @ 25..=32 => set4::run(c),
-        c @ 33..=40 => set5::run(c),
-        c @ 41..=48 => set6::run(c),
-        c @ 49..=56 => set7::run(c),
-        c @ 57..=66 => set8::run(c),
-        _ => Err(anyhow!("Invalid challenge number")),
-    }?;
+fn crack_xor(file: &str, base64: bool, key_len_range: std::ops::Range<usize>) -> Result<()> {
+    let (key, plaintext) = match base64 {
+        true => {
+            let reader = utils::base64_reader(std::fs::File::open(file)?);
+            utils::crack_repeating_xor(reader, key_len_range)?
+        }
+        false => utils::crack_repeating_xor(std::fs::File::open(file)?, key_len_range)?,
+    };
+    println!("Key: {:?}", String::from_utf8_lossy(&key));
+    println!("{}", String::from_utf8_lossy(&plaintext));
     Ok(())
 }
+
+fn main() -> Result<()> {
+    let (command, options) = parse_args()?;
+    options::set(options);
+
+    match command {
+        Command::Challenge(c) => runner::run_one(c),
+        Command::All => runner::run_all(runner::challenges()).map(drop),
+        Command::Set(s) => {
+            let set = runner::set(s)?.iter().filter(|c| c.implemented());
+            runner::run_all(set.map(|c| c.number)).map(drop)
+        }
+        Command::List => {
+            runner::list();
+            Ok(())
+        }
+        Command::CrackXor {
+            file,
+            base64,
+            key_len_range,
+        } => crack_xor(&file, base64, key_len_range),
+        Command::Bonus(name) => bonus::run(&name),
+        Command::Bench(name) => bench::run(name.as_deref()),
+        Command::Tool {
+            operation,
+            args,
+            flags,
+        } => {
+            println!("{}", tool::run(&operation, &args, &flags)?);
+            Ok(())
+        }
+        Command::BenchChallenge { challenge, iters } => bench::challenge(challenge, iters),
+        Command::Repeat { challenge, times } => bench::repeat(challenge, times),
+    }
+}
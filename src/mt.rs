@@ -0,0 +1,276 @@
+//! MT19937 Mersenne Twister, and its 64-bit sibling MT19937-64
+//!
+//! Both implement [`RngCore`] and [`SeedableRng`], so they can stand in for any other RNG, as well
+//! as being iterators over their raw outputs.
+#![allow(dead_code)]
+
+use rand_core::{RngCore, SeedableRng};
+
+// For MT19937:
+// (w,n,m,r) = (32,624,397,31)
+// a = 9908B0DF_{32}
+// (u,d) = (11, FFFFFFFF_{32})
+// (s,b) = (7, 9D2C5680_{32})
+// (t,c) = (15, EFC60000_{32})
+// l = 18
+
+const W: u32 = 32;
+pub const N: usize = 624;
+pub const M: usize = 397;
+const R: u32 = 31;
+pub const A: u32 = 0x9908B0DF;
+
+pub const U: u32 = 11;
+pub const D: u32 = 0xFFFFFFFF;
+pub const S: u32 = 7;
+pub const B: u32 = 0x9D2C5680;
+pub const T: u32 = 15;
+pub const C: u32 = 0xEFC60000;
+pub const L: u32 = 18;
+const F: u32 = 1812433253;
+
+pub const LOWER_MASK: u32 = (1 << R) - 1;
+// In this case lowest W bits is all of them
+pub const UPPER_MASK: u32 = !LOWER_MASK;
+
+#[derive(Clone)]
+pub struct Mt {
+    state: [u32; N],
+    index: usize,
+}
+
+impl Mt {
+    pub fn seed(seed: u32) -> Mt {
+        let mut state = [0; N];
+        state[0] = seed;
+
+        for i in 1..N {
+            state[i] = F
+                .wrapping_mul(state[i - 1] ^ (state[i - 1] >> (W - 2)))
+                .wrapping_add(i as u32);
+        }
+
+        Mt { state, index: N }
+    }
+
+    /// Picks up from an internal state, as recovered from 624 consecutive (untempered) outputs,
+    /// so the next output follows on from the last of them
+    pub fn from_state(state: [u32; N]) -> Mt {
+        Mt { state, index: N }
+    }
+
+    fn twist(&mut self) {
+        for i in 0..N {
+            let x = (self.state[i] & UPPER_MASK) | (self.state[(i + 1) % N] & LOWER_MASK);
+            let mut xa = x >> 1;
+            if !x.is_multiple_of(2) {
+                xa ^= A;
+            }
+            self.state[i] = self.state[(i + M) % N] ^ xa;
+        }
+
+        self.index = 0;
+    }
+}
+
+/// Scrambles a word of the state into an output
+pub fn temper(mut y: u32) -> u32 {
+    y ^= (y >> U) & D;
+    y ^= (y << S) & B;
+    y ^= (y << T) & C;
+    y ^ (y >> L)
+}
+
+impl Iterator for Mt {
+    type Item = u32;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        Some(self.next_u32())
+    }
+}
+
+impl RngCore for Mt {
+    fn next_u32(&mut self) -> u32 {
+        if self.index == N {
+            self.twist();
+        }
+        let y = temper(self.state[self.index]);
+        self.index += 1;
+        y
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        rand_core::impls::next_u64_via_u32(self)
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        rand_core::impls::fill_bytes_via_next(self, dest)
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand_core::Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+impl SeedableRng for Mt {
+    type Seed = [u8; 4];
+
+    fn from_seed(seed: Self::Seed) -> Self {
+        Mt::seed(u32::from_le_bytes(seed))
+    }
+
+    /// MT19937 only takes a 32-bit seed, so this is just the bottom 32 bits, rather than the
+    /// usual mixing, so that it agrees with [`Mt::seed`]
+    fn seed_from_u64(state: u64) -> Self {
+        Mt::seed(state as u32)
+    }
+}
+
+// For MT19937-64:
+// (w,n,m,r) = (64,312,156,31)
+// a = B5026F5AA96619E9_{16}
+// (u,d) = (29, 5555555555555555_{16})
+// (s,b) = (17, 71D67FFFEDA60000_{16})
+// (t,c) = (37, FFF7EEE000000000_{16})
+// l = 43
+
+pub const N64: usize = 312;
+const M64: usize = 156;
+const A64: u64 = 0xB5026F5AA96619E9;
+
+pub const U64: u32 = 29;
+pub const D64: u64 = 0x5555555555555555;
+pub const S64: u32 = 17;
+pub const B64: u64 = 0x71D67FFFEDA60000;
+pub const T64: u32 = 37;
+pub const C64: u64 = 0xFFF7EEE000000000;
+pub const L64: u32 = 43;
+const F64: u64 = 6364136223846793005;
+
+const LOWER_MASK64: u64 = (1 << R) - 1;
+const UPPER_MASK64: u64 = !LOWER_MASK64;
+
+#[derive(Clone)]
+pub struct Mt64 {
+    state: [u64; N64],
+    index: usize,
+}
+
+impl Mt64 {
+    pub fn seed(seed: u64) -> Mt64 {
+        let mut state = [0; N64];
+        state[0] = seed;
+
+        for i in 1..N64 {
+            state[i] = F64
+                .wrapping_mul(state[i - 1] ^ (state[i - 1] >> 62))
+                .wrapping_add(i as u64);
+        }
+
+        Mt64 { state, index: N64 }
+    }
+
+    /// Picks up from an internal state, as recovered from 312 consecutive (untempered) outputs,
+    /// so the next output follows on from the last of them
+    pub fn from_state(state: [u64; N64]) -> Mt64 {
+        Mt64 { state, index: N64 }
+    }
+
+    fn twist(&mut self) {
+        for i in 0..N64 {
+            let x = (self.state[i] & UPPER_MASK64) | (self.state[(i + 1) % N64] & LOWER_MASK64);
+            let mut xa = x >> 1;
+            if !x.is_multiple_of(2) {
+                xa ^= A64;
+            }
+            self.state[i] = self.state[(i + M64) % N64] ^ xa;
+        }
+
+        self.index = 0;
+    }
+}
+
+pub fn temper64(mut y: u64) -> u64 {
+    y ^= (y >> U64) & D64;
+    y ^= (y << S64) & B64;
+    y ^= (y << T64) & C64;
+    y ^ (y >> L64)
+}
+
+impl Iterator for Mt64 {
+    type Item = u64;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        Some(self.next_u64())
+    }
+}
+
+impl RngCore for Mt64 {
+    fn next_u32(&mut self) -> u32 {
+        self.next_u64() as u32
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        if self.index == N64 {
+            self.twist();
+        }
+        let y = temper64(self.state[self.index]);
+        self.index += 1;
+        y
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        rand_core::impls::fill_bytes_via_next(self, dest)
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand_core::Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+impl SeedableRng for Mt64 {
+    type Seed = [u8; 8];
+
+    fn from_seed(seed: Self::Seed) -> Self {
+        Mt64::seed(u64::from_le_bytes(seed))
+    }
+
+    fn seed_from_u64(state: u64) -> Self {
+        Mt64::seed(state)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::Rng;
+
+    #[test]
+    fn reference_outputs() {
+        // From the reference implementation, and the C++ standard's check on the 10000th output
+        let mut mt = Mt::seed(5489);
+        assert_eq!(mt.next(), Some(3499211612));
+        assert_eq!(mt.nth(9998), Some(4123659995));
+
+        assert_eq!(Mt::seed(0).next(), Some(2357136044));
+
+        let mut mt = Mt64::seed(5489);
+        assert_eq!(mt.next(), Some(14514284786278117030));
+        assert_eq!(mt.nth(9998), Some(9981545732273789042));
+    }
+
+    #[test]
+    fn as_an_rng() {
+        let mut mt = Mt::seed_from_u64(5489);
+        let x: u32 = mt.gen();
+        assert_eq!(x, 3499211612);
+
+        let mut mt = Mt::from_seed(5489_u32.to_le_bytes());
+        let mut bytes = [0; 6];
+        mt.fill_bytes(&mut bytes);
+        assert_eq!(bytes[..4], 3499211612_u32.to_le_bytes());
+        assert!((0..100).all(|_| mt.gen_range(0..10) < 10));
+    }
+}
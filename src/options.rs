@@ -0,0 +1,125 @@
+//! Options set on the command line which individual challenges may want to look at
+
+use std::fmt::Display;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::OnceLock;
+
+use anyhow::{anyhow, Result};
+
+use crate::progress::Style;
+use crate::report::Format;
+use crate::set4::challenge31::MacAlgorithm;
+
+#[derive(Debug, Default)]
+pub struct Options {
+    /// Show the runner-up candidates when an attack's best guess isn't clear-cut
+    pub alternatives: bool,
+    /// Let attacks which can be steered by hand ask for corrections on stdin
+    pub interactive: bool,
+    /// MAC behind the leaky comparison in the timing attacks
+    pub mac: MacAlgorithm,
+    /// Where to cache challenge files which have to be downloaded
+    pub data_dir: Option<PathBuf>,
+    /// How the slower attacks show how far they've got
+    pub progress: Style,
+    /// Whether the runner prints what each challenge prints, or a JSON record of it
+    pub format: Format,
+    /// What to seed each challenge's randomness with, rather than a fresh seed every time
+    pub seed: Option<u64>,
+    /// How many challenges `--all` and `-s` run at once
+    pub jobs: usize,
+    /// Where to write what each challenge recovered, a directory per challenge
+    pub out: Option<PathBuf>,
+    /// Keep the challenges' commentary to themselves, leaving just failures and the summary
+    pub quiet: bool,
+    /// Whether the heavier challenges set themselves the problem they ask for, or a smaller one
+    pub profile: Profile,
+    /// Settings only a few challenges care about, from `--arg name=value`
+    pub args: Vec<(String, String)>,
+}
+
+impl Options {
+    /// Looks up and parses `--arg name=value`, taking the last one if it was given more than once
+    pub fn arg<T: FromStr>(&self, name: &str) -> Result<Option<T>>
+    where
+        T::Err: Display,
+    {
+        self.args
+            .iter()
+            .rev()
+            .find(|(n, _)| n == name)
+            .map(|(_, value)| {
+                value
+                    .parse()
+                    .map_err(|e| anyhow!("Invalid value {value:?} for --arg {name}: {e}"))
+            })
+            .transpose()
+    }
+}
+
+/// How big a problem the heavier challenges (47, 48, 58, 64) set themselves
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Profile {
+    /// The sizes the challenges ask for
+    #[default]
+    Full,
+    /// Smaller moduli, groups and messages, from `--quick`, to check the attacks still work
+    /// without waiting on them
+    Quick,
+}
+
+impl Profile {
+    /// `full` normally, or `quick` under `--quick`
+    pub fn pick<T>(self, full: T, quick: T) -> T {
+        match self {
+            Profile::Full => full,
+            Profile::Quick => quick,
+        }
+    }
+}
+
+/// Splits up the `name=value` of an `--arg`
+pub fn parse_arg(arg: &str) -> Result<(String, String)> {
+    match arg.split_once('=') {
+        Some((name, value)) if !name.is_empty() => Ok((name.to_string(), value.to_string())),
+        _ => Err(anyhow!("Expected name=value, got {arg:?}")),
+    }
+}
+
+static OPTIONS: OnceLock<Options> = OnceLock::new();
+
+/// Sets the options for the rest of the run, only the first call has any effect
+pub fn set(options: Options) {
+    let _ = OPTIONS.set(options);
+}
+
+/// Gets the options for this run, falling back to the defaults if none were set (e.g. in tests)
+pub fn get() -> &'static Options {
+    OPTIONS.get_or_init(Options::default)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn args() {
+        let options = Options {
+            args: ["rsa_bits=1024", "e=3", "e=65537", "name=a=b"]
+                .iter()
+                .map(|a| parse_arg(a).unwrap())
+                .collect(),
+            ..Default::default()
+        };
+        assert_eq!(options.arg::<u64>("rsa_bits").unwrap(), Some(1024));
+        assert_eq!(options.arg::<u64>("e").unwrap(), Some(65537));
+        assert_eq!(options.arg::<String>("name").unwrap(), Some("a=b".into()));
+        assert_eq!(options.arg::<u64>("missing").unwrap(), None);
+        assert!(options.arg::<u8>("rsa_bits").is_err());
+        assert!(parse_arg("no_value").is_err());
+        assert!(parse_arg("=3").is_err());
+        assert_eq!(Profile::default().pick(768, 512), 768);
+        assert_eq!(Profile::Quick.pick(768, 512), 512);
+    }
+}
@@ -0,0 +1,239 @@
+//! Polynomials with whole-number coefficients, as a `Vec<BigInt>` lowest degree first
+//!
+//! Just enough of them for Coppersmith's method: arithmetic, reduction of the coefficients mod
+//! some n, and gcds over the rationals (done with primitive pseudo-remainders, so it never needs
+//! fractions).
+#![allow(dead_code)]
+
+use itertools::Itertools;
+use num_bigint::BigInt;
+use num_integer::Integer;
+use num_traits::{One, Signed, Zero};
+
+pub type Poly = Vec<BigInt>;
+
+/// Degree, counting the zero polynomial as degree 0
+pub fn degree(f: &[BigInt]) -> usize {
+    f.iter().rposition(|c| !c.is_zero()).unwrap_or(0)
+}
+
+/// Drops leading zero coefficients, leaving the zero polynomial empty
+pub fn trim(mut f: Poly) -> Poly {
+    while f.last().is_some_and(|c| c.is_zero()) {
+        f.pop();
+    }
+    f
+}
+
+pub fn add(f: &[BigInt], g: &[BigInt]) -> Poly {
+    let mut sum = vec![BigInt::zero(); f.len().max(g.len())];
+    for (i, c) in f.iter().enumerate() {
+        sum[i] += c;
+    }
+    for (i, c) in g.iter().enumerate() {
+        sum[i] += c;
+    }
+    trim(sum)
+}
+
+pub fn mul(f: &[BigInt], g: &[BigInt]) -> Poly {
+    if f.is_empty() || g.is_empty() {
+        return vec![];
+    }
+    let mut product = vec![BigInt::zero(); f.len() + g.len() - 1];
+    for (i, a) in f.iter().enumerate() {
+        for (j, b) in g.iter().enumerate() {
+            product[i + j] += a * b;
+        }
+    }
+    trim(product)
+}
+
+pub fn scale(f: &[BigInt], k: &BigInt) -> Poly {
+    trim(f.iter().map(|c| c * k).collect())
+}
+
+/// x^k f
+pub fn shift(f: &[BigInt], k: usize) -> Poly {
+    match f.is_empty() {
+        true => vec![],
+        false => [vec![BigInt::zero(); k], f.to_vec()].concat(),
+    }
+}
+
+pub fn pow(f: &[BigInt], k: usize) -> Poly {
+    (0..k).fold(vec![BigInt::one()], |acc, _| mul(&acc, f))
+}
+
+/// Every coefficient reduced to [0, n)
+pub fn reduce(f: &[BigInt], n: &BigInt) -> Poly {
+    trim(f.iter().map(|c| c.mod_floor(n)).collect())
+}
+
+/// f(x), by Horner's rule
+pub fn eval(f: &[BigInt], x: &BigInt) -> BigInt {
+    f.iter().rev().fold(BigInt::zero(), |acc, c| acc * x + c)
+}
+
+/// The derivative
+pub fn derivative(f: &[BigInt]) -> Poly {
+    trim(f.iter().enumerate().skip(1).map(|(i, c)| c * i).collect())
+}
+
+/// The whole numbers in [lo, hi] which are roots of f
+///
+/// Between the real roots of f' f is monotonic, so the search finds those first (or rather their
+/// floors, which is as close as whole numbers get) and then bisects each stretch in between for a
+/// change of sign.
+pub fn integer_roots(f: &[BigInt], lo: &BigInt, hi: &BigInt) -> Vec<BigInt> {
+    root_floors(f, lo, hi)
+        .into_iter()
+        .flat_map(|t| [t.clone(), t + 1])
+        .filter(|t| t >= lo && t <= hi && eval(f, t).is_zero())
+        .dedup()
+        .collect()
+}
+
+/// floor(r) for each real root r of f in [lo, hi], give or take the odd extra
+fn root_floors(f: &[BigInt], lo: &BigInt, hi: &BigInt) -> Vec<BigInt> {
+    let f = trim(f.to_vec());
+    if degree(&f) == 0 {
+        return vec![];
+    }
+    // Stretches between the critical points, on which f only goes one way
+    let mut ends = vec![lo.clone(), hi.clone()];
+    for c in root_floors(&derivative(&f), lo, hi) {
+        ends.push(c.clone());
+        ends.push(c + 1);
+    }
+    ends.retain(|t| t >= lo && t <= hi);
+    ends.sort();
+    ends.dedup();
+
+    let sign = |t: &BigInt| eval(&f, t).signum();
+    let mut floors = vec![];
+    for pair in ends.windows(2) {
+        let (mut a, mut b) = (pair[0].clone(), pair[1].clone());
+        let start = sign(&a);
+        if start.is_zero() {
+            floors.push(a.clone());
+            continue;
+        }
+        if sign(&b) != -&start {
+            continue;
+        }
+        // Keep f(a) on the starting side, and f(b) off it
+        while &b - &a > BigInt::one() {
+            let mid: BigInt = (&a + &b) >> 1;
+            match sign(&mid) == start {
+                true => a = mid,
+                false => b = mid,
+            }
+        }
+        floors.push(a);
+    }
+    floors.extend(ends.last().filter(|t| sign(t).is_zero()).cloned());
+    floors
+}
+
+/// f divided by the gcd of its coefficients, and made to lead with a positive one
+pub fn primitive(f: &[BigInt]) -> Poly {
+    let f = trim(f.to_vec());
+    let Some(lead) = f.last() else {
+        return f;
+    };
+    let content = f.iter().fold(BigInt::zero(), |acc, c| acc.gcd(c));
+    let content = match lead.is_negative() {
+        true => -content,
+        false => content,
+    };
+    f.iter().map(|c| c / &content).collect()
+}
+
+/// What's left of lc(g)^k f after taking away multiples of g, for a k big enough that it all
+/// stays whole numbers
+fn pseudo_remainder(f: &[BigInt], g: &[BigInt]) -> Poly {
+    let mut r = trim(f.to_vec());
+    let dg = degree(g);
+    let lead = &g[dg];
+    while !r.is_empty() && degree(&r) >= dg {
+        let dr = degree(&r);
+        let factor = shift(&scale(g, &r[dr]), dr - dg);
+        r = add(&scale(&r, lead), &scale(&factor, &-BigInt::one()));
+    }
+    r
+}
+
+/// The gcd of f and g over the rationals, scaled to be primitive with a positive leading
+/// coefficient
+pub fn gcd(f: &[BigInt], g: &[BigInt]) -> Poly {
+    let (mut a, mut b) = (primitive(f), primitive(g));
+    while !b.is_empty() {
+        let r = primitive(&pseudo_remainder(&a, &b));
+        (a, b) = (b, r);
+    }
+    a
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn p(coefficients: &[i64]) -> Poly {
+        trim(coefficients.iter().map(|c| BigInt::from(*c)).collect())
+    }
+
+    #[test]
+    fn arithmetic() {
+        // (x + 1)(x - 1) = x^2 - 1
+        assert_eq!(mul(&p(&[1, 1]), &p(&[-1, 1])), p(&[-1, 0, 1]));
+        assert_eq!(pow(&p(&[1, 1]), 3), p(&[1, 3, 3, 1]));
+        assert_eq!(add(&p(&[1, 2, 3]), &p(&[0, 0, -3])), p(&[1, 2]));
+        assert_eq!(shift(&p(&[1, 2]), 2), p(&[0, 0, 1, 2]));
+        assert_eq!(reduce(&p(&[-1, 7, 5]), &5.into()), p(&[4, 2]));
+        assert_eq!(eval(&p(&[1, 3, 3, 1]), &2.into()), 27.into());
+        assert_eq!(degree(&p(&[1, 3, 0])), 1);
+        assert_eq!(primitive(&p(&[-6, 0, -4])), p(&[3, 0, 2]));
+    }
+
+    #[test]
+    fn gcds() {
+        // (x - 3)(2x + 1) and (x - 3)(x + 5)
+        let f = mul(&p(&[-3, 1]), &p(&[1, 2]));
+        let g = mul(&p(&[-3, 1]), &p(&[5, 1]));
+        assert_eq!(gcd(&f, &g), p(&[-3, 1]));
+        assert_eq!(
+            gcd(&scale(&f, &12.into()), &scale(&g, &(-8).into())),
+            p(&[-3, 1])
+        );
+        assert_eq!(gcd(&p(&[1, 1]), &p(&[1, 2])), p(&[1]));
+
+        let h = pow(&p(&[7, -2]), 2);
+        assert_eq!(
+            gcd(&mul(&h, &p(&[1, 0, 1])), &mul(&h, &p(&[2, 1]))),
+            pow(&p(&[-7, 2]), 2)
+        );
+    }
+
+    #[test]
+    fn roots() {
+        let (lo, hi) = (BigInt::from(-1000), BigInt::from(1000));
+        // (x - 3)(x + 5)(2x - 1)(x - 999), with a root that isn't whole
+        let f = [p(&[-3, 1]), p(&[5, 1]), p(&[-1, 2]), p(&[-999, 1])]
+            .iter()
+            .fold(p(&[1]), |acc, g| mul(&acc, g));
+        assert_eq!(integer_roots(&f, &lo, &hi), p(&[-5, 3, 999]));
+        assert_eq!(integer_roots(&f, &0.into(), &100.into()), p(&[3]));
+        // Repeated roots don't change sign, and x^2 + 1 has no real roots at all
+        let g = mul(&pow(&p(&[-7, 1]), 2), &p(&[1, 0, 1]));
+        assert_eq!(integer_roots(&g, &lo, &hi), p(&[7]));
+        assert_eq!(integer_roots(&p(&[0, 1]), &lo, &hi), [BigInt::zero()]);
+        assert_eq!(integer_roots(&p(&[5]), &lo, &hi), p(&[]));
+
+        // Big roots, and big coefficients
+        let big = BigInt::from(3).pow(200);
+        let f = mul(&p(&[0, 1, 1]), &[-&big, 2.into()]);
+        let roots = integer_roots(&mul(&f, &[-&big, BigInt::one()]), &-&big, &big);
+        assert_eq!(roots, [BigInt::from(-1), BigInt::zero(), big.clone()]);
+    }
+}
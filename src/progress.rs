@@ -0,0 +1,297 @@
+//! Progress of the long-running attacks, shown however the run was asked to show it
+//!
+//! Attacks report through the [`Progress`] trait rather than driving `indicatif` themselves, so
+//! the same code can draw bars in a terminal, log a line now and then for CI, or keep quiet.
+//! [`bar`], [`counter`] and [`spinner`] pick the implementation from `--progress`, which defaults
+//! to bars on a terminal and a line now and then otherwise, so piped output isn't full of redraws.
+//! Once [`share`] has been called, as it is when challenges run side by side, bars are drawn
+//! together on a `MultiProgress` rather than each fighting over the same line.
+#![allow(dead_code)]
+
+use std::fmt;
+use std::io::IsTerminal;
+use std::str::FromStr;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+use anyhow::anyhow;
+use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
+
+use crate::utils::Result;
+
+/// Something that can be told how far an attack has got
+pub trait Progress: Send + Sync {
+    /// Notes that the attack is still going, without saying how far it's got
+    fn tick(&self);
+    /// Moves the position on by `delta`
+    fn inc(&self, delta: u64);
+    fn set_position(&self, pos: u64);
+    fn set_message(&self, msg: String);
+    /// Marks the end, leaving the last position and message showing
+    fn finish(&self);
+    /// Marks the end and takes it away, for progress not worth keeping once it's done
+    fn clear(&self) {
+        self.finish()
+    }
+}
+
+/// How progress gets shown
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Style {
+    /// `indicatif` bars and spinners
+    Bars,
+    /// A line on stderr every few seconds, for logs
+    Lines,
+    /// Nothing at all
+    None,
+}
+
+impl Style {
+    /// Bars if stderr is a terminal to draw them on, or else lines for a log
+    pub fn detect() -> Self {
+        match std::io::stderr().is_terminal() {
+            true => Self::Bars,
+            false => Self::Lines,
+        }
+    }
+}
+
+impl Default for Style {
+    fn default() -> Self {
+        Self::detect()
+    }
+}
+
+impl FromStr for Style {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "bars" => Ok(Self::Bars),
+            "lines" => Ok(Self::Lines),
+            "none" => Ok(Self::None),
+            _ => Err(anyhow!(
+                "Unknown progress style {s}, expected bars, lines or none"
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Style {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::Bars => "bars",
+            Self::Lines => "lines",
+            Self::None => "none",
+        };
+        write!(f, "{name}")
+    }
+}
+
+/// Where bars are drawn once [`share`] has been called
+static SHARED: OnceLock<MultiProgress> = OnceLock::new();
+
+/// Draws every bar from here on together, one line each, for when several attacks run at once
+pub fn share() {
+    SHARED.get_or_init(MultiProgress::new);
+}
+
+/// `bar`, on the shared `MultiProgress` if there is one
+fn drawn(bar: ProgressBar) -> ProgressBar {
+    match SHARED.get() {
+        Some(shared) => shared.add(bar),
+        None => bar,
+    }
+}
+
+/// Progress towards `len`, in this run's style
+pub fn bar(len: u64) -> Box<dyn Progress> {
+    match crate::options::get().progress {
+        Style::Bars => {
+            let bar = ProgressBar::new(len);
+            bar.set_style(
+                ProgressStyle::with_template(
+                    "[{elapsed_precise}] {bar:40.cyan/blue} {pos:>7}/{len:7} {msg}",
+                )
+                .unwrap()
+                .progress_chars("##-"),
+            );
+            Box::new(drawn(bar))
+        }
+        Style::Lines => Box::new(Lines::new(Some(len))),
+        Style::None => Box::new(Silent),
+    }
+}
+
+/// Progress towards `len` as a count, with the rest of the line for a long message, in this run's
+/// style
+pub fn counter(len: u64) -> Box<dyn Progress> {
+    match crate::options::get().progress {
+        Style::Bars => {
+            let bar = ProgressBar::new(len);
+            bar.set_style(
+                ProgressStyle::with_template("[{elapsed_precise}] {pos:>5}/{len:5} {wide_msg}")
+                    .unwrap(),
+            );
+            Box::new(drawn(bar))
+        }
+        Style::Lines => Box::new(Lines::new(Some(len))),
+        Style::None => Box::new(Silent),
+    }
+}
+
+/// Progress with no end in sight, in this run's style
+pub fn spinner() -> Box<dyn Progress> {
+    match crate::options::get().progress {
+        Style::Bars => Box::new(drawn(ProgressBar::new_spinner())),
+        Style::Lines => Box::new(Lines::new(None)),
+        Style::None => Box::new(Silent),
+    }
+}
+
+impl Progress for ProgressBar {
+    fn tick(&self) {
+        ProgressBar::tick(self)
+    }
+
+    fn inc(&self, delta: u64) {
+        ProgressBar::inc(self, delta)
+    }
+
+    fn set_position(&self, pos: u64) {
+        ProgressBar::set_position(self, pos)
+    }
+
+    fn set_message(&self, msg: String) {
+        ProgressBar::set_message(self, msg)
+    }
+
+    fn finish(&self) {
+        ProgressBar::finish(self)
+    }
+
+    fn clear(&self) {
+        ProgressBar::finish_and_clear(self)
+    }
+}
+
+/// Shows nothing
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Silent;
+
+impl Progress for Silent {
+    fn tick(&self) {}
+    fn inc(&self, _delta: u64) {}
+    fn set_position(&self, _pos: u64) {}
+    fn set_message(&self, _msg: String) {}
+    fn finish(&self) {}
+}
+
+/// How long [`Lines`] waits between lines
+const LINE_EVERY: Duration = Duration::from_secs(5);
+
+/// Writes the position and message to stderr every [`LINE_EVERY`], and once more at the end
+#[derive(Debug)]
+pub struct Lines {
+    len: Option<u64>,
+    started: Instant,
+    state: Mutex<LinesState>,
+}
+
+#[derive(Debug)]
+struct LinesState {
+    pos: u64,
+    msg: String,
+    last: Instant,
+}
+
+impl Lines {
+    pub fn new(len: Option<u64>) -> Self {
+        let now = Instant::now();
+        Self {
+            len,
+            started: now,
+            state: Mutex::new(LinesState {
+                pos: 0,
+                msg: String::new(),
+                last: now,
+            }),
+        }
+    }
+
+    /// What gets written for the state as it stands
+    fn line(&self, state: &LinesState) -> String {
+        let elapsed = self.started.elapsed().as_secs();
+        let pos = match self.len {
+            Some(len) => format!("{}/{len}", state.pos),
+            None => state.pos.to_string(),
+        };
+        let line = format!(
+            "[{:02}:{:02}:{:02}] {pos}",
+            elapsed / 3600,
+            elapsed / 60 % 60,
+            elapsed % 60
+        );
+        match state.msg.is_empty() {
+            true => line,
+            false => format!("{line} {}", state.msg),
+        }
+    }
+
+    /// Applies `update`, then writes a line if it's been long enough since the last one
+    fn update(&self, update: impl FnOnce(&mut LinesState)) {
+        let mut state = self.state.lock().unwrap();
+        update(&mut state);
+        if state.last.elapsed() >= LINE_EVERY {
+            state.last = Instant::now();
+            eprintln!("{}", self.line(&state));
+        }
+    }
+}
+
+impl Progress for Lines {
+    fn tick(&self) {
+        self.update(|_| ());
+    }
+
+    fn inc(&self, delta: u64) {
+        self.update(|state| state.pos += delta);
+    }
+
+    fn set_position(&self, pos: u64) {
+        self.update(|state| state.pos = pos);
+    }
+
+    fn set_message(&self, msg: String) {
+        self.update(|state| state.msg = msg);
+    }
+
+    fn finish(&self) {
+        let state = self.state.lock().unwrap();
+        eprintln!("{}", self.line(&state));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lines() {
+        let lines = Lines::new(Some(40));
+        lines.inc(3);
+        lines.set_message("Generating collisions".to_string());
+        assert_eq!(
+            lines.line(&lines.state.lock().unwrap()),
+            "[00:00:00] 3/40 Generating collisions"
+        );
+
+        let lines = Lines::new(None);
+        lines.set_position(1234);
+        assert_eq!(lines.line(&lines.state.lock().unwrap()), "[00:00:00] 1234");
+
+        assert_eq!("lines".parse::<Style>().unwrap(), Style::Lines);
+        assert_eq!(Style::None.to_string(), "none");
+        assert!("quiet".parse::<Style>().is_err());
+    }
+}
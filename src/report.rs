@@ -0,0 +1,489 @@
+//! What a challenge found, as well as printing it, so that whatever runs it can keep a record
+//!
+//! Each challenge's `main` returns a [`Report`] of the keys, plaintexts and so on it recovered,
+//! along with counts of whatever its attack spends, like oracle queries, and the runner fills in
+//! how long it took. Rather than asserting that it got things right, a challenge adds a
+//! [`Verification`] of each against the truth, which it knows even if its attack doesn't, and the
+//! runner fails it if any don't hold, without bringing down the rest of a run.
+//! [`Report::to_json`] writes one out for other tools to pick up: bytes as hex,
+//! text as strings, and numbers as decimal strings so that big ones come through whole.
+//!
+//! Under `--format json` the runner prints one such record per challenge on stdout, so the
+//! challenges' running commentary goes through [`say!`], which moves it to stderr out of the way.
+//! When challenges run side by side, [`capture`] holds each one's commentary back so that it can
+//! be printed all together rather than interleaved.
+//!
+//! With `--out DIR`, [`Report::write_to`] also leaves what each challenge recovered in a directory
+//! of its own, a file per value, for checking with other tools: `DIR/challenge55/message.bin`,
+//! `DIR/challenge55/flipped.bin` and so on, next to the whole record in `report.json`.
+#![allow(dead_code)]
+
+use std::cell::RefCell;
+use std::fmt::{self, Write};
+use std::path::Path;
+use std::str::FromStr;
+use std::time::Duration;
+
+use anyhow::anyhow;
+use num_bigint::BigInt;
+
+use crate::utils::{bytes_to_hex, Result};
+
+/// How the runner writes up each challenge
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// Whatever the challenge prints, and a table at the end of a run of several
+    #[default]
+    Text,
+    /// A JSON object per challenge on stdout, one per line
+    Json,
+}
+
+impl FromStr for Format {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "text" => Ok(Self::Text),
+            "json" => Ok(Self::Json),
+            _ => Err(anyhow!("Unknown format {s}, expected text or json")),
+        }
+    }
+}
+
+impl fmt::Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::Text => "text",
+            Self::Json => "json",
+        };
+        write!(f, "{name}")
+    }
+}
+
+/// Prints a line of a challenge's commentary, like `println!`, but on stderr under `--format json`
+/// so that stdout is left with just the records
+macro_rules! say {
+    () => {
+        $crate::report::say_line(format_args!(""))
+    };
+    ($($arg:tt)*) => {
+        $crate::report::say_line(format_args!($($arg)*))
+    };
+}
+
+pub(crate) use say;
+
+thread_local! {
+    /// What [`say!`] has been asked to print on this thread inside [`capture`]
+    static CAPTURED: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+/// What [`say!`] does: holds the line back inside [`capture`], or prints it where it belongs,
+/// unless the run is `--quiet`
+pub fn say_line(line: fmt::Arguments) {
+    if crate::options::get().quiet {
+        return;
+    }
+    let held = CAPTURED.with_borrow_mut(|captured| match captured {
+        Some(captured) => writeln!(captured, "{line}").is_ok(),
+        None => false,
+    });
+    if !held {
+        match crate::options::get().format {
+            Format::Text => println!("{line}"),
+            Format::Json => eprintln!("{line}"),
+        }
+    }
+}
+
+/// Runs `f`, returning everything it [`say!`]s on this thread rather than printing it
+pub fn capture<T>(f: impl FnOnce() -> T) -> (T, String) {
+    let outer = CAPTURED.replace(Some(String::new()));
+    let result = f();
+    let said = CAPTURED.replace(outer).unwrap_or_default();
+    (result, said)
+}
+
+/// Something an attack recovered
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Value {
+    Bytes(Vec<u8>),
+    Text(String),
+    Number(BigInt),
+}
+
+impl From<Vec<u8>> for Value {
+    fn from(bytes: Vec<u8>) -> Self {
+        Self::Bytes(bytes)
+    }
+}
+
+impl From<&[u8]> for Value {
+    fn from(bytes: &[u8]) -> Self {
+        Self::Bytes(bytes.to_vec())
+    }
+}
+
+impl<const N: usize> From<[u8; N]> for Value {
+    fn from(bytes: [u8; N]) -> Self {
+        Self::Bytes(bytes.to_vec())
+    }
+}
+
+impl<const N: usize> From<&[u8; N]> for Value {
+    fn from(bytes: &[u8; N]) -> Self {
+        Self::Bytes(bytes.to_vec())
+    }
+}
+
+impl From<String> for Value {
+    fn from(text: String) -> Self {
+        Self::Text(text)
+    }
+}
+
+impl From<&str> for Value {
+    fn from(text: &str) -> Self {
+        Self::Text(text.to_string())
+    }
+}
+
+impl From<BigInt> for Value {
+    fn from(n: BigInt) -> Self {
+        Self::Number(n)
+    }
+}
+
+impl From<&BigInt> for Value {
+    fn from(n: &BigInt) -> Self {
+        Self::Number(n.clone())
+    }
+}
+
+impl From<u64> for Value {
+    fn from(n: u64) -> Self {
+        Self::Number(n.into())
+    }
+}
+
+impl From<i64> for Value {
+    fn from(n: i64) -> Self {
+        Self::Number(n.into())
+    }
+}
+
+impl From<u32> for Value {
+    fn from(n: u32) -> Self {
+        Self::Number(n.into())
+    }
+}
+
+impl From<usize> for Value {
+    fn from(n: usize) -> Self {
+        Self::Number(n.into())
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Bytes(bytes) => write!(f, "{}", bytes_to_hex(bytes)),
+            Self::Text(text) => write!(f, "{text}"),
+            Self::Number(n) => write!(f, "{n}"),
+        }
+    }
+}
+
+/// How something a challenge came up with measures up
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Verification {
+    /// Something the attack recovered, and what it should have been
+    Recovered { expected: Value, actual: Value },
+    /// Something the attack forged, and whether whoever checks it took it as genuine
+    Forged { accepted: bool },
+    /// Nothing to check it against
+    Unverified,
+}
+
+impl Verification {
+    /// Whether it checks out, which anything unverified does for want of evidence otherwise
+    pub fn holds(&self) -> bool {
+        match self {
+            Self::Recovered { expected, actual } => expected == actual,
+            Self::Forged { accepted } => *accepted,
+            Self::Unverified => true,
+        }
+    }
+
+    fn to_json(&self) -> String {
+        match self {
+            Self::Recovered { expected, actual } => format!(
+                "{{\"expected\":{},\"actual\":{}}}",
+                json_string(&expected.to_string()),
+                json_string(&actual.to_string())
+            ),
+            Self::Forged { accepted } => format!("{{\"accepted\":{accepted}}}"),
+            Self::Unverified => "null".to_string(),
+        }
+    }
+}
+
+impl fmt::Display for Verification {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Recovered { expected, actual } if expected == actual => write!(f, "{actual}"),
+            Self::Recovered { expected, actual } => write!(f, "expected {expected}, got {actual}"),
+            Self::Forged { accepted: true } => write!(f, "accepted"),
+            Self::Forged { accepted: false } => write!(f, "rejected"),
+            Self::Unverified => write!(f, "unverified"),
+        }
+    }
+}
+
+/// What a challenge recovered, and what it cost
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Report {
+    /// Keys, plaintexts, forgeries and the like, by name, in the order they were found
+    pub recovered: Vec<(String, Value)>,
+    /// How each thing checked measured up, by name
+    pub checks: Vec<(String, Verification)>,
+    /// Oracle queries, tries and anything else the attack counts, by name
+    pub counts: Vec<(String, u64)>,
+    /// How long the challenge took, which the runner fills in
+    pub elapsed: Option<Duration>,
+}
+
+impl Report {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds something the attack recovered
+    pub fn recovered(mut self, name: &str, value: impl Into<Value>) -> Self {
+        self.recovered.push((name.to_string(), value.into()));
+        self
+    }
+
+    /// Adds how `name` measured up
+    pub fn verified(mut self, name: &str, verification: Verification) -> Self {
+        self.checks.push((name.to_string(), verification));
+        self
+    }
+
+    /// Adds something the attack recovered, checking that it's what it should have been
+    pub fn check(self, name: &str, expected: impl Into<Value>, actual: impl Into<Value>) -> Self {
+        let (expected, actual) = (expected.into(), actual.into());
+        self.recovered(name, actual.clone())
+            .verified(name, Verification::Recovered { expected, actual })
+    }
+
+    /// Adds something the attack recovered, with nothing to check it against
+    pub fn unverified(self, name: &str, value: impl Into<Value>) -> Self {
+        self.recovered(name, value)
+            .verified(name, Verification::Unverified)
+    }
+
+    /// Checks that the forgery `name` was `accepted`
+    pub fn forged(self, name: &str, accepted: bool) -> Self {
+        self.verified(name, Verification::Forged { accepted })
+    }
+
+    /// Each check that doesn't hold, as the name and what went wrong
+    pub fn failures(&self) -> impl Iterator<Item = String> + '_ {
+        self.checks
+            .iter()
+            .filter(|(_, verification)| !verification.holds())
+            .map(|(name, verification)| format!("{name}: {verification}"))
+    }
+
+    /// The report back, or an error listing each check that doesn't hold
+    pub fn verify(self) -> Result<Self> {
+        let failures: Vec<String> = self.failures().collect();
+        match failures.is_empty() {
+            true => Ok(self),
+            false => Err(anyhow!(failures.join("; "))),
+        }
+    }
+
+    /// Adds a count of something the attack spent
+    pub fn count(mut self, name: &str, n: u64) -> Self {
+        self.counts.push((name.to_string(), n));
+        self
+    }
+
+    pub fn elapsed(mut self, elapsed: Duration) -> Self {
+        self.elapsed = Some(elapsed);
+        self
+    }
+
+    /// The value recovered under `name`, if there is one
+    pub fn get(&self, name: &str) -> Option<&Value> {
+        self.recovered
+            .iter()
+            .find_map(|(n, value)| (n == name).then_some(value))
+    }
+
+    /// A JSON object with `recovered`, `checks` and `counts` as objects, and `elapsed` in seconds
+    /// if known
+    pub fn to_json(&self) -> String {
+        let mut json = format!("{{{}", self.json_fields());
+        if let Some(elapsed) = self.elapsed {
+            write!(json, ",\"elapsed\":{}", elapsed.as_secs_f64()).unwrap();
+        }
+        json.push('}');
+        json
+    }
+
+    /// Writes each recovered value to a file in `dir`, named after it: raw bytes in `.bin` files,
+    /// and text and numbers (in decimal) in `.txt` files, along with the whole report as JSON
+    pub fn write_to(&self, dir: &Path) -> Result<()> {
+        std::fs::create_dir_all(dir)?;
+        for (name, value) in &self.recovered {
+            let (extension, contents) = match value {
+                Value::Bytes(bytes) => ("bin", bytes.clone()),
+                Value::Text(text) => ("txt", text.as_bytes().to_vec()),
+                Value::Number(n) => ("txt", format!("{n}\n").into_bytes()),
+            };
+            std::fs::write(
+                dir.join(format!("{}.{extension}", file_name(name))),
+                contents,
+            )?;
+        }
+        std::fs::write(dir.join("report.json"), self.to_json() + "\n")?;
+        Ok(())
+    }
+
+    /// The `recovered`, `checks` and `counts` members, without the braces, to go in a bigger
+    /// object. Each check is its expected and actual values, whether a forgery was accepted, or
+    /// null if it's unverified.
+    pub fn json_fields(&self) -> String {
+        let recovered = self.recovered.iter().map(|(name, value)| {
+            format!("{}:{}", json_string(name), json_string(&value.to_string()))
+        });
+        let checks = self.checks.iter().map(|(name, verification)| {
+            format!("{}:{}", json_string(name), verification.to_json())
+        });
+        let counts = self
+            .counts
+            .iter()
+            .map(|(name, n)| format!("{}:{n}", json_string(name)));
+        format!(
+            "\"recovered\":{{{}}},\"checks\":{{{}}},\"counts\":{{{}}}",
+            recovered.collect::<Vec<_>>().join(","),
+            checks.collect::<Vec<_>>().join(","),
+            counts.collect::<Vec<_>>().join(",")
+        )
+    }
+}
+
+/// `name` in lower case, with anything but letters and digits between words turned into one `-`
+fn file_name(name: &str) -> String {
+    name.split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_ascii_lowercase)
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+/// `s` quoted, with quotes, backslashes and control characters escaped
+pub fn json_string(s: &str) -> String {
+    let mut quoted = String::with_capacity(s.len() + 2);
+    quoted.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c if c.is_control() => write!(quoted, "\\u{:04x}", c as u32).unwrap(),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn json() {
+        let report = Report::new()
+            .recovered("key", b"YELLOW")
+            .unverified("plaintext", "Play that \"funky\" music\n")
+            .check("d", BigInt::from(-12345), BigInt::from(-12345))
+            .forged("mac", true)
+            .count("queries", 42)
+            .elapsed(Duration::from_millis(1500));
+        assert_eq!(
+            report.to_json(),
+            "{\"recovered\":{\"key\":\"59454c4c4f57\",\"plaintext\":\"Play that \\\"funky\\\" \
+             music\\n\",\"d\":\"-12345\"},\"checks\":{\"plaintext\":null,\"d\":{\"expected\":\
+             \"-12345\",\"actual\":\"-12345\"},\"mac\":{\"accepted\":true}},\
+             \"counts\":{\"queries\":42},\"elapsed\":1.5}"
+        );
+        assert_eq!(report.get("d"), Some(&Value::Number((-12345).into())));
+        assert_eq!(report.get("e"), None);
+
+        assert_eq!(
+            Report::new().to_json(),
+            "{\"recovered\":{},\"checks\":{},\"counts\":{}}"
+        );
+        assert_eq!(json_string("\u{1}\\"), "\"\\u0001\\\\\"");
+
+        let ((), said) = capture(|| {
+            say!("key: {}", 42);
+            say!();
+        });
+        assert_eq!(said, "key: 42\n\n");
+
+        assert_eq!("json".parse::<Format>().unwrap(), Format::Json);
+    }
+
+    #[test]
+    fn checks() {
+        let report = Report::new()
+            .check("key", b"YELLOW", b"YELLOW")
+            .check("e", 3_u64, 5_u64)
+            .forged("mac", false)
+            .verified("guess", Verification::Unverified);
+        assert_eq!(
+            report.failures().collect::<Vec<_>>(),
+            ["e: expected 3, got 5", "mac: rejected"]
+        );
+        assert_eq!(report.get("e"), Some(&5_u64.into()));
+        assert!(Report::new()
+            .check("key", "a", "a")
+            .failures()
+            .next()
+            .is_none());
+    }
+
+    #[test]
+    fn files() {
+        let dir = std::env::temp_dir().join(format!("cryptopals-out-{}", std::process::id()));
+        let report = Report::new()
+            .recovered("message", b"\x00\xff")
+            .recovered("session id, CBC", "abc")
+            .recovered("key length", 16_u64);
+        report.write_to(&dir).unwrap();
+        assert_eq!(std::fs::read(dir.join("message.bin")).unwrap(), b"\x00\xff");
+        assert_eq!(
+            std::fs::read_to_string(dir.join("session-id-cbc.txt")).unwrap(),
+            "abc"
+        );
+        assert_eq!(
+            std::fs::read_to_string(dir.join("key-length.txt")).unwrap(),
+            "16\n"
+        );
+        assert_eq!(
+            std::fs::read_to_string(dir.join("report.json")).unwrap(),
+            report.to_json() + "\n"
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert!("yaml".parse::<Format>().is_err());
+    }
+}
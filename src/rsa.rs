@@ -0,0 +1,418 @@
+//! RSA keys, and how big to make them
+//!
+//! The RSA challenges get their keys from [`Params`], which starts from each challenge's own choice
+//! of modulus size and public exponent, and lets `--arg rsa_bits=` and `--arg e=` override them.
+//! That way the attacks can be tried (and timed) against full-size keys and e = 65537, at least
+//! where the attack doesn't rely on a particular e.
+//!
+//! Primes come from [`random_prime`]: a random odd starting point, a sieve over the window of odd
+//! numbers after it to throw out anything with a small factor, and Miller-Rabin on what's left, a
+//! batch at a time in parallel.
+#![allow(dead_code)]
+
+use std::fmt;
+use std::sync::OnceLock;
+
+use anyhow::{anyhow, Result};
+use num_bigint::{BigInt, RandBigInt};
+use num_integer::Integer;
+use num_traits::{One, ToPrimitive, Zero};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use rayon::prelude::*;
+
+use crate::der::{bit_string, der, integer, oid, pem, sequence, NULL, OCTET_STRING, RSA_OID};
+use crate::set5::challenge39::try_invmod;
+
+/// Smallest modulus worth generating
+const MIN_BITS: u64 = 32;
+/// Candidates are sieved by every prime below this
+const SIEVE_LIMIT: u32 = 1 << 14;
+/// Odd numbers to sieve from each random starting point, plenty to expect a few primes even at
+/// 2048 bits (where about one odd number in 700 is prime)
+const WINDOW: usize = 1 << 13;
+/// Miller-Rabin rounds, for a chance of under 2^-80 of letting a composite through
+const ROUNDS: usize = 40;
+
+/// Half of an RSA key pair: an exponent along with the modulus
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Key {
+    pub key: BigInt,
+    pub modulus: BigInt,
+}
+
+impl Key {
+    /// Raises `m` to the key's exponent mod n: encryption for a public key, decryption for a
+    /// private one
+    pub fn apply(&self, m: &BigInt) -> BigInt {
+        m.modpow(&self.key, &self.modulus)
+    }
+
+    /// Length of the modulus in bytes
+    pub fn size(&self) -> usize {
+        self.modulus.bits().div_ceil(8) as usize
+    }
+
+    /// `m` as big-endian bytes, zero-padded on the left to the length of the modulus
+    pub fn to_block(&self, m: &BigInt) -> Vec<u8> {
+        let bytes = m.to_bytes_be().1;
+        let mut block = vec![0; self.size().saturating_sub(bytes.len())];
+        block.extend_from_slice(&bytes);
+        block
+    }
+}
+
+impl Key {
+    /// PKCS#1 RSAPublicKey, taking this as the public half: SEQUENCE { n, e }
+    pub fn to_pkcs1_der(&self) -> Vec<u8> {
+        sequence(&[integer(&self.modulus), integer(&self.key)])
+    }
+
+    /// SubjectPublicKeyInfo, the `PUBLIC KEY` most tools take, wrapping [`Key::to_pkcs1_der`]
+    pub fn to_spki_der(&self) -> Vec<u8> {
+        sequence(&[algorithm(), bit_string(&self.to_pkcs1_der())])
+    }
+
+    pub fn to_pem(&self) -> String {
+        pem("PUBLIC KEY", &self.to_spki_der())
+    }
+}
+
+/// rsaEncryption, with its NULL parameters
+fn algorithm() -> Vec<u8> {
+    sequence(&[oid(RSA_OID), der(NULL, &[])])
+}
+
+#[derive(Clone, Debug)]
+pub struct KeyPair {
+    pub public: Key,
+    pub private: Key,
+}
+
+impl KeyPair {
+    /// The primes behind n, smaller first, from n and both exponents
+    ///
+    /// e d - 1 = 2^t r is a multiple of the order of everything mod n, so for most g, squaring
+    /// g^r over and over reaches 1 by way of a square root of 1 other than ±1, and that shares a
+    /// factor with n once 1 is taken away.
+    pub fn primes(&self) -> Result<(BigInt, BigInt)> {
+        let n = &self.public.modulus;
+        let k: BigInt = &self.public.key * &self.private.key - 1;
+        let t = k.trailing_zeros().unwrap_or_default();
+        let r = &k >> t;
+        for g in small_primes().iter().take(100) {
+            let mut x = BigInt::from(*g).modpow(&r, n);
+            for _ in 0..t {
+                let y = &x * &x % n;
+                if y.is_one() && !x.is_one() && x != n - 1 {
+                    let p = (&x - BigInt::one()).gcd(n);
+                    let q = n / &p;
+                    return Ok(match p < q {
+                        true => (p, q),
+                        false => (q, p),
+                    });
+                }
+                x = y;
+            }
+        }
+        Err(anyhow!("The exponents don't give away the factors of n"))
+    }
+
+    /// PKCS#1 RSAPrivateKey, with the primes and CRT exponents found from [`KeyPair::primes`]
+    pub fn to_pkcs1_der(&self) -> Result<Vec<u8>> {
+        // With p the bigger of the two, as is usual
+        let (q, p) = self.primes()?;
+        let d = &self.private.key;
+        Ok(sequence(&[
+            integer(&BigInt::zero()),
+            integer(&self.public.modulus),
+            integer(&self.public.key),
+            integer(d),
+            integer(&p),
+            integer(&q),
+            integer(&(d % (&p - 1))),
+            integer(&(d % (&q - 1))),
+            integer(&try_invmod(&q, &p).ok_or_else(|| anyhow!("q has no inverse mod p"))?),
+        ]))
+    }
+
+    /// PKCS#8 PrivateKeyInfo, the `PRIVATE KEY` most tools take, wrapping [`KeyPair::to_pkcs1_der`]
+    pub fn to_pkcs8_der(&self) -> Result<Vec<u8>> {
+        Ok(sequence(&[
+            integer(&BigInt::zero()),
+            algorithm(),
+            der(OCTET_STRING, &self.to_pkcs1_der()?),
+        ]))
+    }
+
+    pub fn to_pem(&self) -> Result<String> {
+        Ok(pem("PRIVATE KEY", &self.to_pkcs8_der()?))
+    }
+}
+
+/// Size of the modulus and public exponent to generate keys with
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Params {
+    pub bits: u64,
+    pub e: BigInt,
+}
+
+impl Params {
+    pub fn new(bits: u64, e: u64) -> Self {
+        Self { bits, e: e.into() }
+    }
+
+    /// A challenge's own choice of parameters, unless overridden by `--arg rsa_bits=` or `--arg e=`
+    pub fn from_options(bits: u64, e: u64) -> Result<Self> {
+        let options = crate::options::get();
+        let params = Self {
+            bits: options.arg("rsa_bits")?.unwrap_or(bits),
+            e: options.arg("e")?.unwrap_or_else(|| e.into()),
+        };
+        params.validate()?;
+        Ok(params)
+    }
+
+    fn validate(&self) -> Result<()> {
+        if self.bits < MIN_BITS || !self.bits.is_multiple_of(2) {
+            return Err(anyhow!(
+                "RSA modulus size must be an even number of bits, at least {MIN_BITS}, not {}",
+                self.bits
+            ));
+        }
+        if self.e < 3.into() || self.e.is_even() {
+            return Err(anyhow!(
+                "RSA public exponent must be odd and at least 3, not {}",
+                self.e
+            ));
+        }
+        Ok(())
+    }
+
+    /// For attacks which only work with one exponent, e.g. because they take cube roots
+    pub fn require_e(&self, e: u64) -> Result<()> {
+        match self.e == e.into() {
+            true => Ok(()),
+            false => Err(anyhow!("This attack needs e = {e}, not {}", self.e)),
+        }
+    }
+
+    /// Generates a new key pair, from two primes of half the modulus size
+    pub fn keypair(&self, rng: &mut impl Rng) -> KeyPair {
+        self.keypair_from(|| random_prime(self.bits / 2, &self.e, rng))
+    }
+
+    /// Makes a key pair from primes drawn two at a time from `draw`, going back for another two
+    /// whenever they won't do: if they're the same, if their product isn't exactly `bits` long, or
+    /// if e has no inverse mod (p - 1)(q - 1)
+    pub fn keypair_from(&self, mut draw: impl FnMut() -> BigInt) -> KeyPair {
+        loop {
+            let (p, q) = (draw(), draw());
+            if p == q {
+                continue;
+            }
+            let n = &p * &q;
+            if n.bits() != self.bits {
+                continue;
+            }
+            let et = (p - 1) * (q - 1);
+            let Some(d) = try_invmod(&self.e, &et) else {
+                continue;
+            };
+            debug_assert!((&d * &self.e % &et).is_one());
+            return KeyPair {
+                public: Key {
+                    key: self.e.clone(),
+                    modulus: n.clone(),
+                },
+                private: Key { key: d, modulus: n },
+            };
+        }
+    }
+}
+
+/// The primes below [`SIEVE_LIMIT`], worked out the first time they're needed
+pub fn small_primes() -> &'static [u32] {
+    static PRIMES: OnceLock<Vec<u32>> = OnceLock::new();
+    PRIMES.get_or_init(|| {
+        let mut composite = vec![false; SIEVE_LIMIT as usize];
+        let mut primes = vec![];
+        for i in 2..SIEVE_LIMIT as usize {
+            if !composite[i] {
+                primes.push(i as u32);
+                for j in (i * i..SIEVE_LIMIT as usize).step_by(i) {
+                    composite[j] = true;
+                }
+            }
+        }
+        primes
+    })
+}
+
+/// Miller-Rabin with `rounds` random witnesses, after trial division by the small primes
+pub fn is_probable_prime<R: Rng>(n: &BigInt, rounds: usize, rng: &mut R) -> bool {
+    let two = BigInt::from(2);
+    if n < &two {
+        return false;
+    }
+    for p in small_primes() {
+        let p = BigInt::from(*p);
+        if n == &p {
+            return true;
+        }
+        if (n % &p).is_zero() {
+            return false;
+        }
+    }
+    miller_rabin(n, rounds, rng)
+}
+
+/// Miller-Rabin alone, for odd n > 3
+pub fn miller_rabin<R: Rng>(n: &BigInt, rounds: usize, rng: &mut R) -> bool {
+    let two = BigInt::from(2);
+    // n - 1 = 2^s * d, with d odd
+    let n_minus_1: BigInt = n - 1;
+    let s = n_minus_1.trailing_zeros().unwrap();
+    let d = &n_minus_1 >> s;
+    'witness: for _ in 0..rounds {
+        let a = rng.gen_bigint_range(&two, &n_minus_1);
+        let mut x = a.modpow(&d, n);
+        if x.is_one() || x == n_minus_1 {
+            continue;
+        }
+        for _ in 1..s {
+            x = x.modpow(&two, n);
+            if x == n_minus_1 {
+                continue 'witness;
+            }
+        }
+        return false;
+    }
+    true
+}
+
+/// A random prime of exactly `bits` bits, with its top two bits set so that the product of two of
+/// them has exactly twice as many, and with p - 1 coprime to `e` so that e is a valid exponent
+pub fn random_prime<R: Rng>(bits: u64, e: &BigInt, rng: &mut R) -> BigInt {
+    // Any small prime which divides a candidate makes it composite, as long as it's smaller than
+    // the candidate itself
+    let sieve: Vec<u32> = small_primes()
+        .iter()
+        .copied()
+        .skip(1)
+        .take_while(|p| (*p as u64) < 1 << (bits - 2).min(32))
+        .collect();
+
+    loop {
+        let mut start = BigInt::from(rng.gen_biguint(bits));
+        start.set_bit(bits - 1, true);
+        start.set_bit(bits - 2, true);
+        start.set_bit(0, true);
+
+        // Sieve the odd numbers start + 2k: p divides it for k = -start / 2 mod p, and every p after
+        let mut composite = vec![false; WINDOW];
+        for p in sieve.iter() {
+            let r = (&start % p).to_u64().unwrap();
+            let p = *p as u64;
+            let first = (p - r) * p.div_ceil(2) % p;
+            for k in (first as usize..WINDOW).step_by(p as usize) {
+                composite[k] = true;
+            }
+        }
+        // Each candidate gets its own witnesses, drawn up front, so the threads needn't share `rng`
+        let candidates: Vec<(BigInt, u64)> = (0..WINDOW)
+            .filter(|k| !composite[*k])
+            .map(|k| &start + 2 * k)
+            .filter(|c| c.bits() == bits)
+            .map(|c| (c, rng.gen()))
+            .collect();
+
+        // The first prime in the window rather than whichever a thread finds first, so that the
+        // same generator always gives the same prime
+        let found = candidates.par_iter().find_first(|(c, seed)| {
+            (c - 1u32).gcd(e).is_one() && miller_rabin(c, ROUNDS, &mut StdRng::seed_from_u64(*seed))
+        });
+        if let Some((p, _)) = found {
+            return p.clone();
+        }
+    }
+}
+
+impl fmt::Display for Params {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}-bit modulus, e = {}", self.bits, self.e)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keypairs() {
+        let mut rng = rand::thread_rng();
+        for (bits, e) in [(64, 3), (512, 65537), (1024, 17)] {
+            let KeyPair { public, private } = Params::new(bits, e).keypair(&mut rng);
+            assert_eq!(public.modulus.bits(), bits);
+            assert_eq!(public.size() as u64, bits / 8);
+            let m: BigInt = 0xc0ffee.into();
+            assert_eq!(private.apply(&public.apply(&m)), m);
+        }
+    }
+
+    #[test]
+    fn redrawing() {
+        // Equal, then 7 - 1 shares a factor with e, then too small, then just right
+        let mut primes = [13, 13, 7, 19, 5, 11, 11, 17].into_iter().map(BigInt::from);
+        let KeyPair { public, private } = Params::new(8, 3).keypair_from(|| primes.next().unwrap());
+        assert_eq!(public.modulus, 187.into());
+        assert_eq!(private.key, 107.into());
+        assert_eq!(primes.next(), None);
+    }
+
+    #[test]
+    fn primality() {
+        let mut rng = rand::thread_rng();
+        let is_prime =
+            |n: BigInt, rng: &mut rand::rngs::ThreadRng| is_probable_prime(&n, ROUNDS, rng);
+        for p in [2, 3, 16381, 65537, (1_u64 << 61) - 1] {
+            assert!(is_prime(p.into(), &mut rng), "{p}");
+        }
+        // Including Carmichael numbers, which fool the Fermat test
+        for c in [0, 1, 4, 561, 41041, 16383 * 16381, (1_u64 << 61) + 1] {
+            assert!(!is_prime(c.into(), &mut rng), "{c}");
+        }
+        assert!(is_prime((BigInt::one() << 127) - 1, &mut rng));
+        assert!(!is_prime((BigInt::one() << 128) - 1, &mut rng));
+    }
+
+    #[test]
+    fn random_primes() {
+        let mut rng = rand::thread_rng();
+        let mut ctx = openssl::bn::BigNumContext::new().unwrap();
+        for (bits, e) in [(16, 3), (17, 65537), (256, 3), (512, 65537)] {
+            let e = BigInt::from(e);
+            let p = random_prime(bits, &e, &mut rng);
+            assert_eq!(p.bits(), bits);
+            assert!(p.bit(bits - 2));
+            assert!((&p - 1u32).gcd(&e).is_one());
+            let bn = openssl::bn::BigNum::from_dec_str(&p.to_string()).unwrap();
+            assert!(bn.is_prime(64, &mut ctx).unwrap());
+        }
+        // However the threads happen to finish
+        let e = BigInt::from(65537);
+        let seeded = || random_prime(512, &e, &mut StdRng::seed_from_u64(3745));
+        assert_eq!(seeded(), seeded());
+    }
+
+    #[test]
+    fn validation() {
+        assert!(Params::new(512, 3).validate().is_ok());
+        assert!(Params::new(511, 3).validate().is_err());
+        assert!(Params::new(16, 3).validate().is_err());
+        assert!(Params::new(512, 4).validate().is_err());
+        assert!(Params::new(512, 1).validate().is_err());
+        assert!(Params::new(512, 3).require_e(3).is_ok());
+        assert!(Params::new(512, 65537).require_e(3).is_err());
+    }
+}
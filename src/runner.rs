@@ -0,0 +1,487 @@
+//! Runs challenges one after another, keeping going past failures, and sums up how each one went
+//!
+//! A challenge fails if it returns an error, or if any of the checks in its [`Report`] don't hold,
+//! and those go in the summary. A panic counts as a failure too: it's caught, and its message goes
+//! in the summary the same way.
+//!
+//! Under `--format json` there's no heading or table: each challenge gets a line on stdout with
+//! [`Outcome::to_json`] instead.
+//!
+//! With `--jobs` above 1, the challenges run side by side on a rayon pool of that many threads, each
+//! one's output held back until it's finished and then printed in one go, apart from the timing
+//! attacks in [`ALONE`], which run one at a time afterwards.
+#![allow(dead_code)]
+use std::fmt::{self, Write};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Mutex, PoisonError};
+use std::time::{Duration, Instant};
+
+use anyhow::anyhow;
+use rayon::prelude::*;
+
+use crate::report::{json_string, Format};
+use crate::utils::{Report, Result};
+
+/// How long a challenge takes to run in a release build
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Speed {
+    /// A few seconds at most
+    Fast,
+    /// Minutes, from sleeping through a timing leak or the sheer size of the search
+    Slow,
+}
+
+impl fmt::Display for Speed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::Fast => "fast",
+            Self::Slow => "slow",
+        };
+        f.pad(name)
+    }
+}
+
+/// A challenge, with all it takes to list it and run it
+#[derive(Debug, Clone, Copy)]
+pub struct Challenge {
+    pub number: u64,
+    pub set: u64,
+    /// As it's given on the site
+    pub title: &'static str,
+    /// Unknown until it's been written
+    pub speed: Option<Speed>,
+    /// Or `None` if it hasn't been written yet
+    pub main: Option<fn() -> Result<Report>>,
+}
+
+impl Challenge {
+    pub fn implemented(&self) -> bool {
+        self.main.is_some()
+    }
+}
+
+/// A line of `--list`: number, set, whether it's done, how long it takes and the title
+impl fmt::Display for Challenge {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let status = match self.implemented() {
+            true => "done",
+            false => "unimplemented",
+        };
+        let speed = self
+            .speed
+            .map_or("-".to_string(), |speed| speed.to_string());
+        write!(
+            f,
+            "{:>9}  {:>3}  {status:<13}  {speed:<5}  {}",
+            self.number, self.set, self.title
+        )
+    }
+}
+
+/// Declares each challenge's module and lists it in the set's `CHALLENGES`, so a challenge can't
+/// be written without being wired up to run. Any still to be written go at the end, with `todo`
+/// and just their number and title.
+///
+/// ```ignore
+/// register! {
+///     set 1;
+///     1 => challenge01, "Convert hex to base64", Fast;
+///     todo 2, "Fixed XOR";
+/// }
+/// ```
+macro_rules! register {
+    (
+        set $set:literal;
+        $($number:literal => $module:ident, $title:literal, $speed:ident;)*
+        $(todo $todo:literal, $todo_title:literal;)*
+    ) => {
+        $(pub mod $module;)*
+
+        /// The challenges in this set, in order
+        pub const CHALLENGES: &[$crate::runner::Challenge] = &[$(
+            $crate::runner::Challenge {
+                number: $number,
+                set: $set,
+                title: $title,
+                speed: Some($crate::runner::Speed::$speed),
+                main: Some($module::main),
+            },
+        )* $(
+            $crate::runner::Challenge {
+                number: $todo,
+                set: $set,
+                title: $todo_title,
+                speed: None,
+                main: None,
+            },
+        )*];
+    };
+}
+
+pub(crate) use register;
+
+/// The challenges in each set, set 1 first
+pub const SETS: [&[Challenge]; 8] = [
+    crate::set1::CHALLENGES,
+    crate::set2::CHALLENGES,
+    crate::set3::CHALLENGES,
+    crate::set4::CHALLENGES,
+    crate::set5::CHALLENGES,
+    crate::set6::CHALLENGES,
+    crate::set7::CHALLENGES,
+    crate::set8::CHALLENGES,
+];
+
+/// Every challenge there is, in order
+pub fn registry() -> impl Iterator<Item = &'static Challenge> {
+    SETS.into_iter().flatten()
+}
+
+/// The number of every challenge that's been written, in order
+pub fn challenges() -> impl Iterator<Item = u64> {
+    registry()
+        .filter(|challenge| challenge.implemented())
+        .map(|challenge| challenge.number)
+}
+
+/// Challenge `c`, if there is one
+pub fn find(c: u64) -> Option<&'static Challenge> {
+    registry().find(|challenge| challenge.number == c)
+}
+
+/// The challenges in set `s`
+pub fn set(s: u64) -> Result<&'static [Challenge]> {
+    s.checked_sub(1)
+        .and_then(|i| SETS.get(i as usize))
+        .copied()
+        .ok_or_else(|| anyhow!("Invalid set number, expected 1 to {}", SETS.len()))
+}
+
+/// The seed for a challenge's randomness: `--seed` if it was given, or else a fresh one, so that
+/// a failure can be run again exactly
+pub fn seed() -> u64 {
+    crate::options::get().seed.unwrap_or_else(rand::random)
+}
+
+/// Runs challenge `c`, whichever set it's in, with its randomness all drawn from `seed`
+///
+/// Every challenge starts again from the seed, so one which fails in a run of several can be
+/// reproduced on its own with `-c`.
+pub fn run(c: u64, seed: u64) -> Result<Report> {
+    let challenge = find(c).ok_or_else(|| anyhow!("Invalid challenge number"))?;
+    let main = challenge
+        .main
+        .ok_or_else(|| anyhow!("Challenge {c} hasn't been implemented yet"))?;
+    crate::utils::seeded(seed, main)
+}
+
+/// The timing attacks, which need the machine to themselves to measure anything
+pub const ALONE: &[u64] = &[31, 32];
+
+/// Prints every challenge in the registry, written or not, for `--list`
+pub fn list() {
+    println!(
+        "{:>9}  {:>3}  {:<13}  {:<5}  Title",
+        "Challenge", "Set", "Status", "Speed"
+    );
+    for challenge in registry() {
+        println!("{challenge}");
+    }
+}
+
+/// The title of challenge `c`
+pub fn title(c: u64) -> Option<&'static str> {
+    find(c).map(|challenge| challenge.title)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Status {
+    Passed,
+    /// With the error, or what the panic said
+    Failed(String),
+}
+
+/// How one challenge went
+#[derive(Debug, Clone)]
+pub struct Outcome {
+    pub challenge: u64,
+    pub status: Status,
+    pub elapsed: Duration,
+    /// What it recovered, if it got that far
+    pub report: Option<Report>,
+    /// What its randomness was drawn from, to pass to `--seed` to run it again
+    pub seed: u64,
+}
+
+impl Outcome {
+    /// A JSON object with the challenge's number, title, status, time in seconds and seed, the
+    /// error if it failed, and what it recovered and counted if it passed
+    pub fn to_json(&self) -> String {
+        let mut json = format!(
+            "{{\"challenge\":{},\"name\":{},",
+            self.challenge,
+            json_string(title(self.challenge).unwrap_or_default())
+        );
+        match &self.status {
+            Status::Passed => json.push_str("\"status\":\"pass\","),
+            Status::Failed(why) => {
+                write!(json, "\"status\":\"fail\",\"error\":{},", json_string(why)).unwrap()
+            }
+        }
+        write!(
+            json,
+            "\"elapsed\":{},\"seed\":{}",
+            self.elapsed.as_secs_f64(),
+            self.seed
+        )
+        .unwrap();
+        if let Some(report) = &self.report {
+            write!(json, ",{}", report.json_fields()).unwrap();
+        }
+        json.push('}');
+        json
+    }
+}
+
+/// Runs challenge `c`, catching a panic as well as an error
+pub fn attempt(c: u64) -> Outcome {
+    let seed = seed();
+    let start = Instant::now();
+    let result = panic::catch_unwind(AssertUnwindSafe(|| run(c, seed)));
+    let elapsed = start.elapsed();
+    let (status, report) = match result {
+        Ok(Ok(report)) => {
+            let report = report.elapsed(elapsed);
+            let failures: Vec<String> = report.failures().collect();
+            match write_out(c, &report) {
+                Ok(()) if failures.is_empty() => (Status::Passed, Some(report)),
+                Ok(()) => (Status::Failed(failures.join("; ")), Some(report)),
+                Err(e) => (
+                    Status::Failed(format!("Couldn't write out: {e}")),
+                    Some(report),
+                ),
+            }
+        }
+        Ok(Err(e)) => (Status::Failed(e.to_string()), None),
+        Err(payload) => {
+            let message = payload
+                .downcast_ref::<&str>()
+                .map(|s| s.to_string())
+                .or_else(|| payload.downcast_ref::<String>().cloned())
+                .unwrap_or_else(|| "panicked".to_string());
+            (Status::Failed(message), None)
+        }
+    };
+    Outcome {
+        challenge: c,
+        status,
+        elapsed,
+        report,
+        seed,
+    }
+}
+
+/// Leaves what challenge `c` recovered in its own directory under `--out`, if that was given
+fn write_out(c: u64, report: &Report) -> Result<()> {
+    match &crate::options::get().out {
+        Some(out) => report.write_to(&out.join(format!("challenge{c:02}"))),
+        None => Ok(()),
+    }
+}
+
+/// Runs challenge `c` on its own, printing its record as well under `--format json`, and giving
+/// the seed to run it again with if it fails
+pub fn run_one(c: u64) -> Result<()> {
+    find(c).ok_or_else(|| anyhow!("Invalid challenge number"))?;
+    let outcome = attempt(c);
+    if crate::options::get().format == Format::Json {
+        println!("{}", outcome.to_json());
+    }
+    match outcome.status {
+        Status::Passed => Ok(()),
+        Status::Failed(why) => Err(anyhow!(
+            "Challenge {c} failed, rerun it with --seed {}: {why}",
+            outcome.seed
+        )),
+    }
+}
+
+/// Runs each of `challenges` under a heading, then prints the summary, failing if any of them
+/// did. Under `--format json` it prints each one's record as it finishes instead.
+///
+/// They run in turn, or `--jobs` at a time if that's more than 1 and nothing's `--interactive`.
+pub fn run_all(challenges: impl IntoIterator<Item = u64>) -> Result<Vec<Outcome>> {
+    let options = crate::options::get();
+    let format = options.format;
+    let challenges: Vec<u64> = challenges.into_iter().collect();
+    let outcomes = match options.jobs {
+        jobs if jobs > 1 && !options.interactive => {
+            let (alone, together): (Vec<u64>, Vec<u64>) =
+                challenges.iter().partition(|c| ALONE.contains(c));
+            crate::progress::share();
+            let pool = rayon::ThreadPoolBuilder::new().num_threads(jobs).build()?;
+            let mut outcomes: Vec<Outcome> = pool.install(|| {
+                together
+                    .par_iter()
+                    .map(|&c| attempt_held(c, format))
+                    .collect()
+            });
+            outcomes.extend(alone.into_iter().map(|c| attempt_live(c, format)));
+            outcomes.sort_by_key(|o| o.challenge);
+            outcomes
+        }
+        _ => challenges
+            .into_iter()
+            .map(|c| attempt_live(c, format))
+            .collect(),
+    };
+    if format == Format::Text {
+        print!("{}", Summary(&outcomes));
+    }
+    let failed = outcomes
+        .iter()
+        .filter(|o| o.status != Status::Passed)
+        .count();
+    match failed {
+        0 => Ok(outcomes),
+        _ => Err(anyhow!("{failed} of {} challenges failed", outcomes.len())),
+    }
+}
+
+/// Runs challenge `c` with its output going straight out, under a heading or followed by its
+/// record
+fn attempt_live(c: u64, format: Format) -> Outcome {
+    match format {
+        Format::Text => {
+            println!("=== Challenge {c} ===");
+            attempt(c)
+        }
+        Format::Json => {
+            let outcome = attempt(c);
+            println!("{}", outcome.to_json());
+            outcome
+        }
+    }
+}
+
+/// Held while a challenge's output is printed, so that two finishing at once don't interleave
+static PRINTING: Mutex<()> = Mutex::new(());
+
+/// Runs challenge `c` with its output held back, then prints it in one go as [`attempt_live`]
+/// would have
+fn attempt_held(c: u64, format: Format) -> Outcome {
+    let (outcome, said) = crate::report::capture(|| attempt(c));
+    let _printing = PRINTING.lock().unwrap_or_else(PoisonError::into_inner);
+    match format {
+        Format::Text => print!("=== Challenge {c} ===\n{said}"),
+        Format::Json => {
+            eprint!("{said}");
+            println!("{}", outcome.to_json());
+        }
+    }
+    outcome
+}
+
+/// A table of how each challenge went, with a total at the bottom
+pub struct Summary<'a>(pub &'a [Outcome]);
+
+impl fmt::Display for Summary<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{:>9}  {:<6}  {:>10}", "Challenge", "Result", "Time")?;
+        for outcome in self.0 {
+            let (result, detail) = match &outcome.status {
+                Status::Passed => ("pass", String::new()),
+                // Just the first line, to keep the table readable
+                Status::Failed(why) => {
+                    let why = why.lines().next().unwrap_or_default();
+                    ("FAIL", format!("{why} (--seed {})", outcome.seed))
+                }
+            };
+            let time = format!("{:.2?}", outcome.elapsed);
+            let line = format!(
+                "{:>9}  {result:<6}  {time:>10}  {detail}",
+                outcome.challenge
+            );
+            writeln!(f, "{}", line.trim_end())?;
+        }
+        let passed = self.0.iter().filter(|o| o.status == Status::Passed).count();
+        let total: Duration = self.0.iter().map(|o| o.elapsed).sum();
+        writeln!(f, "{passed}/{} passed in {:.2?}", self.0.len(), total)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn summary() {
+        let outcomes = [
+            Outcome {
+                challenge: 1,
+                status: Status::Passed,
+                elapsed: Duration::from_millis(12),
+                report: Some(Report::new()),
+                seed: 1,
+            },
+            Outcome {
+                challenge: 47,
+                status: Status::Failed("assertion failed\nwith details".to_string()),
+                elapsed: Duration::from_millis(1500),
+                report: None,
+                seed: 42,
+            },
+        ];
+        assert_eq!(
+            Summary(&outcomes).to_string(),
+            "Challenge  Result        Time\n\
+             \x20       1  pass       12.00ms\n\
+             \x20      47  FAIL         1.50s  assertion failed (--seed 42)\n\
+             1/2 passed in 1.51s\n"
+        );
+        assert_eq!(
+            outcomes[0].to_json(),
+            "{\"challenge\":1,\"name\":\"Convert hex to base64\",\"status\":\"pass\",\
+             \"elapsed\":0.012,\"seed\":1,\"recovered\":{},\"checks\":{},\"counts\":{}}"
+        );
+        assert_eq!(
+            outcomes[1].to_json(),
+            "{\"challenge\":47,\"name\":\"Bleichenbacher's PKCS 1.5 Padding Oracle (Simple Case)\",\
+             \"status\":\"fail\",\"error\":\"assertion failed\\nwith details\",\"elapsed\":1.5,\
+             \"seed\":42}"
+        );
+
+        assert_eq!(attempt(1).status, Status::Passed);
+        assert!(attempt(1).report.unwrap().elapsed.is_some());
+        assert!(matches!(attempt(67).status, Status::Failed(_)));
+    }
+
+    mod unwritten {
+        crate::runner::register! {
+            set 9;
+            todo 67, "Something new";
+        }
+    }
+
+    #[test]
+    fn sets() {
+        let numbers = |s| set(s).unwrap().iter().map(|c| c.number).collect::<Vec<_>>();
+        assert_eq!(numbers(1), (1..=8).collect::<Vec<_>>());
+        assert_eq!(numbers(8), (57..=66).collect::<Vec<_>>());
+        assert!(set(8).unwrap().iter().all(|c| c.set == 8));
+        assert!(set(0).is_err());
+        assert!(set(9).is_err());
+        // Every challenge is listed once, in order
+        assert!(challenges().eq(1..=66));
+        assert_eq!(title(67), None);
+        assert_eq!(find(31).unwrap().speed, Some(Speed::Slow));
+        assert_eq!(
+            find(1).unwrap().to_string(),
+            "        1    1  done           fast   Convert hex to base64"
+        );
+        assert_eq!(
+            unwritten::CHALLENGES[0].to_string(),
+            "       67    9  unimplemented  -      Something new"
+        );
+    }
+}
@@ -0,0 +1,247 @@
+//! Plaintext scoring
+//!
+//! Candidate plaintexts are scored by comparing their unigram, bigram and trigram counts with
+//! those of a reference corpus using Pearson's chi-squared statistic. Lower scores are more
+//! plausible.
+//!
+//! Text is decoded as UTF-8 and reduced to a 29-symbol alphabet of case-folded ASCII letters,
+//! whitespace, "other printable character" and "non-ASCII letter" before counting, so any language
+//! with a corpus to hand can be scored; accented letters just show up as the last symbol. Control
+//! characters and invalid UTF-8 break up n-grams and carry a large penalty.
+//!
+//! When there's no corpus for the language at all, [`Language::Printable`] falls back to only
+//! checking that the bytes look like printable ASCII.
+#![allow(dead_code)]
+
+use crate::data;
+use crate::utils::*;
+use std::collections::HashMap;
+
+/// ASCII letters plus symbols for whitespace, other printable characters and non-ASCII letters
+const ALPHABET: usize = 29;
+/// Per-character penalty for control characters and invalid UTF-8
+const JUNK_PENALTY: f64 = 50.0;
+
+/// Largest n-gram statistic to take into account when scoring
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum NGram {
+    Unigram = 1,
+    Bigram = 2,
+    Trigram = 3,
+}
+
+/// Which kind of plaintext an attack expects to recover
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Language {
+    /// English, using War and Peace as the corpus
+    English,
+    /// Any other language, given the path to a corpus written in it
+    Corpus(String),
+    /// No statistics, just printable ASCII
+    Printable,
+}
+
+enum Symbol {
+    Text(usize),
+    Junk,
+}
+
+fn symbol(c: char) -> Symbol {
+    match c {
+        'a'..='z' => Symbol::Text(c as usize - 'a' as usize),
+        'A'..='Z' => Symbol::Text(c as usize - 'A' as usize),
+        c if c.is_whitespace() => Symbol::Text(26),
+        char::REPLACEMENT_CHARACTER => Symbol::Junk,
+        c if c.is_control() => Symbol::Junk,
+        c if c.is_alphabetic() => Symbol::Text(28),
+        _ => Symbol::Text(27),
+    }
+}
+
+/// Splits text into runs of the reduced alphabet, along with the count of junk characters
+fn runs(text: &[u8]) -> (Vec<Vec<usize>>, usize) {
+    let mut runs = vec![vec![]];
+    let mut junk = 0;
+    for c in String::from_utf8_lossy(text).chars() {
+        match symbol(c) {
+            Symbol::Text(s) => runs.last_mut().unwrap().push(s),
+            Symbol::Junk => {
+                junk += 1;
+                runs.push(vec![]);
+            }
+        }
+    }
+    (runs, junk)
+}
+
+fn ngram_index(window: &[usize]) -> usize {
+    window.iter().fold(0, |acc, s| acc * ALPHABET + s)
+}
+
+enum Model {
+    NGrams {
+        order: NGram,
+        /// Probability of each n-gram in the corpus, indexed by order - 1
+        tables: Vec<Vec<f64>>,
+        /// Stand-in probability for n-grams never seen in the corpus
+        floors: Vec<f64>,
+    },
+    Printable,
+}
+
+pub struct Scorer {
+    model: Model,
+}
+
+impl Scorer {
+    pub fn new(language: &Language, order: NGram) -> Result<Self> {
+        match language {
+            Language::English => Ok(Self::from_text(data::WAR_AND_PEACE.bytes()?, order)),
+            Language::Corpus(filename) => Self::from_file(filename, order),
+            Language::Printable => Ok(Self::printable()),
+        }
+    }
+
+    pub fn from_file(filename: &str, order: NGram) -> Result<Self> {
+        let text = std::fs::read(filename)?;
+        Ok(Self::from_text(&text, order))
+    }
+
+    pub fn from_text(corpus: &[u8], order: NGram) -> Self {
+        let (runs, _) = runs(corpus);
+        let mut tables = vec![];
+        let mut floors = vec![];
+        for n in 1..=order as usize {
+            let mut table = vec![0.0; ALPHABET.pow(n as u32)];
+            for run in runs.iter() {
+                for window in run.windows(n) {
+                    table[ngram_index(window)] += 1.0;
+                }
+            }
+            let total: f64 = table.iter().sum();
+            for v in table.iter_mut() {
+                *v /= total;
+            }
+            floors.push(0.1 / total.max(1.0));
+            tables.push(table);
+        }
+
+        Self {
+            model: Model::NGrams {
+                order,
+                tables,
+                floors,
+            },
+        }
+    }
+
+    pub fn printable() -> Self {
+        Self {
+            model: Model::Printable,
+        }
+    }
+
+    /// Scores a candidate plaintext, lower being more plausible
+    pub fn score(&self, candidate: &[u8]) -> f64 {
+        if candidate.is_empty() {
+            return f64::MAX;
+        }
+        match &self.model {
+            Model::NGrams {
+                order,
+                tables,
+                floors,
+            } => {
+                let (runs, junk) = runs(candidate);
+
+                let mut score = JUNK_PENALTY * junk as f64 / candidate.len() as f64;
+                for n in 1..=*order as usize {
+                    match chi_squared(&runs, n, &tables[n - 1], floors[n - 1]) {
+                        // Each statistic grows at a very different rate, so compare them on a log
+                        // scale
+                        Some(chi) => score += chi.ln_1p(),
+                        // Nothing printable at all is about as implausible as it gets
+                        None if n == 1 => return f64::MAX,
+                        None => {}
+                    }
+                }
+                score
+            }
+            // Fraction of bytes which wouldn't turn up in a text file
+            Model::Printable => {
+                candidate
+                    .iter()
+                    .filter(|b| !(b.is_ascii_graphic() || b.is_ascii_whitespace()))
+                    .count() as f64
+                    / candidate.len() as f64
+            }
+        }
+    }
+}
+
+/// Chi-squared statistic of the n-grams in `runs` against the corpus, per n-gram observed
+fn chi_squared(runs: &[Vec<usize>], n: usize, table: &[f64], floor: f64) -> Option<f64> {
+    let mut observed = HashMap::<usize, f64>::new();
+    for run in runs.iter() {
+        for window in run.windows(n) {
+            *observed.entry(ngram_index(window)).or_insert(0.0) += 1.0;
+        }
+    }
+    let total: f64 = observed.values().sum();
+    if total == 0.0 {
+        return None;
+    }
+
+    let mut seen_mass = 0.0;
+    let mut chi = 0.0;
+    for (k, o) in observed.iter() {
+        let p = table[*k].max(floor);
+        seen_mass += total * table[*k];
+        chi += (o - total * p).powi(2) / (total * p);
+    }
+    // Every n-gram we didn't see contributes (0 - E)^2 / E = E
+    chi += (total - seen_mass).max(0.0);
+
+    Some(chi / total)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn english_beats_noise() {
+        let scorer = Scorer::from_text(data::WAR_AND_PEACE.bytes().unwrap(), NGram::Trigram);
+        let english = b"Cooking MC's like a pound of bacon";
+        let shuffled = b"oCnkgoi M'C sleki a dpuno fo obacn";
+        let xored = xor_bytes(english, b"X");
+
+        assert!(scorer.score(english) < scorer.score(shuffled));
+        assert!(scorer.score(english) < scorer.score(&xored));
+    }
+
+    #[test]
+    fn short_text_all_orders() {
+        let english = b"ETAOIN SHRDLU jokes";
+        let xored = xor_bytes(english, &[0x20]);
+        for order in [NGram::Unigram, NGram::Bigram, NGram::Trigram] {
+            let scorer = Scorer::from_text(data::ALICE.bytes().unwrap(), order);
+            assert!(scorer.score(english) < scorer.score(&xored));
+        }
+    }
+
+    #[test]
+    fn other_languages() {
+        let corpus = "Der schnelle braune Fuchs springt über den faulen Hund. Die Würde des \
+            Menschen ist unantastbar. Sie zu achten und zu schützen ist Verpflichtung aller \
+            staatlichen Gewalt. Zwölf Boxkämpfer jagen Viktor quer über den großen Sylter Deich.";
+        let scorer = Scorer::from_text(corpus.as_bytes(), NGram::Bigram);
+        let german = "Größe und Würde".as_bytes();
+        let xored = xor_bytes(german, b"k");
+        assert!(scorer.score(german) < scorer.score(&xored));
+
+        let scorer = Scorer::new(&Language::Printable, NGram::Trigram).unwrap();
+        assert_eq!(scorer.score(b"Anything at all!"), 0.0);
+        assert!(scorer.score(b"Anything at all!") < scorer.score(&xor_bytes(german, b"k")));
+    }
+}
@@ -12,19 +12,19 @@
 //! ```
 //! So go ahead and make that happen. You'll need to use this code for the rest of the exercises.
 
+use crate::report::say;
 use crate::utils::*;
 use anyhow::Result;
 
-pub fn main() -> Result<()> {
+pub fn main() -> Result<Report> {
     let hex = "49276d206b696c6c696e6720796f757220627261696e206c696b65206120706f69736f6e6f7573206d757368726f6f6d";
     let b64 = "SSdtIGtpbGxpbmcgeW91ciBicmFpbiBsaWtlIGEgcG9pc29ub3VzIG11c2hyb29t";
 
     let b64_attempt = bytes_to_b64_str(&hex_to_bytes(hex)?);
-    println!("Target: {b64}");
-    println!("Actual: {b64_attempt}");
-    assert_eq!(b64, b64_attempt);
+    say!("Target: {b64}");
+    say!("Actual: {b64_attempt}");
 
-    Ok(())
+    Ok(Report::new().check("base64", b64, b64_attempt))
 }
 
 #[cfg(test)]
@@ -32,6 +32,10 @@ mod tests {
     use super::*;
     #[test]
     fn one() {
-        main().unwrap();
+        let report = main().and_then(Report::verify).unwrap();
+        assert_eq!(
+            report.get("base64"),
+            Some(&"SSdtIGtpbGxpbmcgeW91ciBicmFpbiBsaWtlIGEgcG9pc29ub3VzIG11c2hyb29t".into())
+        );
     }
 }
@@ -17,10 +17,11 @@
 //! 746865206b696420646f6e277420706c6179
 //! ```
 
+use crate::report::say;
 use crate::utils::*;
 use anyhow::Result;
 
-pub fn main() -> Result<()> {
+pub fn main() -> Result<Report> {
     let input = "1c0111001f010100061a024b53535009181c";
     let xor = "686974207468652062756c6c277320657965";
     let target = "746865206b696420646f6e277420706c6179";
@@ -29,11 +30,10 @@ pub fn main() -> Result<()> {
 
     let output_bytes = xor_bytes(&input_b, &xor_b);
     let output_hex = bytes_to_hex(&output_bytes);
-    println!("Target: {target}");
-    println!("Actual: {output_hex}");
-    assert_eq!(output_hex, target);
+    say!("Target: {target}");
+    say!("Actual: {output_hex}");
 
-    Ok(())
+    Ok(Report::new().check("xor", hex_to_bytes(target)?, output_bytes))
 }
 
 #[cfg(test)]
@@ -41,6 +41,6 @@ mod tests {
     use super::*;
     #[test]
     fn two() {
-        main().unwrap();
+        main().and_then(Report::verify).unwrap();
     }
 }
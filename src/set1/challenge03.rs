@@ -13,52 +13,31 @@
 //! Achievement Unlocked
 //! You now have our permission to make "ETAOIN SHRDLU" jokes on Twitter.
 
-use std::collections::HashMap;
-
+use crate::options;
+use crate::report::say;
+use crate::scoring::{Language, NGram, Scorer};
 use crate::utils::*;
 use anyhow::Result;
-pub fn main() -> Result<()> {
+pub fn main() -> Result<Report> {
     let input = "1b37373331363f78151b7f2b783431333d78397828372d363c78373e783a393b3736";
     let output = three_calc(input)?;
-    println!("{output}");
-    Ok(())
+    say!("{output}");
+    Ok(Report::new().unverified("plaintext", output))
 }
 
 fn three_calc(input: &str) -> Result<String> {
     let input_bytes = hex_to_bytes(input)?;
 
-    let text_freq_map = freq_map_from_file("./data/wap.txt")?;
-    let mut scores = HashMap::new();
-
-    for x in 0..255_u8 {
-        let xored = xor_bytes(&input_bytes, &[x]);
-        // If it can be decoed, then work with it
-        if let Ok(xor_str) = std::str::from_utf8(&xored) {
-            let actual_freq_map = freq_map_from_str(xor_str)?;
-            let score = kl_divergence(&actual_freq_map, &text_freq_map);
-            //println!("Score: {} {xor_str}", score);
-            scores.insert(x, score);
-        }
-        //let xor_string = bytes_to_b64_str(&xored);
-        //let trial_freq_map = freq_map_from_str(xor_string)?;
-
-        //let score = text_score();
+    let scorer = Scorer::new(&Language::English, NGram::Trigram)?;
+    let candidates = crack_single_xor(&input_bytes, &scorer, 5);
+    let best = &candidates[0];
+    say!("Best score: {:?}", (best.key, best.score));
+    say!("{} = {}", best.key, char::from(best.key));
+    if options::get().alternatives && is_ambiguous(&candidates) {
+        say!("Best key is ambiguous, the top few were:");
+        print_candidates(&candidates);
     }
-    let best_score =
-        scores
-            .iter()
-            .fold((0_u8, 1000.0), |acc, x| match *x.1 < acc.1 && *x.1 != 0.0 {
-                true => (*x.0, *x.1),
-                false => acc,
-            });
-    println!("Best score: {best_score:?}");
-    println!(
-        "{} = {}",
-        best_score.0,
-        char::from_u32(best_score.0 as u32).unwrap()
-    );
-    let xored = xor_bytes(&input_bytes, &[best_score.0]);
-    let xor_str = std::str::from_utf8(&xored).unwrap();
+    let xor_str = std::str::from_utf8(&best.plaintext)?;
     Ok(xor_str.into())
 }
 
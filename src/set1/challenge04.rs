@@ -6,73 +6,62 @@
 //!
 //! (Your code from #3 should help.)
 
-use std::collections::HashMap;
-
+use crate::data;
+use crate::options;
+use crate::report::say;
+use crate::scoring::{Language, NGram, Scorer};
 use crate::utils::*;
 use anyhow::Result;
+use rayon::prelude::*;
 
-pub fn main() -> Result<()> {
-    four_result()?;
-    Ok(())
+pub fn main() -> Result<Report> {
+    let plaintext = four_result()?;
+    Ok(Report::new().unverified("plaintext", plaintext))
 }
 
 fn four_result() -> Result<String> {
-    let text_freq_map = freq_map_from_file("./data/wap.txt")?;
-    let mut results = Vec::<(f64, char, String)>::new();
+    let scorer = Scorer::new(&Language::English, NGram::Trigram)?;
 
     // Read file
-    let inputs = read_file("./data/4.txt")?;
-    for i in inputs {
-        if let Ok(output) = four_calc(&i, &text_freq_map) {
-            results.push(output);
-        }
-    }
-    // Get best score of these
-    let top_result =
-        results
-            .iter()
-            .fold((1000.0, 'a', String::new()), |acc, x| match x.0 < acc.0 {
-                true => (*x).clone(),
-                false => acc,
-            });
+    let inputs = data::CHALLENGE_4.hex_lines()?;
+    let results = rank_lines(&inputs, &scorer);
+    let (line, top_result) = results
+        .first()
+        .ok_or_else(|| anyhow::anyhow!("No decryptable lines"))?;
 
-    println!("{top_result:?}");
-    let top_str = top_result.2;
-    Ok(top_str)
+    say!(
+        "{:?}",
+        (
+            line,
+            top_result.score,
+            char::from(top_result.key),
+            String::from_utf8_lossy(&top_result.plaintext)
+        )
+    );
+    let candidates: Vec<Candidate> = results.iter().take(5).map(|(_, c)| c.clone()).collect();
+    if options::get().alternatives && is_ambiguous(&candidates) {
+        say!("Best line is ambiguous, the top few were:");
+        print_candidates(&candidates);
+    }
+    let top_str = std::str::from_utf8(&top_result.plaintext)?;
+    Ok(top_str.into())
 }
 
-fn four_calc(input: &str, ref_map: &HashMap<char, f64>) -> Result<(f64, char, String)> {
-    let input_bytes = hex_to_bytes(input)?;
-
-    let mut scores = HashMap::new();
-
-    for x in 0..255_u8 {
-        let xored = xor_bytes(&input_bytes, &[x]);
-        // If it can be decoed, then work with it
-        if let Ok(xor_str) = std::str::from_utf8(&xored) {
-            let actual_freq_map = freq_map_from_str(xor_str)?;
-            let score = kl_divergence(&actual_freq_map, ref_map);
-            //println!("Score: {} {xor_str}", score);
-            scores.insert(x, score);
-        }
-        //let xor_string = bytes_to_b64_str(&xored);
-        //let trial_freq_map = freq_map_from_str(xor_string)?;
+/// Cracks every line in parallel, returning the line numbers with their best candidates, best first
+fn rank_lines(inputs: &[Vec<u8>], scorer: &Scorer) -> Vec<(usize, Candidate)> {
+    let mut results: Vec<(usize, Candidate)> = inputs
+        .par_iter()
+        .enumerate()
+        .filter_map(|(i, input)| four_calc(input, scorer).ok().map(|c| (i, c)))
+        .collect();
+    results.sort_by(|x, y| x.1.score.total_cmp(&y.1.score));
+    results
+}
 
-        //let score = text_score();
-    }
-    let best_score =
-        scores
-            .iter()
-            .fold((0_u8, 1000.0), |acc, x| match *x.1 < acc.1 && *x.1 != 0.0 {
-                true => (*x.0, *x.1),
-                false => acc,
-            });
-    //    println!("Best score: {best_score:?}");
-    let c = char::from_u32(best_score.0 as u32).unwrap();
-    //   println!("{} = {}", best_score.0, c);
-    let xored = xor_bytes(&input_bytes, &[best_score.0]);
-    let xor_str = std::str::from_utf8(&xored)?;
-    Ok((best_score.1, c, xor_str.into()))
+fn four_calc(input: &[u8], scorer: &Scorer) -> Result<Candidate> {
+    crack_single_xor(input, scorer, 1)
+        .pop()
+        .ok_or_else(|| anyhow::anyhow!("No candidate keys"))
 }
 
 #[cfg(test)]
@@ -84,4 +73,13 @@ mod tests {
         let target = "Now that the party is jumping\n";
         assert_eq!(&top_string, target);
     }
+
+    #[test]
+    fn best_line() {
+        let scorer = Scorer::new(&Language::English, NGram::Trigram).unwrap();
+        let inputs = data::CHALLENGE_4.hex_lines().unwrap();
+        let results = rank_lines(&inputs, &scorer);
+        assert_eq!(results.len(), inputs.len());
+        assert_eq!((results[0].0, results[0].1.key), (170, b'5'));
+    }
 }
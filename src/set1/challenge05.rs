@@ -17,14 +17,18 @@
 //! ```
 //! Encrypt a bunch of stuff using your repeating-key XOR function. Encrypt your mail. Encrypt your password file. Your .sig file. Get a feel for it. I promise, we aren't wasting your time with this.
 
+use crate::report::say;
 use crate::utils::*;
 use anyhow::Result;
 
-pub fn main() -> Result<()> {
+/// The ciphertext the challenge says it should come out to
+const TARGET: &str = "0b3637272a2b2e63622c2e69692a23693a2a3c6324202d623d63343c2a26226324272765272a282b2f20430a652e2c652a3124333a653e2b2027630c692b20283165286326302e27282f";
+
+pub fn main() -> Result<Report> {
     let result = five_calc().unwrap();
-    println!("{result}");
+    say!("{result}");
 
-    Ok(())
+    Ok(Report::new().check("ciphertext", hex_to_bytes(TARGET)?, hex_to_bytes(&result)?))
 }
 
 fn five_calc() -> Result<String> {
@@ -47,7 +51,6 @@ mod test {
     #[test]
     fn five() {
         let result = five_calc().unwrap();
-        let target = "0b3637272a2b2e63622c2e69692a23693a2a3c6324202d623d63343c2a26226324272765272a282b2f20430a652e2c652a3124333a653e2b2027630c692b20283165286326302e27282f";
-        assert_eq!(&result, target);
+        assert_eq!(result, TARGET);
     }
 }
@@ -30,69 +30,162 @@
 //!  No, that's not a mistake.
 //!  We get more tech support questions for this challenge than any of the other ones. We promise, there aren't any blatant errors in this text. In particular: the "wokka wokka!!!" edit distance really is 37.
 
-use std::collections::HashMap;
+use std::io::Read;
+use std::ops::Range;
 
+use crate::data;
+use crate::report::say;
+use crate::scoring::{Language, NGram, Scorer};
 use crate::utils::*;
 use anyhow::Result;
 
-pub fn main() -> Result<()> {
-    let bytes = read_base64_file("./data/6.txt")?;
-    let keysize = get_keysize(&bytes);
+pub fn main() -> Result<Report> {
+    let bytes = data::CHALLENGE_6.base64()?;
+    let (key, decoded) = crack_repeating_xor(&bytes[..], 1..40)?;
 
+    say!("Key: {}", String::from_utf8_lossy(&key));
+    say!("Decoded: {}", String::from_utf8_lossy(&decoded));
+    Ok(Report::new()
+        .unverified("key", key)
+        .unverified("plaintext", String::from_utf8_lossy(&decoded).into_owned()))
+}
+
+/// How many of the most likely key lengths to break before picking the best
+const KEYSIZE_TRIES: usize = 3;
+/// Most blocks to compare with each other when ranking key lengths
+const MAX_BLOCKS: usize = 8;
+
+/// Breaks repeating-key XOR on everything in `reader`, trying key lengths in `key_len_range`
+///
+/// Returns the key and the decrypted plaintext
+pub fn crack_repeating_xor<R: Read>(
+    mut reader: R,
+    key_len_range: Range<usize>,
+) -> Result<(Vec<u8>, Vec<u8>)> {
+    let mut bytes = vec![];
+    reader.read_to_end(&mut bytes)?;
+    // Need at least two blocks of each keysize to compare
+    let key_len_range = key_len_range.start.max(1)..key_len_range.end.min(bytes.len() / 2 + 1);
+    if key_len_range.is_empty() {
+        return Err(anyhow::anyhow!(
+            "Ciphertext of {} bytes is too short for the key lengths requested",
+            bytes.len()
+        ));
+    }
+    let keysizes = rank_keysizes(&bytes, key_len_range.clone());
+    say!(
+        "Top keysizes: {:?}",
+        &keysizes[..KEYSIZE_TRIES.min(keysizes.len())]
+    );
+    // Multiples of the real key length score just as well, and with short ciphertexts they can
+    // crowd it out entirely, so try their divisors too
+    let mut tries: Vec<usize> = vec![];
+    for (keysize, _) in keysizes.into_iter().take(KEYSIZE_TRIES) {
+        for d in (1..=keysize).filter(|d| keysize.is_multiple_of(*d) && key_len_range.contains(d)) {
+            if !tries.contains(&d) {
+                tries.push(d);
+            }
+        }
+    }
+
+    // Each chunk is every keysize-th byte, so only single letter frequencies mean anything
+    let column_scorer = Scorer::new(&Language::English, NGram::Unigram)?;
+    // ...but the full decryption can be judged on everything
+    let text_scorer = Scorer::new(&Language::English, NGram::Trigram)?;
+
+    let mut best: Option<(f64, Vec<u8>, Vec<u8>)> = None;
+    for keysize in tries {
+        let key = shortest_period(&crack_with_keysize(&bytes, keysize, &column_scorer)?);
+        let decoded = xor_bytes(&bytes, &key);
+        let score = text_scorer.score(&decoded);
+        // A multiple of the real key length decrypts to the same thing, so keep the shorter key
+        let better = match &best {
+            None => true,
+            Some((s, k, _)) => score < *s || (score == *s && key.len() < k.len()),
+        };
+        if better {
+            best = Some((score, key, decoded));
+        }
+    }
+
+    let (_, key, decoded) = best.unwrap();
+    Ok((key, decoded))
+}
+
+fn crack_with_keysize(bytes: &[u8], keysize: usize, scorer: &Scorer) -> Result<Vec<u8>> {
     // Now slice the bytes into pieces xored with the same key
     // i.e. block 1: 0, keysize, 2*keysize, ...
     //      block 2: 1, keysize+1, 2*keysize+1, ...
-    let whole_blocks: usize = bytes.len() / keysize;
-    let key_chunks = (0..keysize)
+    (0..keysize)
         .map(|key_index| {
-            (0..whole_blocks)
-                .map(|block_num| bytes[block_num * keysize + key_index])
-                .collect::<Vec<u8>>()
+            let chunk = bytes
+                .iter()
+                .skip(key_index)
+                .step_by(keysize)
+                .copied()
+                .collect::<Vec<u8>>();
+            crack_single_byte_xor(&chunk, scorer)
         })
-        .collect::<Vec<Vec<u8>>>();
-
-    let ref_map = freq_map_from_file("./data/wap.txt")?;
-
-    let key = key_chunks
-        .iter()
-        .map(|chunk| crack_single_byte_xor(chunk, &ref_map))
-        .collect::<Result<Vec<u8>>>()?;
-    println!("Key: {}", std::str::from_utf8(&key).unwrap());
-    // Now crack each block
-    let decoded = bytes
-        .iter()
-        .enumerate()
-        .map(|(i, v)| v ^ key[i % keysize])
-        .collect::<Vec<u8>>();
-
-    println!("Decoded: {}", std::str::from_utf8(&decoded).unwrap());
-    Ok(())
+        .collect()
 }
 
-fn get_keysize(input: &[u8]) -> usize {
-    let mut map = HashMap::new();
-    for keysize in 1..40 {
-        let ham = get_hamming_with_keysize(input, keysize);
-        map.insert(keysize, ham / keysize as f64);
-    }
-    println!("{map:?}");
-    let top = map.iter().fold((0, 50.0), |acc, (x, v)| match *v < acc.1 {
-        true => (*x, *v),
-        false => acc,
-    });
-    println!("top: {top:?}");
-    top.0
+/// Shortens a key which is just a shorter key repeated, e.g. "ICEICE" -> "ICE"
+fn shortest_period(key: &[u8]) -> Vec<u8> {
+    let period = (1..=key.len())
+        .find(|p| key.len().is_multiple_of(*p) && key.chunks(*p).all(|c| c == &key[..*p]))
+        .unwrap_or(key.len());
+    key[..period].to_vec()
+}
+
+/// Ranks key lengths by their normalised Hamming distance, averaged over every pair of the first
+/// few blocks, most likely first
+fn rank_keysizes(input: &[u8], key_len_range: Range<usize>) -> Vec<(usize, f64)> {
+    let mut ranked: Vec<(usize, f64)> = key_len_range
+        .map(|keysize| (keysize, get_hamming_with_keysize(input, keysize)))
+        .collect();
+    ranked.sort_by(|a, b| a.1.total_cmp(&b.1));
+    ranked
 }
 
 fn get_hamming_with_keysize(input: &[u8], keysize: usize) -> f64 {
-    let block = input.iter();
-    // Max block numbers
-    let l: usize = (block.len() - keysize) / keysize;
-    let blockshift = input.iter().skip(keysize).take(keysize * l);
-
-    let s = std::iter::zip(block, blockshift)
-        .map(|(x, y)| x ^ y)
-        .map(ones)
-        .sum::<u64>();
-    s as f64 / (l as f64)
+    let blocks: Vec<&[u8]> = input.chunks_exact(keysize).take(MAX_BLOCKS).collect();
+    let mut total = 0;
+    let mut pairs = 0;
+    for (i, a) in blocks.iter().enumerate() {
+        for b in blocks.iter().skip(i + 1) {
+            total += hamming_bytes(a, b);
+            pairs += 1;
+        }
+    }
+    total as f64 / (pairs * keysize) as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn break_repeating_xor() {
+        let bytes = data::CHALLENGE_6.base64().unwrap();
+        let (key, decoded) = crack_repeating_xor(&bytes[..], 1..40).unwrap();
+        assert_eq!(key, b"Terminator X: Bring the noise");
+        assert!(decoded.starts_with(b"I'm back and I'm ringin' the bell"));
+    }
+
+    #[test]
+    fn short_ciphertext() {
+        // Only ~30 repeats of the key, and it's easy to get a multiple of the key length
+        let plaintext = &data::ALICE.bytes().unwrap()[5000..5150];
+        let ciphertext = xor_bytes(plaintext, b"ALICE");
+        let (key, decoded) = crack_repeating_xor(&ciphertext[..], 2..21).unwrap();
+        assert_eq!(key, b"ALICE");
+        assert_eq!(decoded, plaintext);
+    }
+
+    #[test]
+    fn periods() {
+        assert_eq!(shortest_period(b"ICEICEICE"), b"ICE");
+        assert_eq!(shortest_period(b"ICEICEIC"), b"ICEICEIC");
+        assert_eq!(shortest_period(b"a"), b"a");
+    }
 }
@@ -16,17 +16,41 @@
 //! ECB working in code for a reason. You'll need it a lot later on, and not just for attacking
 //! ECB.
 
+use crate::aes::aes_ecb_decrypt;
+use crate::data;
+use crate::report::say;
 use crate::utils::*;
 use anyhow::Result;
-use openssl::symm::{decrypt, Cipher};
 
-pub fn main() -> Result<()> {
+pub fn main() -> Result<Report> {
     let key = b"YELLOW SUBMARINE";
-    let ciphertext = read_base64_file("./data/7.txt")?;
-    let cipher = Cipher::aes_128_ecb();
+    let ciphertext = data::CHALLENGE_7.base64()?;
 
-    let plaintext = decrypt(cipher, key, None, &ciphertext)?;
-    println!("{}", std::str::from_utf8(&plaintext)?);
+    let plaintext = seven_calc(&ciphertext, key)?;
+    let plaintext = std::str::from_utf8(&plaintext)?;
+    say!("{plaintext}");
 
-    Ok(())
+    Ok(Report::new().unverified("plaintext", plaintext))
+}
+
+fn seven_calc(ciphertext: &[u8], key: &[u8]) -> Result<Vec<u8>> {
+    let padded = aes_ecb_decrypt(ciphertext, key)?;
+    Ok(pkcs7_unpad(&padded)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use openssl::symm::{decrypt, Cipher};
+
+    #[test]
+    fn matches_openssl() {
+        let key = b"YELLOW SUBMARINE";
+        let ciphertext = data::CHALLENGE_7.base64().unwrap();
+        let expected = decrypt(Cipher::aes_128_ecb(), key, None, &ciphertext).unwrap();
+
+        let plaintext = seven_calc(&ciphertext, key).unwrap();
+        assert!(plaintext.starts_with(b"I'm back and I'm ringin' the bell"));
+        assert_eq!(plaintext, expected);
+    }
 }
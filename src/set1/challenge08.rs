@@ -9,40 +9,94 @@
 //! Remember that the problem with ECB is that it is stateless and deterministic; the same 16 byte
 //! plaintext block will always produce the same 16 byte ciphertext.
 
+use crate::data;
+use crate::report::say;
 use crate::utils::*;
 use anyhow::Result;
 use std::collections::HashMap;
 
-pub fn main() -> Result<()> {
-    let ciphertexts = read_base64_lines("./data/8.txt")?;
+pub fn main() -> Result<Report> {
+    let ciphertexts = data::CHALLENGE_8.hex_lines()?;
     let chunk_size = 16;
 
+    let mut found = Report::new();
     for (line_num, t) in ciphertexts.iter().enumerate() {
-        match is_unique(t, chunk_size) {
-            true => {}
-            false => println!("Line: {line_num}"),
+        let report = ecb_report(t, chunk_size);
+        if report.is_ecb() {
+            say!("Line: {line_num}");
+            say!("{report}");
+            found = found.unverified("line", line_num);
         }
     }
 
-    Ok(())
+    Ok(found)
 }
 
-pub fn is_unique(text: &[u8], chunk_size: usize) -> bool {
-    let mut map = HashMap::new();
-
-    let mut v = vec![0; chunk_size];
-    for (i, b) in text.iter().enumerate() {
-        let counter = i % chunk_size;
-        v[counter] = *b;
-
-        if counter == chunk_size - 1 {
-            match map.insert(v.clone(), 1) {
-                None => {}
-                Some(_) => return false,
-            }
+/// What repeats there are in a ciphertext when split into blocks
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct EcbReport {
+    pub block_size: usize,
+    pub blocks: usize,
+    /// Each repeated block, along with every block offset it appears at
+    pub repeats: Vec<(Vec<u8>, Vec<usize>)>,
+}
+
+impl EcbReport {
+    /// Number of blocks which are a repeat of an earlier one
+    pub fn duplicates(&self) -> usize {
+        self.repeats
+            .iter()
+            .map(|(_, offsets)| offsets.len() - 1)
+            .sum()
+    }
+
+    /// Any repeated block at all is a dead giveaway
+    pub fn is_ecb(&self) -> bool {
+        !self.repeats.is_empty()
+    }
+}
+
+impl std::fmt::Display for EcbReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "{} duplicate blocks out of {} ({}-byte blocks)",
+            self.duplicates(),
+            self.blocks,
+            self.block_size
+        )?;
+        for (block, offsets) in self.repeats.iter() {
+            writeln!(f, "  {} at blocks {:?}", bytes_to_hex(block), offsets)?;
         }
+        Ok(())
+    }
+}
+
+/// Splits `text` into `block_size` blocks and finds all which repeat, ignoring any partial block
+/// at the end
+pub fn ecb_report(text: &[u8], block_size: usize) -> EcbReport {
+    let mut map: HashMap<&[u8], Vec<usize>> = HashMap::new();
+    let blocks = text.chunks_exact(block_size);
+    let n = blocks.len();
+    for (i, block) in blocks.enumerate() {
+        map.entry(block).or_default().push(i);
     }
-    true
+    let mut repeats: Vec<(Vec<u8>, Vec<usize>)> = map
+        .into_iter()
+        .filter(|(_, offsets)| offsets.len() > 1)
+        .map(|(block, offsets)| (block.to_vec(), offsets))
+        .collect();
+    repeats.sort_by_key(|(_, offsets)| offsets[0]);
+
+    EcbReport {
+        block_size,
+        blocks: n,
+        repeats,
+    }
+}
+
+pub fn is_unique(text: &[u8], chunk_size: usize) -> bool {
+    !ecb_report(text, chunk_size).is_ecb()
 }
 
 #[cfg(test)]
@@ -55,4 +109,26 @@ mod tests {
         assert!(is_unique(&text, 2));
         assert!(!is_unique(&text, 3));
     }
+
+    #[test]
+    fn report() {
+        // Eight-byte blocks, with a trailing partial block
+        let text = b"DESBLOCKsomethinDESBLOCKDESBLOCKelse!";
+        let report = ecb_report(text, 8);
+        assert_eq!(report.blocks, 4);
+        assert_eq!(report.duplicates(), 2);
+        assert_eq!(report.repeats, vec![(b"DESBLOCK".to_vec(), vec![0, 2, 3])]);
+    }
+
+    #[test]
+    fn find_line() {
+        let ciphertexts = data::CHALLENGE_8.hex_lines().unwrap();
+        let detected: Vec<usize> = ciphertexts
+            .iter()
+            .enumerate()
+            .filter(|(_, l)| ecb_report(l, 16).is_ecb())
+            .map(|(i, _)| i)
+            .collect();
+        assert_eq!(detected, vec![132]);
+    }
 }
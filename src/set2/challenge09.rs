@@ -15,16 +15,18 @@
 //!
 //! "YELLOW SUBMARINE\x04\x04\x04\x04"
 
+use crate::report::say;
+use crate::report::Report;
 use anyhow::Result;
 
-pub fn main() -> Result<()> {
+pub fn main() -> Result<Report> {
     let unpadded = b"YELLOW SUBMARINE";
     let padded = &pkcs7_pad(unpadded, 20);
 
-    println!("Unpadded: {unpadded:?}");
-    println!("Padded: {padded:?}");
+    say!("Unpadded: {unpadded:?}");
+    say!("Padded: {padded:?}");
 
-    Ok(())
+    Ok(Report::new().check("padded", b"YELLOW SUBMARINE\x04\x04\x04\x04", &padded[..]))
 }
 
 pub fn pkcs7_pad(input: &[u8], block: usize) -> Vec<u8> {
@@ -20,21 +20,24 @@
 //! Do not use OpenSSL's CBC code to do CBC mode, even to verify your results. What's the point of
 //! even doing this stuff if you aren't going to learn from it?
 
-use crate::utils::*;
+use crate::data;
+use crate::report::say;
+use crate::report::Report;
 use anyhow::Result;
 use openssl::symm::{Cipher, Crypter, Mode};
 
-pub fn main() -> Result<()> {
-    let ciphertext = read_base64_file("./data/10.txt")?;
+pub fn main() -> Result<Report> {
+    let ciphertext = data::CHALLENGE_10.base64()?;
     let key = b"YELLOW SUBMARINE";
 
     //let ciphertext = pkcs7_pad(&ciphertext, keysize);
 
     let decrypted = cbc_decrypt(&ciphertext, key, None)?;
 
-    println!("{}", std::str::from_utf8(&decrypted)?);
+    let plaintext = std::str::from_utf8(&decrypted)?;
+    say!("{plaintext}");
 
-    Ok(())
+    Ok(Report::new().unverified("plaintext", plaintext))
 }
 
 pub fn cbc_decrypt(
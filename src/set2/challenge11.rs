@@ -24,18 +24,92 @@ pub enum Mode {
     Ecb,
 }
 
+use crate::report::say;
 use crate::utils::*;
 use anyhow::Result;
-use rand::{prelude::*, Rng};
+use rand::Rng;
 
-pub fn main() -> Result<()> {
+pub fn main() -> Result<Report> {
+    let mut rng = crate::utils::rng();
     let input = b"YELLOW SUBMARINEYELLOW SUBMARINEYELLOW SUBMARINEYELLOW SUBMARINE";
-    let (encrypted, mode) = encryption_oracle(input)?;
+    let (encrypted, mode) = encryption_oracle(input, &mut rng)?;
     let detected_mode = detect_mode(&encrypted);
-    println!("Encrypted: {:?}", encrypted);
-    println!("Mode: {:?}", mode);
-    println!("Detected mode: {:?}", detected_mode);
-    Ok(())
+    say!("Encrypted: {:?}", encrypted);
+    say!("Mode: {:?}", mode);
+    say!("Detected mode: {:?}", detected_mode);
+    let mut report = Report::new().check("mode", format!("{mode:?}"), format!("{detected_mode:?}"));
+
+    // How well does this work in general? With 5-10 random bytes in front, 43 bytes of the same
+    // thing always fills two aligned blocks, while 32 bytes never does and ECB goes unnoticed
+    for l in [43, 32] {
+        let confusion = detection_experiment(&vec![b'A'; l], 1000, &mut rng)?;
+        say!("\nInput of {} repeated bytes:", l);
+        say!("{}", confusion);
+        let right = confusion.ecb_as_ecb + confusion.cbc_as_cbc;
+        report = report.count(&format!("right with {l} bytes"), right as u64);
+    }
+    Ok(report)
+}
+
+/// Tally of actual against detected modes over many runs of the oracle
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Confusion {
+    pub ecb_as_ecb: usize,
+    pub ecb_as_cbc: usize,
+    pub cbc_as_ecb: usize,
+    pub cbc_as_cbc: usize,
+}
+
+impl Confusion {
+    fn record(&mut self, actual: Mode, detected: Mode) {
+        match (actual, detected) {
+            (Mode::Ecb, Mode::Ecb) => self.ecb_as_ecb += 1,
+            (Mode::Ecb, Mode::Cbc) => self.ecb_as_cbc += 1,
+            (Mode::Cbc, Mode::Ecb) => self.cbc_as_ecb += 1,
+            (Mode::Cbc, Mode::Cbc) => self.cbc_as_cbc += 1,
+        }
+    }
+
+    pub fn total(&self) -> usize {
+        self.ecb_as_ecb + self.ecb_as_cbc + self.cbc_as_ecb + self.cbc_as_cbc
+    }
+
+    /// Fraction of runs where the detected mode was correct
+    pub fn accuracy(&self) -> f64 {
+        (self.ecb_as_ecb + self.cbc_as_cbc) as f64 / self.total() as f64
+    }
+}
+
+impl std::fmt::Display for Confusion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "             detected ECB  detected CBC")?;
+        writeln!(
+            f,
+            "actual ECB   {:>12}  {:>12}",
+            self.ecb_as_ecb, self.ecb_as_cbc
+        )?;
+        writeln!(
+            f,
+            "actual CBC   {:>12}  {:>12}",
+            self.cbc_as_ecb, self.cbc_as_cbc
+        )?;
+        write!(
+            f,
+            "Accuracy: {:.1}% over {} runs",
+            100.0 * self.accuracy(),
+            self.total()
+        )
+    }
+}
+
+/// Runs the oracle on `input` `runs` times, and keeps track of how often the mode was detected
+pub fn detection_experiment(input: &[u8], runs: usize, rng: &mut impl Rng) -> Result<Confusion> {
+    let mut confusion = Confusion::default();
+    for _ in 0..runs {
+        let (encrypted, mode) = encryption_oracle(input, rng)?;
+        confusion.record(mode, detect_mode(&encrypted));
+    }
+    Ok(confusion)
 }
 
 pub fn detect_mode(ciphertext: &[u8]) -> Mode {
@@ -45,12 +119,10 @@ pub fn detect_mode(ciphertext: &[u8]) -> Mode {
     }
 }
 
-pub fn encryption_oracle(input: &[u8]) -> Result<(Vec<u8>, Mode)> {
-    let mut rng = rand::thread_rng();
-
-    let key = random_key(16, &mut rng);
-    let prepend_bytes = random_bytes(5, 10, &mut rng);
-    let append_bytes = random_bytes(5, 10, &mut rng);
+pub fn encryption_oracle(input: &[u8], rng: &mut impl Rng) -> Result<(Vec<u8>, Mode)> {
+    let key = random_key(16, rng);
+    let prepend_bytes = random_bytes(5, 10, rng);
+    let append_bytes = random_bytes(5, 10, rng);
 
     let mut modified_input = vec![];
     modified_input.extend_from_slice(&prepend_bytes);
@@ -62,21 +134,22 @@ pub fn encryption_oracle(input: &[u8]) -> Result<(Vec<u8>, Mode)> {
         false => Mode::Ecb,
     };
 
+    let padded = pkcs7_pad(&modified_input, 16);
     let encrypted = match mode {
-        Mode::Cbc => cbc_encrypt(input, &key, None)?,
-        Mode::Ecb => ecb_encrypt(input, &key, None)?,
+        Mode::Cbc => cbc_encrypt(&padded, &key, Some(&random_key(16, rng)))?,
+        Mode::Ecb => ecb_encrypt(&padded, &key, None)?,
     };
 
     Ok((encrypted, mode))
 }
 
-pub fn random_key(l: usize, rng: &mut ThreadRng) -> Vec<u8> {
+pub fn random_key(l: usize, rng: &mut impl Rng) -> Vec<u8> {
     let mut v = vec![0; l];
     rng.fill(&mut v[..l]);
     v
 }
 
-pub fn random_bytes(a: usize, b: usize, rng: &mut ThreadRng) -> Vec<u8> {
+pub fn random_bytes(a: usize, b: usize, rng: &mut impl Rng) -> Vec<u8> {
     let len: usize = a + rng.gen::<usize>() % (b - a);
     let mut v = vec![0; len];
     rng.fill(&mut v[..len]);
@@ -88,11 +161,25 @@ mod tests {
     use super::*;
     #[test]
     fn oracle_detector() {
+        let mut rng = rand::thread_rng();
         for _ in 0..100 {
             let input = b"YELLOW SUBMARINEYELLOW SUBMARINEYELLOW SUBMARINEYELLOW SUBMARINE";
-            let (encrypted, mode) = encryption_oracle(input).unwrap();
+            let (encrypted, mode) = encryption_oracle(input, &mut rng).unwrap();
             let detected_mode = detect_mode(&encrypted);
             assert_eq!(mode, detected_mode);
         }
     }
+
+    #[test]
+    fn accuracy() {
+        let mut rng = rand::thread_rng();
+        let confusion = detection_experiment(&[b'A'; 43], 200, &mut rng).unwrap();
+        assert_eq!(confusion.total(), 200);
+        assert_eq!(confusion.accuracy(), 1.0);
+
+        // CBC is never mistaken for ECB, but ECB is often missed if there's not enough input
+        let confusion = detection_experiment(&[b'A'; 32], 200, &mut rng).unwrap();
+        assert_eq!(confusion.cbc_as_ecb, 0);
+        assert!(confusion.accuracy() < 1.0);
+    }
 }
@@ -49,75 +49,132 @@
 //! experience is any guideline, this attack will get you code execution in security tests about
 //! once a year.
 
-use std::collections::HashMap;
-
+use crate::report::say;
 use crate::utils::*;
 use anyhow::{anyhow, Result};
 use base64::{engine::general_purpose, Engine as _};
+use rand::Rng;
+
+const SECRET_BASE_64: &str = "Um9sbGluJyBpbiBteSA1LjAKV2l0aCBteSByYWctdG9wIGRvd24gc28gbXkgaGFpciBjYW4gYmxvdwpUaGUgZ2lybGllcyBvbiBzdGFuZGJ5IHdhdmluZyBqdXN0IHRvIHNheSBoaQpEaWQgeW91IHN0b3A/IE5vLCBJIGp1c3QgZHJvdmUgYnkK";
 
-pub fn main() -> Result<()> {
-    let mut rng = rand::thread_rng();
-    // Need a fixed key over the duration
-    let key = random_key(16, &mut rng);
+pub fn main() -> Result<Report> {
+    let secret_bytes = general_purpose::STANDARD.decode(SECRET_BASE_64)?;
+    let oracle = SuffixOracle::new(&secret_bytes, &mut crate::utils::rng());
 
     // 1. Determine block size
-    let mut padder: Vec<u8> = b"AA".to_vec();
-    while is_unique(&oracle(&padder, &key)?[..padder.len()], padder.len() / 2) {
-        padder.extend_from_slice(b"A");
+    let (block_size, secret_len) = discover_block_size(&oracle)?;
+    say!("Inferred block size: {block_size}");
+    say!("Inferred secret length: {secret_len}");
+
+    // 2. Confirm ECB, 3. decrypt
+    let decrypted_message = byte_at_a_time(&oracle)?;
+    say!("{}", String::from_utf8_lossy(&decrypted_message));
+
+    Ok(Report::new().unverified("secret", decrypted_message))
+}
+
+/// Something which will encrypt whatever we give it, under a key we don't know
+pub trait EncryptionOracle {
+    fn encrypt(&self, input: &[u8]) -> Result<Vec<u8>>;
+}
+
+impl<F: Fn(&[u8]) -> Result<Vec<u8>>> EncryptionOracle for F {
+    fn encrypt(&self, input: &[u8]) -> Result<Vec<u8>> {
+        self(input)
+    }
+}
+
+/// AES-128-ECB(your-string || unknown-string, random-key)
+struct SuffixOracle {
+    key: Vec<u8>,
+    secret: Vec<u8>,
+}
+
+impl SuffixOracle {
+    fn new(secret: &[u8], rng: &mut impl Rng) -> Self {
+        // Need a fixed key over the duration
+        Self {
+            key: random_key(16, rng),
+            secret: secret.to_vec(),
+        }
     }
-    let block_size = padder.len() / 2;
-    println!("Inferred block size: {block_size}");
+}
 
-    // 2. Confirm ECB
-    let ciphertext_mode = detect_mode_explicit(&oracle(&padder, &key)?, block_size);
-    println!("Mode: {:?}", ciphertext_mode);
+impl EncryptionOracle for SuffixOracle {
+    fn encrypt(&self, input: &[u8]) -> Result<Vec<u8>> {
+        let mut input = input.to_vec();
+        input.extend_from_slice(&self.secret);
+        // Make sure it's padded!
+        let padded = pkcs7_pad(&input, self.key.len());
 
-    let mut decrypted_message: Vec<u8> = Vec::with_capacity(oracle(b"", &key)?.len());
+        ecb_encrypt(&padded, &self.key, None)
+    }
+}
 
-    while let Ok(next_byte) = get_next_byte(&decrypted_message, &key, block_size) {
-        decrypted_message.push(next_byte);
-        //        println!("{}", std::str::from_utf8(&decrypted_message).unwrap());
+/// Finds the block size, and how many bytes the oracle adds to our input, by feeding it more and
+/// more bytes until the ciphertext grows by a block
+pub fn discover_block_size<O: EncryptionOracle + ?Sized>(oracle: &O) -> Result<(usize, usize)> {
+    let base = oracle.encrypt(b"")?.len();
+    for i in 1..=256 {
+        let l = oracle.encrypt(&vec![b'A'; i])?.len();
+        if l > base {
+            // The input has just pushed the padding out into a whole block of its own
+            return Ok((l - base, base - i));
+        }
+    }
+    Err(anyhow!(
+        "Ciphertext length never changed, can't find block size"
+    ))
+}
+
+/// Decrypts everything an ECB oracle appends to our input
+pub fn byte_at_a_time<O: EncryptionOracle + ?Sized>(oracle: &O) -> Result<Vec<u8>> {
+    let (bs, secret_len) = discover_block_size(oracle)?;
+    if detect_mode_explicit(&oracle.encrypt(&vec![b'A'; 3 * bs])?, bs) != Mode::Ecb {
+        return Err(anyhow!("Oracle isn't using ECB"));
     }
-    println!("{}", std::str::from_utf8(&decrypted_message).unwrap());
 
-    Ok(())
+    let mut decrypted_message: Vec<u8> = Vec::with_capacity(secret_len);
+    while decrypted_message.len() < secret_len {
+        let next_byte = get_next_byte(oracle, &decrypted_message, bs)?;
+        decrypted_message.push(next_byte);
+    }
+    Ok(decrypted_message)
 }
-fn get_next_byte(current_state: &[u8], key: &[u8], bs: usize) -> Result<u8> {
-    let mut lookup = HashMap::new();
 
-    // Construct lookup table for current scenario
+fn get_next_byte<O: EncryptionOracle + ?Sized>(
+    oracle: &O,
+    current_state: &[u8],
+    bs: usize,
+) -> Result<u8> {
     // Say we know the message is "In a town"
     // Then the dangling is going to be padding this out so that
     // we know the string except for the last letter and that it matches with
     // the block size i.e.
     // |<------16------>|
-    // |AAAAAAIn a townb|
+    // |AAAAAAIn a town?|
     //
     // So the number of padding bytes is
     let padding_size = bs - 1 - (current_state.len() % bs);
-    // Don't want trailing 1 from padding on the final byte
-    for b in 2..255_u8 {
-        // This runs from 0..=bs-1 as modulo is the same
-
-        let mut padded: Vec<u8> = vec![65_u8; padding_size];
-        padded.extend_from_slice(current_state);
-        padded.push(b);
-        let dangling = &padded[padded.len() - bs..padded.len()];
-        let enc = oracle(dangling, key)?[..bs].to_vec();
-        lookup.insert(enc, b);
-    }
-    // Now run with slightly smaller dangling string
-    let padded: Vec<u8> = vec![65_u8; padding_size];
-    // |<------16------>|
-    // |AAAAAAIn a town?|
+    let padded: Vec<u8> = vec![b'A'; padding_size];
     // Select correct block to look at
     let block = current_state.len() / bs;
-    let enc = oracle(&padded, key)?[block * bs..(block + 1) * bs].to_vec();
+    let target = oracle.encrypt(&padded)?[block * bs..(block + 1) * bs].to_vec();
 
-    match lookup.get(&enc) {
-        Some(b) => Ok(*b),
-        None => Err(anyhow!("Failed to find correct block in lookup table")),
+    // Now try every last byte on the end of what we know
+    // |<------16------>|
+    // |AAAAAAIn a townb|
+    let mut dangling = padded;
+    dangling.extend_from_slice(current_state);
+    let mut dangling = dangling[dangling.len() + 1 - bs..].to_vec();
+    dangling.push(0);
+    for b in 0..=255_u8 {
+        dangling[bs - 1] = b;
+        if oracle.encrypt(&dangling)?[..bs] == target[..] {
+            return Ok(b);
+        }
     }
+    Err(anyhow!("Failed to find correct block in lookup table"))
 }
 
 pub fn detect_mode_explicit(ciphertext: &[u8], bs: usize) -> Mode {
@@ -127,37 +184,42 @@ pub fn detect_mode_explicit(ciphertext: &[u8], bs: usize) -> Mode {
     }
 }
 
-fn oracle(input: &[u8], key: &[u8]) -> Result<Vec<u8>> {
-    let secret_base_64 = "Um9sbGluJyBpbiBteSA1LjAKV2l0aCBteSByYWctdG9wIGRvd24gc28gbXkgaGFpciBjYW4gYmxvdwpUaGUgZ2lybGllcyBvbiBzdGFuZGJ5IHdhdmluZyBqdXN0IHRvIHNheSBoaQpEaWQgeW91IHN0b3A/IE5vLCBJIGp1c3QgZHJvdmUgYnkK";
-    let secret_bytes = general_purpose::STANDARD.decode(secret_base_64)?;
-    let mut input = input.to_vec();
-    input.extend_from_slice(&secret_bytes);
-    // Make sure it's padded!
-    let padded = pkcs7_pad(&input, key.len());
-
-    let encrypted = ecb_encrypt(&padded, key, None)?;
-
-    Ok(encrypted)
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
 
     #[test]
     fn test_cracking() {
-        let secret_base_64 = "Um9sbGluJyBpbiBteSA1LjAKV2l0aCBteSByYWctdG9wIGRvd24gc28gbXkgaGFpciBjYW4gYmxvdwpUaGUgZ2lybGllcyBvbiBzdGFuZGJ5IHdhdmluZyBqdXN0IHRvIHNheSBoaQpEaWQgeW91IHN0b3A/IE5vLCBJIGp1c3QgZHJvdmUgYnkK";
-        let secret_bytes = general_purpose::STANDARD.decode(secret_base_64).unwrap();
-        let mut rng = rand::thread_rng();
-        // Need a fixed key over the duration
-        let key = random_key(16, &mut rng);
-
-        let block_size = 16;
-        let mut decrypted_message: Vec<u8> = Vec::with_capacity(oracle(b"", &key).unwrap().len());
-
-        while let Ok(next_byte) = get_next_byte(&decrypted_message, &key, block_size) {
-            decrypted_message.push(next_byte);
-        }
+        let secret_bytes = general_purpose::STANDARD.decode(SECRET_BASE_64).unwrap();
+        let oracle = SuffixOracle::new(&secret_bytes, &mut rand::thread_rng());
+
+        assert_eq!(
+            discover_block_size(&oracle).unwrap(),
+            (16, secret_bytes.len())
+        );
+        let decrypted_message = byte_at_a_time(&oracle).unwrap();
         assert_eq!(&decrypted_message, &secret_bytes);
     }
+
+    #[test]
+    fn other_oracles() {
+        // Anything deterministic block by block will do, such as xor with an 8 byte key
+        let secret = b"\x00\x01binary\xffsecret\x02\x02";
+        let oracle = |input: &[u8]| -> Result<Vec<u8>> {
+            let mut input = input.to_vec();
+            input.extend_from_slice(secret);
+            Ok(xor_bytes(&pkcs7_pad(&input, 8), b"01234567"))
+        };
+        assert_eq!(discover_block_size(&oracle).unwrap(), (8, secret.len()));
+        assert_eq!(byte_at_a_time(&oracle).unwrap(), secret);
+
+        // ...but CBC isn't
+        let key = random_key(16, &mut rand::thread_rng());
+        let oracle = |input: &[u8]| -> Result<Vec<u8>> {
+            let mut input = input.to_vec();
+            input.extend_from_slice(secret);
+            Ok(cbc_encrypt(&pkcs7_pad(&input, 16), &key, None)?)
+        };
+        assert!(byte_at_a_time(&oracle).is_err());
+    }
 }
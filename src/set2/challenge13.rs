@@ -47,10 +47,88 @@
 //! Using only the user input to profile_for() (as an oracle to generate "valid" ciphertexts) and
 //! the ciphertexts themselves, make a role=admin profile.
 
+use crate::report::say;
 use crate::utils::*;
-use anyhow::{anyhow, Result};
+use anyhow::Result;
 use thiserror::Error;
 
+#[derive(Debug, Error, PartialEq)]
+pub enum CookieError {
+    #[error("Metacharacter in value: {0:?}")]
+    Metacharacter(String),
+    #[error("Malformed key-value pair: {0:?}")]
+    MalformedPair(String),
+    #[error("Malformed escape sequence in: {0:?}")]
+    MalformedEscape(String),
+    #[error("Missing field: {0}")]
+    MissingField(&'static str),
+    #[error("Invalid uid: {0:?}")]
+    InvalidUid(String),
+}
+
+/// How `profile_for` deals with metacharacters in the email address
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Encoder {
+    /// Splices the address straight in, metacharacters and all
+    Naive,
+    /// Refuses addresses with metacharacters
+    Reject,
+    /// Percent-escapes metacharacters
+    Escape,
+}
+
+/// Escapes `%`, `&` and `=`, leaving everything else alone
+fn escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '%' => escaped.push_str("%25"),
+            '&' => escaped.push_str("%26"),
+            '=' => escaped.push_str("%3D"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn unescape(value: &str) -> Result<String, CookieError> {
+    let mut unescaped = String::with_capacity(value.len());
+    let mut rest = value;
+    while let Some(i) = rest.find('%') {
+        unescaped.push_str(&rest[..i]);
+        let c = match rest.get(i + 1..i + 3) {
+            Some("25") => '%',
+            Some("26") => '&',
+            Some("3D") => '=',
+            _ => return Err(CookieError::MalformedEscape(value.to_string())),
+        };
+        unescaped.push(c);
+        rest = &rest[i + 3..];
+    }
+    unescaped.push_str(rest);
+    Ok(unescaped)
+}
+
+/// Encodes key-value pairs as `k1=v1&k2=v2`, escaping metacharacters
+pub fn encode_kv(pairs: &[(&str, &str)]) -> String {
+    pairs
+        .iter()
+        .map(|(k, v)| format!("{}={}", escape(k), escape(v)))
+        .collect::<Vec<String>>()
+        .join("&")
+}
+
+/// Parses `k1=v1&k2=v2` into key-value pairs, in order
+pub fn decode_kv(input: &str) -> Result<Vec<(String, String)>, CookieError> {
+    input
+        .split('&')
+        .map(|pair| match pair.split('=').collect::<Vec<&str>>()[..] {
+            [k, v] if !k.is_empty() => Ok((unescape(k)?, unescape(v)?)),
+            _ => Err(CookieError::MalformedPair(pair.to_string())),
+        })
+        .collect()
+}
+
 #[derive(Debug, PartialEq)]
 struct Credentials {
     email: String,
@@ -58,48 +136,56 @@ struct Credentials {
     role: String,
 }
 
-fn poor_deserialize(input: &str) -> Result<Credentials> {
-    println!("INPUT: {input}");
-    let kvs = input.split('&').collect::<Vec<&str>>();
-    if let [ekv, ukv, rkv, ..] = kvs[..] {
-        println!("{ekv}");
-        println!("{ukv}");
-        println!("{rkv}");
-
-        if let ([_, email], [_, uid], [_, role]) = (
-            &ekv.split('=').collect::<Vec<&str>>()[..],
-            &ukv.split('=').collect::<Vec<&str>>()[..],
-            &rkv.split('=').collect::<Vec<&str>>()[..],
-        ) {
-            return Ok(Credentials {
-                email: email.to_string(),
-                uid: uid.parse::<u64>()?,
-                role: role.to_string(),
-            });
-        }
+impl Credentials {
+    fn to_cookie(&self) -> String {
+        encode_kv(&[
+            ("email", &self.email),
+            ("uid", &self.uid.to_string()),
+            ("role", &self.role),
+        ])
     }
-    Err(anyhow!("Failed to parse profile: {}", input))
-}
 
-fn poor_serialize(cred: Credentials) -> String {
-    format!("email={}&uid={}&role={}", cred.email, cred.uid, cred.role)
+    /// Reads credentials from a cookie, ignoring any unknown keys
+    ///
+    /// Like Go's `url.Values.Get`, if a key shows up more than once then the first value wins.
+    fn from_cookie(cookie: &str) -> Result<Self, CookieError> {
+        let pairs = decode_kv(cookie)?;
+        let get = |key: &'static str| {
+            pairs
+                .iter()
+                .find(|(k, _)| k == key)
+                .map(|(_, v)| v.clone())
+                .ok_or(CookieError::MissingField(key))
+        };
+        let uid = get("uid")?;
+        Ok(Self {
+            email: get("email")?,
+            uid: uid.parse().map_err(|_| CookieError::InvalidUid(uid))?,
+            role: get("role")?,
+        })
+    }
 }
 
-fn profile_for(who: &str) -> String {
-    let who = who.escape_default().to_string();
+fn profile_for(who: &str, encoder: Encoder) -> Result<String, CookieError> {
     let c = Credentials {
-        email: who,
+        email: who.to_string(),
         uid: 10,
         role: "user".to_string(),
     };
-    poor_serialize(c)
+    match encoder {
+        Encoder::Naive => Ok(format!("email={}&uid={}&role={}", c.email, c.uid, c.role)),
+        Encoder::Reject if who.contains(['&', '=']) => {
+            Err(CookieError::Metacharacter(who.to_string()))
+        }
+        Encoder::Reject | Encoder::Escape => Ok(c.to_cookie()),
+    }
 }
 
-fn encrypting_oracle(who: &str, key: &[u8]) -> Vec<u8> {
-    let profile = profile_for(who);
+fn encrypting_oracle(who: &str, key: &[u8], encoder: Encoder) -> Result<Vec<u8>> {
+    let profile = profile_for(who, encoder)?;
     let padded = pkcs7_pad(profile.as_bytes(), 16);
 
-    ecb_encrypt(&padded, key, None).unwrap().to_vec()
+    ecb_encrypt(&padded, key, None)
 }
 
 fn decrypting_oracle(bytes: &[u8], key: &[u8]) -> Result<Credentials> {
@@ -107,7 +193,34 @@ fn decrypting_oracle(bytes: &[u8], key: &[u8]) -> Result<Credentials> {
     let unpadded = pkcs7_unpad(&decrypted)?;
 
     let s = std::str::from_utf8(&unpadded)?;
-    poor_deserialize(s)
+    Ok(Credentials::from_cookie(s)?)
+}
+
+/// Against the naive encoder there's no need for any crypto at all
+fn injection_attack(key: &[u8], encoder: Encoder) -> Result<Credentials> {
+    decrypting_oracle(
+        &encrypting_oracle("foo@bar.com&role=admin", key, encoder)?,
+        key,
+    )
+}
+
+/// Builds an admin profile out of blocks from two ordinary ones
+fn cut_and_paste_attack(key: &[u8], encoder: Encoder) -> Result<Credentials> {
+    // First an "admin" block, complete with padding, by filling out the first block
+    // |email=foo@bar.co|admin...........|&uid=10&role=use|r
+    let mut admin = b"foo@bar.coadmin".to_vec();
+    admin.extend_from_slice(&[11; 11]);
+    let admin = encrypting_oracle(std::str::from_utf8(&admin)?, key, encoder)?;
+
+    // Then push "user" on to a block of its own, and replace it
+    // |email=foo12@bar.|com&uid=10&role=|user............|
+    let user = encrypting_oracle("foo12@bar.com", key, encoder)?;
+
+    let mut pasted: Vec<u8> = vec![];
+    pasted.extend_from_slice(&user[..32]);
+    pasted.extend_from_slice(&admin[16..32]);
+
+    decrypting_oracle(&pasted, key)
 }
 
 #[derive(Debug, Error)]
@@ -118,7 +231,9 @@ pub enum PaddingError {
 
 pub fn pkcs7_unpad(bytes: &[u8]) -> Result<Vec<u8>, PaddingError> {
     let l = bytes.len();
-    let padding_val = bytes[l - 1];
+    let Some(&padding_val) = bytes.last() else {
+        return Err(PaddingError::InvalidPadding);
+    };
     let padding_val_valid = (l >= padding_val as usize) & (0_usize < padding_val as usize);
     match padding_val_valid {
         false => Err(PaddingError::InvalidPadding),
@@ -133,46 +248,45 @@ pub fn pkcs7_unpad(bytes: &[u8]) -> Result<Vec<u8>, PaddingError> {
     }
 }
 
-pub fn main() -> Result<()> {
-    let mut rng = rand::thread_rng();
+pub fn main() -> Result<Report> {
+    let mut rng = crate::utils::rng();
     let key = random_key(16, &mut rng);
 
     // What are the rules of the game?
     // We can ask for the profile for anyone, and get an encrypted version spit back
     // We can feed in an encrypted version and get a profile back
+    let mut report = Report::new();
+    for encoder in [Encoder::Naive, Encoder::Reject, Encoder::Escape] {
+        say!("{encoder:?} encoder:");
+        // Injecting metacharacters only works on the naive encoder, but cutting and pasting
+        // blocks works on all of them
+        let attacks = [
+            ("injection", injection_attack(&key, encoder), encoder == Encoder::Naive),
+            ("cut and paste", cut_and_paste_attack(&key, encoder), true),
+        ];
+        for (attack, result, should_work) in attacks {
+            let name = format!("{encoder:?} {attack} role");
+            let role = match result {
+                Ok(cred) => {
+                    say!("  {attack}: {cred:?}");
+                    cred.role
+                }
+                Err(e) => {
+                    say!("  {attack}: {e}");
+                    match should_work {
+                        true => e.to_string(),
+                        false => continue,
+                    }
+                }
+            };
+            report = match should_work {
+                true => report.check(&name, "admin", role),
+                false => report.recovered(&name, role),
+            };
+        }
+    }
 
-    //let cred = decrypting_oracle(&encrypting_oracle("test_user", &key), &key)?;
-
-    // A
-    // email=foo@bar.com&uid=10&role=user
-    // |         |        |         |user      |
-    // We want to push the padding over to a new block, and then four more, to get the encrypted
-    // version of something with user on the end
-    // We then need to get a way to find an encrypted block with just |admin| in it.
-    // Well, we don't quite need that, we really just need to pad the end of a user with
-    // admin such that it lies at a boundary
-    // |email=foo@bar.com|admin&uid=10&qwe|
-    // And then cut a paste these blocks
-    // |                |                |                |                |
-    //  email=foo@bar.co admin&uid=10&rol e=user
-    //  email=foo@bar.co adm&uid=10&role= user
-
-    let s1 = "foo@bar.coadmin";
-    let s2 = "foo@bar.coadm";
-    let shift1 = encrypting_oracle(s1, &key);
-    let shift2 = encrypting_oracle(s2, &key);
-
-    let mut pasted: Vec<u8> = vec![];
-    pasted.extend_from_slice(&shift2[..32]);
-    pasted.extend_from_slice(&shift1[16..32]);
-    // Put valid padding back on the end
-    pasted.extend_from_slice(&shift1[32..]);
-
-    let cred = decrypting_oracle(&pasted, &key)?;
-
-    println!("{cred:?}");
-
-    Ok(())
+    Ok(report)
 }
 
 #[cfg(test)]
@@ -183,7 +297,8 @@ mod tests {
     fn enc_dec() {
         let mut rng = rand::thread_rng();
         let key = random_key(16, &mut rng);
-        let cred = decrypting_oracle(&encrypting_oracle("test_usertest_user", &key), &key).unwrap();
+        let ct = encrypting_oracle("test_usertest_user", &key, Encoder::Escape).unwrap();
+        let cred = decrypting_oracle(&ct, &key).unwrap();
 
         let target = Credentials {
             email: "test_usertest_user".to_string(),
@@ -195,6 +310,67 @@ mod tests {
         assert_eq!(cred, target);
     }
 
+    #[test]
+    fn codec() {
+        let pairs = decode_kv("foo=bar&baz=qux&zap=zazzle").unwrap();
+        assert_eq!(
+            pairs,
+            [("foo", "bar"), ("baz", "qux"), ("zap", "zazzle")]
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+        );
+
+        let encoded = encode_kv(&[("email", "a&b=c%d"), ("x", "")]);
+        assert_eq!(encoded, "email=a%26b%3Dc%25d&x=");
+        assert_eq!(
+            decode_kv(&encoded).unwrap(),
+            [("email", "a&b=c%d"), ("x", "")].map(|(k, v)| (k.to_string(), v.to_string()))
+        );
+
+        assert!(matches!(
+            decode_kv("a=b&c"),
+            Err(CookieError::MalformedPair(_))
+        ));
+        assert!(matches!(
+            decode_kv("a=b=c"),
+            Err(CookieError::MalformedPair(_))
+        ));
+        assert!(matches!(
+            decode_kv("a=%2"),
+            Err(CookieError::MalformedEscape(_))
+        ));
+        assert_eq!(
+            Credentials::from_cookie("email=a&uid=ten&role=user"),
+            Err(CookieError::InvalidUid("ten".to_string()))
+        );
+        assert_eq!(
+            Credentials::from_cookie("email=a&uid=10"),
+            Err(CookieError::MissingField("role"))
+        );
+    }
+
+    #[test]
+    fn attacks() {
+        let mut rng = rand::thread_rng();
+        let key = random_key(16, &mut rng);
+
+        // Metacharacters only get through the naive encoder
+        assert_eq!(
+            injection_attack(&key, Encoder::Naive).unwrap().role,
+            "admin"
+        );
+        assert!(injection_attack(&key, Encoder::Reject).is_err());
+        let cred = injection_attack(&key, Encoder::Escape).unwrap();
+        assert_eq!(cred.email, "foo@bar.com&role=admin");
+        assert_eq!(cred.role, "user");
+
+        // ...but the encoding doesn't matter for cutting and pasting blocks
+        for encoder in [Encoder::Naive, Encoder::Reject, Encoder::Escape] {
+            let cred = cut_and_paste_attack(&key, encoder).unwrap();
+            assert_eq!(cred.email, "foo12@bar.com");
+            assert_eq!(cred.role, "admin");
+        }
+    }
+
     #[test]
     fn test_unpad() {
         let bytes: Vec<u8> = vec![1, 2, 3, 4, 5, 6];
@@ -228,6 +404,10 @@ mod tests {
         assert_eq!(unpadded_manual, unpadded);
     }
     #[test]
+    fn test_unpad_empty() {
+        assert!(pkcs7_unpad(&[]).is_err());
+    }
+    #[test]
     fn test_all_pads() {
         // Full block
         let mut bytes: Vec<u8> = vec![];
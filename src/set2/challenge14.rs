@@ -13,165 +13,122 @@
 //!
 //! Think "STIMULUS" and "RESPONSE".
 
-use std::collections::HashMap;
-
+use crate::report::say;
 use crate::utils::*;
 use anyhow::{anyhow, Result};
 use base64::{engine::general_purpose, Engine as _};
 use rand::Rng;
 
-fn oracle(prepend: &[u8], raw_input: &[u8], key: &[u8]) -> Result<Vec<u8>> {
-    let secret_base_64 = "Um9sbGluJyBpbiBteSA1LjAKV2l0aCBteSByYWctdG9wIGRvd24gc28gbXkgaGFpciBjYW4gYmxvdwpUaGUgZ2lybGllcyBvbiBzdGFuZGJ5IHdhdmluZyBqdXN0IHRvIHNheSBoaQpEaWQgeW91IHN0b3A/IE5vLCBJIGp1c3QgZHJvdmUgYnkK";
-    let secret_bytes = general_purpose::STANDARD.decode(secret_base_64)?;
-    let mut input = prepend.to_vec();
-    input.extend_from_slice(raw_input);
-    input.extend_from_slice(&secret_bytes);
-    // Make sure it's padded!
-    let padded = pkcs7_pad(&input, key.len());
-
-    let encrypted = ecb_encrypt(&padded, key, None)?;
+const SECRET_BASE_64: &str = "Um9sbGluJyBpbiBteSA1LjAKV2l0aCBteSByYWctdG9wIGRvd24gc28gbXkgaGFpciBjYW4gYmxvdwpUaGUgZ2lybGllcyBvbiBzdGFuZGJ5IHdhdmluZyBqdXN0IHRvIHNheSBoaQpEaWQgeW91IHN0b3A/IE5vLCBJIGp1c3QgZHJvdmUgYnkK";
 
-    Ok(encrypted)
+/// AES-128-ECB(random-prefix || attacker-controlled || target-bytes, random-key)
+struct PrefixOracle {
+    prefix: Vec<u8>,
+    key: Vec<u8>,
+    secret: Vec<u8>,
 }
 
-fn get_next_byte(
-    prepend: &[u8],
-    inferred_l: usize,
-    current_state: &[u8],
-    key: &[u8],
-    bs: usize,
-) -> Result<u8> {
-    let mut lookup = HashMap::new();
-
-    let extra_padding = bs - inferred_l % bs;
-    let skip_blocks = inferred_l / bs + 1;
-
-    // Construct lookup table for current scenario
-    // Say we know the message is "In a town"
-    // Then the dangling is going to be padding this out so that
-    // we know the string except for the last letter and that it matches with
-    // the block size i.e.
-    // |                |<------16------>|
-    // |prefixqweAAAAAAA|AAAAAAIn a townb|
-    // Here:
-    //  inferred_prepend_l = 9
-    //  extra_padding = 7
-    //  skip_blocks = 7/16 + 1 = 1
-    // So the number of padding bytes is
-    let padding_size = bs - 1 - (current_state.len() % bs) + extra_padding;
-    let block = current_state.len() / bs + skip_blocks;
-
-    /*
-    println!("Inferred prepend: {}", inferred_l);
-    println!("Extra padding: {}", extra_padding);
-    println!("Sum : {}", extra_padding + inferred_l);
-    println!("Padding size : {}", padding_size);
-    println!("Message length: {}", padding_size);
-    println!("Block: {}", block);
-    println!("Skip blocks: {}", skip_blocks);
-    */
-
-    // PICK UP FROM HERE
-
-    // Don't want trailing 1 from padding on the final byte
-    for b in 2..255_u8 {
-        // This runs from 0..=bs-1 as modulo is the same
-
-        let mut padded: Vec<u8> = vec![65_u8; padding_size];
-        padded.extend_from_slice(current_state);
-        padded.push(b);
-        // We now need to strip off the extra padding at the beginning
-        // Skip blocks is at least 1, which makes sense as we will always have a least one full
-        // block of padding
-        // Just want the block with the probes in it
-        let enc = oracle(prepend, &padded, key)?[block * bs..(block + 1) * bs].to_vec();
-
-        lookup.insert(enc, b);
+impl PrefixOracle {
+    fn new(secret: &[u8], rng: &mut impl Rng) -> Self {
+        // Need a fixed key over the duration
+        let key = random_key(16, rng);
+        let secret_prefix_length = rng.gen::<usize>() % 64_usize;
+        let prefix = random_key(secret_prefix_length, rng);
+        Self {
+            prefix,
+            key,
+            secret: secret.to_vec(),
+        }
     }
-    // Now run with slightly smaller dangling string
-    let padded: Vec<u8> = vec![65_u8; padding_size];
-    // |<------16------>|
-    // |AAAAAAIn a town?|
-    // Select correct block to look at
-    // Need to include skip block now
-    let enc = oracle(prepend, &padded, key)?[block * bs..(block + 1) * bs].to_vec();
-
-    match lookup.get(&enc) {
-        Some(b) => Ok(*b),
-        None => Err(anyhow!("Failed to find correct block in lookup table")),
+}
+
+impl EncryptionOracle for PrefixOracle {
+    fn encrypt(&self, raw_input: &[u8]) -> Result<Vec<u8>> {
+        let mut input = self.prefix.clone();
+        input.extend_from_slice(raw_input);
+        input.extend_from_slice(&self.secret);
+        // Make sure it's padded!
+        let padded = pkcs7_pad(&input, self.key.len());
+
+        ecb_encrypt(&padded, &self.key, None)
     }
 }
 
-fn infer_prefix_length(secret_prefix: &[u8], key: &[u8]) -> Result<usize> {
-    // The logic here is the following: add an "A" and see which is the first block to change
-    // Keep adding "A"s until that block no longer changes
-    // One before this point is then a clean boundary, and we can use this to run the same
-    // game as before, except always cut these first blocks off if we pad accordingly
-    //
-    let bs: usize = 16;
-    let mut probe: Vec<u8> = vec![];
-    let mut reference = oracle(secret_prefix, &probe, key)?;
-    probe.push(65_u8);
-    let mut probed = oracle(secret_prefix, &probe, key)?;
-    let ref_block = first_different_block(&reference, &probed);
-
-    loop {
-        reference = oracle(secret_prefix, &probe, key)?;
-        probe.push(65_u8);
-        probed = oracle(secret_prefix, &probe, key)?;
-        let first_different = first_different_block(&reference, &probed);
-        match first_different != ref_block {
-            true => {
-                let boundary = (ref_block + 1) * bs;
-                let padding = probe.len() - 1;
-
-                return Ok(boundary - padding);
+/// Finds how many bytes an ECB oracle puts in front of our input
+///
+/// Sends a growing amount of filler followed by two blocks of identical probe bytes, and looks for
+/// the first pair of identical adjacent ciphertext blocks from the block where our input starts.
+/// Once they show up, the filler has just finished off the prefix's last block, so the prefix
+/// fills up to where they start, less the filler.
+///
+/// If the secret after our input starts with the probe byte then the pair turns up too early, and
+/// the prefix looks longer than it is. It can't start with two different bytes though, so probing
+/// with both and taking the shorter answer gets it right.
+pub fn find_prefix_length<O: EncryptionOracle + ?Sized>(oracle: &O, bs: usize) -> Result<usize> {
+    // The prefix may well have repeated blocks of its own, so skip past them
+    let first_block = first_different_block(&oracle.encrypt(b"A")?, &oracle.encrypt(b"B")?, bs);
+
+    let mut shortest = usize::MAX;
+    for probe in [b'A', b'Z'] {
+        // At least one filler byte, so that the end of the prefix can't join in with the probe
+        let mut found = None;
+        for padding in 1..=bs {
+            let mut input = vec![!probe; padding];
+            input.extend(std::iter::repeat_n(probe, 2 * bs));
+            let ciphertext = oracle.encrypt(&input)?;
+            let blocks: Vec<&[u8]> = ciphertext.chunks(bs).skip(first_block).collect();
+            if let Some(block) = blocks.windows(2).position(|w| w[0] == w[1]) {
+                found = Some((first_block + block) * bs - padding);
+                break;
             }
-            false => {}
+        }
+        match found {
+            Some(l) => shortest = shortest.min(l),
+            None => return Err(anyhow!("No repeated blocks, oracle doesn't look like ECB")),
         }
     }
+    Ok(shortest)
 }
 
-fn first_different_block(v1: &[u8], v2: &[u8]) -> usize {
-    let l1 = v1.len();
-    let l2 = v2.len();
-    let bs: usize = 16;
-    let min_length: usize = match l1 < l2 {
-        true => l1,
-        false => l2,
-    } / bs;
-    for b in 0..min_length {
-        match v1[b * bs..(b + 1) * bs] == v2[b * bs..(b + 1) * bs] {
-            false => return b,
-            true => {}
-        };
-    }
-    min_length
+fn first_different_block(v1: &[u8], v2: &[u8], bs: usize) -> usize {
+    v1.chunks(bs)
+        .zip(v2.chunks(bs))
+        .position(|(b1, b2)| b1 != b2)
+        .unwrap_or(v1.len().min(v2.len()) / bs)
 }
 
-pub fn main() -> Result<()> {
-    let mut rng = rand::thread_rng();
-    // Need a fixed key over the duration
-    let key = random_key(16, &mut rng);
-    let secret_prefix_length = rng.gen::<usize>() % 64_usize;
-    let secret_prefix = random_key(secret_prefix_length, &mut rng);
-    println!("Actual length: {}", secret_prefix_length);
-    let inferred = infer_prefix_length(&secret_prefix, &key)?;
-    println!("Inferred length: {:?}", inferred);
-    // Now add extra padding on top
-    let bs = 16;
-    let mut decrypted_message: Vec<u8> =
-        Vec::with_capacity(oracle(&secret_prefix, b"", &key)?.len());
-    println!("Cracking...");
-
-    while let Ok(next_byte) = get_next_byte(&secret_prefix, inferred, &decrypted_message, &key, bs)
-    {
-        decrypted_message.push(next_byte);
-        //println!("{}", std::str::from_utf8(&decrypted_message).unwrap());
+/// Knowing the prefix length, pads it out to a whole number of blocks and cuts them off, which
+/// leaves an oracle just like the one from #12
+fn strip_prefix<O: EncryptionOracle>(
+    oracle: &O,
+    prefix_len: usize,
+    bs: usize,
+) -> impl EncryptionOracle + '_ {
+    let extra_padding = (bs - prefix_len % bs) % bs;
+    let skip = prefix_len + extra_padding;
+    move |input: &[u8]| -> Result<Vec<u8>> {
+        let mut padded = vec![b'A'; extra_padding];
+        padded.extend_from_slice(input);
+        Ok(oracle.encrypt(&padded)?[skip..].to_vec())
     }
-    println!("{}", std::str::from_utf8(&decrypted_message).unwrap());
+}
+
+fn crack(oracle: &PrefixOracle) -> Result<Vec<u8>> {
+    let (bs, _) = discover_block_size(oracle)?;
+    let inferred = find_prefix_length(oracle, bs)?;
+    say!("Inferred length: {:?}", inferred);
+    say!("Cracking...");
+    byte_at_a_time(&strip_prefix(oracle, inferred, bs))
+}
+
+pub fn main() -> Result<Report> {
+    let secret_bytes = general_purpose::STANDARD.decode(SECRET_BASE_64)?;
+    let oracle = PrefixOracle::new(&secret_bytes, &mut crate::utils::rng());
+    say!("Actual length: {}", oracle.prefix.len());
+    let decrypted_message = crack(&oracle)?;
+    say!("{}", String::from_utf8_lossy(&decrypted_message));
 
-    Ok(())
+    Ok(Report::new().unverified("secret", decrypted_message))
 }
 
 #[cfg(test)]
@@ -180,29 +137,32 @@ mod tests {
 
     #[test]
     fn test_cracking() {
-        let secret_base_64 = "Um9sbGluJyBpbiBteSA1LjAKV2l0aCBteSByYWctdG9wIGRvd24gc28gbXkgaGFpciBjYW4gYmxvdwpUaGUgZ2lybGllcyBvbiBzdGFuZGJ5IHdhdmluZyBqdXN0IHRvIHNheSBoaQpEaWQgeW91IHN0b3A/IE5vLCBJIGp1c3QgZHJvdmUgYnkK";
-        let secret_bytes = general_purpose::STANDARD.decode(secret_base_64).unwrap();
+        let secret_bytes = general_purpose::STANDARD.decode(SECRET_BASE_64).unwrap();
+        let oracle = PrefixOracle::new(&secret_bytes, &mut rand::thread_rng());
+        println!("Actual length: {}", oracle.prefix.len());
+        let decrypted_message = crack(&oracle).unwrap();
+        assert_eq!(decrypted_message, secret_bytes);
+    }
+
+    #[test]
+    fn prefix_lengths() {
         let mut rng = rand::thread_rng();
-        // Need a fixed key over the duration
         let key = random_key(16, &mut rng);
-        let secret_prefix_length = rng.gen::<usize>() % 64_usize;
-        let secret_prefix = random_key(secret_prefix_length, &mut rng);
-        println!("Actual length: {}", secret_prefix_length);
-        let inferred = infer_prefix_length(&secret_prefix, &key).unwrap();
-        println!("Inferred length: {:?}", inferred);
-        // Now add extra padding on top
-        let bs = 16;
-        let mut decrypted_message: Vec<u8> =
-            Vec::with_capacity(oracle(&secret_prefix, b"", &key).unwrap().len());
-        println!("Cracking...");
-
-        while let Ok(next_byte) =
-            get_next_byte(&secret_prefix, inferred, &decrypted_message, &key, bs)
-        {
-            decrypted_message.push(next_byte);
+        for l in 0..48 {
+            // Make life awkward with the probe bytes on either side
+            for (prefix, secret) in [
+                (random_key(l, &mut rng), b"secret".to_vec()),
+                (vec![b'A'; l], b"AAAAAAAAAAAAAAAAAAAAsecret".to_vec()),
+                (vec![b'Z'; l], b"ZZZZZZZZZZZZZZZZZZZZsecret".to_vec()),
+            ] {
+                let oracle = |input: &[u8]| -> Result<Vec<u8>> {
+                    let mut plaintext = prefix.clone();
+                    plaintext.extend_from_slice(input);
+                    plaintext.extend_from_slice(&secret);
+                    ecb_encrypt(&pkcs7_pad(&plaintext, 16), &key, None)
+                };
+                assert_eq!(find_prefix_length(&oracle, 16).unwrap(), l);
+            }
         }
-        let decrypted_str = std::str::from_utf8(&decrypted_message).unwrap();
-        let secret_str = std::str::from_utf8(&secret_bytes).unwrap();
-        assert_eq!(decrypted_str, secret_str);
     }
 }
@@ -17,22 +17,23 @@
 //!
 //! Crypto nerds know where we're going with this. Bear with us.
 
+use crate::report::say;
 use crate::utils::*;
 use anyhow::Result;
 // This one is a freebie, as we already did this earlier!
 
-pub fn main() -> Result<()> {
+pub fn main() -> Result<Report> {
     let valid_padding = b"ICE ICE BABY\x04\x04\x04\x04";
     let invalid_padding = b"ICE ICE BABY\x05\x05\x05\x05";
     let invalid_padding_2 = b"ICE ICE BABY\x01\x02\x03\x04";
 
-    println!("bytes: {:?}", valid_padding);
-    println!("unpadded: {:?}", pkcs7_unpad(valid_padding));
+    say!("bytes: {:?}", valid_padding);
+    say!("unpadded: {:?}", pkcs7_unpad(valid_padding));
 
-    println!("bytes: {:?}", invalid_padding);
-    println!("unpadded: {:?}", pkcs7_unpad(invalid_padding));
+    say!("bytes: {:?}", invalid_padding);
+    say!("unpadded: {:?}", pkcs7_unpad(invalid_padding));
 
-    println!("bytes: {:?}", invalid_padding_2);
-    println!("unpadded: {:?}", pkcs7_unpad(invalid_padding_2));
-    Ok(())
+    say!("bytes: {:?}", invalid_padding_2);
+    say!("unpadded: {:?}", pkcs7_unpad(invalid_padding_2));
+    Ok(Report::new().check("unpadded", b"ICE ICE BABY", pkcs7_unpad(valid_padding)?))
 }
@@ -28,6 +28,7 @@
 //! Stop and think for a second.
 //! Before you implement this attack, answer this question: why does CBC mode have this property?
 
+use crate::report::say;
 use crate::utils::*;
 
 fn embed(input: &[u8], key: &[u8]) -> Result<Vec<u8>> {
@@ -79,8 +80,8 @@ fn generated_flipped(target: &[u8], key: &[u8]) -> Result<Vec<u8>> {
     Ok(modified)
 }
 
-pub fn main() -> Result<()> {
-    let mut rng = rand::thread_rng();
+pub fn main() -> Result<Report> {
+    let mut rng = crate::utils::rng();
     let key = random_key(16, &mut rng);
 
     let target = b";admin=true;aaaa";
@@ -88,15 +89,14 @@ pub fn main() -> Result<()> {
 
     let target_decrypt = &cbc_decrypt(&modified, &key, None).unwrap()[32..48];
     let target_str = std::str::from_utf8(target_decrypt).unwrap();
-    println!("Decrypted: {}", target_str);
+    say!("Decrypted: {}", target_str);
 
-    let whoami = match authorise(&modified, &key) {
-        Ok(true) => "admin",
-        _ => "not-admin",
-    };
-    println!("whoami: {}", whoami);
+    let admin = authorise(&modified, &key).is_ok_and(|admin| admin);
+    say!("whoami: {}", if admin { "admin" } else { "not-admin" });
 
-    Ok(())
+    Ok(Report::new()
+        .recovered("ciphertext", modified)
+        .forged("admin", admin))
 }
 
 #[cfg(test)]
@@ -1,25 +1,11 @@
-pub mod challenge09;
-pub mod challenge10;
-pub mod challenge11;
-pub mod challenge12;
-pub mod challenge13;
-pub mod challenge14;
-pub mod challenge15;
-pub mod challenge16;
-
-use crate::utils::Result;
-use anyhow::anyhow;
-
-pub fn run(c: u64) -> Result<()> {
-    match c {
-        9 => challenge09::main(),
-        10 => challenge10::main(),
-        11 => challenge11::main(),
-        12 => challenge12::main(),
-        13 => challenge13::main(),
-        14 => challenge14::main(),
-        15 => challenge15::main(),
-        16 => challenge16::main(),
-        i => Err(anyhow!("{} not in set 2", i)),
-    }
+crate::runner::register! {
+    set 2;
+    9 => challenge09, "Implement PKCS#7 padding", Fast;
+    10 => challenge10, "Implement CBC mode", Fast;
+    11 => challenge11, "An ECB/CBC detection oracle", Fast;
+    12 => challenge12, "Byte-at-a-time ECB decryption (Simple)", Fast;
+    13 => challenge13, "ECB cut-and-paste", Fast;
+    14 => challenge14, "Byte-at-a-time ECB decryption (Harder)", Fast;
+    15 => challenge15, "PKCS#7 padding validation", Fast;
+    16 => challenge16, "CBC bitflipping attacks", Fast;
 }
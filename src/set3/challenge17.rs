@@ -53,151 +53,148 @@
 //! specific bit of code that handles decryption. You can mount a padding oracle on any CBC block,
 //! whether it's padded or not.
 
+use crate::report::say;
 use crate::utils::*;
 use base64::{engine::general_purpose, Engine as _};
 use rand::seq::SliceRandom;
 use thiserror::Error;
 
-fn oracle(input: &[u8], key: &[u8]) -> Result<()> {
-    match pkcs7_unpad(&cbc_decrypt(input, key, None)?) {
-        Ok(_) => Ok(()),
-        Err(e) => Err(e.into()),
+/// The server: takes IV || ciphertext, and only lets on whether the padding was valid
+fn oracle(input: &[u8], key: &[u8]) -> bool {
+    let (iv, ciphertext) = input.split_at(key.len());
+    match cbc_decrypt(ciphertext, key, Some(iv)) {
+        Ok(decrypted) => pkcs7_unpad(&decrypted).is_ok(),
+        Err(_) => false,
     }
 }
 
 #[derive(Debug, Error)]
-enum CrackingErr {
-    #[error("Error counting up")]
-    AscendingError,
-    #[error("Error counting down")]
-    DescendingError,
+pub enum CrackingErr {
+    #[error("Ciphertext of {0} bytes isn't a whole number of blocks")]
+    BadLength(usize),
+    #[error("No byte gave valid padding at position {0}")]
+    NoValidPadding(usize),
 }
 
-// The Asc/Desc is to catch the case when we do get the initial padding right but it is *not* \x01
-// The cracking will eventually fail and we instead sweep from the other direction to not have the
-// same coincidence
-enum Dir {
-    Ascending,
-    Descending,
-}
-
-fn crack_pair(block_pair: &[u8], key: &[u8], dir: Dir) -> Result<Vec<u8>, CrackingErr> {
+/// Decrypts a single block, given the one before it (or the IV), with nothing but a padding oracle
+fn crack_block<F: Fn(&[u8]) -> bool>(
+    oracle: &F,
+    previous: &[u8],
+    block: &[u8],
+) -> Result<Vec<u8>, CrackingErr> {
     // This is an expansion of the CBC bit-flip attack from before
     // Instead, the only information we get out is whether or not the padding is correct
 
-    let bs = key.len();
-    // This is the byte from the end we are targetting
-    let mut modified_block = block_pair[bs..].to_vec();
-    modified_block.extend_from_slice(&block_pair[bs..]);
-    for target_byte in 0..bs {
-        let mut b = match dir {
-            Dir::Ascending => 0_u8,
-            Dir::Descending => 255_u8,
-        };
-        modified_block[bs - target_byte - 1] = b;
-        while oracle(&modified_block, key).is_err() {
-            match dir {
-                Dir::Ascending => {
-                    if b == 255 {
-                        return Err(CrackingErr::AscendingError);
-                    }
-                    b += 1
-                }
-                Dir::Descending => {
-                    if b == 0 {
-                        return Err(CrackingErr::DescendingError);
-                    }
+    let bs = block.len();
+    // What the block decrypts to before it's XORed with the previous one
+    let mut intermediate = vec![0_u8; bs];
+    let mut forged = vec![0_u8; 2 * bs];
+    forged[bs..].copy_from_slice(block);
 
-                    b -= 1
-                }
-            };
-            modified_block[bs - target_byte - 1] = b;
+    // Work from the end, forging padding of \x01, then \x02\x02, and so on
+    for target in (0..bs).rev() {
+        let pad = (bs - target) as u8;
+        // Make the bytes we already know decrypt to the padding
+        for i in target + 1..bs {
+            forged[i] = intermediate[i] ^ pad;
         }
-        // Now the padding should be correct ...\xtarget+1\xtarget+1
-        // This implies that decoded[2*bs - target_byte - 1] ^ b = target_byte+1
-        // i.e. decoded[2*bs - target_byte - 1] = b ^(target_byte+1)
-        /*
-        println!(
-            "decoded[{}] = {:?}; b = {}",
-            target_byte,
-            b ^ (target_byte as u8 + 1) ^ block_pair[bs - target_byte - 1],
-            b
-        );
-        */
-        // Now that the padding is correct, we roughly know what is going on
-        // If this is the first byte, then we know the decrypted block ends \x01 (unless we got
-        // lucky and it end \x02\x02, or more, but this is unlikely)
-        // If this is the second byte, it ends \x02 etc
-        // To get the next byte, we now need to make sure all of the bytes we have so far get
-        // updated
-        for update_byte in 0..target_byte + 1 {
-            let loc = bs - update_byte - 1;
-            // When target_byte was 0, intend value was 1
-            let tb = target_byte as u8;
-            //println!("modified_byte was: {}", modified_block[loc]);
-            modified_block[loc] = modified_block[loc] ^ (tb + 1) ^ (tb + 2);
-            //println!("modified_byte now: {}", modified_block[loc]);
+
+        let mut found = None;
+        for b in 0..=255_u8 {
+            forged[target] = b;
+            if !oracle(&forged) {
+                continue;
+            }
+            // On the last byte, valid padding might be \x02\x02 or longer rather than \x01 (for
+            // the original previous block this is guaranteed on the final block). Changing the
+            // byte before it only matters if it's part of the padding.
+            if target == bs - 1 && target > 0 {
+                forged[target - 1] ^= 0xff;
+                let still_valid = oracle(&forged);
+                forged[target - 1] ^= 0xff;
+                if !still_valid {
+                    continue;
+                }
+            }
+            found = Some(b);
+            break;
         }
-        //println!();
+        let b = found.ok_or(CrackingErr::NoValidPadding(target))?;
+        // The padding is correct, so decrypted[target] ^ b = pad
+        intermediate[target] = b ^ pad;
     }
 
-    // Now that this is complete, the modified block should now have the following form:
-    // modified_block[..bs] ^ decrypted[bs..] = \xbs+1 ... \xbs+1
-    // => decrypted[bs..] = \xbs+1 .. \xbs+1 ^ modified_block[..bs]
-    // The +1 is because we overdid in on the last round of updating modified_block, where it was
-    // \xbs..\xbs, and took it one step further
-    let decrypted = modified_block[..bs]
+    Ok(intermediate
         .iter()
-        .enumerate()
-        .map(|(i, x)| block_pair[i] ^ x ^ ((bs as u8) + 1))
-        .collect();
-    Ok(decrypted)
+        .zip(previous.iter())
+        .map(|(i, p)| i ^ p)
+        .collect())
 }
 
-pub fn main() -> Result<()> {
-    let mut rng = rand::thread_rng();
+/// Decrypts a CBC ciphertext of any number of blocks using a padding oracle, which takes the IV
+/// followed by the ciphertext
+///
+/// Without the IV, there's no way to get at the first block, so only the rest is returned. The
+/// plaintext is returned with its padding still on.
+pub fn padding_oracle_attack<F: Fn(&[u8]) -> bool>(
+    oracle: F,
+    bs: usize,
+    iv: Option<&[u8]>,
+    ciphertext: &[u8],
+) -> Result<Vec<u8>, CrackingErr> {
+    if ciphertext.is_empty() || !ciphertext.len().is_multiple_of(bs) {
+        return Err(CrackingErr::BadLength(ciphertext.len()));
+    }
+    let mut extended = iv.map(|iv| iv.to_vec()).unwrap_or_default();
+    extended.extend_from_slice(ciphertext);
+
+    let mut answer = vec![];
+    for pair in extended.chunks(bs).collect::<Vec<&[u8]>>().windows(2) {
+        answer.extend_from_slice(&crack_block(&oracle, pair[0], pair[1])?);
+    }
+    Ok(answer)
+}
+
+const SECRET_STRINGS: [&str; 10] = [
+    "MDAwMDAwTm93IHRoYXQgdGhlIHBhcnR5IGlzIGp1bXBpbmc=",
+    "MDAwMDAxV2l0aCB0aGUgYmFzcyBraWNrZWQgaW4gYW5kIHRoZSBWZWdhJ3MgYXJlIHB1bXBpbic=",
+    "MDAwMDAyUXVpY2sgdG8gdGhlIHBvaW50LCB0byB0aGUgcG9pbnQsIG5vIGZha2luZw==",
+    "MDAwMDAzQ29va2luZyBNQydzIGxpa2UgYSBwb3VuZCBvZiBiYWNvbg==",
+    "MDAwMDA0QnVybmluZyAnZW0sIGlmIHlvdSBhaW4ndCBxdWljayBhbmQgbmltYmxl",
+    "MDAwMDA1SSBnbyBjcmF6eSB3aGVuIEkgaGVhciBhIGN5bWJhbA==",
+    "MDAwMDA2QW5kIGEgaGlnaCBoYXQgd2l0aCBhIHNvdXBlZCB1cCB0ZW1wbw==",
+    "MDAwMDA3SSdtIG9uIGEgcm9sbCwgaXQncyB0aW1lIHRvIGdvIHNvbG8=",
+    "MDAwMDA4b2xsaW4nIGluIG15IGZpdmUgcG9pbnQgb2g=",
+    "MDAwMDA5aXRoIG15IHJhZy10b3AgZG93biBzbyBteSBoYWlyIGNhbiBibG93",
+];
+
+pub fn main() -> Result<Report> {
+    let mut rng = crate::utils::rng();
     let key = random_key(16, &mut rng);
+    let iv = random_key(16, &mut rng);
     let bs = key.len();
 
-    let base64_secret_strings = ["MDAwMDAwTm93IHRoYXQgdGhlIHBhcnR5IGlzIGp1bXBpbmc=",
-        "MDAwMDAxV2l0aCB0aGUgYmFzcyBraWNrZWQgaW4gYW5kIHRoZSBWZWdhJ3MgYXJlIHB1bXBpbic=",
-        "MDAwMDAyUXVpY2sgdG8gdGhlIHBvaW50LCB0byB0aGUgcG9pbnQsIG5vIGZha2luZw==",
-        "MDAwMDAzQ29va2luZyBNQydzIGxpa2UgYSBwb3VuZCBvZiBiYWNvbg==",
-        "MDAwMDA0QnVybmluZyAnZW0sIGlmIHlvdSBhaW4ndCBxdWljayBhbmQgbmltYmxl",
-        "MDAwMDA1SSBnbyBjcmF6eSB3aGVuIEkgaGVhciBhIGN5bWJhbA==",
-        "MDAwMDA2QW5kIGEgaGlnaCBoYXQgd2l0aCBhIHNvdXBlZCB1cCB0ZW1wbw==",
-        "MDAwMDA3SSdtIG9uIGEgcm9sbCwgaXQncyB0aW1lIHRvIGdvIHNvbG8=",
-        "MDAwMDA4b2xsaW4nIGluIG15IGZpdmUgcG9pbnQgb2g=",
-        "MDAwMDA5aXRoIG15IHJhZy10b3AgZG93biBzbyBteSBoYWlyIGNhbiBibG93"];
-    let secret_bytes = base64_secret_strings.choose(&mut rng).unwrap();
+    let secret_bytes = SECRET_STRINGS.choose(&mut rng).unwrap();
     let secret = general_purpose::STANDARD.decode(secret_bytes)?;
     let secret_string = std::str::from_utf8(&secret)?;
 
-    let padded = pkcs7_pad(&secret, 16);
-    let ciphertext = cbc_encrypt(&padded, &key, None)?;
-
-    let mut extended = vec![0_u8; bs];
-    extended.extend_from_slice(&ciphertext);
-    let mut answer = vec![];
+    let padded = pkcs7_pad(&secret, bs);
+    let ciphertext = cbc_encrypt(&padded, &key, Some(&iv))?;
 
-    for chunk_num in 0..(extended.len() / bs - 1) {
-        let block_pair = &extended[chunk_num * bs..(chunk_num + 2) * bs];
-        let cracked = match crack_pair(block_pair, &key, Dir::Ascending) {
-            Ok(x) => Ok(x),
-            Err(_) => crack_pair(block_pair, &key, Dir::Descending),
-        }?;
+    let answer = padding_oracle_attack(|input| oracle(input, &key), bs, Some(&iv), &ciphertext)?;
+    let answer = pkcs7_unpad(&answer)?;
+    say!("Cracked:  {:?}", answer);
+    say!("Original: {:?}", secret);
+    say!("Cracked:  {}", String::from_utf8_lossy(&answer));
+    say!("Original: {}", secret_string);
 
-        answer.extend_from_slice(&cracked);
-    }
+    // Without the IV, everything but the first block is still there for the taking
+    let tail = padding_oracle_attack(|input| oracle(input, &key), bs, None, &ciphertext)?;
+    say!("Without the IV: {}", String::from_utf8_lossy(&tail));
 
-    let answer = pkcs7_unpad(&answer).unwrap();
-    println!("Cracked:  {:?}", answer);
-    println!("Original: {:?}", secret);
-    println!("Cracked:  {}", std::str::from_utf8(&answer).unwrap());
-    println!("Original: {}", secret_string);
-    assert_eq!(answer, secret);
-
-    Ok(())
+    Ok(Report::new()
+        .check("plaintext", secret_string, String::from_utf8_lossy(&answer).into_owned())
+        .check("without the IV", &padded[bs..], tail))
 }
 
 #[cfg(test)]
@@ -207,7 +204,54 @@ mod tests {
     #[test]
     fn repeated_test() {
         for _ in 0..100 {
-            main().unwrap();
+            main().and_then(Report::verify).unwrap();
+        }
+    }
+
+    fn round_trip(plaintext: &[u8]) {
+        let mut rng = rand::thread_rng();
+        let key = random_key(16, &mut rng);
+        let iv = random_key(16, &mut rng);
+        let padded = pkcs7_pad(plaintext, 16);
+        let ciphertext = cbc_encrypt(&padded, &key, Some(&iv)).unwrap();
+
+        let cracked =
+            padding_oracle_attack(|input| oracle(input, &key), 16, Some(&iv), &ciphertext).unwrap();
+        assert_eq!(cracked, padded);
+        let cracked =
+            padding_oracle_attack(|input| oracle(input, &key), 16, None, &ciphertext).unwrap();
+        assert_eq!(cracked, padded[16..]);
+    }
+
+    #[test]
+    fn final_block_padding() {
+        // Every padding length, including a whole block of it
+        for l in 16..=32 {
+            round_trip(&vec![b'A'; l]);
+        }
+    }
+
+    #[test]
+    fn padding_lookalikes() {
+        // Blocks which look a lot like padding already
+        round_trip(&[2; 40]);
+        round_trip(&[16; 48]);
+        for l in 1..=16 {
+            let mut plaintext = vec![b'A'; 16 - l];
+            plaintext.extend(std::iter::repeat_n(l as u8, l));
+            plaintext.extend_from_slice(b"and something after");
+            round_trip(&plaintext);
+        }
+    }
+
+    #[test]
+    fn bad_lengths() {
+        let key = random_key(16, &mut rand::thread_rng());
+        for ciphertext in [vec![], vec![0; 17]] {
+            assert!(matches!(
+                padding_oracle_attack(|input| oracle(input, &key), 16, None, &ciphertext),
+                Err(CrackingErr::BadLength(_))
+            ));
         }
     }
 }
@@ -58,10 +58,11 @@
 
 use std::iter::zip;
 
+use crate::report::say;
 use crate::stream::Ctr;
 use crate::utils::*;
 
-pub fn main() -> Result<()> {
+pub fn main() -> Result<Report> {
     let key = b"YELLOW SUBMARINE";
     let ctr_stream = Ctr::new(key, 0);
 
@@ -73,11 +74,10 @@ pub fn main() -> Result<()> {
         .collect::<Vec<u8>>();
 
     let decoded_str = std::str::from_utf8(&decoded).unwrap();
-    println!("Decoded: {decoded_str}");
+    say!("Decoded: {decoded_str}");
 
     let target = "Yo, VIP Let's kick it Ice, Ice, baby Ice, Ice, baby ";
-    assert_eq!(target, decoded_str);
-    Ok(())
+    Ok(Report::new().check("plaintext", target, decoded_str))
 }
 
 #[cfg(test)]
@@ -85,6 +85,6 @@ mod tests {
     use super::*;
     #[test]
     fn check_decoding() {
-        main().unwrap();
+        main().and_then(Report::verify).unwrap();
     }
 }
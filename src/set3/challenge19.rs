@@ -26,52 +26,51 @@
 //! Points for automating this, but part of the reason I'm having you do this is that I think this
 //! approach is suboptimal.
 
+use crate::data;
+use crate::report::say;
+use crate::set3::challenge20::{fixed_nonce_encrypt, FixedNonceBreaker};
 use crate::utils::*;
 
-use crate::stream::Ctr;
-use crate::utils::read_base64_lines;
-
-pub fn main() -> Result<()> {
-    let data = read_base64_lines("./data/19.txt")?;
+pub fn main() -> Result<Report> {
+    let data = data::CHALLENGE_19.base64_lines()?;
     let key = b"YELLOW SUBMARINE";
-    let data = data
-        .iter()
-        .map(|x| {
-            let stream = Ctr::new(key, 0);
-            x.iter()
-                .zip(stream)
-                .map(|(v, k)| v ^ k)
-                .collect::<Vec<u8>>()
-        })
-        .collect::<Vec<Vec<u8>>>();
-    // Decoded by hand from the stream and guessing bytes
-    // Selecting targets using python ord(a)^ord(b)^c for whichever loks most plausible
-    let mut keystream: Vec<u8> = vec![
-        118, 209, 203, 75, 175, 162, 70, 226, 227, 175, 3, 93, 108, 19, 195, 114, 210, 236, 108,
-        220, 152, 109, 18, 222, 207, 218, 31, 147, 175, 238, 115, 24, 45, 160, 142, 203, 17, 123,
-    ];
-    for d in data {
-        let mut output = d
-            .iter()
-            .zip(keystream.iter())
-            .map(|(v, k)| v ^ k)
-            .collect::<Vec<u8>>();
-        while std::str::from_utf8(&output).is_err() {
-            let l = keystream.len();
-            keystream[l - 1] += 1;
+    let data = fixed_nonce_encrypt(&data, key);
+
+    // Rather than guessing letters by hand, let the trigram statistics do the guessing, and only
+    // step in for the tail ends where there's barely anything to go on
+    let mut breaker = FixedNonceBreaker::new(data)?;
+    breaker.print();
+    if crate::options::get().interactive {
+        breaker.refine_interactively()?;
+    }
+    say!("Keystream: {:?}", breaker.keystream());
 
-            output = d
-                .iter()
-                .zip(keystream.iter())
-                .map(|(v, k)| v ^ k)
-                .collect::<Vec<u8>>();
+    Ok(breaker.report())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn break_substitutions() {
+        let original = data::CHALLENGE_19.base64_lines().unwrap();
+        let mut breaker =
+            FixedNonceBreaker::new(fixed_nonce_encrypt(&original, b"YELLOW SUBMARINE")).unwrap();
+        let recovered = breaker.plaintexts();
+        let min_length = original.iter().map(|x| x.len()).min().unwrap();
+        for (r, o) in recovered.iter().zip(original.iter()) {
+            assert_eq!(r[..min_length], o[..min_length]);
         }
-        let output_str = std::str::from_utf8(&output).unwrap();
 
-        //        println!("Keystream: {keystream:?}");
-        println!("Output: {output_str}");
+        // "He, too, has been changed in his turn,", the longest line, with its end pinned down
+        let longest = (0..original.len())
+            .max_by_key(|i| original[*i].len())
+            .unwrap();
+        let l = original[longest].len();
+        breaker
+            .pin(longest, l - 12, &original[longest][l - 12..])
+            .unwrap();
+        assert_eq!(breaker.plaintexts()[longest], original[longest]);
     }
-    println!("Keystream: {keystream:?}");
-
-    Ok(())
 }
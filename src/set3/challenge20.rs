@@ -15,13 +15,200 @@
 //! Solve the resulting concatenation of ciphertexts as if for repeating- key XOR, with a key size
 //! of the length of the ciphertext you XOR'd.
 
+use std::io::BufRead;
+
+use crate::data;
+use crate::report::say;
+use crate::scoring::{Language, NGram, Scorer};
 use crate::stream::Ctr;
 use crate::utils::*;
+use anyhow::anyhow;
 
-pub fn main() -> Result<()> {
-    let data_raw = read_base64_lines("./data/20.txt")?;
-    let key = b"YELLOW SUBMARINE";
-    let data = data_raw
+/// Most passes over the keystream when refining it
+const REFINE_PASSES: usize = 4;
+/// Tiny score penalty for a letter in the wrong case, only enough to break ties
+const CASE_PENALTY: f64 = 1e-6;
+
+/// Recovers the keystream shared by a collection of ciphertexts, one column at a time
+///
+/// The first guess at each byte only looks at letter frequencies down its column, the same as
+/// repeating-key XOR. This is fine while there are plenty of lines, but falls apart towards the end
+/// of the longest ones, so each guess is then revisited with trigrams over the text leading up to
+/// it in each line. Bytes of the keystream can also be pinned by supplying known plaintext, and the
+/// rest re-derived around them.
+pub struct FixedNonceBreaker {
+    ciphertexts: Vec<Vec<u8>>,
+    keystream: Vec<u8>,
+    pinned: Vec<bool>,
+    column_scorer: Scorer,
+    text_scorer: Scorer,
+}
+
+impl FixedNonceBreaker {
+    pub fn new(ciphertexts: Vec<Vec<u8>>) -> Result<Self> {
+        let len = ciphertexts.iter().map(|c| c.len()).max().unwrap_or(0);
+        let mut breaker = Self {
+            ciphertexts,
+            keystream: vec![0; len],
+            pinned: vec![false; len],
+            // Columns are read across lines, so there are no meaningful bigrams
+            column_scorer: Scorer::new(&Language::English, NGram::Unigram)?,
+            text_scorer: Scorer::new(&Language::English, NGram::Trigram)?,
+        };
+        breaker.solve()?;
+        Ok(breaker)
+    }
+
+    fn column(&self, i: usize) -> Vec<u8> {
+        self.ciphertexts
+            .iter()
+            .filter_map(|c| c.get(i).copied())
+            .collect()
+    }
+
+    /// Scores the decryption of each line up to column `i`
+    ///
+    /// The scorer doesn't care about case, which leaves letters in a column tied with their other
+    /// case. These are broken by counting capitals where they don't belong, and lower case letters
+    /// where a capital does.
+    fn score_column(&self, i: usize) -> f64 {
+        let mut window = vec![];
+        let mut miscased = 0;
+        for c in self.ciphertexts.iter().filter(|c| c.len() > i) {
+            let start = i.saturating_sub(5);
+            let decrypted: Vec<u8> = c[start..=i]
+                .iter()
+                .zip(&self.keystream[start..=i])
+                .map(|(c, k)| c ^ k)
+                .collect();
+            let miscase = match decrypted[..] {
+                [first] => first.is_ascii_lowercase(),
+                [.., previous, last] => last.is_ascii_uppercase() && previous.is_ascii_lowercase(),
+                [] => false,
+            };
+            miscased += miscase as usize;
+            window.extend(decrypted);
+            // Separated by junk so the lines score independently
+            window.push(0);
+        }
+        self.text_scorer.score(&window) + CASE_PENALTY * miscased as f64
+    }
+
+    /// Derives every keystream byte which hasn't been pinned
+    pub fn solve(&mut self) -> Result<()> {
+        for i in 0..self.keystream.len() {
+            if !self.pinned[i] {
+                self.keystream[i] = crack_single_byte_xor(&self.column(i), &self.column_scorer)?;
+            }
+        }
+
+        for _ in 0..REFINE_PASSES {
+            let mut changed = false;
+            for i in (0..self.keystream.len()).filter(|i| !self.pinned[*i]) {
+                let original = self.keystream[i];
+                let mut best = (f64::MAX, original);
+                // Anything that turns the column into junk breaks up the trigrams around it, and
+                // can look better than real but unusual text, so stick to what decrypts to text
+                let column = self.column(i);
+                let printable = |k: &u8| {
+                    column
+                        .iter()
+                        .all(|c| (c ^ k).is_ascii_graphic() || c ^ k == b' ')
+                };
+                for k in (0..=255).filter(printable) {
+                    self.keystream[i] = k;
+                    let score = self.score_column(i);
+                    if score < best.0 {
+                        best = (score, k);
+                    }
+                }
+                self.keystream[i] = best.1;
+                changed |= best.1 != original;
+            }
+            if !changed {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    /// Fixes the keystream so that `line` decrypts to `text` from `column` onwards, and re-derives
+    /// everything else
+    pub fn pin(&mut self, line: usize, column: usize, text: &[u8]) -> Result<()> {
+        let ciphertext = self
+            .ciphertexts
+            .get(line)
+            .ok_or_else(|| anyhow!("No line {}", line))?;
+        if column + text.len() > ciphertext.len() {
+            return Err(anyhow!(
+                "Line {} is only {} bytes long",
+                line,
+                ciphertext.len()
+            ));
+        }
+        for (i, t) in text.iter().enumerate() {
+            self.keystream[column + i] = ciphertext[column + i] ^ t;
+            self.pinned[column + i] = true;
+        }
+        self.solve()
+    }
+
+    pub fn keystream(&self) -> &[u8] {
+        &self.keystream
+    }
+
+    pub fn plaintexts(&self) -> Vec<Vec<u8>> {
+        self.ciphertexts
+            .iter()
+            .map(|c| xor_bytes(c, &self.keystream[..c.len()]))
+            .collect()
+    }
+
+    pub fn print(&self) {
+        for (n, p) in self.plaintexts().iter().enumerate() {
+            say!("{n:>3}: {}", String::from_utf8_lossy(p));
+        }
+    }
+
+    /// The keystream, and the plaintexts it gives a line each
+    pub fn report(&self) -> Report {
+        let plaintexts: Vec<_> = self
+            .plaintexts()
+            .iter()
+            .map(|p| String::from_utf8_lossy(p).into_owned())
+            .collect();
+        Report::new()
+            .unverified("keystream", self.keystream())
+            .unverified("plaintexts", plaintexts.join("\n"))
+    }
+
+    /// Reads corrections from stdin, each of the form `LINE COLUMN TEXT`, until an empty line
+    pub fn refine_interactively(&mut self) -> Result<()> {
+        say!("Pin known plaintext with: LINE COLUMN TEXT (empty line to finish)");
+        for input in std::io::stdin().lock().lines() {
+            let input = input?;
+            if input.trim().is_empty() {
+                break;
+            }
+            let mut parts = input.splitn(3, ' ');
+            let pinned = match (parts.next(), parts.next(), parts.next()) {
+                (Some(line), Some(column), Some(text)) => {
+                    self.pin(line.parse()?, column.parse()?, text.as_bytes())
+                }
+                _ => Err(anyhow!("Expected LINE COLUMN TEXT")),
+            };
+            match pinned {
+                Ok(()) => self.print(),
+                Err(e) => say!("{e}"),
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Encrypts every line under the same key and nonce
+pub fn fixed_nonce_encrypt(lines: &[Vec<u8>], key: &[u8]) -> Vec<Vec<u8>> {
+    lines
         .iter()
         .map(|x| {
             // Fixed nonce = 0
@@ -31,41 +218,73 @@ pub fn main() -> Result<()> {
                 .map(|(v, k)| v ^ k)
                 .collect::<Vec<u8>>()
         })
-        .collect::<Vec<Vec<u8>>>();
+        .collect()
+}
 
-    let map = freq_map_from_file("./data/aiw.txt")?;
+pub fn main() -> Result<Report> {
+    let data_raw = data::CHALLENGE_20.base64_lines()?;
+    let key = b"YELLOW SUBMARINE";
+    let data = fixed_nonce_encrypt(&data_raw, key);
 
-    // Now decrypt this statistically
-    // First, truncate all of them
-    let min_length = data.iter().map(|x| x.len()).min().unwrap();
-    let data_truncated = data
-        .iter()
-        .map(|x| x[..min_length].to_vec())
-        .collect::<Vec<Vec<u8>>>();
+    let mut breaker = FixedNonceBreaker::new(data)?;
+    breaker.print();
+    if crate::options::get().interactive {
+        breaker.refine_interactively()?;
+    }
 
-    // Rearrange and break with fixed-key XOR like many challenges ago
-    // Original data: is of the form data.len() x min_length
-    let data_rearranged = (0..min_length)
-        .map(|i| data_truncated.iter().map(|x| x[i]).collect::<Vec<u8>>())
-        .collect::<Vec<Vec<u8>>>();
+    Ok(breaker.report())
+}
 
-    let single_xor_keys = data_rearranged
-        .iter()
-        .map(|d| crack_single_byte_xor(d, &map).unwrap())
-        .collect::<Vec<u8>>();
-    let unencrypted = data_truncated
-        .iter()
-        .map(|d| {
-            d.iter()
-                .zip(single_xor_keys.iter())
-                .map(|(v, k)| v ^ k)
-                .collect::<Vec<u8>>()
-        })
-        .collect::<Vec<Vec<u8>>>();
-    for u in unencrypted.iter() {
-        let s = std::str::from_utf8(u).unwrap();
-        println!("Unencrytped: {}", s);
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Fraction of bytes recovered correctly
+    fn accuracy(recovered: &[Vec<u8>], original: &[Vec<u8>]) -> f64 {
+        let total: usize = original.iter().map(|o| o.len()).sum();
+        let correct: usize = recovered
+            .iter()
+            .zip(original)
+            .map(|(r, o)| r.iter().zip(o).filter(|(a, b)| a == b).count())
+            .sum();
+        correct as f64 / total as f64
     }
 
-    Ok(())
+    #[test]
+    fn break_fixed_nonce() {
+        let original = data::CHALLENGE_20.base64_lines().unwrap();
+        let breaker =
+            FixedNonceBreaker::new(fixed_nonce_encrypt(&original, b"YELLOW SUBMARINE")).unwrap();
+        let recovered = breaker.plaintexts();
+        // Everything the repeating-key XOR approach sees comes out right
+        let min_length = original.iter().map(|x| x.len()).min().unwrap();
+        for (r, o) in recovered.iter().zip(original.iter()) {
+            assert_eq!(r[..min_length], o[..min_length]);
+        }
+        assert!(accuracy(&recovered, &original) > 0.985);
+    }
+
+    #[test]
+    fn pinning() {
+        let original = data::CHALLENGE_20.base64_lines().unwrap();
+        let mut breaker =
+            FixedNonceBreaker::new(fixed_nonce_encrypt(&original, b"YELLOW SUBMARINE")).unwrap();
+        let longest = (0..original.len())
+            .max_by_key(|i| original[*i].len())
+            .unwrap();
+        let l = original[longest].len();
+        // Pin everything from the first mistake in the longest line, which has most of the tail
+        // to itself
+        let before = accuracy(&breaker.plaintexts(), &original);
+        let first_wrong = (0..l)
+            .find(|i| breaker.plaintexts()[longest][*i] != original[longest][*i])
+            .unwrap();
+        breaker
+            .pin(longest, first_wrong, &original[longest][first_wrong..])
+            .unwrap();
+        assert_eq!(breaker.plaintexts()[longest], original[longest]);
+        assert!(accuracy(&breaker.plaintexts(), &original) > before);
+        assert!(breaker.pin(longest, l - 1, b"too long").is_err());
+        assert!(breaker.pin(original.len(), 0, b"no line").is_err());
+    }
 }
@@ -13,36 +13,120 @@
 //! you're missing some of the fun of this exercise if you do that.
 //!
 //! From the 32 bit RNG output, discover the seed.
+//!
+//! Here the seed is a real Unix timestamp from the last few minutes, hours or days, by a clock
+//! which needn't agree with ours, to show how the search scales with the uncertainty.
+
+use std::time::Duration;
 
-use rand::{prelude::*, thread_rng};
+use rand::prelude::*;
 
+use crate::report::say;
 use crate::utils::*;
+use anyhow::anyhow;
 use chrono::Utc;
 
-pub fn main() -> Result<()> {
-    let mut rng = thread_rng();
+// Lengths of time, in seconds
+const MINUTE: u64 = 60;
+const HOUR: u64 = 60 * MINUTE;
+const DAY: u64 = 24 * HOUR;
+const YEAR: u64 = 365 * DAY;
 
-    let random_offset = (rng.gen::<u64>() % 1000) as i64;
-    let offset_timestamp = Utc::now().timestamp() - random_offset;
-    let mut mt = Mt::seed(offset_timestamp as u32);
+/// What the attacker knows about when the RNG was seeded: some time in the last `window` seconds,
+/// by a clock which may be up to `jitter` seconds out from ours either way
+#[derive(Debug, Clone, Copy)]
+pub struct Uncertainty {
+    pub window: u64,
+    pub jitter: u64,
+}
 
-    let first_byte = mt.next();
+impl Uncertainty {
+    /// Number of seeds which need trying
+    pub fn candidates(&self) -> u64 {
+        self.window + 2 * self.jitter + 1
+    }
+}
+
+/// Seeds an MT19937 with a Unix timestamp somewhere in the window before `now`, as seen by a
+/// clock with some jitter, returning the timestamp used and the first output
+fn victim<R: Rng>(now: i64, uncertainty: Uncertainty, rng: &mut R) -> (i64, u32) {
+    let delay = rng.gen_range(0..=uncertainty.window) as i64;
+    let jitter = uncertainty.jitter as i64;
+    let skew = rng.gen_range(-jitter..=jitter);
+    let timestamp = now - delay + skew;
+    (timestamp, Mt::seed(timestamp as u32).next_u32())
+}
+
+/// Finds the timestamp an MT19937 was seeded with from its first output, given that it was seeded
+/// within `uncertainty` of `now`
+pub fn crack_timestamp(
+    output: u32,
+    now: i64,
+    uncertainty: Uncertainty,
+) -> (Option<i64>, SearchStats) {
+    let latest = now + uncertainty.jitter as i64;
+    let (back, stats) = par_search(0..uncertainty.candidates(), |back| {
+        Mt::seed((latest - back as i64) as u32).next_u32() == output
+    });
+    (back.map(|back| latest - back as i64), stats)
+}
 
+pub fn main() -> Result<Report> {
+    let mut rng = crate::utils::rng();
     let now = Utc::now().timestamp();
-    let mut back_count = 0;
-    while Mt::seed((now - back_count) as u32).next() != first_byte {
-        back_count += 1;
-        if back_count > 1000 {
-            panic!("Missed the answer {random_offset}");
-        }
-    }
 
-    println!("Cracked offset: {back_count}");
-    println!("True offset:    {random_offset}");
+    let mut report = Report::new();
+    let mut rate = 0.0;
+    for (name, uncertainty) in [
+        (
+            "1000 seconds",
+            Uncertainty {
+                window: 1000,
+                jitter: 0,
+            },
+        ),
+        (
+            "an hour",
+            Uncertainty {
+                window: HOUR,
+                jitter: 30,
+            },
+        ),
+        (
+            "a day",
+            Uncertainty {
+                window: DAY,
+                jitter: 5 * MINUTE,
+            },
+        ),
+    ] {
+        let (timestamp, output) = victim(now, uncertainty, &mut rng);
+        say!(
+            "Seeded within {name} (±{}s): {} candidates",
+            uncertainty.jitter,
+            uncertainty.candidates()
+        );
+        let (cracked, stats) = crack_timestamp(output, now, uncertainty);
+        let cracked = cracked.ok_or_else(|| anyhow!("Missed the answer {timestamp}"))?;
 
-    assert_eq!(back_count, random_offset);
+        say!("Cracked timestamp: {cracked}");
+        say!("True timestamp:    {timestamp}");
+        say!("{stats}");
+        rate = stats.rate();
+        report = report
+            .check(&format!("timestamp within {name}"), timestamp, cracked)
+            .count(&format!("tried within {name}"), stats.tried);
+    }
+
+    // It's linear in the window, so anything up to the whole 32-bit seed space is within reach
+    for (name, seconds) in [("a year", YEAR), ("every 32-bit seed", 1 << 32)] {
+        say!(
+            "Searching {name} would take at most {:.0?}",
+            Duration::from_secs_f64(seconds as f64 / rate)
+        );
+    }
 
-    Ok(())
+    Ok(report)
 }
 
 #[cfg(test)]
@@ -50,6 +134,23 @@ mod tests {
     use super::*;
     #[test]
     fn mt_cracker() {
-        main().unwrap();
+        main().and_then(Report::verify).unwrap();
+    }
+
+    #[test]
+    fn jitter() {
+        let mut rng = thread_rng();
+        let now = 1_700_000_000;
+        let uncertainty = Uncertainty {
+            window: 100,
+            jitter: 20,
+        };
+        for _ in 0..20 {
+            let (timestamp, output) = victim(now, uncertainty, &mut rng);
+            assert_eq!(crack_timestamp(output, now, uncertainty).0, Some(timestamp));
+        }
+        // Ahead of our clock by more than the jitter allows
+        let output = Mt::seed((now + 21) as u32).next_u32();
+        assert_eq!(crack_timestamp(output, now, uncertainty).0, None);
     }
 }
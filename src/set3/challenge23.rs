@@ -27,9 +27,15 @@
 //! Stop and think for a second. How would you modify MT19937 to make this attack hard? What would
 //! happen if you subjected each tempered output to a cryptographic hash?
 
-use rand::{prelude::*, thread_rng};
+use rand::prelude::*;
 
-use super::challenge21::{B, C, D, L, LOWEST_W, S, T, U};
+use anyhow::anyhow;
+
+use crate::mt::{
+    Mt64, A, B, B64, C, C64, D, D64, L, L64, LOWER_MASK, M, N, N64, S, S64, T, T64, U, U64,
+    UPPER_MASK,
+};
+use crate::report::say;
 use crate::utils::*;
 
 // ABCDEFGHIJKLMN
@@ -74,73 +80,266 @@ fn unshift_l(value: u32, s: u32, mask: u32) -> u32 {
         let window_mask = bottom_s << (s * i);
         working_value += window_mask & (value ^ (mask & (working_value << s)));
     }
-    (working_value & LOWEST_W) as u32
+    working_value as u32
 }
 
 fn untemper(value: u32) -> u32 {
     // y = y ^ (y >> L as u64);
-    //println!("Input: {value}");
     let mut y = unshift_r(value, L, 0xFFFFFFFF_u32);
-    //println!("Untemper 1: {y}");
     //y = y ^ ((y << T as u64) & C as u64);
     y = unshift_l(y, T, C);
-    //println!("Untemper 2: {y}");
     //y = y ^ ((y << S as u64) & B as u64);
     y = unshift_l(y, S, B);
-    //println!("Untemper 3: {y}");
     //y = y ^ ((y >> U as u64) & D as u64);
     y = unshift_r(y, U, D);
-    //println!("Untemper 4: {y}");
     y
 }
 
-#[allow(dead_code)]
-fn temper(value: u32) -> u32 {
-    let mut y = value as u64;
-    //println!("Original: {y}");
-    y = y ^ ((y >> U as u64) & D as u64);
-    //println!("Temper 1: {y}");
-    y = y ^ ((y << S as u64) & B as u64);
-    //println!("Temper 2: {y}");
-    y = y ^ ((y << T as u64) & C as u64);
-    //println!("Temper 3: {y}");
-    y = y ^ (y >> L as u64);
-    //println!("Output: {y}");
-    y as u32
+// MT19937-64 has different constants, and the shifts aren't much smaller than the word any more,
+// so rather than unpicking a window at a time, just iterate: each round fixes another `s` bits
+fn unshift_r64(value: u64, s: u32, mask: u64) -> u64 {
+    let mut working_value = value;
+    for _ in 0..64 / s {
+        working_value = value ^ ((working_value >> s) & mask);
+    }
+    working_value
 }
 
-pub fn main() -> Result<()> {
-    let mut rng = thread_rng();
+fn unshift_l64(value: u64, s: u32, mask: u64) -> u64 {
+    let mut working_value = value;
+    for _ in 0..64 / s {
+        working_value = value ^ ((working_value << s) & mask);
+    }
+    working_value
+}
 
-    let random_seed = rng.gen::<u32>();
-    let mt = Mt::seed(random_seed);
+fn untemper64(value: u64) -> u64 {
+    let mut y = unshift_r64(value, L64, u64::MAX);
+    y = unshift_l64(y, T64, C64);
+    y = unshift_l64(y, S64, B64);
+    unshift_r64(y, U64, D64)
+}
+
+/// Clones an MT19937-64 from its next 312 outputs
+fn clone_mt64(mt: &mut Mt64) -> Mt64 {
+    let untempered_state: Vec<u64> = mt.by_ref().take(N64).map(untemper64).collect();
+    Mt64::from_state(untempered_state.try_into().unwrap())
+}
+
+// Not every leak is 624 whole outputs in a row. As each new word of state is made from three old
+// ones,
+//
+// x[k + N] = x[k + M] ^ twist(x[k], x[k + 1])
+//
+// and twist() is easy to undo, each pair of outputs N - M apart gives away the top bit of x[k]
+// and the rest of x[k + 1]. Given enough outputs this fills in gaps, and whittles down the
+// candidates for outputs with some bits missing.
+
+/// Most candidates to keep for a word of state, beyond which it's treated as unknown
+const MAX_CANDIDATES: usize = 1 << 16;
+
+/// Undoes the twist, giving the top bit of x[k] and the lower 31 bits of x[k + 1]
+fn untwist(y: u32) -> u32 {
+    // x >> 1 never has the top bit set, so it's set exactly when A was mixed in, i.e. x was odd
+    match y >> 31 {
+        1 => ((y ^ A) << 1) | 1,
+        _ => y << 1,
+    }
+}
+
+fn twist(upper: u32, lower: u32) -> u32 {
+    let x = (upper & UPPER_MASK) | (lower & LOWER_MASK);
+    match x % 2 {
+        1 => (x >> 1) ^ A,
+        _ => x >> 1,
+    }
+}
+
+/// Candidates for each word of state, `None` when there's nothing to go on
+type Candidates = Vec<Option<Vec<u32>>>;
+
+fn prune(candidates: &mut Option<Vec<u32>>, allowed: impl Fn(u32) -> bool) -> bool {
+    match candidates {
+        Some(c) => {
+            let before = c.len();
+            c.retain(|x| allowed(*x));
+            c.len() != before
+        }
+        None => false,
+    }
+}
+
+/// One pass of applying the recurrence everywhere it can be, returning whether anything changed
+fn propagate(candidates: &mut Candidates) -> bool {
+    let mut changed = false;
+    for k in 0..candidates.len().saturating_sub(N) {
+        if let (Some(new), Some(old)) = (&candidates[k + N], &candidates[k + M]) {
+            if new.len() * old.len() > MAX_CANDIDATES {
+                continue;
+            }
+            let untwisted: Vec<u32> = new
+                .iter()
+                .flat_map(|n| old.iter().map(move |o| untwist(n ^ o)))
+                .collect();
+            let top_bits: Vec<u32> = untwisted.iter().map(|t| t & UPPER_MASK).collect();
+            let mut lower_bits: Vec<u32> = untwisted.iter().map(|t| t & LOWER_MASK).collect();
+            lower_bits.sort_unstable();
+            lower_bits.dedup();
+
+            changed |= prune(&mut candidates[k], |x| top_bits.contains(&(x & UPPER_MASK)));
+            if candidates[k + 1].is_none() && 2 * lower_bits.len() <= MAX_CANDIDATES {
+                // Only the top bit is left to find
+                let c = lower_bits
+                    .iter()
+                    .flat_map(|l| [*l, l | UPPER_MASK])
+                    .collect();
+                candidates[k + 1] = Some(c);
+                changed = true;
+            } else {
+                changed |= prune(&mut candidates[k + 1], |x| {
+                    lower_bits.binary_search(&(x & LOWER_MASK)).is_ok()
+                });
+            }
+        }
+
+        // Or the other way around, to fill in a gap further along
+        if let (Some([x0]), Some([x1])) = (candidates[k].as_deref(), candidates[k + 1].as_deref()) {
+            let twisted = twist(*x0, *x1);
+            match (candidates[k + M].as_deref(), candidates[k + N].as_deref()) {
+                (Some([old]), None) => {
+                    candidates[k + N] = Some(vec![old ^ twisted]);
+                    changed = true;
+                }
+                (None, Some([new])) => {
+                    candidates[k + M] = Some(vec![new ^ twisted]);
+                    changed = true;
+                }
+                _ => {}
+            }
+        }
+    }
+    changed
+}
 
-    let untempered_state = mt.take(624).map(untemper).collect::<Vec<u32>>();
+/// Recovers an MT19937 from a patchy view of its outputs
+///
+/// Each output is either missing, or known apart from its lowest `dropped_bits` bits (so
+/// `output >> dropped_bits`). The returned generator carries on from the last of them.
+pub fn recover_state(outputs: &[Option<u32>], dropped_bits: u32) -> Result<Mt> {
+    if dropped_bits > 8 {
+        return Err(anyhow!("Can only brute force up to 8 missing bits"));
+    }
+    let mut candidates: Candidates = outputs
+        .iter()
+        .map(|o| {
+            o.map(|o| {
+                (0..1 << dropped_bits)
+                    .map(|low| untemper((o << dropped_bits) | low))
+                    .collect()
+            })
+        })
+        .collect();
+
+    while propagate(&mut candidates) {}
+
+    // Any N words in a row will do for the state, as the twist works on them in place in the same
+    // way as the recurrence, so take the latest
+    let known: Vec<Option<u32>> = candidates
+        .iter()
+        .map(|c| match c.as_deref() {
+            Some([x]) => Some(*x),
+            _ => None,
+        })
+        .collect();
+    let end = (N..=known.len())
+        .rev()
+        .find(|end| known[end - N..*end].iter().all(|x| x.is_some()))
+        .ok_or_else(|| anyhow!("Not enough outputs to pin down {} words in a row", N))?;
+    let state: Vec<u32> = known[end - N..end].iter().map(|x| x.unwrap()).collect();
+    let mut mt = Mt::from_state(state.try_into().unwrap());
+    for _ in end..outputs.len() {
+        mt.next();
+    }
+    Ok(mt)
+}
+
+pub fn main() -> Result<Report> {
+    let mut rng = crate::utils::rng();
 
-    let mt_spliced = Mt {
-        state: untempered_state,
-        index: 0,
-    };
+    let random_seed = rng.gen::<u32>();
+    let mut mt = Mt::seed(random_seed);
 
-    let mt = Mt::seed(random_seed);
+    let untempered_state: Vec<u32> = mt.by_ref().take(N).map(untemper).collect();
+    let mt_spliced = Mt::from_state(untempered_state.try_into().unwrap());
 
+    // The clone should carry on where the original left off
     let first_byte_run = mt.take(50).collect::<Vec<u32>>();
     let first_byte_run_s = mt_spliced.take(50).collect::<Vec<u32>>();
-    println!("First byte run from mt: {:?}", first_byte_run);
-    println!("First byte run from mt_spliced: {:?}", first_byte_run_s);
-    assert_eq!(first_byte_run_s, first_byte_run);
+    say!("First byte run from mt: {:?}", first_byte_run);
+    say!("First byte run from mt_spliced: {:?}", first_byte_run_s);
+    let mut report = Report::new().recovered("seed", random_seed).check(
+        "clone",
+        words(&first_byte_run),
+        words(&first_byte_run_s),
+    );
+
+    // Lose a few outputs...
+    let mut mt = Mt::seed(rng.gen::<u32>());
+    let leaked: Vec<Option<u32>> = mt
+        .by_ref()
+        .take(3 * N)
+        .map(|o| (rng.gen::<u32>() % 20 != 0).then_some(o))
+        .collect();
+    let missing = leaked.iter().filter(|o| o.is_none()).count();
+    let mt_spliced = recover_state(&leaked, 0)?;
+    say!("Recovered from {} outputs with {} missing", 3 * N, missing);
+    report = report.check(
+        "clone with outputs missing",
+        words(&mt.take(50).collect::<Vec<_>>()),
+        words(&mt_spliced.take(50).collect::<Vec<_>>()),
+    );
 
-    Ok(())
+    // ...or the bottom few bits of every output
+    let mut mt = Mt::seed(rng.gen::<u32>());
+    let leaked: Vec<Option<u32>> = mt.by_ref().take(3 * N).map(|o| Some(o >> 6)).collect();
+    let mt_spliced = recover_state(&leaked, 6)?;
+    say!("Recovered from {} outputs with 6 bits missing", 3 * N);
+    report = report.check(
+        "clone with bits missing",
+        words(&mt.take(50).collect::<Vec<_>>()),
+        words(&mt_spliced.take(50).collect::<Vec<_>>()),
+    );
+
+    // And again for the 64-bit version
+    let mut mt = Mt64::seed(rng.gen::<u64>());
+    let mt_spliced = clone_mt64(&mut mt);
+    let first_byte_run = mt.take(5).collect::<Vec<u64>>();
+    let first_byte_run_s = mt_spliced.take(5).collect::<Vec<u64>>();
+    say!("First byte run from mt64: {:?}", first_byte_run);
+    say!("First byte run from mt64_spliced: {:?}", first_byte_run_s);
+    let bytes = |run: &[u64]| -> Vec<u8> { run.iter().flat_map(|w| w.to_be_bytes()).collect() };
+
+    Ok(report.check(
+        "64-bit clone",
+        bytes(&first_byte_run),
+        bytes(&first_byte_run_s),
+    ))
+}
+
+/// Outputs as big-endian bytes, to check a clone's against the original's
+fn words(run: &[u32]) -> Vec<u8> {
+    run.iter().flat_map(|w| w.to_be_bytes()).collect()
 }
 
 #[cfg(test)]
 mod test {
 
     use super::*;
+    use crate::mt::{temper, temper64};
 
     #[test]
     fn challenge_test() {
-        main().unwrap();
+        main().and_then(Report::verify).unwrap();
     }
     #[test]
     fn untemper_test() {
@@ -164,9 +363,68 @@ mod test {
         for i in 0..1000 {
             let i = i as u64;
             let y = i ^ ((i << T as u64) & C as u64);
-            println!("Partially tempered {y}");
+            say!("Partially tempered {y}");
             let un = unshift_l(y as u32, T, C);
             assert_eq!(i as u32, un);
         }
     }
+
+    #[test]
+    fn untemper64_test() {
+        let mut rng = thread_rng();
+        for _ in 0..1000 {
+            let i = rng.gen::<u64>();
+            assert_eq!(untemper64(temper64(i)), i);
+        }
+    }
+
+    #[test]
+    fn clone64_test() {
+        let mut mt = Mt64::seed(5489);
+        // Somewhere awkward, part way through a twist
+        mt.nth(1000);
+        let clone = clone_mt64(&mut mt);
+        assert!(mt.take(1000).eq(clone.take(1000)));
+    }
+
+    #[test]
+    fn untwisting() {
+        let mut rng = thread_rng();
+        for _ in 0..1000 {
+            let (x0, x1) = (rng.gen::<u32>(), rng.gen::<u32>());
+            let t = untwist(twist(x0, x1));
+            assert_eq!(t & UPPER_MASK, x0 & UPPER_MASK);
+            assert_eq!(t & LOWER_MASK, x1 & LOWER_MASK);
+        }
+    }
+
+    #[test]
+    fn gaps() {
+        let mut mt = Mt::seed(1234);
+        let mut leaked: Vec<Option<u32>> = mt.by_ref().take(2 * N).map(Some).collect();
+        // Whole outputs knocked out of both halves
+        for i in [0, 5, 6, 300, 623, 700, 701, 1000] {
+            leaked[i] = None;
+        }
+        let clone = recover_state(&leaked, 0).unwrap();
+        assert!(mt.take(1000).eq(clone.take(1000)));
+
+        // Not enough to go on
+        assert!(recover_state(&leaked[..N], 0).is_err());
+    }
+
+    #[test]
+    fn truncated() {
+        for dropped_bits in [1, 4, 6] {
+            let mut mt = Mt::seed(dropped_bits);
+            let mut leaked: Vec<Option<u32>> = mt
+                .by_ref()
+                .take(3 * N)
+                .map(|o| Some(o >> dropped_bits))
+                .collect();
+            leaked[400] = None;
+            let clone = recover_state(&leaked, dropped_bits).unwrap();
+            assert!(mt.take(1000).eq(clone.take(1000)));
+        }
+    }
 }
@@ -1,62 +1,41 @@
-use std::collections::VecDeque;
-
 use anyhow::anyhow;
-use rand::{prelude::*, thread_rng};
+use rand::prelude::*;
 
+use crate::report::say;
+use crate::stream::{MtStream, StreamCipher};
 use crate::utils::*;
 
-struct MtStream {
-    mt: Mt,
-    localbuffer: VecDeque<u8>,
-}
-
-impl MtStream {
-    pub fn new(seed: u32) -> MtStream {
-        let mt = Mt::seed(seed);
-        let localbuffer = VecDeque::<u8>::new();
-
-        MtStream { mt, localbuffer }
-    }
+pub fn main() -> Result<Report> {
+    let mut rng = crate::utils::rng();
+    let report = mt_seed_cracker(&mut rng, Report::new())?;
+    pw_reset_token(&mut rng, report)
 }
 
-impl Iterator for MtStream {
-    type Item = u8;
-
-    fn next(&mut self) -> Option<Self::Item> {
-        if self.localbuffer.is_empty() {
-            let byte = self.mt.next().unwrap();
-            let b1 = (byte & 0xff000000_u32) >> 24;
-            let b2 = (byte & 0x00ff0000_u32) >> 16;
-            let b3 = (byte & 0x0000ff00_u32) >> 8;
-            let b4 = byte & 0x000000ff_u32;
-            self.localbuffer.push_back(b1 as u8);
-            self.localbuffer.push_back(b2 as u8);
-            self.localbuffer.push_back(b3 as u8);
-            self.localbuffer.push_back(b4 as u8);
-        }
-        self.localbuffer.pop_front()
-    }
+/// A password reset token: 64 bytes of MT19937 keystream seeded with the time
+pub fn reset_token(timestamp: i64) -> Vec<u8> {
+    MtStream::new(timestamp as u32).take(64).collect()
 }
 
-pub fn main() -> Result<()> {
-    mt_seed_cracker()?;
-
-    pw_reset_token()?;
-    Ok(())
+/// Checks whether `token` came from an MT19937 seeded with a time within `window` seconds
+/// before `now`, returning that time if so
+pub fn token_timestamp(token: &[u8], now: i64, window: u64) -> Option<i64> {
+    let (back, stats) = par_search(0..window + 1, |back| {
+        MtStream::new((now - back as i64) as u32)
+            .take(token.len())
+            .eq(token.iter().copied())
+    });
+    say!("{stats}");
+    back.map(|back| now - back as i64)
 }
 
-fn pw_reset_token() -> Result<()> {
-    let mut rng = thread_rng();
-
+fn pw_reset_token(rng: &mut impl Rng, report: Report) -> Result<Report> {
     let coin = rng.gen::<bool>();
-    let timestamp = chrono::Utc::now().timestamp();
+    // Issued some time in the last ten minutes
+    let now = chrono::Utc::now().timestamp();
+    let timestamp = now - rng.gen_range(0..600);
 
     let token = match coin {
-        true => {
-            let mts = MtStream::new(timestamp as u32);
-
-            mts.take(64).collect::<Vec<u8>>()
-        }
+        true => reset_token(timestamp),
         false => {
             let mut v = vec![0; 64];
             rng.fill(&mut v[..]);
@@ -64,60 +43,49 @@ fn pw_reset_token() -> Result<()> {
         }
     };
 
-    let mts = MtStream::new(timestamp as u32);
-    let rec_token = mts.take(64).collect::<Vec<u8>>();
-    let is_token = { token == rec_token };
-
-    println!("Was token? {coin}");
-    println!("Detected?  {is_token}");
+    let found = token_timestamp(&token, now, 600);
+    let is_token = found.is_some();
 
-    assert_eq!(coin, is_token);
+    say!("Was token? {coin}");
+    say!("Detected?  {is_token}");
 
-    Ok(())
+    let report = report.check("token", coin.to_string(), is_token.to_string());
+    Ok(match found {
+        Some(found) => report.check("token timestamp", timestamp, found),
+        None => report,
+    })
 }
 
-fn mt_seed_cracker() -> Result<()> {
-    let mut rng = thread_rng();
-
+fn mt_seed_cracker(rng: &mut impl Rng, report: Report) -> Result<Report> {
     // Random 16-bit seed
     let random_seed = rng.gen::<u32>() & 0x0000ffff_u32;
-    let mts = MtStream::new(random_seed);
+    let mut mts = MtStream::new(random_seed);
 
-    let mut input: Vec<u8> = random_bytes(5, 10, &mut rng);
+    let mut input: Vec<u8> = random_bytes(5, 10, rng);
     let controlled = b"AAAAAAAAAAAAAA";
     input.extend_from_slice(controlled);
 
-    let encrypted = input
-        .iter()
-        .zip(mts)
-        .map(|(v, k)| v ^ k)
-        .collect::<Vec<u8>>();
+    let encrypted = mts.apply_keystream(&input);
 
     let cracked_seed = crack_seed(&encrypted, controlled)?;
 
-    println!("True seed: {random_seed}");
-    println!("Cracked seed: {cracked_seed}");
-    assert_eq!(random_seed, cracked_seed);
+    say!("True seed: {random_seed}");
+    say!("Cracked seed: {cracked_seed}");
 
-    Ok(())
+    Ok(report.check("seed", random_seed, cracked_seed))
 }
 
 fn crack_seed(encrypted: &[u8], controlled: &[u8]) -> Result<u32> {
     let l = encrypted.len();
     let cl = controlled.len();
-    for i in 0..(1 << 16) {
-        let mts = MtStream::new(i);
-        let decrypted = encrypted
-            .iter()
-            .zip(mts)
-            .map(|(v, k)| v ^ k)
-            .collect::<Vec<u8>>();
-        if &decrypted[l - cl..] == controlled {
-            return Ok(i);
-        }
-    }
-
-    Err(anyhow!("Could not find seed"))
+    let (seed, stats) = par_search(0..1 << 16, |i| {
+        let decrypted = MtStream::new(i as u32).apply_keystream(encrypted);
+        &decrypted[l - cl..] == controlled
+    });
+    say!("{stats}");
+
+    seed.map(|s| s as u32)
+        .ok_or_else(|| anyhow!("Could not find seed"))
 }
 
 #[cfg(test)]
@@ -125,16 +93,29 @@ mod tests {
     use super::*;
     #[test]
     fn crack_seed_test() {
-        mt_seed_cracker().unwrap();
+        let report = mt_seed_cracker(&mut thread_rng(), Report::new()).unwrap();
+        assert_eq!(report.failures().count(), 0);
     }
 
     #[test]
     fn password_token() {
+        let mut rng = thread_rng();
         for _ in 0..100 {
-            pw_reset_token().unwrap();
+            let report = pw_reset_token(&mut rng, Report::new()).unwrap();
+            assert_eq!(report.failures().count(), 0);
         }
     }
 
+    #[test]
+    fn token_window() {
+        let now = 1_700_000_000;
+        let token = reset_token(now - 3000);
+        assert_eq!(token_timestamp(&token, now, 3600), Some(now - 3000));
+        // Too long ago, or not yet issued
+        assert_eq!(token_timestamp(&token, now, 2999), None);
+        assert_eq!(token_timestamp(&token, now - 3001, 3600), None);
+    }
+
     #[test]
     fn test_mt_stream() {
         for seed in 0..10 {
@@ -1,25 +1,11 @@
-pub mod challenge17;
-pub mod challenge18;
-pub mod challenge19;
-pub mod challenge20;
-pub mod challenge21;
-pub mod challenge22;
-pub mod challenge23;
-pub mod challenge24;
-
-use crate::utils::Result;
-use anyhow::anyhow;
-
-pub fn run(c: u64) -> Result<()> {
-    match c {
-        17 => challenge17::main(),
-        18 => challenge18::main(),
-        19 => challenge19::main(),
-        20 => challenge20::main(),
-        21 => challenge21::main(),
-        22 => challenge22::main(),
-        23 => challenge23::main(),
-        24 => challenge24::main(),
-        i => Err(anyhow!("{} not in set 3", i)),
-    }
+crate::runner::register! {
+    set 3;
+    17 => challenge17, "The CBC padding oracle", Fast;
+    18 => challenge18, "Implement CTR, the stream cipher mode", Fast;
+    19 => challenge19, "Break fixed-nonce CTR mode using substitutions", Fast;
+    20 => challenge20, "Break fixed-nonce CTR statistically", Fast;
+    21 => challenge21, "Implement the MT19937 Mersenne Twister RNG", Fast;
+    22 => challenge22, "Crack an MT19937 seed", Fast;
+    23 => challenge23, "Clone an MT19937 RNG from its output", Fast;
+    24 => challenge24, "Create the MT19937 stream cipher and break it", Fast;
 }
@@ -17,82 +17,85 @@
 //! is generate byte N of the keystream. Imagine if you'd relied on that advice to, say, encrypt a
 //! disk.
 
-use rand::{prelude::*, thread_rng};
+use anyhow::anyhow;
+use rand::prelude::*;
 
-use crate::stream::Ctr;
+use crate::aes::aes_ecb_decrypt;
+use crate::data;
+use crate::report::say;
+use crate::stream::{Ctr, StreamCipher};
 use crate::utils::*;
 
-fn edit(
-    ciphertext: &[u8],
-    key: &[u8],
+/// Encrypted "disk" which can be rewritten anywhere, without the key ever leaving it
+pub struct RandomAccessCtr {
+    key: Vec<u8>,
     nonce: u64,
-    offset: usize,
-    newtext: &[u8],
-) -> Result<Vec<u8>> {
-    let ctr = Ctr::new(key, nonce);
-    let decrypted = ciphertext
-        .iter()
-        .zip(ctr)
-        .map(|(k, v)| k ^ v)
-        .collect::<Vec<u8>>();
-    let edited = decrypted
-        .iter()
-        .enumerate()
-        .map(
-            |(i, v)| match (offset..(offset + newtext.len())).contains(&i) {
-                true => {
-                    let index = i - offset;
-                    newtext[index]
-                }
-                false => *v,
-            },
-        )
-        .collect::<Vec<u8>>();
-
-    let ctr = Ctr::new(key, nonce);
-    let encrypted_edited = edited
-        .iter()
-        .zip(ctr)
-        .map(|(v, k)| v ^ k)
-        .collect::<Vec<u8>>();
-
-    Ok(encrypted_edited)
+    ciphertext: Vec<u8>,
 }
 
-pub fn main() -> Result<()> {
-    let mut rng = thread_rng();
-
-    let nonce: u64 = rng.gen();
-    let key = random_key(16, &mut rng);
-
-    let all_lines: Vec<Vec<u8>> = read_base64_lines("./data/20.txt")?;
-    for data in all_lines {
-        let ctr = Ctr::new(&key, nonce);
-        let encrypted = data
-            .iter()
-            .zip(ctr)
-            .map(|(v, k)| v ^ k)
-            .collect::<Vec<u8>>();
-
-        // Fill with zeros, and then this is literally the keystream
-        let newtext = vec![0_u8; encrypted.len()];
-        let keystream = edit(&encrypted, &key, nonce, 0, &newtext)?;
-
-        let data_recovered = encrypted
-            .iter()
-            .zip(keystream.iter())
-            .map(|(k, v)| k ^ v)
-            .collect::<Vec<u8>>();
-
-        println!(
-            "Recovered: {}",
-            std::str::from_utf8(&data_recovered).unwrap()
-        );
+impl RandomAccessCtr {
+    /// Encrypts `plaintext` under a random key and nonce
+    pub fn new(plaintext: &[u8], rng: &mut impl Rng) -> Self {
+        Self::with_key(&random_key(16, rng), rng.gen(), plaintext)
+    }
 
-        assert_eq!(data_recovered, data);
+    pub fn with_key(key: &[u8], nonce: u64, plaintext: &[u8]) -> Self {
+        let ciphertext = Ctr::new(key, nonce).apply_keystream(plaintext);
+        Self {
+            key: key.to_vec(),
+            nonce,
+            ciphertext,
+        }
     }
 
-    Ok(())
+    pub fn ciphertext(&self) -> &[u8] {
+        &self.ciphertext
+    }
+
+    /// Overwrites the plaintext from `offset` with `newtext`, which has to fit on the disk
+    pub fn edit(&mut self, offset: usize, newtext: &[u8]) -> Result<()> {
+        let end = offset
+            .checked_add(newtext.len())
+            .filter(|end| *end <= self.ciphertext.len())
+            .ok_or_else(|| {
+                anyhow!(
+                    "Can't write {} bytes at {offset} on a disk of {} bytes",
+                    newtext.len(),
+                    self.ciphertext.len()
+                )
+            })?;
+        let mut ctr = Ctr::new(&self.key, self.nonce);
+        ctr.seek(offset);
+        self.ciphertext[offset..end].copy_from_slice(&ctr.apply_keystream(newtext));
+        Ok(())
+    }
+}
+
+/// Recovers everything on the disk using only the ciphertext and `edit`
+///
+/// Writing zeros puts the keystream itself on the disk, and the same keystream was used for the
+/// original ciphertext. The plaintext is written back afterwards, so nobody's any the wiser.
+pub fn recover_plaintext(disk: &mut RandomAccessCtr) -> Result<Vec<u8>> {
+    let ciphertext = disk.ciphertext().to_vec();
+    disk.edit(0, &vec![0; ciphertext.len()])?;
+    let plaintext = xor_bytes(&ciphertext, disk.ciphertext());
+    disk.edit(0, &plaintext)?;
+    Ok(plaintext)
+}
+
+pub fn main() -> Result<Report> {
+    // This is the file from challenge 7, under its ECB key
+    let ecb = data::CHALLENGE_25.base64()?;
+    let plaintext = pkcs7_unpad(&aes_ecb_decrypt(&ecb, b"YELLOW SUBMARINE")?)?;
+
+    let mut disk = RandomAccessCtr::new(&plaintext, &mut crate::utils::rng());
+    let original = disk.ciphertext().to_vec();
+    let recovered = recover_plaintext(&mut disk)?;
+    say!("Recovered: {}", String::from_utf8_lossy(&recovered));
+
+    Ok(Report::new()
+        .check("plaintext", plaintext, recovered)
+        .check("ciphertext afterwards", original, disk.ciphertext()))
 }
 
 #[cfg(test)]
@@ -101,6 +104,27 @@ mod test {
 
     #[test]
     fn recovery() {
-        main().unwrap();
+        main().and_then(Report::verify).unwrap();
+    }
+
+    #[test]
+    fn edits() {
+        let mut rng = thread_rng();
+        let key = random_key(16, &mut rng);
+        let plaintext = b"Imagine if you'd relied on that advice to, say, encrypt a disk.";
+        let mut disk = RandomAccessCtr::with_key(&key, 7, plaintext);
+
+        // Straddling blocks, from the middle of one
+        disk.edit(13, b"YOU had relied").unwrap();
+        let mut edited = plaintext.to_vec();
+        edited[13..27].copy_from_slice(b"YOU had relied");
+        assert_eq!(
+            disk.ciphertext(),
+            Ctr::new(&key, 7).apply_keystream(&edited)
+        );
+
+        disk.edit(plaintext.len(), b"").unwrap();
+        assert!(disk.edit(plaintext.len() - 1, b"!!").is_err());
+        assert!(disk.edit(usize::MAX, b"!").is_err());
     }
 }
@@ -6,9 +6,10 @@
 //! Re-implement the CBC bitflipping exercise from earlier to use CTR mode instead of CBC mode.
 //! Inject an "admin=true" token.
 
+use crate::report::say;
 use crate::stream::Ctr;
 use crate::utils::*;
-use rand::{prelude::*, thread_rng};
+use rand::prelude::*;
 
 use crate::set2::challenge16::contains_admin;
 
@@ -58,8 +59,8 @@ fn ctr_encrypt(text: &[u8], key: &[u8], nonce: u64) -> Vec<u8> {
     dec
 }
 
-pub fn main() -> Result<()> {
-    let mut rng = thread_rng();
+pub fn main() -> Result<Report> {
+    let mut rng = crate::utils::rng();
     let key = random_key(16, &mut rng);
     let nonce: u64 = rng.gen();
 
@@ -69,13 +70,12 @@ pub fn main() -> Result<()> {
     let target_decrypt = &ctr_encrypt(&modified, &key, nonce)[32..48];
 
     let target_str = std::str::from_utf8(target_decrypt).unwrap();
-    println!("Decrypted: {}", target_str);
+    say!("Decrypted: {}", target_str);
 
-    let whoami = match authorise(&modified, &key, nonce) {
-        Ok(true) => "admin",
-        _ => "not-admin",
-    };
-    println!("whoami: {}", whoami);
+    let admin = authorise(&modified, &key, nonce).is_ok_and(|admin| admin);
+    say!("whoami: {}", if admin { "admin" } else { "not-admin" });
 
-    Ok(())
+    Ok(Report::new()
+        .recovered("ciphertext", modified)
+        .forged("admin", admin))
 }
@@ -26,7 +26,9 @@
 //!
 //! P'_1 XOR P'_3'_1 XOR P'_3
 
+use crate::report::say;
 use crate::utils::*;
+use anyhow::anyhow;
 use thiserror::Error;
 
 use crate::set2::challenge16::contains_admin;
@@ -55,62 +57,92 @@ fn embed(input: &[u8], key: &[u8]) -> Result<Vec<u8>> {
 
 #[derive(Debug, Error)]
 pub enum ValidationErr {
-    #[error("High ascii detected")]
-    HighAscii { plaintext: Vec<u8> },
+    #[error("High ascii detected at byte {position}")]
+    HighAscii { plaintext: Vec<u8>, position: usize },
     #[error(transparent)]
     PaddingError(#[from] PaddingError),
     #[error(transparent)]
     CryptError(#[from] openssl::error::ErrorStack),
 }
 
-fn authorise(ciphertext: &[u8], key: &[u8]) -> Result<bool, ValidationErr> {
-    let dec = cbc_decrypt(ciphertext, key, Some(key))?;
+/// Checks that every byte is ASCII, handing the whole plaintext back in the error if not
+pub fn validate_ascii(plaintext: &[u8]) -> Result<(), ValidationErr> {
+    match plaintext.iter().position(|b| !b.is_ascii()) {
+        None => Ok(()),
+        Some(position) => Err(ValidationErr::HighAscii {
+            plaintext: plaintext.to_vec(),
+            position,
+        }),
+    }
+}
 
-    for i in &dec[..] {
-        if i > &128 {
-            return Err(ValidationErr::HighAscii { plaintext: dec });
+/// The receiving end of an AES-CBC channel, which checks everything it decrypts is ASCII and, as
+/// older systems are wont to, complains with the offending plaintext when it isn't
+pub struct AsciiReceiver {
+    key: Vec<u8>,
+    iv: Vec<u8>,
+}
+
+impl AsciiReceiver {
+    pub fn new(key: &[u8], iv: &[u8]) -> Self {
+        Self {
+            key: key.to_vec(),
+            iv: iv.to_vec(),
         }
     }
 
-    let unpadded = pkcs7_unpad(&dec)?;
+    /// Decrypts and unpads a message, which must be ASCII throughout
+    pub fn receive(&self, ciphertext: &[u8]) -> Result<Vec<u8>, ValidationErr> {
+        let dec = cbc_decrypt(ciphertext, &self.key, Some(&self.iv))?;
+        validate_ascii(&dec)?;
+        Ok(pkcs7_unpad(&dec)?)
+    }
+}
+
+fn authorise(ciphertext: &[u8], receiver: &AsciiReceiver) -> Result<bool, ValidationErr> {
+    let unpadded = receiver.receive(ciphertext)?;
 
     Ok(contains_admin(&unpadded))
 }
 
-pub fn main() -> Result<()> {
-    let mut rng = rand::thread_rng();
-    let key = random_key(16, &mut rng);
-    let input = b"aaaaaaaaaaaaaaaa";
-
-    let unmodified = embed(input, &key)?;
+/// Recovers the key from a receiver which uses it as the IV too
+fn recover_key(ciphertext: &[u8], receiver: &AsciiReceiver) -> Result<Vec<u8>> {
     let mut modified: Vec<u8> = Vec::with_capacity(48);
     // Decrypts C1 and XORs it with IV (key)
-    modified.extend_from_slice(&unmodified[..16]);
+    modified.extend_from_slice(&ciphertext[..16]);
     modified.extend_from_slice(&[0_u8; 16]);
     // Decrypts C3 and XORs it with all 0s
-    modified.extend_from_slice(&unmodified[..16]);
+    modified.extend_from_slice(&ciphertext[..16]);
 
     // So P1' ^ P3' = P1 ^ IV ^0 ^ P1 = IV = key !
 
-    println!("Modified {modified:?}");
+    say!("Modified {modified:?}");
 
-    let decrypted = match authorise(&modified, &key) {
-        Err(ValidationErr::HighAscii { plaintext }) => plaintext,
-        Ok(x) => panic!("Shouldnt decrypt, plaintext: {}", x),
-        Err(_) => panic!("Not supposed to work!"),
+    let decrypted = match authorise(&modified, receiver) {
+        Err(ValidationErr::HighAscii { plaintext, .. }) => plaintext,
+        Ok(x) => return Err(anyhow!("Decrypted without complaint, admin: {}", x)),
+        Err(e) => return Err(e.into()),
     };
 
     let p1 = &decrypted[..16];
     let p3 = &decrypted[32..48];
 
-    let key_derived: Vec<u8> = p1.iter().zip(p3.iter()).map(|(a, b)| a ^ b).collect();
+    Ok(p1.iter().zip(p3.iter()).map(|(a, b)| a ^ b).collect())
+}
+
+pub fn main() -> Result<Report> {
+    let mut rng = crate::utils::rng();
+    let key = random_key(16, &mut rng);
+    let receiver = AsciiReceiver::new(&key, &key);
+    let input = b"aaaaaaaaaaaaaaaa";
 
-    println!("Key (original): {key:?}");
-    println!("Key (derived):  {key_derived:?}");
+    let unmodified = embed(input, &key)?;
+    let key_derived = recover_key(&unmodified, &receiver)?;
 
-    assert_eq!(key, key_derived);
+    say!("Key (original): {key:?}");
+    say!("Key (derived):  {key_derived:?}");
 
-    Ok(())
+    Ok(Report::new().check("key", key, key_derived))
 }
 
 #[cfg(test)]
@@ -119,6 +151,34 @@ mod tests {
     use super::*;
     #[test]
     fn test_keys() {
-        main().unwrap();
+        main().and_then(Report::verify).unwrap();
+    }
+
+    #[test]
+    fn validation() {
+        assert!(validate_ascii(b"plain old ascii\x7f").is_ok());
+        assert!(matches!(
+            validate_ascii(b"not \x80 ascii"),
+            Err(ValidationErr::HighAscii { position: 4, .. })
+        ));
+
+        let mut rng = rand::thread_rng();
+        let key = random_key(16, &mut rng);
+        let iv = random_key(16, &mut rng);
+        let receiver = AsciiReceiver::new(&key, &iv);
+        let ct = cbc_encrypt(&pkcs7_pad(b"hello", 16), &key, Some(&iv)).unwrap();
+        assert_eq!(receiver.receive(&ct).unwrap(), b"hello");
+
+        let ct = cbc_encrypt(&pkcs7_pad("héllo".as_bytes(), 16), &key, Some(&iv)).unwrap();
+        match receiver.receive(&ct) {
+            Err(ValidationErr::HighAscii {
+                plaintext,
+                position,
+            }) => {
+                assert_eq!(plaintext, pkcs7_pad("héllo".as_bytes(), 16));
+                assert_eq!(position, 1);
+            }
+            r => panic!("Expected high ascii, got {r:?}"),
+        }
     }
 }
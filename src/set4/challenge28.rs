@@ -13,8 +13,7 @@
 //! Verify that you cannot tamper with the message without breaking the MAC you've produced, and
 //! that you can't produce a new MAC without knowing the secret key.
 
-use rand::thread_rng;
-
+use crate::report::say;
 use crate::utils::*;
 
 pub struct Sha1Hasher {
@@ -68,7 +67,6 @@ impl Sha1Hasher {
             449..=512 => 1024 - remainder - 64, // runs from 448 -> 512
             _ => panic!("Unable to pad properly"),
         };
-        //println!("k: {k}");
 
         let pad: Vec<u8> = vec![0; k / 8];
 
@@ -81,31 +79,14 @@ impl Sha1Hasher {
             .collect();
         //let ml_v: Vec<u8> = u32_to_u8s(8 * ml as u32);
         data.extend_from_slice(&ml_v);
-        //println!("dl: {}", data.len() * 8);
 
         assert_eq!((data.len() * 8) % 512, 0);
-        //println!("Padded:           {}", bytes_to_hex(&data));
 
-        /*
-        println!("==Initial hash values==");
-        println!("H[0] = {:x}", self.h0);
-        println!("H[1] = {:x}", self.h1);
-        println!("H[2] = {:x}", self.h2);
-        println!("H[3] = {:x}", self.h3);
-        println!("H[4] = {:x}", self.h4);
-        */
 
         // Want each chunk to be 512 bits
         for chunk in data.chunks(64) {
-            //println!("Chunk size: {} bits", chunk.len() * 8);
             // Each chunk is 16 32-bit big-endian words
             let mut w: Vec<u32> = chunk.chunks(4).map(u8s_to_u32).collect();
-            /*
-            println!("==Block contents==");
-            for (i, v) in w.iter().enumerate() {
-                println!("W[{i}] = {v:x}");
-            }
-            */
 
             // Extend w length from 16 -> 80 32 bit words
             let extender = vec![0; 80 - 16];
@@ -141,20 +122,12 @@ impl Sha1Hasher {
                 c = b.rotate_left(30);
                 b = a;
                 a = temp;
-                //println!("t= {t:02} {a:08x} {b:08x} {c:08x} {d:08x} {e:08x}");
             }
             self.h0 = self.h0.wrapping_add(a);
             self.h1 = self.h1.wrapping_add(b);
             self.h2 = self.h2.wrapping_add(c);
             self.h3 = self.h3.wrapping_add(d);
             self.h4 = self.h4.wrapping_add(e);
-            /*
-            println!("H[0] = {:x}", self.h0);
-            println!("H[1] = {:x}", self.h1);
-            println!("H[2] = {:x}", self.h2);
-            println!("H[3] = {:x}", self.h3);
-            println!("H[4] = {:x}", self.h4);
-            */
         }
 
         let mut hh = vec![];
@@ -209,31 +182,31 @@ pub fn authenticate(key: &[u8], message: &[u8], m: &[u8]) -> Auth {
     }
 }
 
-pub fn main() -> Result<()> {
-    let mut rng = thread_rng();
+pub fn main() -> Result<Report> {
+    let mut rng = crate::utils::rng();
     let key = random_key(16, &mut rng);
     let message = b"super secret test message";
 
     let m = mac(&key, message);
 
-    println!("Generate message and MAC");
-    println!("Authenticate MAC: {:?}", authenticate(&key, message, &m));
+    say!("Generate message and MAC");
+    say!("Authenticate MAC: {:?}", authenticate(&key, message, &m));
 
-    println!("Modify message");
+    say!("Modify message");
     let message_mod = b"supeR secret test message";
-    println!(
+    say!(
         "Authenticate MAC: {:?}",
         authenticate(&key, message_mod, &m)
     );
 
-    println!("Modify MAC");
-    let mut m_mod = m;
+    say!("Modify MAC");
+    let mut m_mod = m.clone();
     m_mod[5] = 2;
-    println!(
+    say!(
         "Authenticate MAC: {:?}",
         authenticate(&key, message, &m_mod)
     );
-    Ok(())
+    Ok(Report::new().unverified("mac", m))
 }
 
 #[cfg(test)]
@@ -242,7 +215,7 @@ mod tests {
 
     #[test]
     fn sha1test() {
-        let str_hash = vec![
+        let str_hash = [
             ("abc", "a9993e364706816aba3e25717850c26c9cd0d89d"),
             (
                 "abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq",
@@ -42,15 +42,100 @@
 //! This is a very useful attack.
 //! For instance: Thai Duong and Juliano Rizzo, who got to this attack before we did, used it to break the Flickr API.
 
-use rand::thread_rng;
+use std::ops::Range;
 
+use rand::Rng;
+
+use crate::progress;
+use crate::report::say;
 use crate::utils::*;
 
-pub fn main() -> Result<()> {
+/// A Merkle-Damgard hash which can carry on from where a digest left off
+pub trait LengthExtension {
+    /// The padding appended to a message of `len` bytes before hashing
+    fn glue_padding(len: usize) -> Vec<u8>;
+
+    /// Hashes `extra` starting from the state in `digest`, padding it as though the whole message
+    /// came to `total_len` bytes
+    fn extend(digest: &[u8], extra: &[u8], total_len: usize) -> Vec<u8>;
+}
+
+impl LengthExtension for Sha1Hasher {
+    fn glue_padding(len: usize) -> Vec<u8> {
+        sha1padding(len as u64)
+    }
+
+    fn extend(digest: &[u8], extra: &[u8], total_len: usize) -> Vec<u8> {
+        Sha1Hasher::load(digest).hash(extra, Some(total_len))
+    }
+}
+
+/// A message and MAC which should pass as having been made with the secret key
+#[derive(Debug)]
+pub struct Forgery {
+    pub key_len: usize,
+    pub message: Vec<u8>,
+    pub mac: Vec<u8>,
+}
+
+/// Extends `message`, with MAC H(key || message), by `addition`, assuming a key of `key_len` bytes
+pub fn forge<H: LengthExtension>(
+    message: &[u8],
+    mac: &[u8],
+    addition: &[u8],
+    key_len: usize,
+) -> Forgery {
+    // The hasher's state after working through
+    // |key||message||    glue     ||
+    // is just the MAC, so we carry on from there, and the final padding must account for all of
+    // |key||message||    glue     || addition || (implied glue)
+    let glue = H::glue_padding(key_len + message.len());
+    let total_len = key_len + message.len() + glue.len() + addition.len();
+    let mac = H::extend(mac, addition, total_len);
+
+    // Which is the MAC of
+    // | message || glue || addition
+    let mut forged = message.to_vec();
+    forged.extend_from_slice(&glue);
+    forged.extend_from_slice(addition);
+
+    Forgery {
+        key_len,
+        message: forged,
+        mac,
+    }
+}
+
+/// Tries a forgery for each key length in `key_lens` until `accepted` takes one, as the server
+/// checking MACs would
+pub fn forge_unknown_key_len<H, F>(
+    message: &[u8],
+    mac: &[u8],
+    addition: &[u8],
+    key_lens: Range<usize>,
+    mut accepted: F,
+) -> Option<Forgery>
+where
+    H: LengthExtension,
+    F: FnMut(&[u8], &[u8]) -> bool,
+{
+    let pb = progress::bar(key_lens.len() as u64);
+    pb.set_message("Guessing key lengths".to_string());
+
+    let forgery = key_lens
+        .map(|key_len| forge::<H>(message, mac, addition, key_len))
+        .inspect(|_| pb.inc(1))
+        .find(|forgery| accepted(&forgery.message, &forgery.mac));
+    pb.clear();
+    forgery
+}
+
+pub fn main() -> Result<Report> {
     // We have our SHA-1 implementation, and the helper function should already be there to resume
     // from a particular hash
-    let mut rng = thread_rng();
-    let key = random_key(16, &mut rng);
+    let mut rng = crate::utils::rng();
+    let key_len = rng.gen_range(1..=64);
+    let key = random_key(key_len, &mut rng);
     let base_message =
         b"comment1=cooking%20MCs;userdata=foo;comment2=%20like%20a%20pound%20of%20bacon";
 
@@ -60,55 +145,29 @@ pub fn main() -> Result<()> {
     let mut hasher = Sha1Hasher::default();
     let mac = hasher.hash(&message, None);
     let auth = authenticate(&key, base_message, &mac);
-    println!("Original message authentication: {:?}", auth);
+    say!("Original message authentication: {:?}", auth);
 
-    // Now to extend!
-    let mut new_mac = vec![];
-    let mut key_len = 0;
+    // Now to extend, without knowing how long the key is
     let addition = b";admin=true;";
-    let mut new_message: Vec<u8> = vec![];
-    let bml = base_message.len() as u64;
-    while authenticate(&key, &new_message, &new_mac) != Auth::Valid {
-        key_len += 1;
-        // What's the idea? We want to take the original mac and start the hasher from this state
-        // 1. Set initial hashing values from what we had before
-        // and run from this
-        let mut cont_hasher = Sha1Hasher::load(&mac);
-        // This should be the state of the hasher after working through
-        // |key||message||    glue     ||
-        // The new mac must account for extra padding
-        // The message length must be that of the original padded message + addition
-        let glue = sha1padding(key_len + bml);
-
-        let total_new_l = glue.len() + key_len as usize + bml as usize + addition.len();
-
-        new_mac = cont_hasher.hash(addition, Some(total_new_l));
-        // We now add addition into this, which should be the hash of
-        // |key||message||    glue     || addition || (implied glue)
-
-        // This new_mac therefore corresponds to the mac of
-        // | message || glue || addition
-        // Which we should now construct as our new message
-        new_message = base_message.to_vec();
-
-        new_message.extend_from_slice(&glue);
-
-        new_message.extend_from_slice(addition);
-        //println!("New message:      {}", bytes_to_hex(&new_message));
-
-        // This padded version should be a multiple of 64 + new_message
-        //println!("New message len + key_len: {}", new_message.len() + key_len);
-    }
+    let forgery =
+        forge_unknown_key_len::<Sha1Hasher, _>(base_message, &mac, addition, 0..256, |m, t| {
+            authenticate(&key, m, t) == Auth::Valid
+        })
+        .ok_or_else(|| anyhow::anyhow!("No key length up to 256 bytes worked"))?;
 
-    println!("Key length: {}", key_len);
-    println!("Original message: {}", bytes_to_hex(base_message));
-    println!("New message:      {}", bytes_to_hex(&new_message));
-    println!("New mac: {}", bytes_to_hex(&new_mac));
+    say!("Key length: {}", forgery.key_len);
+    say!("Original message: {}", bytes_to_hex(base_message));
+    say!("New message:      {}", bytes_to_hex(&forgery.message));
+    say!("New mac: {}", bytes_to_hex(&forgery.mac));
 
-    let auth = authenticate(&key, &new_message, &new_mac);
-    println!("Authentication status: {:?}", auth);
+    let auth = authenticate(&key, &forgery.message, &forgery.mac);
+    say!("Authentication status: {:?}", auth);
 
-    Ok(())
+    Ok(Report::new()
+        .recovered("message", forgery.message)
+        .recovered("mac", forgery.mac)
+        .forged("mac", auth == Auth::Valid)
+        .check("key length", key_len, forgery.key_len))
 }
 
 fn sha1padding(ml: u64) -> Vec<u8> {
@@ -123,7 +182,6 @@ fn sha1padding(ml: u64) -> Vec<u8> {
         449..=512 => 1024 - remainder - 64, // runs from 448 -> 512
         _ => panic!("Unable to pad properly"),
     };
-    //println!("k: {k}");
 
     let pad: Vec<u8> = vec![0; k / 8];
 
@@ -136,7 +194,6 @@ fn sha1padding(ml: u64) -> Vec<u8> {
         .collect();
     //let ml_v: Vec<u8> = u32_to_u8s(8 * ml as u32);
     data.extend_from_slice(&ml_v);
-    //println!("dl: {}", data.len() * 8);
 
     assert_eq!((data.len() * 8) % 512, 0);
     // Now drop first ml bytes
@@ -192,4 +249,36 @@ mod tests {
 
         assert_eq!(e_mac, me_mac);
     }
+
+    #[test]
+    fn key_length_scan() {
+        let mut rng = rand::thread_rng();
+        let message = b"comment1=cooking%20MCs;userdata=foo";
+        for key_len in [0, 1, 20, 55, 64, 100] {
+            let key = random_key(key_len, &mut rng);
+            let mac = Sha1Hasher::default().hash(&[&key[..], message].concat(), None);
+            let valid = |m: &[u8], t: &[u8]| authenticate(&key, m, t) == Auth::Valid;
+
+            let forgery = forge_unknown_key_len::<Sha1Hasher, _>(
+                message,
+                &mac,
+                b";admin=true",
+                0..128,
+                valid,
+            )
+            .unwrap();
+            assert_eq!(forgery.key_len, key_len);
+            assert!(forgery.message.ends_with(b";admin=true"));
+
+            // Nothing to find if the range misses it
+            let short = forge_unknown_key_len::<Sha1Hasher, _>(
+                message,
+                &mac,
+                b";admin=true",
+                key_len + 1..key_len + 10,
+                valid,
+            );
+            assert!(short.is_none());
+        }
+    }
 }
@@ -29,20 +29,80 @@
 //! attacking real-world timing leaks, you have to start writing low-level timing code. We're
 //! keeping things cryptographic in these challenges.
 
-use itertools::Itertools;
-use std::time::Duration;
+use std::fmt;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
 
-use chrono::Utc;
-use rand::thread_rng;
+use anyhow::anyhow;
+use rand::Rng;
 
+use crate::report::say;
 use crate::utils::*;
 
-fn sha1_hmac(key: &[u8], message: &[u8]) -> Vec<u8> {
+/// Which MAC the server checks signatures with
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum MacAlgorithm {
+    #[default]
+    HmacSha1,
+    HmacSha256,
+    /// SHA-1(key || message)
+    Sha1,
+    /// SHA-256(key || message)
+    Sha256,
+}
+
+impl MacAlgorithm {
+    pub fn mac(&self, key: &[u8], message: &[u8]) -> Vec<u8> {
+        match self {
+            Self::HmacSha1 => sha1_hmac(key, message),
+            Self::HmacSha256 => hmac_sha256::HMAC::mac(message, key).to_vec(),
+            Self::Sha1 => sha1_hash(&[key, message].concat()),
+            Self::Sha256 => hmac_sha256::Hash::hash(&[key, message].concat()).to_vec(),
+        }
+    }
+
+    /// Length of the MACs in bytes
+    pub fn mac_len(&self) -> usize {
+        match self {
+            Self::HmacSha1 | Self::Sha1 => 20,
+            Self::HmacSha256 | Self::Sha256 => 32,
+        }
+    }
+}
+
+impl FromStr for MacAlgorithm {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "hmac-sha1" => Ok(Self::HmacSha1),
+            "hmac-sha256" => Ok(Self::HmacSha256),
+            "sha1" => Ok(Self::Sha1),
+            "sha256" => Ok(Self::Sha256),
+            _ => Err(anyhow!(
+                "Unknown MAC {s}, expected hmac-sha1, hmac-sha256, sha1 or sha256"
+            )),
+        }
+    }
+}
+
+impl fmt::Display for MacAlgorithm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::HmacSha1 => "HMAC-SHA1",
+            Self::HmacSha256 => "HMAC-SHA256",
+            Self::Sha1 => "SHA-1(key || message)",
+            Self::Sha256 => "SHA-256(key || message)",
+        };
+        write!(f, "{name}")
+    }
+}
+
+pub fn sha1_hmac(key: &[u8], message: &[u8]) -> Vec<u8> {
     let mut o_pad = vec![0x5c; 64];
     let mut i_pad = vec![0x36; 64];
     let kp = kprime(key);
     assert_eq!(kp.len(), 64);
-    //println!("kp: {}", bytes_to_hex(&kp));
 
     i_pad = i_pad.iter().zip(kp.iter()).map(|(k, v)| k ^ v).collect();
     i_pad.extend_from_slice(message);
@@ -74,69 +134,251 @@ fn kprime(key: &[u8]) -> Vec<u8> {
     key
 }
 
-fn insecure_compare(file: &[u8], hmac: &[u8], key: &[u8]) -> Auth {
-    let true_hmac = sha1_hmac(key, file);
-    let delay = 10;
-    for (i, v) in true_hmac.iter().enumerate() {
-        if hmac[i] != *v {
-            return Auth::Invalid;
+/// The server from the challenge, checking signatures on files with a leaky comparison
+pub struct Server {
+    key: Vec<u8>,
+    algorithm: MacAlgorithm,
+    delay: Duration,
+}
+
+impl Server {
+    pub fn new(algorithm: MacAlgorithm, delay: Duration, rng: &mut impl Rng) -> Self {
+        Self {
+            key: random_key(16, rng),
+            algorithm,
+            delay,
         }
-        std::thread::sleep(Duration::from_millis(delay));
-    }
-    Auth::Valid
-}
-
-pub fn main() -> Result<()> {
-    let mut rng = thread_rng();
-    let key = random_key(16, &mut rng);
-    let h = sha1_hmac(&key, b"file");
-
-    println!("This one can take quite a while to run!");
-    let mut guess: Vec<u8> = vec![0; 20];
-
-    for i in 0..guess.len() {
-        println!("True:  {}", bytes_to_hex(&h));
-        let mut bs = vec![];
-        for _ in 0..5 {
-            let b = (0..255_u8)
-                .map(|x| {
-                    guess[i] = x;
-
-                    let start = Utc::now();
-                    match insecure_compare(b"file", &guess, &key) {
-                        Auth::Valid => println!("Guess is valid!"),
-                        Auth::Invalid => {}
-                    };
-                    let stop = Utc::now();
-
-                    let d = (stop - start).num_microseconds().unwrap();
-                    (x, d)
-                })
-                .collect::<Vec<(u8, i64)>>();
-            bs.extend_from_slice(&b);
+    }
+
+    pub fn algorithm(&self) -> MacAlgorithm {
+        self.algorithm
+    }
+
+    /// The valid signature, to check the attack's answer against
+    pub fn sign(&self, file: &[u8]) -> Vec<u8> {
+        self.algorithm.mac(&self.key, file)
+    }
+
+    pub fn verify(&self, file: &[u8], signature: &[u8]) -> Auth {
+        match insecure_compare(&self.sign(file), signature, self.delay) {
+            true => Auth::Valid,
+            false => Auth::Invalid,
         }
-        let b = get_max_b(&bs);
+    }
+}
 
-        guess[i] = b;
-        println!("Guess: {}", bytes_to_hex(&guess[..i]));
+/// Byte-at-a-time comparison with early exit, sleeping after each byte which matches
+fn insecure_compare(expected: &[u8], given: &[u8], delay: Duration) -> bool {
+    for (e, g) in expected.iter().zip(given.iter()) {
+        if e != g {
+            return false;
+        }
+        std::thread::sleep(delay);
     }
-    println!("Guess: {}", bytes_to_hex(&guess));
-    assert_eq!(h, guess);
+    expected.len() == given.len()
+}
+
+/// Recovers a valid signature for `file` from the timing leak, one byte at a time
+///
+/// Every guess for a byte is timed over and over, and the one with the slowest median wins. The
+/// further along the signature, the more the server's sleeps jitter, so after the number of
+/// samples from calibration the noise is measured again and more are taken if need be. The number
+/// of bytes comes from the server's MAC algorithm.
+///
+/// If a byte was wrong then nothing after it takes any longer, so when the typical time stops going
+/// up (or nothing works for the last byte) the previous byte gets another go.
+pub fn timing_attack(server: &Server, file: &[u8], calibration: &Calibration) -> Vec<u8> {
+    let mut guess: Vec<u8> = vec![0; server.algorithm().mac_len()];
+    // Typical and slowest median time for each byte settled so far
+    let mut history: Vec<(Duration, Duration)> = vec![];
+
+    while history.len() < guess.len() {
+        let i = history.len();
+        let mut timings = vec![vec![]; 256];
+        let mut samples = calibration.samples;
+        let mut rounds = 0;
+        while rounds < samples {
+            for x in 0..=255_u8 {
+                guess[i] = x;
+
+                let (elapsed, auth) = time_request(server, file, &guess);
+                timings[x as usize].push(elapsed);
+
+                if auth == Auth::Valid {
+                    return guess;
+                }
+            }
+            rounds += 1;
+            if rounds == calibration.samples {
+                let mut all: Vec<Duration> = timings.concat();
+                let noise = robust_spread(&mut all);
+                samples = samples.max(samples_needed(
+                    noise,
+                    calibration.leak,
+                    calibration.confidence,
+                    guess.len(),
+                ));
+            }
+        }
+        let (best, slowest, typical) = rank_medians(&mut timings);
+
+        // Each right byte should add another delay, going by the average so far
+        let lost = match (history.first(), history.last()) {
+            (Some(&(first_typical, first_slowest)), Some(&(prev_typical, _))) => {
+                let delay = match i {
+                    1 => first_slowest - first_typical,
+                    _ => (prev_typical - first_typical.min(prev_typical)) / (i - 1) as u32,
+                };
+                i + 1 == guess.len() || typical < prev_typical + delay / 2
+            }
+            _ => false,
+        };
+        if lost {
+            say!("Lost the trail, backing up");
+            history.pop();
+            continue;
+        }
+
+        guess[i] = best;
+        history.push((typical, slowest));
+        say!("Guess: {} ({samples} samples)", bytes_to_hex(&guess[..=i]));
+    }
+    guess
+}
 
-    Ok(())
+fn time_request(server: &Server, file: &[u8], signature: &[u8]) -> (Duration, Auth) {
+    let start = Instant::now();
+    let auth = server.verify(file, signature);
+    (start.elapsed(), auth)
 }
 
-fn get_max_b(b: &[(u8, i64)]) -> u8 {
-    let mut results = vec![0; b.len()];
-    for (v, t) in b {
-        results[*v as usize] += *t;
+/// Requests timed against a signature which can't be right, to see how noisy the server is
+const BASELINE_REQUESTS: usize = 200;
+/// Rounds over every first byte to get a rough idea of how big the leak is
+const PILOT_ROUNDS: usize = 3;
+/// The median of any fewer can't shrug off a single hiccup
+const MIN_SAMPLES: usize = 3;
+const MAX_SAMPLES: usize = 200;
+
+/// What the client learns about the server before attacking it
+#[derive(Debug)]
+pub struct Calibration {
+    /// Spread of the time for a single request
+    pub noise: Duration,
+    /// How much longer each right byte makes a request take
+    pub leak: Duration,
+    /// Samples per guess to get the whole signature right with the confidence asked for
+    pub samples: usize,
+    pub confidence: f64,
+}
+
+/// Measures the noise on requests with an empty signature, which is always wrong, and roughly
+/// sizes up the leak from a quick pass over the first byte, then works out how many samples each
+/// guess needs for the attack to succeed with probability `confidence`
+pub fn calibrate(server: &Server, file: &[u8], confidence: f64) -> Calibration {
+    let mut baseline: Vec<Duration> = (0..BASELINE_REQUESTS)
+        .map(|_| time_request(server, file, &[]).0)
+        .collect();
+    let noise = robust_spread(&mut baseline);
+
+    let mut guess = vec![0; server.algorithm().mac_len()];
+    let mut timings = vec![vec![]; 256];
+    for _ in 0..PILOT_ROUNDS {
+        for x in 0..=255_u8 {
+            guess[0] = x;
+            timings[x as usize].push(time_request(server, file, &guess).0);
+        }
     }
-    results.iter().position_max().unwrap() as u8
+    let (_, slowest, typical) = rank_medians(&mut timings);
+    let leak = slowest - typical;
+
+    Calibration {
+        noise,
+        leak,
+        samples: samples_needed(noise, leak, confidence, guess.len()),
+        confidence,
+    }
+}
+
+/// Standard deviation estimated from the median absolute deviation, so that the odd request held
+/// up by the scheduler doesn't count for much
+fn robust_spread(times: &mut [Duration]) -> Duration {
+    times.sort();
+    let median = times[times.len() / 2];
+    let mut deviations: Vec<Duration> = times.iter().map(|t| t.abs_diff(median)).collect();
+    deviations.sort();
+    deviations[deviations.len() / 2].mul_f64(1.4826)
+}
+
+/// Samples per guess for the right guess's median to beat all 255 wrong ones at every one of
+/// `bytes` positions with probability `confidence`, taking the noise to be normal
+fn samples_needed(noise: Duration, leak: Duration, confidence: f64, bytes: usize) -> usize {
+    if leak.is_zero() {
+        return MAX_SAMPLES;
+    }
+    // Chance of a particular wrong guess coming out on top, splitting the failures allowed evenly
+    let p = (1.0 - confidence) / (255 * bytes) as f64;
+    let z = normal_quantile(1.0 - p);
+    // The median of n samples has a standard deviation of about 1.2533 sigma / sqrt(n), and the
+    // difference between two of them sqrt(2) times that, which has to be z of them below the leak
+    let n = 2.0 * (1.2533 * z * noise.as_secs_f64() / leak.as_secs_f64()).powi(2);
+    (n.ceil() as usize).clamp(MIN_SAMPLES, MAX_SAMPLES)
+}
+
+/// Inverse of the standard normal CDF for 0.5 <= p < 1, from Abramowitz and Stegun 26.2.23
+fn normal_quantile(p: f64) -> f64 {
+    let t = (-2.0 * (1.0 - p).ln()).sqrt();
+    t - (2.515517 + 0.802853 * t + 0.010328 * t * t)
+        / (1.0 + 1.432788 * t + 0.189269 * t * t + 0.001308 * t * t * t)
+}
+
+/// Takes the median time for each guess, returning the slowest guess along with its median and the
+/// median of all of them
+fn rank_medians(timings: &mut [Vec<Duration>]) -> (u8, Duration, Duration) {
+    let mut medians: Vec<(u8, Duration)> = timings
+        .iter_mut()
+        .enumerate()
+        .map(|(x, t)| {
+            t.sort();
+            (x as u8, t[t.len() / 2])
+        })
+        .collect();
+    medians.sort_by_key(|(_, t)| *t);
+    let typical = medians[medians.len() / 2].1;
+    let (best, slowest) = medians[medians.len() - 1];
+    (best, slowest, typical)
+}
+
+pub fn main() -> Result<Report> {
+    let algorithm = crate::options::get().mac;
+    let server = Server::new(
+        algorithm,
+        Duration::from_millis(10),
+        &mut crate::utils::rng(),
+    );
+    let h = server.sign(b"file");
+
+    say!("Breaking {algorithm}, this one can take quite a while to run!");
+    say!("True:  {}", bytes_to_hex(&h));
+    let calibration = calibrate(&server, b"file", 0.99);
+    say!(
+        "Noise {:.2?}, leak {:.2?}: taking {} samples per guess",
+        calibration.noise,
+        calibration.leak,
+        calibration.samples
+    );
+    let guess = timing_attack(&server, b"file", &calibration);
+    say!("Guess: {}", bytes_to_hex(&guess));
+
+    Ok(Report::new()
+        .check("mac", h, guess)
+        .count("samples per guess", calibration.samples as u64))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use rand::thread_rng;
 
     #[test]
     fn hmac_check() {
@@ -147,4 +389,65 @@ mod tests {
         let hmac = sha1_hmac(key, message);
         assert_eq!(hmac, target);
     }
+
+    #[test]
+    fn algorithms() {
+        // RFC 4231, test case 2
+        let hmac = MacAlgorithm::HmacSha256.mac(b"Jefe", b"what do ya want for nothing?");
+        let target = "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843";
+        assert_eq!(bytes_to_hex(&hmac), target);
+
+        for name in ["hmac-sha1", "hmac-sha256", "sha1", "sha256"] {
+            let algorithm: MacAlgorithm = name.parse().unwrap();
+            assert_eq!(algorithm.mac(b"key", b"message").len(), algorithm.mac_len());
+        }
+        assert!("md5".parse::<MacAlgorithm>().is_err());
+    }
+
+    #[test]
+    fn comparison() {
+        let server = Server::new(MacAlgorithm::Sha256, Duration::ZERO, &mut thread_rng());
+        let mac = server.sign(b"file");
+        assert_eq!(server.verify(b"file", &mac), Auth::Valid);
+        assert_eq!(server.verify(b"file", &mac[..20]), Auth::Invalid);
+        assert_eq!(server.verify(b"other", &mac), Auth::Invalid);
+    }
+
+    #[test]
+    fn sample_sizes() {
+        assert!((normal_quantile(0.975) - 1.96).abs() < 1e-3);
+        assert!((normal_quantile(0.5)).abs() < 1e-3);
+
+        let ms = Duration::from_millis(1);
+        assert_eq!(samples_needed(ms / 100, ms, 0.99, 20), MIN_SAMPLES);
+        assert_eq!(samples_needed(ms, Duration::ZERO, 0.99, 20), MAX_SAMPLES);
+        let noisy = samples_needed(ms / 2, ms, 0.99, 20);
+        assert!(noisy > MIN_SAMPLES);
+        assert!(samples_needed(ms / 2, ms, 0.999, 20) > noisy);
+        assert!(samples_needed(ms / 2, ms, 0.99, 32) > noisy);
+        assert!(samples_needed(ms, ms, 0.99, 20) > noisy);
+    }
+
+    #[test]
+    fn calibration() {
+        let delay = Duration::from_millis(1);
+        let server = Server::new(MacAlgorithm::HmacSha1, delay, &mut thread_rng());
+        let calibration = calibrate(&server, b"file", 0.99);
+        println!("{calibration:?}");
+        assert!(calibration.leak > delay / 2);
+        assert!((MIN_SAMPLES..=MAX_SAMPLES).contains(&calibration.samples));
+    }
+
+    #[test]
+    #[ignore = "slow"]
+    fn attack() {
+        let server = Server::new(
+            MacAlgorithm::HmacSha1,
+            Duration::from_millis(1),
+            &mut thread_rng(),
+        );
+        let calibration = calibrate(&server, b"file", 0.99);
+        let guess = timing_attack(&server, b"file", &calibration);
+        assert_eq!(guess, server.sign(b"file"));
+    }
 }
@@ -40,11 +40,10 @@
 //! because you'll blow out your bignum library raising "a" to the 1024-bit-numberth power. You can
 //! find modexp routines on Rosetta Code for most languages.
 
-use crate::{dh::nist_params, utils::*};
-use num_bigint::{BigInt, RandBigInt};
-use num_traits::Zero;
-use openssl::hash::{Hasher, MessageDigest};
-use rand::thread_rng;
+use crate::dh::{nist_params, Party};
+use crate::report::say;
+use crate::utils::*;
+use num_bigint::BigInt;
 
 // BigInt has a modular exponentiation built in already
 /*
@@ -81,39 +80,48 @@ fn modexp<T: ToBigInt>(base: &T, exp: &T, modulus: &T) -> BigInt {
 }
 */
 
-pub fn main() -> Result<()> {
+pub fn main() -> Result<Report> {
     //let p: BigInt = 37.into();
     //let g: BigInt = 5.into();
 
+    let mut rng = crate::utils::rng();
     let (p, g) = nist_params();
-    let mut rng = thread_rng();
-    let a: BigInt = rng.gen_bigint_range(&Zero::zero(), &p);
-    let b: BigInt = rng.gen_bigint_range(&Zero::zero(), &p);
-    println!("a: {a}, b: {b}");
-
-    let pub_a: BigInt = g.modpow(&a, &p);
-    let pub_b: BigInt = g.modpow(&b, &p);
-    println!("A: {pub_a}, B: {pub_b}");
-
-    let s_a: BigInt = pub_b.modpow(&a, &p);
-    let s_b: BigInt = pub_a.modpow(&b, &p);
-    println!("s: {s_a}");
-    assert_eq!(s_a, s_b);
-
-    let s_bytes = s_a.to_bytes_be().1;
-    let mut h = Hasher::new(MessageDigest::sha256())?;
-    h.update(&s_bytes)?;
-
-    let key = h.finish()?;
-    println!("Shared key: {}", bytes_to_hex(&key));
-
-    Ok(())
+    let alice = Party::new(&p, &g, &mut rng);
+    let bob = Party::new(&p, &g, &mut rng);
+    say!("A: {}, B: {}", alice.public, bob.public);
+
+    let s_a: BigInt = alice.shared_secret(&bob.public);
+    let s_b: BigInt = bob.shared_secret(&alice.public);
+    say!("s: {s_a}");
+
+    // Both sides hash the secret into the same AES key...
+    let alice_session = alice.session(&bob.public);
+    let bob_session = bob.session(&alice.public);
+    say!("Shared key: {}", bytes_to_hex(alice_session.key()));
+
+    // ...and can now talk: Alice sends a message, and Bob echoes it back
+    let message = b"Cooking MC's like a pound of bacon";
+    let to_bob = alice_session.encrypt(message, &mut rng)?;
+    say!("A->B: {}", bytes_to_hex(&to_bob));
+    let received = bob_session.decrypt(&to_bob)?;
+    let to_alice = bob_session.encrypt(&received, &mut rng)?;
+    say!("B->A: {}", bytes_to_hex(&to_alice));
+    let echoed = alice_session.decrypt(&to_alice)?;
+    say!("Echoed: {}", String::from_utf8_lossy(&echoed));
+
+    Ok(Report::new()
+        .check("shared secret", s_a, s_b)
+        .recovered("key", alice_session.key())
+        .check("echoed", message, echoed))
 }
 
 #[cfg(test)]
 mod tests {
 
     use super::*;
+    use num_bigint::RandBigInt;
+    use num_traits::Zero;
+    use rand::thread_rng;
 
     #[test]
     fn small_ints() {
@@ -137,6 +145,6 @@ mod tests {
 
     #[test]
     fn big_ints() {
-        main().unwrap();
+        main().and_then(Report::verify).unwrap();
     }
 }
@@ -42,12 +42,13 @@
 //! could just generate Ma, MA, Mb, and MB as valid DH parameters to do a generic MITM attack. But
 //! do the parameter injection attack; it's going to come up again.
 
-use crate::dh::nist_params;
+use crate::dh::{nist_params, Direction, Party, Relay, Session};
+use crate::report::say;
 use crate::utils::*;
-use num_bigint::{BigInt, RandBigInt};
+use anyhow::anyhow;
+use num_bigint::BigInt;
 use num_traits::Zero;
-use openssl::hash::{Hasher, MessageDigest};
-use rand::{distributions::Alphanumeric, thread_rng, Rng};
+use rand::{distributions::Alphanumeric, Rng};
 
 // What happens here?
 //
@@ -61,74 +62,88 @@ use rand::{distributions::Alphanumeric, thread_rng, Rng};
 //
 // Uh oh...
 
-pub fn main() -> Result<()> {
-    let (p, g) = nist_params();
-
-    let mut rng = thread_rng();
-    let a: BigInt = rng.gen_bigint_range(&Zero::zero(), &p);
-    let b: BigInt = rng.gen_bigint_range(&Zero::zero(), &p);
-    println!("a: {a}, b: {b}");
-
-    let pub_a: BigInt = g.modpow(&a, &p);
-    let pub_b: BigInt = g.modpow(&b, &p);
-    println!("A: {pub_a}, B: {pub_b}");
-
-    // Injected parameters!
-    let s_a: BigInt = p.modpow(&a, &p);
-    let s_b: BigInt = p.modpow(&b, &p);
-    println!("s: {s_a}");
-    assert_eq!(s_a, s_b);
-
-    let s_bytes = s_a.to_bytes_be().1;
-    let mut h = Hasher::new(MessageDigest::sha256())?;
-    h.update(&s_bytes)?;
-
-    let shared_key = &h.finish()?[..16].to_vec();
-
-    let mut h = Hasher::new(MessageDigest::sha256())?;
-    h.update(&[0])?;
-    let m_key = &h.finish()?[..16].to_vec();
-
-    println!("Shared key:    {}", bytes_to_hex(shared_key));
-    println!("M deduces key: {}", bytes_to_hex(m_key));
-
-    println!("Shared key length: {}", shared_key.len());
-    assert_eq!(shared_key, m_key);
-
-    println!("=== BEGINNING COMMUNICATIONS ===");
-    let a_iv = random_key(16, &mut rng);
-    let a_plaintext: Vec<u8> = thread_rng()
-        .sample_iter(&Alphanumeric)
-        .take(22)
-        .map(u8::from)
-        .collect();
-    println!("A plaintext: {:?}", std::str::from_utf8(&a_plaintext)?);
-    let a_plaintext = pkcs7_pad(&a_plaintext, 16);
-    let a_ciphertext = cbc_encrypt(&a_plaintext, shared_key, Some(&a_iv))?;
-    let a_message = (a_iv, a_ciphertext);
-
-    let m_decrypted = cbc_decrypt(&a_message.1, m_key, Some(&a_message.0))?;
-    assert_eq!(a_plaintext, m_decrypted);
+/// A sends each message to B, who echoes it back, all by way of Mallory
+///
+/// Checks that A gets back exactly what it sent, and that Mallory read the whole conversation
+pub fn converse(
+    alice: &Session,
+    bob: &Session,
+    mallory: &mut Relay,
+    messages: &[Vec<u8>],
+    rng: &mut impl Rng,
+) -> Result<()> {
+    let mut conversation = vec![];
+    for message in messages {
+        let to_bob = mallory.a_to_b(&alice.encrypt(message, rng)?, rng)?;
+        let received = bob.decrypt(&to_bob)?;
+        let to_alice = mallory.b_to_a(&bob.encrypt(&received, rng)?, rng)?;
+        let echoed = alice.decrypt(&to_alice)?;
+        say!("A sent:   {}", String::from_utf8_lossy(message));
+        say!("A got:    {}", String::from_utf8_lossy(&echoed));
+        if &echoed != message {
+            return Err(anyhow!("Echo didn't match what was sent"));
+        }
+        conversation.push((Direction::AToB, message.clone()));
+        conversation.push((Direction::BToA, received));
+    }
 
-    println!(
-        "M intercepted A: {}",
-        std::str::from_utf8(&pkcs7_unpad(&m_decrypted).unwrap()).unwrap()
-    );
+    for (direction, message) in &mallory.log {
+        say!("M read {direction:?}: {}", String::from_utf8_lossy(message));
+    }
+    match mallory.log == conversation {
+        true => Ok(()),
+        false => Err(anyhow!("M's log doesn't match the conversation")),
+    }
+}
 
-    let b_decrypted = cbc_decrypt(&a_message.1, shared_key, Some(&a_message.0))?;
-    let b_iv = random_key(16, &mut rng);
-    let b_ciphertext = cbc_encrypt(&b_decrypted, shared_key, Some(&b_iv))?;
-    let b_message = (b_iv, b_ciphertext);
+/// A few random lines of text for A to send
+pub fn messages(rng: &mut impl Rng) -> Vec<Vec<u8>> {
+    (0..3)
+        .map(|_| {
+            let len = rng.gen_range(1..48);
+            (0..len).map(|_| rng.sample(Alphanumeric)).collect()
+        })
+        .collect()
+}
 
-    let m_decrypted = cbc_decrypt(&b_message.1, m_key, Some(&b_message.0))?;
-    println!(
-        "M intercepted B: {}",
-        std::str::from_utf8(&pkcs7_unpad(&m_decrypted)?).unwrap()
+pub fn main() -> Result<Report> {
+    let mut rng = crate::utils::rng();
+    let (p, g) = nist_params();
+    let alice = Party::new(&p, &g, &mut rng);
+    let bob = Party::new(&p, &g, &mut rng);
+    say!("A: {}, B: {}", alice.public, bob.public);
+
+    // Injected parameters! Each side gets p in place of the other's public key
+    let s_a: BigInt = alice.shared_secret(&p);
+    let s_b: BigInt = bob.shared_secret(&p);
+    say!("s: {s_a}");
+
+    let alice_session = alice.session(&p);
+    let bob_session = bob.session(&p);
+    let mut mallory = Relay::new(&[BigInt::zero()], &BigInt::zero());
+    say!("Shared key:    {}", bytes_to_hex(alice_session.key()));
+    say!(
+        "M deduces key: {}",
+        bytes_to_hex(Session::new(&BigInt::zero()).key())
     );
-    // Send some secret messages and M can intercept
-    assert_eq!(a_plaintext, m_decrypted);
 
-    Ok(())
+    say!("=== BEGINNING COMMUNICATIONS ===");
+    let messages = messages(&mut rng);
+    converse(
+        &alice_session,
+        &bob_session,
+        &mut mallory,
+        &messages,
+        &mut rng,
+    )?;
+    Ok(Report::new()
+        .check("shared secret", s_a, s_b)
+        .check(
+            "key",
+            alice_session.key(),
+            Session::new(&BigInt::zero()).key(),
+        )
+        .count("messages read", mallory.log.len() as u64))
 }
 
 #[cfg(test)]
@@ -137,6 +152,6 @@ mod tests {
 
     #[test]
     fn parameter_injection() {
-        main().unwrap();
+        main().and_then(Report::verify).unwrap();
     }
 }
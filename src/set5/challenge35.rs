@@ -24,122 +24,80 @@
 //! mess with something worse. Most systems pre-agree on a static DH group. But the same
 //! construction exists in Elliptic Curve Diffie-Hellman, and this becomes more relevant there.
 
-use crate::dh::nist_params;
+use crate::dh::{nist_params, Party, Relay};
+use crate::report::say;
+use crate::set5::challenge34::{converse, messages};
 use crate::utils::*;
-use num_bigint::{BigInt, RandBigInt};
-use num_traits::{One, Zero};
-use openssl::hash::{Hasher, MessageDigest};
-use rand::{distributions::Alphanumeric, thread_rng, Rng};
+use num_bigint::BigInt;
 
 // What happens here?
 //
 // A has p, g, A = (g**a) mod p
 //
-// B receives p, g', p
+// B receives p, g', and in place of A, g' itself
+// B has B = (g'**b) mod p, and thinks the shared secret is g'**b mod p = B, which M sees go past
 //
-// g' = 1 => B = (1**b) mod p = 1 => s_A = B**a mod p = 1, s_B = A**b = very different
-// g' = p => B = (p**b) mod p = 0 => s_A = B**a mod p = 0
-// g' = p-1 => B = (p-1)**(b) mod p = (p-1), b odd, 1 b even => s_a = (-1)*(a+b) mod p, s_B = A**b
-// = very different
+// A receives B untouched, and so thinks the shared secret is B**a mod p:
 //
-// In all of these cases M will know the secret key A uses by simply changing the g B see
-// But A and B will *not* have a shared secret
+// g' = 1 => B = 1 => s_A = 1
+// g' = p => B = 0 => s_A = 0
+// g' = p-1 => B = (p-1)**b mod p = p-1 when b is odd, 1 when b is even => s_A = 1 when B = 1, but
+// when B = p-1, s_A = (p-1)**a = p-1 when a is odd, 1 when a is even, which M can only find out by
+// trying both on A's first message
+//
+// In all of these cases M knows the key each side is using, even though they're different, so M
+// can sit in between them, decrypting and re-encrypting every message
 //
 
-pub fn main() -> Result<()> {
+pub fn main() -> Result<Report> {
+    let mut rng = crate::utils::rng();
     let (p, g) = nist_params();
+    let gbs: [(&str, BigInt); 3] = [("1", 1.into()), ("p", p.clone()), ("p - 1", p.clone() - 1)];
 
-    let mut rng = thread_rng();
-    let a: BigInt = rng.gen_bigint_range(&Zero::zero(), &p);
-    let b: BigInt = rng.gen_bigint_range(&Zero::zero(), &p);
-    println!("a: {a}, b: {b}");
-    let gbs: [BigInt; 3] = [1.into(), p.clone(), p.clone() - 1];
-
-    for gb in &gbs {
-        println!("Injected g for B!");
-
-        let pub_a: BigInt = g.modpow(&a, &p);
-        let pub_b: BigInt = gb.modpow(&b, &p);
-        println!("A: {pub_a}, B: {pub_b}");
+    let mut report = Report::new();
+    for (name, gb) in &gbs {
+        say!("Injected g for B: {gb}");
+        let alice = Party::new(&p, &g, &mut rng);
+        let bob = Party::new(&p, gb, &mut rng);
+        say!("A: {}, B: {}", alice.public, bob.public);
 
         // Injected parameters!
-        let s_a: BigInt = pub_b.modpow(&a, &p);
-        let s_b: BigInt = pub_a.modpow(&b, &p);
-        println!("s_a: {s_a}");
-        println!("s_b: {s_b}");
-
-        let s_bytes = s_a.to_bytes_be().1;
-        let mut h = Hasher::new(MessageDigest::sha256())?;
-        h.update(&s_bytes)?;
-
-        let shared_key = &h.finish()?[..16].to_vec();
-
-        let mut h = Hasher::new(MessageDigest::sha256())?;
-        // g' = 1 => B = (1**b) mod p = 1 => s_A = B**a mod p = 1, s_B = A**b = very different
-        // g' = p => B = (p**b) mod p = 0 => s_A = B**a mod p = 0
-        // g' = p-1 => B = (p-1)**(b) mod p = (p-1), b odd, 1 b even => s_a = (-1)*(a+b) mod p, s_B = A**b
-        let one: BigInt = One::one();
-        let zero: BigInt = Zero::zero();
-        let pms: BigInt = p.clone() - 1;
-        let m_s_a: BigInt = match gb {
-            _ if gb == &one => {
-                println!("s_a should be: 1");
-
-                one.clone()
-            }
-            _ if gb == &p => {
-                println!("s_a should be: 0");
-                zero.clone()
-            }
-            _ if gb == &pms => {
-                println!("s_a should be: pm 1");
-                pms
-            }
-            _ => panic!("Not covered"),
+        let s_a: BigInt = alice.shared_secret(&bob.public);
+        let s_b: BigInt = bob.shared_secret(gb);
+        say!("s_a: {s_a}");
+        say!("s_b: {s_b}");
+
+        let m_s_a: Vec<BigInt> = match &bob.public {
+            b if b == &(&p - 1) => vec![1.into(), b.clone()],
+            b => vec![b.clone()],
         };
-
-        h.update(&m_s_a.to_bytes_be().1)?;
-        let m_key = &h.finish()?[..16].to_vec();
-
-        println!("Shared key:    {}", bytes_to_hex(shared_key));
-
-        println!("=== BEGINNING COMMUNICATIONS ===");
-        let a_iv = random_key(16, &mut rng);
-        let a_plaintext: Vec<u8> = thread_rng()
-            .sample_iter(&Alphanumeric)
-            .take(22)
-            .map(u8::from)
-            .collect();
-        println!("A plaintext: {}", std::str::from_utf8(&a_plaintext)?);
-        let a_plaintext = pkcs7_pad(&a_plaintext, 16);
-        let a_ciphertext = cbc_encrypt(&a_plaintext, shared_key, Some(&a_iv))?;
-        let a_message = (a_iv, a_ciphertext);
-
-        let m_decrypted = cbc_decrypt(&a_message.1, m_key, Some(&a_message.0))?;
-
-        // 50/50 chance sign is wrong on last g
-        let m_plaintext = match pkcs7_unpad(&m_decrypted) {
-            Ok(x) => x,
-            Err(_) => {
-                let mut h = Hasher::new(MessageDigest::sha256())?;
-                let one: BigInt = One::one();
-                let m_s_a = one.clone();
-
-                h.update(&m_s_a.to_bytes_be().1)?;
-                let m_key = &h.finish()?[..16].to_vec();
-                let m_decrypted = cbc_decrypt(&a_message.1, m_key, Some(&a_message.0))?;
-                pkcs7_unpad(&m_decrypted)?
-            }
-        };
-        println!(
-            "M intercepted A: {}",
-            std::str::from_utf8(&m_plaintext).unwrap()
+        say!(
+            "s_a should be: {}",
+            m_s_a
+                .iter()
+                .map(|s| s.to_string())
+                .collect::<Vec<_>>()
+                .join(" or ")
         );
-        let a_unpadded = pkcs7_unpad(&a_plaintext)?;
-
-        assert_eq!(a_unpadded, m_plaintext);
+        let mut mallory = Relay::new(&m_s_a, &bob.public);
+
+        say!("=== BEGINNING COMMUNICATIONS ===");
+        let messages = messages(&mut rng);
+        converse(
+            &alice.session(&bob.public),
+            &bob.session(gb),
+            &mut mallory,
+            &messages,
+            &mut rng,
+        )?;
+        report = report
+            .check(&format!("B's secret with g = {name}"), &bob.public, s_b)
+            .count(
+                &format!("messages read with g = {name}"),
+                mallory.log.len() as u64,
+            );
     }
-    Ok(())
+    Ok(report)
 }
 
 #[cfg(test)]
@@ -148,6 +106,6 @@ mod tests {
 
     #[test]
     fn parameter_injection() {
-        main().unwrap();
+        main().and_then(Report::verify).unwrap();
     }
 }
@@ -41,12 +41,11 @@
 //! This is basically Diffie Hellman with a tweak of mixing the password into the public keys. The
 //! server also takes an extra step to avoid storing an easily crackable password-equivalent.
 
-use num_bigint::{BigInt, RandBigInt};
-use num_traits::Zero;
-use openssl::sha::sha256;
-use rand::{distributions::Alphanumeric, thread_rng, Rng};
+use rand::{distributions::Alphanumeric, Rng};
 
-use crate::{dh::nist_params, utils::*};
+use crate::report::say;
+use crate::srp::{Client, Hash, Params, Server};
+use crate::utils::*;
 
 // What does this do? We have our standard DH procedure to establish a shared secret key, while
 // exchanging information publicly. We also already have a shared secret we wish to confirm: the
@@ -81,73 +80,47 @@ use crate::{dh::nist_params, utils::*};
 // Then the server secret would become g**(ab), which the client can calculate from everythign
 // known!
 
-pub fn main() -> Result<()> {
-    let (p, g) = nist_params();
-    let k: BigInt = 3.into();
-
-    let _i = b"username@website.com";
-    let password_bytes: Vec<u8> = thread_rng()
-        .sample_iter(&Alphanumeric)
-        .take(22)
-        .map(u8::from)
-        .collect();
-    //let password = std::str::from_utf8(&password_bytes).unwrap();
-
-    // Server
-    let mut rng = thread_rng();
-    let s_salt = rng.gen::<usize>();
-    let mut saltpass: Vec<u8> = vec![];
-    saltpass.extend_from_slice(&s_salt.to_be_bytes());
-    saltpass.extend_from_slice(&password_bytes);
-
-    let xh = sha256(&saltpass);
-    let x = BigInt::from_bytes_be(num_bigint::Sign::Plus, &xh);
-
-    let v = g.modpow(&x, &p);
-
-    let a: BigInt = rng.gen_bigint_range(&Zero::zero(), &p);
-    let pub_a = g.modpow(&a, &p);
-    println!("Pub a: {pub_a}");
-
-    // Send email, pub_a to server
-
-    // Server
-
-    let b: BigInt = rng.gen_bigint_range(&Zero::zero(), &p);
-    let pub_b: BigInt = (&k * &v + g.modpow(&b, &p)) % &p;
-    let mut pub_apub_b: Vec<u8> = vec![];
-    pub_apub_b.extend_from_slice(&pub_a.to_bytes_be().1);
-    pub_apub_b.extend_from_slice(&pub_b.to_bytes_be().1);
-
-    let uh = sha256(&pub_apub_b);
-    let u = BigInt::from_bytes_be(num_bigint::Sign::Plus, &uh);
-
-    // Client
-    // Client has s_salt from server, so can also compute x in the same way
-    /*
-    let one: BigInt = One::one();
-    let derived_b = (&pub_b - &k * g.modpow(&x, &p)).modpow(&one, &p);
-    println!("Actual B: {}", g.modpow(&b, &p));
-    println!("Derived B: {derived_b}");
-    */
-    let exp = &a + &u * &x;
-    let s = (&pub_b - &k * g.modpow(&x, &p)).modpow(&exp, &p);
-    println!("Client s: {s}");
-    let client_k = sha256(&s.to_bytes_be().1);
-    let client_hmac = hmac_sha256::HMAC::mac(client_k, s_salt.to_be_bytes());
-
-    println!("Client hmac: {}", bytes_to_hex(&client_hmac));
-    // Server
-    let server_s = (pub_a * v.modpow(&u, &p)).modpow(&b, &p);
-    println!("Server s: {server_s}");
-    let server_k = sha256(&server_s.to_bytes_be().1);
-
-    let server_hmac = hmac_sha256::HMAC::mac(server_k, s_salt.to_be_bytes());
-    println!("Server hmac: {}", bytes_to_hex(&server_hmac));
-
-    assert_eq!(server_hmac, client_hmac);
-
-    Ok(())
+// Beyond the challenge's description, this follows RFC 5054, so that it agrees with any other SRP
+// implementation: k isn't just 3 but H(N | PAD(g)), A and B are padded to the length of N before
+// they go into u, and the user's name goes into x along with the password
+
+pub fn main() -> Result<Report> {
+    let params = Params::from_options(2048, Hash::Sha256)?;
+    say!("Using the {params}");
+
+    let mut rng = crate::utils::rng();
+    let email = b"username@website.com";
+    let password: Vec<u8> = (0..22).map(|_| rng.sample(Alphanumeric)).collect();
+
+    // Server saves the salt and v = g**x % N when the user registers
+    let server = Server::new(params.clone(), email, &password, &mut rng);
+
+    // C->S: I, A
+    let client = Client::new(params, email, &password, &mut rng);
+    let pub_a = client.public();
+    say!("A: {pub_a}");
+
+    // S->C: salt, B
+    let pub_b = server.public();
+    say!("Salt: {}, B: {pub_b}", bytes_to_hex(&server.salt));
+
+    let client_s = client.secret(&server.salt, &pub_b);
+    let server_s = server.secret(&pub_a);
+    say!("Client s: {client_s}");
+    say!("Server s: {server_s}");
+
+    // C->S: HMAC-SHA256(K, salt)
+    let proof = client.proof(&server.salt, &pub_b);
+    say!("Client hmac: {}", bytes_to_hex(&proof));
+
+    // S->C: "OK"
+    match server.verify(&pub_a, &proof) {
+        true => {
+            say!("OK");
+            Ok(Report::new().unverified("s", client_s))
+        }
+        false => Err(anyhow::anyhow!("Server rejected the client's HMAC")),
+    }
 }
 
 #[cfg(test)]
@@ -156,6 +129,6 @@ mod tests {
 
     #[test]
     fn check_srp() {
-        main().unwrap();
+        main().and_then(Report::verify).unwrap();
     }
 }
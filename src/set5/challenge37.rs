@@ -13,193 +13,230 @@
 //! bypass. Almost every implementation of SRP we've ever seen has this flaw; if you see a new one,
 //! go look for this bug.
 
-use crate::utils::*;
-use num_bigint::{BigInt, RandBigInt};
+use std::collections::HashMap;
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use anyhow::anyhow;
+use num_bigint::BigInt;
 use num_traits::Zero;
-use openssl::sha::sha256;
-use rand::{distributions::Alphanumeric, thread_rng, Rng};
-
-use crate::dh::nist_params;
-
-struct SrpServer {
-    p: BigInt,
-    g: BigInt,
-    k: BigInt,
-    v: BigInt,
-    b: BigInt,
-    salt: usize,
-}
+use rand::rngs::StdRng;
+use rand::{distributions::Alphanumeric, Rng, SeedableRng};
+
+use crate::report::say;
+use crate::srp::{Client, Hash, Params, Server};
+use crate::utils::*;
 
-impl SrpServer {
-    fn new(password_bytes: &[u8]) -> Self {
-        let (p, g) = nist_params();
-        let k: BigInt = 3.into();
-        let mut rng = thread_rng();
-        let salt = rng.gen::<usize>();
-        let mut saltpass: Vec<u8> = vec![];
-        saltpass.extend_from_slice(&salt.to_be_bytes());
-        saltpass.extend_from_slice(password_bytes);
+// Why does this work?
+//
+// The server works out S = (A * v**u) ** b % N, so if A is 0 mod N, then so is S, whatever v (and
+// so the password) is. The client then knows K = H(0) without needing x at all. A = 0 on its own
+// would be easy enough to spot, but N, 2N, ... all do the same, and a server which doesn't reduce
+// A mod N and check it (as RFC 5054 says it MUST) falls for all of them
 
-        let xh = sha256(&saltpass);
-        let x = BigInt::from_bytes_be(num_bigint::Sign::Plus, &xh);
+/// Salt and verifier for each registered email
+type Users = HashMap<Vec<u8>, (Vec<u8>, BigInt)>;
+
+/// What the client sends
+enum Request {
+    Hello { email: Vec<u8>, pub_a: BigInt },
+    Login { proof: Vec<u8> },
+}
+
+/// What the server sends back
+#[derive(Debug, PartialEq, Eq)]
+enum Response {
+    Challenge { salt: Vec<u8>, pub_b: BigInt },
+    Ok,
+    Denied,
+}
 
-        let v = g.modpow(&x, &p);
-        let b: BigInt = rng.gen_bigint_range(&Zero::zero(), &p);
+/// An SRP login service, with a thread for each connection
+pub struct LoginServer {
+    params: Params,
+    users: Arc<Users>,
+    /// The S the server worked out for each login attempt, in order
+    secrets: Arc<Mutex<Vec<BigInt>>>,
+}
 
+impl LoginServer {
+    pub fn new(params: Params, users: &[(&[u8], &[u8])], rng: &mut impl Rng) -> Self {
+        let users = users
+            .iter()
+            .map(|(email, password)| {
+                let server = Server::new(params.clone(), email, password, rng);
+                (
+                    email.to_vec(),
+                    (server.salt.clone(), server.verifier().clone()),
+                )
+            })
+            .collect();
         Self {
-            p,
-            g,
-            k,
-            v,
-            b,
-            salt,
+            params,
+            users: Arc::new(users),
+            secrets: Arc::new(Mutex::new(vec![])),
         }
     }
 
-    fn pub_b(&self) -> BigInt {
-        let pub_b: BigInt = (&self.k * &self.v + self.g.modpow(&self.b, &self.p)) % &self.p;
-        pub_b
+    /// Starts a login attempt, with the server's side on a thread of its own and a generator of
+    /// its own seeded from `rng`
+    pub fn connect(&self, rng: &mut impl Rng) -> Connection {
+        let (to_server, requests) = channel();
+        let (responses, from_server) = channel();
+        let params = self.params.clone();
+        let users = self.users.clone();
+        let secrets = self.secrets.clone();
+        let mut rng = StdRng::seed_from_u64(rng.gen());
+        thread::spawn(move || serve(params, &users, &secrets, requests, responses, &mut rng));
+        Connection {
+            to_server,
+            from_server,
+        }
     }
 
-    fn u(&self, pub_a: &BigInt) -> BigInt {
-        let pub_b = self.pub_b();
-        let mut pub_apub_b: Vec<u8> = vec![];
-        pub_apub_b.extend_from_slice(&pub_a.to_bytes_be().1);
-        pub_apub_b.extend_from_slice(&pub_b.to_bytes_be().1);
-
-        let uh = sha256(&pub_apub_b);
-        BigInt::from_bytes_be(num_bigint::Sign::Plus, &uh)
+    /// Every S the server has worked out so far
+    pub fn secrets(&self) -> Vec<BigInt> {
+        self.secrets.lock().unwrap().clone()
     }
+}
 
-    fn server_hmac(&self, pub_a: &BigInt) -> Vec<u8> {
-        let u = self.u(pub_a);
-        let server_s = (pub_a * self.v.modpow(&u, &self.p)).modpow(&self.b, &self.p);
-        println!("Server s: {server_s}");
-        let server_k = sha256(&server_s.to_bytes_be().1);
-        let server_hmac = hmac_sha256::HMAC::mac(server_k, self.salt.to_be_bytes());
-        server_hmac.to_vec()
+/// One login attempt, from the server's side
+fn serve(
+    params: Params,
+    users: &Users,
+    secrets: &Mutex<Vec<BigInt>>,
+    requests: Receiver<Request>,
+    responses: Sender<Response>,
+    rng: &mut impl Rng,
+) -> Option<()> {
+    let Ok(Request::Hello { email, pub_a }) = requests.recv() else {
+        return responses.send(Response::Denied).ok();
+    };
+    let Some((salt, v)) = users.get(&email) else {
+        return responses.send(Response::Denied).ok();
+    };
+    let server = Server::from_verifier(params, salt.clone(), v.clone(), rng);
+    responses
+        .send(Response::Challenge {
+            salt: salt.clone(),
+            pub_b: server.public(),
+        })
+        .ok()?;
+
+    let Ok(Request::Login { proof }) = requests.recv() else {
+        return responses.send(Response::Denied).ok();
+    };
+    // No check on A at all
+    secrets.lock().unwrap().push(server.secret(&pub_a));
+    match server.verify(&pub_a, &proof) {
+        true => responses.send(Response::Ok).ok(),
+        false => responses.send(Response::Denied).ok(),
     }
 }
 
-struct SrpClient {
-    p: BigInt,
-    g: BigInt,
-    k: BigInt,
-    a: BigInt,
-    x: BigInt,
-    salt: usize,
+/// The client's end of a connection
+pub struct Connection {
+    to_server: Sender<Request>,
+    from_server: Receiver<Response>,
 }
 
-impl SrpClient {
-    fn new(password_bytes: &[u8], salt: usize) -> Self {
-        let mut rng = thread_rng();
-        let (p, g) = nist_params();
-        let k: BigInt = 3.into();
-
-        let a: BigInt = rng.gen_bigint_range(&Zero::zero(), &p);
-
-        let mut saltpass: Vec<u8> = vec![];
-        saltpass.extend_from_slice(&salt.to_be_bytes());
-        saltpass.extend_from_slice(password_bytes);
-
-        let xh = sha256(&saltpass);
-        let x = BigInt::from_bytes_be(num_bigint::Sign::Plus, &xh);
-
-        Self {
-            p,
-            g,
-            k,
-            a,
-            x,
-            salt,
-        }
+impl Connection {
+    fn request(&self, request: Request) -> Result<Response> {
+        self.to_server
+            .send(request)
+            .map_err(|_| anyhow!("Server hung up"))?;
+        self.from_server
+            .recv()
+            .map_err(|_| anyhow!("Server hung up"))
     }
 
-    fn pub_a(&self) -> BigInt {
-        self.g.modpow(&self.a, &self.p)
-    }
-
-    fn u(&self, pub_b: &BigInt) -> BigInt {
-        let pub_a = self.pub_a();
-        let mut pub_apub_b: Vec<u8> = vec![];
-        pub_apub_b.extend_from_slice(&pub_a.to_bytes_be().1);
-        pub_apub_b.extend_from_slice(&pub_b.to_bytes_be().1);
-
-        let uh = sha256(&pub_apub_b);
-        BigInt::from_bytes_be(num_bigint::Sign::Plus, &uh)
+    /// Sends I and A, getting back the salt and B
+    pub fn hello(&self, email: &[u8], pub_a: &BigInt) -> Result<(Vec<u8>, BigInt)> {
+        match self.request(Request::Hello {
+            email: email.to_vec(),
+            pub_a: pub_a.clone(),
+        })? {
+            Response::Challenge { salt, pub_b } => Ok((salt, pub_b)),
+            r => Err(anyhow!("Expected a salt and B, got {r:?}")),
+        }
     }
 
-    fn client_hmac(&self, pub_b: &BigInt) -> Vec<u8> {
-        let u = self.u(pub_b);
-        let exp = &self.a + &u * &self.x;
-        let s = (pub_b - &self.k * self.g.modpow(&self.x, &self.p)).modpow(&exp, &self.p);
-        println!("Client s: {s}");
-        let client_k = sha256(&s.to_bytes_be().1);
-        let client_hmac = hmac_sha256::HMAC::mac(client_k, self.salt.to_be_bytes());
-        client_hmac.to_vec()
+    /// Sends the HMAC, and finds out if it was accepted
+    pub fn login(&self, proof: &[u8]) -> Result<bool> {
+        match self.request(Request::Login {
+            proof: proof.to_vec(),
+        })? {
+            Response::Ok => Ok(true),
+            Response::Denied => Ok(false),
+            r => Err(anyhow!("Expected OK or not, got {r:?}")),
+        }
     }
 }
 
-pub fn main() -> Result<()> {
-    println!("=== REGULAR FLOW === ");
-    {
-        let _i = b"username@website.com";
-        let password_bytes: Vec<u8> = thread_rng()
-            .sample_iter(&Alphanumeric)
-            .take(22)
-            .map(u8::from)
-            .collect();
-
-        // Initialise server
-        let server = SrpServer::new(&password_bytes);
-        let salt = server.salt;
-        // Initialise client
-        let client = SrpClient::new(&password_bytes, salt);
-
-        let server_hmac = server.server_hmac(&client.pub_a());
-        let pub_b = server.pub_b();
+/// Logs in properly, with the password
+pub fn login(
+    server: &LoginServer,
+    email: &[u8],
+    password: &[u8],
+    rng: &mut impl Rng,
+) -> Result<bool> {
+    let client = Client::new(server.params.clone(), email, password, rng);
+    let connection = server.connect(rng);
+    let (salt, pub_b) = connection.hello(email, &client.public())?;
+    connection.login(&client.proof(&salt, &pub_b))
+}
 
-        let client_hmac = client.client_hmac(&pub_b);
-        println!("Client hmac: {}", bytes_to_hex(&client_hmac));
-        println!("Server hmac: {}", bytes_to_hex(&server_hmac));
+/// Logs in without the password, sending A = multiple * N, and so knowing S = 0
+pub fn bypass(
+    server: &LoginServer,
+    email: &[u8],
+    multiple: u32,
+    rng: &mut impl Rng,
+) -> Result<bool> {
+    let pub_a = &server.params.n * multiple;
+    let connection = server.connect(rng);
+    let (salt, _) = connection.hello(email, &pub_a)?;
+    connection.login(&server.params.proof(&BigInt::zero(), &salt))
+}
 
-        assert_eq!(client_hmac, server_hmac);
+pub fn main() -> Result<Report> {
+    let params = Params::from_options(2048, Hash::Sha256)?;
+    say!("Using the {params}");
+    let mut rng = crate::utils::rng();
+    let email = b"username@website.com";
+    let password: Vec<u8> = (0..22).map(|_| rng.sample(Alphanumeric)).collect();
+    let server = LoginServer::new(params, &[(email, &password)], &mut rng);
+
+    say!("=== REGULAR FLOW ===");
+    let ok = login(&server, email, &password, &mut rng)?;
+    say!("With the password: {}", if ok { "OK" } else { "denied" });
+    let wrong = login(&server, email, b"password1", &mut rng)?;
+    say!(
+        "With the wrong one: {}",
+        if wrong { "OK" } else { "denied" }
+    );
+    if !ok || wrong {
+        return Err(anyhow!("Server doesn't check passwords properly"));
     }
 
-    println!("=== ZERO PASSWORD FLOW === ");
-    {
-        let _i = b"username@website.com";
-        let password_bytes: Vec<u8> = thread_rng()
-            .sample_iter(&Alphanumeric)
-            .take(22)
-            .map(u8::from)
-            .collect();
-
-        // Initialise server
-        let server = SrpServer::new(&password_bytes);
-        let salt = server.salt;
-        // Don't even need a client
-        // Set the password wrong
-        let zero: BigInt = Zero::zero();
-
-        let server_hmac = server.server_hmac(&zero);
-        //let pub_b = server.pub_b();
-        // This injection means that server_s = 0
-        // And so server k = sha256(0)
-        let deduced_server_k = sha256(&[0]);
-        // And so server_hmac is trivially calculable
-        let deduced_server_hmac =
-            hmac_sha256::HMAC::mac(deduced_server_k, salt.to_be_bytes()).to_vec();
-        println!(
-            "Deduced server hmac: {}",
-            bytes_to_hex(&deduced_server_hmac)
+    say!("=== MALICIOUS A FLOW ===");
+    let mut report = Report::new();
+    let mut bypassed = 0;
+    for multiple in 0..=4 {
+        let ok = bypass(&server, email, multiple, &mut rng)?;
+        let s = server.secrets().pop().unwrap();
+        say!(
+            "A = {multiple}N: {}, server's S = {s}",
+            if ok { "OK" } else { "denied" }
         );
-        println!("Server hmac:         {}", bytes_to_hex(&server_hmac));
-
-        assert_eq!(deduced_server_hmac, server_hmac);
+        report = report
+            .forged(&format!("login with A = {multiple}N"), ok)
+            .check(&format!("S with A = {multiple}N"), BigInt::zero(), s);
+        bypassed += u64::from(ok);
     }
 
-    Ok(())
+    Ok(report.count("logins without the password", bypassed))
 }
 
 #[cfg(test)]
@@ -208,6 +245,21 @@ mod tests {
 
     #[test]
     fn test_zero_pub_key() {
-        main().unwrap();
+        main().and_then(Report::verify).unwrap();
+    }
+
+    #[test]
+    fn server() {
+        let mut rng = rand::thread_rng();
+        let params = Params::new(1024, Hash::Sha1).unwrap();
+        let users: [(&[u8], &[u8]); 2] = [(b"alice", b"hunter2"), (b"bob", b"letmein")];
+        let server = LoginServer::new(params, &users, &mut rng);
+        assert!(login(&server, b"alice", b"hunter2", &mut rng).unwrap());
+        assert!(login(&server, b"bob", b"letmein", &mut rng).unwrap());
+        assert!(!login(&server, b"bob", b"hunter2", &mut rng).unwrap());
+        assert!(login(&server, b"carol", b"hunter2", &mut rng).is_err());
+        assert!(bypass(&server, b"bob", 7, &mut rng).unwrap());
+        assert_eq!(server.secrets().len(), 4);
+        assert!(server.secrets()[3].is_zero());
     }
 }
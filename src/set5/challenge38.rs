@@ -26,29 +26,26 @@
 //!
 //! Crack the password from A's HMAC-SHA256(K, salt).
 
+use crate::report::say;
 use crate::{dh::nist_params, utils::*};
 use num_bigint::{BigInt, RandBigInt, Sign};
 use num_traits::Zero;
 use openssl::sha::sha256;
-use rand::{distributions::Alphanumeric, thread_rng, Rng};
+use rand::{distributions::Alphanumeric, Rng};
 
-pub fn main() -> Result<()> {
-    println!("=== BEGIN REGULAR ===");
+pub fn main() -> Result<Report> {
+    say!("=== BEGIN REGULAR ===");
 
     let (p, g) = nist_params();
 
-    let mut rng = thread_rng();
+    let mut rng = crate::utils::rng();
     let salt = rng.gen::<usize>();
-    let password_bytes: Vec<u8> = thread_rng()
-        .sample_iter(&Alphanumeric)
-        .take(22)
-        .map(u8::from)
-        .collect();
+    let password_bytes: Vec<u8> = (0..22).map(|_| rng.sample(Alphanumeric)).collect();
     let mut saltpass: Vec<u8> = vec![];
     saltpass.extend_from_slice(&salt.to_be_bytes());
     saltpass.extend_from_slice(&password_bytes);
     let xh = sha256(&saltpass);
-    {
+    let report = {
         // Server
         let x = BigInt::from_bytes_be(Sign::Plus, &xh);
         let v = g.modpow(&x, &p);
@@ -65,26 +62,26 @@ pub fn main() -> Result<()> {
         // Client, also calculates x
         let exp = &a + &u * &x;
         let s_client = pub_b.modpow(&exp, &p);
-        println!("S(client): {s_client}");
+        say!("S(client): {s_client}");
         let k_client = sha256(&s_client.to_bytes_be().1);
-        println!("K(client): {}", bytes_to_hex(&k_client));
+        say!("K(client): {}", bytes_to_hex(&k_client));
 
         let vu = v.modpow(&u, &p);
         let s_server = (&pub_a * &vu).modpow(&b, &p);
-        println!("S(client): {s_server}");
+        say!("S(client): {s_server}");
         let k_server = sha256(&s_server.to_bytes_be().1);
-        println!("K(server): {}", bytes_to_hex(&k_server));
+        say!("K(server): {}", bytes_to_hex(&k_server));
 
         let salt_bytes = salt.to_be_bytes();
         let hmac_client = hmac_sha256::HMAC::mac(salt_bytes, k_client);
-        println!("Client hmac: {}", bytes_to_hex(&hmac_client));
+        say!("Client hmac: {}", bytes_to_hex(&hmac_client));
         let hmac_server = hmac_sha256::HMAC::mac(salt_bytes, k_server);
-        println!("server hmac: {}", bytes_to_hex(&hmac_server));
+        say!("server hmac: {}", bytes_to_hex(&hmac_server));
 
-        assert_eq!(hmac_client, hmac_server);
-    }
+        Report::new().check("hmac", hmac_server, hmac_client)
+    };
 
-    println!("=== MITM ===");
+    say!("=== MITM ===");
     // We get to pick arbitrary values for b, B, u, and salt
     // What does the client calculate using these?
     // s_client = B**(a+ux)
@@ -107,16 +104,13 @@ pub fn main() -> Result<()> {
     // stored
 
     // Read all sowpods into an array
-    let passwords: Vec<String> = std::fs::read_to_string("./data/sowpods.txt")
-        .unwrap()
-        .split('\n')
+    let passwords: Vec<String> = crate::data::SOWPODS
+        .lines()?
         .map(String::from)
         .take(1000)
         .collect::<Vec<String>>();
     let random_pw_index = rng.gen::<usize>() % passwords.len();
-    println!("Chosen password is: {}", passwords[random_pw_index]);
-
-    let mut rng = thread_rng();
+    say!("Chosen password is: {}", passwords[random_pw_index]);
 
     let a: BigInt = rng.gen_bigint_range(&Zero::zero(), &p);
     let pub_a: BigInt = g.modpow(&a, &p);
@@ -124,19 +118,23 @@ pub fn main() -> Result<()> {
     let b: BigInt = 1.into();
 
     let client_hmac = hmac_from_pw_client(&passwords[random_pw_index], &u, &a, &b, &g, &p);
-    println!("Client hmac: {}", bytes_to_hex(&client_hmac));
+    say!("Client hmac: {}", bytes_to_hex(&client_hmac));
 
     let cracked_pw = passwords
         .iter()
         .find(|pw| hmac_from_pw_server(pw, &pub_a, &g, &p) == client_hmac)
         .unwrap();
-    println!("Cracked pw: {cracked_pw}");
+    say!("Cracked pw: {cracked_pw}");
 
     let server_hmac = hmac_from_pw_server(&passwords[random_pw_index], &pub_a, &g, &p);
 
-    println!("Server hmac: {}", bytes_to_hex(&server_hmac));
+    say!("Server hmac: {}", bytes_to_hex(&server_hmac));
 
-    Ok(())
+    Ok(report.check(
+        "password",
+        passwords[random_pw_index].as_str(),
+        cracked_pw.as_str(),
+    ))
 }
 fn hmac_from_pw_server(password: &str, pub_a: &BigInt, g: &BigInt, p: &BigInt) -> Vec<u8> {
     let password_bytes = password.as_bytes();
@@ -169,9 +167,9 @@ fn hmac_from_pw_client(
     let exp = a + u * &x;
     let pub_b = g.modpow(b, p);
     let s_client = pub_b.modpow(&exp, p);
-    println!("S(client): {s_client}");
+    say!("S(client): {s_client}");
     let k_client = sha256(&s_client.to_bytes_be().1);
-    println!("K(client): {}", bytes_to_hex(&k_client));
+    say!("K(client): {}", bytes_to_hex(&k_client));
 
     let hmac_client = hmac_sha256::HMAC::mac([1], k_client);
     hmac_client.to_vec()
@@ -182,6 +180,6 @@ mod tests {
     use super::*;
     #[test]
     fn srp_dict() {
-        main().unwrap();
+        main().and_then(Report::verify).unwrap();
     }
 }
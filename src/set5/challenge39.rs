@@ -30,98 +30,60 @@
 //! on the front of it to turn it into a number. The math cares not how stupidly you feed it
 //! strings.
 
+use crate::report::say;
+use crate::rsa::{Key, KeyPair, Params};
 use crate::utils::*;
 use num_bigint::{BigInt, ToBigInt};
 use num_integer::Integer;
 use num_traits::One;
-use num_traits::Zero;
-use openssl::bn::BigNum;
 
-fn prime(bits: i32) -> BigInt {
-    let mut big = BigNum::new().unwrap();
-    big.generate_prime(bits, false, None, None).unwrap();
-    let ps = big.to_dec_str().unwrap();
-    let p: BigInt = ps.parse().unwrap();
-    p
-}
-
-pub fn invmod<T: ToBigInt>(a: &T, m: &T) -> BigInt {
-    let m_orig = m.to_bigint().unwrap();
-    let a = a.to_bigint().unwrap();
-
-    if m_orig.is_one() {
-        return One::one();
+/// Inverse of a mod m, or None when a and m aren't coprime and there isn't one
+pub fn try_invmod<T: ToBigInt>(a: &T, m: &T) -> Option<BigInt> {
+    let (a, m) = (a.to_bigint()?, m.to_bigint()?);
+    if m <= One::one() {
+        return None;
     }
-
-    let (mut a, mut m, mut x, mut inv) = (a, m_orig.clone(), BigInt::zero(), BigInt::one());
-
-    while a > One::one() {
-        let (div, rem) = a.div_rem(&m);
-        inv -= div * &x;
-        a = rem;
-        std::mem::swap(&mut a, &mut m);
-        std::mem::swap(&mut x, &mut inv);
-    }
-
-    while inv < Zero::zero() {
-        inv += &m_orig;
-    }
-
-    inv
+    let egcd = a.mod_floor(&m).extended_gcd(&m);
+    egcd.gcd.is_one().then(|| egcd.x.mod_floor(&m))
 }
 
-pub fn et_n(bits: i32, e: &BigInt) -> (BigInt, BigInt) {
-    let mut et: BigInt = 0.into();
-    let mut n = 0.into();
-    while &et % e == Zero::zero() {
-        let (p, q) = (prime(bits), prime(bits));
-        et = (&p - 1) * (&q - 1);
-        n = &p * &q;
-    }
-    (et, n)
-}
-
-pub fn main() -> Result<()> {
-    let bits = 256;
-    let e: BigInt = 3.into();
-
-    let (et, n) = et_n(bits, &e);
+pub fn main() -> Result<Report> {
+    let params = Params::from_options(512, 3)?;
+    say!("Using a {params}");
 
-    let d = invmod(&e, &et);
-
-    println!("d: {d}, e: {e}, et: {et}");
-    println!("e*d % et = {}", (&e * &d) % &et);
-    println!("invmod(17. 3120): {}", invmod(&17, &3120));
-
-    let public_key = (e, n.clone());
-    let private_key = (d, n);
+    let KeyPair {
+        public: public_key,
+        private: private_key,
+    } = params.keypair(&mut crate::utils::rng());
+    say!("d: {}, e: {}", private_key.key, public_key.key);
+    say!("invmod(17, 3120): {:?}", try_invmod(&17, &3120));
+    // Which is why keypair() goes back for new primes when e isn't coprime to et
+    say!("invmod(3, 3120): {:?}", try_invmod(&3, &3120));
 
     // NB secret as an integer must be less than n!
     let secret = b"super secret message";
-    println!("Secret: {}", bytes_to_hex(secret));
+    say!("Secret: {}", bytes_to_hex(secret));
 
     let encrypted = rsa_encrypt(&public_key, secret);
-    println!("Encrypted: {}", bytes_to_hex(&encrypted));
+    say!("Encrypted: {}", bytes_to_hex(&encrypted));
 
     let decrypted = rsa_decrypt(&private_key, &encrypted);
-    println!("Decrypted: {}", bytes_to_hex(&decrypted));
+    say!("Decrypted: {}", bytes_to_hex(&decrypted));
 
-    assert_eq!(secret.to_vec(), decrypted);
-
-    Ok(())
+    Ok(Report::new().check("decrypted", secret, decrypted))
 }
 
-pub fn rsa_encrypt(public_key: &(BigInt, BigInt), data: &[u8]) -> Vec<u8> {
+pub fn rsa_encrypt(public_key: &Key, data: &[u8]) -> Vec<u8> {
     let data = BigInt::from_bytes_be(num_bigint::Sign::Plus, data);
 
-    let encrypted = data.modpow(&public_key.0, &public_key.1);
+    let encrypted = public_key.apply(&data);
     encrypted.to_bytes_be().1.to_vec()
 }
 
-pub fn rsa_decrypt(private_key: &(BigInt, BigInt), data: &[u8]) -> Vec<u8> {
+pub fn rsa_decrypt(private_key: &Key, data: &[u8]) -> Vec<u8> {
     let data = BigInt::from_bytes_be(num_bigint::Sign::Plus, data);
 
-    let decrypted = data.modpow(&private_key.0, &private_key.1);
+    let decrypted = private_key.apply(&data);
     decrypted.to_bytes_be().1.to_vec()
 }
 
@@ -130,18 +92,21 @@ mod tests {
 
     use super::*;
     #[test]
-    fn invmod_test() {
-        let target: BigInt = 1969.into();
-        let im = invmod(&42, &2017);
-        assert_eq!(im, target);
-
-        let target: BigInt = 2753.into();
-        let im = invmod(&17, &3120);
-        assert_eq!(im, target);
+    fn try_invmod_test() {
+        assert_eq!(try_invmod(&42, &2017), Some(1969.into()));
+        assert_eq!(try_invmod(&17, &3120), Some(2753.into()));
+        assert_eq!(try_invmod(&-17, &3120), Some((3120 - 2753).into()));
+        assert_eq!(try_invmod(&3, &3120), None);
+        assert_eq!(try_invmod(&0, &7), None);
+        assert_eq!(try_invmod(&5, &1), None);
+        for a in 1..101 {
+            let inverse = try_invmod(&a, &101).unwrap();
+            assert_eq!((a * inverse) % 101, BigInt::one());
+        }
     }
 
     #[test]
     fn rsa() {
-        main().unwrap();
+        main().and_then(Report::verify).unwrap();
     }
 }
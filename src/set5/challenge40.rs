@@ -31,66 +31,245 @@
 //! To decrypt RSA using a simple cube root, leave off the final modulus operation; just take the
 //! raw accumulated result and cube-root it.
 
+use crate::lattice::small_roots;
+use crate::poly;
+use crate::report::say;
+use crate::rsa::{Key, Params};
+use crate::set5::challenge39::try_invmod;
 use crate::utils::*;
+use anyhow::anyhow;
 use num_bigint::BigInt;
+use num_traits::{One, Signed};
 
-pub fn main() -> Result<()> {
-    let e: BigInt = 3.into();
+/// Largest e worth trying, as the attack needs e different keys
+const MAX_E: u32 = 17;
 
-    let (et1, n1) = et_n(256, &e);
-    let d1 = invmod(&e, &et1);
-    let public_key1 = (e.clone(), n1.clone());
-    let _private_key1 = (d1, n1.clone());
+/// The exact nth root of x, or None if x isn't a perfect nth power
+pub fn iroot(x: &BigInt, n: u32) -> Option<BigInt> {
+    let root = x.nth_root(n);
+    (root.pow(n) == *x).then_some(root)
+}
+
+/// Recovers m from its encryptions under e different public keys, all with the same small e
+///
+/// CRT gives m**e mod n_0 * n_1 * ... * n_(e-1), and as m is less than each n, m**e is less than
+/// their product, so that's just m**e, and the e-th root is m
+pub fn broadcast_attack(intercepted: &[(BigInt, Key)]) -> Result<BigInt> {
+    let e = match intercepted {
+        [(_, key), ..] => key.key.clone(),
+        [] => return Err(anyhow!("No ciphertexts")),
+    };
+    let e_u32 = u32::try_from(&e)?;
+    if intercepted.iter().any(|(_, key)| key.key != e) {
+        return Err(anyhow!("Ciphertexts aren't all under the same e"));
+    }
+    if intercepted.len() < e_u32 as usize {
+        return Err(anyhow!(
+            "Need {e} ciphertexts, only have {}",
+            intercepted.len()
+        ));
+    }
+
+    let residues: Vec<(BigInt, BigInt)> = intercepted
+        .iter()
+        .take(e_u32 as usize)
+        .map(|(c, key)| (key.modulus.clone(), c.clone()))
+        .collect();
+    let (m_e, _) = crt(&residues).ok_or_else(|| anyhow!("Moduli share a factor"))?;
+    iroot(&m_e, e_u32).ok_or_else(|| anyhow!("CRT result isn't an exact {e}th power"))
+}
+
+/// How a message was padded for one recipient before being encrypted: as a m + b
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Padding {
+    pub a: BigInt,
+    pub b: BigInt,
+}
 
-    let (et2, n2) = et_n(256, &e);
-    let d2 = invmod(&e, &et2);
-    let public_key2 = (e.clone(), n2.clone());
-    let _private_key2 = (d2, n2.clone());
+impl Padding {
+    pub fn apply(&self, m: &BigInt) -> BigInt {
+        &self.a * m + &self.b
+    }
+}
+
+/// Håstad's broadcast attack: recovers m from its encryptions under different public keys, even
+/// with it padded differently for each, as long as m < `bound`
+///
+/// Each ciphertext makes m a root of f_i(x) = (a_i x + b_i)^e_i - c_i mod n_i. CRT stitches these
+/// together (each times x^(δ - e_i), with δ the biggest e, so they're all of degree δ) into one
+/// polynomial mod N = n_0 * n_1 * ..., which made monic is a job for Coppersmith's method. That
+/// finds roots up to nearly N^(1/δ), so the more recipients, the more room there is for m.
+pub fn hastad_attack(intercepted: &[(BigInt, Key, Padding)], bound: &BigInt) -> Result<BigInt> {
+    let exponents = intercepted
+        .iter()
+        .map(|(_, key, _)| usize::try_from(&key.key))
+        .collect::<Result<Vec<usize>, _>>()?;
+    let delta = *exponents
+        .iter()
+        .max()
+        .ok_or_else(|| anyhow!("No ciphertexts"))?;
+
+    let product: BigInt = intercepted.iter().map(|(_, key, _)| &key.modulus).product();
+    let mut f = vec![];
+    for ((c, key, padding), e) in intercepted.iter().zip(exponents) {
+        let n = &key.modulus;
+        // 1 mod n_i, and 0 mod the rest
+        let ms = &product / n;
+        let t = try_invmod(&ms, n).ok_or_else(|| anyhow!("Moduli share a factor"))? * ms;
+        let padded = poly::pow(&[padding.b.clone(), padding.a.clone()], e);
+        let f_i = poly::shift(&poly::add(&padded, &[-c]), delta - e);
+        f = poly::add(&f, &poly::scale(&f_i, &t));
+    }
+    let f = poly::reduce(&f, &product);
+    let lead = f
+        .get(delta)
+        .and_then(|lead| try_invmod(lead, &product))
+        .ok_or_else(|| anyhow!("Can't make the combined polynomial monic"))?;
+    let monic = poly::reduce(&poly::scale(&f, &lead), &product);
+
+    small_roots(&monic, &product, bound)?
+        .into_iter()
+        .find(|m| !m.is_negative())
+        .ok_or_else(|| anyhow!("No message below {bound}"))
+}
 
-    let (et3, n3) = et_n(256, &e);
-    let d3 = invmod(&e, &et3);
-    let public_key3 = (e, n3.clone());
-    let _private_key3 = (d3, n3.clone());
+pub fn main() -> Result<Report> {
+    let params = Params::from_options(512, 3)?;
+    // One key for each power of m
+    let e = u32::try_from(&params.e)
+        .ok()
+        .filter(|e| *e <= MAX_E)
+        .ok_or_else(|| anyhow!("The attack needs e keys, so e = {} is too big", params.e))?;
+    say!("Using a {params}");
+    let mut rng = crate::utils::rng();
 
     let secret = b"super secret";
     let secret_num = BigInt::from_bytes_be(num_bigint::Sign::Plus, secret);
 
-    let c1 = BigInt::from_bytes_be(num_bigint::Sign::Plus, &rsa_encrypt(&public_key1, secret));
-    let c2 = BigInt::from_bytes_be(num_bigint::Sign::Plus, &rsa_encrypt(&public_key2, secret));
-    let c3 = BigInt::from_bytes_be(num_bigint::Sign::Plus, &rsa_encrypt(&public_key3, secret));
-
-    let ms_1 = &n2 * &n3;
-    let ms_2 = &n1 * &n3;
-    let ms_3 = &n1 * &n2;
+    // The same message, encrypted under e different keys
+    let intercepted: Vec<(BigInt, Key)> = (0..e)
+        .map(|_| {
+            let public_key = params.keypair(&mut rng).public;
+            let c =
+                BigInt::from_bytes_be(num_bigint::Sign::Plus, &rsa_encrypt(&public_key, secret));
+            (c, public_key)
+        })
+        .collect();
 
     // CRT:
     // x = a1 mod n1
     // x = a2 mod n2
-    // x = a3 mod n3
+    // ...
     //
     // Here we have the ciphertexts are
-    // m**3 = c1 mod n1
-    // m**3 = c2 mod n2
-    // m**3 = c3 mod n3
-
-    let result: BigInt = (&c1 * &ms_1 * invmod(&ms_1, &n1)
-        + &c2 * &ms_2 * invmod(&ms_2, &n2)
-        + &c3 * &ms_3 * invmod(&ms_3, &n3))
-        % (&n1 * &n2 * &n3);
-    let cuberoot = result.cbrt();
-    println!("Cube root: {cuberoot}");
-    println!("Secret:    {}", secret_num);
-    assert_eq!(secret_num, cuberoot);
-
-    Ok(())
+    // m**e = c1 mod n1
+    // m**e = c2 mod n2
+    // ...
+    let root = broadcast_attack(&intercepted)?;
+    say!("e-th root: {root}");
+    say!("Secret:    {}", secret_num);
+    let report = Report::new().check("broadcast", secret_num, root);
+
+    // Håstad: with the recipient's number stuck on the front, the messages all differ
+    let secret = b"Padding with a recipient ID doesn't save it";
+    let secret_num = BigInt::from_bytes_be(num_bigint::Sign::Plus, secret);
+    let bound = BigInt::one() << (8 * secret.len());
+    let intercepted: Vec<(BigInt, Key, Padding)> = (0..e)
+        .map(|i| {
+            let public_key = params.keypair(&mut rng).public;
+            let padding = Padding {
+                a: BigInt::one(),
+                b: BigInt::from(i + 1) * &bound,
+            };
+            (
+                public_key.apply(&padding.apply(&secret_num)),
+                public_key,
+                padding,
+            )
+        })
+        .collect();
+    let plain: Vec<_> = intercepted
+        .iter()
+        .map(|(c, key, _)| (c.clone(), key.clone()))
+        .collect();
+    say!(
+        "\nPadded for each recipient, CRT and a root give: {}",
+        match broadcast_attack(&plain) {
+            Ok(root) => root.to_string(),
+            Err(e) => e.to_string(),
+        }
+    );
+    let found = hastad_attack(&intercepted, &bound)?;
+    say!(
+        "Coppersmith gives: {}",
+        String::from_utf8_lossy(&found.to_bytes_be().1)
+    );
+
+    Ok(report.check("padded broadcast", secret_num, found))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use num_traits::{One, Zero};
 
     #[test]
     fn crt() {
-        main().unwrap();
+        main().and_then(Report::verify).unwrap();
+    }
+
+    #[test]
+    fn roots() {
+        let x = BigInt::from(12345).pow(7);
+        assert_eq!(iroot(&x, 7), Some(12345.into()));
+        assert_eq!(iroot(&(x + 1), 7), None);
+        assert_eq!(iroot(&BigInt::one(), 3), Some(BigInt::one()));
+    }
+
+    #[test]
+    fn other_exponents() {
+        let mut rng = rand::thread_rng();
+        let m = BigInt::from_bytes_be(num_bigint::Sign::Plus, b"attack at dawn");
+        for e in [3, 5, 7, 11] {
+            let params = Params::new(256, e);
+            let intercepted: Vec<_> = (0..e)
+                .map(|_| {
+                    let key = params.keypair(&mut rng).public;
+                    (key.apply(&m), key)
+                })
+                .collect();
+            assert_eq!(broadcast_attack(&intercepted).unwrap(), m);
+            assert!(broadcast_attack(&intercepted[1..]).is_err());
+        }
+    }
+
+    #[test]
+    fn hastad() {
+        use num_bigint::RandBigInt;
+        let mut rng = rand::thread_rng();
+        let m = rng.gen_bigint_range(&Zero::zero(), &(BigInt::one() << 150));
+        let bound = BigInt::one() << 150;
+        let intercept = |exponents: &[u64], rng: &mut rand::rngs::ThreadRng| -> Vec<_> {
+            exponents
+                .iter()
+                .map(|e| {
+                    let key = Params::new(256, *e).keypair(rng).public;
+                    let padding = Padding {
+                        a: rng.gen_bigint_range(&One::one(), &key.modulus),
+                        b: rng.gen_bigint_range(&Zero::zero(), &key.modulus),
+                    };
+                    (key.apply(&padding.apply(&m)), key, padding)
+                })
+                .collect()
+        };
+
+        let intercepted = intercept(&[3, 3, 3], &mut rng);
+        assert_eq!(hastad_attack(&intercepted, &bound).unwrap(), m);
+        // Not enough to go on with only two
+        assert!(hastad_attack(&intercepted[1..], &bound).is_err());
+
+        // Different exponents, which takes more recipients
+        let intercepted = intercept(&[3, 5, 3, 5, 5], &mut rng);
+        assert_eq!(hastad_attack(&intercepted, &bound).unwrap(), m);
     }
 }
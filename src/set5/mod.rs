@@ -1,25 +1,11 @@
-pub mod challenge33;
-pub mod challenge34;
-pub mod challenge35;
-pub mod challenge36;
-pub mod challenge37;
-pub mod challenge38;
-pub mod challenge39;
-pub mod challenge40;
-
-use crate::utils::Result;
-use anyhow::anyhow;
-
-pub fn run(c: u64) -> Result<()> {
-    match c {
-        33 => challenge33::main(),
-        34 => challenge34::main(),
-        35 => challenge35::main(),
-        36 => challenge36::main(),
-        37 => challenge37::main(),
-        38 => challenge38::main(),
-        39 => challenge39::main(),
-        40 => challenge40::main(),
-        i => Err(anyhow!("{} not in set 5", i)),
-    }
+crate::runner::register! {
+    set 5;
+    33 => challenge33, "Implement Diffie-Hellman", Fast;
+    34 => challenge34, "Implement a MITM key-fixing attack on Diffie-Hellman with parameter injection", Fast;
+    35 => challenge35, "Implement DH with negotiated groups, and break with malicious \"g\" parameters", Fast;
+    36 => challenge36, "Implement Secure Remote Password (SRP)", Fast;
+    37 => challenge37, "Break SRP with a zero key", Fast;
+    38 => challenge38, "Offline dictionary attack on simplified SRP", Fast;
+    39 => challenge39, "Implement RSA", Fast;
+    40 => challenge40, "Implement an E=3 RSA Broadcast attack", Fast;
 }
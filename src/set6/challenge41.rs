@@ -39,36 +39,120 @@
 //! Remember: you don't simply divide mod N; you multiply by the multiplicative inverse mod N. So
 //! you'll need a modinv() function.
 
+use std::collections::HashSet;
+
+use crate::hash::sha256::sha256;
+use crate::report::say;
+use crate::rsa::{Key, KeyPair, Params};
+use crate::set5::challenge39::try_invmod;
 use crate::utils::*;
+use anyhow::anyhow;
 use num_bigint::{BigInt, RandBigInt, Sign};
-use rand::thread_rng;
-
-pub fn main() -> Result<()> {
-    let mut rng = thread_rng();
-    let secret = random_bytes(16, 32, &mut rng);
-    println!("Original secret: {}", bytes_to_hex(&secret));
-    let e: BigInt = 3.into();
-    let (et, n) = et_n(256, &e);
-    let d = invmod(&e, &et);
-    let public_key = (e.clone(), n.clone());
-    let private_key = (d, n.clone());
-
-    let encrypted = rsa_encrypt(&public_key, &secret);
-    let encrypted_num = BigInt::from_bytes_be(Sign::Plus, &encrypted);
-    let s = rng.gen_bigint_range(&2.into(), &n);
-
-    let encryptedp = (s.modpow(&e, &n) * encrypted_num) % &n;
-
-    let ppbytes = rsa_decrypt(&private_key, &encryptedp.to_bytes_be().1);
-    let pp = BigInt::from_bytes_be(Sign::Plus, &ppbytes);
-    let sinv = invmod(&s, &n);
+use rand::Rng;
+use thiserror::Error;
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum OracleError {
+    #[error("Already decrypted a ciphertext with SHA-256 {0}")]
+    Replay(String),
+}
+
+/// The server, which decrypts whatever it's sent, but never the same thing twice
+///
+/// It goes by the hash of the ciphertext reduced mod N, so that sending C + N (which decrypts the
+/// same) doesn't get past it either.
+pub struct DecryptionOracle {
+    public_key: Key,
+    private_key: Key,
+    seen: HashSet<Vec<u8>>,
+}
+
+impl DecryptionOracle {
+    pub fn new(KeyPair { public, private }: KeyPair) -> Self {
+        Self {
+            public_key: public,
+            private_key: private,
+            seen: HashSet::new(),
+        }
+    }
+
+    pub fn public_key(&self) -> &Key {
+        &self.public_key
+    }
+
+    pub fn decrypt(&mut self, ciphertext: &[u8]) -> Result<Vec<u8>, OracleError> {
+        let c = BigInt::from_bytes_be(Sign::Plus, ciphertext) % &self.public_key.modulus;
+        let hash = sha256(&c.to_bytes_be().1);
+        if !self.seen.insert(hash.clone()) {
+            return Err(OracleError::Replay(bytes_to_hex(&hash)));
+        }
+        Ok(rsa_decrypt(&self.private_key, &c.to_bytes_be().1))
+    }
+}
+
+/// Gets the oracle to decrypt a ciphertext it's already seen, by blinding it
+///
+/// C' = S**E * C mod N decrypts to P' = S * P mod N, and C' looks nothing like C, so the oracle
+/// will happily decrypt it. P' / S is then P.
+pub fn recover(
+    oracle: &mut DecryptionOracle,
+    ciphertext: &[u8],
+    rng: &mut impl Rng,
+) -> Result<Vec<u8>> {
+    let Key { key: e, modulus: n } = oracle.public_key().clone();
+    let c = BigInt::from_bytes_be(Sign::Plus, ciphertext);
+    // Any S > 1 will do, as long as it can be divided by afterwards
+    let (s, sinv) = loop {
+        let s = rng.gen_bigint_range(&2.into(), &n);
+        if let Some(sinv) = try_invmod(&s, &n) {
+            break (s, sinv);
+        }
+    };
+
+    let blinded = (s.modpow(&e, &n) * c) % &n;
+    let pp = BigInt::from_bytes_be(Sign::Plus, &oracle.decrypt(&blinded.to_bytes_be().1)?);
     let p = (pp * sinv) % &n;
+    Ok(p.to_bytes_be().1)
+}
+
+pub fn main() -> Result<Report> {
+    let params = Params::from_options(512, 3)?;
+    say!("Using a {params}");
+    let mut rng = crate::utils::rng();
+    let mut oracle = DecryptionOracle::new(params.keypair(&mut rng));
+    let public_key = oracle.public_key().clone();
+
+    let secret = format!(
+        "{{time: {}, social: '555-55-5555'}}",
+        chrono::Utc::now().timestamp()
+    );
+    if secret.len() >= public_key.size() {
+        return Err(anyhow!(
+            "{}-byte message doesn't fit in a {}-byte key",
+            secret.len(),
+            public_key.size()
+        ));
+    }
+    say!("Original secret: {secret}");
 
-    let pbytes = p.to_bytes_be().1;
-    println!("Derived secret:  {}", bytes_to_hex(&pbytes));
-    assert_eq!(pbytes, secret);
+    // Someone else sends their message, which the server decrypts for them
+    let encrypted = rsa_encrypt(&public_key, secret.as_bytes());
+    oracle.decrypt(&encrypted)?;
 
-    Ok(())
+    // Which means it won't decrypt it for us, even disguised as C + N
+    let replay = oracle.decrypt(&encrypted);
+    say!("Replaying it:    {replay:?}");
+    let shifted = BigInt::from_bytes_be(Sign::Plus, &encrypted) + &public_key.modulus;
+    let shifted = oracle.decrypt(&shifted.to_bytes_be().1);
+    say!("Sending C + N:   {shifted:?}");
+    if replay.is_ok() || shifted.is_ok() {
+        return Err(anyhow!("Server decrypted the same message twice"));
+    }
+
+    let recovered = recover(&mut oracle, &encrypted, &mut rng)?;
+    say!("Derived secret:  {}", String::from_utf8_lossy(&recovered));
+
+    Ok(Report::new().check("secret", secret.as_bytes(), recovered))
 }
 
 #[cfg(test)]
@@ -77,6 +161,18 @@ mod tests {
     use super::*;
     #[test]
     fn message_recovery() {
-        main().unwrap();
+        main().and_then(Report::verify).unwrap();
+    }
+
+    #[test]
+    fn replays() {
+        let mut rng = rand::thread_rng();
+        let mut oracle = DecryptionOracle::new(Params::new(256, 65537).keypair(&mut rng));
+        let c = rsa_encrypt(oracle.public_key(), b"hello");
+        assert_eq!(oracle.decrypt(&c).unwrap(), b"hello");
+        assert!(matches!(oracle.decrypt(&c), Err(OracleError::Replay(_))));
+        // Blinding works whatever e is
+        assert_eq!(recover(&mut oracle, &c, &mut rng).unwrap(), b"hello");
+        assert_eq!(recover(&mut oracle, &c, &mut rng).unwrap(), b"hello");
     }
 }
@@ -53,87 +53,315 @@
 //! Forge a 1024-bit RSA signature for the string "hi mom". Make sure your implementation actually
 //! accepts the signature!
 
+use anyhow::anyhow;
 use num_bigint::{BigInt, Sign};
+use num_traits::One;
 use openssl::sha::sha256;
+use thiserror::Error;
 
+use crate::report::say;
+use crate::rsa::{Key, KeyPair, Params};
 use crate::utils::*;
 
-pub fn verify(public_key: &(BigInt, BigInt), message: &[u8], signed_digest: &[u8]) -> Auth {
-    let hash = sha256(message).to_vec();
-    let digest = rsa_encrypt(public_key, signed_digest);
-
-    // First check there is an index where the hash exists
-    if let Some(index) = digest.windows(hash.len()).position(|x| *x.to_vec() == hash) {
-        // Should have form
-        // 00h 01h ffh ffh ... ffh ffh 00h ASN.1 GOOP HASH
-        // Ignore first 00 because it gets dropped from be
-        let mut target_padding = vec![0x01];
-        // What is l here?
-        let asn1 = b"SHA256";
-        let ff_len = index - (asn1.len() + 1 + 1);
-        target_padding.extend_from_slice(&vec![0xff; ff_len]);
-        target_padding.push(0x00);
-        target_padding.extend_from_slice(asn1);
-        match target_padding[..] == digest[..index] {
-            true => Auth::Valid,
-            false => Auth::Invalid,
+// A real DigestInfo is a lot longer than "SHA256": 19 bytes of DER before the hash. Along with the
+// eight ffh bytes PKCS#1 insists on, that's too much for a 1024-bit modulus, where the error in a
+// cube root is bigger than the space left for garbage. 2048 bits leaves plenty.
+
+/// SHA-256's object identifier, 2.16.840.1.101.3.4.2.1
+pub const SHA256_OID: &[u64] = &[2, 16, 840, 1, 101, 3, 4, 2, 1];
+
+/// Fewest ffh bytes PKCS#1 allows in the padding
+const MIN_PADDING: usize = 8;
+
+pub const SEQUENCE: u8 = 0x30;
+pub const OBJECT_IDENTIFIER: u8 = 0x06;
+pub const NULL: u8 = 0x05;
+pub const OCTET_STRING: u8 = 0x04;
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum Asn1Error {
+    #[error("DER ended early")]
+    Truncated,
+    #[error("Expected DER tag {expected:02x}, got {found:02x}")]
+    UnexpectedTag { expected: u8, found: u8 },
+    #[error("Invalid DER length")]
+    BadLength,
+    #[error("{0} bytes left over after the DER")]
+    TrailingData(usize),
+}
+
+/// DER encoding of a tag and its contents
+pub fn der(tag: u8, contents: &[u8]) -> Vec<u8> {
+    let mut encoded = vec![tag];
+    match contents.len() {
+        len if len < 0x80 => encoded.push(len as u8),
+        len => {
+            let len_bytes: Vec<u8> = len
+                .to_be_bytes()
+                .into_iter()
+                .skip_while(|b| *b == 0)
+                .collect();
+            encoded.push(0x80 | len_bytes.len() as u8);
+            encoded.extend_from_slice(&len_bytes);
         }
-    } else {
-        Auth::Invalid
     }
+    encoded.extend_from_slice(contents);
+    encoded
 }
 
-#[allow(dead_code)]
-pub fn sign(private_key: &(BigInt, BigInt), message: &[u8]) -> Vec<u8> {
-    // How big is the block? Let's say 256 bytes
-    // SHA256 hash is 256 / 8 = 32 bytes
-    // So this leaves
-    let hash = sha256(message).to_vec();
-    let mut padding = vec![0x00, 0x01];
-    let asn1 = b"SHA256";
-    let ff_len = 256 - (asn1.len() + 2 + 1 + hash.len());
-    padding.extend_from_slice(&vec![0xff; ff_len]);
-    padding.push(0x00);
-    padding.extend_from_slice(asn1);
-    padding.extend_from_slice(&hash);
-
-    rsa_decrypt(private_key, &padding)
+/// Reads one DER value with tag `expected` from the front of `input`, returning its contents and
+/// whatever comes after it
+pub fn read_der(input: &[u8], expected: u8) -> Result<(&[u8], &[u8]), Asn1Error> {
+    let (&tag, rest) = input.split_first().ok_or(Asn1Error::Truncated)?;
+    if tag != expected {
+        return Err(Asn1Error::UnexpectedTag {
+            expected,
+            found: tag,
+        });
+    }
+    let (&first, mut rest) = rest.split_first().ok_or(Asn1Error::Truncated)?;
+    let len = match first {
+        len if len < 0x80 => len as usize,
+        0x80 => return Err(Asn1Error::BadLength),
+        n => {
+            let n = (n & 0x7f) as usize;
+            if n > std::mem::size_of::<usize>() || rest.len() < n {
+                return Err(Asn1Error::BadLength);
+            }
+            let (len_bytes, after) = rest.split_at(n);
+            rest = after;
+            // DER lengths use as few bytes as they can
+            if len_bytes[0] == 0 {
+                return Err(Asn1Error::BadLength);
+            }
+            let len = len_bytes.iter().fold(0, |acc, b| (acc << 8) | *b as usize);
+            if len < 0x80 {
+                return Err(Asn1Error::BadLength);
+            }
+            len
+        }
+    };
+    if rest.len() < len {
+        return Err(Asn1Error::Truncated);
+    }
+    Ok(rest.split_at(len))
 }
 
-pub fn main() -> Result<()> {
-    let e: BigInt = 3.into();
-    let (et, n) = et_n(1024, &e);
-    let d = invmod(&e, &et);
-    let public_key = (e, n.clone());
-    let _private_key = (d, n);
+/// Contents of an OBJECT IDENTIFIER: the first two arcs in one byte, then each arc in base 128
+pub fn encode_oid(arcs: &[u64]) -> Vec<u8> {
+    let mut encoded = vec![(arcs[0] * 40 + arcs[1]) as u8];
+    for &arc in &arcs[2..] {
+        let mut digits = vec![(arc & 0x7f) as u8];
+        let mut rest = arc >> 7;
+        while rest > 0 {
+            digits.push(0x80 | (rest & 0x7f) as u8);
+            rest >>= 7;
+        }
+        encoded.extend(digits.iter().rev());
+    }
+    encoded
+}
 
-    let message = b"hi mom";
-    let hash = sha256(message);
-    println!("Hash: {:?}", hash);
-    //let signed = sign(&private_key, message);
-
-    // Now to forge  the message
-    // Make an extremely small padding string
-    let mut padded = vec![0x01, 0xff, 0xff];
-    let asn1 = b"SHA256";
+fn decode_oid(encoded: &[u8]) -> Result<Vec<u64>, Asn1Error> {
+    let (&first, rest) = encoded.split_first().ok_or(Asn1Error::Truncated)?;
+    let mut arcs = vec![
+        (first / 40).min(2) as u64,
+        first as u64 - 40 * (first / 40).min(2) as u64,
+    ];
+    let mut arc = 0_u64;
+    for (i, &b) in rest.iter().enumerate() {
+        arc = (arc << 7) | (b & 0x7f) as u64;
+        if b & 0x80 == 0 {
+            arcs.push(arc);
+            arc = 0;
+        } else if i == rest.len() - 1 {
+            return Err(Asn1Error::Truncated);
+        }
+    }
+    Ok(arcs)
+}
+
+/// What PKCS#1 v1.5 actually signs: the hash, along with which hash it is
+///
+/// DigestInfo ::= SEQUENCE {
+///     digestAlgorithm AlgorithmIdentifier, -- SEQUENCE { OBJECT IDENTIFIER, NULL }
+///     digest OCTET STRING
+/// }
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DigestInfo {
+    pub algorithm: Vec<u64>,
+    pub digest: Vec<u8>,
+}
+
+impl DigestInfo {
+    pub fn sha256(message: &[u8]) -> Self {
+        Self {
+            algorithm: SHA256_OID.to_vec(),
+            digest: sha256(message).to_vec(),
+        }
+    }
+
+    pub fn to_der(&self) -> Vec<u8> {
+        let algorithm = [
+            der(OBJECT_IDENTIFIER, &encode_oid(&self.algorithm)),
+            der(NULL, &[]),
+        ];
+        der(
+            SEQUENCE,
+            &[
+                der(SEQUENCE, &algorithm.concat()),
+                der(OCTET_STRING, &self.digest),
+            ]
+            .concat(),
+        )
+    }
+
+    /// Reads a DigestInfo from the front of `input`, returning whatever comes after it
+    pub fn from_der(input: &[u8]) -> Result<(Self, &[u8]), Asn1Error> {
+        let (info, after) = read_der(input, SEQUENCE)?;
+        let (algorithm, rest) = read_der(info, SEQUENCE)?;
+        let (digest, rest) = read_der(rest, OCTET_STRING)?;
+        if !rest.is_empty() {
+            return Err(Asn1Error::TrailingData(rest.len()));
+        }
+        let (oid, rest) = read_der(algorithm, OBJECT_IDENTIFIER)?;
+        // The parameters are NULL, or may be left out altogether
+        if !rest.is_empty() {
+            let (_, rest) = read_der(rest, NULL)?;
+            if !rest.is_empty() {
+                return Err(Asn1Error::TrailingData(rest.len()));
+            }
+        }
+        let info = Self {
+            algorithm: decode_oid(oid)?,
+            digest: digest.to_vec(),
+        };
+        Ok((info, after))
+    }
+}
+
+/// EMSA-PKCS1-v1_5 encoding of a message, for a key of `size` bytes:
+/// 00h 01h ffh ffh ... ffh ffh 00h DigestInfo
+pub fn encode(message: &[u8], size: usize) -> Result<Vec<u8>> {
+    let info = DigestInfo::sha256(message).to_der();
+    if size < info.len() + MIN_PADDING + 3 {
+        return Err(anyhow!("A {size}-byte key is too short to sign with"));
+    }
+    let mut encoded = vec![0x00, 0x01];
+    encoded.resize(size - info.len() - 1, 0xff);
+    encoded.push(0x00);
+    encoded.extend_from_slice(&info);
+    Ok(encoded)
+}
+
+/// "Decrypts" a signature, and picks out the DigestInfo from after the padding, along with whatever
+/// follows it
+fn parse(public_key: &Key, signature: &[u8]) -> Option<(DigestInfo, usize)> {
+    let size = public_key.size();
+    let m = public_key.apply(&BigInt::from_bytes_be(Sign::Plus, signature));
+    let mut block = m.to_bytes_be().1;
+    if block.len() >= size {
+        return None;
+    }
+    block.splice(0..0, vec![0; size - block.len()]);
+
+    let padding = block[2..].iter().take_while(|b| **b == 0xff).count();
+    if block[..2] != [0x00, 0x01] || padding < MIN_PADDING || block.get(2 + padding) != Some(&0) {
+        return None;
+    }
+    let (info, rest) = DigestInfo::from_der(&block[3 + padding..]).ok()?;
+    Some((info, rest.len()))
+}
+
+/// The flawed verifier: checks the padding and the DigestInfo, but not that the DigestInfo is the
+/// end of the block, so anything at all can come after it
+pub fn verify_sloppy(public_key: &Key, message: &[u8], signature: &[u8]) -> Auth {
+    match parse(public_key, signature) {
+        Some((info, _)) if info == DigestInfo::sha256(message) => Auth::Valid,
+        _ => Auth::Invalid,
+    }
+}
+
+/// The same, apart from also insisting that the hash is right-justified, with nothing after it
+pub fn verify_strict(public_key: &Key, message: &[u8], signature: &[u8]) -> Auth {
+    match parse(public_key, signature) {
+        Some((info, 0)) if info == DigestInfo::sha256(message) => Auth::Valid,
+        _ => Auth::Invalid,
+    }
+}
+
+pub fn sign(private_key: &Key, message: &[u8]) -> Result<Vec<u8>> {
+    Ok(rsa_decrypt(
+        private_key,
+        &encode(message, private_key.size())?,
+    ))
+}
+
+/// Forges a signature on `message` which [`verify_sloppy`] accepts for any e=3 key with a modulus
+/// of `size` bytes
+pub fn forge(message: &[u8], size: usize) -> Vec<u8> {
+    // As little padding as the verifier will take
+    let mut padded = vec![0x00, 0x01];
+    padded.extend_from_slice(&[0xff; MIN_PADDING]);
     padded.push(0x00);
-    padded.extend_from_slice(asn1);
-    padded.extend_from_slice(&hash);
-    // Now make up a lot more of it by right-padding with junk (zeros make the cube root difficult
-    // to hit)
-    padded.extend_from_slice(&vec![0x01; 255 - padded.len()]);
+    padded.extend_from_slice(&DigestInfo::sha256(message).to_der());
+    // Now make up the rest of the block with zeros, as room for the cube root's error to go
+    padded.resize(size, 0);
     // Convert to a BigInt
     let padded_int = BigInt::from_bytes_be(Sign::Plus, &padded);
-    // Cube root
-    let forged_int = padded_int.cbrt();
-    let forged_message = BigInt::to_bytes_be(&forged_int);
+    // Cube root, rounded up so that cubing it again only ever adds to the garbage
+    let mut forged_int = padded_int.cbrt();
+    if forged_int.pow(3) != padded_int {
+        forged_int += BigInt::one();
+    }
+    forged_int.to_bytes_be().1
+}
 
-    println!("Forged: {:?}", forged_message.1);
-    let verified = verify(&public_key, message, &forged_message.1);
-    println!("Verified? {:?}", verified);
+pub fn main() -> Result<Report> {
+    let params = Params::from_options(2048, 3)?;
+    // The forgery is a cube root
+    params.require_e(3)?;
+    say!("Using a {params}");
+    let KeyPair {
+        public: public_key,
+        private: private_key,
+    } = params.keypair(&mut crate::utils::rng());
 
-    assert_eq!(verified, Auth::Valid);
-    Ok(())
+    let message = b"hi mom";
+    say!(
+        "DigestInfo: {}",
+        bytes_to_hex(&DigestInfo::sha256(message).to_der())
+    );
+
+    // A real signature gets past both
+    let signed = sign(&private_key, message)?;
+    if verify_strict(&public_key, message, &signed) != Auth::Valid
+        || verify_sloppy(&public_key, message, &signed) != Auth::Valid
+    {
+        return Err(anyhow!("Genuine signature didn't verify"));
+    }
+
+    // Now to forge the message
+    let forged_message = forge(message, public_key.size());
+    say!("Forged: {}", bytes_to_hex(&forged_message));
+
+    let sloppy = verify_sloppy(&public_key, message, &forged_message);
+    let strict = verify_strict(&public_key, message, &forged_message);
+    say!("Verified by the sloppy verifier? {sloppy:?}");
+    say!("Verified by the strict verifier? {strict:?}");
+
+    if strict == Auth::Valid {
+        return Err(anyhow!("Strict verifier accepted a forgery"));
+    }
+    if sloppy != Auth::Valid {
+        say!(
+            "A {}-bit modulus may not leave enough room for the forgery",
+            params.bits
+        );
+    }
+    Ok(Report::new()
+        .recovered("forgery", forged_message)
+        .forged("forgery", sloppy == Auth::Valid))
 }
 
 #[cfg(test)]
@@ -142,21 +370,67 @@ mod tests {
 
     #[test]
     fn signing_validation() {
-        let e: BigInt = 3.into();
-        let (et, n) = et_n(1024, &e);
-        let d = invmod(&e, &et);
-        let public_key = (e, n.clone());
-        let private_key = (d, n);
+        let KeyPair {
+            public: public_key,
+            private: private_key,
+        } = Params::new(1024, 3).keypair(&mut rand::thread_rng());
 
         let message = b"hi mom";
-        let signed = sign(&private_key, message);
-        let verified = verify(&public_key, message, &signed);
+        let signed = sign(&private_key, message).unwrap();
+        assert_eq!(verify_strict(&public_key, message, &signed), Auth::Valid);
+        assert_eq!(verify_sloppy(&public_key, message, &signed), Auth::Valid);
+        assert_eq!(
+            verify_strict(&public_key, b"hi dad", &signed),
+            Auth::Invalid
+        );
+        let small = Params::new(256, 3).keypair(&mut rand::thread_rng());
+        assert!(sign(&small.private, message).is_err());
+    }
+
+    #[test]
+    fn digest_info() {
+        // The well-known prefix from RFC 8017
+        let info = DigestInfo::sha256(b"abc");
+        let encoded = info.to_der();
+        assert_eq!(
+            bytes_to_hex(&encoded[..19]),
+            "3031300d060960864801650304020105000420"
+        );
+        assert_eq!(encoded[19..], sha256(b"abc"));
+        assert_eq!(DigestInfo::from_der(&encoded), Ok((info.clone(), &[][..])));
 
-        assert_eq!(verified, Auth::Valid);
+        // Anything after it is handed back, not an error
+        let mut garbage = encoded.clone();
+        garbage.extend_from_slice(b"garbage");
+        assert_eq!(DigestInfo::from_der(&garbage), Ok((info, &b"garbage"[..])));
+        assert_eq!(
+            DigestInfo::from_der(&encoded[..40]),
+            Err(Asn1Error::Truncated)
+        );
+        assert!(DigestInfo::from_der(&encoded[2..]).is_err());
+    }
+
+    #[test]
+    fn der_lengths() {
+        for len in [0, 1, 127, 128, 255, 256, 70000] {
+            let contents = vec![0xab; len];
+            let encoded = der(OCTET_STRING, &contents);
+            assert_eq!(
+                read_der(&encoded, OCTET_STRING),
+                Ok((&contents[..], &[][..]))
+            );
+        }
+        assert_eq!(der(OCTET_STRING, &[0; 200])[..3], [0x04, 0x81, 200]);
+        // Non-minimal lengths aren't DER
+        assert_eq!(
+            read_der(&[0x04, 0x81, 0x01, 0x00], OCTET_STRING),
+            Err(Asn1Error::BadLength)
+        );
+        assert_eq!(decode_oid(&encode_oid(SHA256_OID)).unwrap(), SHA256_OID);
     }
 
     #[test]
     fn forged() {
-        main().unwrap();
+        main().and_then(Report::verify).unwrap();
     }
 }
@@ -65,114 +65,21 @@
 //! 0954edd5e0afe5542a4adf012611a91912a3ec16
 //! Obviously, it also generates the same signature for that string.
 
-use num_bigint::{BigInt, RandBigInt, Sign};
+use std::ops::RangeInclusive;
+
+use anyhow::anyhow;
+use num_bigint::BigInt;
 use num_traits::Num;
 use openssl::sha::sha1;
-use rand::thread_rng;
+use rand::Rng;
+use rayon::prelude::*;
 
+use crate::dsa::{hash, sign_with_k, verify, Params, Sig};
+use crate::report::say;
 use crate::utils::*;
 
-#[derive(Debug)]
-pub struct Params {
-    pub q: BigInt,
-    pub p: BigInt,
-    pub g: BigInt,
-}
-
-#[derive(Debug)]
-pub struct Sig {
-    pub r: BigInt,
-    pub s: BigInt,
-}
-impl Default for Params {
-    fn default() -> Self {
-        let p: BigInt = BigInt::from_str_radix(
-            "800000000000000089e1855218a0e7dac38136ffafa72eda7\
-         859f2171e25e65eac698c1702578b07dc2a1076da241c76c6\
-         2d374d8389ea5aeffd3226a0530cc565f3bf6b50929139ebe\
-         ac04f48c3c84afb796d61e5a4f9a8fda812ab59494232c7d2\
-         b4deb50aa18ee9e132bfa85ac4374d7f9091abc3d015efc87\
-         1a584471bb1",
-            16,
-        )
-        .unwrap();
-
-        let q: BigInt =
-            BigInt::from_str_radix("f4f47f05794b256174bba6e9b396a7707e563c5b", 16).unwrap();
-
-        let g: BigInt = BigInt::from_str_radix(
-            "5958c9d3898b224b12672c0b98e06c60df923cb8bc999d119\
-         458fef538b8fa4046c8db53039db620c094c9fa077ef389b5\
-         322a559946a71903f990f1f7e0e025e2d7f7cf494aff1a047\
-         0f5b64c36b625a097f1651fe775323556fe00b3608c887892\
-         878480e99041be601a62166ca6894bdd41a7054ec89f756ba\
-         9fc95302291",
-            16,
-        )
-        .unwrap();
-        Self { q, p, g }
-    }
-}
-
-#[allow(dead_code)]
-pub fn sign(private_key: &BigInt, params: &Params, message: &[u8]) -> Sig {
-    let Params { q, p, g } = params;
-
-    let mut rng = thread_rng();
-    let h: BigInt = BigInt::from_bytes_be(Sign::Plus, &sha1(message));
-    loop {
-        let k = rng.gen_bigint_range(&1.into(), q);
-        let kinv = invmod(&k, q);
-        let two: BigInt = 2.into();
-        let qm2 = q - &two;
-        let kinvprime = k.modpow(&qm2, q);
-        assert_eq!(kinv, kinvprime);
-
-        let r = g.modpow(&k, p) % q;
-        let s = (kinv * (&h + private_key * &r)) % q;
-
-        if r != 0.into() && s != 0.into() {
-            return Sig { r, s };
-        }
-    }
-}
-
-#[allow(dead_code)]
-pub fn verify(y: &BigInt, params: &Params, message: &[u8], signature: &Sig) -> Auth {
-    let Params { q, p, g } = params;
-    let Sig { r, s } = signature;
-    let h: BigInt = BigInt::from_bytes_be(Sign::Plus, &sha1(message));
-    let zero: BigInt = 0.into();
-
-    match &zero < r && r < q {
-        true => {}
-        false => return Auth::Invalid,
-    };
-    match &zero < s && s < q {
-        true => {}
-        false => return Auth::Invalid,
-    };
-
-    let w: BigInt = invmod(s, q);
-
-    // g**(h w) * g**(x * rw)
-    // g**(hw + xrw) mod p mod q
-    // w = s^{-1}
-    // s = (k^-1 (h+x r))
-    // w(h+r) = (h+xr) * k / (h+xr)
-    //        = k
-    // g**k mod p mod q
-    // = r
-
-    let u1: BigInt = (h * &w) % q;
-    let u2: BigInt = (r * &w) % q;
-    let v = ((g.modpow(&u1, p) * y.modpow(&u2, p)) % p) % q;
-
-    match &v == r {
-        true => Auth::Valid,
-        false => Auth::Invalid,
-    }
-}
+/// SHA-1 of the private key in hex, as published with the challenge
+const FINGERPRINT: &str = "0954edd5e0afe5542a4adf012611a91912a3ec16";
 
 //           (s * k) - H(msg)
 //       x = ----------------  mod q
@@ -180,21 +87,67 @@ pub fn verify(y: &BigInt, params: &Params, message: &[u8], signature: &Sig) -> A
 pub fn get_x_from_k(signature: &Sig, k: &BigInt, params: &Params, message: &[u8]) -> BigInt {
     let Params { q, .. } = params;
     let Sig { r, s } = signature;
-    let h: BigInt = BigInt::from_bytes_be(Sign::Plus, &sha1(message));
+    let h = hash(message);
 
     // Have to use their weird way of computing the hash
     //let hash = sha1(message);
     //let hex = bytes_to_hex(&hash);
     //let h = BigInt::from_str_radix(&hex, 16).unwrap();
-    println!("Hash as hex int: {h:x}");
-
-    let rinv = invmod(r, q);
+    let rinv = try_invmod(r, q).unwrap();
     (rinv * (s * k - &h)) % q
 }
 
-pub fn main() -> Result<()> {
-    let params = Params::default();
+/// Tries every k in `nonces` across all the cores, for the one which reproduces r and gives the
+/// private key matching `y`, stopping as soon as any of them finds it
+pub fn brute_force_x(
+    y: &BigInt,
+    params: &Params,
+    message: &[u8],
+    signature: &Sig,
+    nonces: RangeInclusive<u64>,
+) -> Option<(u64, BigInt)> {
+    nonces.into_par_iter().find_map_any(|k| {
+        let ktry: BigInt = k.into();
+        if params.g.modpow(&ktry, &params.p) % &params.q != signature.r {
+            return None;
+        }
+        let x = get_x_from_k(signature, &ktry, params, message);
+        (params.g.modpow(&x, &params.p) == *y).then_some((k, x))
+    })
+}
+
+pub fn main() -> Result<Report> {
+    let mut rng = crate::utils::rng();
+    let params = Params::from_options(&mut rng)?;
+    match params == Params::default() {
+        true => published(&params),
+        false => generated(&params, &mut rng),
+    }
+}
+
+/// With fresh parameters there's no published key to find, so make one, and sign with a small k
+fn generated(params: &Params, rng: &mut impl Rng) -> Result<Report> {
+    say!("Using new parameters: {params:?}");
+    let (x, y) = params.keypair(rng);
+    let message = b"Hello, world!";
+    let (k, signature) = loop {
+        let k: BigInt = rng.gen_range(0..=1 << 16).into();
+        if let Some(signature) = sign_with_k(&x, params, message, &k) {
+            break (k, signature);
+        }
+    };
+    say!("Signature: {signature:?}");
+
+    let start = std::time::Instant::now();
+    let (k_found, x_derived) = brute_force_x(&y, params, message, &signature, 0..=1 << 16)
+        .ok_or_else(|| anyhow!("Did not find k"))?;
+    say!("Found k: {k_found} in {:.2?}", start.elapsed());
+    say!("Found x: {x_derived}");
+    Ok(Report::new().check("k", k, k_found).check("x", x, x_derived))
+}
 
+/// The key and signature from the challenge
+fn published(params: &Params) -> Result<Report> {
     let y = BigInt::from_str_radix(
         "84ad4719d044495496a3201c8ff484feb45b962e7302e56a392aee4\
          abab3e4bdebf2955b4736012f21a08084056b19bcd7fee56048e004\
@@ -206,9 +159,9 @@ pub fn main() -> Result<()> {
     .unwrap();
 
     let message = b"For those that envy a MC it can be hazardous to your health\nSo be friendly, a matter of life and death, just like a etch-a-sketch\n";
-    println!("message: {}", std::str::from_utf8(message).unwrap());
+    say!("message: {}", std::str::from_utf8(message).unwrap());
     let hash = sha1(message);
-    println!("Hash: {}", bytes_to_hex(&hash));
+    say!("Hash: {}", bytes_to_hex(&hash));
     let signature = Sig {
         r: "548099063082341131477253921760299949438196259240"
             .parse()
@@ -217,44 +170,38 @@ pub fn main() -> Result<()> {
             .parse()
             .unwrap(),
     };
-    println!("Signature: {signature:?}");
+    say!("Signature: {signature:?}");
     // Verify signature
-    let verified = verify(&y, &params, message, &signature);
-    println!("Verifies? {:?}", verified);
-    assert_eq!(verified, Auth::Valid);
-
-    // Find first k which reproduces r
-    if let Some(k) = (0..(1 << 16)).find(|i| {
-        let ktry: BigInt = (*i).into();
-        //        println!("Trying k: {ktry}");
-        let r = params.g.modpow(&ktry, &params.p) % &params.q;
-        r == signature.r
-    }) {
-        println!("Found k: {k}");
-        let x_derived = get_x_from_k(&signature, &k.into(), &params, message);
-        println!("Found x: {x_derived}");
-
-        // Check we get public key
-        let y_derived = params.g.modpow(&x_derived, &params.p);
-        assert_eq!(y, y_derived);
-
-        // Convert x to hex string, and then hash these bytes
-        let x_str = x_derived.to_str_radix(16);
-        let fingerprint = sha1(x_str.as_bytes());
-        let fingerprint_hex = bytes_to_hex(&fingerprint);
-        println!("Fingerprint: {fingerprint_hex}");
-
-        assert_eq!(fingerprint_hex, "0954edd5e0afe5542a4adf012611a91912a3ec16");
-    } else {
-        panic!("Did not find k");
+    let verified = verify(&y, params, message, &signature);
+    say!("Verifies? {:?}", verified);
+    if verified != Auth::Valid {
+        return Err(anyhow!("The published signature doesn't verify"));
     }
 
-    Ok(())
+    let start = std::time::Instant::now();
+    let (k, x_derived) = brute_force_x(&y, params, message, &signature, 0..=1 << 16)
+        .ok_or_else(|| anyhow!("Did not find k"))?;
+    say!("Found k: {k} in {:.2?}", start.elapsed());
+    say!("Found x: {x_derived}");
+
+    // Convert x to hex string, and then hash these bytes
+    let x_str = x_derived.to_str_radix(16);
+    let fingerprint = sha1(x_str.as_bytes());
+    let fingerprint_hex = bytes_to_hex(&fingerprint);
+    say!("Fingerprint: {fingerprint_hex}");
+
+    Ok(Report::new()
+        .recovered("k", k)
+        .recovered("x", x_derived)
+        .check("fingerprint", hex_to_bytes(FINGERPRINT)?, fingerprint))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::dsa::sign;
+    use num_bigint::RandBigInt;
+    use rand::thread_rng;
 
     #[test]
     fn functioning_verification() {
@@ -268,7 +215,7 @@ mod tests {
 
         let message = b"test message";
 
-        let signature = sign(&x, &params, message);
+        let signature = sign(&x, &params, message, &mut rng);
         println!("Signature {signature:?}");
         let x_derived = get_x_from_k(&signature, &2.into(), &params, message);
         println!("derived x: {x_derived}");
@@ -277,9 +224,29 @@ mod tests {
         println!("Verified: {:?}", verified);
     }
 
+    #[test]
+    fn small_nonce() {
+        let params = Params::default();
+        let x = thread_rng().gen_bigint_range(&1.into(), &params.q);
+        let y = params.g.modpow(&x, &params.p);
+        let signature = sign_with_k(&x, &params, b"test message", &1234.into()).unwrap();
+        assert_eq!(
+            verify(&y, &params, b"test message", &signature),
+            Auth::Valid
+        );
+        assert_eq!(
+            brute_force_x(&y, &params, b"test message", &signature, 0..=2000),
+            Some((1234, x))
+        );
+        assert_eq!(
+            brute_force_x(&y, &params, b"test message", &signature, 0..=1000),
+            None
+        );
+    }
+
     #[ignore = "slow"]
     #[test]
     fn find_private_key() {
-        main().unwrap();
+        main().and_then(Report::verify).unwrap();
     }
 }
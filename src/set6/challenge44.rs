@@ -39,15 +39,22 @@
 // => k(s1 - s2) = m1 - m2 mod q, as k and therefore r are the same
 // => k = (m1 - m2) / (s1 - s2) mod q
 
+use anyhow::anyhow;
 use num_bigint::BigInt;
 use num_traits::Num;
 use openssl::sha::sha1;
+use rand::Rng;
 
+use crate::report::say;
 use crate::{
-    set6::challenge43::{get_x_from_k, Params, Sig},
+    dsa::{hash, sign_with_k, Params, Sig},
+    set6::challenge43::get_x_from_k,
     utils::*,
 };
 
+/// SHA-1 of the private key in hex, as published with the challenge
+const FINGERPRINT: &str = "ca8f6f7c66fa362d40760d135b763eb8527d3d52";
+
 #[derive(Debug, Clone)]
 struct Quad {
     message: String,
@@ -56,8 +63,32 @@ struct Quad {
     m: BigInt,
 }
 
-pub fn main() -> Result<()> {
-    let params = Params::default();
+pub fn main() -> Result<Report> {
+    let mut rng = crate::utils::rng();
+    let params = Params::from_options(&mut rng)?;
+    let (quads, y, expected) = match params == Params::default() {
+        true => published()?,
+        false => generated(&params, &mut rng),
+    };
+
+    let x = recover_x(&quads, &params, &y).ok_or_else(|| anyhow!("No repeated nonce"))?;
+    say!("x: {x}");
+    let fingerprint = fingerprint(&x);
+    say!("Fingerprint: {}", bytes_to_hex(&fingerprint));
+
+    Ok(Report::new()
+        .recovered("x", x)
+        .check("fingerprint", expected, fingerprint))
+}
+
+/// SHA-1 of `x` in hex, which is how the challenge gives the key to find
+fn fingerprint(x: &BigInt) -> Vec<u8> {
+    sha1(x.to_str_radix(16).as_bytes()).to_vec()
+}
+
+/// The signatures from the challenge, the public key they were made with, and the private key's
+/// fingerprint
+fn published() -> Result<(Vec<Quad>, BigInt, Vec<u8>)> {
     let y = BigInt::from_str_radix(
         "2d026f4bf30195ede3a088da85e398ef869611d0f68f07\
      13d51c9c1a3a26c95105d915e2d8cdf26d056b86b8a7b8\
@@ -70,7 +101,7 @@ pub fn main() -> Result<()> {
     .unwrap();
 
     // First read the data from the file into triplets
-    let big_str = std::fs::read_to_string("./data/44.txt").unwrap();
+    let big_str = crate::data::CHALLENGE_44.text()?;
     let mut quads: Vec<Quad> = vec![];
     let splits: Vec<&str> = big_str.split('\n').collect();
     for quad in splits[..].chunks(4) {
@@ -88,23 +119,49 @@ pub fn main() -> Result<()> {
         };
         quads.push(q);
     }
+    Ok((quads, y, hex_to_bytes(FINGERPRINT)?))
+}
 
-    let mut pairs: Vec<Vec<Quad>> = vec![];
+/// With fresh parameters, a fresh key signs a few messages, reusing one nonce
+fn generated(params: &Params, rng: &mut impl Rng) -> (Vec<Quad>, BigInt, Vec<u8>) {
+    say!("Using new parameters: {params:?}");
+    let (x, y) = params.keypair(rng);
+    let reused: BigInt = rng.gen_range(1..u64::MAX).into();
+    let quads = (0..6)
+        .map(|i| {
+            let message = format!("Message number {i}");
+            let k = match i {
+                1 | 4 => reused.clone(),
+                _ => rng.gen_range(1..u64::MAX).into(),
+            };
+            let Sig { r, s } = sign_with_k(&x, params, message.as_bytes(), &k).unwrap();
+            Quad {
+                m: hash(message.as_bytes()),
+                message,
+                r,
+                s,
+            }
+        })
+        .collect();
+    (quads, y, fingerprint(&x))
+}
+
+/// Finds two signatures with the same r, and so the same k, and gets the private key from them
+fn recover_x(quads: &[Quad], params: &Params, y: &BigInt) -> Option<BigInt> {
     // Read all data, now find two with the same nonce k
+    let mut pairs: Vec<Vec<Quad>> = vec![];
     for (i, qi) in quads.iter().enumerate() {
         // Skip means we don't find all pairs twice
-        for (j, qj) in quads.iter().skip(i).enumerate() {
-            if i != j && qi.r == qj.r && qi.message != qj.message {
+        for qj in quads.iter().skip(i + 1) {
+            if qi.r == qj.r && qi.message != qj.message {
                 pairs.push(vec![qi.clone(), qj.clone()]);
             }
         }
     }
 
     // For each pair, find the k
-    for p in pairs {
-        println!("Pair: {p:?}");
-        //let m1 = BigInt::from_bytes_be(Sign::Plus, p[0].message.as_bytes());
-        //let m2 = BigInt::from_bytes_be(Sign::Plus, p[1].message.as_bytes());
+    pairs.into_iter().find_map(|p| {
+        say!("Pair: {p:?}");
         let mut mdiff = (&p[0].m - &p[1].m) % &params.q;
         while mdiff < 0.into() {
             mdiff += &params.q;
@@ -113,34 +170,18 @@ pub fn main() -> Result<()> {
         while sdiff < 0.into() {
             sdiff += &params.q;
         }
-        let sdiffinv = invmod(&sdiff, &params.q);
+        let sdiffinv = try_invmod(&sdiff, &params.q)?;
         let k = (mdiff * sdiffinv) % &params.q;
-        println!("k: {k}");
-        // Check that r is indeed the same
-
-        let r = params.g.modpow(&k, &params.p) % &params.q;
-        println!("r derived = {r}");
-        println!("r true = {}", p[1].r);
+        say!("k: {k}");
 
         // Now get private key from this k again
         let sig = Sig {
             s: p[0].s.clone(),
             r: p[0].r.clone(),
         };
-
-        let x = get_x_from_k(&sig, &k, &params, p[0].message.as_bytes());
-        println!("x: {x}");
-        let derived_y = params.g.modpow(&x, &params.p);
-        assert_eq!(derived_y, y);
-
-        let x_str = x.to_str_radix(16);
-        let fingerprint = sha1(x_str.as_bytes());
-        let fingerprint_hex = bytes_to_hex(&fingerprint);
-        println!("Fingerprint: {fingerprint_hex}");
-
-        assert_eq!(fingerprint_hex, "ca8f6f7c66fa362d40760d135b763eb8527d3d52");
-    }
-    Ok(())
+        let x = get_x_from_k(&sig, &k, params, p[0].message.as_bytes());
+        (params.g.modpow(&x, &params.p) == *y).then_some(x)
+    })
 }
 
 #[cfg(test)]
@@ -149,6 +190,6 @@ mod tests {
 
     #[test]
     fn find_private_key() {
-        main().unwrap();
+        main().and_then(Report::verify).unwrap();
     }
 }
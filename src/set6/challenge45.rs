@@ -21,21 +21,26 @@
 //!         z
 //! Sign "Hello, world". And "Goodbye, world".
 
+use anyhow::anyhow;
 use num_bigint::{BigInt, RandBigInt, Sign};
 use openssl::sha::sha1;
-use rand::thread_rng;
+use rand::Rng;
 
+use crate::report::say;
 use crate::{
-    set6::challenge43::{sign, verify, Params, Sig},
+    dsa::{sign, verify, Params, Sig},
     utils::*,
 };
-pub fn sign_broken(private_key: &BigInt, params: &Params, message: &[u8]) -> Sig {
+pub fn sign_broken(
+    private_key: &BigInt,
+    params: &Params,
+    message: &[u8],
+    rng: &mut impl Rng,
+) -> Sig {
     let Params { q, p, g } = params;
-
-    let mut rng = thread_rng();
     let h: BigInt = BigInt::from_bytes_be(Sign::Plus, &sha1(message));
     let k = rng.gen_bigint_range(&1.into(), q);
-    let kinv = invmod(&k, q);
+    let kinv = try_invmod(&k, q).unwrap();
     let two: BigInt = 2.into();
     let qm2 = q - &two;
     let kinvprime = k.modpow(&qm2, q);
@@ -47,44 +52,49 @@ pub fn sign_broken(private_key: &BigInt, params: &Params, message: &[u8]) -> Sig
     Sig { r, s }
 }
 
-pub fn main() -> Result<()> {
+pub fn main() -> Result<Report> {
+    let mut rng = crate::utils::rng();
+    let params = Params::from_options(&mut rng)?;
+    if params != Params::default() {
+        say!("Using new parameters: {params:?}");
+    }
+    let good_params = params.clone();
     let params = Params {
         g: 0.into(),
-        ..Params::default()
+        ..params
     };
 
     // Generate private and public keys
-    let mut rng = thread_rng();
 
     let x = rng.gen_bigint_range(&0.into(), &params.q);
     let y = params.g.modpow(&x, &params.p);
 
-    println!("x: {x}");
+    say!("x: {x}");
     let message = b"test message";
     // r = (y**z) mod p mod q
     // When g is 0, y = g**x mod p = 1
     // r=0
-    println!("Producing signature");
+    say!("Producing signature");
     // We had to go in and remove the r!=0 to make this work
-    let sig = sign_broken(&y, &params, message);
+    let sig = sign_broken(&y, &params, message, &mut rng);
 
-    println!("Signature: {:?}", sig);
+    say!("Signature: {:?}", sig);
     //  Deduced values:
     //  s = h/k when r=0;
     //  r = g**k mod p
     //  => k = h/s % q
     //  Let's deduce k, and then decude the private key
     let h = BigInt::from_bytes_be(Sign::Plus, &sha1(message));
-    let sinv = invmod(&sig.s, &params.q);
+    let sinv = try_invmod(&sig.s, &params.q).ok_or_else(|| anyhow!("s has no inverse mod q"))?;
     let k = (&h * &sinv) % &params.q;
     // r will be the same, and s for an abitrary message is h/k;
-    let kinv = invmod(&k, &params.q);
+    let kinv = try_invmod(&k, &params.q).ok_or_else(|| anyhow!("k has no inverse mod q"))?;
     let forged_sig = Sig {
         r: 0.into(),
         s: (&h * &kinv) % &params.q,
     };
 
-    println!("Forged sig: {:?}", forged_sig);
+    say!("Forged sig: {:?}", forged_sig);
 
     // This forged signature is invalid because verify checks if r is 0!
     // In that sense, the original signature is also invalid
@@ -92,7 +102,7 @@ pub fn main() -> Result<()> {
 
     // Let's try the other attack, g=p+1
     // The idea here is that g gets modified in the *memory of the verifier*
-    let mut params = Params::default();
+    let mut params = good_params;
     let y = params.g.modpow(&x, &params.p);
     // y gets generated, now g gets changed
 
@@ -108,31 +118,32 @@ pub fn main() -> Result<()> {
     // r = y**z mod q
     // for arbitrary z
 
-    println!("=====");
-    let sig = sign(&x, &params, b"any message");
-    println!("sig: {:?}", sig);
+    say!("=====");
+    let sig = sign(&x, &params, b"any message", &mut rng);
+    say!("sig: {:?}", sig);
     let verified = verify(&y, &params, b"any message", &sig);
-    println!("Verified: {:?}", verified);
-    println!("=====");
+    say!("Verified: {:?}", verified);
+    say!("=====");
 
     // This signature will valid literally anything
     let z: BigInt = rng.gen_bigint_range(&1.into(), &params.q);
     let r = y.modpow(&z, &params.p) % &params.q;
-    let s = &r * invmod(&z, &params.q) % &params.q;
+    let s = &r * try_invmod(&z, &params.q).unwrap() % &params.q;
     let sig = Sig { r, s };
 
     // Sig should now verify anything!
-    println!("sig: {:?}", sig);
-    let verified = verify(&y, &params, b"Hello, world!", &sig);
-    assert_eq!(verified, Auth::Valid);
-
-    println!("Verified: {:?}", verified);
-
-    let verified = verify(&y, &params, b"Goodbye, world!", &sig);
-    println!("Verified: {:?}", verified);
-    assert_eq!(verified, Auth::Valid);
-
-    Ok(())
+    say!("sig: {:?}", sig);
+    let hello = verify(&y, &params, b"Hello, world!", &sig);
+    say!("Verified: {:?}", hello);
+
+    let goodbye = verify(&y, &params, b"Goodbye, world!", &sig);
+    say!("Verified: {:?}", goodbye);
+
+    Ok(Report::new()
+        .recovered("magic r", sig.r)
+        .recovered("magic s", sig.s)
+        .forged("Hello, world!", hello == Auth::Valid)
+        .forged("Goodbye, world!", goodbye == Auth::Valid))
 }
 
 #[cfg(test)]
@@ -140,6 +151,6 @@ mod tests {
     use super::*;
     #[test]
     fn forging() {
-        main().unwrap();
+        main().and_then(Report::verify).unwrap();
     }
 }
@@ -43,121 +43,122 @@
 //! Decrypt the string (after encrypting it to a hidden private key) above.
 
 use num_bigint::{BigInt, Sign};
+use num_integer::Integer;
+use num_traits::{One, Zero};
 
+use crate::progress;
+use crate::report::say;
+use crate::rsa::{Key, KeyPair, Params};
 use crate::utils::*;
 
-#[derive(Clone, Debug)]
-pub struct Key {
-    pub key: BigInt,
-    pub modulus: BigInt,
+/// Anything which can say whether a ciphertext's plaintext is even, and nothing more
+pub trait ParityOracle {
+    fn public_key(&self) -> &Key;
+    fn is_even(&self, ciphertext: &BigInt) -> bool;
 }
 
-enum Parity {
-    Even,
-    Odd,
+/// The server, which has the private key and checks the parity of whatever it's sent
+pub struct Server {
+    public_key: Key,
+    private_key: Key,
 }
 
-fn parity_oracle(ciphertext_num: &BigInt, private_key: &Key) -> Parity {
-    let plaintext = ciphertext_num.modpow(&private_key.key, &private_key.modulus);
-
-    let zero: BigInt = 0.into();
-    let two: BigInt = 2.into();
-    match (plaintext % two) == zero {
-        true => Parity::Even,
-        false => Parity::Odd,
+impl Server {
+    pub fn new(KeyPair { public, private }: KeyPair) -> Self {
+        Self {
+            public_key: public,
+            private_key: private,
+        }
     }
 }
-#[derive(Debug)]
-struct Range {
-    pub lower: BigInt,
-    pub upper: BigInt,
-}
-fn rsa(key: &Key, number: &BigInt) -> BigInt {
-    number.modpow(&key.key, &key.modulus)
-}
 
-pub fn main() -> Result<()> {
-    let bits = 512;
-    let e: BigInt = 3.into();
-    let (et, n) = et_n(bits, &e);
-    let d = invmod(&e, &et);
+impl ParityOracle for Server {
+    fn public_key(&self) -> &Key {
+        &self.public_key
+    }
+
+    fn is_even(&self, ciphertext: &BigInt) -> bool {
+        self.private_key.apply(ciphertext).is_even()
+    }
+}
 
-    let public_key = Key {
-        key: e,
-        modulus: n.clone(),
-    };
-    let private_key = Key { key: d, modulus: n };
+pub fn main() -> Result<Report> {
+    let params = Params::from_options(1024, 3)?;
+    say!("Using a {params}");
+    let server = Server::new(params.keypair(&mut crate::utils::rng()));
+    let public_key = server.public_key().clone();
 
     let secret_b64 = "VGhhdCdzIHdoeSBJIGZvdW5kIHlvdSBkb24ndCBwbGF5IGFyb3VuZCB3aXRoIHRoZSBGdW5reSBDb2xkIE1lZGluYQ==";
     let secret = decode_b64_str(secret_b64).unwrap();
     let secret_num = BigInt::from_bytes_be(Sign::Plus, &secret);
 
     // Ciphertext is encrypted with the public key
-    let ciphertext = rsa(&public_key, &secret_num);
-
-    // All the work is in the deduce function, which only uses the private key to pass to the oracle
-    let de = deduce(&ciphertext, &public_key, &private_key);
-    println!("Secret number: {}", secret_num);
-    println!("Deduce number: {}", de);
-    assert_eq!(secret_num, de);
+    let ciphertext = public_key.apply(&secret_num);
+
+    // Hollywood style: the upper bound, as text, after every step
+    let pb = progress::counter(public_key.modulus.bits());
+    let start = std::time::Instant::now();
+    let mut queries = 0;
+    let de = deduce(&server, &ciphertext, |upper| {
+        queries += 1;
+        pb.inc(1);
+        pb.set_message(printable(&upper.to_bytes_be().1));
+    });
+    pb.finish();
+    say!("Deduced in {:.2?}", start.elapsed());
+    say!("Secret number: {}", secret_num);
+    say!("Deduce number: {}", de);
 
     // We now have the secret, and simply have to convert it back to a str
     let secret_deduced = de.to_bytes_be().1;
-    println!(
-        "Deduced secret: {}",
-        std::str::from_utf8(&secret_deduced).unwrap()
-    );
+    say!("Deduced secret: {}", String::from_utf8_lossy(&secret_deduced));
+
+    Ok(Report::new()
+        .check("secret", secret, secret_deduced)
+        .count("queries", queries))
+}
 
-    Ok(())
+/// Text as it stands, with a dot for anything which isn't
+fn printable(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .map(|b| match b.is_ascii_graphic() || *b == b' ' {
+            true => *b as char,
+            false => '.',
+        })
+        .collect()
 }
 
-fn deduce(ciphertext: &BigInt, public_key: &Key, private_key: &Key) -> BigInt {
-    // This is an exclusive range
-    let mut range = Range {
-        lower: 0.into(),
-        upper: public_key.modulus.clone(),
-    };
+/// Decrypts `ciphertext` a bit at a time, by doubling its plaintext and asking the oracle
+///
+/// With n odd, 2m mod n is even if 2m < n, and odd if it wrapped around, which says which half of
+/// [0, n) m is in. 4m says which quarter, and so on, so that after i steps
+/// k n / 2**i <= m < (k + 1) n / 2**i
+/// where k is the answers so far, as bits (1 for odd). Keeping k and i, rather than the bounds
+/// themselves, keeps it all exact. `progress` gets the upper bound after each step.
+pub fn deduce<O: ParityOracle>(
+    oracle: &O,
+    ciphertext: &BigInt,
+    mut progress: impl FnMut(&BigInt),
+) -> BigInt {
+    let Key { key: e, modulus: n } = oracle.public_key();
+    let doubler = BigInt::from(2).modpow(e, n);
     let mut running_ciphertext = ciphertext.clone();
-    let two: BigInt = 2.into();
-    let multiplier = two.modpow(&public_key.key, &public_key.modulus);
-    let mut range_multiplier: BigInt = 1.into();
-    let mut running: BigInt = 1.into();
-    let one: BigInt = 1.into();
-
-    // Explanation
-    //
-    // Each additional step gets us one more digit of accuracy in the binary
-    // fractional representation of the secret number
-    // The first step puts the unknown quantity either in (0,floor(p/2)) or (floor(p/2)+1,p)
-    // After n steps, the window is of a size p / 2**n below the upper bound
-    // If the answer was in the upper half, the upper bound stays the same, so
-    // j/2**n -> 2j/2**(n+1)
-    // If the answer was in the lower half, the upper bound decreases by half the accuracy window
-    // i.e j/2**n -> 2j-1 / 2**(n+1)
-
-    while &range.upper - &range.lower != one {
-        running_ciphertext *= &multiplier;
-        running_ciphertext %= &public_key.modulus;
-        range_multiplier *= &two;
-
-        match parity_oracle(&running_ciphertext, private_key) {
-            // Lower half i.e. < midpoint
-            Parity::Even => {
-                running *= &two;
-                running -= &one;
-                // Upper end of range is fraction we are bounded above by * modulus + 1 (for ceil)
-                range.upper = 1 + (&running * &public_key.modulus) / &range_multiplier;
-            }
-
-            // Upper half
-            Parity::Odd => {
-                running *= &two;
-                // Lower end of range is upper - size of accuracy window - 1 (for floor)
-                range.lower = &range.upper - 1 - &public_key.modulus / &range_multiplier;
-            }
+    let mut k = BigInt::zero();
+
+    for i in 1..=n.bits() {
+        running_ciphertext *= &doubler;
+        running_ciphertext %= n;
+        k <<= 1;
+        if !oracle.is_even(&running_ciphertext) {
+            k += 1;
         }
+        progress(&(((&k + 1) * n) >> i));
     }
-    range.lower
+
+    // The bounds are now less than 1 apart, and only whole numbers at 0 (n being odd), so the
+    // lower one rounded up is m
+    (k * n).div_ceil(&(BigInt::one() << n.bits()))
 }
 
 #[cfg(test)]
@@ -170,7 +171,7 @@ mod tests {
         let p: BigInt = 17.into();
         let q: BigInt = 11.into();
         let et = (&p - 1) * (&q - 1);
-        let d = invmod(&e, &et);
+        let d = try_invmod(&e, &et).unwrap();
         let n = &p * &q;
         // n = 187
 
@@ -178,18 +179,32 @@ mod tests {
             key: e,
             modulus: n.clone(),
         };
-        let private_key = Key {
-            key: d,
-            modulus: n,
-        };
+        let private_key = Key { key: d, modulus: n };
+        let server = Server::new(KeyPair {
+            public: public_key.clone(),
+            private: private_key,
+        });
         for secret_num in 0..187 {
             let secret_num: BigInt = secret_num.into();
             // Encrypt secret
-            let ciphertext = rsa(&public_key, &secret_num);
-            let deduced = deduce(&ciphertext, &public_key, &private_key);
+            let ciphertext = public_key.apply(&secret_num);
+            let mut steps = 0;
+            let deduced = deduce(&server, &ciphertext, |upper| {
+                steps += 1;
+                assert!(upper >= &secret_num);
+            });
             println!("Secret num:  {}", secret_num);
             println!("Deduced num: {}", deduced);
             assert_eq!(secret_num, deduced);
+            assert_eq!(steps, 8);
         }
     }
+
+    #[test]
+    fn full_size() {
+        let server = Server::new(Params::new(512, 65537).keypair(&mut rand::thread_rng()));
+        let m = BigInt::from_bytes_be(Sign::Plus, b"Funky Cold Medina");
+        let c = server.public_key().apply(&m);
+        assert_eq!(deduce(&server, &c, |_| {}), m);
+    }
 }
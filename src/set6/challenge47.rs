@@ -52,137 +52,164 @@
 //! We recommend you just use the raw math from paper (check, check, double check your translation
 //! to code) and not spend too much time trying to grok how the math works.
 #![allow(non_snake_case)]
-use std::collections::BTreeSet;
-use std::ops::Bound::Included;
-use std::ops::Mul;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Instant;
 
+use anyhow::anyhow;
 use num_bigint::{BigInt, RandBigInt, Sign};
 use num_integer::Integer;
-use num_traits::{FromPrimitive, Zero};
-use rand::thread_rng;
+use num_traits::One;
+use rand::Rng;
+use rayon::prelude::*;
+use thiserror::Error;
 
+use crate::report::say;
 use crate::utils::*;
-use indicatif::{ProgressBar, ProgressStyle};
 
-use super::challenge46::Key;
+use crate::progress::{self, Progress};
+use crate::rsa::{Key, KeyPair, Params};
 
-// Need some sort of intervals struct
-#[derive(Debug, PartialEq, Clone)]
-struct Interval {
-    start: BigInt,
-    end: BigInt,
+/// Fewest padding bytes PKCS#1 allows
+const MIN_PADDING: usize = 8;
+
+/// How much of PKCS#1 v1.5 encryption padding to check when taking it off
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pkcs1Mode {
+    /// 00 02, at least eight nonzero padding bytes, then a 00 to mark the end of them
+    Strict,
+    /// Just the 00 02 at the front, like the oracle the challenge describes
+    Lenient,
 }
 
-impl Interval {
-    pub fn new(start: &BigInt, end: &BigInt) -> Self {
-        Self {
-            start: start.clone(),
-            end: end.clone(),
+impl FromStr for Pkcs1Mode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "strict" => Ok(Self::Strict),
+            "lenient" => Ok(Self::Lenient),
+            _ => Err(anyhow!(
+                "Unknown padding check {s}, expected strict or lenient"
+            )),
         }
     }
 }
 
-// Represents series of disjoint intervals
-#[derive(Default)]
-struct IntervalTree {
-    lefts: BTreeSet<BigInt>,
-    rights: BTreeSet<BigInt>,
+impl fmt::Display for Pkcs1Mode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Strict => write!(f, "strict"),
+            Self::Lenient => write!(f, "lenient"),
+        }
+    }
 }
 
-impl IntervalTree {
-    pub fn get_intervals(&self) -> Vec<Interval> {
-        // Intervals are disjoint, so the ordering is the same
-        self.lefts
-            .iter()
-            .zip(self.rights.iter())
-            .map(|(x, y)| Interval {
-                start: x.clone(),
-                end: y.clone(),
-            })
-            .collect()
-    }
-
-    pub fn insert_interval(&mut self, interval: &Interval) {
-        // There are four cases to consider:
-        // 1. Interval is disjoint
-        // 2. Interval overlaps one set on the left
-        // 3. Interval overlaps one set on the right
-        // 4. Interval joins two intervals
-
-        // How does this play out? We start by taking lefts and rights and doing "split_off"
-        // Imagine our intervals are (4,8) (11,13) (20,25)
-        // And we wish to insert (x,y)
-        // Our two BTreeSets are [4,11,20], [8,13,25]
-        // We can find the elements which are included in the range defined by this
-        //let left_pt: BigInt = &interval.start - 1;
-        //let right_pt: BigInt = &interval.end + 1;
-
-        // Count how many left points are inside interval
-        let left_number = self
-            .lefts
-            .range((
-                Included(&(interval.start.clone())),
-                Included(&(interval.end.clone())),
-            ))
-            .count();
-        let right_number = self
-            .rights
-            .range((
-                Included(&(interval.start.clone())),
-                Included(&(interval.end.clone())),
-            ))
-            .count();
-
-        // There are three options here:
-        // 1. They are equal, in which case our interval completely encompasses them and we can
-        //    delete all of them and insert our new interval markers
-        // 2. L = R+1, which means that we can delete all the lefts, and all of the rights, but
-        //    only insert the leftmost point
-        //    [ () () (  ]   )
-        // 3. L+1 = R, which means that we can delete all the rights and all the lefts in the
-        //    interval, and only insert the rightmost point
-        // All of these cases delete all of them, so lets do that!
-        let mut left_split = self
-            .lefts
-            .split_off(&interval.start)
-            .split_off(&interval.end);
-        self.lefts.append(&mut left_split);
-
-        let mut left_split = self
-            .rights
-            .split_off(&interval.start)
-            .split_off(&interval.end);
-        self.rights.append(&mut left_split);
-
-        // Now add the points back in which ought to be there
-        match left_number == right_number {
-            true => {
-                self.lefts.insert(interval.start.clone());
-                self.rights.insert(interval.end.clone());
-            }
-            false => match left_number < right_number {
-                true => {
-                    self.rights.insert(interval.end.clone());
-                }
-                false => {
-                    self.lefts.insert(interval.start.clone());
-                }
-            },
-        }
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum Pkcs1Error {
+    #[error("{length}-byte message is too long to pad for a {size}-byte key")]
+    TooLong { length: usize, size: usize },
+    #[error("Padding error")]
+    InvalidPadding,
+}
+
+/// Pads `message` for encryption with a key of `size` bytes:
+/// 00h 02h, random nonzero bytes, 00h, message
+pub fn pkcs1v15_pad<R: Rng>(
+    message: &[u8],
+    size: usize,
+    rng: &mut R,
+) -> Result<Vec<u8>, Pkcs1Error> {
+    if size < message.len() + MIN_PADDING + 3 {
+        return Err(Pkcs1Error::TooLong {
+            length: message.len(),
+            size,
+        });
+    }
+    let mut padded = vec![0x00, 0x02];
+    padded.extend((3 + message.len()..size).map(|_| rng.gen_range(1..=0xff_u8)));
+    padded.push(0x00);
+    padded.extend_from_slice(message);
+    Ok(padded)
+}
 
-        // Finally, we do a quick check to "fuse" intervals
-        // (    )[    ](     )
-        // ->
-        // (                 )
-        // or any combination thereof
-        let left_pt: BigInt = &interval.start - 1;
-        if self.rights.remove(&left_pt) {
-            self.lefts.remove(&interval.start);
+/// Takes the padding off a decrypted block, as long as it passes `mode`'s checks
+///
+/// Leniently, anything starting 00 02 will do, and the message is whatever follows the first 00
+/// after that, or nothing if there isn't one.
+pub fn pkcs1v15_unpad(block: &[u8], mode: Pkcs1Mode) -> Result<Vec<u8>, Pkcs1Error> {
+    let Some(padding) = block.strip_prefix(&[0x00, 0x02]) else {
+        return Err(Pkcs1Error::InvalidPadding);
+    };
+    let end = padding.iter().position(|b| *b == 0x00);
+    match (mode, end) {
+        (Pkcs1Mode::Strict, Some(end)) if end >= MIN_PADDING => Ok(padding[end + 1..].to_vec()),
+        (Pkcs1Mode::Strict, _) => Err(Pkcs1Error::InvalidPadding),
+        (Pkcs1Mode::Lenient, Some(end)) => Ok(padding[end + 1..].to_vec()),
+        (Pkcs1Mode::Lenient, None) => Ok(vec![]),
+    }
+}
+
+// What does this padding mean for the plaintext?
+// k is number of bytes, hence
+// 0x02,0x00,...,0x00,0x00
+// ->
+// 0x02,0xff,...,0xff,0x00
+//
+// The loose bound here is 2**(8(k-2)), all the way through to 2**(8k) - 1
+// This is 2B -> 3B-1
+// Whenever we have a valid plaintext, it must be in this interval
+
+/// B = 2**(8(k-2)), for a k-byte modulus
+pub fn bound(key: &Key) -> BigInt {
+    BigInt::one() << (8 * (key.size() - 2))
+}
+
+/// The server: decrypts whatever it's sent and says whether the padding is right, and nothing else
+///
+/// How much it checks is up to its [`Pkcs1Mode`]: the less it checks, the more often it says yes,
+/// and the more it gives away. It counts how often it's asked, that being the measure of how well
+/// the attack does.
+pub struct PaddingOracle {
+    public_key: Key,
+    private_key: Key,
+    mode: Pkcs1Mode,
+    // 2B and 3B, so that checking for 00 02 is just comparing, not converting to bytes
+    two_b: BigInt,
+    three_b: BigInt,
+    calls: AtomicU64,
+}
+
+impl PaddingOracle {
+    pub fn new(KeyPair { public, private }: KeyPair, mode: Pkcs1Mode) -> Self {
+        let B = bound(&public);
+        Self {
+            two_b: 2 * &B,
+            three_b: 3 * B,
+            public_key: public,
+            private_key: private,
+            mode,
+            calls: AtomicU64::new(0),
         }
-        let right_pt: BigInt = &interval.end + 1;
-        if self.lefts.remove(&right_pt) {
-            self.rights.remove(&interval.end);
+    }
+
+    pub fn public_key(&self) -> &Key {
+        &self.public_key
+    }
+
+    pub fn calls(&self) -> u64 {
+        self.calls.load(Ordering::Relaxed)
+    }
+
+    pub fn query(&self, c: &BigInt) -> bool {
+        self.calls.fetch_add(1, Ordering::Relaxed);
+        let m = self.private_key.apply(c);
+        // Almost everything falls at the first hurdle, so only convert what gets past it
+        if m < self.two_b || m >= self.three_b {
+            return false;
         }
+        pkcs1v15_unpad(&self.private_key.to_block(&m), self.mode).is_ok()
     }
 }
 
@@ -198,177 +225,290 @@ enum Step {
     Step4,
 }
 
-pub struct Attacker {
+/// Oracle calls between progress bar updates
+const REPORT_EVERY: u64 = 1 << 10;
+/// Biggest denominator t to try trimming with
+const MAX_T: u64 = 250;
+/// Biggest t' to combine the trimmers' denominators into
+const MAX_LCM: u64 = 1 << 12;
+
+/// Later improvements to the attack, all off unless asked for with `--arg trim=true` and
+/// `--arg batch=` some number
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Improvements {
+    /// Narrow down [2B, 3B) with trimmers before searching for any s, as in Bardou et al., "Efficient
+    /// Padding Oracle Attacks on Cryptographic Hardware" (2012)
+    pub trim: bool,
+    /// How many s to send to the oracle at once, in parallel: more calls, but less waiting
+    pub batch: usize,
+}
+
+impl Default for Improvements {
+    fn default() -> Self {
+        Self {
+            trim: false,
+            batch: 1,
+        }
+    }
+}
+
+impl Improvements {
+    pub fn from_options() -> Result<Self> {
+        let options = crate::options::get();
+        let improvements = Self {
+            trim: options.arg("trim")?.unwrap_or(false),
+            batch: options.arg("batch")?.unwrap_or(1),
+        };
+        if improvements.batch == 0 {
+            return Err(anyhow!("Can't search for s in batches of 0"));
+        }
+        Ok(improvements)
+    }
+}
+
+impl fmt::Display for Improvements {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.trim {
+            true => write!(f, "trimmed")?,
+            false => write!(f, "untrimmed")?,
+        }
+        match self.batch {
+            1 => write!(f, ", one s at a time"),
+            n => write!(f, ", {n} s at a time"),
+        }
+    }
+}
+
+pub struct Attacker<'a> {
     intervals: IntervalTree,
     c0: BigInt,
     s0: BigInt,
     s: BigInt,
+    // c0 * s**e for the latest s, multiplied and reduced in place
+    blinded: BigInt,
+    oracle: &'a PaddingOracle,
+    improvements: Improvements,
     publickey: Key,
-    privatekey: Key,
     b: BigInt,
     state: Step,
     c: BigInt,
+    progress: Box<dyn Progress>,
 }
 
-// What does this padding mean for the plaintext?
-// k is number of bytes, hence
-// 0x02,0x00,...,0x00,0x00
-// ->
-// 0x02,0xff,...,0xff,0x00
-//
-// The loose bound here is 2**(8(k-2)), all the way through to 2**(8k) - 1
-// This is 2B -> 3B-1
-// Whenever we have a valid plaintext, it must be in this interval
-//
-//
+impl<'a> Attacker<'a> {
+    pub fn new(c: &BigInt, oracle: &'a PaddingOracle, improvements: Improvements) -> Self {
+        let publickey = oracle.public_key().clone();
+        let b = bound(&publickey);
 
-pub fn is_pkcs(c: &BigInt, private_key: &Key) -> bool {
-    // First decrypt with the private key
-    let c_decrypted = c.modpow(&private_key.key, &private_key.modulus);
-
-    let cb = c_decrypted.to_bytes_be().1;
-    let mut k = private_key.modulus.bits() as usize;
-    k = num_integer::Integer::div_ceil(&k, &8);
-    if cb.len() != k - 1 {
-        return false;
-    }
-    cb[0] == 0x02
-    /*
-    let mut p_bytes = vec![0x00];
-    p_bytes.extend_from_slice(&c_decrypted.to_bytes_be().1);
-    for (i, b) in p_bytes.iter().enumerate() {
-        if i == 0 && *b != 0x00 {
-            return false;
-        }
-        if i == 1 && *b != 0x02 {
-            return false;
-        }
-        if i > 1 && i <= 9 && *b == 0x00 {
-            return false;
-        }
-        if i > 9 && *b == 0x00 {
-            return true;
-        }
-    }
-    false
-    */
-}
+        let intervals = Self::conforming(&b);
 
-impl Attacker {
-    pub fn new(c: &BigInt, public_key: &Key, private_key: &Key) -> Self {
-        let mut intervals = IntervalTree::default();
-        let c0 = BigInt::zero();
-        let k: u32 = private_key.modulus.bits() as u32 / 8;
-        let b = BigInt::from_u8(2).unwrap().pow(8 * (k - 2));
-        let c = c.clone();
-        let publickey: Key = public_key.clone();
-        let privatekey: Key = private_key.clone();
-        let state = Step::Step1;
-
-        let twob: BigInt = 2 * &b;
-        let one: BigInt = 1.into();
-        let tbm1: BigInt = 3 * &b - one;
-
-        let interval = Interval::new(&twob, &tbm1);
-        intervals.insert_interval(&interval);
-        let s: BigInt = BigInt::zero();
-        let s0: BigInt = BigInt::zero();
+        let progress = progress::bar(b.bits());
 
         Self {
-            s,
-            s0,
+            s: BigInt::default(),
+            s0: BigInt::default(),
+            c0: BigInt::default(),
+            blinded: BigInt::default(),
             intervals,
-            c0,
+            oracle,
+            improvements,
             publickey,
-            privatekey,
             b,
-            state,
-            c,
+            state: Step::Step1,
+            c: c.clone(),
+            progress,
         }
     }
 
-    pub fn run(&mut self) -> BigInt {
-        let pb = ProgressBar::new(self.b.bits());
-        pb.set_message("Searching for plaintext");
-        pb.set_style(
-            ProgressStyle::with_template(
-                "[{elapsed_precise}] {bar:40.cyan/blue} {pos:>7}/{len:7} {msg}",
-            )
-            .unwrap()
-            .progress_chars("##-"),
-        );
+    /// [2B, 3B-1], where every PKCS plaintext is
+    fn conforming(B: &BigInt) -> IntervalTree {
+        let mut intervals = IntervalTree::default();
+        let twob: BigInt = 2 * B;
+        let tbm1: BigInt = 3 * B - 1;
+        intervals.insert_interval(&Interval::new(&twob, &tbm1));
+        intervals
+    }
 
+    pub fn run(&mut self, rng: &mut impl Rng) -> BigInt {
         loop {
-            if self.intervals.get_intervals().len() == 1 {
-                let Interval { start: a, end: b } = self.intervals.get_intervals()[0].clone();
-                // Print size of interval just to check it's getting smaller
-                pb.set_message(format!("Size of interval: {}", (&b - &a).bits()));
-                pb.set_position(self.b.bits() - (&b - &a).bits());
-            }
-            //println!("State: {:?}", self.state);
             match self.state {
-                Step::Step1 => self.step1(),
+                Step::Step1 => self.step1(rng),
                 Step::Step2a => self.step2a(),
                 Step::Step2b => self.step2b(),
                 Step::Step2c => self.step2c(),
                 Step::Step3 => self.step3(),
                 Step::Step4 => {
-                    pb.finish();
+                    self.progress.finish();
                     return self.step4();
                 }
             }
         }
     }
+
+    /// Shows how many bits of the plaintext are pinned down, and how many calls it's taken
+    fn report(&self) {
+        let width = self
+            .intervals
+            .iter()
+            .map(|(a, b)| (b - a).bits())
+            .max()
+            .unwrap_or_default();
+        self.progress.set_position(self.b.bits() - width);
+        self.progress.set_message(format!(
+            "{} intervals, {} oracle calls",
+            self.intervals.len(),
+            self.oracle.calls()
+        ));
+    }
+
     // Blinding: we choose to skip this for a PKCS valid input,
     // but it basically hides our input in a way that we can undo
-    fn step1(&mut self) {
-        let mut rng = thread_rng();
+    fn step1(&mut self, rng: &mut impl Rng) {
         // Start with
         self.s = 1.into();
         loop {
-            self.c0 = self
-                .c
-                .clone()
-                .mul(self.s.modpow(&self.publickey.key, &self.publickey.modulus))
-                .mod_floor(&self.publickey.modulus);
-            if is_pkcs(&self.c0, &self.privatekey) {
+            self.c0.clone_from(&self.c);
+            if self.try_si() {
+                self.c0.clone_from(&self.blinded);
                 self.s0.clone_from(&self.s);
                 break;
             }
-            self.s = rng.gen_bigint_range(&BigInt::zero(), &self.publickey.modulus);
+            self.s = rng.gen_bigint_range(&BigInt::one(), &self.publickey.modulus);
         }
 
+        if self.improvements.trim {
+            self.trim();
+        }
         self.state = Step::Step2a;
     }
+
+    // Trimming: if m0 * u/t is PKCS for some small u and t (which needs t to divide m0), then
+    // 2B <= m0 * u/t < 3B, so
+    // 2B * t/u <= m0 < 3B * t/u
+    // which is more than we knew before. Trying u = t - 1 and t + 1 for each t up to MAX_T turns
+    // up a few such t.
+    //
+    // Their lcm t' divides m0 too, and then m0 * u/t' is PKCS for every u between some u_min and
+    // u_max, so a binary search on each side finds them, and
+    // 2B * t'/u_min <= m0 < 3B * t'/u_max
+    fn trim(&mut self) {
+        let n = self.publickey.modulus.clone();
+        let fraction = |u: u64, t: u64| (try_invmod(&BigInt::from(t), &n).unwrap() * u) % &n;
+
+        let trimmers: Vec<(u64, u64)> =
+            (3..=MAX_T).flat_map(|t| [(t - 1, t), (t + 1, t)]).collect();
+        let fractions: Vec<BigInt> = trimmers.iter().map(|(u, t)| fraction(*u, *t)).collect();
+        let t = std::iter::zip(&trimmers, self.query_all(&fractions))
+            .filter(|(_, ok)| *ok)
+            .map(|((_, t), _)| *t)
+            .fold(1, |lcm, t| match lcm.lcm(&t) {
+                l if l <= MAX_LCM => l,
+                _ => lcm,
+            });
+        if t == 1 {
+            return;
+        }
+
+        // The last u which works, going from t' towards `limit`
+        let last = |limit: u64| {
+            let (mut good, mut bad) = (t, limit);
+            while good.abs_diff(bad) > 1 {
+                let u = (good + bad) / 2;
+                match self.try_s(&fraction(u, t)) {
+                    true => good = u,
+                    false => bad = u,
+                }
+            }
+            good
+        };
+        // m0 * u/t' leaves [2B, 3B) by u = 2t'/3 and 3t'/2 at the latest
+        let u_min = last(2 * t / 3);
+        let u_max = last(3 * t / 2 + 1);
+
+        let B = &self.b;
+        let (two_bt, three_bt): (BigInt, BigInt) = (2 * B * t, 3 * B * t);
+        let a = two_bt.div_ceil(&u_min.into());
+        let b = three_bt.div_ceil(&u_max.into()) - 1;
+        let mut trimmed = IntervalTree::default();
+        trimmed.insert_interval(&Interval { start: a, end: b });
+        self.intervals = self.intervals.intersect(&trimmed);
+        self.report();
+    }
+
+    /// Asks about c0 * s**e for another s, leaving self.s alone
+    fn try_s(&self, s: &BigInt) -> bool {
+        let n = &self.publickey.modulus;
+        self.oracle.query(&(&self.c0 * self.publickey.apply(s) % n))
+    }
+
+    /// Asks about each of `candidates`, all at once if searching in batches
+    fn query_all(&self, candidates: &[BigInt]) -> Vec<bool> {
+        match self.improvements.batch {
+            1 => candidates.iter().map(|s| self.try_s(s)).collect(),
+            _ => candidates.par_iter().map(|s| self.try_s(s)).collect(),
+        }
+    }
+
     // Consults the oracle in a way we use more than once
-    fn try_si(&self) -> bool {
+    fn try_si(&mut self) -> bool {
         // (c0 *(s)**e) mod n
-        let c = (&self.c0 * &self.s.modpow(&self.publickey.key, &self.publickey.modulus))
-            % &self.publickey.modulus;
-        is_pkcs(&c, &self.privatekey)
+        self.blinded = self.publickey.apply(&self.s);
+        self.blinded *= &self.c0;
+        self.blinded %= &self.publickey.modulus;
+        if self.oracle.calls().is_multiple_of(REPORT_EVERY) {
+            self.report();
+        }
+        self.oracle.query(&self.blinded)
     }
+
     // Smallest s which can produce a PKCS-compliant message
     fn step2a(&mut self) {
-        let three_b: BigInt = &BigInt::from_u8(3).unwrap() * &self.b;
-        // Initialise s = n/3B;
-        self.s.clone_from(&self.publickey.modulus);
-        self.s = self.s.div_ceil(&three_b);
-
-        while !self.try_si() {
-            self.s += 1;
-        }
-
-        self.state = Step::Step3;
+        self.s = match self.improvements.trim {
+            // Initialise s = n/3B;
+            false => self.publickey.modulus.div_ceil(&(3 * &self.b)),
+            // With m0 <= b, m0 * s is under n + 2B for any smaller s, and so either just m0 * s,
+            // too big to be PKCS for s > 1, or m0 * s - n, too small. Trimming makes b smaller,
+            // and so this a lot bigger.
+            true => {
+                let b = self.intervals.iter().map(|(_, b)| b).max().unwrap();
+                let lowest: BigInt = &self.publickey.modulus + 2 * &self.b;
+                lowest.div_ceil(b)
+            }
+        };
+        self.search(|s| *s += 1);
     }
 
     // Can't do the efficient logarithmic searching, so fall back to linear search until we can
     // again
     fn step2b(&mut self) {
         self.s += 1;
-        while !self.try_si() {
-            self.s += 1;
-        }
+        self.search(|s| *s += 1);
+    }
 
+    /// Tries s, then whatever `next` moves it on to, and so on until the oracle says yes: one at a
+    /// time, or a batch at once, in which case the first that works is the one to go with
+    fn search(&mut self, mut next: impl FnMut(&mut BigInt)) {
+        match self.improvements.batch {
+            1 => {
+                while !self.try_si() {
+                    next(&mut self.s);
+                }
+            }
+            size => loop {
+                let mut batch: Vec<BigInt> = (0..size)
+                    .map(|_| {
+                        let s = self.s.clone();
+                        next(&mut self.s);
+                        s
+                    })
+                    .collect();
+                self.report();
+                if let Some(i) = self.query_all(&batch).iter().position(|ok| *ok) {
+                    self.s = batch.swap_remove(i);
+                    break;
+                }
+            },
+        }
         self.state = Step::Step3;
     }
 
@@ -385,34 +525,32 @@ impl Attacker {
     // shrink by 1/2. We thuse choose r = 2(bs-2B)/n, but could just as well choose 4 to shrink by
     // a factor of 4, but it would take longer to find a solution on average
     fn step2c(&mut self) {
-        assert_eq!(self.intervals.get_intervals().len(), 1);
-        let B: BigInt = self.b.clone();
-        let n: BigInt = self.publickey.modulus.clone();
-
-        assert_eq!(self.intervals.get_intervals().len(), 1);
+        assert_eq!(self.intervals.len(), 1);
         // There is only one interval
-        let Interval { start: a, end: b } = self.intervals.get_intervals()[0].clone();
-        // Print size of interval just to check it's getting smaller
-        //println!("Size of diff:        {}", &b - &a);
-        //println!("Size of diff (bits): {}", (&b - &a).bits());
+        let (a, b) = self.intervals.iter().next().unwrap();
+        let (a, b) = (a.clone(), b.clone());
+        let B = &self.b;
+        let n = self.publickey.modulus.clone();
+
         // r = 2(bs - 2B)/n
-        let mut r: BigInt = 2 * (&b * &self.s - 2 * &B);
+        let mut r: BigInt = 2 * (&b * &self.s - 2 * B);
         r = r.div_ceil(&n);
-        self.s = 2 * &B + &r * &n;
-        self.s = self.s.div_ceil(&b);
-        let mut upper: BigInt = (3 * &B + &r * &n) / &a;
-
-        while !self.try_si() {
-            self.s += 1;
-
-            if self.s > upper {
-                r += 1;
-                upper = (3 * &B + &r * &n) / &a;
-                self.s = 2 * &B + &r * &n;
-                self.s = self.s.div_ceil(&b);
+        // 2B + rn and 3B + rn, moving up by n with each r
+        let mut low: BigInt = 2 * B + &r * &n;
+        let mut high: BigInt = 3 * B + &r * &n;
+        self.s = low.div_ceil(&b);
+        let mut upper: BigInt = &high / &a;
+
+        self.search(move |s| {
+            *s += 1;
+
+            if *s > upper {
+                low += &n;
+                high += &n;
+                upper = &high / &a;
+                *s = low.div_ceil(&b);
             }
-        }
-        self.state = Step::Step3;
+        });
     }
 
     // Again, m1 = m0s - rn
@@ -428,175 +566,268 @@ impl Attacker {
     // b' = (3B-1 + rn)/s
     fn step3(&mut self) {
         // First narrow set of solutions
-
-        //println!("Intervals: {:?}", self.intervals.get_intervals());
         let mut new_m = IntervalTree::default();
         let si = &self.s;
-        let two = BigInt::from_u8(2).unwrap();
+        let B = &self.b;
+        let n = &self.publickey.modulus;
 
-        for interval in self.intervals.get_intervals() {
-            let Interval { start: a, end: b } = interval;
-            //println!("a,b: {a}, {b}");
+        for (a, b) in self.intervals.iter() {
+            let mut r: BigInt = a * si - 3 * B + 1;
+            r = r.div_ceil(n);
 
-            let B: BigInt = self.b.clone();
-            let n = self.publickey.modulus.clone();
-
-            let mut r: BigInt = &a * si - 3 * &B + 1;
-            r = r.div_ceil(&n);
-
-            let mut max_r: BigInt = &b * si - 2 * &B;
-            max_r = max_r.div_floor(&n);
+            let mut max_r: BigInt = b * si - 2 * B;
+            max_r = max_r.div_floor(n);
 
             while r <= max_r {
                 // max of a and
                 // (2B + rn)/s
-                let mut lval: BigInt = &two * &B + &r * &n;
+                let mut lval: BigInt = 2 * B + &r * n;
                 lval = lval.div_ceil(si);
-                lval = lval.max(a.clone());
+                if &lval < a {
+                    lval.clone_from(a);
+                }
 
                 // min of b and
                 // (3B - 1 + rn)/s
-                let mut rval: BigInt = 3 * &B - 1 + &r * &n;
+                let mut rval: BigInt = 3 * B - 1 + &r * n;
                 rval = rval.div_floor(si);
-                rval = rval.min(b.clone());
+                if &rval > b {
+                    rval.clone_from(b);
+                }
 
-                //println!("l,r: {lval}, {rval}");
-                let new_interval = Interval::new(&lval, &rval);
-                new_m.insert_interval(&new_interval);
+                new_m.insert_interval(&Interval {
+                    start: lval,
+                    end: rval,
+                });
                 r += 1;
             }
         }
 
         self.intervals = new_m;
-        //println!("Intervals: {:?}", self.intervals.get_intervals());
-
-        // Now determine which step to go to
-        if self.intervals.get_intervals().len() == 1 {
-            let Interval { start: a, end: b } = self.intervals.get_intervals()[0].clone();
-            match a == b {
-                true => self.state = Step::Step4,
-                false => self.state = Step::Step2c,
+        self.report();
+
+        // Now determine which step to go to, or whether to start again
+        let next = match self.intervals.iter().next() {
+            // Checking the answer, in case trimming went wrong
+            Some((a, b)) if self.intervals.len() == 1 && a == b => {
+                (self.publickey.apply(a) == self.c0).then_some(Step::Step4)
             }
-        } else {
-            self.state = Step::Step2b;
+            Some(_) if self.intervals.len() == 1 => Some(Step::Step2c),
+            Some(_) => Some(Step::Step2b),
+            None => None,
+        };
+        match next {
+            Some(step) => self.state = step,
+            None => self.untrim(),
         }
     }
+
+    // A trimmer can pass by chance, about as often as any random s does, without t dividing m0.
+    // Then m0 isn't in the trimmed interval at all, and the search ends up with nothing, or the
+    // wrong answer, so it has to start again without trimming.
+    fn untrim(&mut self) {
+        assert!(self.improvements.trim, "Lost track of m0 without trimming");
+        self.improvements.trim = false;
+        self.intervals = Self::conforming(&self.b);
+        self.report();
+        self.state = Step::Step2a;
+    }
+
     fn step4(&self) -> BigInt {
         // To get here, m should contain one interval
-        let Interval { start: a, .. } = self.intervals.get_intervals()[0].clone();
-        let s0inv = invmod(&self.s0, &self.publickey.modulus);
+        let (a, _) = self.intervals.iter().next().unwrap();
+        let s0inv = try_invmod(&self.s0, &self.publickey.modulus).unwrap();
         (a * s0inv) % &self.publickey.modulus
     }
 }
 
-pub fn main() -> Result<()> {
-    // Set up problem
-    let bits = 128;
-    let e: BigInt = 3.into();
-    let (et, n) = et_n(bits, &e);
-    let d = invmod(&e, &et);
-
-    let public_key = Key {
-        key: e,
-        modulus: n.clone(),
-    };
-    let private_key = Key { key: d, modulus: n };
-    let message = b"kick it, CC";
-    let mut pkcs_message: Vec<u8> = vec![0x00, 0x02];
-    let bytes = &private_key.modulus.bits() / 8;
-    pkcs_message.extend_from_slice(&vec![0xff; bytes as usize - 3 - message.len()]);
-    pkcs_message.push(0x00);
-    pkcs_message.extend_from_slice(message);
+/// Decrypts `c` with the oracle's help, showing how long it took and how many calls it needed
+///
+/// With any of the [`Improvements`] switched on, the plain attack goes first, for comparison.
+pub fn decrypt(oracle: &PaddingOracle, c: &BigInt, rng: &mut impl Rng) -> Result<BigInt> {
+    let improvements = Improvements::from_options()?;
+    let mut runs = vec![improvements];
+    if improvements != Improvements::default() {
+        runs.insert(0, Improvements::default());
+    }
 
-    println!("PKCS message length: {}", pkcs_message.len());
-    println!("bytes: {}", bytes);
-    // PKCS pad this
+    let mut decrypted = vec![];
+    for improvements in runs {
+        let (start, calls) = (Instant::now(), oracle.calls());
+        decrypted.push(Attacker::new(c, oracle, improvements).run(rng));
+        say!(
+            "Decrypted {improvements} in {:.2?}, with {} oracle calls",
+            start.elapsed(),
+            oracle.calls() - calls
+        );
+    }
+    if decrypted.windows(2).any(|w| w[0] != w[1]) {
+        return Err(anyhow!("Attacks disagree on the plaintext: {decrypted:?}"));
+    }
+    Ok(decrypted.swap_remove(0))
+}
 
-    let m = BigInt::from_bytes_be(Sign::Plus, &pkcs_message);
-    println!("m true: {m}");
-    let c = m.modpow(&public_key.key, &public_key.modulus);
+pub fn main() -> Result<Report> {
+    // Set up problem
+    let params = Params::from_options(crate::options::get().profile.pick(256, 192), 3)?;
+    let mode = crate::options::get()
+        .arg("padding")?
+        .unwrap_or(Pkcs1Mode::Lenient);
+    say!("Using a {params}, against a {mode} padding oracle");
+    let mut rng = crate::utils::rng();
+    let oracle = PaddingOracle::new(params.keypair(&mut rng), mode);
+    let public_key = oracle.public_key().clone();
 
-    // Check is is pkcs padded
-    println!("Is pkcs padded? {}", is_pkcs(&c, &private_key));
+    let message = b"kick it, CC";
+    let padded = pkcs1v15_pad(message, public_key.size(), &mut rng)?;
+    let m = BigInt::from_bytes_be(Sign::Plus, &padded);
+    say!("m true: {m}");
+    let c = public_key.apply(&m);
 
-    let mut attacker = Attacker::new(&c, &public_key, &private_key);
+    // Check is is pkcs padded
+    say!("Is pkcs padded? {}", oracle.query(&c));
 
-    let md = attacker.run();
+    let md = decrypt(&oracle, &c, &mut rng)?;
 
-    println!("m true: {m}");
-    println!("m     : {md}");
-    let decrypted_padded = md.to_bytes_be().1;
-    // Now strip off padding
-    let index = decrypted_padded.iter().position(|&x| x == 0x00).unwrap();
-    let decrypted = &decrypted_padded[index + 1..];
-    let decrypted_message = std::str::from_utf8(decrypted).unwrap();
-    println!("Message: {}", decrypted_message);
-    assert_eq!(decrypted, message);
+    say!("m true: {m}");
+    say!("m     : {md}");
+    let decrypted = pkcs1v15_unpad(&public_key.to_block(&md), Pkcs1Mode::Strict)?;
+    say!("Message: {}", String::from_utf8_lossy(&decrypted));
 
-    Ok(())
+    Ok(Report::new()
+        .check("message", message, decrypted)
+        .count("queries", oracle.calls()))
 }
 
 #[cfg(test)]
 mod tests {
-    use num_traits::FromPrimitive;
-
     use super::*;
+    use rand::thread_rng;
 
     #[test]
-    fn interval_tests() {
-        let mut tree = IntervalTree::default();
-        println!("Empty tree: {:?}", tree.get_intervals());
-        assert_eq!(tree.get_intervals(), vec![]);
-        let five_ten_int = Interval::new(
-            &BigInt::from_i32(5).unwrap(),
-            &BigInt::from_i32(10).unwrap(),
-        );
-        tree.insert_interval(&five_ten_int);
-        assert_eq!(tree.get_intervals(), vec![five_ten_int.clone()]);
-        let twelve_thirteen_int = Interval::new(
-            &BigInt::from_i32(12).unwrap(),
-            &BigInt::from_i32(13).unwrap(),
-        );
-        tree.insert_interval(&twelve_thirteen_int);
+    fn oracle() {
+        let keypair = Params::new(256, 3).keypair(&mut thread_rng());
+        let oracle = PaddingOracle::new(keypair, Pkcs1Mode::Lenient);
+        let key = oracle.public_key().clone();
+        let B = bound(&key);
+        assert_eq!(B.bits(), 8 * 30 + 1);
+        for (m, conforming) in [
+            (2 * &B, true),
+            (3 * &B - 1, true),
+            (2 * &B - 1, false),
+            (3 * &B, false),
+            (B.clone(), false),
+        ] {
+            assert_eq!(oracle.query(&key.apply(&m)), conforming, "{m:x}");
+        }
+        assert_eq!(oracle.calls(), 5);
+    }
+
+    #[test]
+    fn padding() {
+        let mut rng = thread_rng();
+        let padded = pkcs1v15_pad(b"kick it, CC", 32, &mut rng).unwrap();
+        assert_eq!(padded.len(), 32);
+        assert_eq!(padded[..2], [0x00, 0x02]);
+        assert!(!padded[2..20].contains(&0));
+        for mode in [Pkcs1Mode::Strict, Pkcs1Mode::Lenient] {
+            assert_eq!(pkcs1v15_unpad(&padded, mode).unwrap(), b"kick it, CC");
+        }
+        assert!(pkcs1v15_pad(&[0x41; 21], 32, &mut rng).is_ok());
         assert_eq!(
-            tree.get_intervals(),
-            vec![five_ten_int, twelve_thirteen_int]
-        );
-        let eleven_fifteen_int = Interval::new(
-            &BigInt::from_i32(11).unwrap(),
-            &BigInt::from_i32(15).unwrap(),
-        );
-        tree.insert_interval(&eleven_fifteen_int);
-        let five_fifteen_int = Interval::new(
-            &BigInt::from_i32(5).unwrap(),
-            &BigInt::from_i32(15).unwrap(),
+            pkcs1v15_pad(&[0x41; 22], 32, &mut rng),
+            Err(Pkcs1Error::TooLong {
+                length: 22,
+                size: 32
+            })
         );
-        assert_eq!(tree.get_intervals(), vec![five_fifteen_int]);
-        let two_six_int =
-            Interval::new(&BigInt::from_i32(2).unwrap(), &BigInt::from_i32(6).unwrap());
-        tree.insert_interval(&two_six_int);
-        let two_fifteen_int = Interval::new(
-            &BigInt::from_i32(2).unwrap(),
-            &BigInt::from_i32(15).unwrap(),
+
+        // What only the lenient check lets through
+        let short_padding = [&[0x00, 0x02, 1, 2, 3, 0][..], b"message"].concat();
+        let no_end = [&[0x00, 0x02][..], &[0xff; 30]].concat();
+        assert_eq!(
+            pkcs1v15_unpad(&short_padding, Pkcs1Mode::Lenient).unwrap(),
+            b"message"
         );
-        assert_eq!(tree.get_intervals(), vec![two_fifteen_int]);
+        assert_eq!(pkcs1v15_unpad(&no_end, Pkcs1Mode::Lenient).unwrap(), b"");
+        for block in [&short_padding, &no_end] {
+            assert_eq!(
+                pkcs1v15_unpad(block, Pkcs1Mode::Strict),
+                Err(Pkcs1Error::InvalidPadding)
+            );
+        }
+        // And what neither does
+        for block in [&padded[1..], &[&[0x00, 0x01], &padded[2..]].concat()] {
+            for mode in [Pkcs1Mode::Strict, Pkcs1Mode::Lenient] {
+                assert!(pkcs1v15_unpad(block, mode).is_err());
+            }
+        }
+    }
+
+    #[test]
+    fn strict_oracle_leaks_less() {
+        // Random plaintexts starting 00 02: the lenient oracle says yes to all of them, but most
+        // don't have a 00 in the right place for the strict one
+        let mut rng = thread_rng();
+        let keypair = Params::new(256, 3).keypair(&mut rng);
+        let lenient = PaddingOracle::new(keypair.clone(), Pkcs1Mode::Lenient);
+        let strict = PaddingOracle::new(keypair, Pkcs1Mode::Strict);
+        let key = lenient.public_key().clone();
+        let B = bound(&key);
+        let (mut yes_lenient, mut yes_strict) = (0, 0);
+        for _ in 0..200 {
+            let m = rng.gen_bigint_range(&(2 * &B), &(3 * &B));
+            let c = key.apply(&m);
+            yes_lenient += lenient.query(&c) as usize;
+            yes_strict += strict.query(&c) as usize;
+        }
+        assert_eq!(yes_lenient, 200);
+        // About 1 in 12, for a 32-byte block
+        assert!(yes_strict < 50, "{yes_strict}");
+
+        // But both agree on a properly padded message
+        let padded = pkcs1v15_pad(b"kick it, CC", key.size(), &mut rng).unwrap();
+        let c = key.apply(&BigInt::from_bytes_be(Sign::Plus, &padded));
+        assert!(lenient.query(&c) && strict.query(&c));
+    }
 
-        println!("Tree: {:?}", tree.get_intervals());
-        let mut tree = IntervalTree::default();
-        let five_five_int =
-            Interval::new(&BigInt::from_i32(5).unwrap(), &BigInt::from_i32(5).unwrap());
-        let five_six_int =
-            Interval::new(&BigInt::from_i32(5).unwrap(), &BigInt::from_i32(6).unwrap());
-        let six_six_int =
-            Interval::new(&BigInt::from_i32(5).unwrap(), &BigInt::from_i32(6).unwrap());
-        tree.insert_interval(&five_five_int);
-        tree.insert_interval(&six_six_int);
-        assert_eq!(tree.get_intervals(), vec![five_six_int]);
+    #[test]
+    fn trimming() {
+        let mut rng = thread_rng();
+        let oracle = PaddingOracle::new(Params::new(256, 3).keypair(&mut rng), Pkcs1Mode::Lenient);
+        let key = oracle.public_key().clone();
+        let B = bound(&key);
+        // About 2.6B, and a multiple of 5 and 7, so that 4/5 and 6/7 are trimmers
+        let m: BigInt = (&B * 13 / 5) / 210 * 210;
+        let trim = Improvements {
+            trim: true,
+            batch: 1,
+        };
+        let mut attacker = Attacker::new(&key.apply(&m), &oracle, trim);
+        attacker.step1(&mut rng);
+        assert!(attacker.intervals.contains(&m));
+        assert!(attacker.intervals.measure() < &B / 4);
+    }
+
+    #[ignore = "slow"]
+    #[test]
+    fn improvements() {
+        // A small key, to be quick about it
+        let mut rng = thread_rng();
+        let oracle = PaddingOracle::new(Params::new(128, 3).keypair(&mut rng), Pkcs1Mode::Lenient);
+        let key = oracle.public_key().clone();
+        let B = bound(&key);
+        let m = rng.gen_bigint_range(&(2 * &B), &(3 * &B));
+        let c = key.apply(&m);
+        for (trim, batch) in [(false, 1), (true, 1), (false, 4), (true, 4)] {
+            let improvements = Improvements { trim, batch };
+            assert_eq!(Attacker::new(&c, &oracle, improvements).run(&mut rng), m);
+        }
     }
 
     #[ignore = "slow"]
     #[test]
     fn bleichenbacher_small() {
-        main().unwrap();
+        main().and_then(Report::verify).unwrap();
     }
 }
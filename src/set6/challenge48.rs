@@ -31,66 +31,49 @@
 //! work well in practice*
 
 use num_bigint::{BigInt, Sign};
-use rand::{thread_rng, Rng};
+use rand::Rng;
 
-use super::challenge46::Key;
-use super::challenge47::Attacker;
-use crate::set6::challenge47::is_pkcs;
+use super::challenge47::{decrypt, PaddingOracle};
+use crate::report::say;
+use crate::rsa::Params;
 use crate::utils::*;
 
-pub fn main() -> Result<()> {
+pub fn main() -> Result<Report> {
     // Set up problem
-    let bits = 384;
-    let e: BigInt = 3.into();
-    let (et, n) = et_n(bits, &e);
-    let d = invmod(&e, &et);
-
-    let public_key = Key {
-        key: e,
-        modulus: n.clone(),
-    };
-    let private_key = Key { key: d, modulus: n };
-    let mut rng = thread_rng();
+    let params = Params::from_options(crate::options::get().profile.pick(768, 512), 3)?;
+    let mode = crate::options::get()
+        .arg("padding")?
+        .unwrap_or(Pkcs1Mode::Lenient);
+    say!("Using a {params}, against a {mode} padding oracle");
+    let mut rng = crate::utils::rng();
+    let oracle = PaddingOracle::new(params.keypair(&mut rng), mode);
+    let public_key = oracle.public_key().clone();
 
     // Make the message a bit more interesting this time
     // Pick 40 bytes from War and Peace
-    let wap_full = std::fs::read_to_string("./data/wap.txt").unwrap();
+    let wap_full = crate::data::WAR_AND_PEACE.text()?;
     let idx: usize = rng.gen_range(0..wap_full.chars().count() - 40);
 
     let message = wap_full.chars().skip(idx).take(40).collect::<String>();
     let message = message.as_bytes();
-    let mut pkcs_message: Vec<u8> = vec![0x00, 0x02];
-    let bytes = &private_key.modulus.bits() / 8;
-    pkcs_message.extend_from_slice(&vec![0xff; bytes as usize - 3 - message.len()]);
-    pkcs_message.push(0x00);
-    pkcs_message.extend_from_slice(message);
-
-    println!("PKCS message length: {}", pkcs_message.len());
-    println!("bytes: {}", bytes);
-    // PKCS pad this
-
-    let m = BigInt::from_bytes_be(Sign::Plus, &pkcs_message);
-    println!("m true: {m}");
-    let c = m.modpow(&public_key.key, &public_key.modulus);
+    let padded = pkcs1v15_pad(message, public_key.size(), &mut rng)?;
+    let m = BigInt::from_bytes_be(Sign::Plus, &padded);
+    say!("m true: {m}");
+    let c = public_key.apply(&m);
 
     // Check is is pkcs padded
-    println!("Is pkcs padded? {}", is_pkcs(&c, &private_key));
-
-    let mut attacker = Attacker::new(&c, &public_key, &private_key);
+    say!("Is pkcs padded? {}", oracle.query(&c));
 
-    let md = attacker.run();
+    let md = decrypt(&oracle, &c, &mut rng)?;
 
-    println!("m true: {m}");
-    println!("m     : {md}");
-    let decrypted_padded = md.to_bytes_be().1;
-    // Now strip off padding
-    let index = decrypted_padded.iter().position(|&x| x == 0x00).unwrap();
-    let decrypted = &decrypted_padded[index + 1..];
-    let decrypted_message = std::str::from_utf8(decrypted).unwrap();
-    println!("Message: {}", decrypted_message);
-    assert_eq!(decrypted, message);
+    say!("m true: {m}");
+    say!("m     : {md}");
+    let decrypted = pkcs1v15_unpad(&public_key.to_block(&md), Pkcs1Mode::Strict)?;
+    say!("Message: {}", String::from_utf8_lossy(&decrypted));
 
-    Ok(())
+    Ok(Report::new()
+        .check("message", message, decrypted)
+        .count("queries", oracle.calls()))
 }
 
 #[cfg(test)]
@@ -99,6 +82,6 @@ mod tests {
     #[ignore = "slow"]
     #[test]
     fn bleichenbacher_big() {
-        main().unwrap();
+        main().and_then(Report::verify).unwrap();
     }
 }
@@ -1,25 +1,11 @@
-pub mod challenge41;
-pub mod challenge42;
-pub mod challenge43;
-pub mod challenge44;
-pub mod challenge45;
-pub mod challenge46;
-pub mod challenge47;
-pub mod challenge48;
-
-use crate::utils::Result;
-use anyhow::anyhow;
-
-pub fn run(c: u64) -> Result<()> {
-    match c {
-        41 => challenge41::main(),
-        42 => challenge42::main(),
-        43 => challenge43::main(),
-        44 => challenge44::main(),
-        45 => challenge45::main(),
-        46 => challenge46::main(),
-        47 => challenge47::main(),
-        48 => challenge48::main(),
-        i => Err(anyhow!("{} not in set 6", i)),
-    }
+crate::runner::register! {
+    set 6;
+    41 => challenge41, "Implement unpadded message recovery oracle", Fast;
+    42 => challenge42, "Bleichenbacher's e=3 RSA Attack", Fast;
+    43 => challenge43, "DSA key recovery from nonce", Fast;
+    44 => challenge44, "DSA nonce recovery from repeated nonce", Fast;
+    45 => challenge45, "DSA parameter tampering", Fast;
+    46 => challenge46, "RSA parity oracle", Fast;
+    47 => challenge47, "Bleichenbacher's PKCS 1.5 Padding Oracle (Simple Case)", Fast;
+    48 => challenge48, "Bleichenbacher's PKCS 1.5 Padding Oracle (Complete Case)", Fast;
 }
@@ -96,12 +96,11 @@ pub fn cbc_mac(message: &[u8], key: &[u8], iv: Option<&[u8]>) -> Result<Vec<u8>>
     Ok(mac.to_vec())
 }
 
-use rand::thread_rng;
-
+use crate::report::say;
 use crate::utils::*;
 
-pub fn main() -> Result<()> {
-    let mut rng = thread_rng();
+pub fn main() -> Result<Report> {
+    let mut rng = crate::utils::rng();
     // Part 1
     let key = random_key(16, &mut rng);
 
@@ -109,15 +108,12 @@ pub fn main() -> Result<()> {
     let enc = cbc_encrypt(original, &key, None)?;
     let original_mac = &enc[enc.len() - 16..];
 
-    assert_eq!(
-        cbc_mac_verify(original, original_mac, None, &key),
-        Auth::Valid
-    );
-    println!("MAC: {}", bytes_to_hex(original_mac));
-    println!(
-        "Original message MAC: {:?}",
-        cbc_mac_verify(original, original_mac, None, &key)
-    );
+    let original_status = cbc_mac_verify(original, original_mac, None, &key);
+    say!("MAC: {}", bytes_to_hex(original_mac));
+    say!("Original message MAC: {:?}", original_status);
+    if original_status != Auth::Valid {
+        return Err(anyhow::anyhow!("The original message's MAC doesn't verify"));
+    }
 
     // Now to forge
     let target = b"from=#you&to=#me&amount=#1M";
@@ -129,8 +125,11 @@ pub fn main() -> Result<()> {
     // MAC will remain the same, so we should be able to check now
 
     let forged_status = cbc_mac_verify(target, original_mac, Some(&new_iv), &key);
-    println!("Forged message MAC: {:?}", forged_status);
-    assert_eq!(forged_status, Auth::Valid);
+    let forged_iv = new_iv.clone();
+    say!("Forged message MAC: {:?}", forged_status);
+    let report = Report::new()
+        .recovered("forged iv", forged_iv)
+        .forged("forged iv", forged_status == Auth::Valid);
 
     // Part 2
     // Honestly, this one seems a little implausible
@@ -142,8 +141,8 @@ pub fn main() -> Result<()> {
     let original = b"from=#you&tx_list=somebody:10000";
     let enc = cbc_encrypt(original, &key, None)?;
     let original_mac = &enc[enc.len() - 16..];
-    println!("MAC: {}", bytes_to_hex(original_mac));
-    println!(
+    say!("MAC: {}", bytes_to_hex(original_mac));
+    say!(
         "Original message MAC: {:?}",
         cbc_mac_verify(original, original_mac, None, &key)
     );
@@ -153,11 +152,13 @@ pub fn main() -> Result<()> {
 
     let new_mac = cbc_encrypt(extension, &key, Some(original_mac))?;
     let forged_status = cbc_mac_verify(&target, &new_mac, None, &key);
-    println!("MAC: {}", bytes_to_hex(&new_mac));
-    println!("New MAC status: {:?}", forged_status);
-    assert_eq!(forged_status, Auth::Valid);
+    say!("MAC: {}", bytes_to_hex(&new_mac));
+    say!("New MAC status: {:?}", forged_status);
 
-    Ok(())
+    Ok(report
+        .recovered("extended message", target)
+        .recovered("extended mac", new_mac)
+        .forged("extended mac", forged_status == Auth::Valid))
 }
 
 #[cfg(test)]
@@ -166,6 +167,6 @@ mod tests {
 
     #[test]
     fn run() {
-        main().unwrap();
+        main().and_then(Report::verify).unwrap();
     }
 }
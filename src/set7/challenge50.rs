@@ -23,18 +23,18 @@
 //! Write JavaScript code that downloads your file, checks its CBC-MAC, and inserts it into the DOM
 //! iff it matches the expected hash.
 
+use crate::report::say;
 use crate::utils::*;
 
 use super::challenge49::cbc_mac;
 
-pub fn main() -> Result<()> {
+pub fn main() -> Result<Report> {
     let original = b"alert('MZA who was that?');\n";
     let key = b"YELLOW SUBMARINE";
     let padded_original = pkcs7_pad(original, 16);
     let mac = cbc_mac(&padded_original, key, None)?;
-    let mac_string = bytes_to_hex(&mac);
-    assert_eq!(mac_string, String::from("296b8d7cb78a243dda4d0a61d33bbdd1"));
-    println!("MAC: {}", bytes_to_hex(&mac));
+    say!("MAC: {}", bytes_to_hex(&mac));
+    let report = Report::new().check("mac", hex_to_bytes("296b8d7cb78a243dda4d0a61d33bbdd1")?, &mac[..]);
 
     // How can we forged this message? Well, we know the block that went into producing the MAC was
     // IV ^ original
@@ -44,9 +44,9 @@ pub fn main() -> Result<()> {
     // original' = original ^ CBC(previous block)
     let mut target = b"alert('Ayo, the Wu is back!');//asd".to_vec();
     target = pkcs7_pad(&target, 16);
-    println!("Target len: {}", target.len());
+    say!("Target len: {}", target.len());
     let cbc_prev = cbc_mac(&target, key, None)?;
-    println!("cbc_prev len: {}", cbc_prev.len());
+    say!("cbc_prev len: {}", cbc_prev.len());
     let append: Vec<u8> = cbc_prev
         .iter()
         .zip(padded_original.iter())
@@ -56,14 +56,14 @@ pub fn main() -> Result<()> {
     // The original message was more than a block, and we only modified the first block, so paste
     // this back on the end
     target.extend_from_slice(&padded_original[16..]);
-    println!("Target: {:?}", target);
+    say!("Target: {:?}", target);
     let new_mac = cbc_mac(&target, key, None)?;
-    let new_mac_string = bytes_to_hex(&new_mac);
 
-    println!("New mac: {}", new_mac_string);
-    assert_eq!(new_mac_string, mac_string);
+    say!("New mac: {}", bytes_to_hex(&new_mac));
 
-    Ok(())
+    Ok(report
+        .recovered("forgery", target)
+        .check("forgery's mac", mac, new_mac))
 }
 
 #[cfg(test)]
@@ -72,6 +72,6 @@ mod tests {
 
     #[test]
     fn mac_hashing() {
-        main().unwrap();
+        main().and_then(Report::verify).unwrap();
     }
 }
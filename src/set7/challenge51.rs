@@ -62,11 +62,12 @@
 //! oracle(P) -> length(encrypt(compress(format_request(P))))
 //! Format the request like this:
 
+use crate::progress;
+use crate::report::say;
 use crate::{stream::Ctr, utils::*};
 use flate2::write::DeflateEncoder;
 use flate2::Compression;
-use indicatif::ProgressBar;
-use rand::{thread_rng, Rng};
+use rand::Rng;
 use std::io::prelude::*;
 
 enum Enc {
@@ -88,16 +89,15 @@ impl Oracle {
             content
         )
     }
-    pub fn len(&self, content: String, enc: &Enc) -> usize {
+    /// With a fresh key, and nonce or IV, for every request
+    pub fn len(&self, content: String, enc: &Enc, rng: &mut impl Rng) -> usize {
         let embed = self.payload(content);
-        //println!("Embedded: {}", embed);
         // Compress message
         let mut e = DeflateEncoder::new(Vec::new(), Compression::best());
         e.write_all(embed.as_bytes()).unwrap();
         let compressed = e.finish().unwrap();
 
-        let mut rng = thread_rng();
-        let key = random_key(16, &mut rng);
+        let key = random_key(16, rng);
 
         // Determine compr
         // The difference between the two is one of padding, but it doesn't really make a different
@@ -114,7 +114,7 @@ impl Oracle {
                 decrypted.len()
             }
             Enc::Cbc => {
-                let iv = random_key(16, &mut rng);
+                let iv = random_key(16, rng);
                 let encrypted: Vec<u8> =
                     cbc_encrypt(&pkcs7_pad(&compressed, 16), &key, Some(&iv)).unwrap();
                 // Refresh stream
@@ -126,21 +126,19 @@ impl Oracle {
     }
 }
 
-fn make_guess(oracle: &Oracle, enc: Enc) -> (String, usize) {
-    let mut rng = thread_rng();
+fn make_guess(oracle: &Oracle, enc: Enc, rng: &mut impl Rng) -> (String, usize) {
     let session_header = format!("POST/ HTTP/1.1\nHost: {}\nCookie: sessionid=", oracle.host);
     //let session_header = format!("sessionid=");
     // Let's check what compression looks like using the correct string, rather than the wrong one
-    //println!("session id: {}", oracle.session_id);
-    //println!("session id l: {}", oracle.session_id.len());
 
     // Make a guess of our id, and run through each time picking the best version
-    let mut guess_id: String = bytes_to_hex(&random_key(oracle.keysize, &mut rng));
-    //println!("OG guess: {}", guess_id);
-    let chars = ['a', 'b', 'c', 'd', 'e', 'f', '0', '1', '2', '3', '4', '5', '6', '7', '8', '9'];
+    let mut guess_id: String = bytes_to_hex(&random_key(oracle.keysize, rng));
+    let chars = [
+        'a', 'b', 'c', 'd', 'e', 'f', '0', '1', '2', '3', '4', '5', '6', '7', '8', '9',
+    ];
 
     // Have some junk which we can use to disambiguate different equally-good compression
-    let mut junk = bytes_to_hex(&random_key(4, &mut rng));
+    let mut junk = bytes_to_hex(&random_key(4, rng));
     for _pass in 0..3 {
         for char_num in 0..guess_id.len() {
             #[allow(unused_assignments)]
@@ -159,17 +157,15 @@ fn make_guess(oracle: &Oracle, enc: Enc) -> (String, usize) {
                             current_guess.push_str(&new_guess);
                             current_guess.push_str(&junk);
                         }
-                        //println!("Current guess: {}", &s[..64]);
-                        (c, oracle.len(current_guess, &enc))
+                        (c, oracle.len(current_guess, &enc, rng))
                     })
                     .collect::<Vec<_>>();
-                //println!("MIN: {:?}", minl);
                 minimum = *minl.iter().min_by(|x, y| x.1.cmp(&y.1)).unwrap();
                 if minl.iter().filter(|x| x.1 == minimum.1).count() == 1 {
                     break;
                 }
                 // If there's no best compression, re-randomise the junk and try again
-                junk = bytes_to_hex(&random_key(4, &mut rng));
+                junk = bytes_to_hex(&random_key(4, rng));
             }
 
             guess_id = guess_id
@@ -181,21 +177,19 @@ fn make_guess(oracle: &Oracle, enc: Enc) -> (String, usize) {
                 })
                 .collect();
         }
-        //println!("Key:   {}", oracle.session_id);
-        //println!("Guess: {}", guess_id);
     }
 
     let mut guess = String::new();
     guess.push_str(&session_header);
     guess.push_str(&guess_id);
 
-    (guess_id, oracle.len(guess, &enc))
+    (guess_id, oracle.len(guess, &enc, rng))
 }
 
-pub fn main() -> Result<()> {
+pub fn main() -> Result<Report> {
     let keysize = 16;
     // Initialise oracle
-    let mut rng = thread_rng();
+    let mut rng = crate::utils::rng();
     let session_id = bytes_to_hex(&random_key(keysize, &mut rng));
     //let session_id = String::from("e2df42256d4cc3bec3a9cdce1c55c5e3");
 
@@ -210,41 +204,41 @@ pub fn main() -> Result<()> {
     // correct answer, so we ensure that it is a good one
     // The compression achieved eliminates the extra string + about 3 bytes for keysize=16, and this appears to be
     // a good enough heuristic to succeed
-    let target_length = oracle.len(String::new(), &Enc::Stream) + 3;
+    let target_length = oracle.len(String::new(), &Enc::Stream, &mut rng) + 3;
 
-    let stream_spinner = ProgressBar::new_spinner();
-    stream_spinner.set_message("Finding stream key");
+    let stream_spinner = progress::spinner();
+    stream_spinner.set_message("Finding stream key".to_string());
     // Run until we find good compression
-    loop {
+    let stream_guess = loop {
         stream_spinner.tick();
-        let (best_guess, l) = make_guess(&oracle, Enc::Stream);
+        let (best_guess, l) = make_guess(&oracle, Enc::Stream, &mut rng);
         if l <= target_length {
             stream_spinner.finish();
-            println!("Key:   {}", oracle.session_id);
-            println!("Guess: {}", best_guess);
-            assert_eq!(oracle.session_id, best_guess);
-            break;
+            say!("Key:   {}", oracle.session_id);
+            say!("Guess: {}", best_guess);
+            break best_guess;
         }
-    }
+    };
     // Do it again, but for CBC
-    let target_length = oracle.len(String::new(), &Enc::Cbc) + 48 / keysize;
+    let target_length = oracle.len(String::new(), &Enc::Cbc, &mut rng) + 48 / keysize;
 
-    let stream_spinner = ProgressBar::new_spinner();
-    stream_spinner.set_message("Finding CBC key");
+    let stream_spinner = progress::spinner();
+    stream_spinner.set_message("Finding CBC key".to_string());
     // Run until we find good compression
-    loop {
+    let cbc_guess = loop {
         stream_spinner.tick();
-        let (best_guess, l) = make_guess(&oracle, Enc::Cbc);
+        let (best_guess, l) = make_guess(&oracle, Enc::Cbc, &mut rng);
         if l <= target_length {
             stream_spinner.finish();
-            println!("Key:   {}", oracle.session_id);
-            println!("Guess: {}", best_guess);
-            assert_eq!(oracle.session_id, best_guess);
-            break;
+            say!("Key:   {}", oracle.session_id);
+            say!("Guess: {}", best_guess);
+            break best_guess;
         }
-    }
+    };
 
-    Ok(())
+    Ok(Report::new()
+        .check("session id, stream", &*oracle.session_id, stream_guess)
+        .check("session id, CBC", &*oracle.session_id, cbc_guess))
 }
 
 #[cfg(test)]
@@ -254,6 +248,6 @@ mod tests {
     #[ignore = "slow"]
     #[test]
     fn crack() {
-        main().unwrap();
+        main().and_then(Report::verify).unwrap();
     }
 }
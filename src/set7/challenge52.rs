@@ -63,12 +63,12 @@
 //! the one you just used. Find a pair of messages that collide under both functions. Measure the
 //! total number of calls to the collision function.
 
-use std::collections::HashMap;
-
+use crate::fxhash::FxHashMap;
+use crate::progress;
+use crate::report::say;
 use crate::utils::*;
-use indicatif::{ProgressBar, ProgressStyle};
 use openssl::symm::{Cipher, Crypter, Mode};
-use rand::{thread_rng, Rng};
+use rand::Rng;
 
 pub trait CrapHasher {
     /// Updates the inner state with some data
@@ -96,7 +96,7 @@ impl Crash {
 
         let cipher = Cipher::chacha20();
 
-        let mut encrypter = Crypter::new(cipher, Mode::Encrypt, &key, None).unwrap();
+        let mut encrypter = Crypter::new(cipher, Mode::Encrypt, &key, Some(&[0; 16])).unwrap();
         encrypter.pad(false);
 
         encrypter.update(chunk, &mut ciphertext).unwrap();
@@ -189,9 +189,8 @@ impl Default for SlowCrash {
     }
 }
 
-fn find_collision<T: CrapHasher>(state: u16) -> (Vec<u8>, Vec<u8>) {
-    let mut rng = thread_rng();
-    let mut map = HashMap::<u16, Vec<u8>>::new();
+fn find_collision<T: CrapHasher>(state: u16, rng: &mut impl Rng) -> (Vec<u8>, Vec<u8>) {
+    let mut map = FxHashMap::<u16, Vec<u8>>::default();
     // Now go through these blocks in a deterministic fashion
     loop {
         let random_block: Vec<u8> = (0..16).map(|_| rng.gen::<u8>()).collect();
@@ -225,38 +224,28 @@ pub fn hash_full<T: CrapHasher>(block: &[u8], state: u16) -> u16 {
 fn gen_collision_pairs<T: CrapHasher>(
     initial_state: u16,
     length: usize,
+    rng: &mut impl Rng,
 ) -> Vec<(Vec<u8>, Vec<u8>)> {
     // Pairs of blocks
     let mut pairs: Vec<(Vec<u8>, Vec<u8>)> = vec![];
     let mut states = vec![initial_state];
 
-    let pb = ProgressBar::new(length as u64);
-    pb.set_message("Generating collisions");
-    pb.set_style(
-        ProgressStyle::with_template(
-            "[{elapsed_precise}] {bar:40.cyan/blue} {pos:>7}/{len:7} {msg}",
-        )
-        .unwrap()
-        .progress_chars("##-"),
-    );
+    let pb = progress::bar(length as u64);
+    pb.set_message("Generating collisions".to_string());
 
     for i in 0..length {
         // Okay, now how are we going to generate collisions?
         // First, we find a collision given a particular initial state
-        let pair = find_collision::<T>(states[i]);
+        let pair = find_collision::<T>(states[i], rng);
 
-        //println!("Pair: {:?}", pair);
 
         let hash0 = hash::<T>(&pair.0, states[i]);
-        //println!("Hash0: {}", hash0);
 
         //let hash1 = hash(&pair.1, states[i]);
-        //println!("Hash1: {}", hash1);
         //assert_eq!(hash0, hash1);
 
         pairs.push(pair);
         states.push(hash0);
-        //println!("States: {:?}", states);
         pb.inc(1);
     }
     pb.finish();
@@ -264,18 +253,11 @@ fn gen_collision_pairs<T: CrapHasher>(
 }
 
 fn get_bits_for_slow_collision(collision_pairs: &[(Vec<u8>, Vec<u8>)]) -> Option<(usize, usize)> {
-    let mut map = HashMap::new();
+    let mut map = FxHashMap::default();
     let n = collision_pairs.len();
 
-    let pb = ProgressBar::new((1 << n) as u64);
-    pb.set_message("Generating slow collisions");
-    pb.set_style(
-        ProgressStyle::with_template(
-            "[{elapsed_precise}] {bar:40.cyan/blue} {pos:>7}/{len:7} {msg}",
-        )
-        .unwrap()
-        .progress_chars("##-"),
-    );
+    let pb = progress::bar((1 << n) as u64);
+    pb.set_message("Generating slow collisions".to_string());
 
     // Now run through each of these and determine whether there is a collision for slow_crash
     // How many options utilise the full tree? n choices, so 2**n distinct hashes, 2**n = 1 <<<
@@ -292,8 +274,9 @@ fn get_bits_for_slow_collision(collision_pairs: &[(Vec<u8>, Vec<u8>)]) -> Option
         let h = slow_hasher.finalise();
 
         if let Some(old) = map.get(&h) {
-            pb.finish_with_message(format!("Found collision after {} attempts", i));
-            println!("Total calls: {}", i);
+            pb.set_message(format!("Found collision after {} attempts", i));
+            pb.finish();
+            say!("Total calls: {}", i);
             return Some((*old, i));
         } else {
             pb.inc(1);
@@ -309,10 +292,10 @@ struct SlowFastCollision {
     path_b: usize,
 }
 
-fn get_slow_and_fast_collision(search_size: usize) -> SlowFastCollision {
+fn get_slow_and_fast_collision(search_size: usize, rng: &mut impl Rng) -> SlowFastCollision {
     // Keep generating until we find a collision pair
     loop {
-        let collision_pairs = gen_collision_pairs::<Crash>(0, search_size);
+        let collision_pairs = gen_collision_pairs::<Crash>(0, search_size, rng);
         if let Some(bitpair) = get_bits_for_slow_collision(&collision_pairs) {
             return SlowFastCollision {
                 pairs: collision_pairs,
@@ -320,16 +303,16 @@ fn get_slow_and_fast_collision(search_size: usize) -> SlowFastCollision {
                 path_b: bitpair.1,
             };
         }
-        println!("Had to loop");
+        say!("Had to loop");
     }
 }
 
-pub fn main() -> Result<()> {
+pub fn main() -> Result<Report> {
     let data = b"YELLOW SUBMARINE";
     let mut hasher = Crash::default();
     hasher.update(data);
     let hash_val = hasher.finalise();
-    println!("Hash: {}", hash_val);
+    say!("Hash: {}", hash_val);
 
     // New hash function is crash(x) || slowcrash(x)
     // So, make 2**n colliding hashes
@@ -342,9 +325,9 @@ pub fn main() -> Result<()> {
         pairs: collision_pairs,
         path_a,
         path_b,
-    } = get_slow_and_fast_collision(n);
+    } = get_slow_and_fast_collision(n, &mut crate::utils::rng());
 
-    println!("Bitpair: {}, {}", path_a, path_b);
+    say!("Bitpair: {}, {}", path_a, path_b);
     // Bitpair now determines two blocks
     let mut a_blocks = vec![];
     let mut b_blocks = vec![];
@@ -366,19 +349,22 @@ pub fn main() -> Result<()> {
     // Now calculate hashes for each of these
     let regular_hash_a = hash_full::<Crash>(&a_blocks, 0);
     let regular_hash_b = hash_full::<Crash>(&b_blocks, 0);
-    println!("Fast hash a: {}", regular_hash_a);
-    println!("Fast hash b: {}", regular_hash_b);
-
-    assert_eq!(regular_hash_a, regular_hash_b);
+    say!("Fast hash a: {}", regular_hash_a);
+    say!("Fast hash b: {}", regular_hash_b);
 
     let slow_hash_a = hash_full::<SlowCrash>(&a_blocks, 0);
     let slow_hash_b = hash_full::<SlowCrash>(&b_blocks, 0);
-    println!("Slow hash a: {}", slow_hash_a);
-    println!("Slow hash b: {}", slow_hash_b);
-    assert_eq!(slow_hash_a, slow_hash_b);
+    say!("Slow hash a: {}", slow_hash_a);
+    say!("Slow hash b: {}", slow_hash_b);
 
-    assert_ne!(a_blocks, b_blocks);
-    Ok(())
+    if a_blocks == b_blocks {
+        return Err(anyhow::anyhow!("The two messages are the same"));
+    }
+    Ok(Report::new()
+        .recovered("message a", a_blocks)
+        .recovered("message b", b_blocks)
+        .check("fast hash", u32::from(regular_hash_a), u32::from(regular_hash_b))
+        .check("slow hash", u32::from(slow_hash_a), u32::from(slow_hash_b)))
 }
 
 #[cfg(test)]
@@ -387,11 +373,11 @@ mod tests {
     use super::*;
     #[test]
     fn test_tree() {
-        let mut rng = thread_rng();
+        let mut rng = rand::thread_rng();
         let initial_val = rng.gen::<u16>();
 
         let n = 10;
-        let collision_pairs = gen_collision_pairs::<Crash>(initial_val, n);
+        let collision_pairs = gen_collision_pairs::<Crash>(initial_val, n, &mut rng);
 
         // Pick two random paths through the tree and verify hashes are the same
         let mut hasher_one = Crash::new(initial_val);
@@ -415,6 +401,6 @@ mod tests {
 
     #[test]
     fn double_collision() {
-        main().unwrap();
+        main().and_then(Report::verify).unwrap();
     }
 }
@@ -48,10 +48,11 @@
 //! The padding in the final block should now be correct, and your forgery should hash to the same
 //! value as M.
 
-use indicatif::{ProgressBar, ProgressStyle};
-use rand::{thread_rng, Rng};
-use std::collections::HashMap;
+use rand::Rng;
 
+use crate::fxhash::FxHashMap;
+use crate::progress;
+use crate::report::say;
 use crate::utils::*;
 
 use super::challenge52::{hash_full, CrapHasher, Crash};
@@ -73,19 +74,12 @@ struct Expandable {
 }
 
 impl Expandable {
-    pub fn new(l: usize) -> Self {
+    pub fn new(l: usize, rng: &mut impl Rng) -> Self {
         let mut expandable = Self::default();
-        let pb = ProgressBar::new(l as u64);
-        pb.set_message("Generating expandable message");
-        pb.set_style(
-            ProgressStyle::with_template(
-                "[{elapsed_precise}] {bar:40.cyan/blue} {pos:>7}/{len:7} {msg}",
-            )
-            .unwrap()
-            .progress_chars("##-"),
-        );
+        let pb = progress::bar(l as u64);
+        pb.set_message("Generating expandable message".to_string());
         for _ in 0..l {
-            expandable.extend();
+            expandable.extend(rng);
             pb.inc(1);
         }
         pb.finish();
@@ -93,7 +87,7 @@ impl Expandable {
         expandable
     }
 
-    pub fn extend(&mut self) {
+    pub fn extend(&mut self, rng: &mut impl Rng) {
         // Get current k
         let k = self.short_blocks.len();
         // Get starting seed value
@@ -114,10 +108,9 @@ impl Expandable {
 
         // Okay, now all set up
         // Create two hashmaps for both long and short blocks
-        let mut short_map = HashMap::<u16, Vec<u8>>::new();
-        let mut long_map = HashMap::<u16, Vec<u8>>::new();
+        let mut short_map = FxHashMap::<u16, Vec<u8>>::default();
+        let mut long_map = FxHashMap::<u16, Vec<u8>>::default();
 
-        let mut rng = thread_rng();
         loop {
             let short_block: Vec<u8> = (0..16).map(|_| rng.gen::<u8>()).collect();
             let long_block: Vec<u8> = (0..16).map(|_| rng.gen::<u8>()).collect();
@@ -159,8 +152,8 @@ impl Expandable {
     }
 }
 
-pub fn main() -> Result<()> {
-    let mut rng = thread_rng();
+pub fn main() -> Result<Report> {
+    let mut rng = crate::utils::rng();
 
     // Random message of length 2**16 blocks blocks
     let message: Vec<u8> = (0..16 * 65536).map(|_| rng.gen::<u8>()).collect();
@@ -178,13 +171,13 @@ pub fn main() -> Result<()> {
     // Generate expandable messages until we find a match
     loop {
         let k = 16;
-        let expandable = Expandable::new(k);
+        let expandable = Expandable::new(k, &mut rng);
         let expandable_hash = expandable.hashes[expandable.hashes.len() - 1];
 
         let short_length: usize = expandable.short_blocks.iter().map(|x| x.len()).sum();
         let long_length: usize = expandable.long_blocks.iter().map(|x| x.len()).sum();
 
-        println!(
+        say!(
             "Generated expandable messages from length {} to {} blocks, all with hash {}",
             short_length / 16,
             long_length / 16,
@@ -201,8 +194,8 @@ pub fn main() -> Result<()> {
             .iter()
             .map(|x| x.len() / 16)
             .collect();
-        println!("Short lengths: {:?}", short_lengths);
-        println!("Long lengths:  {:?}", long_lengths);
+        say!("Short lengths: {:?}", short_lengths);
+        say!("Long lengths:  {:?}", long_lengths);
 
         // Scan through expandable messages and check if there is a collision
         // Must skip first k because our expandable message has a minimum length of k
@@ -216,9 +209,9 @@ pub fn main() -> Result<()> {
             // As such, we want to take the position one beyond this in order to actually replace the
             // block with the matching hash
             let index = index + 1;
-            println!("Collision found!");
+            say!("Collision found!");
             let position = k + index;
-            println!("Block number: {}", position);
+            say!("Block number: {}", position);
 
             // Construct expandable message with this length
             let mut expandable_message = vec![];
@@ -238,31 +231,35 @@ pub fn main() -> Result<()> {
 
             // Patch on remainder of the blocks from the original message
             expandable_message.extend_from_slice(&message[16 * position..]);
-
-            // Check lengths match
-            assert_eq!(expandable_message.len(), message.len());
-
-            // And check that hashes match!
             let expandable_hash_full = hash_full::<Crash>(&expandable_message, 0);
-            assert_eq!(expandable_hash_full, message_hash);
-
-            assert_ne!(expandable_message, message);
-            println!(
+            if expandable_message == message {
+                return Err(anyhow::anyhow!("The second preimage is the message itself"));
+            }
+            say!(
                 "Expanded message total length: {}",
                 expandable_message.len()
             );
-            println!("Original message total length: {}", message.len());
-            println!(
+            say!("Original message total length: {}", message.len());
+            say!(
                 "Expanded message first block: {:?}",
                 &expandable_message[0..16]
             );
-            println!("Original message first block: {:?}", &message[0..16]);
-            println!("Expanded message full hash: {}", expandable_hash_full);
-            println!("Original message full hash: {}", message_hash);
-
-            return Ok(());
+            say!("Original message first block: {:?}", &message[0..16]);
+            say!("Expanded message full hash: {}", expandable_hash_full);
+            say!("Original message full hash: {}", message_hash);
+
+            // Lengths and hashes should match
+            return Ok(Report::new()
+                .recovered("block", position)
+                .recovered("prefix", &expandable_message[..16 * position])
+                .check("length", message.len(), expandable_message.len())
+                .check(
+                    "hash",
+                    u32::from(message_hash),
+                    u32::from(expandable_hash_full),
+                ));
         }
-        println!("Didn't find a match, regenerating expandable message");
+        say!("Didn't find a match, regenerating expandable message");
     }
 }
 
@@ -273,8 +270,8 @@ mod tests {
     #[test]
     fn test_paths() {
         let n = 10;
-        let expandable = Expandable::new(n);
-        let mut rng = thread_rng();
+        let mut rng = rand::thread_rng();
+        let expandable = Expandable::new(n, &mut rng);
 
         let mut message_one = vec![];
         let mut message_two = vec![];
@@ -304,6 +301,6 @@ mod tests {
 
     #[test]
     fn collision() {
-        main().unwrap();
+        main().and_then(Report::verify).unwrap();
     }
 }
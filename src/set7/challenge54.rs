@@ -52,10 +52,9 @@ struct MiniFunnel {
 
 // Pairs off two hashes by finding blocks which collide them
 impl MiniFunnel {
-    fn new(input_a: u16, input_b: u16) -> Self {
-        let mut rng = thread_rng();
-        let mut map_a = HashMap::new();
-        let mut map_b = HashMap::new();
+    fn new(input_a: u16, input_b: u16, rng: &mut impl Rng) -> Self {
+        let mut map_a = FxHashMap::default();
+        let mut map_b = FxHashMap::default();
         loop {
             let random_a: Vec<u8> = (0..16).map(|_| rng.gen::<u8>()).collect();
             let random_b: Vec<u8> = (0..16).map(|_| rng.gen::<u8>()).collect();
@@ -91,36 +90,28 @@ impl MiniFunnel {
 
 #[derive(Debug)]
 struct Funnel {
-    map: HashMap<u16, (Vec<u8>, u16)>,
+    map: FxHashMap<u16, (Vec<u8>, u16)>,
     final_hash: u16,
     initial_layer: Vec<u16>,
 }
 
 impl Funnel {
-    fn new(layers: usize) -> Self {
-        let mut rng = thread_rng();
-        let mut map = HashMap::<u16, (Vec<u8>, u16)>::new();
+    fn new(layers: usize, rng: &mut impl Rng) -> Self {
+        let mut map = FxHashMap::<u16, (Vec<u8>, u16)>::default();
         let mut current_layer: Vec<u16> = (0..2_i32.pow(layers as u32) as usize)
             .map(|_| rng.gen::<u16>())
             .collect();
         let initial_layer = current_layer.clone();
         let mut next_layer: Vec<u16> = vec![];
 
-        let pb = ProgressBar::new(layers as u64);
-        pb.set_style(
-            ProgressStyle::with_template(
-                "[{elapsed_precise}] {bar:40.cyan/blue} {pos:>7}/{len:7} {msg}",
-            )
-            .unwrap()
-            .progress_chars("##-"),
-        );
-        pb.set_message("Generating funnel layers");
+        let pb = progress::bar(layers as u64);
+        pb.set_message("Generating funnel layers".to_string());
 
         for _l in 0..layers {
             for p in current_layer.chunks(2) {
                 // Ensures this is a full tree and has no loops/shortcuts
                 loop {
-                    let minifunnel = MiniFunnel::new(p[0], p[1]);
+                    let minifunnel = MiniFunnel::new(p[0], p[1], rng);
                     if !map.contains_key(&minifunnel.output_hash)
                         && !current_layer.contains(&minifunnel.output_hash)
                     {
@@ -133,7 +124,6 @@ impl Funnel {
             }
             current_layer = next_layer;
             next_layer = vec![];
-            //println!("Layer {}: {:?}", l + 1, current_layer);
             pb.inc(1);
         }
         pb.finish();
@@ -163,27 +153,27 @@ impl Funnel {
 }
 
 use super::challenge52::{hash, Crash};
+use crate::fxhash::FxHashMap;
+use crate::progress;
+use crate::report::say;
 use crate::{set7::challenge52::hash_full, utils::*};
-use indicatif::{ProgressBar, ProgressStyle};
-use rand::{thread_rng, Rng};
-use std::collections::HashMap;
+use rand::Rng;
 
-pub fn main() -> Result<()> {
-    nost(12)
+pub fn main() -> Result<Report> {
+    nost(12, &mut crate::utils::rng())
 }
 
-fn nost(funnel_depth: usize) -> Result<()> {
-    let funnel = Funnel::new(funnel_depth);
-    let mut rng = thread_rng();
+fn nost(funnel_depth: usize, rng: &mut impl Rng) -> Result<Report> {
+    let funnel = Funnel::new(funnel_depth, rng);
 
-    println!(
+    say!(
         "I can predict the random bytes to come out of the hat, the hash of my prediction is: {}, and will be {} blocks long",
         funnel.final_hash,
         funnel_depth+3
     );
 
     let answer: Vec<u8> = (0..16).map(|_| rng.gen::<u8>()).collect();
-    println!("The bytes from the hat are: {:?}", answer);
+    say!("The bytes from the hat are: {:?}", answer);
 
     let mut forged_answer = answer.clone();
     // Padding
@@ -195,22 +185,17 @@ fn nost(funnel_depth: usize) -> Result<()> {
     let mut seq = funnel.get_sequence(forged_hash);
 
     let mut loop_num = 1;
-    let spinner = ProgressBar::new_spinner();
+    let spinner = progress::spinner();
     spinner.set_message(format!("Retro-diction, loop {}", loop_num));
     loop {
         if let Some(x) = seq {
             spinner.finish();
             forged_answer.extend_from_slice(&x);
             let forged_hash_final = hash_full::<Crash>(&forged_answer, 0);
-            println!("This is my prediction (the first block, followed by one of zero padding, and the rest is just to, uh, obfuscate my prediction):");
-            println!("{:?}", forged_answer);
-            println!("Sequence hash: {}", forged_hash_final);
-            println!("Blocks: {}", forged_answer.len() / 16);
-
-            assert_eq!(&forged_answer[..16], &answer[..16]);
-            assert_eq!(forged_hash_final, funnel.final_hash);
-            assert_eq!(forged_answer.len() / 16, funnel_depth + 3);
-
+            say!("This is my prediction (the first block, followed by one of zero padding, and the rest is just to, uh, obfuscate my prediction):");
+            say!("{:?}", forged_answer);
+            say!("Sequence hash: {}", forged_hash_final);
+            say!("Blocks: {}", forged_answer.len() / 16);
             break;
         }
         let random_bytes: Vec<u8> = (0..16).map(|_| rng.gen::<u8>()).collect();
@@ -222,7 +207,17 @@ fn nost(funnel_depth: usize) -> Result<()> {
         spinner.set_message(format!("Retro-diction, loop {}", loop_num));
     }
 
-    Ok(())
+    let forged_hash_final = hash_full::<Crash>(&forged_answer, 0);
+    Ok(Report::new()
+        .check("first block", &answer[..16], &forged_answer[..16])
+        .check(
+            "hash",
+            u32::from(funnel.final_hash),
+            u32::from(forged_hash_final),
+        )
+        .check("blocks", funnel_depth + 3, forged_answer.len() / 16)
+        .recovered("prediction", forged_answer)
+        .count("loops", loop_num))
 }
 
 #[cfg(test)]
@@ -233,7 +228,7 @@ mod tests {
     fn pair() {
         let a = 15_u16;
         let b = 16_u16;
-        let f = MiniFunnel::new(a, b);
+        let f = MiniFunnel::new(a, b, &mut rand::thread_rng());
 
         let hash_a = hash::<Crash>(&f.block_a, a);
         let hash_b = hash::<Crash>(&f.block_b, b);
@@ -244,6 +239,6 @@ mod tests {
 
     #[test]
     fn nostradamus() {
-        nost(10).unwrap();
+        nost(10, &mut rand::thread_rng()).unwrap();
     }
 }
@@ -43,10 +43,12 @@
 use std::collections::HashSet;
 
 use hex;
-use indicatif::ProgressBar;
-use rand::{rngs::StdRng, Rng, SeedableRng};
+use rand::Rng;
 
-use crate::{set4::challenge30::md4_hash, utils::*};
+use crate::hash::md4::{block, ff, ff_inverse, gg, md4_hash, words, INIT};
+use crate::progress;
+use crate::report::say;
+use crate::utils::*;
 
 // Round 1 conditions
 //
@@ -100,39 +102,27 @@ impl Bittable for u32 {
     }
 }
 
-// Copying a lot from challenge 30 where we implemented MD4 (foreshadowing!)
-
 fn check_round1(data: &[u8]) -> bool {
     // Split the data into the appropriate chunks, again
-    let m: Vec<u32> = data
-        .chunks(4)
-        .map(|x| {
-            let y: Vec<u8> = x.iter().copied().rev().collect();
-            u8s_to_u32(&y)
-        })
-        .collect();
-
-    let mut a: u32 = 0x67452301;
-    let mut b: u32 = 0xefcdab89;
-    let mut c: u32 = 0x98badcfe;
-    let mut d: u32 = 0x10325476;
-    let x: Vec<u32> = m.to_vec();
-
-    a = a.wrapping_add(round1(b, c, d, x[0])).rotate_left(3);
+    let x = words(data);
+
+    let [mut a, mut b, mut c, mut d] = INIT;
+
+    a = ff(a, b, c, d, x[0], 3);
     assert_eq!(a.get_bit(6), b.get_bit(6));
 
-    d = d.wrapping_add(round1(a, b, c, x[1])).rotate_left(7);
+    d = ff(d, a, b, c, x[1], 7);
     assert_eq!(d.get_bit(6), 0);
     assert_eq!(d.get_bit(7), a.get_bit(7));
     assert_eq!(d.get_bit(10), a.get_bit(10));
 
-    c = c.wrapping_add(round1(d, a, b, x[2])).rotate_left(11);
+    c = ff(c, d, a, b, x[2], 11);
     assert_eq!(c.get_bit(6), 1);
     assert_eq!(c.get_bit(7), 1);
     assert_eq!(c.get_bit(10), 0);
     assert_eq!(c.get_bit(25), d.get_bit(25));
 
-    b = b.wrapping_add(round1(c, d, a, x[3])).rotate_left(19);
+    b = ff(b, c, d, a, x[3], 19);
 
     assert_eq!(b.get_bit(6), 1);
     assert_eq!(b.get_bit(7), 0);
@@ -140,14 +130,14 @@ fn check_round1(data: &[u8]) -> bool {
     assert_eq!(b.get_bit(25), 0);
 
     // Now update a
-    a = a.wrapping_add(round1(b, c, d, x[4])).rotate_left(3);
+    a = ff(a, b, c, d, x[4], 3);
     // And check that the condition applies
     assert_eq!(a.get_bit(7), 1);
     assert_eq!(a.get_bit(10), 1);
     assert_eq!(a.get_bit(25), 0);
     assert_eq!(a.get_bit(13), b.get_bit(13));
 
-    d = d.wrapping_add(round1(a, b, c, x[5])).rotate_left(7);
+    d = ff(d, a, b, c, x[5], 7);
     // And check conditions
     assert_eq!(d.get_bit(13), 0);
     assert_eq!(d.get_bit(18), a.get_bit(18));
@@ -156,7 +146,7 @@ fn check_round1(data: &[u8]) -> bool {
     assert_eq!(d.get_bit(21), a.get_bit(21));
     assert_eq!(d.get_bit(25), 1);
 
-    c = c.wrapping_add(round1(d, a, b, x[6])).rotate_left(11);
+    c = ff(c, d, a, b, x[6], 11);
     assert_eq!(c.get_bit(12), d.get_bit(12));
     assert_eq!(c.get_bit(13), 0);
     assert_eq!(c.get_bit(14), d.get_bit(14));
@@ -165,7 +155,7 @@ fn check_round1(data: &[u8]) -> bool {
     assert_eq!(c.get_bit(20), 1);
     assert_eq!(c.get_bit(21), 0);
 
-    b = b.wrapping_add(round1(c, d, a, x[7])).rotate_left(19);
+    b = ff(b, c, d, a, x[7], 19);
 
     assert_eq!(b.get_bit(12), 1);
     assert_eq!(b.get_bit(13), 1);
@@ -176,7 +166,7 @@ fn check_round1(data: &[u8]) -> bool {
     assert_eq!(b.get_bit(20), 0);
     assert_eq!(b.get_bit(21), 0);
 
-    a = a.wrapping_add(round1(b, c, d, x[8])).rotate_left(3);
+    a = ff(a, b, c, d, x[8], 3);
     assert_eq!(a.get_bit(12), 1);
     assert_eq!(a.get_bit(13), 1);
     assert_eq!(a.get_bit(14), 1);
@@ -188,7 +178,7 @@ fn check_round1(data: &[u8]) -> bool {
     assert_eq!(a.get_bit(22), b.get_bit(22));
     assert_eq!(a.get_bit(25), b.get_bit(25));
 
-    d = d.wrapping_add(round1(a, b, c, x[9])).rotate_left(7);
+    d = ff(d, a, b, c, x[9], 7);
     assert_eq!(d.get_bit(12), 1);
     assert_eq!(d.get_bit(13), 1);
     assert_eq!(d.get_bit(14), 1);
@@ -200,7 +190,7 @@ fn check_round1(data: &[u8]) -> bool {
     assert_eq!(d.get_bit(25), 1);
     assert_eq!(d.get_bit(29), a.get_bit(29));
 
-    c = c.wrapping_add(round1(d, a, b, x[10])).rotate_left(11);
+    c = ff(c, d, a, b, x[10], 11);
     assert_eq!(c.get_bit(16), 1);
     assert_eq!(c.get_bit(19), 0);
     assert_eq!(c.get_bit(20), 0);
@@ -210,7 +200,7 @@ fn check_round1(data: &[u8]) -> bool {
     assert_eq!(c.get_bit(29), 1);
     assert_eq!(c.get_bit(31), d.get_bit(31));
 
-    b = b.wrapping_add(round1(c, d, a, x[11])).rotate_left(19);
+    b = ff(b, c, d, a, x[11], 19);
     assert_eq!(b.get_bit(19), 0);
     assert_eq!(b.get_bit(20), 1);
     assert_eq!(b.get_bit(21), 1);
@@ -219,7 +209,7 @@ fn check_round1(data: &[u8]) -> bool {
     assert_eq!(b.get_bit(29), 0);
     assert_eq!(b.get_bit(31), 0);
 
-    a = a.wrapping_add(round1(b, c, d, x[12])).rotate_left(3);
+    a = ff(a, b, c, d, x[12], 3);
     assert_eq!(a.get_bit(22), 0);
     assert_eq!(a.get_bit(25), 0);
     assert_eq!(a.get_bit(26), b.get_bit(26));
@@ -227,7 +217,7 @@ fn check_round1(data: &[u8]) -> bool {
     assert_eq!(a.get_bit(29), 1);
     assert_eq!(a.get_bit(31), 0);
 
-    d = d.wrapping_add(round1(a, b, c, x[13])).rotate_left(7);
+    d = ff(d, a, b, c, x[13], 7);
     assert_eq!(d.get_bit(22), 0);
     assert_eq!(d.get_bit(25), 0);
     assert_eq!(d.get_bit(26), 1);
@@ -235,7 +225,7 @@ fn check_round1(data: &[u8]) -> bool {
     assert_eq!(d.get_bit(29), 0);
     assert_eq!(d.get_bit(31), 1);
 
-    c = c.wrapping_add(round1(d, a, b, x[14])).rotate_left(11);
+    c = ff(c, d, a, b, x[14], 11);
     assert_eq!(c.get_bit(18), d.get_bit(18));
     assert_eq!(c.get_bit(22), 1);
     assert_eq!(c.get_bit(25), 1);
@@ -246,7 +236,7 @@ fn check_round1(data: &[u8]) -> bool {
     // b4 b4[18] = 0, b4[25] = c4[25] = 1, b4[26] = 1, b4[28] = 1, b4[29] = 0
 
     // Set and verify
-    b = b.wrapping_add(round1(c, d, a, x[15])).rotate_left(19);
+    b = ff(b, c, d, a, x[15], 19);
     assert_eq!(b.get_bit(18), 0);
     assert_eq!(b.get_bit(25), c.get_bit(25));
     assert_eq!(b.get_bit(26), 1);
@@ -257,52 +247,42 @@ fn check_round1(data: &[u8]) -> bool {
 }
 pub fn massage_round1(data: &[u8]) -> Vec<u8> {
     // Split the data into the appropriate chunks, again
-    let m: Vec<u32> = data
-        .chunks(4)
-        .map(|x| {
-            let y: Vec<u8> = x.iter().copied().rev().collect();
-            u8s_to_u32(&y)
-        })
-        .collect();
-
-    let mut a: u32 = 0x67452301;
-    let mut b: u32 = 0xefcdab89;
-    let mut c: u32 = 0x98badcfe;
-    let mut d: u32 = 0x10325476;
-    let mut x: Vec<u32> = m.to_vec();
+    let mut x = words(data);
+
+    let [mut a, mut b, mut c, mut d] = INIT;
     // Round 1
     // In the paper ai is (4i-3)th step i.e. a2 is o=4, and so life is easier than it has
     // to be!
 
     // calculate the new value for a[1] in the normal fashion
-    let mut a1: u32 = a.wrapping_add(f(b, c, d).wrapping_add(x[0])).rotate_left(3);
+    let mut a1: u32 = ff(a, b, c, d, x[0], 3);
     // a1 | a[1][6] = b[0][6]
     a1.set_bit(6, &b.get_bit(6));
-    x[0] = a1.rotate_right(3).wrapping_sub(a).wrapping_sub(f(b, c, d));
+    x[0] = ff_inverse(a1, a, b, c, d, 3);
 
     // Now update a
-    a = a.wrapping_add(round1(b, c, d, x[0])).rotate_left(3);
+    a = ff(a, b, c, d, x[0], 3);
     // And check that the condition applies
     assert_eq!(a.get_bit(6), b.get_bit(6));
 
     // d1 | d[1][6] = 0, d[1][7] = a[1][7], d[1][10] = a[1][10]
-    let mut d1 = d.wrapping_add(f(a, b, c)).wrapping_add(x[1]).rotate_left(7);
+    let mut d1 = ff(d, a, b, c, x[1], 7);
     // Correct the bits
     d1.set_bit(6, &0);
     d1.set_bit(7, &a1.get_bit(7));
     d1.set_bit(10, &a1.get_bit(10));
 
-    x[1] = d1.rotate_right(7).wrapping_sub(d).wrapping_sub(f(a, b, c));
+    x[1] = ff_inverse(d1, d, a, b, c, 7);
 
     {
-        let d1 = d.wrapping_add(round1(a, b, c, x[1])).rotate_left(7);
+        let d1 = ff(d, a, b, c, x[1], 7);
         assert_eq!(d1.get_bit(6), 0);
         assert_eq!(d1.get_bit(7), a1.get_bit(7));
         assert_eq!(d1.get_bit(10), a1.get_bit(10));
     }
 
     // Now update d for real
-    d = d.wrapping_add(round1(a, b, c, x[1])).rotate_left(7);
+    d = ff(d, a, b, c, x[1], 7);
     // And check conditions
     assert_eq!(d.get_bit(6), 0);
     assert_eq!(d.get_bit(7), a.get_bit(7));
@@ -310,33 +290,33 @@ pub fn massage_round1(data: &[u8]) -> Vec<u8> {
 
     // c1 | c[1][6] = 1, c[1][7] = 1, c[1][10] = 0, c[1][25] = d[1][25]
 
-    let mut c1 = c.wrapping_add(round1(d, a, b, x[2])).rotate_left(11);
+    let mut c1 = ff(c, d, a, b, x[2], 11);
 
     c1.set_bit(6, &1);
     c1.set_bit(7, &1);
     c1.set_bit(10, &0);
     c1.set_bit(25, &d.get_bit(25));
 
-    x[2] = c1.rotate_right(11).wrapping_sub(c).wrapping_sub(f(d, a, b));
+    x[2] = ff_inverse(c1, c, d, a, b, 11);
 
     // Update and check that conditions hold
-    c = c.wrapping_add(round1(d, a, b, x[2])).rotate_left(11);
+    c = ff(c, d, a, b, x[2], 11);
     assert_eq!(c.get_bit(6), 1);
     assert_eq!(c.get_bit(7), 1);
     assert_eq!(c.get_bit(10), 0);
     assert_eq!(c.get_bit(25), d.get_bit(25));
 
     // b1 | b[1][6] = 1, b[1][7] = 0, b[1][10] = 0, b[1][25] = 0
-    let mut b1 = b.wrapping_add(round1(c, d, a, x[3])).rotate_left(19);
+    let mut b1 = ff(b, c, d, a, x[3], 19);
     b1.set_bit(6, &1);
     b1.set_bit(7, &0);
     b1.set_bit(10, &0);
     b1.set_bit(25, &0);
 
-    x[3] = b1.rotate_right(19).wrapping_sub(b).wrapping_sub(f(c, d, a));
+    x[3] = ff_inverse(b1, b, c, d, a, 19);
 
     // Set and verify
-    b = b.wrapping_add(round1(c, d, a, x[3])).rotate_left(19);
+    b = ff(b, c, d, a, x[3], 19);
 
     assert_eq!(b.get_bit(6), 1);
     assert_eq!(b.get_bit(7), 0);
@@ -344,16 +324,16 @@ pub fn massage_round1(data: &[u8]) -> Vec<u8> {
     assert_eq!(b.get_bit(25), 0);
 
     // calculate the new value for a[2] in the normal fashion
-    let mut a2: u32 = a.wrapping_add(f(b, c, d).wrapping_add(x[4])).rotate_left(3);
+    let mut a2: u32 = ff(a, b, c, d, x[4], 3);
     // a2 a[2][7] = 1, a[2][10] = 1, a[2][25] = 0, a[2][13] = b[1][13]
     a2.set_bit(7, &1);
     a2.set_bit(10, &1);
     a2.set_bit(25, &0);
     a2.set_bit(13, &b.get_bit(13));
-    x[4] = a2.rotate_right(3).wrapping_sub(a).wrapping_sub(f(b, c, d));
+    x[4] = ff_inverse(a2, a, b, c, d, 3);
 
     // Now update a
-    a = a.wrapping_add(round1(b, c, d, x[4])).rotate_left(3);
+    a = ff(a, b, c, d, x[4], 3);
     // And check that the condition applies
     assert_eq!(a.get_bit(7), 1);
     assert_eq!(a.get_bit(10), 1);
@@ -362,7 +342,7 @@ pub fn massage_round1(data: &[u8]) -> Vec<u8> {
 
     // d2 d2[13] = 0, d2[18] = a2[18], d2[19] = a2[19], d2[20] = a2[20],
     // d2[21] = a2[21], d2[25] = 1
-    let mut d2 = d.wrapping_add(f(a, b, c)).wrapping_add(x[5]).rotate_left(7);
+    let mut d2 = ff(d, a, b, c, x[5], 7);
     // Correct the bits
     d2.set_bit(13, &0);
     d2.set_bit(18, &a.get_bit(18));
@@ -371,10 +351,10 @@ pub fn massage_round1(data: &[u8]) -> Vec<u8> {
     d2.set_bit(21, &a.get_bit(21));
     d2.set_bit(25, &1);
 
-    x[5] = d2.rotate_right(7).wrapping_sub(d).wrapping_sub(f(a, b, c));
+    x[5] = ff_inverse(d2, d, a, b, c, 7);
 
     // Now update d for real
-    d = d.wrapping_add(round1(a, b, c, x[5])).rotate_left(7);
+    d = ff(d, a, b, c, x[5], 7);
     // And check conditions
     assert_eq!(d.get_bit(13), 0);
     assert_eq!(d.get_bit(18), a.get_bit(18));
@@ -385,7 +365,7 @@ pub fn massage_round1(data: &[u8]) -> Vec<u8> {
 
     // c2 c2[12] = d2[12], c2[13] = 0, c2[14] = d2[14], c2[18] = 0, c2[19] = 0,
     // c2[20] = 1, c2[21] = 0
-    let mut c2 = c.wrapping_add(round1(d, a, b, x[6])).rotate_left(11);
+    let mut c2 = ff(c, d, a, b, x[6], 11);
 
     c2.set_bit(12, &d.get_bit(12));
     c2.set_bit(13, &0);
@@ -395,10 +375,10 @@ pub fn massage_round1(data: &[u8]) -> Vec<u8> {
     c2.set_bit(20, &1);
     c2.set_bit(21, &0);
 
-    x[6] = c2.rotate_right(11).wrapping_sub(c).wrapping_sub(f(d, a, b));
+    x[6] = ff_inverse(c2, c, d, a, b, 11);
 
     // Update and check that conditions hold
-    c = c.wrapping_add(round1(d, a, b, x[6])).rotate_left(11);
+    c = ff(c, d, a, b, x[6], 11);
     assert_eq!(c.get_bit(12), d.get_bit(12));
     assert_eq!(c.get_bit(13), 0);
     assert_eq!(c.get_bit(14), d.get_bit(14));
@@ -409,7 +389,7 @@ pub fn massage_round1(data: &[u8]) -> Vec<u8> {
 
     // b2 b2[12] = 1, b2[13] = 1, b2[14] = 0, b2[16] = c2[16], b2[18] = 0,
     // b2[19] = 0, b2[20] = 0 b2[21] = 0
-    let mut b2 = b.wrapping_add(round1(c, d, a, x[7])).rotate_left(19);
+    let mut b2 = ff(b, c, d, a, x[7], 19);
     b2.set_bit(12, &1);
     b2.set_bit(13, &1);
     b2.set_bit(14, &0);
@@ -419,10 +399,10 @@ pub fn massage_round1(data: &[u8]) -> Vec<u8> {
     b2.set_bit(20, &0);
     b2.set_bit(21, &0);
 
-    x[7] = b2.rotate_right(19).wrapping_sub(b).wrapping_sub(f(c, d, a));
+    x[7] = ff_inverse(b2, b, c, d, a, 19);
 
     // Set and verify
-    b = b.wrapping_add(round1(c, d, a, x[7])).rotate_left(19);
+    b = ff(b, c, d, a, x[7], 19);
 
     assert_eq!(b.get_bit(12), 1);
     assert_eq!(b.get_bit(13), 1);
@@ -437,7 +417,7 @@ pub fn massage_round1(data: &[u8]) -> Vec<u8> {
     // a3[19] = 0, a3[20] = 0, a3[22] = b2[22]
     // a3[21] = 1, a3[25] = b2[25]
 
-    let mut a3: u32 = a.wrapping_add(f(b, c, d).wrapping_add(x[8])).rotate_left(3);
+    let mut a3: u32 = ff(a, b, c, d, x[8], 3);
     a3.set_bit(12, &1);
     a3.set_bit(13, &1);
     a3.set_bit(14, &1);
@@ -449,10 +429,10 @@ pub fn massage_round1(data: &[u8]) -> Vec<u8> {
     a3.set_bit(22, &b.get_bit(22));
     a3.set_bit(25, &b.get_bit(25));
 
-    x[8] = a3.rotate_right(3).wrapping_sub(a).wrapping_sub(f(b, c, d));
+    x[8] = ff_inverse(a3, a, b, c, d, 3);
 
     // Now update a
-    a = a.wrapping_add(round1(b, c, d, x[8])).rotate_left(3);
+    a = ff(a, b, c, d, x[8], 3);
     // And check that the condition applies
 
     assert_eq!(a.get_bit(12), 1);
@@ -469,7 +449,7 @@ pub fn massage_round1(data: &[u8]) -> Vec<u8> {
     // d3 d3[12] = 1, d3[13] = 1, d3[14] = 1, d3[16] = 0, d3[19] = 0,
     // d3[20] = 1, d3[21] = 1, d3[22] = 0, d3[25] = 1, d3[29] = a3[29]
 
-    let mut d3 = d.wrapping_add(f(a, b, c)).wrapping_add(x[9]).rotate_left(7);
+    let mut d3 = ff(d, a, b, c, x[9], 7);
     // Correct the bits
     d3.set_bit(12, &1);
     d3.set_bit(13, &1);
@@ -482,10 +462,10 @@ pub fn massage_round1(data: &[u8]) -> Vec<u8> {
     d3.set_bit(25, &1);
     d3.set_bit(29, &a.get_bit(29));
 
-    x[9] = d3.rotate_right(7).wrapping_sub(d).wrapping_sub(f(a, b, c));
+    x[9] = ff_inverse(d3, d, a, b, c, 7);
 
     // Now update d for real
-    d = d.wrapping_add(round1(a, b, c, x[9])).rotate_left(7);
+    d = ff(d, a, b, c, x[9], 7);
     // And check conditions
 
     assert_eq!(d.get_bit(12), 1);
@@ -501,7 +481,7 @@ pub fn massage_round1(data: &[u8]) -> Vec<u8> {
 
     // c3 c3[16] = 1, c3[19] = 0, c3[20] = 0, c3[21] = 0,
     // c3[22] = 0, c3[25] = 0, c3[29] = 1, c3[31] = d3[31]
-    let mut c3 = c.wrapping_add(round1(d, a, b, x[10])).rotate_left(11);
+    let mut c3 = ff(c, d, a, b, x[10], 11);
 
     c3.set_bit(16, &1);
     c3.set_bit(19, &0);
@@ -512,10 +492,10 @@ pub fn massage_round1(data: &[u8]) -> Vec<u8> {
     c3.set_bit(29, &1);
     c3.set_bit(31, &d.get_bit(31));
 
-    x[10] = c3.rotate_right(11).wrapping_sub(c).wrapping_sub(f(d, a, b));
+    x[10] = ff_inverse(c3, c, d, a, b, 11);
 
     // Update and check that conditions hold
-    c = c.wrapping_add(round1(d, a, b, x[10])).rotate_left(11);
+    c = ff(c, d, a, b, x[10], 11);
     assert_eq!(c.get_bit(16), 1);
     assert_eq!(c.get_bit(19), 0);
     assert_eq!(c.get_bit(20), 0);
@@ -527,7 +507,7 @@ pub fn massage_round1(data: &[u8]) -> Vec<u8> {
 
     // b3 b3[19] = 0, b3[20] = 1, b3[21] = 1, b3[22] = c3[22],
     // b3[25] = 1, b3[29] = 0, b3[31] = 0
-    let mut b3 = b.wrapping_add(round1(c, d, a, x[11])).rotate_left(19);
+    let mut b3 = ff(b, c, d, a, x[11], 19);
 
     b3.set_bit(19, &0);
     b3.set_bit(20, &1);
@@ -537,10 +517,10 @@ pub fn massage_round1(data: &[u8]) -> Vec<u8> {
     b3.set_bit(29, &0);
     b3.set_bit(31, &0);
 
-    x[11] = b3.rotate_right(19).wrapping_sub(b).wrapping_sub(f(c, d, a));
+    x[11] = ff_inverse(b3, b, c, d, a, 19);
 
     // Set and verify
-    b = b.wrapping_add(round1(c, d, a, x[11])).rotate_left(19);
+    b = ff(b, c, d, a, x[11], 19);
     assert_eq!(b.get_bit(19), 0);
     assert_eq!(b.get_bit(20), 1);
     assert_eq!(b.get_bit(21), 1);
@@ -551,9 +531,7 @@ pub fn massage_round1(data: &[u8]) -> Vec<u8> {
 
     // a4 a4[22] = 0, a4[25] = 0, a4[26] = b3[26], a4[28] = b3[28],
     // a4[29] = 1, a4[31] = 0
-    let mut a4: u32 = a
-        .wrapping_add(f(b, c, d).wrapping_add(x[12]))
-        .rotate_left(3);
+    let mut a4: u32 = ff(a, b, c, d, x[12], 3);
     a4.set_bit(22, &0);
     a4.set_bit(25, &0);
     a4.set_bit(26, &b.get_bit(26));
@@ -561,10 +539,10 @@ pub fn massage_round1(data: &[u8]) -> Vec<u8> {
     a4.set_bit(29, &1);
     a4.set_bit(31, &0);
 
-    x[12] = a4.rotate_right(3).wrapping_sub(a).wrapping_sub(f(b, c, d));
+    x[12] = ff_inverse(a4, a, b, c, d, 3);
 
     // Now update a
-    a = a.wrapping_add(round1(b, c, d, x[12])).rotate_left(3);
+    a = ff(a, b, c, d, x[12], 3);
     // And check that the condition applies
     assert_eq!(a.get_bit(22), 0);
     assert_eq!(a.get_bit(25), 0);
@@ -575,10 +553,7 @@ pub fn massage_round1(data: &[u8]) -> Vec<u8> {
 
     // d4 d4[22] = 0, d4[25] = 0, d4[26] = 1, d4[28] = 1,
     // d4[29] = 0, d4[31] = 1
-    let mut d4 = d
-        .wrapping_add(f(a, b, c))
-        .wrapping_add(x[13])
-        .rotate_left(7);
+    let mut d4 = ff(d, a, b, c, x[13], 7);
     // Correct the bits
     d4.set_bit(22, &0);
     d4.set_bit(25, &0);
@@ -587,10 +562,10 @@ pub fn massage_round1(data: &[u8]) -> Vec<u8> {
     d4.set_bit(29, &0);
     d4.set_bit(31, &1);
 
-    x[13] = d4.rotate_right(7).wrapping_sub(d).wrapping_sub(f(a, b, c));
+    x[13] = ff_inverse(d4, d, a, b, c, 7);
 
     // Now update d for real
-    d = d.wrapping_add(round1(a, b, c, x[13])).rotate_left(7);
+    d = ff(d, a, b, c, x[13], 7);
 
     assert_eq!(d.get_bit(22), 0);
     assert_eq!(d.get_bit(25), 0);
@@ -601,7 +576,7 @@ pub fn massage_round1(data: &[u8]) -> Vec<u8> {
 
     // c4 c4[18] = d4[18], c4[22] = 1, c4[25] = 1, c4[26] = 0,
     // c4[28] = 0, c4[29] = 0
-    let mut c4 = c.wrapping_add(round1(d, a, b, x[14])).rotate_left(11);
+    let mut c4 = ff(c, d, a, b, x[14], 11);
 
     c4.set_bit(18, &d.get_bit(18));
     c4.set_bit(22, &1);
@@ -610,10 +585,10 @@ pub fn massage_round1(data: &[u8]) -> Vec<u8> {
     c4.set_bit(28, &0);
     c4.set_bit(29, &0);
 
-    x[14] = c4.rotate_right(11).wrapping_sub(c).wrapping_sub(f(d, a, b));
+    x[14] = ff_inverse(c4, c, d, a, b, 11);
 
     // Update and check that conditions hold
-    c = c.wrapping_add(round1(d, a, b, x[14])).rotate_left(11);
+    c = ff(c, d, a, b, x[14], 11);
     assert_eq!(c.get_bit(18), d.get_bit(18));
     assert_eq!(c.get_bit(22), 1);
     assert_eq!(c.get_bit(25), 1);
@@ -623,7 +598,7 @@ pub fn massage_round1(data: &[u8]) -> Vec<u8> {
 
     // b4 b4[18] = 0, b4[25] = c4[25] = 1, b4[26] = 1, b4[28] = 1, b4[29] = 0
 
-    let mut b4 = b.wrapping_add(round1(c, d, a, x[15])).rotate_left(19);
+    let mut b4 = ff(b, c, d, a, x[15], 19);
 
     b4.set_bit(18, &0);
     b4.set_bit(25, &c.get_bit(25));
@@ -631,42 +606,27 @@ pub fn massage_round1(data: &[u8]) -> Vec<u8> {
     b4.set_bit(28, &1);
     b4.set_bit(29, &0);
 
-    x[15] = b4.rotate_right(19).wrapping_sub(b).wrapping_sub(f(c, d, a));
+    x[15] = ff_inverse(b4, b, c, d, a, 19);
 
     // Set and verify
-    b = b.wrapping_add(round1(c, d, a, x[15])).rotate_left(19);
+    b = ff(b, c, d, a, x[15], 19);
     assert_eq!(b.get_bit(18), 0);
     assert_eq!(b.get_bit(25), c.get_bit(25));
     assert_eq!(b.get_bit(26), 1);
     assert_eq!(b.get_bit(28), 1);
     assert_eq!(b.get_bit(29), 0);
 
-    let mut massaged_block: Vec<u8> = vec![];
-    for b in x[..16].iter() {
-        for byte in u32_to_u8s(*b).iter().rev() {
-            massaged_block.push(*byte);
-        }
-    }
-    massaged_block
+    block(&x)
 }
 pub fn massage_d5_round2(data: &[u8], tofix: Corrections) -> Vec<u8> {
-    let m: Vec<u32> = data
-        .chunks(4)
-        .map(|x| {
-            let y: Vec<u8> = x.iter().copied().rev().collect();
-            u8s_to_u32(&y)
-        })
-        .collect();
+    let m = words(data);
 
     // Reset to canonical values
-    let mut a: u32 = 0x67452301;
-    let mut a_p: u32 = 0x67452301;
-    let mut b: u32 = 0xefcdab89;
-    let mut c: u32 = 0x98badcfe;
-    let mut d: u32 = 0x10325476;
-
-    let x: Vec<u32> = m[..16].to_vec();
-    let mut x_p: Vec<u32> = m[..16].to_vec();
+    let [mut a, mut b, mut c, mut d] = INIT;
+    let mut a_p = a;
+
+    let x = m;
+    let mut x_p = m;
     // Round 1
 
     match tofix {
@@ -695,83 +655,56 @@ pub fn massage_d5_round2(data: &[u8], tofix: Corrections) -> Vec<u8> {
     }
     // First have to do a complete round
 
-    a = a.wrapping_add(round1(b, c, d, x[0])).rotate_left(3);
-    a_p = a_p.wrapping_add(round1(b, c, d, x_p[0])).rotate_left(3);
+    a = ff(a, b, c, d, x[0], 3);
+    a_p = ff(a_p, b, c, d, x_p[0], 3);
 
-    d = d.wrapping_add(round1(a, b, c, x[1])).rotate_left(7);
-    c = c.wrapping_add(round1(d, a, b, x[2])).rotate_left(11);
-    b = b.wrapping_add(round1(c, d, a, x[3])).rotate_left(19);
+    d = ff(d, a, b, c, x[1], 7);
+    c = ff(c, d, a, b, x[2], 11);
+    b = ff(b, c, d, a, x[3], 19);
 
     // Onto round 1 part 2
     // i.e. a2
-    a = a.wrapping_add(round1(b, c, d, x[4])).rotate_left(3);
+    a = ff(a, b, c, d, x[4], 3);
     // This is where the first difference will occur,
     // as x[4] is what gets modified
     // a2'
-    a_p = a_p.wrapping_add(round1(b, c, d, x_p[4])).rotate_left(3);
+    a_p = ff(a_p, b, c, d, x_p[4], 3);
 
     // These modifications contain the changes to a2' and stop them propagating by only modifying
     // parts of the message which come later in the process
-    let d2 = d.wrapping_add(round1(a, b, c, x[5])).rotate_left(7);
-    x_p[5] = d2
-        .rotate_right(7)
-        .wrapping_sub(d)
-        .wrapping_sub(f(a_p, b, c));
-    d = d.wrapping_add(round1(a_p, b, c, x_p[5])).rotate_left(7);
+    let d2 = ff(d, a, b, c, x[5], 7);
+    x_p[5] = ff_inverse(d2, d, a_p, b, c, 7);
+    d = ff(d, a_p, b, c, x_p[5], 7);
     assert_eq!(d2, d);
 
-    let c2 = c.wrapping_add(round1(d, a, b, x[6])).rotate_left(11);
-    x_p[6] = c2
-        .rotate_right(11)
-        .wrapping_sub(c)
-        .wrapping_sub(f(d, a_p, b));
-    c = c.wrapping_add(round1(d, a_p, b, x_p[6])).rotate_left(11);
+    let c2 = ff(c, d, a, b, x[6], 11);
+    x_p[6] = ff_inverse(c2, c, d, a_p, b, 11);
+    c = ff(c, d, a_p, b, x_p[6], 11);
     assert_eq!(c2, c);
 
-    let b2 = b.wrapping_add(round1(c, d, a, x[7])).rotate_left(19);
-    x_p[7] = b2
-        .rotate_right(19)
-        .wrapping_sub(b)
-        .wrapping_sub(f(c, d, a_p));
-    b = b.wrapping_add(round1(c, d, a_p, x_p[7])).rotate_left(19);
+    let b2 = ff(b, c, d, a, x[7], 19);
+    x_p[7] = ff_inverse(b2, b, c, d, a_p, 19);
+    b = ff(b, c, d, a_p, x_p[7], 19);
     assert_eq!(b2, b);
 
-    let a3 = a.wrapping_add(round1(b, c, d, x[8])).rotate_left(3);
-    x_p[8] = a3
-        .rotate_right(3)
-        .wrapping_sub(a_p)
-        .wrapping_sub(f(b, c, d));
+    let a3 = ff(a, b, c, d, x[8], 3);
+    x_p[8] = ff_inverse(a3, a_p, b, c, d, 3);
 
-    a = a_p.wrapping_add(round1(b, c, d, x_p[8])).rotate_left(3);
+    a = ff(a_p, b, c, d, x_p[8], 3);
     assert_eq!(a3, a);
 
-    let mut massaged_block: Vec<u8> = vec![];
-    for b in x_p[..16].iter() {
-        for byte in u32_to_u8s(*b).iter().rev() {
-            massaged_block.push(*byte);
-        }
-    }
-    massaged_block
+    block(&x_p)
 }
 
 pub fn massage_a5_round2(data: &[u8], tofix: Corrections) -> Vec<u8> {
-    let m: Vec<u32> = data
-        .chunks(4)
-        .map(|x| {
-            let y: Vec<u8> = x.iter().copied().rev().collect();
-            u8s_to_u32(&y)
-        })
-        .collect();
+    let m = words(data);
 
     // Reset to canonical values
-    let mut a: u32 = 0x67452301;
-    let mut a_p: u32 = 0x67452301;
-    let mut b: u32 = 0xefcdab89;
-    let mut c: u32 = 0x98badcfe;
-    let mut d: u32 = 0x10325476;
+    let [mut a, mut b, mut c, mut d] = INIT;
+    let mut a_p = a;
 
-    let x: Vec<u32> = m[..16].to_vec();
-    let mut x_p: Vec<u32> = m[..16].to_vec();
+    let x = m;
+    let mut x_p = m;
 
     // Round 1
     // Table 1, row 1
@@ -800,53 +733,35 @@ pub fn massage_a5_round2(data: &[u8], tofix: Corrections) -> Vec<u8> {
         _ => panic!("Trying to fix something we can't do here"),
     }
 
-    a = a.wrapping_add(round1(b, c, d, x[0])).rotate_left(3);
-    a_p = a_p.wrapping_add(round1(b, c, d, x_p[0])).rotate_left(3);
+    a = ff(a, b, c, d, x[0], 3);
+    a_p = ff(a_p, b, c, d, x_p[0], 3);
 
     // Table 1, row 2
-    let d1 = d.wrapping_add(round1(a, b, c, x[1])).rotate_left(7);
-    x_p[1] = d1
-        .rotate_right(7)
-        .wrapping_sub(d)
-        .wrapping_sub(f(a_p, b, c));
-    d = d.wrapping_add(round1(a_p, b, c, x_p[1])).rotate_left(7);
+    let d1 = ff(d, a, b, c, x[1], 7);
+    x_p[1] = ff_inverse(d1, d, a_p, b, c, 7);
+    d = ff(d, a_p, b, c, x_p[1], 7);
     assert_eq!(d, d1);
 
     // Table 1, row 3
-    let c1 = c.wrapping_add(round1(d, a, b, x[2])).rotate_left(11);
-    x_p[2] = c1
-        .rotate_right(11)
-        .wrapping_sub(c)
-        .wrapping_sub(f(d, a_p, b));
-    c = c.wrapping_add(round1(d, a_p, b, x_p[2])).rotate_left(11);
+    let c1 = ff(c, d, a, b, x[2], 11);
+    x_p[2] = ff_inverse(c1, c, d, a_p, b, 11);
+    c = ff(c, d, a_p, b, x_p[2], 11);
     assert_eq!(c, c1);
 
     // Table 1, row 4
 
-    let b1 = b.wrapping_add(round1(c, d, a, x[3])).rotate_left(19);
-    x_p[3] = b1
-        .rotate_right(19)
-        .wrapping_sub(b)
-        .wrapping_sub(f(c, d, a_p));
-    b = b.wrapping_add(round1(c, d, a_p, x_p[3])).rotate_left(19);
+    let b1 = ff(b, c, d, a, x[3], 19);
+    x_p[3] = ff_inverse(b1, b, c, d, a_p, 19);
+    b = ff(b, c, d, a_p, x_p[3], 19);
     assert_eq!(b, b1);
 
     // Table 1, row 5
-    let a2 = a.wrapping_add(round1(b, c, d, x[4])).rotate_left(3);
-    x_p[4] = a2
-        .rotate_right(3)
-        .wrapping_sub(a_p)
-        .wrapping_sub(f(b, c, d));
-    a = a.wrapping_add(round1(b, c, d, x[4])).rotate_left(3);
+    let a2 = ff(a, b, c, d, x[4], 3);
+    x_p[4] = ff_inverse(a2, a_p, b, c, d, 3);
+    a = ff(a, b, c, d, x[4], 3);
     assert_eq!(a, a2);
 
-    let mut massaged_block: Vec<u8> = vec![];
-    for b in x_p[..16].iter() {
-        for byte in u32_to_u8s(*b).iter().rev() {
-            massaged_block.push(*byte);
-        }
-    }
-    massaged_block
+    block(&x_p)
 }
 
 #[derive(Debug, PartialEq, Eq, Hash)]
@@ -880,32 +795,22 @@ pub enum Corrections {
 pub fn check_round2(data: &[u8]) -> Vec<Corrections> {
     let mut set = HashSet::new();
 
-    let m: Vec<u32> = data
-        .chunks(4)
-        .map(|x| {
-            let y: Vec<u8> = x.iter().copied().rev().collect();
-            u8s_to_u32(&y)
-        })
-        .collect();
+    let x = words(data);
 
     // Reset to canonical values
-    let mut a: u32 = 0x67452301;
-    let mut b: u32 = 0xefcdab89;
-    let mut c: u32 = 0x98badcfe;
-    let mut d: u32 = 0x10325476;
+    let [mut a, mut b, mut c, mut d] = INIT;
 
-    let x: Vec<u32> = m[..16].to_vec();
     // Round 1
     for &o in &[0, 4, 8, 12] {
-        a = a.wrapping_add(round1(b, c, d, x[o])).rotate_left(3);
-        d = d.wrapping_add(round1(a, b, c, x[o + 1])).rotate_left(7);
-        c = c.wrapping_add(round1(d, a, b, x[o + 2])).rotate_left(11);
-        b = b.wrapping_add(round1(c, d, a, x[o + 3])).rotate_left(19);
+        a = ff(a, b, c, d, x[o], 3);
+        d = ff(d, a, b, c, x[o + 1], 7);
+        c = ff(c, d, a, b, x[o + 2], 11);
+        b = ff(b, c, d, a, x[o + 3], 19);
     }
 
     // Now we must ensure the round two conditions
     // a5 a5[18] = c4[18], a5[25] = 1, a5[26] = 0, a5[28] = 1, a5[31] = 1
-    a = a.wrapping_add(round2(b, c, d, x[0])).rotate_left(3);
+    a = gg(a, b, c, d, x[0], 3);
 
     if a.get_bit(18) != c.get_bit(18) {
         set.insert(Corrections::A5i18);
@@ -926,7 +831,7 @@ pub fn check_round2(data: &[u8]) -> Vec<Corrections> {
     // d5 d5[18] = a5[18], d5[25] = b4[25], d5[26] = b4[26],
     // d5[28] = b4[28], d5[31] = b4[31]
 
-    d = d.wrapping_add(round2(a, b, c, x[4])).rotate_left(5);
+    d = gg(d, a, b, c, x[4], 5);
     if d.get_bit(18) != a.get_bit(18) {
         set.insert(Corrections::D5i18);
     }
@@ -946,7 +851,7 @@ pub fn check_round2(data: &[u8]) -> Vec<Corrections> {
     // c5 c5[25] = d5[25], c5[26] =  { return false; }
     // c5[29] = d5[29], c5[31] = d5[31]
 
-    c = c.wrapping_add(round2(d, a, b, x[8])).rotate_left(9);
+    c = gg(c, d, a, b, x[8], 9);
     if c.get_bit(25) != d.get_bit(25) {
         set.insert(Corrections::C5i25);
     }
@@ -963,7 +868,7 @@ pub fn check_round2(data: &[u8]) -> Vec<Corrections> {
         set.insert(Corrections::C5i31);
     }
 
-    b = b.wrapping_add(round2(c, d, a, x[12])).rotate_left(13);
+    b = gg(b, c, d, a, x[12], 13);
     if b.get_bit(28) != c.get_bit(28) {
         set.insert(Corrections::B5i28);
     }
@@ -975,7 +880,7 @@ pub fn check_round2(data: &[u8]) -> Vec<Corrections> {
     }
 
     // a6 a6[28] = 1, a6[31] = 1
-    a = a.wrapping_add(round2(b, c, d, x[1])).rotate_left(3);
+    a = gg(a, b, c, d, x[1], 3);
     if a.get_bit(28) != 1 {
         set.insert(Corrections::A6i28);
     }
@@ -984,13 +889,13 @@ pub fn check_round2(data: &[u8]) -> Vec<Corrections> {
     }
 
     // d6 d6[28] = b5[28]
-    d = d.wrapping_add(round2(a, b, c, x[5])).rotate_left(5);
+    d = gg(d, a, b, c, x[5], 5);
     if d.get_bit(28) != b.get_bit(28) {
         set.insert(Corrections::D6i28);
     }
 
     // c6 c6[28] = d6[28], c6[29] = d6[29] + 1, c6[31] = d6[31] + 1
-    c = c.wrapping_add(round2(d, a, b, x[9])).rotate_left(9);
+    c = gg(c, d, a, b, x[9], 9);
     if c.get_bit(28) != d.get_bit(28) {
         set.insert(Corrections::C6i28);
     }
@@ -1005,36 +910,14 @@ pub fn check_round2(data: &[u8]) -> Vec<Corrections> {
     set.into_iter().collect()
 }
 
-fn round1(x: u32, y: u32, z: u32, xx: u32) -> u32 {
-    f(x, y, z).wrapping_add(xx)
-}
-fn round2(x: u32, y: u32, z: u32, xx: u32) -> u32 {
-    g(x, y, z).wrapping_add(xx).wrapping_add(0x5a827999)
-}
-
-fn f(x: u32, y: u32, z: u32) -> u32 {
-    (x & y) | ((!x) & z)
-}
-fn g(x: u32, y: u32, z: u32) -> u32 {
-    (x & y) | (x & z) | (y & z)
-}
-
-fn generate_md4_candidate_pair(seed: Option<u64>) -> (Vec<u8>, Vec<u8>) {
-    let mut rng = match seed {
-        None => StdRng::from_entropy(),
-        Some(x) => StdRng::seed_from_u64(x),
-    };
-
+pub fn generate_md4_candidate_pair(rng: &mut impl Rng) -> (Vec<u8>, Vec<u8>) {
     let mut message: Vec<u8> = (0..64).map(|_| rng.gen::<u8>()).collect();
-    //println!("Pre-massage: {}", bytes_to_hex(&message));
 
     message = massage_round1(&message);
-    //println!("Post-massage: {}", bytes_to_hex(&message));
     // Round 1 massaging conditions should hold
     check_round1(&message);
     // Check round 2 problems
     let mut corrections = check_round2(&message);
-    //println!("Corrections: {:?}", corrections);
     // Now try to correct them
     // A5 corrections
     if corrections.contains(&Corrections::A5i18) {
@@ -1057,10 +940,8 @@ fn generate_md4_candidate_pair(seed: Option<u64>) -> (Vec<u8>, Vec<u8>) {
         message = massage_a5_round2(&message, Corrections::A5i31);
         corrections = check_round2(&message);
     }
-    //println!("Post-post-massage: {}", bytes_to_hex(&message));
     //message = massage_round1(&message);
     //corrections = check_round2(&message);
-    //println!("New corrections (A5s removed): {:?}", corrections);
     check_round1(&message);
 
     // D5 corrections
@@ -1068,7 +949,6 @@ fn generate_md4_candidate_pair(seed: Option<u64>) -> (Vec<u8>, Vec<u8>) {
         message = massage_d5_round2(&message, Corrections::D5i18);
         corrections = check_round2(&message);
     }
-    //println!("New corrections (D5i18 removed): {:?}", corrections);
     if corrections.contains(&Corrections::D5i25) {
         message = massage_d5_round2(&message, Corrections::D5i25);
         corrections = check_round2(&message);
@@ -1085,8 +965,6 @@ fn generate_md4_candidate_pair(seed: Option<u64>) -> (Vec<u8>, Vec<u8>) {
         message = massage_d5_round2(&message, Corrections::D5i31);
         //corrections = check_round2(&message);
     }
-    //println!("New corrections (A5s and D5s removed): {:?}", corrections);
-    //println!("Post-post-massage: {}", bytes_to_hex(&message));
     //message = massage_round1(&message);
     //corrections = check_round2(&message);
     message = massage_round1(&message);
@@ -1099,62 +977,56 @@ fn generate_md4_candidate_pair(seed: Option<u64>) -> (Vec<u8>, Vec<u8>) {
 
 fn flip_bits(message: &[u8]) -> Vec<u8> {
     // Split the data into the appropriate chunks, again
-    let mut x: Vec<u32> = message
-        .chunks(4)
-        .map(|x| {
-            let y: Vec<u8> = x.iter().copied().rev().collect();
-            u8s_to_u32(&y)
-        })
-        .collect();
+    let mut x = words(message);
 
     x[1] = x[1].wrapping_add(1 << 31);
     x[2] = x[2].wrapping_add((1 << 31) - (1 << 28));
     x[12] = x[12].wrapping_sub(1 << 16);
 
-    let mut output: Vec<u8> = vec![];
-    for b in x[..16].iter() {
-        for byte in u32_to_u8s(*b).iter().rev() {
-            output.push(*byte);
-        }
-    }
-    output
+    block(&x)
 }
 
-pub fn main() -> Result<()> {
-    let mut tries = 1;
-    let spinner = ProgressBar::new_spinner();
+pub fn main() -> Result<Report> {
+    let mut rng = crate::utils::rng();
+    let mut tries: u64 = 1;
+    let spinner = progress::spinner();
     spinner.set_message(format!("Tries: {}", tries));
     loop {
         spinner.set_message(format!("Tries: {}", tries));
         spinner.tick();
         tries += 1;
 
-        let (message, message_p) = generate_md4_candidate_pair(None);
+        let (message, message_p) = generate_md4_candidate_pair(&mut rng);
         let hash = md4_hash(&message);
         let hash_p = md4_hash(&message_p);
 
         if hash == hash_p && message != message_p {
             spinner.finish();
-            println!("Original: {}", bytes_to_hex(&message));
-            print!("Flipped:  ");
-            for (i, b) in message_p.iter().enumerate() {
-                match message[i] == *b {
-                    true => print!("{}", hex::encode([*b])),
-                    false => print!("\x1b[91m{}\x1b[0m", hex::encode([*b])),
-                }
-            }
-            println!();
-            println!("Hash: {}", hash);
-            break;
+            say!("Original: {}", bytes_to_hex(&message));
+            let flipped: String = message_p
+                .iter()
+                .zip(&message)
+                .map(|(b, original)| match b == original {
+                    true => hex::encode([*b]),
+                    false => format!("\x1b[91m{}\x1b[0m", hex::encode([*b])),
+                })
+                .collect();
+            say!("Flipped:  {flipped}");
+            say!("Hash: {}", hash);
+            return Ok(Report::new()
+                .recovered("message", message)
+                .recovered("flipped", message_p)
+                .check("hash", hash_p, hash)
+                .count("tries", tries - 1));
         }
     }
-
-    Ok(())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use rand::{rngs::StdRng, SeedableRng};
+
     #[test]
     fn get_set_bit() {
         // 100
@@ -1173,13 +1045,14 @@ mod tests {
     #[test]
     fn md4_collision() {
         let mut tries = 1;
-        let spinner = ProgressBar::new_spinner();
+        let spinner = progress::spinner();
         spinner.set_message(format!("Tries: {}", tries));
         while tries < 1_000_000_000 {
             spinner.set_message(format!("Tries: {}", tries));
             spinner.tick();
             // Seed that doesn't take very long
-            let (message, message_p) = generate_md4_candidate_pair(Some(150000 + tries));
+            let mut rng = StdRng::seed_from_u64(150000 + tries);
+            let (message, message_p) = generate_md4_candidate_pair(&mut rng);
             let hash = md4_hash(&message);
             let hash_p = md4_hash(&message_p);
 
@@ -54,16 +54,16 @@
 //!
 //! Build bias maps for a couple chosen indices (z16 and z32 are good) and decrypt the cookie.
 
+use crate::progress;
+use crate::report::say;
 use crate::utils::*;
 use base64::{engine::general_purpose, Engine as _};
-use indicatif::ProgressBar;
 use itertools::Itertools;
 use rand::Rng;
-use rand::{rngs::ThreadRng, thread_rng};
 use rc4::Rc4;
 use rc4::{KeyInit, StreamCipher};
 
-fn encrypt(message: &[u8], rng: &mut ThreadRng) -> Vec<u8> {
+fn encrypt(message: &[u8], rng: &mut impl Rng) -> Vec<u8> {
     let mut key = [0; 16];
     rng.fill(&mut key[..]);
 
@@ -73,12 +73,11 @@ fn encrypt(message: &[u8], rng: &mut ThreadRng) -> Vec<u8> {
     data.to_vec()
 }
 
-fn decode_pos_32(cookie: &[u8], offset: usize) -> u8 {
-    let spinner = ProgressBar::new_spinner();
+fn decode_pos_32(cookie: &[u8], offset: usize, rng: &mut impl Rng) -> u8 {
+    let spinner = progress::spinner();
 
     let mut message = vec![0_u8; offset + 2];
     message.extend_from_slice(cookie);
-    let mut rng = thread_rng();
 
     let mut byte_count = [0; 256];
     let mut counter = 1;
@@ -88,7 +87,7 @@ fn decode_pos_32(cookie: &[u8], offset: usize) -> u8 {
             spinner.set_message(format!("Offset {}: {}", offset, counter));
             spinner.tick();
         }
-        let b = encrypt(&message, &mut rng)[31] as usize;
+        let b = encrypt(&message, rng)[31] as usize;
         byte_count[b] += 1;
         counter += 1;
     }
@@ -100,17 +99,20 @@ fn decode_pos_32(cookie: &[u8], offset: usize) -> u8 {
     output
 }
 
-pub fn main() -> Result<()> {
+pub fn main() -> Result<Report> {
     let secret_base_64 = "QkUgU1VSRSBUTyBEUklOSyBZT1VSIE9WQUxUSU5F";
     let cookie = general_purpose::STANDARD.decode(secret_base_64).unwrap();
-    println!("Cookie length: {}", cookie.len());
+    say!("Cookie length: {}", cookie.len());
 
     // Length of cookie is 30, so we can always target byte 31 (position 32)
-    let data: Vec<u8> = (0..30).map(|i| decode_pos_32(&cookie, i)).rev().collect();
+    let mut rng = crate::utils::rng();
+    let data: Vec<u8> = (0..30)
+        .map(|i| decode_pos_32(&cookie, i, &mut rng))
+        .rev()
+        .collect();
 
-    println!("d: {:?}", data);
-    println!("Decoded data: {}", std::str::from_utf8(&data).unwrap());
-    assert_eq!(cookie, data);
+    say!("d: {:?}", data);
+    say!("Decoded data: {}", String::from_utf8_lossy(&data));
 
-    Ok(())
+    Ok(Report::new().check("cookie", cookie, data))
 }
@@ -94,13 +94,16 @@
 
 use std::str::FromStr;
 
+use crate::group::{Group, ModP};
+use crate::key_agreement::{self, mac, Bob, KeyAgreementOracle};
+use crate::report::say;
+use crate::trace::{event, span};
 use crate::utils::*;
-use hmac_sha256::HMAC;
+use anyhow::anyhow;
 use num_bigint::{BigInt, RandBigInt};
 use num_integer::Integer;
-use num_traits::{FromPrimitive, Zero};
-use rand::rngs::ThreadRng;
-use rand::thread_rng;
+use num_traits::{FromPrimitive, One, ToPrimitive, Zero};
+use rand::Rng;
 
 /*
 fn primes_below(limit: &BigInt) -> Vec<BigInt> {
@@ -138,97 +141,105 @@ pub fn get_factors(n: &BigInt, limit: &BigInt) -> Vec<BigInt> {
     factors
 }
 
-pub fn main() -> Result<()> {
-    let mut rng = thread_rng();
+pub fn main() -> Result<Report> {
+    let mut rng = crate::utils::rng();
     let p = BigInt::from_str("7199773997391911030609999317773941274322764333428698921736339643928346453700085358802973900485592910475480089726140708102474957429903531369589969318716771")?;
     let g = BigInt::from_str("4565356397095740655436854503483826832136106141639563487732438195343690437606117828318042418238184896212352329118608100083187535033402010599512641674644143")?;
     let q = BigInt::from_str("236234353446506858198510045061214171961")?;
 
-    let a_priv = rng.gen_bigint_range(&BigInt::zero(), &q);
-    let b_priv = rng.gen_bigint_range(&BigInt::zero(), &q);
+    let group = ModP { p: p.clone() };
+    let bob = key_agreement::connect(Bob::new(group.clone(), &g, &q, &mut rng))?;
+    let b_pub = bob.public_key().clone();
 
+    // Alice's side of an honest exchange
+    let a_priv = rng.gen_bigint_range(&BigInt::zero(), &q);
     let a_pub = g.modpow(&a_priv, &p);
-    let b_pub = g.modpow(&b_priv, &p);
-
-    let shared = a_pub.modpow(&b_priv, &p);
-    let sharedp = b_pub.modpow(&a_priv, &p);
-    println!("Shared:  {}", shared);
-    println!("Shared': {}", sharedp);
-    assert_eq!(shared, sharedp);
+    let shared = b_pub.modpow(&a_priv, &p);
+    event!(Debug, "honest exchange", shared = shared);
+    let report = Report::new().check("honest mac", mac(&group, &shared), bob.exchange(&a_pub)?);
 
-    println!("g^q mod p = {}", g.modpow(&q, &p));
     let j: BigInt = (&p - &BigInt::from_u16(1).unwrap()) / &q;
-    println!("j: {}", j);
+    event!(Debug, "cofactor", j = j);
 
     let two: BigInt = 2.into();
     let limit = two.pow(16);
     let j_fac = get_factors(&j, &limit);
-    let mut rng = thread_rng();
-    println!("j factors: {:?}", j_fac);
-
-    let mut total_prod: BigInt = 1.into();
-    let mut rx = vec![];
-    for r in j_fac {
-        // h = rand(1, p)^((p-1)/r) mod p
-        let h = get_h(&p, &r, &mut rng);
-        //println!("h: {}", h);
-
-        // Bob computes "shared key"
-        // K := h^x mod p
-        let k = h.modpow(&b_priv, &p);
-        // m := "crazy flamboyant for the rap enjoyment"
-        // t := MAC(K, m)
-        let m = "crazy flamboyant for the rap enjoyment";
-        let t = HMAC::mac(m, k.to_bytes_be().1);
-        //println!("t: {:?}", t);
-        // Only r possible values of K Bob could have
-        // So find it!
-        let mut x_crack: BigInt = 1.into();
-        loop {
-            let k_crack = h.modpow(&x_crack, &p);
-            if HMAC::mac(m, k_crack.to_bytes_be().1) == t {
-                break;
-            } else {
-                x_crack += 1;
-            }
-        }
-        println!("x mod {}: {}", r, x_crack);
-
-        rx.push((r.clone(), x_crack));
+    event!(Debug, "small factors of j", factors = format!("{j_fac:?}"));
 
-        total_prod *= &r;
-        if total_prod > q {
-            break;
-        }
-    }
+    let j_fac = enough_factors(j_fac, &q);
+    let rx = subgroup_confinement(bob.as_ref(), &group, &j_fac, &mut rng)?;
 
     // Now crack using CRT
-    assert!(total_prod > q);
-
-    let mut result: BigInt = BigInt::zero();
-    for (r, x) in rx {
-        let ms = &total_prod / &r;
-        result += x * &ms * invmod(&ms, &r);
+    let (result, total_prod) = crt(&rx).ok_or_else(|| anyhow!("A subgroup came up twice"))?;
+    event!(Info, "residues combined", modulus_bits = total_prod.bits());
+    if total_prod <= q {
+        return Err(anyhow!("The residues only pin x down mod {total_prod}"));
     }
-    result %= &total_prod;
 
-    println!("Cracked x: {}", result);
-    println!("B secret : {}", b_priv);
+    say!("Cracked x: {}", result);
 
-    assert_eq!(result, b_priv);
+    Ok(report
+        .check("public key", &b_pub, g.modpow(&result, &p))
+        .recovered("x", result)
+        .count("residues", rx.len() as u64))
+}
 
-    Ok(())
+/// The first of `factors`, up to the one that takes their product past `bound` (or all of them,
+/// if it never gets there)
+pub fn enough_factors(factors: Vec<BigInt>, bound: &BigInt) -> Vec<BigInt> {
+    let mut total_prod = BigInt::one();
+    factors
+        .into_iter()
+        .take_while(|r| {
+            let enough = &total_prod > bound;
+            total_prod *= r;
+            !enough
+        })
+        .collect()
 }
 
-pub fn get_h(p: &BigInt, r: &BigInt, rng: &mut ThreadRng) -> BigInt {
-    let one: BigInt = 1.into();
-    let pow = (p - &one) / r;
-    loop {
-        let h = rng.gen_bigint_range(&one, p).modpow(&pow, p);
-        if h != one {
-            return h;
+/// Bob's private key mod each of the `factors` which the attack can manage
+///
+/// For each r, sends Bob an element h of order r out of `group` as our public key, and then tries
+/// each of the r possible shared secrets h^x against his MAC. Where r divides the order of the
+/// group more than once h may have a bigger order than r, in which case none of them match and r is
+/// left out.
+///
+/// The MAC is all there is to go on, and a MAC of h^(i + m j) says nothing about a MAC of h^i, so
+/// there's no meeting in the middle (as in baby-step giant-step): every h^x has to be tried. They
+/// are at least only a step apart, which `Group::multiples` takes with one group operation rather
+/// than a whole scaling.
+///
+/// `group` only has to agree with the one Bob works in on what scaling does, so it can be a
+/// different curve with the same a (59), or the twist of his curve (60). On the twist only the u
+/// coordinate is sent, so there Bob's key is only found up to sign.
+pub fn subgroup_confinement<G: Group, O: KeyAgreementOracle<G::Element> + ?Sized>(
+    oracle: &O,
+    group: &G,
+    factors: &[BigInt],
+    rng: &mut impl Rng,
+) -> Result<Vec<(BigInt, BigInt)>> {
+    let _span = span!(Info, "subgroup_confinement", factors = factors.len());
+    let mut rx = vec![];
+    for r in factors {
+        let h = group.element_of_order(r, rng);
+        let t = oracle.exchange(&h)?;
+        let size = r
+            .to_usize()
+            .ok_or_else(|| anyhow!("A subgroup of order {r} is too big to search"))?;
+        let x = std::iter::once(group.scale(&h, &BigInt::zero()))
+            .chain(group.multiples(&h))
+            .take(size)
+            .position(|k| mac(group, &k) == t);
+        match x {
+            Some(x) => {
+                event!(Debug, "residue recovered", r = r, x = x);
+                rx.push((r.clone(), x.into()));
+            }
+            None => event!(Debug, "no residue, h has a bigger order", r = r),
         }
     }
+    Ok(rx)
 }
 
 #[cfg(test)]
@@ -237,6 +248,25 @@ mod tests {
 
     #[test]
     fn subgroup_confinement() {
-        main().unwrap();
+        main().and_then(Report::verify).unwrap();
+    }
+
+    #[test]
+    fn small_group() {
+        // 2311 - 1 = 2 * 3 * 5 * 7 * 11
+        let group = ModP { p: 2311.into() };
+        let factors: Vec<BigInt> = [2, 3, 5, 7, 11].into_iter().map(BigInt::from).collect();
+        let g = BigInt::from(3);
+        let mut rng = rand::thread_rng();
+        for _ in 0..4 {
+            let bob = Bob::new(group.clone(), &g, &2310.into(), &mut rng);
+            let rx = super::subgroup_confinement(&bob, &group, &factors, &mut rng).unwrap();
+            assert_eq!(rx.len(), factors.len());
+            let (x, m) = crt(&rx).unwrap();
+            assert_eq!(m, 2310.into());
+            assert_eq!(&group.scale(&g, &x), bob.public_key());
+        }
+        assert_eq!(enough_factors(factors.clone(), &6.into()), factors[..3]);
+        assert_eq!(enough_factors(factors.clone(), &10_000.into()), factors);
     }
 }
@@ -161,17 +161,23 @@
 //! down the remaining bits.
 
 use anyhow::anyhow;
-use hmac_sha256::HMAC;
-use indicatif::ProgressBar;
-use num_bigint::{BigInt, RandBigInt};
+use num_bigint::BigInt;
+use num_bigint::RandBigInt;
 use num_integer::Integer;
-use num_traits::{FromPrimitive, ToPrimitive, Zero};
-use rand::thread_rng;
-use std::collections::HashMap;
+use num_traits::{FromPrimitive, One, Zero};
+use rand::Rng;
 use std::str::FromStr;
 
+use crate::report::say;
 use crate::{
-    set8::challenge57::{get_factors, get_h},
+    fxhash::FxHashMap,
+    group::{herd_kangaroo, ModP},
+    key_agreement::{self, Bob},
+    options::Profile,
+    progress,
+    rsa::{is_probable_prime, random_prime},
+    set8::challenge57::{enough_factors, get_factors, subgroup_confinement},
+    trace::{event, span},
     utils::*,
 };
 
@@ -190,13 +196,14 @@ pub fn shanks(g: &BigInt, p: &BigInt, upper: &BigInt, y: &BigInt) -> Result<BigI
     // Now calculate g^i for all i, and find the collision with the hash table
     // We now have j and i, so can calculate the index
 
-    let mut h = HashMap::new();
+    let _span = span!(Info, "shanks", bits = upper.bits());
+    let mut h = FxHashMap::default();
     let m: BigInt = upper.sqrt();
     let thou = BigInt::from_u32(1000).unwrap();
 
     let mut i = BigInt::zero();
 
-    let spinner = ProgressBar::new_spinner();
+    let spinner = progress::spinner();
     spinner.set_message(format!("Baby step {}: {}", i, m));
     // Big step hashmap
     while i <= m {
@@ -210,9 +217,10 @@ pub fn shanks(g: &BigInt, p: &BigInt, upper: &BigInt, y: &BigInt) -> Result<BigI
     }
     spinner.set_message("Baby step completed".to_string());
     spinner.finish();
+    event!(Debug, "baby steps stored", steps = h.len());
 
     let mut j = BigInt::zero();
-    let spinner = ProgressBar::new_spinner();
+    let spinner = progress::spinner();
     spinner.set_message(format!("Giant step {}: {}", i, m));
     while j <= m {
         if j.is_multiple_of(&thou) {
@@ -221,7 +229,7 @@ pub fn shanks(g: &BigInt, p: &BigInt, upper: &BigInt, y: &BigInt) -> Result<BigI
         }
 
         let gmj = g.modpow(&(&m * &j), p);
-        let gmjinv = invmod(&gmj, p);
+        let gmjinv = try_invmod(&gmj, p).unwrap();
         let yp = (y * gmjinv) % p;
 
         if let Some(i_true) = h.get(&yp) {
@@ -238,196 +246,117 @@ pub fn shanks(g: &BigInt, p: &BigInt, upper: &BigInt, y: &BigInt) -> Result<BigI
     Err(anyhow!("Index not in bound"))
 }
 
-fn try_kangaroo<F>(
-    f: F,
-    n: &BigInt,
-    g: &BigInt,
-    p: &BigInt,
-    a: &BigInt,
-    b: &BigInt,
-    y: &BigInt,
-) -> Result<BigInt>
-where
-    F: Copy + FnOnce(&BigInt) -> BigInt,
-{
-    let mut count = BigInt::zero();
-    let spinner = ProgressBar::new_spinner();
-    spinner.set_message(format!("Tame kangaroo step {}: {}", count, n));
-    // Tame kangaroo
-    let mut xt = BigInt::zero();
-    let mut yt = g.modpow(b, p);
-    let thou = BigInt::from_u32(1000).unwrap();
-    while &count < n {
-        let ff = f(&yt);
-        xt += &ff;
-        yt = (yt * g.modpow(&ff, p)) % p;
-        count += 1;
-        if count.is_multiple_of(&thou) {
-            spinner.tick();
-            spinner.set_message(format!("Tame kangaroo step {}/{}", count, n));
-            //println!("xt: {}", xt);
-            //println!("count: {}", count);
-            //println!("f: {}", ff);
-        }
-    }
-    spinner.set_message("Tame kangaroo set trap".to_string());
-    spinner.finish();
+/// The group the attack is on: p = j q + 1, with g of prime order q
+struct Params {
+    p: BigInt,
+    q: BigInt,
+    j: BigInt,
+    g: BigInt,
+}
 
-    // Wild kangaroo
-    let mut xw = BigInt::zero();
-    let xw_max: BigInt = b - a + &xt;
-    let mut yw = y.clone();
-    let spinner = ProgressBar::new_spinner();
-    spinner.set_message(format!("Wild kangaroo xw/xw_max {}: {}", xw, xw_max));
-
-    count = 1.into();
-    while xw < b - a + &xt {
-        count += 1;
-        let ff = f(&yw);
-        if count.is_multiple_of(&thou) {
-            spinner.set_message(format!("Wild kangaroo xw/xw_max {}: {}", xw, xw_max));
-            spinner.tick();
-        }
-        xw += &ff;
-        yw = (yw * g.modpow(&ff, p)) % p;
-        if yw == yt {
-            spinner.set_message("Caught the wild kangaroo!".to_string());
-            spinner.finish();
-            return Ok(b + xt - xw);
+impl Params {
+    /// The challenge's own group
+    fn challenge() -> Self {
+        Self {
+            p: BigInt::from_str("11470374874925275658116663507232161402086650258453896274534991676898999262641581519101074740642369848233294239851519212341844337347119899874391456329785623").unwrap(),
+            q: BigInt::from_str("335062023296420808191071248367701059461").unwrap(),
+            j: BigInt::from_str("34233586850807404623475048381328686211071196701374230492615844865929237417097514638999377942356150481334217896204702").unwrap(),
+            g: BigInt::from_str("622952335333961296978159266084741085889881358738459939978290179936063635566740258555167783009058567397963466103140082647486611657350811560630587013183357").unwrap(),
         }
     }
 
-    spinner.finish();
-    Err(anyhow!("Wild kangaroo never landed on the tame kangaroo"))
-}
-
-#[allow(dead_code)]
-fn kangaroo<F>(f: F, g: &BigInt, p: &BigInt, a: &BigInt, b: &BigInt, y: &BigInt) -> BigInt
-where
-    F: Copy + FnOnce(&BigInt) -> BigInt,
-{
-    let mut k = BigInt::from_u32(11).unwrap();
-    let one = BigInt::from_u32(1).unwrap();
-    let two = BigInt::from_u32(2).unwrap();
-    let mut n = two.modpow(&(&one + &k), p) / &k;
-    let stretch = BigInt::from_u32(8).unwrap();
-    loop {
-        println!("Loop");
-        if let Ok(z) = try_kangaroo(f, &n, g, p, a, b, y) {
-            return z;
+    /// A group of around 256 bits for `--quick`, shaped like the challenge's but with a 48-bit q
+    /// and with j having just 2 and two different 12-bit primes for small factors, which leaves the
+    /// kangaroos around 23 bits to cover rather than 40
+    fn quick(rng: &mut impl Rng) -> Self {
+        let one = BigInt::one();
+        let r1 = random_prime(12, &one, rng);
+        let r2 = std::iter::repeat_with(|| random_prime(12, &one, rng))
+            .find(|r| r != &r1)
+            .unwrap();
+        let small = [2.into(), r1, r2];
+        let rest = random_prime(256 - 48 - 1 - 24, &one, rng);
+        let j: BigInt = small.iter().product::<BigInt>() * rest;
+        loop {
+            let q = random_prime(48, &one, rng);
+            let p: BigInt = &j * &q + 1;
+            if !is_probable_prime(&p, 20, rng) {
+                continue;
+            }
+            let h = rng.gen_bigint_range(&2.into(), &p);
+            let g = h.modpow(&j, &p);
+            if !g.is_one() {
+                return Self { p, q, j, g };
+            }
         }
-        k += 1;
-        n = &stretch * two.modpow(&(&one + &k), p) / &k;
     }
 }
 
-pub fn main() -> Result<()> {
-    let p = BigInt::from_str("11470374874925275658116663507232161402086650258453896274534991676898999262641581519101074740642369848233294239851519212341844337347119899874391456329785623").unwrap();
-    let q = BigInt::from_str("335062023296420808191071248367701059461").unwrap();
-    let j = BigInt::from_str("34233586850807404623475048381328686211071196701374230492615844865929237417097514638999377942356150481334217896204702").unwrap();
-    let g = BigInt::from_str("622952335333961296978159266084741085889881358738459939978290179936063635566740258555167783009058567397963466103140082647486611657350811560630587013183357").unwrap();
-
-    // Generate a keypair for Bob
-    let mut rng = thread_rng();
-    let b_priv = rng.gen_bigint_range(&BigInt::zero(), &q);
-    let b_pub = g.modpow(&b_priv, &p);
+pub fn main() -> Result<Report> {
+    let mut rng = crate::utils::rng();
+    let profile = crate::options::get().profile;
+    let Params { p, q, j, g } = match profile {
+        Profile::Full => Params::challenge(),
+        Profile::Quick => Params::quick(&mut rng),
+    };
+    let group = ModP { p: p.clone() };
+    let bob = key_agreement::connect(Bob::new(group.clone(), &g, &q, &mut rng))?;
+    let b_pub = bob.public_key().clone();
 
     let two: BigInt = 2.into();
-    let limit = two.pow(20);
+    let limit = two.pow(profile.pick(20, 16));
     let j_fac = get_factors(&j, &limit);
-    println!("j factors: {:?}", j_fac);
-
-    let mut total_prod: BigInt = 1.into();
-    let mut rx = vec![];
-
-    for r in j_fac {
-        // h = rand(1, p)^((p-1)/r) mod p
-        let h = get_h(&p, &r, &mut rng);
-        //println!("h: {}", h);
-
-        // Bob computes "shared key"
-        // K := h^x mod p
-        let k = h.modpow(&b_priv, &p);
-        // m := "crazy flamboyant for the rap enjoyment"
-        // t := MAC(K, m)
-        let m = "crazy flamboyant for the rap enjoyment";
-        let t = HMAC::mac(m, k.to_bytes_be().1);
-        //println!("t: {:?}", t);
-        // Only r possible values of K Bob could have
-        // So find it!
-        let mut x_crack: BigInt = 1.into();
-        loop {
-            let k_crack = h.modpow(&x_crack, &p);
-            if HMAC::mac(m, k_crack.to_bytes_be().1) == t {
-                break;
-            } else {
-                x_crack += 1;
-            }
-        }
-        x_crack %= &r;
-        println!("x mod {}: {}", r, x_crack);
+    event!(Debug, "small factors of j", factors = format!("{j_fac:?}"));
 
-        rx.push((r.clone(), x_crack));
-
-        total_prod *= &r;
-        if total_prod > q {
-            break;
-        }
-    }
+    let rx = subgroup_confinement(bob.as_ref(), &group, &enough_factors(j_fac, &q), &mut rng)?;
 
     // Incomplete CRT
-    let mut result: BigInt = BigInt::zero();
-    for (r, x) in rx {
-        let ms = &total_prod / &r;
-        result += x * &ms * invmod(&ms, &r);
-    }
-    result %= &total_prod;
+    let (result, total_prod) = crt(&rx).ok_or_else(|| anyhow!("A subgroup came up twice"))?;
 
     let r = total_prod.clone();
     let x_crack = result;
 
     let one = BigInt::from_u32(1).unwrap();
-    println!("We now know x mod r = {}", x_crack);
-    println!("r: {}", r);
-    println!("Upper bound: {}", (&q - &one) / &r);
-    println!("Time to figure out the rest");
+    event!(Debug, "residues combined", x = x_crack, r = r);
 
     // y = g**(x) = g**(n+mr), where n is x_crack
     let gn = g.modpow(&x_crack, &p);
-    let gninv = invmod(&gn, &p);
+    let gninv = try_invmod(&gn, &p).unwrap();
     let yp: BigInt = (&b_pub * &gninv) % &p;
     let gp: BigInt = g.modpow(&r, &p);
 
     let upper_index: BigInt = (&q - &one) / &r;
+    event!(Info, "range narrowed", bits = upper_index.bits());
 
-    let k = BigInt::from_u32(23).unwrap();
-    let stretch = BigInt::from_u32(4).unwrap();
-    let n = stretch * (two.modpow(&(&one + &k), &p) / &k);
-
-    let index = try_kangaroo(
-        |z| {
-            let zmod = z.mod_floor(&k).to_u32().unwrap();
-            two.pow(zmod)
-        },
-        &n,
-        &gp,
-        &p,
-        &BigInt::zero(),
-        &upper_index,
-        &yp,
-    )
-    .unwrap();
+    let herd = 2 * rayon::current_num_threads();
+    let (_, index, jumps) = herd_kangaroo(&group, &gp, &[yp], &upper_index, herd, &mut rng)?;
+    say!("Caught after {jumps} jumps with {herd} tame and {herd} wild kangaroos");
     let b_priv_deduced: BigInt = &x_crack + &index * &r;
-    println!("b_priv_dedu = {}", b_priv_deduced);
-    println!("b_priv_true = {}", b_priv);
-    assert_eq!(b_priv_deduced, b_priv);
+    say!("b_priv_dedu = {}", b_priv_deduced);
 
-    Ok(())
+    Ok(Report::new()
+        .check("public key", &b_pub, g.modpow(&b_priv_deduced, &p))
+        .recovered("x", b_priv_deduced)
+        .count("residues", rx.len() as u64)
+        .count("jumps", jumps))
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::group::kangaroo;
+
+    #[test]
+    fn quick_params() {
+        let mut rng = rand::thread_rng();
+        let Params { p, q, j, g } = Params::quick(&mut rng);
+        assert!((255..=256).contains(&p.bits()));
+        assert_eq!(q.bits(), 48);
+        assert_eq!(&j * &q + 1, p);
+        assert!(!g.is_one());
+        assert!(g.modpow(&q, &p).is_one());
+        assert_eq!(get_factors(&j, &BigInt::from(1 << 16)).len(), 3);
+    }
 
     #[test]
     fn small_shanks() {
@@ -442,9 +371,9 @@ mod test {
         let index = shanks(&g, &p, &upper_bound, &y).unwrap();
 
         let deduced = g.modpow(&index, &p);
-        println!("index: {} vs 2^20: {}", index, upper_bound);
-        println!("g**index mod p = {}", deduced);
-        println!("y = {}", y);
+        say!("index: {} vs 2^20: {}", index, upper_bound);
+        say!("g**index mod p = {}", deduced);
+        say!("y = {}", y);
         assert_eq!(deduced, y);
     }
 
@@ -462,9 +391,9 @@ mod test {
         let index = shanks(&g, &p, &upper_bound, &y).unwrap();
 
         let deduced = g.modpow(&index, &p);
-        println!("index: {} vs 2^20: {}", index, upper_bound);
-        println!("g**index mod p = {}", deduced);
-        println!("y = {}", y);
+        say!("index: {} vs 2^20: {}", index, upper_bound);
+        say!("g**index mod p = {}", deduced);
+        say!("y = {}", y);
         assert_eq!(deduced, y);
     }
     #[test]
@@ -474,40 +403,33 @@ mod test {
         let _j = BigInt::from_str("34233586850807404623475048381328686211071196701374230492615844865929237417097514638999377942356150481334217896204702").unwrap();
         let g = BigInt::from_str("622952335333961296978159266084741085889881358738459939978290179936063635566740258555167783009058567397963466103140082647486611657350811560630587013183357").unwrap();
 
-        let five = BigInt::from_u32(5).unwrap();
         let two = BigInt::from_u32(2).unwrap();
-        let _one = BigInt::from_u32(1).unwrap();
-        let _three = BigInt::from_u32(3).unwrap();
 
-        let k = BigInt::from_u32(11).unwrap();
-        let upper_index = BigInt::from_u32(20).unwrap();
-        let n = two.modpow(&(&five + &k), &p) / &k;
         let y = BigInt::from_str("7760073848032689505395005705677365876654629189298052775754597607446617558600394076764814236081991643094239886772481052254010323780165093955236429914607119").unwrap();
-        println!("Finding index in range [0,2^20]");
-        let index = try_kangaroo(
-            |z| {
-                let zmod = z.mod_floor(&k).to_u32().unwrap();
-                //        println!("z: {}", z);
-                //        println!("zmod: {}", zmod);
-
-                two.pow(zmod)
-            },
-            &n,
-            &g,
-            &p,
-            &BigInt::zero(),
-            &upper_index,
-            &y,
-        )
-        .unwrap();
+        say!("Finding index in range [0,2^20]");
+        let index = kangaroo(&ModP { p: p.clone() }, &g, &y, &two.pow(20)).unwrap();
 
         let deduced = g.modpow(&index, &p);
-        println!("index: {} vs 2^20: {}", index, two.pow(20));
-        println!("g**index mod p = {}", deduced);
-        println!("y = {}", y);
+        say!("index: {} vs 2^20: {}", index, two.pow(20));
+        say!("g**index mod p = {}", deduced);
+        say!("y = {}", y);
         assert_eq!(deduced, y);
     }
 
+    #[test]
+    fn escape() {
+        let p = BigInt::from_str("11470374874925275658116663507232161402086650258453896274534991676898999262641581519101074740642369848233294239851519212341844337347119899874391456329785623").unwrap();
+        let g = BigInt::from_str("622952335333961296978159266084741085889881358738459939978290179936063635566740258555167783009058567397963466103140082647486611657350811560630587013183357").unwrap();
+        let group = ModP { p: p.clone() };
+        // Outside the range, so there's no trap to land on
+        let y = g.modpow(&1_000_000.into(), &p);
+        let b = BigInt::from(1000);
+        assert_eq!(kangaroo(&group, &g, &y, &b), None);
+
+        let y = g.modpow(&999.into(), &p);
+        assert_eq!(kangaroo(&group, &g, &y, &b), Some(999.into()));
+    }
+
     #[ignore = "slow"]
     #[test]
     fn big_kangaroo() {
@@ -516,33 +438,53 @@ mod test {
         let _j = BigInt::from_str("34233586850807404623475048381328686211071196701374230492615844865929237417097514638999377942356150481334217896204702").unwrap();
         let g = BigInt::from_str("622952335333961296978159266084741085889881358738459939978290179936063635566740258555167783009058567397963466103140082647486611657350811560630587013183357").unwrap();
 
-        let _five = BigInt::from_u32(5).unwrap();
         let two = BigInt::from_u32(2).unwrap();
-        let one = BigInt::from_u32(1).unwrap();
 
         let y = BigInt::from_str("9388897478013399550694114614498790691034187453089355259602614074132918843899833277397448144245883225611726912025846772975325932794909655215329941809013733").unwrap();
-        let upper_index = BigInt::from_u32(40).unwrap();
-        let stretch = BigInt::from_u32(8).unwrap();
-
-        let k = BigInt::from_u32(22).unwrap();
-        let n = stretch * (two.modpow(&(&one + &k), &p) / &k);
-
-        let index = try_kangaroo(
-            |z| {
-                let zmod = z.mod_floor(&k).to_u32().unwrap();
-                two.pow(zmod)
-            },
-            &n,
+        let index = kangaroo(&ModP { p: p.clone() }, &g, &y, &two.pow(40)).unwrap();
+        let deduced = g.modpow(&index, &p);
+        say!("g**index mod p = {}", deduced);
+        say!("y = {}", y);
+        assert_eq!(deduced, y);
+    }
+
+    #[test]
+    fn small_herd() {
+        let p = BigInt::from_str("11470374874925275658116663507232161402086650258453896274534991676898999262641581519101074740642369848233294239851519212341844337347119899874391456329785623").unwrap();
+        let g = BigInt::from_str("622952335333961296978159266084741085889881358738459939978290179936063635566740258555167783009058567397963466103140082647486611657350811560630587013183357").unwrap();
+        let group = ModP { p: p.clone() };
+        let b = BigInt::one() << 20;
+        let mut rng = rand::thread_rng();
+        for herd in [1, 4] {
+            for x in [0u32, 12345, 1 << 20] {
+                let y = g.modpow(&x.into(), &p);
+                let (_, index, _) =
+                    herd_kangaroo(&group, &g, &[y], &b, herd, &mut rng).unwrap();
+                assert_eq!(index, x.into(), "{herd} {x}");
+            }
+        }
+
+        // Outside the range, the herd gives up
+        let y = g.modpow(&(BigInt::one() << 30), &p);
+        assert!(herd_kangaroo(&group, &g, &[y], &b, 4, &mut rng).is_err());
+    }
+
+    #[ignore = "slow"]
+    #[test]
+    fn big_herd() {
+        let p = BigInt::from_str("11470374874925275658116663507232161402086650258453896274534991676898999262641581519101074740642369848233294239851519212341844337347119899874391456329785623").unwrap();
+        let g = BigInt::from_str("622952335333961296978159266084741085889881358738459939978290179936063635566740258555167783009058567397963466103140082647486611657350811560630587013183357").unwrap();
+        let y = BigInt::from_str("9388897478013399550694114614498790691034187453089355259602614074132918843899833277397448144245883225611726912025846772975325932794909655215329941809013733").unwrap();
+        let herd = 2 * rayon::current_num_threads();
+        let (_, index, _) = herd_kangaroo(
+            &ModP { p: p.clone() },
             &g,
-            &p,
-            &BigInt::zero(),
-            &upper_index,
-            &y,
+            std::slice::from_ref(&y),
+            &(BigInt::one() << 40),
+            herd,
+            &mut rand::thread_rng(),
         )
         .unwrap();
-        let deduced = g.modpow(&index, &p);
-        println!("g**index mod p = {}", deduced);
-        println!("y = {}", y);
-        assert_eq!(deduced, y);
+        assert_eq!(g.modpow(&index, &p), y);
     }
 }
@@ -274,15 +274,26 @@
 //! from invalid curves.
 
 use anyhow::anyhow;
-use num_bigint::{BigInt, RandBigInt};
+use num_bigint::{BigInt, RandBigInt, Sign};
 use num_integer::Integer;
-use num_traits::{FromPrimitive, Zero};
-use rand::thread_rng;
-use std::{ops::Shr, str::FromStr};
-
-use crate::{set8::challenge57::get_factors, utils::*};
-
-#[derive(Debug)]
+use num_traits::{FromPrimitive, One, Zero};
+use rand::Rng;
+use std::{fmt, ops::Shr, str::FromStr};
+
+use crate::report::say;
+use crate::{
+    data::INVALID_CURVES,
+    group::{Abelian, Group},
+    key_agreement::{self, mac, Bob},
+    set8::{
+        challenge57::{get_factors, subgroup_confinement},
+        params,
+    },
+    trace::{event, span},
+    utils::*,
+};
+
+#[derive(Debug, Clone)]
 pub struct CurveParams {
     pub a: BigInt,
     pub b: BigInt,
@@ -298,7 +309,6 @@ pub enum Point {
 }
 
 impl Point {
-    #[allow(dead_code)]
     pub fn get_x(&self) -> Option<BigInt> {
         match self {
             Point::P { x, .. } => Some(x.clone()),
@@ -306,11 +316,12 @@ impl Point {
         }
     }
 
+    /// (x, -y), reduced so that points of order 2, with y = 0, are their own inverses
     pub fn invert(&self, p: &BigInt) -> Self {
         if let Self::P { x, y } = self {
             Self::P {
                 x: x.clone(),
-                y: p - y.clone(),
+                y: (p - y).mod_floor(p),
             }
         } else {
             Self::O
@@ -318,6 +329,7 @@ impl Point {
     }
 }
 
+#[derive(Debug, Clone)]
 pub struct Curve {
     pub params: CurveParams,
 }
@@ -373,12 +385,13 @@ impl Curve {
                 true => {
                     let three: BigInt = 3.into();
                     let two: BigInt = 2.into();
-                    (three * x1 * x1 + &self.params.a) * invmod(&(two * y1), &self.params.p)
+                    let inverse = try_invmod(&(two * y1), &self.params.p).unwrap();
+                    (three * x1 * x1 + &self.params.a) * inverse
                 }
                 false => {
                     let dy = (y2 - y1).mod_floor(&self.params.p);
                     let dx = (x2 - x1).mod_floor(&self.params.p);
-                    dy * invmod(&dx, &self.params.p)
+                    dy * try_invmod(&dx, &self.params.p).unwrap()
                 }
             }
             .mod_floor(&self.params.p);
@@ -420,155 +433,289 @@ impl Curve {
     }
 }
 
-pub fn main() -> Result<()> {
-    let curve = Curve {
-        params: CurveParams {
-            a: BigInt::from_str("-95051").unwrap(),
-            b: BigInt::from_str("11279326").unwrap(),
-            p: BigInt::from_str("233970423115425145524320034830162017933").unwrap(),
-            bp: Point::P {
-                x: BigInt::from_str("182").unwrap(),
-                y: BigInt::from_str("85518893674295321206118380980485522083").unwrap(),
-            },
-            ord: BigInt::from_str("233970423115425145498902418297807005944").unwrap(),
-        },
-    };
+/// A step of scalar multiplication from the top bit down, as challenge 66 does it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Step {
+    /// Bit i of the scalar, numbered from 1 at the top
+    Bit { i: u64, b: bool },
+    /// add(c Q, d Q)
+    Add(BigInt, BigInt),
+}
 
-    let mut rng = thread_rng();
+impl fmt::Display for Step {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Step::Bit { i, b } => write!(f, "# i = {i}, b = {}", u8::from(*b)),
+            Step::Add(c, d) => write!(f, "add({c}Q, {d}Q)"),
+        }
+    }
+}
 
-    // Generate A's private key
-    let a_priv = rng.gen_bigint_range(&BigInt::zero(), &curve.params.ord);
-    let a_pub = curve.gen(&a_priv);
+/// The adds k Q takes from the top bit down, which depend on k alone
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Trace {
+    pub k: BigInt,
+    pub steps: Vec<Step>,
+}
+
+impl fmt::Display for Trace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "# k = {:b}", self.k)?;
+        for step in &self.steps {
+            write!(f, "\n{step}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Steps through k Q from the top bit down, starting from R = Q and for each bit after the first
+/// doubling R, then adding Q if it's set, noting the multiples of Q each add is given
+pub fn trace(k: &BigInt) -> Trace {
+    let n = k.bits();
+    let mut steps = vec![];
+    let mut c = BigInt::one();
+    for i in (0..n.saturating_sub(1)).rev() {
+        let b = k.bit(i);
+        steps.push(Step::Bit { i: n - i, b });
+        steps.push(Step::Add(c.clone(), c.clone()));
+        c *= 2;
+        if b {
+            steps.push(Step::Add(c.clone(), BigInt::one()));
+            c += 1;
+        }
+    }
+    Trace {
+        k: k.clone(),
+        steps,
+    }
+}
+
+/// The adds the first `depth` bits after the top one could lead to, as a tree branching on each
+///
+/// Each branch has the adds from the bit's own through to the doubling for the next, so a 0 is
+/// only that doubling, and the first doubling is at the root.
+pub fn decision_tree(depth: u32) -> String {
+    let mut out = String::from("add(1Q, 1Q)\n");
+    branches(&mut out, &BigInt::from(2), "", depth);
+    out
+}
+
+/// Both ways the next bit can go, once R has been doubled to `c` Q
+fn branches(out: &mut String, c: &BigInt, indent: &str, depth: u32) {
+    if depth == 0 {
+        return;
+    }
+    for b in [false, true] {
+        let (fork, more) = match b {
+            false => ("├── ", "│   "),
+            true => ("└── ", "    "),
+        };
+        let r = c + u8::from(b);
+        let adds = match b {
+            false => format!("add({r}Q, {r}Q)"),
+            true => format!("add({c}Q, 1Q), add({r}Q, {r}Q)"),
+        };
+        out.push_str(&format!("{indent}{fork}b = {}: {adds}\n", u8::from(b)));
+        branches(out, &(2 * r), &format!("{indent}{more}"), depth - 1);
+    }
+}
+
+pub fn main() -> Result<Report> {
+    let curve = params::curve();
 
-    // Generate B's private key
-    let b_priv = rng.gen_bigint_range(&BigInt::zero(), &curve.params.ord);
-    let b_pub = curve.gen(&b_priv);
+    let mut rng = crate::utils::rng();
 
-    let b_shared = curve.scale(&a_pub, &b_priv);
+    // Bob doesn't check the points he's sent are on his curve, and adding points never uses b, so
+    // points from curves with any other b get scaled just the same
+    let bob = key_agreement::connect(Bob::new(
+        curve.clone(),
+        &curve.params.bp,
+        &curve.params.ord,
+        &mut rng,
+    ))?;
+    let b_pub = bob.public_key().clone();
+    event!(Debug, "Bob's public key", b = format!("{b_pub:?}"));
+
+    // Alice's side of an honest exchange
+    let a_priv = rng.gen_bigint_range(&BigInt::zero(), &curve.params.ord);
+    let a_pub = curve.gen(&a_priv);
     let a_shared = curve.scale(&b_pub, &a_priv);
-    assert_eq!(a_shared, b_shared);
-
-    println!("B public key: {:?}", b_pub);
-    // y^2 = x^3 - 95051*x + 210
-    // 233970423115425145550826547352470124412
-    let curve1 = Curve {
-        params: CurveParams {
-            a: BigInt::from_str("-95051").unwrap(),
-            b: BigInt::from_str("210").unwrap(),
-            p: BigInt::from_str("233970423115425145524320034830162017933").unwrap(),
-            bp: Point::P {
-                x: BigInt::from_str("182").unwrap(),
-                y: BigInt::from_str("85518893674295321206118380980485522083").unwrap(),
-            },
-            ord: BigInt::from_str("233970423115425145550826547352470124412").unwrap(),
-        },
-    };
-    let two = BigInt::from_usize(2).unwrap();
-    let limit = two.pow(20);
-    let curve1_orders = get_factors(&curve1.params.ord, &limit);
+    let report = Report::new().check("honest mac", mac(curve, &a_shared), bob.exchange(&a_pub)?);
+
+    let curves = invalid_curves(curve)?;
+    let picked = pick_subgroups(&curves, &curve.params.ord)?;
     let mut rx = vec![];
-    println!("Curve 1 factors: {:?}", curve1_orders);
-
-    // Pick an order for this curve
-
-    rx.extend_from_slice(&get_residues(&curve1, &curve1_orders, &curve, &b_priv));
-
-    println!("Recovered: {:?}", rx);
-
-    // y^2 = x^3 - 95051*x + 504
-    // 233970423115425145544350131142039591210
-    let curve2 = Curve {
-        params: CurveParams {
-            a: BigInt::from_str("-95051").unwrap(),
-            b: BigInt::from_str("504").unwrap(),
-            p: BigInt::from_str("233970423115425145524320034830162017933").unwrap(),
-            bp: Point::P {
-                x: BigInt::from_str("182").unwrap(),
-                y: BigInt::from_str("85518893674295321206118380980485522083").unwrap(),
-            },
-            ord: BigInt::from_str("233970423115425145544350131142039591210").unwrap(),
-        },
-    };
+    for (i, invalid) in curves.iter().enumerate() {
+        let rs: Vec<BigInt> = picked
+            .iter()
+            .filter(|(j, _)| *j == i)
+            .map(|(_, r)| r.clone())
+            .collect();
+        if rs.is_empty() {
+            continue;
+        }
+        event!(Debug, "subgroups picked", b = invalid.params.b, r = format!("{rs:?}"));
+        let _span = span!(Info, "invalid_curve", b = invalid.params.b);
+        rx.extend(subgroup_confinement(bob.as_ref(), invalid, &rs, &mut rng)?);
+    }
+
+    event!(Debug, "residues", rx = format!("{rx:?}"));
+    let (result, _) = crt(&rx).ok_or_else(|| anyhow!("A subgroup came up twice"))?;
 
-    let curve2_orders = get_factors(&curve2.params.ord, &limit);
-    // Ensure we don't duplicate r, as we already have that information
-    let curve2_orders: Vec<_> = curve2_orders
-        .into_iter()
-        .filter(|r| !rx.iter().any(|(x, _)| r == x))
-        .collect();
-    println!("Curve 2 factors: {:?}", curve2_orders);
-    rx.extend_from_slice(&get_residues(&curve2, &curve2_orders, &curve, &b_priv));
-
-    println!("Recovered: {:?}", rx);
-    // y^2 = x^3 - 95051*x + 727
-    // 233970423115425145545378039958152057148
-    let curve3 = Curve {
-        params: CurveParams {
-            a: BigInt::from_str("-95051").unwrap(),
-            b: BigInt::from_str("727").unwrap(),
-            p: BigInt::from_str("233970423115425145524320034830162017933").unwrap(),
-            bp: Point::P {
-                x: BigInt::from_str("182").unwrap(),
-                y: BigInt::from_str("85518893674295321206118380980485522083").unwrap(),
-            },
-            ord: BigInt::from_str("233970423115425145545378039958152057148").unwrap(),
-        },
+    say!("Cracked x: {}", result);
+
+    Ok(report
+        .check(
+            "public key",
+            format!("{b_pub:?}"),
+            format!("{:?}", curve.gen(&result)),
+        )
+        .recovered("x", result)
+        .count("residues", rx.len() as u64))
+}
+
+/// `curve` with each b and order from `--arg curves=` (a file) or else [`INVALID_CURVES`]
+///
+/// Lines starting with # are comments.
+pub fn invalid_curves(curve: &Curve) -> Result<Vec<Curve>> {
+    let path: Option<String> = crate::options::get().arg("curves")?;
+    let text = match path {
+        Some(path) => std::fs::read_to_string(&path)
+            .map_err(|e| anyhow!("Couldn't read curves from {path}: {e}"))?,
+        None => INVALID_CURVES.text()?.to_string(),
     };
-    let curve3_orders = get_factors(&curve3.params.ord, &limit);
-    // Ensure we don't duplicate r, as we already have that information
-    let curve3_orders: Vec<_> = curve3_orders
-        .into_iter()
-        .filter(|r| !rx.iter().any(|(x, _)| r == x))
-        .collect();
-    println!("Curve 3 factors: {:?}", curve3_orders);
-    rx.extend_from_slice(&get_residues(&curve3, &curve3_orders, &curve, &b_priv));
-
-    println!("Recovered: {:?}", rx);
-    // CRT
-    // First get total product
-    let total_prod = rx
-        .iter()
-        .fold(BigInt::from_usize(1).unwrap(), |a, (r, _)| a * r);
-
-    let mut result: BigInt = BigInt::zero();
-    for (r, x) in rx {
-        let ms = &total_prod / &r;
-        result += x * &ms * invmod(&ms, &r);
-    }
-    result %= &total_prod;
-
-    println!("Cracked x: {}", result);
-    println!("B secret : {}", b_priv);
-    assert_eq!(result, b_priv);
-
-    Ok(())
+    parse_curves(curve, &text)
 }
-fn get_residues(
-    curve: &Curve,
-    orders: &[BigInt],
-    orig_curve: &Curve,
-    b_priv: &BigInt,
-) -> Vec<(BigInt, BigInt)> {
-    let mut recovered = vec![];
-
-    // Skip first factor
-    for r in &orders[1..] {
-        let p1 = get_curve_pt(curve, r);
-        println!("Random point of order {r}: {p1:?}");
-        println!("r P1 = {:?}", curve.scale(&p1, r));
-        // Now send this point to B and see what we get back
-        // (Note that this point still has the same small order in the "real curve" which B uses, as b
-        // does not enter into it
-
-        let b1 = orig_curve.scale(&p1, b_priv);
-        // Reverse b_priv modulo r for this
-        let mut b_r = BigInt::zero();
-        while curve.scale(&p1, &b_r) != b1 {
-            b_r += 1;
+
+fn parse_curves(curve: &Curve, text: &str) -> Result<Vec<Curve>> {
+    text.lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty() && !l.starts_with('#'))
+        .map(|l| {
+            let numbers: Vec<BigInt> = l
+                .split_whitespace()
+                .map(BigInt::from_str)
+                .collect::<Result<_, _>>()
+                .map_err(|e| anyhow!("Bad curve {l:?}: {e}"))?;
+            let [b, ord] = <[BigInt; 2]>::try_from(numbers)
+                .map_err(|_| anyhow!("Expected b and an order, got {l:?}"))?;
+            Ok(Curve {
+                params: CurveParams {
+                    b,
+                    ord,
+                    ..curve.params.clone()
+                },
+            })
+        })
+        .collect()
+}
+
+/// (index into `curves`, r) for the smallest subgroups to confine Bob to, so that the r multiply
+/// to more than `bound`
+///
+/// Each curve offers the primes r below 2^20 which divide its order just once, as those are the
+/// ones there's a point of order r for, apart from 2 (doubling a point with y = 0 goes wrong). A
+/// prime on more than one curve is only taken from the first. Confining Bob to a subgroup of order
+/// r costs up to r MACs, so the primes go in smallest first, and then any the others make up for
+/// without are dropped, smallest first again.
+pub fn pick_subgroups(curves: &[Curve], bound: &BigInt) -> Result<Vec<(usize, BigInt)>> {
+    let limit = BigInt::one() << 20;
+    let mut offered: Vec<(usize, BigInt)> = vec![];
+    for (i, curve) in curves.iter().enumerate() {
+        let ord = &curve.params.ord;
+        for r in get_factors(ord, &limit) {
+            let once = !(ord / &r).is_multiple_of(&r);
+            if r != BigInt::from(2) && once && !offered.iter().any(|(_, s)| s == &r) {
+                offered.push((i, r));
+            }
+        }
+    }
+    offered.sort_by(|(_, r), (_, s)| r.cmp(s));
+
+    let mut product = BigInt::one();
+    let mut picked = vec![];
+    for (i, r) in offered {
+        if &product > bound {
+            break;
+        }
+        product *= &r;
+        picked.push((i, r));
+    }
+    if &product <= bound {
+        return Err(anyhow!(
+            "The curves' small subgroups only cover {product}, not {bound}"
+        ));
+    }
+
+    let mut k = 0;
+    while k < picked.len() {
+        let without = &product / &picked[k].1;
+        match &without > bound {
+            true => {
+                product = without;
+                picked.remove(k);
+            }
+            false => k += 1,
         }
-        recovered.push((r.clone(), b_r));
     }
-    recovered
+    Ok(picked)
+}
+
+impl Group for Curve {
+    type Element = Point;
+
+    fn scale(&self, element: &Point, k: &BigInt) -> Point {
+        Curve::scale(self, element, k)
+    }
+
+    fn multiples<'a>(&'a self, element: &Point) -> Box<dyn Iterator<Item = Point> + 'a> {
+        let element = element.clone();
+        Box::new(std::iter::successors(Some(element.clone()), move |x| {
+            Some(self.add(x, &element))
+        }))
+    }
+
+    fn element_of_order(&self, r: &BigInt, rng: &mut impl Rng) -> Point {
+        get_curve_pt(self, r, rng)
+    }
+
+    /// x and y, each as many bytes as p, or nothing for O
+    fn encode(&self, element: &Point) -> Vec<u8> {
+        let width = self.params.p.to_bytes_be().1.len();
+        let padded = |c: &BigInt| {
+            let bytes = c.to_bytes_be().1;
+            [vec![0; width - bytes.len()], bytes].concat()
+        };
+        match element {
+            Point::P { x, y } => [padded(x), padded(y)].concat(),
+            Point::O => vec![],
+        }
+    }
+
+    fn decode(&self, bytes: &[u8]) -> Result<Point> {
+        let width = self.params.p.to_bytes_be().1.len();
+        match bytes.len() {
+            0 => Ok(Point::O),
+            n if n == 2 * width => {
+                let (x, y) = bytes.split_at(width);
+                Ok(Point::P {
+                    x: BigInt::from_bytes_be(Sign::Plus, x),
+                    y: BigInt::from_bytes_be(Sign::Plus, y),
+                })
+            }
+            n => Err(anyhow!("{n} bytes isn't a point")),
+        }
+    }
+}
+
+impl Abelian for Curve {
+    fn combine(&self, a: &Point, b: &Point) -> Point {
+        self.add(a, b)
+    }
+
+    fn inverse(&self, element: &Point) -> Point {
+        element.invert(&self.params.p)
+    }
 }
 
 /// Tonelli-Shanks modular sqrt
@@ -589,8 +736,6 @@ pub fn ts_sqrt(n: &BigInt, modulus: &BigInt) -> Result<BigInt> {
     }
 
     // p-1 = q * 2^s
-    //println!("p-1:   {}", modulus - &one);
-    //println!("q*2^s: {}", &q * two.exp(&s));
 
     // Now find a z which is quadratic non-residue
     let z = quad_non_res(modulus);
@@ -603,7 +748,6 @@ pub fn ts_sqrt(n: &BigInt, modulus: &BigInt) -> Result<BigInt> {
     let mut r = n.modpow(&qp, modulus);
 
     loop {
-        // println!("In main loop");
         match t {
             z if t == BigInt::zero() => return Ok(z),
             _ if t == one => return Ok(r),
@@ -615,10 +759,8 @@ pub fn ts_sqrt(n: &BigInt, modulus: &BigInt) -> Result<BigInt> {
                 break;
             }
             i = &i + &one;
-            // println!("i: {}", i);
         }
 
-        // println!("i: {}", i);
         let b = c.modpow(&two.exp(&(m - &i - &one)), modulus);
         m = i;
         c = (&b * &b) % modulus;
@@ -659,20 +801,17 @@ fn is_sq(n: &BigInt, modulus: &BigInt) -> bool {
     d == one
 }
 
+/// The smallest quadratic non-residue, which for a prime modulus is hardly ever more than a few
+/// steps from 2, and means a square root always comes out the same
 fn quad_non_res(modulus: &BigInt) -> BigInt {
-    let mut rng = thread_rng();
-    loop {
-        let z = rng.gen_bigint_range(&BigInt::zero(), modulus);
-
-        if !is_sq(&z, modulus) {
-            return z;
-        }
+    let mut z = BigInt::from(2);
+    while is_sq(&z, modulus) {
+        z += 1;
     }
+    z
 }
 
-fn get_curve_pt(curve: &Curve, r: &BigInt) -> Point {
-    let mut rng = thread_rng();
-
+fn get_curve_pt(curve: &Curve, r: &BigInt, rng: &mut impl Rng) -> Point {
     loop {
         let x = rng.gen_bigint_range(&BigInt::zero(), &curve.params.p);
         if let Ok(y) = get_y(curve, &x) {
@@ -695,21 +834,107 @@ fn get_y(curve: &Curve, x: &BigInt) -> Result<BigInt> {
 mod tests {
 
     use super::*;
+    use crate::group::{discrete_log, pohlig_hellman};
+    use rand::{thread_rng, Rng};
 
     #[test]
-    fn scale_test() {
-        let curve = Curve {
-            params: CurveParams {
-                a: BigInt::from_str("-95051").unwrap(),
-                b: BigInt::from_str("11279326").unwrap(),
-                p: BigInt::from_str("233970423115425145524320034830162017933").unwrap(),
-                bp: Point::P {
-                    x: BigInt::from_str("182").unwrap(),
-                    y: BigInt::from_str("85518893674295321206118380980485522083").unwrap(),
-                },
-                ord: BigInt::from_str("233970423115425145498902418297807005944").unwrap(),
-            },
+    fn traces() {
+        let expected = "# k = 111010
+# i = 2, b = 1
+add(1Q, 1Q)
+add(2Q, 1Q)
+# i = 3, b = 1
+add(3Q, 3Q)
+add(6Q, 1Q)
+# i = 4, b = 0
+add(7Q, 7Q)
+# i = 5, b = 1
+add(14Q, 14Q)
+add(28Q, 1Q)
+# i = 6, b = 0
+add(29Q, 29Q)";
+        assert_eq!(trace(&58.into()).to_string(), expected);
+        // 62 only differs from bit 4 on
+        let (a, b) = (trace(&58.into()).steps, trace(&62.into()).steps);
+        assert_eq!(a[..6], b[..6]);
+        assert_eq!(
+            (&a[6], &b[6]),
+            (&Step::Bit { i: 4, b: false }, &Step::Bit { i: 4, b: true })
+        );
+        assert_eq!(b.last(), Some(&Step::Add(31.into(), 31.into())));
+        assert!(trace(&1.into()).steps.is_empty());
+
+        let expected = "add(1Q, 1Q)
+├── b = 0: add(2Q, 2Q)
+│   ├── b = 0: add(4Q, 4Q)
+│   └── b = 1: add(4Q, 1Q), add(5Q, 5Q)
+└── b = 1: add(2Q, 1Q), add(3Q, 3Q)
+    ├── b = 0: add(6Q, 6Q)
+    └── b = 1: add(6Q, 1Q), add(7Q, 7Q)
+";
+        assert_eq!(decision_tree(2), expected);
+    }
+
+    #[test]
+    fn group_laws() {
+        let curve = params::curve();
+        let p = &curve.params.p;
+        let mut rng = thread_rng();
+        // Multiples of the base point, with the identity and points of order 2, 4 and 8 mixed
+        // in, where doubling meets y = 0 and sums wrap round to O
+        let small: Vec<Point> = [2, 4, 8]
+            .iter()
+            .map(|r| curve.element_of_order(&BigInt::from(*r), &mut rng))
+            .collect();
+        let mut point = || match rng.gen_range(0..6) {
+            0 => Point::O,
+            1 => small[rng.gen_range(0..small.len())].clone(),
+            _ => curve.gen(&rng.gen_bigint_range(&BigInt::zero(), params::base_order())),
         };
+        for _ in 0..100 {
+            let (a, b, c) = (point(), point(), point());
+            assert_eq!(curve.add(&a, &b), curve.add(&b, &a), "{a:?} {b:?}");
+            assert_eq!(
+                curve.add(&curve.add(&a, &b), &c),
+                curve.add(&a, &curve.add(&b, &c)),
+                "{a:?} {b:?} {c:?}"
+            );
+            assert_eq!(curve.add(&a, &Point::O), a);
+            assert_eq!(curve.add(&a, &a.invert(p)), Point::O, "{a:?}");
+            assert_eq!(curve.add(&a, &a), curve.scale(&a, &2.into()), "{a:?}");
+            assert_eq!(curve.scale(&a, &curve.params.ord), Point::O, "{a:?}");
+        }
+        // Scaling is repeated addition, whatever the scalars and however large
+        let mut scalars = thread_rng();
+        let bound = BigInt::one() << 140;
+        for _ in 0..20 {
+            let a = point();
+            let j = scalars.gen_bigint_range(&BigInt::zero(), &bound);
+            let k = scalars.gen_bigint_range(&BigInt::zero(), &bound);
+            assert_eq!(
+                curve.add(&curve.scale(&a, &j), &curve.scale(&a, &k)),
+                curve.scale(&a, &(&j + &k)),
+                "{a:?} {j} {k}"
+            );
+            assert_eq!(
+                curve.scale(&a, &(&j * &k)),
+                curve.scale(&curve.scale(&a, &j), &k),
+                "{a:?} {j} {k}"
+            );
+        }
+    }
+
+    #[test]
+    fn multiples() {
+        let curve = params::curve();
+        let stepped: Vec<_> = Group::multiples(curve, &curve.params.bp).take(50).collect();
+        let scaled: Vec<_> = (1..=50).map(|k| curve.gen(&k.into())).collect();
+        assert_eq!(stepped, scaled);
+    }
+
+    #[test]
+    fn scale_test() {
+        let curve = params::curve();
         let mut running = Point::O;
         println!("Base point: {:?}", running);
         for i in 0..1_000 {
@@ -724,18 +949,7 @@ mod tests {
 
     #[test]
     fn ec_abelian() {
-        let curve = Curve {
-            params: CurveParams {
-                a: BigInt::from_str("-95051").unwrap(),
-                b: BigInt::from_str("11279326").unwrap(),
-                p: BigInt::from_str("233970423115425145524320034830162017933").unwrap(),
-                bp: Point::P {
-                    x: BigInt::from_str("182").unwrap(),
-                    y: BigInt::from_str("85518893674295321206118380980485522083").unwrap(),
-                },
-                ord: BigInt::from_str("233970423115425145498902418297807005944").unwrap(),
-            },
-        };
+        let curve = params::curve();
         let p1 = Point::P {
             x: BigInt::from_str("231110995916992900219346197897292237295").unwrap(),
             y: BigInt::from_str("63844552430235414594643301238328922535").unwrap(),
@@ -753,18 +967,7 @@ mod tests {
 
     #[test]
     fn ord() {
-        let curve = Curve {
-            params: CurveParams {
-                a: BigInt::from_str("-95051").unwrap(),
-                b: BigInt::from_str("11279326").unwrap(),
-                p: BigInt::from_str("233970423115425145524320034830162017933").unwrap(),
-                bp: Point::P {
-                    x: BigInt::from_str("182").unwrap(),
-                    y: BigInt::from_str("85518893674295321206118380980485522083").unwrap(),
-                },
-                ord: BigInt::from_str("233970423115425145498902418297807005944").unwrap(),
-            },
-        };
+        let curve = params::curve();
 
         // Test the order!
         let p_ord = curve.scale(&curve.params.bp, &curve.params.ord);
@@ -774,29 +977,18 @@ mod tests {
 
     #[test]
     fn dh_ec() {
-        let curve = Curve {
-            params: CurveParams {
-                a: BigInt::from_str("-95051").unwrap(),
-                b: BigInt::from_str("11279326").unwrap(),
-                p: BigInt::from_str("233970423115425145524320034830162017933").unwrap(),
-                bp: Point::P {
-                    x: BigInt::from_str("182").unwrap(),
-                    y: BigInt::from_str("85518893674295321206118380980485522083").unwrap(),
-                },
-                ord: BigInt::from_str("233970423115425145498902418297807005944").unwrap(),
-            },
-        };
+        let curve = params::curve();
 
-        let ord = BigInt::from_str("29246302889428143187362802287225875743").unwrap();
+        let ord = params::base_order();
 
         let mut rng = thread_rng();
 
         // Generate A's private key
-        let a_priv = rng.gen_bigint_range(&BigInt::zero(), &ord);
+        let a_priv = rng.gen_bigint_range(&BigInt::zero(), ord);
         let a_pub = curve.gen(&a_priv);
 
         // Generate B's private key
-        let b_priv = rng.gen_bigint_range(&BigInt::zero(), &ord);
+        let b_priv = rng.gen_bigint_range(&BigInt::zero(), ord);
         let b_pub = curve.gen(&b_priv);
 
         let b_shared = curve.scale(&a_pub, &b_priv);
@@ -819,18 +1011,7 @@ mod tests {
 
     #[test]
     fn sqrt_test() {
-        let curve = Curve {
-            params: CurveParams {
-                a: BigInt::from_str("-95051").unwrap(),
-                b: BigInt::from_str("11279326").unwrap(),
-                p: BigInt::from_str("233970423115425145524320034830162017933").unwrap(),
-                bp: Point::P {
-                    x: BigInt::from_str("182").unwrap(),
-                    y: BigInt::from_str("85518893674295321206118380980485522083").unwrap(),
-                },
-                ord: BigInt::from_str("233970423115425145498902418297807005944").unwrap(),
-            },
-        };
+        let curve = params::curve();
 
         for i in 1..10_000 {
             let pt = BigInt::from_usize(i).unwrap();
@@ -843,4 +1024,64 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn invalid_curve_logs() {
+        // y^2 = x^3 - 95051*x + 210, the first of main's invalid curves
+        let curves = parse_curves(params::curve(), INVALID_CURVES.text().unwrap()).unwrap();
+        let curve = &curves[0];
+        assert_eq!(curve.params.b, 210.into());
+        assert_eq!(&curve.params.p, params::p());
+        let factors = get_factors(&curve.params.ord, &BigInt::from(1 << 20));
+        let factors = &factors[1..];
+        let mut rng = thread_rng();
+        let b_priv = rng.gen_bigint_range(&BigInt::zero(), &curve.params.ord);
+
+        // Each subgroup on its own, as the residues come out of a leaked shared point
+        for r in factors {
+            let h = curve.element_of_order(r, &mut rng);
+            let shared = Group::scale(curve, &h, &b_priv);
+            assert_eq!(discrete_log(curve, &h, &shared, r), Some(&b_priv % r));
+        }
+
+        // And all at once, from a point of order the product of them
+        let h = factors.iter().fold(Point::O, |acc, r| {
+            curve.add(&acc, &curve.element_of_order(r, &mut rng))
+        });
+        let shared = Group::scale(curve, &h, &b_priv);
+        let n: BigInt = factors.iter().product();
+        assert_eq!(
+            pohlig_hellman(curve, &h, &shared, factors),
+            Some(&b_priv % n)
+        );
+    }
+
+    #[test]
+    fn picking_curves() {
+        let curve = params::curve();
+        let bound = params::curve_order();
+        let curves = parse_curves(curve, INVALID_CURVES.text().unwrap()).unwrap();
+        assert_eq!(curves.len(), 3);
+        assert_eq!(curves[1].params.b, 504.into());
+        assert_eq!(curves[1].params.a, curve.params.a);
+
+        let picked = pick_subgroups(&curves, bound).unwrap();
+        let rs: Vec<&BigInt> = picked.iter().map(|(_, r)| r).collect();
+        let product: BigInt = rs.iter().copied().product();
+        assert!(&product > bound);
+        for (i, r) in &picked {
+            assert_ne!(r, &BigInt::from(2));
+            assert!(curves[*i].params.ord.is_multiple_of(r));
+            assert_eq!(rs.iter().filter(|s| **s == r).count(), 1);
+            // None of them spare
+            assert!(&(&product / r) <= bound);
+        }
+        // 11 divides the first and second curves' orders, so it comes from the first
+        assert!(picked.contains(&(0, 11.into())));
+        assert!(picked.contains(&(1, 7.into())));
+
+        assert!(pick_subgroups(&curves[..1], bound).is_err());
+        assert!(parse_curves(curve, "# b order\n210\n").is_err());
+        assert!(parse_curves(curve, "210 x\n").is_err());
+    }
 }
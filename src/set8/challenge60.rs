@@ -268,103 +268,86 @@
 // slowly.
 
 use anyhow::anyhow;
-use indicatif::ProgressBar;
 use std::{
-    collections::HashMap,
     ops::{BitAnd, Shr},
     str::FromStr,
 };
 
-use num_bigint::{BigInt, RandBigInt};
+use num_bigint::{BigInt, RandBigInt, Sign};
 use num_integer::Integer;
-use num_traits::{FromPrimitive, Zero};
-use rand::thread_rng;
-
-use crate::{set8::challenge57::get_factors, utils::*};
-
-use super::challenge59::{ts_sqrt, Curve, CurveParams, Point};
-
-// How can we solve the DLP in this case?
-// The ladder allows us to calculate n Q very efficiently i.e. is equivalent to our "scale" of
-// before
-// But this isn't really what we do with DLP: we hop the following way
-// g^x -> g^(x+x') -> g^(x+x'+x'')
-// which is equivalent to adding
-// We can keep track of the index and redo the ladder every time i.e. calculate
-// b P, (b+x1)P, (b+x1+x2)P, etc. very efficiently
-// The tame kangaroo is easily doable in this way
-// BUT how do we do this for the wild kangaroo?
-//
-// In this case, it will be easier to do this for the Weierstrass curve as we already have all the
-// addition sorted out
-// Furthermore, I prefer giant-step baby-step :)
-
-// b_pub = (x+modulus n) P
-
-// b_priv = x+modulus n
-// (b_priv -x) = (modulus n)P
-// (b_priv - x) = n (modulus P)
-
-/*
-fn dlp(b_pub: &BigInt, x: &BigInt, modulus: &BigInt) -> Option<BigInt> {
-    let curve = Curve {
-        params: CurveParams {
-            a: BigInt::from_str("-95051").unwrap(),
-            b: BigInt::from_str("11279326").unwrap(),
-            p: BigInt::from_str("233970423115425145524320034830162017933").unwrap(),
-            bp: Point::P {
-                x: BigInt::from_str("182").unwrap(),
-                y: BigInt::from_str("85518893674295321206118380980485522083").unwrap(),
-            },
-            ord: BigInt::from_str("233970423115425145498902418297807005944").unwrap(),
-        },
-    };
+use num_traits::{FromPrimitive, One, Zero};
+use rand::Rng;
+
+use crate::report::say;
+use crate::{
+    group::{herd_kangaroo, Abelian, Group},
+    key_agreement::{self, mac, Bob, KeyAgreementOracle},
+    set8::challenge57::{get_factors, subgroup_confinement},
+    trace::event,
+    utils::*,
+};
 
-    // Convert b_pub to true b_pub
-    let b_pub = b_pub - BigInt::from_usize(178).unwrap();
-
-    // b_pub = x + modulus*n
-    // We wish to find n
-    let modn: BigInt = &b_pub - x;
-
-    let limit = 2_usize.pow(20);
-    let m = 2_usize.pow(10);
-    // Now we're going to do giant step baby step
-    let h = HashMap::new();
-    // y' = n *(modulus * P)
-    // n = i + j m
-    // First do giant step
-    for j in 0..m {
-        let jm: BigInt = curve.scale(
-            &curve.params.bp,
-            &(&curve.params.ord - &BigInt::from_usize(j * m).unwrap()),
-        );
-        let i = curve.add(&b_pub, &jm);
-        h.insert(i, j);
+use super::{
+    challenge59::{ts_sqrt, Point},
+    params,
+};
+
+/// Kangaroos of each kind in [`kangaroo_for_mc`]'s herd
+const HERD: usize = 8;
+
+/// Bob's private key, given x = ±`res` mod `modulus`, and that x = r + n `modulus` for r the true
+/// residue and n in [0, `upper`], along with how many jumps it took
+///
+/// The tame kangaroos could have their u recomputed with the ladder after each jump, from the
+/// index, but there's no index for the wild ones, and u alone can't be added to. So they all jump
+/// on the whole points of [`Lifted`]. That leaves two signs unknown: that of the residue, and that
+/// of the v found for Bob's public key, which might be -x P rather than x P. So the herd has four
+/// targets, one for each way round, and a kind of wild kangaroo for each.
+fn kangaroo_for_mc(
+    curve: &MontgomeryCurve,
+    res: &BigInt,
+    modulus: &BigInt,
+    b_pub: &BigInt,
+    upper: &BigInt,
+    rng: &mut impl Rng,
+) -> Result<(BigInt, u64)> {
+    let lifted = Lifted {
+        curve: curve.clone(),
+    };
+    let p = curve.lift(&curve.bp)?;
+    let y = curve.lift(b_pub)?;
+    let g = curve.scale_point(&p, modulus);
+    // y - r P = n g, for one of these
+    let (mut residues, mut targets) = (vec![], vec![]);
+    for y in [y.clone(), y.invert(&curve.p)] {
+        for r in [res.clone(), (-res).mod_floor(modulus)] {
+            targets.push(curve.add_points(&y, &curve.scale_point(&p, &r).invert(&curve.p)));
+            residues.push(r);
+        }
     }
 
-    None
+    let (i, n, jumps) = herd_kangaroo(&lifted, &g, &targets, upper, HERD, rng)?;
+    Ok((&residues[i] + n * modulus, jumps))
 }
-*/
 
 //  B*v^2 = u^3 + A*u^2 + u
 #[allow(non_snake_case, dead_code)]
-struct MontgomeryCurve {
-    A: BigInt,
-    B: BigInt,
-    p: BigInt,
-    bp: BigInt,
-    ord: BigInt,
+#[derive(Debug, Clone)]
+pub struct MontgomeryCurve {
+    pub A: BigInt,
+    pub B: BigInt,
+    pub p: BigInt,
+    pub bp: BigInt,
+    pub ord: BigInt,
 }
 
 impl MontgomeryCurve {
-    fn ladder(&self, u: &BigInt, k: &BigInt) -> BigInt {
+    pub fn ladder(&self, u: &BigInt, k: &BigInt) -> BigInt {
         let one = BigInt::from_usize(1).unwrap();
         let two = BigInt::from_usize(2).unwrap();
         let (mut u2, mut w2) = (one.clone(), BigInt::zero());
         let (mut u3, mut w3) = (u.clone(), one.clone());
         for i in (0..self.p.bits()).rev() {
-            //            println!("i: {i}");
             let b = one.clone().bitand(k.shr(i));
             if b == one {
                 std::mem::swap(&mut u2, &mut u3);
@@ -394,20 +377,16 @@ impl MontgomeryCurve {
         //     B*v^2 = u^3 + A*u^2 + u
         let v1 = self.get_v(&u1)?.mod_floor(&self.p);
         let v2 = self.get_v(&u2)?.mod_floor(&self.p);
-        println!("v1: {}", v1);
-        println!("v2: {}", v2);
 
         let u3 = match u1 == u2 {
             // Distinct points
             false => {
-                println!("Distinct");
                 let num: BigInt = &v2 - &v1;
                 let den: BigInt = u2 - u1;
                 &self.B * &num * &num * invmod(&(&den * &den), &self.p) - &self.A - u1 - u2
             }
             // Doubling point
             true => {
-                println!("Double");
                 let one = BigInt::from_usize(1).unwrap();
                 let num: BigInt = 3 * u1 * u1 + 2 * &self.A * u1 + &one;
                 let den: BigInt = 2 * &self.B * &v1;
@@ -418,377 +397,398 @@ impl MontgomeryCurve {
     }
     */
 
+    /// u(P), u(2P), u(3P), ... from u = u(P), keeping (k - 1)P and kP as (U : W) and getting
+    /// (k + 1)P by the differential addition (k + 1)P = kP + P, where the difference is (k - 1)P
+    ///
+    /// That only breaks down when the difference is O, which is when kP = P, so then it doubles
+    /// instead.
+    fn stepper(&self, u: &BigInt) -> impl Iterator<Item = BigInt> + '_ {
+        let u = u.clone();
+        let one = BigInt::one();
+        let start = ((one.clone(), BigInt::zero()), (u.clone(), one));
+        std::iter::successors(Some(start), move |(prev, cur)| {
+            let (x, z) = cur;
+            let next = match prev.1.is_zero() {
+                true => {
+                    let (xx, zz, xz) = (x * x, z * z, x * z);
+                    (
+                        (&xx - &zz) * (&xx - &zz) % &self.p,
+                        4 * &xz * (&xx + &self.A * &xz + &zz) % &self.p,
+                    )
+                }
+                false => {
+                    let a: BigInt = (x - z) * (&u + 1);
+                    let b: BigInt = (x + z) * (&u - 1);
+                    (
+                        &prev.1 * (&a + &b) * (&a + &b) % &self.p,
+                        &prev.0 * (&a - &b) * (&a - &b) % &self.p,
+                    )
+                }
+            };
+            Some((cur.clone(), next))
+        })
+        .map(|(_, (x, z))| match z.mod_floor(&self.p).is_zero() {
+            true => BigInt::zero(),
+            false => (x * z.modpow(&(&self.p - 2), &self.p)).mod_floor(&self.p),
+        })
+    }
+
+    /// (u, v) for v either square root of (u^3 + A u^2 + u) / B
+    fn lift(&self, u: &BigInt) -> Result<Point> {
+        Ok(Point::P {
+            x: u.clone(),
+            y: self.get_v(u)?.mod_floor(&self.p),
+        })
+    }
+
+    /// Addition of whole points on the curve, with x as u and y as v
+    fn add_points(&self, p1: &Point, p2: &Point) -> Point {
+        self.add_many(&[(p1, p2)]).remove(0)
+    }
+
+    /// The sum of each pair, with one inversion between them all (by Montgomery's trick: invert
+    /// the product of the denominators, and peel each one's inverse off that)
+    fn add_many(&self, pairs: &[(&Point, &Point)]) -> Vec<Point> {
+        // Either the sum, or the slope of the line through the points as a fraction
+        let sums: Vec<std::result::Result<Point, (BigInt, BigInt)>> = pairs
+            .iter()
+            .map(|(p1, p2)| {
+                let (Point::P { x: u1, y: v1 }, Point::P { x: u2, y: v2 }) = (p1, p2) else {
+                    return Ok(match p1 {
+                        Point::O => (*p2).clone(),
+                        _ => (*p1).clone(),
+                    });
+                };
+                match u1 == u2 {
+                    true if (v1 + v2).is_multiple_of(&self.p) => Ok(Point::O),
+                    true => Err((
+                        3 * u1 * u1 + 2 * &self.A * u1 + 1,
+                        (2 * &self.B * v1 % &self.p),
+                    )),
+                    false => Err((v2 - v1, (u2 - u1).mod_floor(&self.p))),
+                }
+            })
+            .collect();
+
+        let mut products = vec![BigInt::one()];
+        for sum in &sums {
+            let last = products.last().unwrap();
+            let next = match sum {
+                Ok(_) => last.clone(),
+                Err((_, den)) => last * den % &self.p,
+            };
+            products.push(next);
+        }
+        // 1 / (the product of the denominators up to each one)
+        let mut inverse = try_invmod(products.last().unwrap(), &self.p).unwrap();
+        let mut result = vec![Point::O; sums.len()];
+        for (i, sum) in sums.into_iter().enumerate().rev() {
+            result[i] = match sum {
+                Ok(point) => point,
+                Err((num, den)) => {
+                    let slope = num * &inverse * &products[i] % &self.p;
+                    inverse = inverse * den % &self.p;
+                    let (Point::P { x: u1, y: v1 }, Point::P { x: u2, .. }) = pairs[i] else {
+                        unreachable!()
+                    };
+                    let u3 = (&self.B * &slope * &slope - &self.A - u1 - u2).mod_floor(&self.p);
+                    let v3 = (slope * (u1 - &u3) - v1).mod_floor(&self.p);
+                    Point::P { x: u3, y: v3 }
+                }
+            };
+        }
+        result
+    }
+
+    /// k `point` by doubling and adding
+    fn scale_point(&self, point: &Point, k: &BigInt) -> Point {
+        let mut result = Point::O;
+        for i in (0..k.bits()).rev() {
+            result = self.add_points(&result, &result);
+            if k.bit(i) {
+                result = self.add_points(&result, point);
+            }
+        }
+        result
+    }
+
     fn get_v(&self, u: &BigInt) -> Result<BigInt> {
-        let vsq = (u * u * u + &self.A * u * u + u) * invmod(&self.B, &self.p);
+        let vsq = (u * u * u + &self.A * u * u + u) * try_invmod(&self.B, &self.p).unwrap();
 
         ts_sqrt(&vsq, &self.p)
     }
 }
 
-pub fn main() -> Result<()> {
-    let curve = MontgomeryCurve {
-        A: BigInt::from_str("534").unwrap(),
-        B: BigInt::from_str("1").unwrap(),
-        p: BigInt::from_str("233970423115425145524320034830162017933").unwrap(),
-        bp: BigInt::from_str("4").unwrap(),
-        ord: BigInt::from_str("233970423115425145498902418297807005944").unwrap(),
-    };
-    println!("ladder(4,n): {}", curve.ladder(&curve.bp, &curve.ord));
+impl Group for MontgomeryCurve {
+    type Element = BigInt;
+
+    fn scale(&self, element: &BigInt, k: &BigInt) -> BigInt {
+        self.ladder(element, k)
+    }
+
+    fn multiples<'a>(&'a self, element: &BigInt) -> Box<dyn Iterator<Item = BigInt> + 'a> {
+        Box::new(self.stepper(element))
+    }
+
+    /// A u with a v to go with it, so a point on the curve, scaled by ord / r
+    fn element_of_order(&self, r: &BigInt, rng: &mut impl Rng) -> BigInt {
+        loop {
+            let u = rng.gen_bigint_range(&BigInt::zero(), &self.p);
+            if self.get_v(&u).is_ok() {
+                let p = self.ladder(&u, &(&self.ord / r));
+                if p != BigInt::zero() {
+                    return p;
+                }
+            }
+        }
+    }
+
+    fn encode(&self, element: &BigInt) -> Vec<u8> {
+        element.to_bytes_be().1
+    }
+
+    fn decode(&self, bytes: &[u8]) -> Result<BigInt> {
+        Ok(BigInt::from_bytes_be(Sign::Plus, bytes))
+    }
+}
+
+/// The twist of a Montgomery curve: the u without a v on the curve, which the ladder scales all
+/// the same
+#[derive(Clone)]
+struct Twist {
+    curve: MontgomeryCurve,
+    order: BigInt,
+}
+
+impl Group for Twist {
+    type Element = BigInt;
+
+    fn scale(&self, element: &BigInt, k: &BigInt) -> BigInt {
+        self.curve.ladder(element, k)
+    }
+
+    fn multiples<'a>(&'a self, element: &BigInt) -> Box<dyn Iterator<Item = BigInt> + 'a> {
+        Box::new(self.curve.stepper(element))
+    }
+
+    fn element_of_order(&self, r: &BigInt, rng: &mut impl Rng) -> BigInt {
+        gen_twist_point(&self.curve, r, &self.order, rng)
+    }
+
+    fn encode(&self, element: &BigInt) -> Vec<u8> {
+        element.to_bytes_be().1
+    }
+
+    fn decode(&self, bytes: &[u8]) -> Result<BigInt> {
+        Ok(BigInt::from_bytes_be(Sign::Plus, bytes))
+    }
+}
+
+/// A Montgomery curve's whole points, (u, v), which can be added and negated as u alone can't
+///
+/// Each addition needs an inverse, and those cost about as much as all the rest of it many times
+/// over, so a batch of them shares one by Montgomery's trick.
+#[derive(Clone)]
+struct Lifted {
+    curve: MontgomeryCurve,
+}
+
+impl Group for Lifted {
+    type Element = Point;
+
+    fn scale(&self, element: &Point, k: &BigInt) -> Point {
+        self.curve.scale_point(element, k)
+    }
+
+    /// The curve's own element of order r, with either v
+    fn element_of_order(&self, r: &BigInt, rng: &mut impl Rng) -> Point {
+        let u = self.curve.element_of_order(r, rng);
+        self.curve.lift(&u).expect("a u on the curve")
+    }
+
+    fn encode(&self, element: &Point) -> Vec<u8> {
+        let width = self.curve.p.to_bytes_be().1.len();
+        let padded = |c: &BigInt| {
+            let bytes = c.to_bytes_be().1;
+            [vec![0; width - bytes.len()], bytes].concat()
+        };
+        match element {
+            Point::P { x, y } => [padded(x), padded(y)].concat(),
+            Point::O => vec![],
+        }
+    }
+
+    fn decode(&self, bytes: &[u8]) -> Result<Point> {
+        let width = self.curve.p.to_bytes_be().1.len();
+        match bytes.len() {
+            0 => Ok(Point::O),
+            n if n == 2 * width => {
+                let (u, v) = bytes.split_at(width);
+                Ok(Point::P {
+                    x: BigInt::from_bytes_be(Sign::Plus, u),
+                    y: BigInt::from_bytes_be(Sign::Plus, v),
+                })
+            }
+            n => Err(anyhow!("{n} bytes isn't a point")),
+        }
+    }
+}
+
+impl Abelian for Lifted {
+    fn combine(&self, a: &Point, b: &Point) -> Point {
+        self.curve.add_points(a, b)
+    }
+
+    fn inverse(&self, element: &Point) -> Point {
+        element.invert(&self.curve.p)
+    }
+
+    fn combine_many(&self, pairs: &[(&Point, &Point)]) -> Vec<Point> {
+        self.curve.add_many(pairs)
+    }
+}
+
+pub fn main() -> Result<Report> {
+    let curve = params::montgomery();
+    say!("ladder(4,n): {}", curve.ladder(&curve.bp, &curve.ord));
 
     let u = BigInt::from_str("76600469441198017145391791613091732004").unwrap();
     let k = BigInt::from_str("11").unwrap();
-    println!(
+    say!(
         "ladder(76600469441198017145391791613091732004, 11): {}",
         curve.ladder(&u, &k)
     );
 
     // v^2 = u^3 + 534*u^2 + u
-    println!("corresponding v: {:?}", curve.get_v(&u));
+    event!(Debug, "corresponding v", v = format!("{:?}", curve.get_v(&u)));
 
     let twist_ord: BigInt = 2 * &curve.p + BigInt::from_usize(2).unwrap() - &curve.ord;
 
-    println!("Order: {}", curve.ord);
-    println!("Twist order: {}", twist_ord);
+    event!(Debug, "orders", curve = curve.ord, twist = twist_ord);
     let limit = BigInt::from_usize(2).unwrap().pow(24);
     let twist_factors = get_factors(&twist_ord, &limit);
 
-    println!("Twist order factors: {:?}", twist_factors);
-    println!(
-        "Effective order: {} bits",
-        (&curve.ord
+    event!(
+        Debug,
+        "small factors of the twist's order",
+        factors = format!("{twist_factors:?}"),
+        bits_left = (&curve.ord
             / twist_factors
                 .iter()
                 .fold(BigInt::from_usize(1).unwrap(), |a, x| a * x))
         .bits()
     );
-    let mut rng = thread_rng();
-    let b_priv = rng.gen_bigint_range(&BigInt::zero(), &curve.ord);
-    let b_pub = curve.ladder(&curve.bp, &b_priv);
-
-    let mut rx: Vec<(BigInt, BigInt)> = vec![];
-
-    let mut running_modulus = BigInt::from_usize(1).unwrap();
-    let mut running_residue = BigInt::zero();
-
-    for r in &twist_factors[1..] {
-        println!("r: {}", r);
-
-        let p = gen_twist_point(&curve, r, &twist_ord);
-        println!("ladder(p,r): {}", curve.ladder(&p, r));
-        // Send point to Bob
-        let b_shared = curve.ladder(&p, &b_priv);
-        // Now crack this
-        let res = get_residue(&curve, &p, &b_shared, r).mod_floor(r);
-        // res is "+ve" root
-        println!("res: {}", res);
-        println!("-res: {}", (-&res).mod_floor(r));
-        println!("b_priv % r: {}", b_priv.mod_floor(r));
-
-        // The true residue could be +- this, so try both
-        match running_modulus == BigInt::from_usize(1).unwrap() {
-            false => {
-                let p = r.clone();
-                let q = running_modulus.clone();
-                let p_i = invmod(&p, &q);
-                let q_i = invmod(&q, &p);
-
-                let rrp = running_residue.mod_floor(&q);
-                let rrm = (-&running_residue).mod_floor(&q);
-                println!("rrp: {}", rrp);
-                println!("rrm: {}", rrm);
-
-                let resp = res.mod_floor(&p);
-                let resm = (-&res).mod_floor(&p);
-                println!("resp: {}", resp);
-                println!("resm: {}", resm);
-
-                let crt_a: BigInt = (&rrp * &p * &p_i + &resp * &q * &q_i).mod_floor(&(&p * &q));
-                let crt_b: BigInt = (&rrp * &p * &p_i + &resm * &q * &q_i).mod_floor(&(&p * &q));
-                let crt_c: BigInt = (&rrm * &p * &p_i + &resp * &q * &q_i).mod_floor(&(&p * &q));
-                let crt_d: BigInt = (&rrm * &p * &p_i + &resm * &q * &q_i).mod_floor(&(&p * &q));
-
-                running_modulus = &p * &q;
-                println!("Running modulus: {}", running_modulus);
-                let p_new = gen_twist_point(&curve, &running_modulus, &twist_ord);
-
-                println!(
-                    "ladder(p_new,{}): {}",
-                    running_modulus,
-                    curve.ladder(&p_new, &running_modulus)
-                );
-                println!("ladder(p_new,{}): {}", p, curve.ladder(&p_new, &p));
-                println!("ladder(p_new,{}): {}", q, curve.ladder(&p_new, &q));
-                let b_a = curve.ladder(&p_new, &crt_a);
-                let b_b = curve.ladder(&p_new, &crt_b);
-                let b_c = curve.ladder(&p_new, &crt_c);
-                let b_d = curve.ladder(&p_new, &crt_d);
-                let b_new = curve.ladder(&p_new, &b_priv);
-
-                println!("x_a: {}", crt_a);
-                println!("x_b: {}", crt_b);
-                println!("x_c: {}", crt_c);
-                println!("x_d: {}", crt_d);
-                println!("b_priv % mod: {}", b_priv.mod_floor(&running_modulus));
-                println!("b_a: {}", b_a);
-                println!("b_b: {}", b_b);
-                println!("b_c: {}", b_c);
-                println!("b_d: {}", b_d);
-                println!("b_new: {}", b_new);
-
-                if b_a == b_new {
-                    running_residue = crt_a;
-                } else if b_b == b_new {
-                    running_residue = crt_b;
-                } else if b_c == b_new {
-                    running_residue = crt_c;
-                } else if b_d == b_new {
-                    running_residue = crt_d;
-                } else {
-                    panic!("Neither worked!");
-                }
-                println!("running res: {}", running_residue);
-                println!(
-                    "running res: {}",
-                    (-&running_residue).mod_floor(&running_modulus)
-                );
-            }
-            true => {
-                running_modulus = &running_modulus * r;
-                running_residue = res.mod_floor(r);
-            }
-        };
-        rx.push((r.clone(), res.clone()));
-    }
-    println!("Residues: {:?}", rx);
-
-    //let crt_result = crt(&rx);
-    //println!("Crt result: {:?}", crt_result);
-    //let x = crt_result.0;
-    //let m = crt_result.1;
-
-    //// Check CRT is working
-    //println!("bpriv = {x} mod {m}");
-    println!("bpriv mod m = {}", &b_priv % &running_modulus);
-    println!("running residue = {}", running_residue);
-    println!("-running residue = {}", &running_modulus - &running_residue);
-
-    // We're now going to big-step little-step this
-    // Unfortunately, the ladder is not terribly helpful here, so we'll instead use the Weierstrass
-    // curve, as here we have good old addition
-    let bits = (twist_ord / &running_modulus).bits() as u32;
-    println!("Remaining bits: {}", bits);
-
-    let cracked = match shanks_for_mc(&running_residue, &running_modulus, &b_pub, bits) {
-        Some(x) => x,
-        None => match shanks_for_mc(
-            &(&running_modulus - &running_residue),
-            &running_modulus,
-            &b_pub,
-            bits,
-        ) {
-            Some(x) => x,
-            None => panic!("Never found!"),
-        },
+    let mut rng = crate::utils::rng();
+    let bob = key_agreement::connect(Bob::new(curve.clone(), &curve.bp, &curve.ord, &mut rng))?;
+    let b_pub = bob.public_key().clone();
+    let twist = Twist {
+        curve: curve.clone(),
+        order: twist_ord.clone(),
     };
-    println!("Cracked: {}", cracked);
-    let other_cracked: BigInt = &curve.ord - &cracked;
-    println!("Other cracked: {}", other_cracked);
-    println!("b_priv: {}", b_priv);
-
-    let found: bool = (cracked == b_priv) || (other_cracked == b_priv);
-
-    assert!(found);
-
-    // index = x mod m
-
-    // Now do a Kangaroo on this
-    //
-    // index = x + n m
-    // b_pub = ladder(base, index)
-    // Which we can think of in the same way as p
-    // y = ladder(ladder(base, x), n m)
-
-    Ok(())
+    // Only u is sent, and -x P has the same u as x P, so each residue is only known up to sign
+    let rx = subgroup_confinement(bob.as_ref(), &twist, &twist_factors[1..], &mut rng)?;
+    event!(Debug, "residues up to sign", rx = format!("{rx:?}"));
+
+    let (running_residue, running_modulus) =
+        combine_residues_with_oracle(bob.as_ref(), &twist, &rx, &mut rng)?;
+    say!("Residue: {running_residue} mod {running_modulus}");
+
+    // Bob's key is below the order of the curve, and the residues are of the key itself rather
+    // than the key mod the base point's order, so that's the range to search
+    let upper: BigInt = &curve.ord / &running_modulus;
+    event!(Info, "range narrowed", bits = upper.bits());
+    let (cracked, jumps) = kangaroo_for_mc(
+        curve,
+        &running_residue,
+        &running_modulus,
+        &b_pub,
+        &upper,
+        &mut rng,
+    )?;
+    say!("Cracked: {}", cracked);
+
+    // Either one gives Bob's public key, as it's only u
+    Ok(Report::new()
+        .check("public key", &b_pub, curve.ladder(&curve.bp, &cracked))
+        .recovered("x", cracked)
+        .count("residues", rx.len() as u64)
+        .count("jumps", jumps))
 }
 
-fn shanks_for_mc(res: &BigInt, modulus: &BigInt, b_pub: &BigInt, bits: u32) -> Option<BigInt> {
-    // First convert b_pub point from Montgomery curve to Weierstrass
-    // N.B. that b_priv will actually be ill-defined from this procedure, as there are two points
-    // which generate b_pub: b_priv, and ord-b_priv
-    // This must be true, because (x,y) and (x,-y) add to give O by the geometric rule
-
-    // So, we will pick the positive sqrt of the equation to get started and work from there
-    let curve = Curve {
-        params: CurveParams {
-            a: BigInt::from_str("-95051").unwrap(),
-            b: BigInt::from_str("11279326").unwrap(),
-            p: BigInt::from_str("233970423115425145524320034830162017933").unwrap(),
-            bp: Point::P {
-                x: BigInt::from_str("182").unwrap(),
-                y: BigInt::from_str("85518893674295321206118380980485522083").unwrap(),
-            },
-            ord: BigInt::from_str("233970423115425145498902418297807005944").unwrap(),
-        },
-    };
-
-    //     u = x - 178
-    //     v = y
-    let x = b_pub + &BigInt::from_usize(178).unwrap();
-    // y^2 = x^3 + ax + b
-    let y2: BigInt =
-        (&x * &x * &x + &curve.params.a * &x + &curve.params.b).mod_floor(&curve.params.p);
-
-    let y_one = ts_sqrt(&y2, &curve.params.p).unwrap();
-
-    let ys: [BigInt; 2] = [y_one.clone(), &curve.params.p - &y_one];
-
-    for y in ys {
-        // We now have b_pub as a point
-        let b_pub = Point::P { x: x.clone(), y };
-        println!("Reconstructed Weierstrass point: {:?}", b_pub);
-
-        // b_pub now = b_priv P, where P is our base point
-        // We have established that b_pub = (res + n * modulus) P
-        // So, let's scan over all the different ms until we have a hit
-        // We will just store the x-coordinate for simplicty
-        let mut hm = HashMap::new();
-
-        let m = 2_usize.pow(bits / 2);
-        // Let's now make the hash-table of giant steps
-        // We will denote n = i + j m, m = 0...sqrt(n)
-
-        // For the giant step
-        // dj = (-m modulus P)
-        let dj = curve
-            .scale(
-                &curve.params.bp,
-                &(modulus * &BigInt::from_usize(m).unwrap()),
-            )
-            .invert(&curve.params.p);
-        // b_sub = b_pub - res P
-        let mut b_sub = curve.add(
-            &b_pub,
-            &curve.scale(&curve.params.bp, res).invert(&curve.params.p),
-        );
-
-        println!("Reconstructed Weierstrass point - res P: {:?}", b_sub);
-
-        let fake_index = BigInt::from_str("362400").unwrap();
-
-        println!(
-            "Fake recon: {:?}",
-            curve.scale(&curve.params.bp, &(modulus * &fake_index))
-        );
-        println!("m: {}", m);
-
-        // Should be
-        // j= 353
-        // i= 928
-        //let b_priv = BigInt::from_str("146907443384").unwrap();
-
-        let spinner = ProgressBar::new_spinner();
-        for j in 0..m {
-            if j.is_multiple_of(&1000) {
-                spinner.set_message(format!("Giant step {}: {}", j, m));
-                spinner.tick();
-            }
-            // Now subtract off m P at each step
-            // b_sub = (b_priv - res - j m modulus) P
-            //let b_sub_alleged = curve.scale(
-            //    &curve.params.bp,
-            //    &(&b_priv - res - (j * m) * modulus).mod_floor(&curve.params.ord),
-            //);
-            //println!("j: {}", j);
-            ////println!("True b_sub: {:?}", b_sub_alleged);
-            //println!("b_sub:      {:?}", b_sub);
-            //if b_sub_alleged != b_sub {
-            //    panic!("Differing");
-            //}
-
-            hm.insert(b_sub.clone(), j);
-            b_sub = curve.add(&b_sub, &dj);
-            // Should then simply need to scan the hashmap for i P
-        }
-        spinner.finish();
-
-        // Print correct point
-        // j= 353
-        //let j = BigInt::from_usize(353).unwrap();
-        //let b_sub_alleged = curve.scale(
-        //    &curve.params.bp,
-        //    &(&b_priv - res - (j * m) * modulus).mod_floor(&curve.params.ord),
-        //);
-        //println!("Seeking: {:?}", b_sub_alleged);
-
-        //// i= 928
-        //let i_true = BigInt::from_usize(928).unwrap();
-        //let b_sub_alleged = curve.scale(&curve.params.bp, &(&i_true * modulus));
-        //println!("i_true: {:?}", b_sub_alleged);
-
-        // Now baby step
-        // The entries in the hashmap should now be in the range (0..m) modulus P,
-        // so we just need to check if this is in there
-        let di = curve.scale(&curve.params.bp, modulus);
-        let mut i_p = Point::O;
-        let spinner = ProgressBar::new_spinner();
-        for i in 0..m {
-            if i.is_multiple_of(&1000) {
-                spinner.set_message(format!("Baby step {}: {}", i, m));
-                spinner.tick();
-            }
-            let ib = BigInt::from_usize(i).unwrap();
-            if i != 0 {
-                //i_p = curve.scale(&curve.params.bp, &(modulus * &ib));
-                i_p = curve.add(&i_p, &di);
+/// x mod the product of the r, for residues (r, x mod r) each only known up to sign, give or take
+/// the sign of the result too
+///
+/// CRT on the residues so far (x mod m, up to sign) and the next one (x mod r, up to sign) gives
+/// four candidates for x mod m r, in pairs which are each other's negatives. Asking `oracle` about
+/// an element of order exactly m r tells the two pairs apart, as only the right one gives the same
+/// MAC, so each residue after the first costs one more query.
+pub fn combine_residues_with_oracle<G, O>(
+    oracle: &O,
+    group: &G,
+    rx: &[(BigInt, BigInt)],
+    rng: &mut impl Rng,
+) -> Result<(BigInt, BigInt)>
+where
+    G: Group,
+    O: KeyAgreementOracle<G::Element> + ?Sized,
+{
+    let mut modulus = BigInt::one();
+    let mut residue = BigInt::zero();
+    for (k, (r, x)) in rx.iter().enumerate() {
+        let combined: BigInt = &modulus * r;
+        let inverse = try_invmod(&modulus, r).ok_or_else(|| anyhow!("{r} is a factor twice"))?;
+        let candidates = [x.clone(), -x].map(|x| {
+            let t = (&x - &residue) * &inverse;
+            (&residue + t.mod_floor(r) * &modulus).mod_floor(&combined)
+        });
+        residue = match k {
+            0 => candidates[0].clone(),
+            _ => {
+                let h = element_of_exact_order(group, &combined, &rx[..=k], rng);
+                let t = oracle.exchange(&h)?;
+                candidates
+                    .into_iter()
+                    .find(|x| mac(group, &group.scale(&h, x)) == t)
+                    .ok_or_else(|| anyhow!("No residue mod {combined} fits Bob's MAC"))?
             }
-
-            let b_x = i_p.clone();
-            if let Some(f) = hm.get(&b_x) {
-                //println!("Found a hit: i: {}, j: {}", ib, f);
-                //println!("res: {res}");
-                spinner.finish();
-                //let ib = BigInt::from_str("928").unwrap();
-                //let f = BigInt::from_str("353").unwrap();
-                let index: BigInt = &ib + m * f;
-                //println!("Index: {}", index);
-                let full_index: BigInt = res + modulus * &index;
-                return Some(full_index);
-            }
-        }
-        spinner.finish();
+        };
+        event!(
+            Debug,
+            "residue combined",
+            r = r,
+            modulus_bits = combined.bits()
+        );
+        modulus = combined;
     }
-
-    None
+    Ok((residue, modulus))
 }
 
-fn get_residue(curve: &MontgomeryCurve, pt: &BigInt, b_shared: &BigInt, r: &BigInt) -> BigInt {
+/// An element of order n, the product of the r in `rx`, rather than one of some divisor of it
+fn element_of_exact_order<G: Group>(
+    group: &G,
+    n: &BigInt,
+    rx: &[(BigInt, BigInt)],
+    rng: &mut impl Rng,
+) -> G::Element {
     loop {
-        if let Ok(res) = try_get_residue(curve, pt, b_shared, r) {
-            return res;
+        let h = group.element_of_order(n, rng);
+        let identity = group.scale(&h, &BigInt::zero());
+        if rx
+            .iter()
+            .all(|(r, _)| group.scale(&h, &(n / r)) != identity)
+        {
+            return h;
         }
     }
 }
 
-fn try_get_residue(
+fn gen_twist_point(
     curve: &MontgomeryCurve,
-    pt: &BigInt,
-    b_shared: &BigInt,
     r: &BigInt,
-) -> Result<BigInt> {
-    let mut index = BigInt::zero();
-    while &curve.ladder(pt, &index) != b_shared {
-        index += 1;
-        if &index > r {
-            return Err(anyhow!("Residue not found"));
-        }
-    }
-    Ok(index)
-}
-
-fn gen_twist_point(curve: &MontgomeryCurve, r: &BigInt, twist_order: &BigInt) -> BigInt {
-    let mut rng = thread_rng();
+    twist_order: &BigInt,
+    rng: &mut impl Rng,
+) -> BigInt {
     let nr: BigInt = twist_order / r;
-    println!("nr: {nr}");
 
     loop {
         let u = rng.gen_bigint_range(&BigInt::zero(), &curve.p);
@@ -796,10 +796,9 @@ fn gen_twist_point(curve: &MontgomeryCurve, r: &BigInt, twist_order: &BigInt) ->
         match curve.get_v(&u) {
             Ok(_) => {}
             Err(_) => {
-                //println!("Found a u: {u}");
                 let p = curve.ladder(&u, &nr);
-                //println!("Found a p: {p}");
                 if p != BigInt::zero() {
+                    event!(Trace, "twist point of order r", r = r, u = p);
                     return p;
                 }
             }
@@ -807,63 +806,47 @@ fn gen_twist_point(curve: &MontgomeryCurve, r: &BigInt, twist_order: &BigInt) ->
     }
 }
 
-/*
-/// Takes vector of (modulus, residue) and returns result of CRT
-fn crt(rx: &[(BigInt, BigInt)]) -> (BigInt, BigInt) {
-    let total_prod = rx
-        .iter()
-        .fold(BigInt::from_usize(1).unwrap(), |a, (r, _)| a * r);
-
-    let mut result: BigInt = BigInt::zero();
-    for (r, x) in rx {
-        let ms = &total_prod / r;
-        result += x * &ms * invmod(&ms, r);
-    }
-    (result % &total_prod, total_prod)
-}
-*/
-
 #[cfg(test)]
 mod tests {
-    use crate::set8::challenge59::{Curve, CurveParams, Point};
+    use crate::set8::challenge59::Point;
+    use rand::{thread_rng, Rng};
 
     use super::*;
 
     #[test]
     fn montgomery_order_test() {
-        let curve = MontgomeryCurve {
-            A: BigInt::from_str("534").unwrap(),
-            B: BigInt::from_str("1").unwrap(),
-            p: BigInt::from_str("233970423115425145524320034830162017933").unwrap(),
-            bp: BigInt::from_str("4").unwrap(),
-            ord: BigInt::from_str("233970423115425145498902418297807005944").unwrap(),
-        };
+        let curve = params::montgomery();
         println!("ladder(4,n): {}", curve.ladder(&curve.bp, &curve.ord));
         assert_eq!(curve.ladder(&curve.bp, &curve.ord), BigInt::zero());
     }
 
     #[test]
-    fn montgomery_ec_test() {
-        let ec = Curve {
-            params: CurveParams {
-                a: BigInt::from_str("-95051").unwrap(),
-                b: BigInt::from_str("11279326").unwrap(),
-                p: BigInt::from_str("233970423115425145524320034830162017933").unwrap(),
-                bp: Point::P {
-                    x: BigInt::from_str("182").unwrap(),
-                    y: BigInt::from_str("85518893674295321206118380980485522083").unwrap(),
-                },
-                ord: BigInt::from_str("233970423115425145498902418297807005944").unwrap(),
-            },
+    fn montgomery_multiples() {
+        let mc = params::montgomery();
+        let twist_ord: BigInt = 2 * &mc.p + 2 - &mc.ord;
+        let twist = Twist {
+            curve: mc.clone(),
+            order: twist_ord,
         };
 
-        let mc = MontgomeryCurve {
-            A: BigInt::from_str("534").unwrap(),
-            B: BigInt::from_str("1").unwrap(),
-            p: BigInt::from_str("233970423115425145524320034830162017933").unwrap(),
-            bp: BigInt::from_str("4").unwrap(),
-            ord: BigInt::from_str("233970423115425145498902418297807005944").unwrap(),
-        };
+        let stepped: Vec<_> = mc.stepper(&mc.bp).take(50).collect();
+        let laddered: Vec<_> = (1..=50).map(|k| mc.ladder(&mc.bp, &k.into())).collect();
+        assert_eq!(stepped, laddered);
+
+        // Through O and round again, on the twist
+        let r = BigInt::from(11);
+        let u = twist.element_of_order(&r, &mut thread_rng());
+        let stepped: Vec<_> = twist.multiples(&u).take(30).collect();
+        let laddered: Vec<_> = (1..=30).map(|k| mc.ladder(&u, &k.into())).collect();
+        assert_eq!(stepped, laddered);
+        assert_eq!(stepped[10], BigInt::zero());
+    }
+
+    #[test]
+    fn montgomery_ec_test() {
+        let ec = params::curve();
+
+        let mc = params::montgomery();
 
         for n in 1..100 {
             let p = ec.gen(&n.into()).get_x();
@@ -874,13 +857,7 @@ mod tests {
 
     #[test]
     fn montgomery_dup_test() {
-        let mc = MontgomeryCurve {
-            A: BigInt::from_str("534").unwrap(),
-            B: BigInt::from_str("1").unwrap(),
-            p: BigInt::from_str("233970423115425145524320034830162017933").unwrap(),
-            bp: BigInt::from_str("4").unwrap(),
-            ord: BigInt::from_str("233970423115425145498902418297807005944").unwrap(),
-        };
+        let mc = params::montgomery();
 
         let i1 = BigInt::from_usize(50).unwrap();
         let p1 = mc.ladder(&mc.bp, &i1);
@@ -896,82 +873,54 @@ mod tests {
     }
 
     #[test]
-    fn montgomery_shanks_test() {
-        let mc = MontgomeryCurve {
-            A: BigInt::from_str("534").unwrap(),
-            B: BigInt::from_str("1").unwrap(),
-            p: BigInt::from_str("233970423115425145524320034830162017933").unwrap(),
-            bp: BigInt::from_str("4").unwrap(),
-            ord: BigInt::from_str("233970423115425145498902418297807005944").unwrap(),
-        };
-
-        let wc = Curve {
-            params: CurveParams {
-                a: BigInt::from_str("-95051").unwrap(),
-                b: BigInt::from_str("11279326").unwrap(),
-                p: BigInt::from_str("233970423115425145524320034830162017933").unwrap(),
-                bp: Point::P {
-                    x: BigInt::from_str("182").unwrap(),
-                    y: BigInt::from_str("85518893674295321206118380980485522083").unwrap(),
-                },
-                ord: BigInt::from_str("233970423115425145498902418297807005944").unwrap(),
-            },
-        };
+    fn montgomery_points() {
+        let mc = params::montgomery();
+        let p = mc.lift(&mc.bp).unwrap();
+        // Adding and doubling agree with the ladder on u
+        let mut sum = Point::O;
+        for k in 1..=20u32 {
+            sum = mc.add_points(&sum, &p);
+            assert_eq!(sum.get_x(), Some(mc.ladder(&mc.bp, &k.into())), "{k}");
+            assert_eq!(mc.scale_point(&p, &k.into()), sum, "{k}");
+        }
+        let k = BigInt::from_str("146907443384").unwrap();
+        assert_eq!(mc.scale_point(&p, &k).get_x(), Some(mc.ladder(&mc.bp, &k)));
+        assert_eq!(mc.add_points(&p, &p.invert(&mc.p)), Point::O);
+
+        // As a group, with batches added together
+        let lifted = Lifted { curve: mc.clone() };
+        let q = lifted.scale(&p, &k);
+        for point in [p.clone(), q.clone(), Point::O] {
+            assert_eq!(lifted.decode(&lifted.encode(&point)).unwrap(), point);
+        }
+        let pairs = [(&p, &q), (&q, &q), (&p, &Point::O)];
+        let sums: Vec<Point> = pairs.iter().map(|(a, b)| lifted.combine(a, b)).collect();
+        assert_eq!(lifted.combine_many(&pairs), sums);
+    }
 
+    #[test]
+    fn montgomery_kangaroo_test() {
+        let mc = params::montgomery();
         let mut rng = thread_rng();
 
-        let f1 = BigInt::from_str("405373").unwrap();
-        let modulus: BigInt = f1;
+        let modulus = BigInt::from_str("405373").unwrap();
         // Generate a random index which we can find quickly
         let res = rng.gen_bigint_range(&BigInt::zero(), &modulus);
-        let index = rng.gen_bigint_range(&BigInt::zero(), &modulus);
-        //let index: BigInt = &i + &j * &modulus;
-        //let res = BigInt::from_usize(50).unwrap();
-        //let index = BigInt::from_usize(160).unwrap();
-        //
-        //
-        // Fix with some which fail the test
-        //let res = BigInt::from_str("268184").unwrap();
-        //let index = BigInt::from_str("362400").unwrap();
-
-        // b_priv: 146907443384
-
+        let index = rng.gen_bigint_range(&BigInt::zero(), &BigInt::from(1 << 16));
         let b_priv: BigInt = &res + &modulus * &index;
-
-        let wp = wc.scale(&wc.params.bp, &b_priv);
-        println!("Weierstrass point: {:?}", wp);
-
-        let b_sub = wc.scale(&wc.params.bp, &(&modulus * &index));
-        println!("Weierstrass - res: {:?}", b_sub);
-
         let b_pub = mc.ladder(&mc.bp, &b_priv);
-        let bits = index.bits() as u32;
-        println!("res: {res}");
-        println!("Index: {index}");
-        println!("b_priv: {b_priv}");
-        println!("Bits: {bits}");
-        let crack = shanks_for_mc(&res, &modulus, &b_pub, bits + 1);
-        println!("b_pub: {b_pub}");
-        if let Some(x) = crack.clone() {
-            println!("b_pub? {}", mc.ladder(&mc.bp, &x));
+
+        // Either sign of the residue will do
+        for res in [res.clone(), &modulus - &res] {
+            let (crack, _) =
+                kangaroo_for_mc(mc, &res, &modulus, &b_pub, &(1 << 16).into(), &mut rng).unwrap();
+            assert_eq!(crack, b_priv);
         }
-        assert_eq!(Some(b_priv), crack);
     }
 
     #[test]
     fn ec_scaling_test() {
-        let curve = Curve {
-            params: CurveParams {
-                a: BigInt::from_str("-95051").unwrap(),
-                b: BigInt::from_str("11279326").unwrap(),
-                p: BigInt::from_str("233970423115425145524320034830162017933").unwrap(),
-                bp: Point::P {
-                    x: BigInt::from_str("182").unwrap(),
-                    y: BigInt::from_str("85518893674295321206118380980485522083").unwrap(),
-                },
-                ord: BigInt::from_str("233970423115425145498902418297807005944").unwrap(),
-            },
-        };
+        let curve = params::curve();
 
         let minus_4 = curve
             .scale(&curve.params.bp, &BigInt::from_str("4").unwrap())
@@ -990,4 +939,64 @@ mod tests {
 
         assert_eq!(minus_4, minus_4_alt);
     }
+
+    /// Bob with a private key the test knows, on the twist
+    struct KnownBob {
+        twist: Twist,
+        private: BigInt,
+        public: BigInt,
+    }
+
+    impl KeyAgreementOracle<BigInt> for KnownBob {
+        fn public_key(&self) -> &BigInt {
+            &self.public
+        }
+
+        fn exchange(&self, public: &BigInt) -> Result<[u8; 32]> {
+            Ok(mac(&self.twist, &self.twist.scale(public, &self.private)))
+        }
+    }
+
+    #[test]
+    fn combining_residues() {
+        let curve = params::montgomery();
+        let twist = Twist {
+            order: 2 * &curve.p + 2 - &curve.ord,
+            curve: curve.clone(),
+        };
+        let mut rng = thread_rng();
+        let private = rng.gen_bigint_range(&BigInt::one(), &curve.ord);
+        let bob = KnownBob {
+            public: curve.ladder(&curve.bp, &private),
+            twist: twist.clone(),
+            private: private.clone(),
+        };
+
+        // Some small factors of the twist's order, each residue with a sign picked at random
+        let rx: Vec<(BigInt, BigInt)> = [11, 107, 197, 1621]
+            .map(BigInt::from)
+            .into_iter()
+            .map(|r| {
+                let x = &private % &r;
+                match rng.gen_bool(0.5) {
+                    true => (r, x),
+                    false => (r.clone(), (-x).mod_floor(&r)),
+                }
+            })
+            .collect();
+        let (x, m) = combine_residues_with_oracle(&bob, &twist, &rx, &mut rng).unwrap();
+        assert_eq!(m, BigInt::from(11 * 107 * 197 * 1621));
+        let expected = &private % &m;
+        assert!(
+            x == expected || x == (-&expected).mod_floor(&m),
+            "{x} {expected}"
+        );
+
+        // A residue which is wrong either way round fits neither pair
+        let mut wrong = rx.clone();
+        wrong[2].1 = (&wrong[2].1 + 1) % &wrong[2].0;
+        if wrong[2].1 != (-&rx[2].1).mod_floor(&rx[2].0) {
+            assert!(combine_residues_with_oracle(&bob, &twist, &wrong, &mut rng).is_err());
+        }
+    }
 }
@@ -145,8 +145,246 @@
 //! random (or chosen) ciphertext and creating a key to decrypt it to a
 //! plaintext of your choice!
 
-use crate::utils::*;
+use num_bigint::{BigInt, Sign};
+use num_integer::Integer;
+use num_traits::{One, ToPrimitive};
+use rand::{seq::SliceRandom, Rng};
 
-pub fn main() -> Result<()> {
-    unimplemented!()
+use crate::report::say;
+use crate::{
+    ecdsa::{self, duplicate_key, Params},
+    group::{pohlig_hellman, ModP},
+    rsa::{self, miller_rabin, small_primes, Key, KeyPair},
+    set6::challenge42::{self, encode},
+    trace::{event, span},
+    utils::*,
+};
+
+/// Miller-Rabin rounds for the smooth primes
+const ROUNDS: usize = 40;
+
+pub fn main() -> Result<Report> {
+    let mut rng = crate::utils::rng();
+    let params = Params::default();
+    let (d, q) = params.keypair(&mut rng);
+    let message = b"Alice signs this";
+    let signature = ecdsa::sign(&d, &params, message, &mut rng);
+    say!("Alice's public key: {q:?}");
+    say!("Her signature: {signature:?}");
+    if ecdsa::verify(&q, &params, message, &signature) != Auth::Valid {
+        return Err(anyhow::anyhow!("Alice's own ECDSA signature doesn't verify"));
+    }
+
+    let (eve_params, _, eve_q) = duplicate_key(&q, &params, message, &signature, &mut rng)?;
+    say!("Eve's base point: {:?}", eve_params.g);
+    say!("Eve's public key: {eve_q:?}");
+    let ecdsa_accepted = ecdsa::verify(&eve_q, &eve_params, message, &signature) == Auth::Valid;
+    say!("Alice's signature verifies under Eve's key: {ecdsa_accepted}");
+
+    let rsa_params = rsa::Params::from_options(1024, 65537)?;
+    say!("Now with a {rsa_params}");
+    let KeyPair { public, private } = rsa_params.keypair(&mut rng);
+    let message = b"Alice signs this too";
+    let signature = challenge42::sign(&private, message)?;
+    if challenge42::verify_strict(&public, message, &signature) != Auth::Valid {
+        return Err(anyhow::anyhow!("Alice's own RSA signature doesn't verify"));
+    }
+
+    let eve = duplicate_rsa_key(&public, message, &signature, &mut rng)?;
+    say!("Eve's e': {}", eve.public.key);
+    say!("Eve's N': {}", eve.public.modulus);
+    let rsa_accepted = challenge42::verify_strict(&eve.public, message, &signature) == Auth::Valid;
+    say!("Alice's signature verifies under Eve's RSA key too: {rsa_accepted}");
+
+    Ok(Report::new()
+        .recovered("ecdsa public key", format!("{eve_q:?}"))
+        .forged("ecdsa public key", ecdsa_accepted)
+        .recovered("rsa e", eve.public.key)
+        .recovered("rsa n", eve.public.modulus)
+        .forged("rsa key", rsa_accepted))
+}
+
+/// A key pair (e', N'), (d', N') which verifies `signature` over `message` as well as
+/// `public_key` does, with N' as many bytes as N so the padding still lines up
+///
+/// N' = p q for p - 1 and q - 1 smooth, with no factors in common but 2, and both s and pad(m)
+/// primitive roots mod each of them. Then e' mod p - 1 and mod q - 1 are discrete logs which
+/// Pohlig-Hellman finds a small factor at a time, and CRT puts them together.
+pub fn duplicate_rsa_key(
+    public_key: &Key,
+    message: &[u8],
+    signature: &[u8],
+    rng: &mut impl Rng,
+) -> Result<KeyPair> {
+    if challenge42::verify_strict(public_key, message, signature) == Auth::Invalid {
+        return Err(anyhow::anyhow!(
+            "The signature doesn't verify in the first place"
+        ));
+    }
+    let n = &public_key.modulus;
+    let _span = span!(Info, "duplicate_rsa_key", bits = n.bits());
+    let s = BigInt::from_bytes_be(Sign::Plus, signature);
+    let m = BigInt::from_bytes_be(Sign::Plus, &encode(message, public_key.size())?);
+
+    // p a little over sqrt(N), and q between N / p and the next power of 256 over p, which puts
+    // N' above N but keeps it the same number of bytes
+    let root = n.sqrt();
+    let (p, p_factors) = smooth_prime(&root, &(&root + (&root >> 2)), &[&s, &m], &[], rng);
+    let top = BigInt::one() << (8 * public_key.size());
+    let (q, q_factors) = smooth_prime(&(n / &p + 1), &(&top / &p), &[&s, &m], &p_factors, rng);
+
+    // s^e' = m mod p and mod q
+    let log = |prime: &BigInt, factors: &[BigInt]| -> Result<BigInt> {
+        pohlig_hellman(&ModP { p: prime.clone() }, &s, &m, factors)
+            .ok_or_else(|| anyhow::anyhow!("pad(m) isn't a power of s mod {prime}"))
+    };
+    let ep = log(&p, &p_factors)?;
+    let eq = log(&q, &q_factors)?;
+    event!(Debug, "discrete logs recovered", ep = ep, eq = eq);
+
+    // p - 1 and q - 1 only share the factor 2, and ep and eq are both odd (as pad(m) is a primitive
+    // root too), so they agree mod 2 and CRT mod the halves does the rest
+    let (hp, hq): (BigInt, BigInt) = ((&p - 1) / 2, (&q - 1) / 2);
+    let half_gap: BigInt = (&eq - &ep) / 2;
+    let hp_inv = try_invmod(&hp, &hq)
+        .ok_or_else(|| anyhow::anyhow!("(p - 1) / 2 and (q - 1) / 2 share a factor"))?;
+    let t = (half_gap * hp_inv).mod_floor(&hq);
+    let lambda: BigInt = 2 * &hp * &hq;
+    let e: BigInt = &ep + 2 * t * &hp;
+    let e = e.mod_floor(&lambda);
+    let d = try_invmod(&e, &lambda).ok_or_else(|| anyhow::anyhow!("e' has no inverse mod lambda"))?;
+
+    let modulus = p * q;
+    Ok(KeyPair {
+        public: Key {
+            key: e,
+            modulus: modulus.clone(),
+        },
+        private: Key { key: d, modulus },
+    })
+}
+
+/// A prime p in [lo, hi), and the primes dividing p - 1, all below 2^25 and none of them in
+/// `exclude` apart from 2, with each of `bases` a primitive root
+///
+/// p = 2 r1 r2 ... f + 1, with the r multiplying up to about 2^21 short of hi and f whatever
+/// prime lands p in the range. Most candidates for f fall to a sieve over the small primes in
+/// u64s, so only a few reach Miller-Rabin.
+fn smooth_prime<R: Rng>(
+    lo: &BigInt,
+    hi: &BigInt,
+    bases: &[&BigInt],
+    exclude: &[BigInt],
+    rng: &mut R,
+) -> (BigInt, Vec<BigInt>) {
+    let sieve: Vec<u64> = small_primes().iter().map(|r| *r as u64).collect();
+    let excluded = |r: u64| exclude.contains(&BigInt::from(r));
+    let pool: Vec<u64> = sieve[1..]
+        .iter()
+        .copied()
+        .filter(|r| !excluded(*r))
+        .collect();
+    // f is below 2^25 so trial division by primes up to 2^14 settles it
+    let is_prime = |f: u64| {
+        f > 1
+            && sieve
+                .iter()
+                .take_while(|r| *r * *r <= f)
+                .all(|r| !f.is_multiple_of(*r))
+    };
+    loop {
+        let mut factors = vec![2];
+        let mut product = BigInt::from(2);
+        // Passing over any which don't fit, so it ends up close to 21 bits short
+        for r in pool.choose_multiple(rng, pool.len()) {
+            if product.bits() + u64::from(64 - r.leading_zeros()) > hi.bits() - 21 {
+                continue;
+            }
+            product *= *r;
+            factors.push(*r);
+        }
+        let (f_lo, f_hi): (BigInt, BigInt) = (lo / &product + 1, hi / &product);
+        let (Some(f_lo), Some(f_hi)) = (f_lo.to_u64(), f_hi.to_u64()) else {
+            continue;
+        };
+        if f_lo >= f_hi {
+            continue;
+        }
+        // p mod r is (product mod r) f + 1
+        let residues: Vec<u64> = sieve
+            .iter()
+            .map(|r| (&product % r).to_u64().unwrap())
+            .collect();
+        // Plenty of f to try before starting again with a different product
+        for _ in 0..1 << 16 {
+            let f = rng.gen_range(f_lo..f_hi);
+            if !is_prime(f) || factors.contains(&f) || excluded(f) {
+                continue;
+            }
+            if sieve
+                .iter()
+                .zip(&residues)
+                .any(|(r, residue)| (residue * f + 1).is_multiple_of(*r))
+            {
+                continue;
+            }
+            let p = &product * f + 1;
+            if !miller_rabin(&p, ROUNDS, rng) {
+                continue;
+            }
+            let factors: Vec<BigInt> = factors
+                .iter()
+                .chain([&f])
+                .map(|r| BigInt::from(*r))
+                .collect();
+            let primitive = |g: &&BigInt| {
+                factors
+                    .iter()
+                    .all(|r| !g.modpow(&((&p - 1) / r), &p).is_one())
+            };
+            if bases.iter().all(primitive) {
+                event!(
+                    Debug,
+                    "smooth prime found",
+                    bits = p.bits(),
+                    factors = factors.len()
+                );
+                return (p, factors);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn duplicate_signature_keys() {
+        super::main().and_then(Report::verify).unwrap();
+    }
+
+    #[test]
+    fn duplicated_rsa_key() {
+        let mut rng = rand::thread_rng();
+        let KeyPair { public, private } = rsa::Params::new(512, 3).keypair(&mut rng);
+        let message = b"Pay Eve";
+        let signature = challenge42::sign(&private, message).unwrap();
+
+        let eve = duplicate_rsa_key(&public, message, &signature, &mut rng).unwrap();
+        assert_ne!(eve.public.modulus, public.modulus);
+        assert_eq!(eve.public.size(), public.size());
+        assert_eq!(
+            challenge42::verify_strict(&eve.public, message, &signature),
+            Auth::Valid
+        );
+        // Eve can sign with it too
+        let other = challenge42::sign(&eve.private, b"Pay Eve twice").unwrap();
+        assert_eq!(
+            challenge42::verify_strict(&eve.public, b"Pay Eve twice", &other),
+            Auth::Valid
+        );
+
+        assert!(duplicate_rsa_key(&public, b"Pay Mallory", &signature, &mut rng).is_err());
+    }
 }
@@ -360,8 +360,246 @@
 //!    entry. There's a good chance it will have -d/2^l as the
 //!    second-to-last entry. Extract d.
 
+use std::fmt;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+use anyhow::anyhow;
+use num_bigint::{BigInt, RandBigInt};
+use num_integer::Integer;
+use num_traits::{One, Zero};
+use rand::Rng;
+
+use crate::ecdsa::{self, Params, Sig};
+use crate::lattice::lll;
+use crate::report::say;
+use crate::set8::challenge59::Point;
+use crate::trace::{event, span};
 use crate::utils::*;
 
-pub fn main() -> Result<()> {
-    unimplemented!()
+/// Low bits of each nonce zeroed, unless `--arg bias=` says otherwise
+const BIAS: u32 = 8;
+/// Signatures collected, unless `--arg signatures=` says otherwise
+const SIGNATURES: usize = 20;
+
+/// One or more values, comma separated, for an `--arg` to sweep over
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Sweep<T>(pub Vec<T>);
+
+impl<T: FromStr> FromStr for Sweep<T>
+where
+    T::Err: fmt::Display,
+{
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        s.split(',')
+            .map(|v| v.trim().parse().map_err(|e| anyhow!("{v:?}: {e}")))
+            .collect::<Result<_>>()
+            .map(Self)
+    }
+}
+
+/// A nonce with its low `bias` bits zeroed, in [1, q)
+fn biased_nonce(n: &BigInt, bias: u32, rng: &mut impl Rng) -> BigInt {
+    let top = (n - 1) >> bias;
+    rng.gen_bigint_range(&BigInt::one(), &(top + 1)) << bias
+}
+
+/// (m, sig) for `count` random messages, all signed with biased nonces
+pub fn biased_signatures(
+    private_key: &BigInt,
+    params: &Params,
+    count: usize,
+    bias: u32,
+    rng: &mut impl Rng,
+) -> Vec<(Vec<u8>, Sig)> {
+    (0..count)
+        .map(|i| {
+            let message = format!("Message number {i}").into_bytes();
+            let sig = ecdsa::sign_with_nonces(private_key, params, &message, || {
+                biased_nonce(&params.n, bias, rng)
+            });
+            (message, sig)
+        })
+        .collect()
+}
+
+/// The lattice hiding the private key behind `signatures`, given that every nonce had its low
+/// `bias` bits zeroed
+///
+/// Each signature gives d t = u + b mod q, with b < q / 2^l. Scaled up by 2^l, so that everything
+/// is whole, the basis is q 2^l on the diagonal, then bt = (2^l t_i, 1, 0) and bu = (2^l u_i, 0,
+/// q), and bu - d bt + sum m_i b_i = (2^l b_i, -d, q) is short enough for LLL to find.
+pub fn basis(params: &Params, signatures: &[(Vec<u8>, Sig)], bias: u32) -> Vec<Vec<BigInt>> {
+    let q = &params.n;
+    let scale = BigInt::one() << bias;
+    let size = signatures.len() + 2;
+    let mut basis: Vec<Vec<BigInt>> = (0..signatures.len())
+        .map(|i| {
+            let mut row = vec![BigInt::zero(); size];
+            row[i] = q * &scale;
+            row
+        })
+        .collect();
+    let (mut bt, mut bu) = (vec![BigInt::zero(); size], vec![BigInt::zero(); size]);
+    for (i, (message, Sig { r, s })) in signatures.iter().enumerate() {
+        // t = r / (s 2^l) and u = H(m) / (-s 2^l), reduced mod q before they're scaled up
+        let w = try_invmod(&(s * &scale), q).unwrap();
+        bt[i] = (r * &w).mod_floor(q) * &scale;
+        bu[i] = (-ecdsa::hash(message, q) * &w).mod_floor(q) * &scale;
+    }
+    bt[size - 2] = BigInt::one();
+    bu[size - 1] = q.clone();
+    basis.push(bt);
+    basis.push(bu);
+    basis
+}
+
+/// The private key behind `signatures`, if LLL turns it up from their [`basis`]
+pub fn recover_key(
+    public_key: &Point,
+    params: &Params,
+    signatures: &[(Vec<u8>, Sig)],
+    bias: u32,
+) -> Option<BigInt> {
+    let q = &params.n;
+    let size = signatures.len() + 2;
+    let _span = span!(Debug, "recover_key", dimension = size);
+    let mut basis = basis(params, signatures, bias);
+    lll(&mut basis);
+    event!(Trace, "basis reduced");
+
+    let d = basis
+        .iter()
+        .filter_map(|row| match &row[size - 1] {
+            last if last == q => Some(-&row[size - 2]),
+            last if *last == -q => Some(row[size - 2].clone()),
+            _ => None,
+        })
+        .map(|d| d.mod_floor(q))
+        .find(|d| &params.curve.scale(&params.g, d) == public_key);
+    event!(Debug, "key search done", found = d.is_some());
+    d
+}
+
+/// How one bias and number of signatures fared over some trials
+struct Outcome {
+    bias: u32,
+    signatures: usize,
+    trials: usize,
+    recovered: usize,
+    elapsed: Duration,
+}
+
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "l = {:2}, {:3} signatures: {}/{} keys recovered ({:.0}%), {:.2?} per attack",
+            self.bias,
+            self.signatures,
+            self.recovered,
+            self.trials,
+            100.0 * self.recovered as f64 / self.trials as f64,
+            self.elapsed / self.trials as u32
+        )
+    }
+}
+
+/// A fresh key each trial, signing `signatures` messages with nonces biased by `bias` bits
+fn attempt(
+    params: &Params,
+    bias: u32,
+    signatures: usize,
+    trials: usize,
+    rng: &mut impl Rng,
+) -> Outcome {
+    let mut outcome = Outcome {
+        bias,
+        signatures,
+        trials,
+        recovered: 0,
+        elapsed: Duration::ZERO,
+    };
+    for _ in 0..trials {
+        let (d, q) = params.keypair(rng);
+        let collected = biased_signatures(&d, params, signatures, bias, rng);
+        let start = Instant::now();
+        let found = recover_key(&q, params, &collected, bias);
+        outcome.elapsed += start.elapsed();
+        if found == Some(d) {
+            outcome.recovered += 1;
+        }
+    }
+    outcome
+}
+
+pub fn main() -> Result<Report> {
+    let options = crate::options::get();
+    let Sweep(biases) = options.arg("bias")?.unwrap_or(Sweep(vec![BIAS]));
+    let Sweep(counts) = options
+        .arg("signatures")?
+        .unwrap_or(Sweep(vec![SIGNATURES]));
+    let trials: usize = options.arg("trials")?.unwrap_or(1);
+    let params = Params::default();
+    if trials == 0
+        || biases
+            .iter()
+            .any(|l| *l == 0 || *l as u64 >= params.n.bits())
+    {
+        return Err(anyhow!(
+            "Need at least one trial, and a bias of 1 to {} bits",
+            params.n.bits() - 1
+        ));
+    }
+
+    let mut rng = crate::utils::rng();
+    let (mut recovered, mut attempts) = (0, 0);
+    for &bias in &biases {
+        for &count in &counts {
+            let outcome = attempt(&params, bias, count, trials, &mut rng);
+            say!("{outcome}");
+            recovered += outcome.recovered;
+            attempts += outcome.trials;
+        }
+    }
+    // Too few signatures for the bias is meant to fail, but not every time
+    match recovered {
+        0 => Err(anyhow!("Didn't recover a single key")),
+        _ => Ok(Report::new()
+            .count("keys recovered", recovered as u64)
+            .count("trials", attempts as u64)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sweeps() {
+        assert_eq!("8".parse::<Sweep<u32>>().unwrap(), Sweep(vec![8]));
+        assert_eq!(
+            "16, 20,24".parse::<Sweep<usize>>().unwrap(),
+            Sweep(vec![16, 20, 24])
+        );
+        assert!("8,x".parse::<Sweep<u32>>().is_err());
+    }
+
+    #[test]
+    fn biased_nonces() {
+        let params = Params::default();
+        let mut rng = rand::thread_rng();
+        let (d, q) = params.keypair(&mut rng);
+        let signatures = biased_signatures(&d, &params, 24, 8, &mut rng);
+        assert_eq!(recover_key(&q, &params, &signatures, 8), Some(d));
+        // Far too few signatures to pin it down
+        assert_eq!(recover_key(&q, &params, &signatures[..4], 8), None);
+    }
+
+    #[test]
+    fn biased_nonce_attack() {
+        super::main().and_then(Report::verify).unwrap();
+    }
 }
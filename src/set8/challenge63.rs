@@ -433,8 +433,526 @@
 //! 2. Just attempt a forgery with each candidate. This is probably
 //!    easier.
 
+use std::collections::BTreeSet;
+
+use anyhow::anyhow;
+use num_bigint::BigInt;
+use num_traits::One;
+use rand::Rng;
+
+use crate::aes::{aes_ecb_encrypt, BLOCK_SIZE};
+use crate::gcm::{ghash, ghash_blocks, Gcm, Gf, NONCE_SIZE, TAG_SIZE};
+use crate::report::say;
+use crate::trace::{event, span};
 use crate::utils::*;
 
-pub fn main() -> Result<()> {
-    unimplemented!()
+/// Pairs of messages sent under a repeated nonce, unless `--arg pairs=` says otherwise
+const PAIRS: usize = 2;
+
+/// A polynomial over GF(2^128), lowest degree first, with no leading zeros
+pub type Poly = Vec<Gf>;
+
+pub fn trim(mut f: Poly) -> Poly {
+    while f.last().is_some_and(|c| c.is_zero()) {
+        f.pop();
+    }
+    f
+}
+
+/// Degree, counting the zero polynomial as degree 0
+pub fn degree(f: &[Gf]) -> usize {
+    f.iter().rposition(|c| !c.is_zero()).unwrap_or(0)
+}
+
+fn is_one(f: &[Gf]) -> bool {
+    f == [Gf::ONE]
+}
+
+/// Addition, which is subtraction too
+pub fn add(f: &[Gf], g: &[Gf]) -> Poly {
+    let mut sum = vec![Gf::ZERO; f.len().max(g.len())];
+    for (i, c) in f.iter().enumerate() {
+        sum[i] += *c;
+    }
+    for (i, c) in g.iter().enumerate() {
+        sum[i] += *c;
+    }
+    trim(sum)
+}
+
+pub fn mul(f: &[Gf], g: &[Gf]) -> Poly {
+    if f.is_empty() || g.is_empty() {
+        return vec![];
+    }
+    let mut product = vec![Gf::ZERO; f.len() + g.len() - 1];
+    for (i, a) in f.iter().enumerate() {
+        for (j, b) in g.iter().enumerate() {
+            product[i + j] += *a * *b;
+        }
+    }
+    trim(product)
+}
+
+/// (q, r) with f = q g + r and r of lower degree than g, for g non-zero
+pub fn div_rem(f: &[Gf], g: &[Gf]) -> (Poly, Poly) {
+    let g = trim(g.to_vec());
+    let dg = g.len() - 1;
+    let lead = g[dg].inverse();
+    let mut r = trim(f.to_vec());
+    let mut q = vec![Gf::ZERO; r.len().saturating_sub(dg)];
+    while r.len() > dg {
+        let dr = r.len() - 1;
+        let c = r[dr] * lead;
+        q[dr - dg] = c;
+        for (i, b) in g.iter().enumerate() {
+            r[dr - dg + i] += c * *b;
+        }
+        r = trim(r);
+    }
+    (trim(q), r)
+}
+
+fn div(f: &[Gf], g: &[Gf]) -> Poly {
+    div_rem(f, g).0
+}
+
+fn rem(f: &[Gf], g: &[Gf]) -> Poly {
+    div_rem(f, g).1
+}
+
+/// f divided through by its leading coefficient
+pub fn monic(f: &[Gf]) -> Poly {
+    let f = trim(f.to_vec());
+    match f.last() {
+        Some(lead) => {
+            let inverse = lead.inverse();
+            f.iter().map(|c| *c * inverse).collect()
+        }
+        None => f,
+    }
+}
+
+/// The monic gcd, with gcd(f, 0) = f
+pub fn gcd(f: &[Gf], g: &[Gf]) -> Poly {
+    let (mut a, mut b) = (trim(f.to_vec()), trim(g.to_vec()));
+    while !b.is_empty() {
+        let r = rem(&a, &b);
+        (a, b) = (b, r);
+    }
+    monic(&a)
+}
+
+/// f^k mod m
+fn pow_mod(f: &[Gf], k: &BigInt, m: &[Gf]) -> Poly {
+    let f = rem(f, m);
+    let mut acc = rem(&[Gf::ONE], m);
+    for i in (0..k.bits()).rev() {
+        acc = rem(&mul(&acc, &acc), m);
+        if k.bit(i) {
+            acc = rem(&mul(&acc, &f), m);
+        }
+    }
+    acc
+}
+
+/// In characteristic 2, only the odd powers survive, each as the coefficient of the power below
+fn derivative(f: &[Gf]) -> Poly {
+    trim(
+        f.iter()
+            .enumerate()
+            .skip(1)
+            .map(|(i, c)| if i % 2 == 1 { *c } else { Gf::ZERO })
+            .collect(),
+    )
+}
+
+/// g with g^2 = f, for f with a zero derivative: squaring is additive here, so g's coefficients
+/// are the square roots of f's even ones
+fn sqrt(f: &[Gf]) -> Poly {
+    f.iter().step_by(2).map(|c| c.sqrt()).collect()
+}
+
+/// Square-free factors of a monic f, each with how many times it divides f
+pub fn square_free(f: &[Gf]) -> Vec<(Poly, usize)> {
+    let mut factors = vec![];
+    let mut c = gcd(f, &derivative(f));
+    let mut w = div(f, &c);
+    let mut i = 1;
+    while !is_one(&w) {
+        let y = gcd(&w, &c);
+        let factor = div(&w, &y);
+        if !is_one(&factor) {
+            factors.push((factor, i));
+        }
+        c = div(&c, &y);
+        w = y;
+        i += 1;
+    }
+    // What's left is a square, since its derivative is zero
+    if !is_one(&c) {
+        for (g, j) in square_free(&sqrt(&c)) {
+            factors.push((g, 2 * j));
+        }
+    }
+    factors
+}
+
+/// Splits a monic, square-free f into the products of its irreducible factors of each degree,
+/// as (product, degree)
+///
+/// x^(q^i) - x, with q = 2^128, is the product of all the monic irreducibles of degree dividing
+/// i, so its gcd with what's left of f, once the lower degrees are gone, is those of degree i.
+pub fn distinct_degree(f: &[Gf]) -> Vec<(Poly, usize)> {
+    let x = vec![Gf::ZERO, Gf::ONE];
+    let mut factors = vec![];
+    let mut f = f.to_vec();
+    let mut h = rem(&x, &f);
+    let mut i = 1;
+    while degree(&f) >= 2 * i {
+        // h^q, as 128 squarings
+        for _ in 0..128 {
+            h = rem(&mul(&h, &h), &f);
+        }
+        let g = gcd(&f, &add(&h, &x));
+        if !is_one(&g) {
+            f = div(&f, &g);
+            h = rem(&h, &f);
+            factors.push((g, i));
+        }
+        i += 1;
+    }
+    if degree(&f) > 0 {
+        let d = degree(&f);
+        factors.push((f, d));
+    }
+    factors
+}
+
+/// Splits f, a product of distinct monic irreducibles of degree d, into them, by
+/// Cantor-Zassenhaus
+pub fn equal_degree<R: Rng>(f: &[Gf], d: usize, rng: &mut R) -> Vec<Poly> {
+    let r = degree(f) / d;
+    let e: BigInt = ((BigInt::one() << (128 * d)) - 1) / 3;
+    let mut factors = vec![f.to_vec()];
+    while factors.len() < r {
+        let h = trim((0..degree(f)).map(|_| Gf::random(rng)).collect());
+        let mut g = gcd(&h, f);
+        if is_one(&g) {
+            // Into the subgroup of order 3 mod each factor, which is 1 a third of the time
+            g = add(&pow_mod(&h, &e, f), &[Gf::ONE]);
+        }
+        factors = factors
+            .into_iter()
+            .flat_map(|u| {
+                let k = gcd(&g, &u);
+                match degree(&u) == d || is_one(&k) || k == u {
+                    true => vec![u],
+                    false => {
+                        let other = div(&u, &k);
+                        vec![k, other]
+                    }
+                }
+            })
+            .collect();
+    }
+    factors
+}
+
+/// The monic irreducible factors of f, each with how many times it divides f
+pub fn factor(f: &[Gf], rng: &mut impl Rng) -> Vec<(Poly, usize)> {
+    square_free(&monic(f))
+        .into_iter()
+        .flat_map(|(g, multiplicity)| {
+            distinct_degree(&g)
+                .into_iter()
+                .flat_map(|(h, d)| equal_degree(&h, d, rng))
+                .map(move |h| (h, multiplicity))
+                .collect::<Vec<_>>()
+        })
+        .collect()
+}
+
+/// The roots of f, from its factors of degree one, y + c
+pub fn roots(f: &[Gf], rng: &mut impl Rng) -> BTreeSet<Gf> {
+    factor(f, rng)
+        .into_iter()
+        .filter(|(g, _)| degree(g) == 1)
+        .map(|(g, _)| g[0])
+        .collect()
+}
+
+/// All Eve sees of a message
+#[derive(Debug, Clone)]
+pub struct Sealed {
+    pub nonce: [u8; NONCE_SIZE],
+    pub ad: Vec<u8>,
+    pub ciphertext: Vec<u8>,
+    pub tag: [u8; TAG_SIZE],
+}
+
+/// Alice, who repeats nonces, and Bob, who opens whatever has the right tag
+struct Server {
+    gcm: Gcm,
+}
+
+impl Server {
+    fn seal(&self, nonce: &[u8; NONCE_SIZE], ad: &[u8], plaintext: &[u8]) -> Sealed {
+        let (ciphertext, tag) = self.gcm.seal(nonce, ad, plaintext);
+        Sealed {
+            nonce: *nonce,
+            ad: ad.to_vec(),
+            ciphertext,
+            tag,
+        }
+    }
+
+    fn open(&self, sealed: &Sealed) -> Result<Vec<u8>> {
+        self.gcm
+            .open(&sealed.nonce, &sealed.ad, &sealed.ciphertext, &sealed.tag)
+    }
+}
+
+/// t + b_n y + ... + b_1 y^n, for the blocks b GHASH runs over, which is s, the mask, at y = h
+fn mac_poly(sealed: &Sealed) -> Poly {
+    let blocks = ghash_blocks(&sealed.ad, &sealed.ciphertext);
+    let constant = std::iter::once(Gf::from_block(&sealed.tag));
+    trim(constant.chain(blocks.into_iter().rev()).collect())
+}
+
+/// Two messages under the same nonce share a mask, so h is a root of the sum of their polynomials
+pub fn difference(a: &Sealed, b: &Sealed) -> Result<Poly> {
+    if a.nonce != b.nonce {
+        return Err(anyhow!("The messages weren't sent under the same nonce"));
+    }
+    Ok(add(&mac_poly(a), &mac_poly(b)))
+}
+
+/// The candidates for h, as the roots every pair's difference has in common
+pub fn candidates(pairs: &[(Sealed, Sealed)], rng: &mut impl Rng) -> Result<BTreeSet<Gf>> {
+    let _span = span!(Info, "candidates", pairs = pairs.len());
+    let mut common: Option<BTreeSet<Gf>> = None;
+    for (a, b) in pairs {
+        let difference = difference(a, b)?;
+        let found = roots(&difference, rng);
+        event!(
+            Debug,
+            "roots found",
+            degree = degree(&difference),
+            roots = found.len()
+        );
+        common = Some(match common {
+            Some(common) => common.intersection(&found).copied().collect(),
+            None => found,
+        });
+        if let Some(common) = &common {
+            event!(Debug, "candidates narrowed", left = common.len());
+        }
+    }
+    common.ok_or_else(|| anyhow!("Need at least one pair of messages"))
+}
+
+/// `sealed` with its ciphertext XORed with `delta`, and a tag to match if h is the key
+///
+/// The mask is the same for anything under the same nonce, s = t + GHASH(h, A, C).
+pub fn forge(h: Gf, sealed: &Sealed, delta: &[u8]) -> Sealed {
+    let s = Gf::from_block(&sealed.tag) + ghash(h, &sealed.ad, &sealed.ciphertext);
+    let ciphertext: Vec<u8> = sealed
+        .ciphertext
+        .iter()
+        .zip(delta.iter().chain(std::iter::repeat(&0)))
+        .map(|(c, d)| c ^ d)
+        .collect();
+    let tag = (ghash(h, &sealed.ad, &ciphertext) + s).to_block();
+    Sealed {
+        ciphertext,
+        tag,
+        ..sealed.clone()
+    }
+}
+
+/// h, from the candidates the pairs leave, trying a forgery with each if there's more than one
+pub fn recover_key(
+    pairs: &[(Sealed, Sealed)],
+    accepts: impl Fn(&Sealed) -> bool,
+    rng: &mut impl Rng,
+) -> Result<Gf> {
+    let candidates = candidates(pairs, rng)?;
+    say!("Candidates for h: {}", candidates.len());
+    if candidates.len() == 1 {
+        return Ok(*candidates.first().unwrap());
+    }
+    let (sealed, _) = &pairs[0];
+    candidates
+        .into_iter()
+        .find(|h| accepts(&forge(*h, sealed, &[1])))
+        .ok_or_else(|| anyhow!("No candidate for h forges a message"))
+}
+
+pub fn main() -> Result<Report> {
+    let pairs: usize = crate::options::get().arg("pairs")?.unwrap_or(PAIRS);
+    let mut rng = crate::utils::rng();
+    let key: [u8; 16] = rng.gen();
+    let server = Server {
+        gcm: Gcm::new(&key)?,
+    };
+    let h = Gf::from_block(&aes_ecb_encrypt(&[0; BLOCK_SIZE], &key)?);
+
+    // Each nonce gets used twice
+    let messages = [
+        (&b"header"[..], &b"Transfer $100 to Bob"[..]),
+        (
+            b"header",
+            b"Meet at the usual place at noon, bring the documents",
+        ),
+        (b"", b"Short one"),
+        (b"some associated data, longer than a block", b"and more"),
+    ];
+    let collected = (0..pairs)
+        .map(|i| {
+            let nonce: [u8; NONCE_SIZE] = rng.gen();
+            let (a, b) = (messages[i % 4], messages[(i + 1) % 4]);
+            (server.seal(&nonce, a.0, a.1), server.seal(&nonce, b.0, b.1))
+        })
+        .collect::<Vec<_>>();
+    for (i, (a, b)) in collected.iter().enumerate() {
+        let f = difference(a, b)?;
+        say!(
+            "Pair {}: a polynomial of degree {}, with {} roots",
+            i + 1,
+            degree(&f),
+            roots(&f, &mut rng).len()
+        );
+    }
+
+    let found = recover_key(&collected, |sealed| server.open(sealed).is_ok(), &mut rng)?;
+    say!("h = {:032x}", u128::from_be_bytes(found.to_block()));
+
+    // Now Eve can change a message she knows the plaintext of, and fix up the tag
+    let (original, _) = &collected[0];
+    let forged_text = b"Transfer $999 to Eve";
+    let delta = xor_bytes(messages[0].1, forged_text);
+    let forged = forge(found, original, &delta);
+    let opened = server.open(&forged)?;
+    say!("Bob opens {:?}", String::from_utf8_lossy(&opened));
+
+    Ok(Report::new()
+        .check("h", h.to_block(), found.to_block())
+        .recovered("forged ciphertext", forged.ciphertext)
+        .recovered("forged tag", forged.tag)
+        .check("opened", forged_text, opened))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn linear(c: Gf) -> Poly {
+        vec![c, Gf::ONE]
+    }
+
+    #[test]
+    fn arithmetic() {
+        let mut rng = rand::thread_rng();
+        let f: Poly = (0..6).map(|_| Gf::random(&mut rng)).collect();
+        let g: Poly = (0..3).map(|_| Gf::random(&mut rng)).collect();
+        let (q, r) = div_rem(&f, &g);
+        assert!(degree(&r) < degree(&g));
+        assert_eq!(add(&mul(&q, &g), &r), f);
+        assert_eq!(gcd(&mul(&f, &g), &g), monic(&g));
+        assert_eq!(
+            pow_mod(&g, &BigInt::from(5), &f),
+            rem(&mul(&mul(&g, &g), &mul(&mul(&g, &g), &g)), &f)
+        );
+        assert_eq!(sqrt(&mul(&g, &g)), g);
+    }
+
+    /// Up to `max` coefficients, some of them zero, so leading zeros and the zero polynomial
+    /// turn up as well
+    fn poly<R: Rng>(rng: &mut R, max: usize) -> Poly {
+        let len = rng.gen_range(0..=max);
+        (0..len)
+            .map(|_| match rng.gen_range(0..4) {
+                0 => Gf::ZERO,
+                1 => Gf::ONE,
+                _ => Gf::random(rng),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn ring_invariants() {
+        let mut rng = rand::thread_rng();
+        for _ in 0..300 {
+            let (f, g, h) = (poly(&mut rng, 8), poly(&mut rng, 5), poly(&mut rng, 3));
+            let (f, g, h) = (trim(f), trim(g), trim(h));
+            assert_eq!(add(&f, &g), add(&g, &f));
+            assert_eq!(mul(&f, &g), mul(&g, &f));
+            assert_eq!(mul(&mul(&f, &g), &h), mul(&f, &mul(&g, &h)));
+            assert_eq!(mul(&f, &add(&g, &h)), add(&mul(&f, &g), &mul(&f, &h)));
+            assert_eq!(add(&f, &f), Poly::new());
+            assert_eq!(mul(&f, &[Gf::ONE]), f);
+
+            if !g.is_empty() {
+                let (q, r) = div_rem(&f, &g);
+                assert!(r.is_empty() || degree(&r) < degree(&g), "{f:?} / {g:?}");
+                assert_eq!(add(&mul(&q, &g), &r), f, "{f:?} / {g:?}");
+            }
+
+            let d = gcd(&f, &g);
+            if f.is_empty() && g.is_empty() {
+                assert!(d.is_empty());
+                continue;
+            }
+            assert_eq!(d.last(), Some(&Gf::ONE), "{f:?}, {g:?}");
+            assert!(rem(&f, &d).is_empty() && rem(&g, &d).is_empty());
+            if !h.is_empty() {
+                assert_eq!(gcd(&mul(&f, &h), &mul(&g, &h)), monic(&mul(&d, &h)));
+            }
+        }
+    }
+
+    #[test]
+    fn factoring() {
+        let mut rng = rand::thread_rng();
+        let (a, b, c) = (
+            Gf::random(&mut rng),
+            Gf::random(&mut rng),
+            Gf::random(&mut rng),
+        );
+        // (y + a)^2 (y + b) (y + c)^3, times a quadratic with no roots: y^2 + y + w, for w
+        // of trace one, is irreducible
+        let w = (0..)
+            .map(|_| Gf::random(&mut rng))
+            .find(|w| (0..128).fold((Gf::ZERO, *w), |(t, p), _| (t + p, p * p)).0 == Gf::ONE)
+            .unwrap();
+        let quadratic = vec![w, Gf::ONE, Gf::ONE];
+        let f = [
+            linear(a),
+            linear(a),
+            linear(b),
+            linear(c),
+            linear(c),
+            linear(c),
+        ]
+        .iter()
+        .fold(quadratic.clone(), |acc, g| mul(&acc, g));
+        let f: Poly = f.iter().map(|x| *x * w).collect();
+
+        let mut factors = factor(&f, &mut rng);
+        factors.sort();
+        let mut expected = vec![
+            (linear(a), 2),
+            (linear(b), 1),
+            (linear(c), 3),
+            (quadratic, 1),
+        ];
+        expected.sort();
+        assert_eq!(factors, expected);
+        assert_eq!(roots(&f, &mut rng), BTreeSet::from([a, b, c]));
+    }
+
+    #[test]
+    fn repeated_nonces() {
+        super::main().and_then(Report::verify).unwrap();
+    }
 }
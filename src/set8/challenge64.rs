@@ -255,8 +255,331 @@
 //!    the key. You should be able to zero out 16 bits of each tag to
 //!    start, and you'll only gain leverage from there.
 
+use anyhow::anyhow;
+use rand::Rng;
+
+use crate::aes::{aes_ecb_encrypt, BLOCK_SIZE};
+use crate::gcm::{Gcm, Gf, NONCE_SIZE};
+use crate::gf2::{apply, dot, kernel, null_space, row, Bits, Columns};
+use crate::report::say;
+use crate::trace::{event, span};
 use crate::utils::*;
 
-pub fn main() -> Result<()> {
-    unimplemented!()
+/// log2 of the number of blocks in the message, unless `--arg blocks_log=` says otherwise
+const BLOCKS_LOG: u32 = 17;
+/// Bits of tag Bob checks, unless `--arg tag_bits=` says otherwise
+const TAG_BITS: usize = 32;
+/// The same under `--quick`
+const QUICK_BLOCKS_LOG: u32 = 8;
+const QUICK_TAG_BITS: usize = 16;
+
+/// x, which multiplying by is only a shift, as long as it's on the left
+pub const X: Gf = Gf(2);
+
+/// Bob, who only checks the first few bytes of a tag
+pub struct Server {
+    pub gcm: Gcm,
+    pub nonce: [u8; NONCE_SIZE],
+}
+
+impl Server {
+    pub fn accepts(&self, ciphertext: &[u8], tag: &[u8]) -> bool {
+        self.gcm
+            .open_truncated(&self.nonce, b"", ciphertext, tag)
+            .is_ok()
+    }
+}
+
+/// Forgeries on one message of 2^n blocks
+///
+/// Flipping bits in the blocks multiplied by h^(2^i), adding d_i to them, changes the tag by
+/// e = sum d_i h^(2^i) = Ad h, where Ad = sum Mdi Ms^i and both multiplying by d_i and squaring
+/// are linear maps.
+pub struct Forger {
+    /// So the blocks multiplied by h^2, h^4, ..., h^(2^n) are all there to flip
+    n: usize,
+    tag_bits: usize,
+    ciphertext: Vec<u8>,
+    /// Ms^i for i = 1, ..., n, i.e. squaring i times, worked out once
+    squarings: Vec<Columns>,
+}
+
+impl Forger {
+    pub fn new(ciphertext: Vec<u8>, tag_bits: usize) -> Result<Self> {
+        let blocks = ciphertext.len() / BLOCK_SIZE;
+        if !blocks.is_power_of_two() || blocks < 2 || !ciphertext.len().is_multiple_of(BLOCK_SIZE) {
+            return Err(anyhow!(
+                "Need a message of 2^n whole blocks for n >= 1, not {} bytes",
+                ciphertext.len()
+            ));
+        }
+        let n = blocks.ilog2() as usize;
+        let mut squaring: Columns = (0..128).map(|k| Gf(1 << k)).collect();
+        let squarings = (0..n)
+            .map(|_| {
+                squaring = squaring.iter().map(|c| *c * *c).collect();
+                squaring.clone()
+            })
+            .collect();
+        Ok(Self {
+            n,
+            tag_bits,
+            ciphertext,
+            squarings,
+        })
+    }
+
+    /// How many rows of Ad X can be forced to zero: few enough to leave some flips to spare, and
+    /// at least one row of the tag to learn from
+    fn zeroable(&self, x: &[Gf]) -> usize {
+        ((self.n * 128 - 1) / x.len()).min(self.tag_bits - 1)
+    }
+
+    /// T, transposed: for each bit of each d_i, the first `zeroed` rows of the Ad X which flipping
+    /// that bit alone gives
+    ///
+    /// With just bit j of d_i set, Ad v = x^j v^(2^i), so column c of Ad X is x^j Ms^i X_c. That's
+    /// a matrix product once for each i, and from one j to the next it's a shift, rather than a
+    /// 128 x 128 product for every bit.
+    fn dependencies(&self, x: &[Gf], zeroed: usize) -> Vec<Bits> {
+        let width = zeroed * x.len();
+        let mut rows = Vec::with_capacity(self.n * 128);
+        for squaring in &self.squarings {
+            let mut columns: Columns = x.iter().map(|c| apply(squaring, *c)).collect();
+            for _ in 0..128 {
+                let mut row = Bits::zeros(width);
+                for (c, column) in columns.iter().enumerate() {
+                    for r in (0..zeroed).filter(|r| column.0 >> r & 1 == 1) {
+                        row.flip(r * x.len() + c);
+                    }
+                }
+                rows.push(row);
+                for column in columns.iter_mut() {
+                    *column = X * *column;
+                }
+            }
+        }
+        rows
+    }
+
+    /// d_1, ..., d_n, from a vector of n 128 bits saying which to flip
+    fn flips(&self, d: &Bits) -> Vec<Gf> {
+        (0..self.n)
+            .map(|i| {
+                let bits = (0..128).filter(|j| d.get(128 * i + j));
+                Gf(bits.fold(0, |acc, j| acc | 1 << j))
+            })
+            .collect()
+    }
+
+    /// Ad = sum Mdi Ms^i, so column k is sum d_i (x^k)^(2^i)
+    fn ad(&self, flips: &[Gf]) -> Columns {
+        (0..128)
+            .map(|k| {
+                flips
+                    .iter()
+                    .zip(&self.squarings)
+                    .fold(Gf::ZERO, |acc, (d, squaring)| acc + *d * squaring[k])
+            })
+            .collect()
+    }
+
+    /// The ciphertext with each d_i added to the block h^(2^i) multiplies. GHASH multiplies the
+    /// lengths by h, and the last block of ciphertext by h^2.
+    fn flipped(&self, flips: &[Gf]) -> Vec<u8> {
+        let mut ciphertext = self.ciphertext.clone();
+        let blocks = ciphertext.len() / BLOCK_SIZE;
+        for (i, d) in flips.iter().enumerate() {
+            let index = blocks + 1 - (1 << (i + 1));
+            let block = &mut ciphertext[BLOCK_SIZE * index..BLOCK_SIZE * (index + 1)];
+            let sum = Gf::from_block(block) + *d;
+            block.copy_from_slice(&sum.to_block());
+        }
+        ciphertext
+    }
+
+    /// A ciphertext which `accepts`, as the Ad it was forged with
+    ///
+    /// The flips all come from the null space of T, so the first rows of the error are zero for
+    /// any h = X h', and the rest of the tag is down to luck.
+    fn forge(
+        &self,
+        x: &[Gf],
+        accepts: impl Fn(&[u8]) -> bool,
+        rng: &mut impl Rng,
+    ) -> Result<Forgery> {
+        let zeroed = self.zeroable(x);
+        let basis = kernel(&self.dependencies(x, zeroed), zeroed * x.len());
+        if basis.is_empty() {
+            return Err(anyhow!("No flips leave {zeroed} rows of Ad X zero"));
+        }
+        let mut tries = 0;
+        loop {
+            let mut d = Bits::zeros(self.n * 128);
+            for v in basis.iter().filter(|_| rng.gen()) {
+                d.xor(v);
+            }
+            if d.is_zero() {
+                continue;
+            }
+            tries += 1;
+            let flips = self.flips(&d);
+            if accepts(&self.flipped(&flips)) {
+                return Ok(Forgery {
+                    ad: self.ad(&flips),
+                    zeroed,
+                    tries,
+                });
+            }
+        }
+    }
+}
+
+impl Forger {
+    /// h, from forgeries on this message which `accepts` lets through
+    pub fn recover_key(
+        &self,
+        accepts: impl Fn(&[u8]) -> bool,
+        rng: &mut impl Rng,
+    ) -> Result<Recovered> {
+        recover_key(self.tag_bits, |x| self.forge(x, &accepts, rng))
+    }
+}
+
+/// A forged message Bob accepted
+pub struct Forgery {
+    /// Ad for the changes which made it
+    pub ad: Columns,
+    /// How many rows of Ad X were forced to zero
+    pub zeroed: usize,
+    /// How many tries it took
+    pub tries: usize,
+}
+
+/// h, and what it took to get it
+pub struct Recovered {
+    pub h: Gf,
+    pub forgeries: usize,
+    pub tries: usize,
+}
+
+/// h, from forgeries until K has rank 127 and N(K) has nothing else in it, with `forge` making
+/// each one given X, a basis of N(K)
+///
+/// Each forgery zeroes as many rows of Ad X as it can, and the rows of Ad for the rest of the tag
+/// have to have come out zero too. Those which aren't already in the row space of K, i.e.
+/// orthogonal to all of N(K), go in.
+pub fn recover_key(
+    tag_bits: usize,
+    mut forge: impl FnMut(&[Gf]) -> Result<Forgery>,
+) -> Result<Recovered> {
+    let _span = span!(Info, "recover_key", tag_bits = tag_bits);
+    let mut k: Vec<Bits> = vec![];
+    let mut x: Columns = (0..128).map(|k| Gf(1 << k)).collect();
+    let (mut forgeries, mut total) = (0, 0);
+    while x.len() > 1 {
+        let Forgery { ad, zeroed, tries } = forge(&x)?;
+        forgeries += 1;
+        total += tries;
+        for row in (0..tag_bits).map(|r| row(&ad, r)) {
+            if x.iter().any(|v| dot(&row, *v)) {
+                k.push(row);
+                x = null_space(&k);
+                event!(Debug, "rank increased", rank = k.len(), forgery = forgeries);
+            }
+        }
+        say!(
+            "Forgery {forgeries}: {tries} tries with {zeroed} rows zeroed, K has rank {}",
+            k.len()
+        );
+    }
+    say!(
+        "K has rank {}, which leaves h as the only nonzero vector in N(K)",
+        k.len()
+    );
+    let h = x
+        .first()
+        .copied()
+        .ok_or_else(|| anyhow!("K has full rank, so only h = 0 fits"))?;
+    Ok(Recovered {
+        h,
+        forgeries,
+        tries: total,
+    })
+}
+
+/// Recovers h from forgeries on a message of 2^`blocks_log` blocks, with a tag of `tag_bits`, and
+/// gives it alongside the real h
+fn attack(blocks_log: u32, tag_bits: usize, rng: &mut impl Rng) -> Result<(Gf, Recovered)> {
+    if !tag_bits.is_multiple_of(8) || !(8..=128).contains(&tag_bits) {
+        return Err(anyhow!("Tags are whole bytes, from 8 to 128 bits"));
+    }
+    let key: [u8; 16] = rng.gen();
+    let server = Server {
+        gcm: Gcm::new(&key)?,
+        nonce: rng.gen(),
+    };
+    let h = Gf::from_block(&aes_ecb_encrypt(&[0; BLOCK_SIZE], &key)?);
+
+    let plaintext: Vec<u8> = (0..BLOCK_SIZE << blocks_log).map(|_| rng.gen()).collect();
+    let (ciphertext, tag) = server.gcm.seal(&server.nonce, b"", &plaintext);
+    let tag = tag[..tag_bits / 8].to_vec();
+    say!("A message of 2^{blocks_log} blocks, with a {tag_bits}-bit tag");
+
+    let forger = Forger::new(ciphertext, tag_bits)?;
+    let found = forger.recover_key(|ciphertext| server.accepts(ciphertext, &tag), rng)?;
+    say!(
+        "h = {:032x}, after {} forgeries and {} tries",
+        u128::from_be_bytes(found.h.to_block()),
+        found.forgeries,
+        found.tries
+    );
+    Ok((h, found))
+}
+
+pub fn main() -> Result<Report> {
+    let options = crate::options::get();
+    let blocks_log = options.arg("blocks_log")?;
+    let blocks_log = blocks_log.unwrap_or(options.profile.pick(BLOCKS_LOG, QUICK_BLOCKS_LOG));
+    let tag_bits = options.arg("tag_bits")?;
+    let tag_bits = tag_bits.unwrap_or(options.profile.pick(TAG_BITS, QUICK_TAG_BITS));
+    let (h, found) = attack(blocks_log, tag_bits, &mut crate::utils::rng())?;
+    Ok(Report::new()
+        .check("h", h.to_block(), found.h.to_block())
+        .count("forgeries", found.forgeries as u64)
+        .count("tries", found.tries as u64))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gcm::ghash;
+
+    #[test]
+    fn error_polynomial() {
+        let mut rng = rand::thread_rng();
+        let h = Gf::random(&mut rng);
+        let ciphertext: Vec<u8> = (0..BLOCK_SIZE << 5).map(|_| rng.gen()).collect();
+        let forger = Forger::new(ciphertext.clone(), 32).unwrap();
+        let flips: Vec<Gf> = (0..5).map(|_| Gf::random(&mut rng)).collect();
+
+        // Ad h is how much the hash moves
+        let e = ghash(h, b"", &forger.flipped(&flips)) + ghash(h, b"", &ciphertext);
+        assert_eq!(apply(&forger.ad(&flips), h), e);
+
+        // and with flips from the null space of T, the first rows of it are zero
+        let x: Columns = (0..128).map(|k| Gf(1 << k)).collect();
+        let zeroed = forger.zeroable(&x);
+        assert_eq!(zeroed, 4);
+        let basis = kernel(&forger.dependencies(&x, zeroed), zeroed * 128);
+        assert_eq!(basis.len(), 128);
+        let e = apply(&forger.ad(&forger.flips(&basis[0])), h);
+        assert_eq!(e.0 & 0b1111, 0);
+    }
+
+    #[test]
+    fn truncated_tag() {
+        let (h, found) = attack(8, 16, &mut rand::thread_rng()).unwrap();
+        assert_eq!(found.h, h);
+    }
 }
@@ -110,8 +110,429 @@
 //!    successful forgery. If this happens, you can fall back to your old
 //!    code. Or just wait for a new message of a different length.
 
+use anyhow::anyhow;
+use rand::Rng;
+
+use crate::aes::{aes_ecb_encrypt, BLOCK_SIZE};
+use crate::gcm::{Gcm, Gf};
+use crate::report::say;
+use crate::set8::challenge62::Sweep;
+use crate::gf2::{apply, kernel, Bits, Columns};
+use crate::set8::challenge64::{recover_key, Forgery, Recovered, Server, X};
 use crate::utils::*;
 
-pub fn main() -> Result<()> {
-    unimplemented!()
+/// Bytes of ciphertext, unless `--arg length=` says otherwise: 2^17 blocks, the last of them
+/// holding a single byte
+const LENGTH: usize = (BLOCK_SIZE << 17) - 15;
+/// Bits of tag Bob checks, unless `--arg tag_bits=` says otherwise
+const TAG_BITS: [usize; 3] = [16, 32, 64];
+/// Configurations whose first forgery should take more than 2^this tries are only reported on
+const MAX_TRIES_LOG: usize = 24;
+/// Solutions to spare beyond the 2^k tries a forgery is expected to take, as a power of two
+const SPARE_BITS: usize = 4;
+
+/// Forgeries on one message of any length, which complete a partial last block when they can
+///
+/// Block j of B is multiplied by h^(B + 1 - j), so those multiplied by h^(2^i) for i >= 1 are
+/// there to flip as long as 2^i <= B + 1. If the last of them is partial, only the bytes it has can
+/// be flipped, unless the lengths block says it's whole. That adds a fixed d_0 to the lengths,
+/// which h multiplies, so Ad = Md0 + sum Mdi Ms^i, and zeroing rows of Ad X means solving
+/// T d = t, for t those rows of Md0 X.
+pub struct Extender {
+    /// So the blocks multiplied by h^2, h^4, ..., h^(2^n) are all there to flip
+    n: usize,
+    tag_bits: usize,
+    ciphertext: Vec<u8>,
+    /// Ms^i for i = 1, ..., n
+    squarings: Vec<Columns>,
+}
+
+impl Extender {
+    pub fn new(ciphertext: Vec<u8>, tag_bits: usize) -> Result<Self> {
+        if ciphertext.is_empty() {
+            return Err(anyhow!("Need at least one byte of ciphertext"));
+        }
+        let n = (ciphertext.len().div_ceil(BLOCK_SIZE) + 1).ilog2() as usize;
+        let mut squaring: Columns = (0..128).map(|k| Gf(1 << k)).collect();
+        let squarings = (0..n)
+            .map(|_| {
+                squaring = squaring.iter().map(|c| *c * *c).collect();
+                squaring.clone()
+            })
+            .collect();
+        Ok(Self {
+            n,
+            tag_bits,
+            ciphertext,
+            squarings,
+        })
+    }
+
+    /// Bytes in the last block, if it isn't whole
+    fn partial(&self) -> Option<usize> {
+        Some(self.ciphertext.len() % BLOCK_SIZE).filter(|r| *r != 0)
+    }
+
+    /// d_0, which makes the lengths block count the last block as whole
+    fn lengths_flip(&self) -> Gf {
+        let lengths = |len: usize| Gf::from_block(&(8 * len as u128).to_be_bytes());
+        let whole = self.ciphertext.len().next_multiple_of(BLOCK_SIZE);
+        lengths(self.ciphertext.len()) + lengths(whole)
+    }
+
+    /// Whether bit j of d_i can be flipped: all of them, bar the padding of a partial last block
+    /// which is left as it is
+    fn is_free(&self, extend: bool, i: usize, j: usize) -> bool {
+        match self.partial() {
+            Some(bytes) if i == 0 && !extend => j < 8 * bytes,
+            _ => true,
+        }
+    }
+
+    /// (i, j) for each bit j of d_i which can be flipped, in the order d has them
+    fn free(&self, extend: bool) -> Vec<(usize, usize)> {
+        (0..self.n)
+            .flat_map(|i| (0..128).map(move |j| (i, j)))
+            .filter(|(i, j)| self.is_free(extend, *i, *j))
+            .collect()
+    }
+
+    /// How many rows of Ad X can be forced to zero with `free` bits to flip, leaving solutions to
+    /// spare for the tries the rest of the tag should take, and at least one row of it to learn from
+    fn zeroable(&self, x: &[Gf], free: usize) -> Option<usize> {
+        (1..self.tag_bits)
+            .rev()
+            .find(|z| z * x.len() + self.tag_bits - z + SPARE_BITS <= free)
+    }
+
+    /// T, transposed: for each free bit of each d_i, the first `zeroed` rows of the Ad X which
+    /// flipping that bit alone gives, worked out as in challenge 64
+    fn dependencies(&self, x: &[Gf], zeroed: usize, extend: bool) -> Vec<Bits> {
+        let width = zeroed * x.len();
+        let mut rows = vec![];
+        for (i, squaring) in self.squarings.iter().enumerate() {
+            let mut columns: Columns = x.iter().map(|c| apply(squaring, *c)).collect();
+            for j in 0..128 {
+                if self.is_free(extend, i, j) {
+                    rows.push(rows_of(&columns, zeroed, width));
+                }
+                for column in columns.iter_mut() {
+                    *column = X * *column;
+                }
+            }
+        }
+        rows
+    }
+
+    /// t, the first `zeroed` rows of Md0 X
+    fn target(&self, x: &[Gf], zeroed: usize) -> Bits {
+        let d0 = self.lengths_flip();
+        let columns: Columns = x.iter().map(|c| d0 * *c).collect();
+        rows_of(&columns, zeroed, zeroed * x.len())
+    }
+
+    /// d_1, ..., d_n, from a vector saying which of the free bits to flip
+    fn flips(&self, d: &Bits, extend: bool) -> Vec<Gf> {
+        let mut flips = vec![Gf::ZERO; self.n];
+        for (k, (i, j)) in self.free(extend).into_iter().enumerate() {
+            if d.get(k) {
+                flips[i].0 ^= 1 << j;
+            }
+        }
+        flips
+    }
+
+    /// Ad = Md0 + sum Mdi Ms^i, so column k is d_0 x^k + sum d_i (x^k)^(2^i), without the d_0 if
+    /// the lengths are left alone
+    fn ad(&self, flips: &[Gf], extend: bool) -> Columns {
+        let d0 = if extend {
+            self.lengths_flip()
+        } else {
+            Gf::ZERO
+        };
+        (0..128)
+            .map(|k| {
+                flips
+                    .iter()
+                    .zip(&self.squarings)
+                    .fold(d0 * Gf(1 << k), |acc, (d, squaring)| acc + *d * squaring[k])
+            })
+            .collect()
+    }
+
+    /// The ciphertext with each d_i added to the block h^(2^i) multiplies, completed with zeros
+    /// first if it's to be extended
+    fn flipped(&self, flips: &[Gf], extend: bool) -> Vec<u8> {
+        let mut ciphertext = self.ciphertext.clone();
+        if extend {
+            ciphertext.resize(ciphertext.len().next_multiple_of(BLOCK_SIZE), 0);
+        }
+        let blocks = ciphertext.len().div_ceil(BLOCK_SIZE);
+        for (i, d) in flips.iter().enumerate() {
+            let index = blocks + 1 - (1 << (i + 1));
+            let end = (BLOCK_SIZE * (index + 1)).min(ciphertext.len());
+            let block = &mut ciphertext[BLOCK_SIZE * index..end];
+            let sum = Gf::from_block(block) + *d;
+            block.copy_from_slice(&sum.to_block()[..block.len()]);
+        }
+        ciphertext
+    }
+
+    /// A ciphertext which `accepts`, extending the last block if `extend`
+    ///
+    /// The solutions to T d = t are the combinations of the null space of [T | t] which use t, so
+    /// half of any random combination of it, when there are some at all.
+    fn forge_with(
+        &self,
+        x: &[Gf],
+        extend: bool,
+        accepts: impl Fn(&[u8]) -> bool,
+        rng: &mut impl Rng,
+    ) -> Result<Forgery> {
+        let free = self.free(extend).len();
+        let zeroed = self
+            .zeroable(x, free)
+            .ok_or_else(|| anyhow!("Too few bits to flip to zero any rows of Ad X"))?;
+        let mut vectors = self.dependencies(x, zeroed, extend);
+        if extend {
+            vectors.push(self.target(x, zeroed));
+        }
+        let basis = kernel(&vectors, zeroed * x.len());
+        if extend && !basis.iter().any(|v| v.get(free)) {
+            return Err(anyhow!("No flips cancel out the change to the lengths"));
+        }
+        if basis.is_empty() {
+            return Err(anyhow!("No flips leave {zeroed} rows of Ad X zero"));
+        }
+        let mut tries = 0;
+        loop {
+            let mut d = Bits::zeros(vectors.len());
+            for v in basis.iter().filter(|_| rng.gen()) {
+                d.xor(v);
+            }
+            if (extend && !d.get(free)) || d.is_zero() {
+                continue;
+            }
+            tries += 1;
+            let flips = self.flips(&d, extend);
+            if accepts(&self.flipped(&flips, extend)) {
+                return Ok(Forgery {
+                    ad: self.ad(&flips, extend),
+                    zeroed,
+                    tries,
+                });
+            }
+        }
+    }
+
+    /// A ciphertext which `accepts`, and whether it took extending the last block
+    ///
+    /// Extending comes first, if the last block is partial, falling back to flipping only the bytes
+    /// which are there when nothing cancels out the change to the lengths.
+    fn forge(
+        &self,
+        x: &[Gf],
+        accepts: impl Fn(&[u8]) -> bool,
+        rng: &mut impl Rng,
+    ) -> Result<(Forgery, bool)> {
+        if self.partial().is_some() {
+            if let Ok(forgery) = self.forge_with(x, true, &accepts, rng) {
+                return Ok((forgery, true));
+            }
+        }
+        Ok((self.forge_with(x, false, accepts, rng)?, false))
+    }
+
+    /// How many rows the first forgery zeroes, extending the last block or not
+    fn first_zeroed(&self, extend: bool) -> Option<usize> {
+        let x: Columns = (0..128).map(|k| Gf(1 << k)).collect();
+        self.zeroable(&x, self.free(extend).len())
+    }
+}
+
+/// `zeroed` rows of the matrix with these columns, one after the other
+fn rows_of(columns: &[Gf], zeroed: usize, width: usize) -> Bits {
+    let mut rows = Bits::zeros(width);
+    for (c, column) in columns.iter().enumerate() {
+        for r in (0..zeroed).filter(|r| column.0 >> r & 1 == 1) {
+            rows.flip(r * columns.len() + c);
+        }
+    }
+    rows
+}
+
+/// How recovering h went for one length and tag size
+struct Outcome {
+    length: usize,
+    tag_bits: usize,
+    /// The real h, to check the recovered one against
+    h: Gf,
+    /// h, and how many of the forgeries extended the last block, unless it was never attempted
+    /// because the first forgery alone would take about 2^this many tries
+    result: std::result::Result<(Recovered, usize), usize>,
+}
+
+impl std::fmt::Display for Outcome {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} bytes, {}-bit tag: ", self.length, self.tag_bits)?;
+        match &self.result {
+            Ok((recovered, extended)) => write!(
+                f,
+                "{} forgeries ({extended} extending the last block), {} tries",
+                recovered.forgeries, recovered.tries
+            ),
+            Err(log) => write!(
+                f,
+                "not attempted, the first forgery needs about 2^{log} tries"
+            ),
+        }
+    }
+}
+
+/// Recovers h from forgeries on a message of `length` bytes with a tag of `tag_bits`, unless the
+/// first forgery would take too long
+fn attack(length: usize, tag_bits: usize, rng: &mut impl Rng) -> Result<Outcome> {
+    if !tag_bits.is_multiple_of(8) || !(8..=128).contains(&tag_bits) {
+        return Err(anyhow!("Tags are whole bytes, from 8 to 128 bits"));
+    }
+    let key: [u8; 16] = rng.gen();
+    let server = Server {
+        gcm: Gcm::new(&key)?,
+        nonce: rng.gen(),
+    };
+    let h = Gf::from_block(&aes_ecb_encrypt(&[0; BLOCK_SIZE], &key)?);
+
+    let plaintext: Vec<u8> = (0..length).map(|_| rng.gen()).collect();
+    let (ciphertext, tag) = server.gcm.seal(&server.nonce, b"", &plaintext);
+    let tag = tag[..tag_bits / 8].to_vec();
+    say!("A message of {length} bytes, with a {tag_bits}-bit tag");
+
+    let extender = Extender::new(ciphertext, tag_bits)?;
+    let (left, extended) = (extender.first_zeroed(false), extender.first_zeroed(true));
+    if extender.partial().is_some() {
+        say!(
+            "Extending the last block lets the first forgery zero {} rows rather than {}",
+            extended.unwrap_or(0),
+            left.unwrap_or(0)
+        );
+    }
+    let best = match extender.partial() {
+        Some(_) => left.max(extended),
+        None => left,
+    };
+    let log = tag_bits - best.ok_or_else(|| anyhow!("Too short a message to forge"))?;
+    if log > MAX_TRIES_LOG {
+        say!("That leaves about 2^{log} tries for the first forgery, so stopping there");
+        return Ok(Outcome {
+            length,
+            tag_bits,
+            h,
+            result: Err(log),
+        });
+    }
+
+    let mut extensions = 0;
+    let recovered = recover_key(tag_bits, |x| {
+        let (forgery, extended) =
+            extender.forge(x, |ciphertext| server.accepts(ciphertext, &tag), rng)?;
+        extensions += usize::from(extended);
+        Ok(forgery)
+    })?;
+    say!("h = {:032x}", u128::from_be_bytes(recovered.h.to_block()));
+    Ok(Outcome {
+        length,
+        tag_bits,
+        h,
+        result: Ok((recovered, extensions)),
+    })
+}
+
+pub fn main() -> Result<Report> {
+    let options = crate::options::get();
+    let Sweep(lengths) = options.arg("length")?.unwrap_or(Sweep(vec![LENGTH]));
+    let Sweep(tag_sizes) = options.arg("tag_bits")?.unwrap_or(Sweep(TAG_BITS.to_vec()));
+
+    let mut rng = crate::utils::rng();
+    let mut outcomes = vec![];
+    for &length in &lengths {
+        for &tag_bits in &tag_sizes {
+            outcomes.push(attack(length, tag_bits, &mut rng)?);
+        }
+    }
+    let mut report = Report::new();
+    for outcome in outcomes {
+        say!("{outcome}");
+        if let Ok((recovered, extensions)) = &outcome.result {
+            let name = format!("{} bytes, {}-bit tag", outcome.length, outcome.tag_bits);
+            report = report
+                .check(
+                    &format!("h, {name}"),
+                    outcome.h.to_block(),
+                    recovered.h.to_block(),
+                )
+                .count(&format!("forgeries, {name}"), recovered.forgeries as u64)
+                .count(&format!("tries, {name}"), recovered.tries as u64)
+                .count(&format!("extensions, {name}"), *extensions as u64);
+        }
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gcm::ghash;
+
+    #[test]
+    fn length_extension() {
+        let mut rng = rand::thread_rng();
+        let h = Gf::random(&mut rng);
+        // 5 blocks, the last with 3 bytes, so h^2, h^4 from blocks 4 and 2
+        let ciphertext: Vec<u8> = (0..4 * BLOCK_SIZE + 3).map(|_| rng.gen()).collect();
+        let extender = Extender::new(ciphertext.clone(), 32).unwrap();
+        assert_eq!(extender.n, 2);
+        assert_eq!(extender.free(false).len(), 128 + 24);
+        assert_eq!(extender.free(true).len(), 256);
+
+        // Ad h is how much the hash moves, lengths and all
+        for extend in [false, true] {
+            let mut flips: Vec<Gf> = (0..2).map(|_| Gf::random(&mut rng)).collect();
+            if !extend {
+                flips[0].0 &= (1 << 24) - 1;
+            }
+            let flipped = extender.flipped(&flips, extend);
+            assert_eq!(flipped.len() == ciphertext.len(), !extend);
+            let e = ghash(h, b"", &flipped) + ghash(h, b"", &ciphertext);
+            assert_eq!(apply(&extender.ad(&flips, extend), h), e);
+        }
+
+        // and solutions of T d = t zero the first rows of it, despite d_0
+        let x: Columns = (0..128).map(|k| Gf(1 << k)).collect();
+        let zeroed = 1;
+        let mut vectors = extender.dependencies(&x, zeroed, true);
+        vectors.push(extender.target(&x, zeroed));
+        let d = kernel(&vectors, zeroed * 128)
+            .into_iter()
+            .find(|v| v.get(256))
+            .unwrap();
+        let e = apply(&extender.ad(&extender.flips(&d, true), true), h);
+        assert_eq!(e.0 & 1, 0);
+    }
+
+    #[test]
+    fn extended_forgeries() {
+        let mut rng = rand::thread_rng();
+        let outcome = attack(BLOCK_SIZE * 256 - 15, 16, &mut rng).unwrap();
+        let (recovered, extended) = outcome.result.unwrap();
+        assert_eq!(recovered.h, outcome.h);
+        assert!(extended > 0);
+        assert!(recovered.forgeries >= 1);
+
+        // Whole blocks can't be extended, so it's challenge 64 over again
+        let outcome = attack(BLOCK_SIZE * 256, 16, &mut rng).unwrap();
+        assert_eq!(outcome.result.unwrap().1, 0);
+
+        let outcome = attack(BLOCK_SIZE * 256, 64, &mut rng).unwrap();
+        assert!(outcome.result.is_err());
+    }
 }
@@ -253,8 +253,291 @@
 //!    of the attack offline using standard discrete logarithm attacks
 //!    (e.g. Pollard's kangaroo).
 
-use crate::utils::*;
+use anyhow::anyhow;
+use num_bigint::{BigInt, RandBigInt};
+use num_traits::{One, ToPrimitive};
+use rand::Rng;
 
-pub fn main() -> Result<()> {
-    unimplemented!()
+use crate::progress;
+use crate::report::say;
+use crate::{
+    ecdsa::Params,
+    group::{kangaroo, Abelian},
+    set8::challenge59::{decision_tree, trace, Curve, Point},
+    utils::*,
+};
+
+/// Odds against any one add faulting, unless `--arg fault_odds=` says otherwise
+const FAULT_ODDS: u64 = 256;
+/// Low bits of the key left for the kangaroo, unless `--arg kangaroo_bits=` says otherwise
+const KANGAROO_BITS: u64 = 32;
+/// A run of faults is trusted once the chance of the bit being wrong is below 2^-this
+const DOUBT_LOG: u32 = 20;
+/// 2^61 - 1, the prime [`Faulty::fault`] reduces by
+const MERSENNE: u64 = (1 << 61) - 1;
+
+/// Scalar multiplication as the challenge has it, from the top bit down, with an add which falls
+/// over for about one pair of points in `odds`
+pub struct Faulty {
+    pub curve: Curve,
+    pub odds: u64,
+}
+
+impl Faulty {
+    /// Whether adding these two faults: when x1 x2 mod 2^61 - 1 lands in the top 1/odds of its
+    /// range, which is as good as random but the same every time
+    pub fn fault(&self, p1: &Point, p2: &Point) -> bool {
+        match (p1, p2) {
+            (Point::P { x: x1, .. }, Point::P { x: x2, .. }) => {
+                (x1 * x2 % MERSENNE).to_u64().unwrap() >= MERSENNE - MERSENNE / self.odds
+            }
+            _ => false,
+        }
+    }
+
+    /// p1 + p2, unless it faults
+    fn add(&self, p1: &Point, p2: &Point) -> Option<Point> {
+        (!self.fault(p1, p2)).then(|| self.curve.add(p1, p2))
+    }
+
+    /// k Q, or nothing if one of the adds on the way faults
+    pub fn scalarmult(&self, q: &Point, k: &BigInt) -> Option<Point> {
+        let bits: Vec<bool> = (0..k.bits()).rev().map(|i| k.bit(i)).collect();
+        self.run(q, &bits)
+    }
+
+    /// R after the scalar's bits from the top down to the last of `bits`, the first of which is 1
+    fn run(&self, q: &Point, bits: &[bool]) -> Option<Point> {
+        let mut r = q.clone();
+        for b in &bits[1..] {
+            r = self.add(&r, &r)?;
+            if *b {
+                r = self.add(&r, q)?;
+            }
+        }
+        Some(r)
+    }
+}
+
+/// Bob, whose ECDH falls over now and then, which is all he lets slip about his key
+struct Bob {
+    faulty: Faulty,
+    key: BigInt,
+}
+
+impl Bob {
+    fn faults(&self, q: &Point) -> bool {
+        self.faulty.scalarmult(q, &self.key).is_none()
+    }
+}
+
+/// How getting a key out of the faults went
+#[derive(Debug)]
+pub struct Recovery {
+    pub key: BigInt,
+    /// How many times the oracle was asked
+    pub queries: usize,
+    /// Bits which came from a query that didn't fault, so are certain
+    pub certain: usize,
+    /// Bits which came from enough faults to be trusted
+    pub trusted: usize,
+}
+
+/// Bob's key, of `bits` bits with the top one set, from whether multiplying the points he's given
+/// by it `faults`, as far as the last `kangaroo_bits`, which come from his `public` key instead
+///
+/// For the next bit, after doubling, a 1 means adding Q and a 0 means doubling again. A point
+/// which survives the bits known so far and faults on one of those but not the other tells which it
+/// is if Bob doesn't fault. If he does, it could still be an add further on, about
+/// 1 - (1 - 1 / odds)^(1.5 r) likely for r bits to go, so that goes into the odds on the bit and
+/// more points are tried until they're long enough to trust.
+pub fn recover_key(
+    faulty: &Faulty,
+    params: &Params,
+    public: &Point,
+    bits: u64,
+    kangaroo_bits: u64,
+    faults: impl Fn(&Point) -> bool,
+    rng: &mut impl Rng,
+) -> Result<Recovery> {
+    let mut known = vec![true];
+    let (mut queries, mut certain, mut trusted) = (0, 0, 0);
+    let step = params.g.clone();
+    let mut q = params
+        .curve
+        .scale(&params.g, &rng.gen_bigint_range(&BigInt::one(), &params.n));
+    let threshold = f64::from(DOUBT_LOG) * std::f64::consts::LN_2;
+    let to_find = bits.saturating_sub(kangaroo_bits).max(1);
+    let progress = progress::bar(to_find);
+    while (known.len() as u64) < to_find {
+        let rest = bits - known.len() as u64 - 1;
+        let clean = (1.0 - 1.0 / faulty.odds as f64).powf(1.5 * rest as f64);
+        // ln of how much likelier a fault is if the point faults on the branch taken
+        let weight = -(1.0 - clean).ln();
+        // ln of the odds on the bit being 1
+        let mut evidence = 0.0;
+        let bit = loop {
+            q = params.curve.add(&q, &step);
+            let Some(branch) = diverges(faulty, &q, &known, rest > 0) else {
+                continue;
+            };
+            queries += 1;
+            if !faults(&q) {
+                certain += 1;
+                break !branch;
+            }
+            evidence += if branch { weight } else { -weight };
+            if evidence.abs() >= threshold {
+                trusted += 1;
+                break evidence > 0.0;
+            }
+        };
+        known.push(bit);
+        progress.inc(1);
+        progress.set_message(format!("{queries} queries"));
+    }
+    progress.finish();
+
+    // key = high 2^w + x, for x in [0, 2^w), and x g = public - high 2^w g
+    let w = bits - known.len() as u64;
+    let high = known
+        .iter()
+        .fold(BigInt::from(0), |acc, b| 2 * acc + u8::from(*b));
+    let shift = &high << w;
+    let target = params.curve.combine(
+        public,
+        &params.curve.inverse(&params.curve.scale(&params.g, &shift)),
+    );
+    say!(
+        "The top {} bits are {high:x}, kangaroo for the rest",
+        known.len()
+    );
+    let x = kangaroo(
+        &params.curve,
+        &params.g,
+        &target,
+        &((BigInt::one() << w) - 1),
+    )
+    .ok_or_else(|| anyhow!("The kangaroo found nothing, so one of the bits was wrong"))?;
+    Ok(Recovery {
+        key: shift + x,
+        queries,
+        certain,
+        trusted,
+    })
+}
+
+/// Which way the next bit has to go for Q to fault, if only one of them does, after surviving the
+/// `known` bits and the doubling for the next. A 0 only does anything if there's a bit after it.
+fn diverges(faulty: &Faulty, q: &Point, known: &[bool], more: bool) -> Option<bool> {
+    let r = faulty.run(q, known)?;
+    let doubled = faulty.add(&r, &r)?;
+    let one = faulty.fault(&doubled, q);
+    let zero = more && faulty.fault(&doubled, &doubled);
+    (one != zero).then_some(one)
+}
+
+/// Recovers a key of `bits` bits from Bob's faults, with adds faulting one time in `odds`, and
+/// gives it alongside Bob's real key
+fn attack(
+    bits: u64,
+    odds: u64,
+    kangaroo_bits: u64,
+    rng: &mut impl Rng,
+) -> Result<(BigInt, Recovery)> {
+    let params = Params::default();
+    if bits < 2 || bits >= params.n.bits() {
+        return Err(anyhow!(
+            "Keys are from 2 to {} bits on this curve",
+            params.n.bits() - 1
+        ));
+    }
+    if odds < 2 {
+        return Err(anyhow!("Fault odds of at least 2, please"));
+    }
+    let top = BigInt::one() << (bits - 1);
+    let bob = Bob {
+        faulty: Faulty {
+            curve: params.curve.clone(),
+            odds,
+        },
+        key: rng.gen_bigint_range(&top, &(2 * &top)),
+    };
+    let public = params.curve.scale(&params.g, &bob.key);
+    say!("Bob has a {bits}-bit key, and one add in {odds} faults");
+
+    let recovery = recover_key(
+        &bob.faulty,
+        &params,
+        &public,
+        bits,
+        kangaroo_bits,
+        |q| bob.faults(q),
+        rng,
+    )?;
+    say!(
+        "{} queries, with {} bits certain and {} trusted on the strength of faults",
+        recovery.queries,
+        recovery.certain,
+        recovery.trusted
+    );
+    say!("Bob's key: {:x}", recovery.key);
+    Ok((bob.key, recovery))
+}
+
+pub fn main() -> Result<Report> {
+    let options = crate::options::get();
+    // Just the tracer, to get a feel for it
+    let traced: Option<BigInt> = options.arg("trace")?;
+    let depth: Option<u32> = options.arg("tree")?;
+    if traced.is_some() || depth.is_some() {
+        if let Some(k) = traced {
+            say!("{}", trace(&k));
+        }
+        if let Some(depth) = depth {
+            say!("{}", decision_tree(depth).trim_end());
+        }
+        return Ok(Report::new());
+    }
+
+    let bits = Params::default().n.bits() - 1;
+    let odds = options.arg("fault_odds")?.unwrap_or(FAULT_ODDS);
+    let kangaroo_bits = options.arg("kangaroo_bits")?.unwrap_or(KANGAROO_BITS);
+    let (key, recovery) = attack(bits, odds, kangaroo_bits, &mut crate::utils::rng())?;
+    Ok(Report::new()
+        .check("key", key, recovery.key)
+        .count("queries", recovery.queries as u64))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn faulty_scalarmult() {
+        let params = Params::default();
+        let faulty = Faulty {
+            curve: params.curve.clone(),
+            odds: 1 << 40,
+        };
+        let k = BigInt::from(58);
+        assert_eq!(
+            faulty.scalarmult(&params.g, &k),
+            Some(params.curve.scale(&params.g, &k))
+        );
+
+        // With every add faulting, even 2 Q does
+        let faulty = Faulty { odds: 1, ..faulty };
+        assert!(faulty.fault(&params.g, &params.g));
+        assert_eq!(faulty.scalarmult(&params.g, &k), None);
+        assert_eq!(faulty.scalarmult(&params.g, &BigInt::one()), Some(params.g));
+    }
+
+    #[test]
+    fn fault_attack() {
+        let (key, recovery) = attack(40, 16, 12, &mut rand::thread_rng()).unwrap();
+        assert_eq!(recovery.key, key);
+        assert_eq!(recovery.key.bits(), 40);
+        assert_eq!(recovery.certain + recovery.trusted, 27);
+    }
 }
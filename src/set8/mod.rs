@@ -1,30 +1,15 @@
-pub mod challenge57;
-pub mod challenge58;
-pub mod challenge59;
-pub mod challenge60;
-pub mod challenge61;
-pub mod challenge62;
-pub mod challenge63;
-pub mod challenge64;
-pub mod challenge65;
-pub mod challenge66;
+pub mod params;
 
-use crate::utils::Result;
-use anyhow::anyhow;
-
-pub fn run(c: u64) -> Result<()> {
-    match c {
-        57 => challenge57::main(),
-        58 => challenge58::main(),
-        59 => challenge59::main(),
-        60 => challenge60::main(),
-        61 => challenge61::main(),
-        62 => challenge62::main(),
-        63 => challenge63::main(),
-        64 => challenge64::main(),
-        65 => challenge65::main(),
-        66 => challenge66::main(),
-        i => Err(anyhow!("{} not in set 8", i)),
-    }
+crate::runner::register! {
+    set 8;
+    57 => challenge57, "Diffie-Hellman Revisited: Subgroup-Confinement Attacks", Fast;
+    58 => challenge58, "Pollard's Method for Catching Kangaroos", Fast;
+    59 => challenge59, "Elliptic Curve Diffie-Hellman and Invalid-Curve Attacks", Fast;
+    60 => challenge60, "Single-Coordinate Ladders and Insecure Twists", Slow;
+    61 => challenge61, "Duplicate-Signature Key Selection in ECDSA (and RSA)", Fast;
+    62 => challenge62, "Key-Recovery Attacks on ECDSA with Biased Nonces", Fast;
+    63 => challenge63, "Key-Recovery Attacks on GCM with Repeated Nonces", Fast;
+    64 => challenge64, "Key-Recovery Attacks on GCM with a Truncated MAC", Slow;
+    65 => challenge65, "Truncated-MAC GCM Revisited: Improving the Key-Recovery Attack", Slow;
+    66 => challenge66, "Exploiting Implementation Errors in Diffie-Hellman", Slow;
 }
-
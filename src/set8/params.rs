@@ -0,0 +1,66 @@
+//! The curve set 8 keeps coming back to, challenge 59's
+//!
+//!     y^2 = x^3 - 95051*x + 11279326
+//!
+//! over GF(233970423115425145524320034830162017933), and the Montgomery form of it from challenge
+//! 60. Each is parsed the first time it's needed and shared from then on.
+
+use num_bigint::BigInt;
+use std::str::FromStr;
+use std::sync::OnceLock;
+
+use super::challenge59::{Curve, CurveParams, Point};
+use super::challenge60::MontgomeryCurve;
+
+/// The prime both forms of the curve are over
+pub fn p() -> &'static BigInt {
+    static P: OnceLock<BigInt> = OnceLock::new();
+    P.get_or_init(|| BigInt::from_str("233970423115425145524320034830162017933").unwrap())
+}
+
+/// The number of points on the curve, 8 times [`base_order`]
+pub fn curve_order() -> &'static BigInt {
+    static ORDER: OnceLock<BigInt> = OnceLock::new();
+    ORDER.get_or_init(|| BigInt::from_str("233970423115425145498902418297807005944").unwrap())
+}
+
+/// The order of the base point, a prime
+pub fn base_order() -> &'static BigInt {
+    static ORDER: OnceLock<BigInt> = OnceLock::new();
+    ORDER.get_or_init(|| BigInt::from_str("29246302889428143187362802287225875743").unwrap())
+}
+
+/// (182, 85518893674295321206118380980485522083)
+pub fn base_point() -> &'static Point {
+    static BP: OnceLock<Point> = OnceLock::new();
+    BP.get_or_init(|| Point::P {
+        x: BigInt::from(182),
+        y: BigInt::from_str("85518893674295321206118380980485522083").unwrap(),
+    })
+}
+
+/// The curve in Weierstrass form, with [`base_point`] and [`curve_order`] as its parameters
+pub fn curve() -> &'static Curve {
+    static CURVE: OnceLock<Curve> = OnceLock::new();
+    CURVE.get_or_init(|| Curve {
+        params: CurveParams {
+            a: BigInt::from(-95051),
+            b: BigInt::from(11279326),
+            p: p().clone(),
+            bp: base_point().clone(),
+            ord: curve_order().clone(),
+        },
+    })
+}
+
+/// The same curve as v^2 = u^3 + 534*u^2 + u, where the base point has u = 4
+pub fn montgomery() -> &'static MontgomeryCurve {
+    static CURVE: OnceLock<MontgomeryCurve> = OnceLock::new();
+    CURVE.get_or_init(|| MontgomeryCurve {
+        A: BigInt::from(534),
+        B: BigInt::from(1),
+        p: p().clone(),
+        bp: BigInt::from(4),
+        ord: curve_order().clone(),
+    })
+}
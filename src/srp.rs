@@ -0,0 +1,374 @@
+//! Secure Remote Password, as SRP-6a from RFC 5054
+//! https://www.rfc-editor.org/rfc/rfc5054
+//!
+//! The groups are those from the RFC's appendix A, and the hash can be SHA-1 (as the RFC uses, and
+//! its test vectors need) or SHA-256 (as the challenges use). Everything going into the hash is
+//! worked out the RFC's way: x = H(salt | H(I | ":" | P)), k = H(N | PAD(g)) and
+//! u = H(PAD(A) | PAD(B)), where PAD() left-pads with zeros to the length of N.
+//!
+//! Past the shared secret, the challenges' own protocol takes over: K = H(S), and the client proves
+//! it knows K with HMAC-SHA256(K, salt).
+#![allow(dead_code)]
+
+use std::fmt;
+use std::str::FromStr;
+
+use anyhow::{anyhow, Result};
+use num_bigint::{BigInt, RandBigInt, Sign};
+use rand::Rng;
+
+use crate::hash::sha256::sha256;
+use crate::utils::{hex_to_bytes, Sha1Hasher};
+
+const N_1024: &str = "EEAF0AB9ADB38DD69C33F80AFA8FC5E86072618775FF3C0B9EA2314C9C256576D674DF7496\
+    EA81D3383B4813D692C6E0E0D5D8E250B98BE48E495C1D6089DAD15DC7D7B46154D6B6CE8EF4AD69B15D4982559B29\
+    7BCF1885C529F566660E57EC68EDBC3C05726CC02FD4CBF4976EAA9AFD5138FE8376435B9FC61D2FC0EB06E3";
+const N_1536: &str = "9DEF3CAFB939277AB1F12A8617A47BBBDBA51DF499AC4C80BEEEA9614B19CC4D5F4F5F556E\
+    27CBDE51C6A94BE4607A291558903BA0D0F84380B655BB9A22E8DCDF028A7CEC67F0D08134B1C8B97989149B609E0B\
+    E3BAB63D47548381DBC5B1FC764E3F4B53DD9DA1158BFD3E2B9C8CF56EDF019539349627DB2FD53D24B7C48665772E\
+    437D6C7F8CE442734AF7CCB7AE837C264AE3A9BEB87F8A2FE9B8B5292E5A021FFF5E91479E8CE7A28C2442C6F31518\
+    0F93499A234DCF76E3FED135F9BB";
+const N_2048: &str = "AC6BDB41324A9A9BF166DE5E1389582FAF72B6651987EE07FC3192943DB56050A37329CBB4\
+    A099ED8193E0757767A13DD52312AB4B03310DCD7F48A9DA04FD50E8083969EDB767B0CF6095179A163AB3661A05FB\
+    D5FAAAE82918A9962F0B93B855F97993EC975EEAA80D740ADBF4FF747359D041D5C33EA71D281E446B14773BCA97B4\
+    3A23FB801676BD207A436C6481F1D2B9078717461A5B9D32E688F87748544523B524B0D57D5EA77A2775D2ECFA032C\
+    FBDBF52FB3786160279004E57AE6AF874E7303CE53299CCC041C7BC308D82A5698F3A8D0C38271AE35F8E9DBFBB694\
+    B5C803D89F7AE435DE236D525F54759B65E372FCD68EF20FA7111F9E4AFF73";
+
+/// Hash function underneath everything
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Hash {
+    Sha1,
+    #[default]
+    Sha256,
+}
+
+impl Hash {
+    /// Hash of all of `parts` one after the other
+    pub fn digest(&self, parts: &[&[u8]]) -> Vec<u8> {
+        let data = parts.concat();
+        match self {
+            Self::Sha1 => Sha1Hasher::default().hash(&data, None),
+            Self::Sha256 => sha256(&data),
+        }
+    }
+}
+
+impl FromStr for Hash {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "sha1" => Ok(Self::Sha1),
+            "sha256" => Ok(Self::Sha256),
+            _ => Err(anyhow!("Unknown hash {s}, expected sha1 or sha256")),
+        }
+    }
+}
+
+impl fmt::Display for Hash {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Sha1 => write!(f, "SHA-1"),
+            Self::Sha256 => write!(f, "SHA-256"),
+        }
+    }
+}
+
+/// Group and hash both sides have agreed on
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Params {
+    pub bits: u64,
+    pub n: BigInt,
+    pub g: BigInt,
+    pub hash: Hash,
+}
+
+impl Params {
+    /// One of the groups from RFC 5054, by the size of N
+    pub fn new(bits: u64, hash: Hash) -> Result<Self> {
+        let n = match bits {
+            1024 => N_1024,
+            1536 => N_1536,
+            2048 => N_2048,
+            _ => {
+                return Err(anyhow!(
+                    "No {bits}-bit SRP group, expected 1024, 1536 or 2048"
+                ))
+            }
+        };
+        Ok(Self {
+            bits,
+            n: BigInt::from_bytes_be(Sign::Plus, &hex_to_bytes(n)?),
+            g: 2.into(),
+            hash,
+        })
+    }
+
+    /// A challenge's own choice of group and hash, unless overridden by `--arg srp_group=` or
+    /// `--arg srp_hash=`
+    pub fn from_options(bits: u64, hash: Hash) -> Result<Self> {
+        let options = crate::options::get();
+        Self::new(
+            options.arg("srp_group")?.unwrap_or(bits),
+            options.arg("srp_hash")?.unwrap_or(hash),
+        )
+    }
+
+    /// Big-endian bytes of `x`, left-padded with zeros to the length of N
+    pub fn pad(&self, x: &BigInt) -> Vec<u8> {
+        let bytes = x.to_bytes_be().1;
+        let len = self.n.bits().div_ceil(8) as usize;
+        let mut padded = vec![0; len.saturating_sub(bytes.len())];
+        padded.extend_from_slice(&bytes);
+        padded
+    }
+
+    /// The hash, as an integer
+    pub fn h(&self, parts: &[&[u8]]) -> BigInt {
+        BigInt::from_bytes_be(Sign::Plus, &self.hash.digest(parts))
+    }
+
+    /// Multiplier parameter, k = H(N | PAD(g))
+    pub fn k(&self) -> BigInt {
+        self.h(&[&self.n.to_bytes_be().1, &self.pad(&self.g)])
+    }
+
+    /// Private key, x = H(salt | H(I | ":" | P))
+    pub fn x(&self, salt: &[u8], identity: &[u8], password: &[u8]) -> BigInt {
+        let inner = self.hash.digest(&[identity, b":", password]);
+        self.h(&[salt, &inner])
+    }
+
+    /// Password verifier, v = g^x % N
+    pub fn verifier(&self, salt: &[u8], identity: &[u8], password: &[u8]) -> BigInt {
+        self.g.modpow(&self.x(salt, identity, password), &self.n)
+    }
+
+    /// Random scrambling parameter, u = H(PAD(A) | PAD(B))
+    pub fn u(&self, pub_a: &BigInt, pub_b: &BigInt) -> BigInt {
+        self.h(&[&self.pad(pub_a), &self.pad(pub_b)])
+    }
+
+    /// Session key, K = H(S)
+    pub fn key(&self, s: &BigInt) -> Vec<u8> {
+        self.hash.digest(&[&s.to_bytes_be().1])
+    }
+
+    /// What the client sends to prove it has K: HMAC-SHA256(K, salt)
+    pub fn proof(&self, s: &BigInt, salt: &[u8]) -> Vec<u8> {
+        hmac_sha256::HMAC::mac(salt, self.key(s)).to_vec()
+    }
+
+    fn random_private(&self, rng: &mut impl Rng) -> BigInt {
+        rng.gen_bigint_range(&1.into(), &self.n)
+    }
+}
+
+impl fmt::Display for Params {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}-bit group from RFC 5054, with {}",
+            self.bits, self.hash
+        )
+    }
+}
+
+/// The server's side: the salt and verifier saved when the user registered, and an ephemeral key
+pub struct Server {
+    pub params: Params,
+    pub salt: Vec<u8>,
+    v: BigInt,
+    b: BigInt,
+}
+
+impl Server {
+    /// Registers a user, with a fresh random salt
+    pub fn new(params: Params, identity: &[u8], password: &[u8], rng: &mut impl Rng) -> Self {
+        let salt = crate::utils::random_key(16, rng);
+        let v = params.verifier(&salt, identity, password);
+        Self::from_verifier(params, salt, v, rng)
+    }
+
+    /// Picks up a user already registered with `salt` and `v`, with a fresh ephemeral key
+    pub fn from_verifier(params: Params, salt: Vec<u8>, v: BigInt, rng: &mut impl Rng) -> Self {
+        let b = params.random_private(rng);
+        Self::with_private(params, salt, v, b)
+    }
+
+    /// The verifier, to save for the next login
+    pub fn verifier(&self) -> &BigInt {
+        &self.v
+    }
+
+    pub fn with_private(params: Params, salt: Vec<u8>, v: BigInt, b: BigInt) -> Self {
+        Self { params, salt, v, b }
+    }
+
+    /// B = k*v + g^b % N
+    pub fn public(&self) -> BigInt {
+        let Params { n, g, .. } = &self.params;
+        (self.params.k() * &self.v + g.modpow(&self.b, n)) % n
+    }
+
+    /// S = (A * v^u) ^ b % N
+    pub fn secret(&self, pub_a: &BigInt) -> BigInt {
+        let n = &self.params.n;
+        let u = self.params.u(pub_a, &self.public());
+        (pub_a * self.v.modpow(&u, n)).modpow(&self.b, n)
+    }
+
+    /// Whether the client's proof matches the one the server works out for itself
+    pub fn verify(&self, pub_a: &BigInt, proof: &[u8]) -> bool {
+        self.params.proof(&self.secret(pub_a), &self.salt) == proof
+    }
+}
+
+/// The client's side: the user's credentials, and an ephemeral key
+pub struct Client {
+    pub params: Params,
+    identity: Vec<u8>,
+    password: Vec<u8>,
+    a: BigInt,
+}
+
+impl Client {
+    pub fn new(params: Params, identity: &[u8], password: &[u8], rng: &mut impl Rng) -> Self {
+        let a = params.random_private(rng);
+        Self::with_private(params, identity, password, a)
+    }
+
+    pub fn with_private(params: Params, identity: &[u8], password: &[u8], a: BigInt) -> Self {
+        Self {
+            params,
+            identity: identity.to_vec(),
+            password: password.to_vec(),
+            a,
+        }
+    }
+
+    /// A = g^a % N
+    pub fn public(&self) -> BigInt {
+        self.params.g.modpow(&self.a, &self.params.n)
+    }
+
+    /// S = (B - (k * g^x)) ^ (a + (u * x)) % N
+    pub fn secret(&self, salt: &[u8], pub_b: &BigInt) -> BigInt {
+        let Params { n, g, .. } = &self.params;
+        let x = self.params.x(salt, &self.identity, &self.password);
+        let u = self.params.u(&self.public(), pub_b);
+        let base = (pub_b - self.params.k() * g.modpow(&x, n)) % n;
+        // Keep the base positive, for modpow
+        let base = (base + n) % n;
+        base.modpow(&(&self.a + u * x), n)
+    }
+
+    pub fn proof(&self, salt: &[u8], pub_b: &BigInt) -> Vec<u8> {
+        self.params.proof(&self.secret(salt, pub_b), salt)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn int(hex: &str) -> BigInt {
+        BigInt::from_bytes_be(Sign::Plus, &hex_to_bytes(&hex.replace(' ', "")).unwrap())
+    }
+
+    #[test]
+    fn rfc_5054_vectors() {
+        // Appendix B
+        let params = Params::new(1024, Hash::Sha1).unwrap();
+        let (identity, password) = (b"alice", b"password123");
+        let salt = hex_to_bytes("BEB25379D1A8581EB5A727673A2441EE").unwrap();
+        assert_eq!(
+            params.k(),
+            int("7556AA04 5AEF2CDD 07ABAF0F 665C3E81 8913186F")
+        );
+        assert_eq!(
+            params.x(&salt, identity, password),
+            int("94B7555A ABE9127C C58CCF49 93DB6CF8 4D16C124")
+        );
+        let v = params.verifier(&salt, identity, password);
+        assert_eq!(
+            v,
+            int(
+                "7E273DE8 696FFC4F 4E337D05 B4B375BE B0DDE156 9E8FA00A 9886D812 9BADA1F1 \
+                 822223CA 1A605B53 0E379BA4 729FDC59 F105B478 7E5186F5 C671085A 1447B52A \
+                 48CF1970 B4FB6F84 00BBF4CE BFBB1681 52E08AB5 EA53D15C 1AFF87B2 B9DA6E04 \
+                 E058AD51 CC72BFC9 033B564E 26480D78 E955A5E2 9E7AB245 DB2BE315 E2099AFB"
+            )
+        );
+
+        let a = int("60975527 035CF2AD 1989806F 0407210B C81EDC04 E2762A56 AFD529DD DA2D4393");
+        let b = int("E487CB59 D31AC550 471E81F0 0F6928E0 1DDA08E9 74A004F4 9E61F5D1 05284D20");
+        let client = Client::with_private(params.clone(), identity, password, a);
+        let server = Server::with_private(params.clone(), salt.clone(), v, b);
+        let (pub_a, pub_b) = (client.public(), server.public());
+        assert_eq!(
+            pub_a,
+            int(
+                "61D5E490 F6F1B795 47B0704C 436F523D D0E560F0 C64115BB 72557EC4 4352E890 \
+                 3211C046 92272D8B 2D1A5358 A2CF1B6E 0BFCF99F 921530EC 8E393561 79EAE45E \
+                 42BA92AE ACED8251 71E1E8B9 AF6D9C03 E1327F44 BE087EF0 6530E69F 66615261 \
+                 EEF54073 CA11CF58 58F0EDFD FE15EFEA B349EF5D 76988A36 72FAC47B 0769447B"
+            )
+        );
+        assert_eq!(
+            pub_b,
+            int(
+                "BD0C6151 2C692C0C B6D041FA 01BB152D 4916A1E7 7AF46AE1 05393011 BAF38964 \
+                 DC46A067 0DD125B9 5A981652 236F99D9 B681CBF8 7837EC99 6C6DA044 53728610 \
+                 D0C6DDB5 8B318885 D7D82C7F 8DEB75CE 7BD4FBAA 37089E6F 9C6059F3 88838E7A \
+                 00030B33 1EB76840 910440B1 B27AAEAE EB4012B7 D7665238 A8E3FB00 4B117B58"
+            )
+        );
+        assert_eq!(
+            params.u(&pub_a, &pub_b),
+            int("CE38B959 3487DA98 554ED47D 70A7AE5F 462EF019")
+        );
+
+        let premaster = int(
+            "B0DC82BA BCF30674 AE450C02 87745E79 90A3381F 63B387AA F271A10D 233861E3 \
+             59B48220 F7C4693C 9AE12B0A 6F67809F 0876E2D0 13800D6C 41BB59B6 D5979B5C \
+             00A172B4 A2A5903A 0BDCAF8A 709585EB 2AFAFA8F 3499B200 210DCC1F 10EB3394 \
+             3CD67FC8 8A2F39A4 BE5BEC4E C0A3212D C346D7E4 74B29EDE 8A469FFE CA686E5A",
+        );
+        assert_eq!(client.secret(&salt, &pub_b), premaster);
+        assert_eq!(server.secret(&pub_a), premaster);
+    }
+
+    #[test]
+    fn groups() {
+        let mut ctx = openssl::bn::BigNumContext::new().unwrap();
+        for bits in [1024, 1536, 2048] {
+            let params = Params::new(bits, Hash::Sha256).unwrap();
+            assert_eq!(params.n.bits(), bits);
+            // Safe primes, N = 2q + 1
+            for p in [&params.n, &(&params.n >> 1)] {
+                let bn = openssl::bn::BigNum::from_dec_str(&p.to_string()).unwrap();
+                assert!(bn.is_prime(20, &mut ctx).unwrap(), "{bits}");
+            }
+            assert_eq!(params.pad(&params.g).len() as u64, bits / 8);
+        }
+        assert!(Params::new(1000, Hash::Sha1).is_err());
+    }
+
+    #[test]
+    fn login() {
+        let mut rng = rand::thread_rng();
+        for hash in [Hash::Sha1, Hash::Sha256] {
+            let params = Params::new(1024, hash).unwrap();
+            let server = Server::new(params.clone(), b"alice", b"hunter2", &mut rng);
+            let client = Client::new(params.clone(), b"alice", b"hunter2", &mut rng);
+            let proof = client.proof(&server.salt, &server.public());
+            assert!(server.verify(&client.public(), &proof));
+
+            let imposter = Client::new(params, b"alice", b"hunter3", &mut rng);
+            let proof = imposter.proof(&server.salt, &server.public());
+            assert!(!server.verify(&imposter.public(), &proof));
+        }
+    }
+}
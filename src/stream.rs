@@ -1,37 +1,112 @@
-use byteorder::{LittleEndian, WriteBytesExt};
+use std::collections::VecDeque;
 
-use crate::utils::ecb_encrypt;
+use crate::aes::{Aes, BLOCK_SIZE};
+use crate::utils::Mt;
+
+/// A keystream to XOR with, which is both encryption and decryption
+pub trait StreamCipher: Iterator<Item = u8> {
+    /// XORs `data` with the keystream, carrying on from wherever the keystream is up to
+    fn apply_keystream(&mut self, data: &[u8]) -> Vec<u8> {
+        data.iter().zip(self).map(|(v, k)| v ^ k).collect()
+    }
+}
+
+/// Byte order of a CTR counter
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Endian {
+    Little,
+    Big,
+}
+
+/// How CTR lays out each block it encrypts: the nonce, then the counter filling the rest of the
+/// block, wrapping around if it outgrows it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Layout {
+    /// Bytes of nonce at the start of the block
+    pub nonce_len: usize,
+    /// Bytes of counter after it, at most 8
+    pub counter_len: usize,
+    pub endian: Endian,
+    /// The counter for the first block of keystream
+    pub start: u64,
+}
+
+impl Layout {
+    /// The challenges' own CTR: a 64-bit nonce and a 64-bit counter from 0, both little-endian
+    pub const CRYPTOPALS: Layout = Layout {
+        nonce_len: 8,
+        counter_len: 8,
+        endian: Endian::Little,
+        start: 0,
+    };
+
+    /// GCM's: a 96-bit nonce and a 32-bit big-endian counter from 1, the first block of keystream
+    /// masking the tag and the rest encrypting the data
+    pub const GCM: Layout = Layout {
+        nonce_len: 12,
+        counter_len: 4,
+        endian: Endian::Big,
+        start: 1,
+    };
+
+    fn block(&self, nonce: &[u8], counter: u64) -> [u8; BLOCK_SIZE] {
+        let mut block = [0; BLOCK_SIZE];
+        block[..self.nonce_len].copy_from_slice(nonce);
+        let counter = match self.endian {
+            Endian::Little => counter.to_le_bytes()[..self.counter_len].to_vec(),
+            Endian::Big => counter.to_be_bytes()[8 - self.counter_len..].to_vec(),
+        };
+        block[self.nonce_len..].copy_from_slice(&counter);
+        block
+    }
+}
 
 pub struct Ctr {
-    key: Vec<u8>,
-    nonce: u64,
-    byte_buffer: Vec<u8>,
+    aes: Aes,
+    nonce: Vec<u8>,
+    layout: Layout,
+    byte_buffer: [u8; BLOCK_SIZE],
     byte_count: usize,
-    block_size: usize,
 }
 
 impl Ctr {
+    /// The challenges' CTR, under an AES `key`, which panics if it isn't 16, 24 or 32 bytes
     pub fn new(key: &[u8], nonce: u64) -> Ctr {
-        let key: Vec<u8> = key.to_vec();
-        let byte_count = 0;
-        let block_size = 16;
-        let byte_buffer = Vec::with_capacity(block_size);
+        let aes = Aes::new(key).expect("AES key");
+        Self::with_layout(aes, &nonce.to_le_bytes(), Layout::CRYPTOPALS)
+    }
 
+    /// CTR with counter blocks laid out as `layout` says, which `nonce` must fit
+    pub fn with_layout(aes: Aes, nonce: &[u8], layout: Layout) -> Ctr {
+        assert_eq!(
+            layout.nonce_len,
+            nonce.len(),
+            "nonce doesn't fit the layout"
+        );
+        assert!(layout.counter_len <= 8 && layout.nonce_len + layout.counter_len == BLOCK_SIZE);
         Self {
-            key,
-            nonce,
-            byte_count,
-            byte_buffer,
-            block_size,
+            aes,
+            nonce: nonce.to_vec(),
+            layout,
+            byte_buffer: [0; BLOCK_SIZE],
+            byte_count: 0,
+        }
+    }
+
+    /// Jumps to byte `offset` of the keystream
+    pub fn seek(&mut self, offset: usize) {
+        self.byte_count = offset;
+        // The next byte is only generated on the fly at the start of a block
+        if !offset.is_multiple_of(BLOCK_SIZE) {
+            self.update_block();
         }
     }
 
     fn update_block(&mut self) {
-        let block = self.byte_count / self.block_size;
-        let mut input = vec![];
-        input.write_u64::<LittleEndian>(self.nonce).unwrap();
-        input.write_u64::<LittleEndian>(block as u64).unwrap();
-        self.byte_buffer = ecb_encrypt(&input, &self.key, None).unwrap();
+        let block = (self.byte_count / BLOCK_SIZE) as u64;
+        let counter = self.layout.start.wrapping_add(block);
+        self.byte_buffer = self.layout.block(&self.nonce, counter);
+        self.aes.encrypt_block(&mut self.byte_buffer);
     }
 }
 
@@ -39,7 +114,7 @@ impl Iterator for Ctr {
     type Item = u8;
 
     fn next(&mut self) -> Option<Self::Item> {
-        let index = self.byte_count % self.block_size;
+        let index = self.byte_count % BLOCK_SIZE;
         if index == 0 {
             self.update_block();
         }
@@ -48,3 +123,59 @@ impl Iterator for Ctr {
         Some(byte)
     }
 }
+
+impl StreamCipher for Ctr {}
+
+/// The MT19937 stream cipher of challenge 24: a 16-bit key seeds the twister, and each output
+/// gives four bytes of keystream, most significant first
+pub struct MtStream {
+    mt: Mt,
+    localbuffer: VecDeque<u8>,
+}
+
+impl MtStream {
+    pub fn new(seed: u32) -> MtStream {
+        let mt = Mt::seed(seed);
+        let localbuffer = VecDeque::<u8>::new();
+
+        MtStream { mt, localbuffer }
+    }
+}
+
+impl Iterator for MtStream {
+    type Item = u8;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.localbuffer.is_empty() {
+            let word = self.mt.next().unwrap();
+            self.localbuffer.extend(word.to_be_bytes());
+        }
+        self.localbuffer.pop_front()
+    }
+}
+
+impl StreamCipher for MtStream {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn layouts() {
+        let block = Layout::CRYPTOPALS.block(&[1; 8], 0x0102);
+        assert_eq!(block, [1, 1, 1, 1, 1, 1, 1, 1, 2, 1, 0, 0, 0, 0, 0, 0]);
+        let block = Layout::GCM.block(&[1; 12], 0x0102);
+        assert_eq!(block, [1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 0, 0, 1, 2]);
+        // A 32-bit counter wraps
+        assert_eq!(
+            Layout::GCM.block(&[1; 12], 1 << 32),
+            Layout::GCM.block(&[1; 12], 0)
+        );
+
+        // Seeking lands on the same keystream as reading up to there
+        let key = b"YELLOW SUBMARINE";
+        let mut ctr = Ctr::new(key, 3);
+        ctr.seek(37);
+        assert!(ctr.take(20).eq(Ctr::new(key, 3).skip(37).take(20)));
+    }
+}
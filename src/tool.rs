@@ -0,0 +1,214 @@
+//! The primitives from the challenges, one at a time from the command line: converting between hex
+//! and base64, XOR, breaking single-byte XOR, AES in ECB and CBC mode, and the hashes
+//!
+//! Bytes go in and come out as hex, except that base64 is base64, and plaintexts are text with
+//! `--text`. Data left off the command line is read from stdin.
+use std::io::Read;
+
+use anyhow::anyhow;
+
+use crate::aes::{aes_ecb_decrypt, aes_ecb_encrypt};
+use crate::hash::{md4::md4_hash, sha256::sha256};
+use crate::scoring::{Language, NGram, Scorer};
+use crate::utils::*;
+
+/// Everything `tool` can do
+pub const OPERATIONS: [&str; 11] = [
+    "hex-to-b64",
+    "b64-to-hex",
+    "xor",
+    "crack-byte",
+    "ecb-encrypt",
+    "ecb-decrypt",
+    "cbc-encrypt",
+    "cbc-decrypt",
+    "sha1",
+    "sha256",
+    "md4",
+];
+
+/// How `tool` reads and writes bytes
+#[derive(Debug, Default)]
+pub struct Flags {
+    /// Plaintexts, going in or coming out, are text rather than hex
+    pub text: bool,
+    /// Hex IV for CBC [default: all zeros]
+    pub iv: Option<String>,
+}
+
+impl Flags {
+    fn plaintext_in(&self, input: &str) -> Result<Vec<u8>> {
+        match self.text {
+            true => Ok(input.as_bytes().to_vec()),
+            false => hex_to_bytes(input),
+        }
+    }
+
+    fn plaintext_out(&self, bytes: &[u8]) -> String {
+        match self.text {
+            true => String::from_utf8_lossy(bytes).into_owned(),
+            false => bytes_to_hex(bytes),
+        }
+    }
+}
+
+/// Carries out `operation` on `args`, giving what should be printed
+pub fn run(operation: &str, args: &[String], flags: &Flags) -> Result<String> {
+    let key = || {
+        let key = args
+            .first()
+            .ok_or_else(|| anyhow!("{operation} needs a hex key"))?;
+        hex_to_bytes(key)
+    };
+    match operation {
+        "hex-to-b64" => Ok(bytes_to_b64_str(&hex_to_bytes(&input(args.first())?)?)),
+        "b64-to-hex" => Ok(bytes_to_hex(&decode_b64_str(&input(args.first())?)?)),
+        "xor" => {
+            let data = flags.plaintext_in(&input(args.get(1))?)?;
+            Ok(flags.plaintext_out(&xor_bytes(&data, &key()?)))
+        }
+        "crack-byte" => {
+            let data = hex_to_bytes(&input(args.first())?)?;
+            let scorer = Scorer::new(&Language::English, NGram::Trigram)?;
+            let best = crack_single_xor(&data, &scorer, 1)
+                .into_iter()
+                .next()
+                .ok_or_else(|| anyhow!("Nothing to crack"))?;
+            Ok(format!(
+                "Key: {:02x}\n{}",
+                best.key,
+                String::from_utf8_lossy(&best.plaintext)
+            ))
+        }
+        "ecb-encrypt" => {
+            let data = flags.plaintext_in(&input(args.get(1))?)?;
+            Ok(bytes_to_hex(&aes_ecb_encrypt(
+                &pkcs7_pad(&data, 16),
+                &key()?,
+            )?))
+        }
+        "ecb-decrypt" => {
+            let data = hex_to_bytes(&input(args.get(1))?)?;
+            let plaintext = pkcs7_unpad(&aes_ecb_decrypt(&data, &key()?)?)?;
+            Ok(flags.plaintext_out(&plaintext))
+        }
+        "cbc-encrypt" => {
+            let data = flags.plaintext_in(&input(args.get(1))?)?;
+            let iv = iv(flags)?;
+            let ciphertext = cbc_encrypt(&pkcs7_pad(&data, 16), &key()?, iv.as_deref())?;
+            Ok(bytes_to_hex(&ciphertext))
+        }
+        "cbc-decrypt" => {
+            let data = hex_to_bytes(&input(args.get(1))?)?;
+            let iv = iv(flags)?;
+            let plaintext = pkcs7_unpad(&cbc_decrypt(&data, &key()?, iv.as_deref())?)?;
+            Ok(flags.plaintext_out(&plaintext))
+        }
+        "sha1" => {
+            let data = flags.plaintext_in(&input(args.first())?)?;
+            Ok(bytes_to_hex(&Sha1Hasher::default().hash(&data, None)))
+        }
+        "sha256" => Ok(bytes_to_hex(&sha256(
+            &flags.plaintext_in(&input(args.first())?)?,
+        ))),
+        "md4" => Ok(md4_hash(&flags.plaintext_in(&input(args.first())?)?)),
+        other => Err(anyhow!(
+            "No operation called {other}, try one of: {}",
+            OPERATIONS.join(", ")
+        )),
+    }
+}
+
+/// The argument if there was one, or else stdin, without the trailing newline either way
+fn input(arg: Option<&String>) -> Result<String> {
+    let input = match arg {
+        Some(arg) => arg.clone(),
+        None => {
+            let mut input = String::new();
+            std::io::stdin().read_to_string(&mut input)?;
+            input
+        }
+    };
+    Ok(input.trim_end_matches(['\r', '\n']).to_string())
+}
+
+fn iv(flags: &Flags) -> Result<Option<Vec<u8>>> {
+    let iv = flags.iv.as_deref().map(hex_to_bytes).transpose()?;
+    match iv {
+        Some(ref iv) if iv.len() != 16 => Err(anyhow!("The IV must be 16 bytes, not {}", iv.len())),
+        _ => Ok(iv),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tool(operation: &str, args: &[&str], flags: &Flags) -> String {
+        let args: Vec<String> = args.iter().map(|a| a.to_string()).collect();
+        run(operation, &args, flags).unwrap()
+    }
+
+    #[test]
+    fn conversions() {
+        let hex = "49276d206b696c6c696e6720796f757220627261696e206c696b65206120706f69736f6e6f7573206d757368726f6f6d";
+        let b64 = "SSdtIGtpbGxpbmcgeW91ciBicmFpbiBsaWtlIGEgcG9pc29ub3VzIG11c2hyb29t";
+        let hex_flags = Flags::default();
+        assert_eq!(tool("hex-to-b64", &[hex], &hex_flags), b64);
+        assert_eq!(tool("b64-to-hex", &[b64], &hex_flags), hex);
+    }
+
+    #[test]
+    fn operations() {
+        let hex_flags = Flags::default();
+        let text_flags = Flags {
+            text: true,
+            iv: Some("000102030405060708090a0b0c0d0e0f".to_string()),
+        };
+        assert_eq!(
+            tool(
+                "xor",
+                &[
+                    "686974207468652062756c6c277320657965",
+                    "1c0111001f010100061a024b53535009181c"
+                ],
+                &hex_flags
+            ),
+            "746865206b696420646f6e277420706c6179"
+        );
+        assert_eq!(
+            tool(
+                "crack-byte",
+                &["1b37373331363f78151b7f2b783431333d78397828372d363c78373e783a393b3736"],
+                &hex_flags
+            ),
+            "Key: 58\nCooking MC's like a pound of bacon"
+        );
+
+        let key = "59454c4c4f57205355424d4152494e45";
+        for mode in ["ecb", "cbc"] {
+            let ciphertext = tool(
+                &format!("{mode}-encrypt"),
+                &[key, "attack at dawn"],
+                &text_flags,
+            );
+            assert_eq!(ciphertext.len(), 32);
+            let plaintext = tool(&format!("{mode}-decrypt"), &[key, &ciphertext], &text_flags);
+            assert_eq!(plaintext, "attack at dawn");
+        }
+
+        assert_eq!(
+            tool("sha1", &["abc"], &text_flags),
+            "a9993e364706816aba3e25717850c26c9cd0d89d"
+        );
+        assert_eq!(
+            tool("sha256", &["616263"], &hex_flags),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        assert_eq!(
+            tool("md4", &["abc"], &text_flags),
+            "a448017aaf21d8525fc10ae87aa6729d"
+        );
+        assert!(run("rot13", &[], &hex_flags).is_err());
+    }
+}
@@ -0,0 +1,319 @@
+//! Levelled, structured logging for the long attacks, along the lines of `tracing`
+//!
+//! [`event!`] writes a message with `name = value` fields to stderr, and [`span!`] marks out a
+//! phase of an attack: events inside it are prefixed with its name, and it logs how long it took
+//! when it's dropped. Both are filtered by level and module with `CRYPTOPALS_LOG`, which takes the
+//! same shape as `RUST_LOG`, e.g. `CRYPTOPALS_LOG=info,set8::challenge60=trace`, or more simply
+//! with `-v` (info), `-vv` (debug) or `-vvv` (trace) for every module. Nothing is logged without
+//! one or the other, and the fields of disabled events aren't even formatted.
+#![allow(dead_code)]
+
+use std::cell::RefCell;
+use std::fmt::{self, Display, Write};
+use std::str::FromStr;
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+
+use anyhow::anyhow;
+
+use crate::utils::Result;
+
+/// The environment variable the filter is read from
+pub const ENV: &str = "CRYPTOPALS_LOG";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    Error,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+impl FromStr for Level {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "error" => Ok(Self::Error),
+            "warn" => Ok(Self::Warn),
+            "info" => Ok(Self::Info),
+            "debug" => Ok(Self::Debug),
+            "trace" => Ok(Self::Trace),
+            _ => Err(anyhow!(
+                "Unknown log level {s}, expected error, warn, info, debug, trace or off"
+            )),
+        }
+    }
+}
+
+impl Display for Level {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::Error => "ERROR",
+            Self::Warn => "WARN",
+            Self::Info => "INFO",
+            Self::Debug => "DEBUG",
+            Self::Trace => "TRACE",
+        };
+        write!(f, "{name:>5}")
+    }
+}
+
+/// Which events get logged: the most verbose level for each module, falling back to a default
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Filter {
+    /// For modules no directive mentions, where `None` is off
+    default: Option<Level>,
+    /// Module paths within the crate, like `set8::challenge60`, and their levels
+    directives: Vec<(String, Option<Level>)>,
+}
+
+impl Filter {
+    /// Everything up to `level`, in every module
+    pub fn new(level: Option<Level>) -> Self {
+        Self {
+            default: level,
+            directives: vec![],
+        }
+    }
+
+    /// Whether an event at `level` from the module at `target` (from `module_path!`) gets logged
+    pub fn enabled(&self, level: Level, target: &str) -> bool {
+        let target = relative(target);
+        self.directives
+            .iter()
+            .filter(|(module, _)| {
+                target == module
+                    || target
+                        .strip_prefix(module.as_str())
+                        .is_some_and(|rest| rest.starts_with("::"))
+            })
+            .max_by_key(|(module, _)| module.len())
+            .map_or(self.default, |(_, max)| *max)
+            .is_some_and(|max| level <= max)
+    }
+}
+
+/// Comma-separated directives, each a level (`off` for none) for every module, or `module=level`
+impl FromStr for Filter {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let level = |l: &str| match l {
+            "off" => Ok(None),
+            l => l.parse().map(Some),
+        };
+        let mut filter = Self::default();
+        for directive in s.split(',').map(str::trim).filter(|d| !d.is_empty()) {
+            match directive.split_once('=') {
+                Some((module, l)) => {
+                    let module = module.trim_start_matches("cryptopals::");
+                    filter.directives.push((module.to_string(), level(l)?));
+                }
+                None => filter.default = level(directive)?,
+            }
+        }
+        Ok(filter)
+    }
+}
+
+/// The module path without the crate's name
+fn relative(target: &str) -> &str {
+    target.split_once("::").map_or("", |(_, rest)| rest)
+}
+
+static FILTER: OnceLock<Filter> = OnceLock::new();
+
+/// Sets the filter for the rest of the run instead of reading `CRYPTOPALS_LOG`, only the first
+/// call has any effect
+pub fn set_filter(filter: Filter) {
+    let _ = FILTER.set(filter);
+}
+
+/// Sets the filter from the number of `-v`s, unless there were none or `CRYPTOPALS_LOG` is set,
+/// as that can say more
+pub fn set_verbosity(verbose: u8) {
+    let level = match verbose {
+        0 => return,
+        1 => Level::Info,
+        2 => Level::Debug,
+        _ => Level::Trace,
+    };
+    if std::env::var_os(ENV).is_none() {
+        set_filter(Filter::new(Some(level)));
+    }
+}
+
+/// The filter for this run, from `CRYPTOPALS_LOG` unless one was set, and warning (once) if that
+/// doesn't parse
+pub fn filter() -> &'static Filter {
+    FILTER.get_or_init(|| match std::env::var(ENV) {
+        Ok(s) => s.parse().unwrap_or_else(|e| {
+            eprintln!("Ignoring {ENV}: {e}");
+            Filter::default()
+        }),
+        Err(_) => Filter::default(),
+    })
+}
+
+pub fn enabled(level: Level, target: &str) -> bool {
+    filter().enabled(level, target)
+}
+
+thread_local! {
+    /// The spans entered on this thread, innermost last, as they're written out
+    static SPANS: RefCell<Vec<String>> = const { RefCell::new(Vec::new()) };
+}
+
+/// `name`, and the fields in braces if there are any
+fn with_fields(name: &str, fields: &[(&str, &dyn Display)]) -> String {
+    let mut s = name.to_string();
+    if !fields.is_empty() {
+        let fields = fields.iter().map(|(k, v)| format!("{k}={v}"));
+        write!(s, "{{{}}}", fields.collect::<Vec<_>>().join(" ")).unwrap();
+    }
+    s
+}
+
+/// An event as it's written out, under the spans it's in
+fn line(
+    level: Level,
+    target: &str,
+    spans: &[String],
+    message: &str,
+    fields: &[(&str, &dyn Display)],
+) -> String {
+    let mut line = format!("{level} {}:", relative(target));
+    for span in spans {
+        write!(line, " {span}:").unwrap();
+    }
+    write!(line, " {message}").unwrap();
+    for (k, v) in fields {
+        write!(line, " {k}={v}").unwrap();
+    }
+    line
+}
+
+/// Writes out an event, which [`event!`] only calls once it knows it's enabled
+pub fn emit(level: Level, target: &str, message: &str, fields: &[(&str, &dyn Display)]) {
+    let line = SPANS.with(|spans| line(level, target, &spans.borrow(), message, fields));
+    eprintln!("{line}");
+}
+
+/// A phase of an attack, from [`span!`] to when this is dropped
+#[must_use = "the span ends as soon as this is dropped"]
+pub struct Span {
+    /// Where the span was entered, if it's enabled
+    entered: Option<(Level, &'static str, Instant)>,
+}
+
+impl Span {
+    /// A span at `level` in `target`, entered if that's enabled
+    pub fn enter(
+        level: Level,
+        target: &'static str,
+        name: &str,
+        fields: &[(&str, &dyn Display)],
+    ) -> Self {
+        if !enabled(level, target) {
+            return Self { entered: None };
+        }
+        SPANS.with(|spans| spans.borrow_mut().push(with_fields(name, fields)));
+        Self {
+            entered: Some((level, target, Instant::now())),
+        }
+    }
+
+    /// How long since the span was entered, if it was
+    pub fn elapsed(&self) -> Option<Duration> {
+        self.entered.map(|(_, _, started)| started.elapsed())
+    }
+}
+
+impl Drop for Span {
+    fn drop(&mut self) {
+        if let Some((level, target, started)) = self.entered {
+            let elapsed = format!("{:.3?}", started.elapsed());
+            emit(level, target, "done", &[("elapsed", &elapsed)]);
+            SPANS.with(|spans| spans.borrow_mut().pop());
+        }
+    }
+}
+
+/// Logs a message with fields at a [`Level`], if the filter lets it through
+///
+/// ```ignore
+/// event!(Debug, "residue recovered", r = r, x = x);
+/// ```
+macro_rules! event {
+    ($level:ident, $message:expr $(, $key:ident = $value:expr)* $(,)?) => {
+        if $crate::trace::enabled($crate::trace::Level::$level, module_path!()) {
+            $crate::trace::emit(
+                $crate::trace::Level::$level,
+                module_path!(),
+                &$message,
+                &[$((stringify!($key), &$value as &dyn std::fmt::Display)),*],
+            );
+        }
+    };
+}
+
+/// Enters a [`Span`] which lasts until the value it returns is dropped
+///
+/// ```ignore
+/// let _span = span!(Info, "kangaroo", bits = b.bits());
+/// ```
+macro_rules! span {
+    ($level:ident, $name:expr $(, $key:ident = $value:expr)* $(,)?) => {
+        $crate::trace::Span::enter(
+            $crate::trace::Level::$level,
+            module_path!(),
+            &$name,
+            &[$((stringify!($key), &$value as &dyn std::fmt::Display)),*],
+        )
+    };
+}
+
+pub(crate) use {event, span};
+
+#[cfg(test)]
+mod tests {
+    use num_bigint::BigInt;
+
+    use super::*;
+
+    #[test]
+    fn filter() {
+        let filter: Filter = "info, set8::challenge60=trace,set8=off".parse().unwrap();
+        assert!(filter.enabled(Level::Info, "cryptopals::set1::challenge01"));
+        assert!(!filter.enabled(Level::Debug, "cryptopals::set1::challenge01"));
+        assert!(!filter.enabled(Level::Error, "cryptopals::set8::challenge59"));
+        assert!(filter.enabled(Level::Trace, "cryptopals::set8::challenge60"));
+        // Only whole path segments match, so set8=off doesn't cover this
+        assert!(filter.enabled(Level::Info, "cryptopals::set8x"));
+        assert!(!Filter::default().enabled(Level::Error, "cryptopals::set1"));
+        assert!("loud".parse::<Filter>().is_err());
+        assert!("set8=".parse::<Filter>().is_err());
+    }
+
+    #[test]
+    fn lines() {
+        let x = BigInt::from(12345);
+        let spans = [with_fields("kangaroo", &[("bits", &40)])];
+        assert_eq!(
+            line(
+                Level::Debug,
+                "cryptopals::set8::challenge58",
+                &spans,
+                "trap set",
+                &[("jumps", &7), ("x", &x)]
+            ),
+            "DEBUG set8::challenge58: kangaroo{bits=40}: trap set jumps=7 x=12345"
+        );
+        assert_eq!(
+            line(Level::Info, "cryptopals::set8", &[], "done", &[]),
+            " INFO set8: done"
+        );
+    }
+}
@@ -1,21 +1,71 @@
 #![allow(dead_code)]
-use std::hash::Hash;
+use std::cell::RefCell;
+use std::collections::BTreeMap;
 use std::io::BufRead;
-use std::{collections::HashMap, fs::File, io::BufReader};
+use std::ops::Range;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+use std::{fs::File, io::BufReader};
 
 // Re-export useful functions introduced in specific challenges
+pub use crate::mt::Mt;
+pub use crate::report::Report;
+pub use crate::set1::challenge06::crack_repeating_xor;
 pub use crate::set1::challenge08::is_unique;
 pub use crate::set2::challenge09::pkcs7_pad;
 pub use crate::set2::challenge10::{cbc_decrypt, cbc_encrypt};
 pub use crate::set2::challenge10::{ecb_decrypt, ecb_encrypt};
 pub use crate::set2::challenge11::{random_bytes, random_key, Mode};
+pub use crate::set2::challenge12::{byte_at_a_time, discover_block_size, EncryptionOracle};
 pub use crate::set2::challenge13::{pkcs7_unpad, PaddingError};
-pub use crate::set3::challenge21::Mt;
-pub use crate::set4::challenge28::{authenticate, u32_to_u8s, u8s_to_u32, Auth, Sha1Hasher};
-pub use crate::set5::challenge39::{et_n, invmod, rsa_decrypt, rsa_encrypt};
+pub use crate::set4::challenge28::{authenticate, Auth, Sha1Hasher};
+pub use crate::set5::challenge39::{rsa_decrypt, rsa_encrypt, try_invmod};
+pub use crate::set6::challenge47::{pkcs1v15_pad, pkcs1v15_unpad, Pkcs1Mode};
 
 pub use anyhow::Result;
-use base64::{engine::general_purpose, Engine as _};
+
+use crate::report::say;
+use crate::scoring::Scorer;
+use anyhow::anyhow;
+use base64::engine::{general_purpose, DecodePaddingMode, GeneralPurpose};
+use base64::{alphabet, read::DecoderReader, write::EncoderWriter, Engine as _};
+use num_bigint::BigInt;
+use num_traits::Zero;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use rayon::prelude::*;
+use std::io::{self, Read, Write};
+
+thread_local! {
+    /// Where [`rng`] gets its generators from inside [`seeded`], one per thread so that challenges
+    /// running side by side don't take each other's
+    static SEEDED: RefCell<Option<StdRng>> = const { RefCell::new(None) };
+}
+
+/// The generator a challenge draws all its randomness from, passed down to everything that needs
+/// some, so that a seeded one makes a whole run repeatable
+///
+/// Inside [`seeded`], each one is seeded in turn from the seed given there, so a challenge which
+/// asks for them in the same order gets the same numbers every time.
+pub fn rng() -> StdRng {
+    SEEDED.with_borrow_mut(|seeded| match seeded.as_mut() {
+        Some(seeded) => StdRng::from_rng(seeded).unwrap(),
+        None => StdRng::from_entropy(),
+    })
+}
+
+/// Runs `f` with [`rng`] handing out generators derived from `seed`, as the runner does for
+/// `--seed`, and puts back whatever was there before, even if `f` panics
+pub fn seeded<T>(seed: u64, f: impl FnOnce() -> T) -> T {
+    struct Restore(Option<StdRng>);
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            SEEDED.set(self.0.take());
+        }
+    }
+    let _restore = Restore(SEEDED.replace(Some(StdRng::seed_from_u64(seed))));
+    f()
+}
 
 pub fn hex_to_bytes(input: &str) -> Result<Vec<u8>> {
     Ok(hex::decode(input)?)
@@ -29,50 +79,259 @@ pub fn bytes_to_b64_str(input: &[u8]) -> String {
     general_purpose::STANDARD.encode(input)
 }
 
-pub fn freq_map_from_file(filename: &str) -> Result<HashMap<char, f64>> {
-    let mut map = HashMap::new();
-    let f = File::open(filename)?;
-    let reader = BufReader::new(f);
-    for line in reader.lines() {
-        for c in line?.as_str().chars() {
-            if c.is_alphabetic() {
-                map.entry(c).and_modify(|v| *v += 1.0).or_insert(1.0);
+/// How close (relative to the best score) the runner-up has to be for a guess to be ambiguous
+const AMBIGUITY_MARGIN: f64 = 0.05;
+
+/// A guess at a single-byte XOR key, along with the plaintext it produces
+#[derive(Debug, Clone, PartialEq)]
+pub struct Candidate {
+    pub key: u8,
+    pub score: f64,
+    pub plaintext: Vec<u8>,
+}
+
+/// Returns the `n` most plausible single-byte keys according to `scorer`, best first
+pub fn crack_single_xor(input_bytes: &[u8], scorer: &Scorer, n: usize) -> Vec<Candidate> {
+    let mut candidates: Vec<Candidate> = (0..=255_u8)
+        .map(|key| {
+            let plaintext = xor_bytes(input_bytes, &[key]);
+            Candidate {
+                key,
+                score: scorer.score(&plaintext),
+                plaintext,
             }
-        }
+        })
+        .collect();
+    candidates.sort_by(|a, b| a.score.total_cmp(&b.score));
+    candidates.truncate(n);
+    candidates
+}
+
+/// Whether the runner-up candidate scores close enough to the best to be a real contender
+pub fn is_ambiguous(candidates: &[Candidate]) -> bool {
+    match candidates {
+        [best, second, ..] => second.score - best.score < AMBIGUITY_MARGIN * best.score.abs(),
+        _ => false,
     }
-    let sum: f64 = map.values().sum();
-    for val in map.values_mut() {
-        *val /= sum;
+}
+
+/// Prints the candidates, e.g. so a human can pick when the best guess is ambiguous
+pub fn print_candidates(candidates: &[Candidate]) {
+    for c in candidates {
+        say!(
+            "{:>3} ({:.3}): {:?}",
+            c.key,
+            c.score,
+            String::from_utf8_lossy(&c.plaintext)
+        );
     }
+}
 
-    Ok(map)
+/// Finds the single-byte key which produces the most plausible plaintext according to `scorer`
+pub fn crack_single_byte_xor(input_bytes: &[u8], scorer: &Scorer) -> Result<u8> {
+    crack_single_xor(input_bytes, scorer, 1)
+        .first()
+        .map(|c| c.key)
+        .ok_or_else(|| anyhow::anyhow!("No candidate keys"))
 }
-pub fn crack_single_byte_xor(input_bytes: &[u8], ref_map: &HashMap<char, f64>) -> Result<u8> {
-    let mut scores = HashMap::new();
-    for x in 0..255_u8 {
-        let xored = xor_bytes(input_bytes, &[x]);
-        // If it can be decoed, then work with it
-        if let Ok(xor_str) = std::str::from_utf8(&xored) {
-            let actual_freq_map = freq_map_from_str(xor_str)?;
-            let score = kl_divergence(&actual_freq_map, ref_map);
-            //println!("Score: {} {xor_str}", score);
-            scores.insert(x, score);
+pub fn decode_b64_str(input: &str) -> Result<Vec<u8>> {
+    let res = general_purpose::STANDARD.decode(input)?;
+    Ok(res)
+}
+
+/// URL-safe base64 which takes its input with or without padding
+const URL_SAFE_ANY_PAD: GeneralPurpose = GeneralPurpose::new(
+    &alphabet::URL_SAFE,
+    general_purpose::NO_PAD.with_decode_padding_mode(DecodePaddingMode::Indifferent),
+);
+
+/// Base64 with '-' and '_' in place of '+' and '/', optionally leaving off the '=' padding
+pub fn bytes_to_b64_url(input: &[u8], padded: bool) -> String {
+    match padded {
+        true => general_purpose::URL_SAFE.encode(input),
+        false => general_purpose::URL_SAFE_NO_PAD.encode(input),
+    }
+}
+
+/// Decodes URL-safe base64, whether or not it's padded
+pub fn b64_url_to_bytes(input: &str) -> Result<Vec<u8>> {
+    Ok(URL_SAFE_ANY_PAD.decode(input)?)
+}
+
+/// Bitcoin's base58 alphabet, which leaves out 0, O, I and l so they can't be mixed up
+const BASE58_ALPHABET: &[u8; 58] = b"123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+
+/// Encodes as one big base58 number, with a '1' for each leading zero byte
+pub fn bytes_to_base58(input: &[u8]) -> String {
+    let zeros = input.iter().take_while(|b| **b == 0).count();
+    // Least significant digit first, multiplying in a byte at a time
+    let mut digits: Vec<u8> = vec![];
+    for b in &input[zeros..] {
+        let mut carry = *b as u32;
+        for d in digits.iter_mut() {
+            carry += (*d as u32) << 8;
+            *d = (carry % 58) as u8;
+            carry /= 58;
+        }
+        while carry > 0 {
+            digits.push((carry % 58) as u8);
+            carry /= 58;
         }
     }
+    std::iter::repeat_n('1', zeros)
+        .chain(
+            digits
+                .iter()
+                .rev()
+                .map(|d| BASE58_ALPHABET[*d as usize] as char),
+        )
+        .collect()
+}
 
-    let best_score =
-        scores
+pub fn base58_to_bytes(input: &str) -> Result<Vec<u8>> {
+    let zeros = input.bytes().take_while(|c| *c == b'1').count();
+    // Least significant byte first, multiplying in a digit at a time
+    let mut bytes: Vec<u8> = vec![];
+    for c in input.chars().skip(zeros) {
+        let digit = BASE58_ALPHABET
             .iter()
-            .fold((0_u8, 1000.0), |acc, x| match *x.1 < acc.1 && *x.1 != 0.0 {
-                true => (*x.0, *x.1),
-                false => acc,
-            });
-    let b = best_score.0;
-    Ok(b)
+            .position(|a| *a as char == c)
+            .ok_or_else(|| anyhow!("Invalid base58 character {c:?}"))?;
+        let mut carry = digit as u32;
+        for b in bytes.iter_mut() {
+            carry += *b as u32 * 58;
+            *b = carry as u8;
+            carry >>= 8;
+        }
+        while carry > 0 {
+            bytes.push(carry as u8);
+            carry >>= 8;
+        }
+    }
+    bytes.resize(bytes.len() + zeros, 0);
+    bytes.reverse();
+    Ok(bytes)
 }
-pub fn decode_b64_str(input: &str) -> Result<Vec<u8>> {
-    let res = general_purpose::STANDARD.decode(input)?;
-    Ok(res)
+
+fn invalid_data(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// Reads hex from the inner reader, ignoring whitespace, and hands out the bytes it encodes
+pub struct HexReader<R> {
+    inner: R,
+    /// High nibble of a byte whose low nibble hasn't turned up yet
+    pending: Option<u8>,
+}
+
+impl<R: Read> HexReader<R> {
+    pub fn new(inner: R) -> Self {
+        Self {
+            inner,
+            pending: None,
+        }
+    }
+}
+
+impl<R: Read> Read for HexReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        let mut hex = [0; 4096];
+        // Two characters a byte, so that even with a nibble pending this can't overfill `buf`
+        let want = (2 * buf.len()).min(hex.len());
+        loop {
+            let n = self.inner.read(&mut hex[..want])?;
+            if n == 0 {
+                return match self.pending {
+                    Some(_) => Err(invalid_data("Odd number of hex digits".into())),
+                    None => Ok(0),
+                };
+            }
+            let mut written = 0;
+            for c in hex[..n].iter().filter(|c| !c.is_ascii_whitespace()) {
+                let nibble = (*c as char)
+                    .to_digit(16)
+                    .ok_or_else(|| invalid_data(format!("Invalid hex digit {:?}", *c as char)))?
+                    as u8;
+                match self.pending.take() {
+                    Some(high) => {
+                        buf[written] = high << 4 | nibble;
+                        written += 1;
+                    }
+                    None => self.pending = Some(nibble),
+                }
+            }
+            // Nothing but whitespace, or a lone nibble, so go round again rather than look like EOF
+            if written > 0 {
+                return Ok(written);
+            }
+        }
+    }
+}
+
+/// Writes everything written to it out to the inner writer as lowercase hex
+pub struct HexWriter<W> {
+    inner: W,
+}
+
+impl<W: Write> HexWriter<W> {
+    pub fn new(inner: W) -> Self {
+        Self { inner }
+    }
+
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+impl<W: Write> Write for HexWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.inner.write_all(hex::encode(buf).as_bytes())?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Drops whitespace from the inner reader, e.g. the line breaks in a base64 file
+struct SkipWhitespace<R>(R);
+
+impl<R: Read> Read for SkipWhitespace<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            let n = self.0.read(buf)?;
+            if n == 0 {
+                return Ok(0);
+            }
+            let mut kept = 0;
+            for i in 0..n {
+                if !buf[i].is_ascii_whitespace() {
+                    buf[kept] = buf[i];
+                    kept += 1;
+                }
+            }
+            if kept > 0 {
+                return Ok(kept);
+            }
+        }
+    }
+}
+
+/// Decodes standard base64 from `reader` as it's read, ignoring line breaks
+pub fn base64_reader<R: Read>(reader: R) -> impl Read {
+    DecoderReader::new(SkipWhitespace(reader), &general_purpose::STANDARD)
+}
+
+/// Encodes everything written as standard base64 into `writer`
+///
+/// The last few bytes only go out with [`EncoderWriter::finish`] (or when it's dropped, ignoring
+/// any error)
+pub fn base64_writer<W: Write>(writer: W) -> EncoderWriter<'static, GeneralPurpose, W> {
+    EncoderWriter::new(writer, &general_purpose::STANDARD)
 }
 
 pub fn read_base64_lines(filename: &str) -> Result<Vec<Vec<u8>>> {
@@ -111,48 +370,38 @@ pub fn read_file(filename: &str) -> Result<Vec<String>> {
     Ok(v)
 }
 
-pub fn freq_map_from_str(input: &str) -> Result<HashMap<char, f64>> {
-    let mut map = HashMap::new();
-    for c in input.chars() {
-        if c.is_alphabetic() {
-            map.entry(c).and_modify(|v| *v += 1.0).or_insert(1.0);
-        }
-    }
-    let sum: f64 = map.values().sum();
-    for val in map.values_mut() {
-        *val /= sum;
+/// XORs `a` with `x`, cycling `x` if it's shorter
+///
+/// Works a u64 at a time, as this gets called a great many times by the statistical attacks
+pub fn xor_bytes(a: &[u8], x: &[u8]) -> Vec<u8> {
+    if x.is_empty() {
+        return vec![];
     }
+    // Lay out enough of the cycled key that every lane of `a` lines up with a lane of it
+    let period = match x.len() >= a.len() {
+        true => a.len(),
+        false => num_integer::lcm(x.len(), 8),
+    };
+    let keystream: Vec<u8> = x.iter().cycle().take(period).copied().collect();
 
-    Ok(map)
-}
-
-pub fn kl_divergence<T: Eq + Hash>(p: &HashMap<T, f64>, q: &HashMap<T, f64>) -> f64 {
-    q.keys()
-        .map(|k| {
-            let px = p.get(k);
-            let qx = q.get(k).unwrap();
-            match px {
-                // Penalise a missing value, but not too much
-                None => 0.1,
-                Some(px_val) => *px_val * ((*px_val + 0.01) / (*qx + 0.01)).ln(),
-            }
-        })
-        .sum()
+    let mut output = Vec::with_capacity(a.len());
+    let lanes = a.chunks_exact(8);
+    let remainder = lanes.remainder();
+    for (i, lane) in lanes.enumerate() {
+        let offset = (8 * i) % period;
+        let k = u64::from_ne_bytes(keystream[offset..offset + 8].try_into().unwrap());
+        let v = u64::from_ne_bytes(lane.try_into().unwrap());
+        output.extend_from_slice(&(v ^ k).to_ne_bytes());
+    }
+    let start = a.len() - remainder.len();
+    for (i, v) in remainder.iter().enumerate() {
+        output.push(v ^ keystream[(start + i) % period]);
+    }
+    output
 }
 
-pub fn xor_bytes(a: &[u8], x: &[u8]) -> Vec<u8> {
-    // Cycle x if possible
-    std::iter::zip(a, x.iter().cycle())
-        .map(|(&x, &y)| x ^ y)
-        .collect::<Vec<u8>>()
-}
 pub fn ones(x: u8) -> u64 {
-    (0..8)
-        .map(|mask_shift| match x & (1 << mask_shift) {
-            0 => 0,
-            _ => 1,
-        })
-        .sum()
+    x.count_ones() as u64
 }
 
 pub fn hamming(str1: &str, str2: &str) -> u64 {
@@ -161,17 +410,343 @@ pub fn hamming(str1: &str, str2: &str) -> u64 {
     hamming_bytes(s1b, s2b)
 }
 
+/// Number of differing bits between the two, up to the length of the shorter one
 pub fn hamming_bytes(b1: &[u8], b2: &[u8]) -> u64 {
-    std::iter::zip(b1.iter(), b2.iter())
-        .map(|(x, y)| x ^ y)
-        .map(ones)
-        .sum()
+    let l = b1.len().min(b2.len());
+    let lanes1 = b1[..l].chunks_exact(16);
+    let lanes2 = b2[..l].chunks_exact(16);
+    let tail: u64 = std::iter::zip(lanes1.remainder(), lanes2.remainder())
+        .map(|(x, y)| ones(x ^ y))
+        .sum();
+    std::iter::zip(lanes1, lanes2)
+        .map(|(x, y)| {
+            let x = u128::from_ne_bytes(x.try_into().unwrap());
+            let y = u128::from_ne_bytes(y.try_into().unwrap());
+            (x ^ y).count_ones() as u64
+        })
+        .sum::<u64>()
+        + tail
+}
+
+/// Chinese remainder theorem: x mod N = n_0 n_1 ..., along with N, from (n_i, x mod n_i) for
+/// each modulus, or None if the moduli aren't pairwise coprime
+pub fn crt(residues: &[(BigInt, BigInt)]) -> Option<(BigInt, BigInt)> {
+    let product: BigInt = residues.iter().map(|(n, _)| n).product();
+    let mut result = BigInt::zero();
+    for (n, x) in residues {
+        let ms = &product / n;
+        result += x * &ms * try_invmod(&ms, n)?;
+    }
+    Some((result % &product, product))
+}
+
+/// How a brute force search went
+#[derive(Debug, Clone, Copy)]
+pub struct SearchStats {
+    pub tried: u64,
+    pub elapsed: Duration,
+}
+
+impl SearchStats {
+    /// Candidates tried per second
+    pub fn rate(&self) -> f64 {
+        self.tried as f64 / self.elapsed.as_secs_f64()
+    }
+}
+
+impl std::fmt::Display for SearchStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Tried {} candidates in {:.2?} ({:.0}/s)",
+            self.tried,
+            self.elapsed,
+            self.rate()
+        )
+    }
+}
+
+/// Searches `range` in parallel for a value for which `found` holds, stopping as soon as any turns
+/// up. If there's more than one, there's no telling which will be returned.
+pub fn par_search<F>(range: Range<u64>, found: F) -> (Option<u64>, SearchStats)
+where
+    F: Fn(u64) -> bool + Sync,
+{
+    let start = Instant::now();
+    let tried = AtomicU64::new(0);
+    let result = range.into_par_iter().find_any(|x| {
+        tried.fetch_add(1, Ordering::Relaxed);
+        found(*x)
+    });
+    let stats = SearchStats {
+        tried: tried.into_inner(),
+        elapsed: start.elapsed(),
+    };
+    (result, stats)
+}
+
+/// A closed interval of integers, [start, end], empty if start > end
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Interval {
+    pub start: BigInt,
+    pub end: BigInt,
+}
+
+impl Interval {
+    pub fn new(start: &BigInt, end: &BigInt) -> Self {
+        Self {
+            start: start.clone(),
+            end: end.clone(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.start > self.end
+    }
+
+    /// How many integers it covers
+    pub fn measure(&self) -> BigInt {
+        match self.is_empty() {
+            true => BigInt::zero(),
+            false => &self.end - &self.start + 1,
+        }
+    }
+
+    pub fn contains(&self, x: &BigInt) -> bool {
+        &self.start <= x && x <= &self.end
+    }
+
+    /// The integers in both, if there are any
+    pub fn intersect(&self, other: &Interval) -> Option<Interval> {
+        let overlap = Interval::new((&self.start).max(&other.start), (&self.end).min(&other.end));
+        (!overlap.is_empty()).then_some(overlap)
+    }
+}
+
+/// A set of integers, as disjoint intervals
+///
+/// Intervals which overlap or touch are merged as they go in, so each one in the tree is as long
+/// as it can be, with a gap of at least one either side.
+#[derive(Debug, Default, PartialEq, Eq, Clone)]
+pub struct IntervalTree {
+    // Start of each interval, to its end
+    intervals: BTreeMap<BigInt, BigInt>,
+}
+
+impl IntervalTree {
+    pub fn get_intervals(&self) -> Vec<Interval> {
+        self.iter().map(|(x, y)| Interval::new(x, y)).collect()
+    }
+
+    /// The intervals in order, as (start, end), without copying them out
+    pub fn iter(&self) -> impl Iterator<Item = (&BigInt, &BigInt)> {
+        self.intervals.iter()
+    }
+
+    /// Number of intervals, not of integers: that's [`IntervalTree::measure`]
+    pub fn len(&self) -> usize {
+        self.intervals.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.intervals.is_empty()
+    }
+
+    /// How many integers are in the set
+    pub fn measure(&self) -> BigInt {
+        self.iter().map(|(start, end)| end - start + 1).sum()
+    }
+
+    pub fn contains(&self, x: &BigInt) -> bool {
+        // The only interval which could is the last one starting at or before x
+        self.intervals
+            .range(..=x)
+            .next_back()
+            .is_some_and(|(_, end)| x <= end)
+    }
+
+    pub fn insert_interval(&mut self, interval: &Interval) {
+        if interval.is_empty() {
+            return;
+        }
+        let Interval { mut start, mut end } = interval.clone();
+
+        // An interval starting before this one might reach into it, or end right next to it
+        if let Some((s, e)) = self.intervals.range(..&start).next_back() {
+            if e + 1 >= start {
+                start = s.clone();
+                end = end.max(e.clone());
+            }
+        }
+        // And any starting inside it, or just after it, are swallowed up
+        let after: BigInt = &end + 1;
+        let swallowed: Vec<BigInt> = self
+            .intervals
+            .range(&start..=&after)
+            .map(|(s, _)| s.clone())
+            .collect();
+        for s in swallowed {
+            let e = self.intervals.remove(&s).unwrap();
+            end = end.max(e);
+        }
+
+        self.intervals.insert(start, end);
+    }
+
+    /// The integers in both, found by walking through the two lists of intervals together
+    pub fn intersect(&self, other: &IntervalTree) -> IntervalTree {
+        let mut result = IntervalTree::default();
+        let (mut xs, mut ys) = (self.iter().peekable(), other.iter().peekable());
+        while let (Some(&(x0, x1)), Some(&(y0, y1))) = (xs.peek(), ys.peek()) {
+            // Neither side has any gaps to fill in, so what's in both doesn't need merging
+            if let Some(Interval { start, end }) =
+                Interval::new(x0, x1).intersect(&Interval::new(y0, y1))
+            {
+                result.intervals.insert(start, end);
+            }
+            // Whichever ends first can't overlap anything else on the other side
+            match x1 < y1 {
+                true => xs.next(),
+                false => ys.next(),
+            };
+        }
+        result
+    }
 }
 
 #[cfg(test)]
 mod tests {
 
     use super::*;
+    use num_traits::FromPrimitive;
+    use rand::Rng;
+    use std::ops::RangeInclusive;
+
+    #[test]
+    fn chinese_remainders() {
+        let moduli = [3, 5, 7].map(BigInt::from);
+        let residues = [2, 3, 2].map(BigInt::from);
+        let system: Vec<_> = moduli.into_iter().zip(residues).collect();
+        assert_eq!(crt(&system), Some((23.into(), 105.into())));
+        assert_eq!(crt(&[(4.into(), 1.into()), (6.into(), 1.into())]), None);
+    }
+
+    #[test]
+    fn interval_tests() {
+        let mut tree = IntervalTree::default();
+        assert_eq!(tree.get_intervals(), vec![]);
+        let five_ten_int = Interval::new(
+            &BigInt::from_i32(5).unwrap(),
+            &BigInt::from_i32(10).unwrap(),
+        );
+        tree.insert_interval(&five_ten_int);
+        assert_eq!(tree.get_intervals(), vec![five_ten_int.clone()]);
+        let twelve_thirteen_int = Interval::new(
+            &BigInt::from_i32(12).unwrap(),
+            &BigInt::from_i32(13).unwrap(),
+        );
+        tree.insert_interval(&twelve_thirteen_int);
+        assert_eq!(
+            tree.get_intervals(),
+            vec![five_ten_int, twelve_thirteen_int]
+        );
+        let eleven_fifteen_int = Interval::new(
+            &BigInt::from_i32(11).unwrap(),
+            &BigInt::from_i32(15).unwrap(),
+        );
+        tree.insert_interval(&eleven_fifteen_int);
+        let five_fifteen_int = Interval::new(
+            &BigInt::from_i32(5).unwrap(),
+            &BigInt::from_i32(15).unwrap(),
+        );
+        assert_eq!(tree.get_intervals(), vec![five_fifteen_int]);
+        let two_six_int =
+            Interval::new(&BigInt::from_i32(2).unwrap(), &BigInt::from_i32(6).unwrap());
+        tree.insert_interval(&two_six_int);
+        let two_fifteen_int = Interval::new(
+            &BigInt::from_i32(2).unwrap(),
+            &BigInt::from_i32(15).unwrap(),
+        );
+        assert_eq!(tree.get_intervals(), vec![two_fifteen_int]);
+
+        let mut tree = IntervalTree::default();
+        let five_five_int =
+            Interval::new(&BigInt::from_i32(5).unwrap(), &BigInt::from_i32(5).unwrap());
+        let five_six_int =
+            Interval::new(&BigInt::from_i32(5).unwrap(), &BigInt::from_i32(6).unwrap());
+        let six_six_int =
+            Interval::new(&BigInt::from_i32(5).unwrap(), &BigInt::from_i32(6).unwrap());
+        tree.insert_interval(&five_five_int);
+        tree.insert_interval(&six_six_int);
+        assert_eq!(tree.get_intervals(), vec![five_six_int]);
+    }
+
+    #[test]
+    fn interval_tree_against_model() {
+        // A naive model, as a list of possibly overlapping ranges, checked point by point over a
+        // window a bit wider than they can reach
+        let mut rng = rand::thread_rng();
+        let window = -2..72;
+        let random_tree = |rng: &mut rand::rngs::ThreadRng| {
+            let mut tree = IntervalTree::default();
+            let mut model = vec![];
+            for _ in 0..rng.gen_range(0..8) {
+                let start: i64 = rng.gen_range(0..64);
+                // Including the odd empty one
+                let end = start + rng.gen_range(-1..8);
+                tree.insert_interval(&Interval::new(&start.into(), &end.into()));
+                model.push(start..=end);
+            }
+            (tree, model)
+        };
+        let covered = |model: &[RangeInclusive<i64>], x: i64| model.iter().any(|r| r.contains(&x));
+        // Checks the tree holds exactly the points in the window for which `covered` holds
+        let check = |tree: &IntervalTree, covered: &dyn Fn(i64) -> bool| {
+            let points: Vec<i64> = window.clone().filter(|x| covered(*x)).collect();
+            for x in window.clone() {
+                assert_eq!(tree.contains(&x.into()), covered(x), "{x}");
+            }
+            assert_eq!(tree.measure(), points.len().into());
+
+            // Runs of consecutive points, which the intervals ought to be exactly
+            let mut runs: Vec<(i64, i64)> = vec![];
+            for x in points {
+                match runs.last_mut() {
+                    Some((_, end)) if *end + 1 == x => *end = x,
+                    _ => runs.push((x, x)),
+                }
+            }
+            let intervals: Vec<(i64, i64)> = tree
+                .iter()
+                .map(|(s, e)| (s.try_into().unwrap(), e.try_into().unwrap()))
+                .collect();
+            assert_eq!(intervals, runs);
+            assert_eq!(tree.len(), runs.len());
+            assert_eq!(tree.is_empty(), runs.is_empty());
+        };
+
+        for _ in 0..1000 {
+            let (tree, model) = random_tree(&mut rng);
+            check(&tree, &|x| covered(&model, x));
+
+            let (other, other_model) = random_tree(&mut rng);
+            let both = tree.intersect(&other);
+            check(&both, &|x| covered(&model, x) && covered(&other_model, x));
+            assert_eq!(both, other.intersect(&tree));
+        }
+    }
+
+    #[test]
+    fn intervals() {
+        let i = |s: i64, e: i64| Interval::new(&s.into(), &e.into());
+        assert_eq!(i(3, 7).measure(), 5.into());
+        assert_eq!(i(7, 3).measure(), 0.into());
+        assert!(i(3, 7).contains(&7.into()));
+        assert!(!i(3, 7).contains(&8.into()));
+        assert_eq!(i(3, 7).intersect(&i(5, 10)), Some(i(5, 7)));
+        assert_eq!(i(3, 7).intersect(&i(7, 10)), Some(i(7, 7)));
+        assert_eq!(i(3, 7).intersect(&i(8, 10)), None);
+    }
 
     #[test]
     fn ones_test() {
@@ -187,4 +762,130 @@ mod tests {
 
         assert_eq!(hamming(first, second), 37);
     }
+
+    #[test]
+    fn lanes_match_bytewise() {
+        let mut rng = rand::thread_rng();
+        for (l, kl) in [
+            (0, 1),
+            (7, 3),
+            (8, 8),
+            (33, 1),
+            (45, 5),
+            (64, 29),
+            (100, 200),
+        ] {
+            let a = random_key(l, &mut rng);
+            let x = random_key(kl, &mut rng);
+            let expected: Vec<u8> = std::iter::zip(&a, x.iter().cycle())
+                .map(|(v, k)| v ^ k)
+                .collect();
+            assert_eq!(xor_bytes(&a, &x), expected);
+
+            let b = random_key(l + kl % 3, &mut rng);
+            let expected: u64 = std::iter::zip(&a, &b)
+                .map(|(v, w)| (v ^ w).count_ones() as u64)
+                .sum();
+            assert_eq!(hamming_bytes(&a, &b), expected);
+        }
+    }
+
+    #[test]
+    fn ranked_candidates() {
+        let scorer = Scorer::from_text(
+            crate::data::ALICE.bytes().unwrap(),
+            crate::scoring::NGram::Bigram,
+        );
+        let ciphertext = xor_bytes(b"Down the rabbit hole", &[0x5a]);
+        let candidates = crack_single_xor(&ciphertext, &scorer, 3);
+
+        assert_eq!(candidates.len(), 3);
+        assert_eq!(candidates[0].key, 0x5a);
+        assert_eq!(candidates[0].plaintext, b"Down the rabbit hole");
+        assert!(candidates.windows(2).all(|w| w[0].score <= w[1].score));
+    }
+
+    #[test]
+    fn search() {
+        let (found, stats) = par_search(0..1 << 20, |x| x == 12345);
+        assert_eq!(found, Some(12345));
+        assert!(stats.tried > 0);
+        let (found, stats) = par_search(0..1000, |_| false);
+        assert_eq!(found, None);
+        assert_eq!(stats.tried, 1000);
+    }
+
+    #[test]
+    fn url_safe_base64() {
+        let bytes = [0xfb, 0xff];
+        assert_eq!(bytes_to_b64_str(&bytes), "+/8=");
+        assert_eq!(bytes_to_b64_url(&bytes, true), "-_8=");
+        assert_eq!(bytes_to_b64_url(&bytes, false), "-_8");
+        assert_eq!(b64_url_to_bytes("-_8=").unwrap(), bytes);
+        assert_eq!(b64_url_to_bytes("-_8").unwrap(), bytes);
+        assert!(b64_url_to_bytes("+/8=").is_err());
+    }
+
+    #[test]
+    fn base58() {
+        // From the base58 IETF draft
+        for (bytes, encoded) in [
+            (&b"Hello World!"[..], "2NEpo7TZRRrLZSi2U"),
+            (&[0, 0, 0x28, 0x7f, 0xb4, 0xcd], "11233QC4"),
+            (&[], ""),
+            (&[0], "1"),
+        ] {
+            assert_eq!(bytes_to_base58(bytes), encoded);
+            assert_eq!(base58_to_bytes(encoded).unwrap(), bytes);
+        }
+        assert!(base58_to_bytes("0OIl").is_err());
+
+        let mut rng = rand::thread_rng();
+        let bytes = random_key(100, &mut rng);
+        assert_eq!(base58_to_bytes(&bytes_to_base58(&bytes)).unwrap(), bytes);
+    }
+
+    #[test]
+    fn streaming_codecs() {
+        let mut rng = rand::thread_rng();
+        let bytes = random_key(10_000, &mut rng);
+
+        let mut writer = HexWriter::new(vec![]);
+        for chunk in bytes.chunks(777) {
+            writer.write_all(chunk).unwrap();
+        }
+        let hex = writer.into_inner();
+        assert_eq!(hex, bytes_to_hex(&bytes).as_bytes());
+        // Line breaks anywhere, even mid-byte
+        let wrapped: Vec<u8> = hex.chunks(61).flat_map(|l| [l, b"\n"].concat()).collect();
+        let mut decoded = vec![];
+        HexReader::new(&wrapped[..])
+            .read_to_end(&mut decoded)
+            .unwrap();
+        assert_eq!(decoded, bytes);
+        assert!(HexReader::new(&b"abc"[..])
+            .read_to_end(&mut vec![])
+            .is_err());
+        assert!(HexReader::new(&b"zz"[..]).read_to_end(&mut vec![]).is_err());
+
+        let mut writer = base64_writer(vec![]);
+        writer.write_all(&bytes).unwrap();
+        let encoded = writer.finish().unwrap();
+        assert_eq!(encoded, bytes_to_b64_str(&bytes).as_bytes());
+        let wrapped: Vec<u8> = encoded
+            .chunks(60)
+            .flat_map(|l| [l, b"\r\n"].concat())
+            .collect();
+        let mut decoded = vec![];
+        base64_reader(&wrapped[..])
+            .read_to_end(&mut decoded)
+            .unwrap();
+        assert_eq!(decoded, bytes);
+
+        let mut decoded = vec![];
+        base64_reader(crate::data::CHALLENGE_6.bytes().unwrap())
+            .read_to_end(&mut decoded)
+            .unwrap();
+        assert_eq!(decoded, crate::data::CHALLENGE_6.base64().unwrap());
+    }
 }
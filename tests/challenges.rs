@@ -0,0 +1,371 @@
+//! Runs each challenge through the binary with a fixed seed, scaled down by `--quick` and, where
+//! its options allow, by `--arg`, and checks the JSON record it gives
+//!
+//! Every challenge has to pass, and every check in its report has to hold: a recovered value has
+//! to be what it should have been, and a forgery has to have been accepted. On top of that each
+//! test names the checks it expects to see, so that one going missing fails too, and for the
+//! challenges with nothing to check against, a recovered value or a count it should have. The few
+//! which take minutes even when scaled down are ignored, for `cargo test -- --ignored` to pick up.
+
+use std::process::Command;
+
+/// What each challenge's randomness is seeded with, so a failure can be repeated
+const SEED: u64 = 1;
+
+/// Just enough of JSON to read a record back
+#[derive(Debug, Clone, PartialEq)]
+enum Json {
+    Null,
+    Bool(bool),
+    Number(String),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    fn parse(text: &str) -> Json {
+        let mut chars = text.trim().chars().peekable();
+        let json = Self::value(&mut chars);
+        assert_eq!(chars.next(), None, "trailing characters in {text}");
+        json
+    }
+
+    fn value(chars: &mut std::iter::Peekable<std::str::Chars>) -> Json {
+        let skip_space = |chars: &mut std::iter::Peekable<std::str::Chars>| {
+            while chars.next_if(|c| c.is_whitespace()).is_some() {}
+        };
+        skip_space(chars);
+        let json = match chars.peek().copied() {
+            Some('{') => {
+                chars.next();
+                let mut members = vec![];
+                while chars.next_if_eq(&'}').is_none() {
+                    chars.next_if_eq(&',');
+                    let Json::String(name) = Self::value(chars) else {
+                        panic!("object keys are strings");
+                    };
+                    skip_space(chars);
+                    assert_eq!(chars.next(), Some(':'));
+                    members.push((name, Self::value(chars)));
+                    skip_space(chars);
+                }
+                Json::Object(members)
+            }
+            Some('[') => {
+                chars.next();
+                let mut items = vec![];
+                while chars.next_if_eq(&']').is_none() {
+                    chars.next_if_eq(&',');
+                    items.push(Self::value(chars));
+                    skip_space(chars);
+                }
+                Json::Array(items)
+            }
+            Some('"') => {
+                chars.next();
+                let mut s = String::new();
+                loop {
+                    match chars.next().expect("unterminated string") {
+                        '"' => break,
+                        '\\' => match chars.next().expect("unterminated escape") {
+                            'n' => s.push('\n'),
+                            'r' => s.push('\r'),
+                            't' => s.push('\t'),
+                            'u' => {
+                                let hex: String = chars.by_ref().take(4).collect();
+                                let c = u32::from_str_radix(&hex, 16).unwrap();
+                                s.push(char::from_u32(c).unwrap());
+                            }
+                            c => s.push(c),
+                        },
+                        c => s.push(c),
+                    }
+                }
+                Json::String(s)
+            }
+            Some(c) if c.is_alphabetic() => {
+                let word: String =
+                    std::iter::from_fn(|| chars.next_if(|c| c.is_alphabetic())).collect();
+                match word.as_str() {
+                    "null" => Json::Null,
+                    "true" => Json::Bool(true),
+                    "false" => Json::Bool(false),
+                    _ => panic!("{word} isn't JSON"),
+                }
+            }
+            _ => Json::Number(
+                std::iter::from_fn(|| {
+                    chars.next_if(|c| "+-.eE".contains(*c) || c.is_ascii_digit())
+                })
+                .collect(),
+            ),
+        };
+        skip_space(chars);
+        json
+    }
+
+    /// The member `name` of an object, if there is one
+    fn get(&self, name: &str) -> Option<&Json> {
+        match self {
+            Json::Object(members) => members.iter().find(|(n, _)| n == name).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    fn members(&self) -> &[(String, Json)] {
+        match self {
+            Json::Object(members) => members,
+            _ => &[],
+        }
+    }
+
+    fn as_str(&self) -> &str {
+        match self {
+            Json::String(s) | Json::Number(s) => s,
+            _ => panic!("{self:?} isn't a string"),
+        }
+    }
+}
+
+/// Runs `challenge` with `--arg` for each of `args`, and gives back its record, having checked
+/// that it passed and that every check in it held
+fn run(challenge: u64, args: &[&str]) -> Json {
+    let output = Command::new(env!("CARGO_BIN_EXE_cryptopals"))
+        .args(["-c", &challenge.to_string(), "--seed", &SEED.to_string()])
+        .args(["--format", "json", "--quick"])
+        .args(args.iter().flat_map(|arg| ["--arg", arg]))
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let line = stdout
+        .lines()
+        .find(|line| line.starts_with('{'))
+        .unwrap_or_else(|| panic!("challenge {challenge} gave no record:\n{stdout}\n{stderr}"));
+    let record = Json::parse(line);
+
+    let status = record.get("status").map(Json::as_str);
+    assert_eq!(
+        status,
+        Some("pass"),
+        "challenge {challenge} failed with --seed {SEED}: {:?}\n{stderr}",
+        record.get("error")
+    );
+    for (name, check) in record.get("checks").map_or(&[][..], Json::members) {
+        match (
+            check.get("expected"),
+            check.get("actual"),
+            check.get("accepted"),
+        ) {
+            (Some(expected), Some(actual), _) => {
+                assert_eq!(expected, actual, "challenge {challenge}'s {name}")
+            }
+            (_, _, Some(accepted)) => {
+                assert_eq!(
+                    accepted,
+                    &Json::Bool(true),
+                    "challenge {challenge}'s {name}"
+                )
+            }
+            _ => {}
+        }
+    }
+    record
+}
+
+macro_rules! challenge {
+    (
+        $(#[$attr:meta])* $name:ident: $challenge:expr, [$($arg:expr),*]
+        $(, checks [$($check:expr),*])?
+        $(, recovered [$(($recovered:expr, $value:expr)),*])?
+        $(, counts [$(($count:expr, $n:expr)),*])?
+    ) => {
+        #[test]
+        $(#[$attr])*
+        fn $name() {
+            let record = run($challenge, &[$($arg),*]);
+            $($(
+                assert!(
+                    record.get("checks").and_then(|checks| checks.get($check)).is_some(),
+                    "challenge {} didn't check {:?}: {record:?}",
+                    $challenge,
+                    $check
+                );
+            )*)?
+            $($(
+                let value = record
+                    .get("recovered")
+                    .and_then(|recovered| recovered.get($recovered))
+                    .unwrap_or_else(|| panic!("no {:?} in {record:?}", $recovered));
+                assert!(
+                    value.as_str().contains($value),
+                    "challenge {}'s {:?} is {value:?}, without {:?}",
+                    $challenge,
+                    $recovered,
+                    $value
+                );
+            )*)?
+            $($(
+                let count = record.get("counts").and_then(|counts| counts.get($count));
+                assert_eq!(
+                    count.map(Json::as_str),
+                    Some($n.to_string().as_str()),
+                    "challenge {}'s {:?}",
+                    $challenge,
+                    $count
+                );
+            )*)?
+        }
+    };
+}
+
+// Set 1
+challenge!(challenge01: 1, [], checks ["base64"]);
+challenge!(challenge02: 2, [], checks ["xor"]);
+challenge!(challenge03: 3, [], recovered [("plaintext", "Cooking MC's like a pound of bacon")]);
+challenge!(challenge04: 4, [], recovered [("plaintext", "Now that the party is jumping")]);
+challenge!(challenge05: 5, [], checks ["ciphertext"]);
+challenge!(
+    challenge06: 6, [],
+    recovered [("key", "5465726d696e61746f7220583a204272696e6720746865206e6f697365")]
+);
+challenge!(challenge07: 7, [], recovered [("plaintext", "Play that funky music")]);
+challenge!(challenge08: 8, [], recovered [("line", "132")]);
+
+// Set 2
+challenge!(challenge09: 9, [], checks ["padded"]);
+challenge!(
+    #[ignore = "downloads 10.txt from cryptopals.com"]
+    challenge10: 10, [], recovered [("plaintext", "Play that funky music")]
+);
+challenge!(challenge11: 11, [], checks ["mode"]);
+// "Rollin' in my 5.0"
+challenge!(challenge12: 12, [], recovered [("secret", "526f6c6c696e2720696e206d7920352e30")]);
+challenge!(
+    challenge13: 13, [],
+    checks [
+        "Naive injection role",
+        "Naive cut and paste role",
+        "Reject cut and paste role",
+        "Escape cut and paste role"
+    ]
+);
+challenge!(challenge14: 14, [], recovered [("secret", "526f6c6c696e2720696e206d7920352e30")]);
+challenge!(challenge15: 15, [], checks ["unpadded"]);
+challenge!(challenge16: 16, [], checks ["admin"]);
+
+// Set 3
+challenge!(challenge17: 17, [], checks ["plaintext", "without the IV"]);
+challenge!(challenge18: 18, [], checks ["plaintext"]);
+challenge!(challenge19: 19, [], recovered [("plaintexts", "A terrible beauty is born.")]);
+challenge!(challenge20: 20, [], recovered [("plaintexts", "I'm rated \"R\"...this is a warning")]);
+challenge!(challenge21: 21, [], recovered [("outputs", "2357136044 2546248239 3071714933 ")]);
+challenge!(
+    challenge22: 22, [],
+    checks ["timestamp within 1000 seconds", "timestamp within an hour", "timestamp within a day"]
+);
+challenge!(
+    challenge23: 23, [],
+    checks ["clone", "clone with outputs missing", "clone with bits missing", "64-bit clone"]
+);
+challenge!(challenge24: 24, [], checks ["seed", "token", "token timestamp"]);
+
+// Set 4
+challenge!(challenge25: 25, [], checks ["plaintext", "ciphertext afterwards"]);
+challenge!(challenge26: 26, [], checks ["admin"]);
+challenge!(challenge27: 27, [], checks ["key"]);
+challenge!(challenge28: 28, [], recovered [("mac", "")]);
+challenge!(challenge29: 29, [], checks ["mac", "key length"]);
+challenge!(challenge30: 30, [], checks ["mac", "key length"]);
+challenge!(
+    #[ignore = "sleeps 50ms a byte, for tens of minutes"]
+    challenge31: 31, [], checks ["mac"]
+);
+challenge!(
+    #[ignore = "sleeps for minutes"]
+    challenge32: 32, [], checks ["mac"]
+);
+
+// Set 5
+challenge!(challenge33: 33, [], checks ["shared secret", "echoed"]);
+challenge!(challenge34: 34, [], checks ["shared secret", "key"]);
+challenge!(
+    challenge35: 35, [],
+    checks ["B's secret with g = 1", "B's secret with g = p", "B's secret with g = p - 1"]
+);
+challenge!(challenge36: 36, [], recovered [("s", "")]);
+challenge!(
+    challenge37: 37, [],
+    checks ["login with A = 0N", "login with A = 4N", "S with A = 4N"],
+    counts [("logins without the password", 5)]
+);
+challenge!(challenge38: 38, [], checks ["hmac", "password"]);
+challenge!(challenge39: 39, [], checks ["decrypted"]);
+challenge!(challenge40: 40, [], checks ["broadcast", "padded broadcast"]);
+
+// Set 6
+challenge!(challenge41: 41, [], checks ["secret"]);
+challenge!(challenge42: 42, [], checks ["forgery"]);
+challenge!(challenge43: 43, [], checks ["fingerprint"]);
+challenge!(challenge44: 44, [], checks ["fingerprint"]);
+challenge!(challenge45: 45, [], checks ["Hello, world!", "Goodbye, world!"]);
+challenge!(challenge46: 46, [], checks ["secret"]);
+challenge!(challenge47: 47, [], checks ["message"]);
+challenge!(
+    #[ignore = "takes minutes in a debug build"]
+    challenge48: 48, [], checks ["message"]
+);
+
+// Set 7
+challenge!(challenge49: 49, [], checks ["forged iv", "extended mac"]);
+challenge!(challenge50: 50, [], checks ["mac", "forgery's mac"]);
+challenge!(
+    #[ignore = "can run for many minutes"]
+    challenge51: 51, [], checks ["session id, stream", "session id, CBC"]
+);
+challenge!(challenge52: 52, [], checks ["fast hash", "slow hash"]);
+challenge!(challenge53: 53, [], checks ["length", "hash"]);
+challenge!(challenge54: 54, [], checks ["first block", "hash", "blocks"]);
+challenge!(challenge55: 55, [], checks ["hash"]);
+challenge!(
+    #[ignore = "takes tens of minutes"]
+    challenge56: 56, [], checks ["cookie"]
+);
+
+// Set 8
+challenge!(challenge57: 57, [], checks ["honest mac", "public key"]);
+challenge!(challenge58: 58, [], checks ["public key"]);
+challenge!(challenge59: 59, [], checks ["honest mac", "public key"]);
+challenge!(
+    #[ignore = "takes minutes"]
+    challenge60: 60, [], checks ["public key"]
+);
+challenge!(challenge61: 61, [], checks ["ecdsa public key", "rsa key"]);
+challenge!(challenge62: 62, ["trials=1"], counts [("keys recovered", 1), ("trials", 1)]);
+challenge!(challenge63: 63, [], checks ["h", "opened"]);
+challenge!(challenge64: 64, ["blocks_log=8", "tag_bits=16"], checks ["h"]);
+challenge!(
+    challenge65: 65, ["length=4081", "tag_bits=16"],
+    checks ["h, 4081 bytes, 16-bit tag"]
+);
+challenge!(
+    #[ignore = "the key can't be shortened from the command line, so this takes minutes"]
+    challenge66: 66, ["fault_odds=16", "kangaroo_bits=12"], checks ["key"]
+);
+
+#[test]
+fn bench_and_unused_arguments() {
+    let cryptopals = |args: &[&str]| {
+        let output = Command::new(env!("CARGO_BIN_EXE_cryptopals"))
+            .args(args)
+            .output()
+            .unwrap();
+        output.status.success()
+    };
+    assert!(cryptopals(&["--bench", "-c", "1", "--iters", "2"]));
+    assert!(cryptopals(&["bench", "-c", "1", "--iters", "2"]));
+    // Without --bench, --iters means nothing, so shouldn't be quietly dropped
+    assert!(!cryptopals(&["-c", "1", "--iters", "2"]));
+    assert!(!cryptopals(&["bench", "lll", "extra"]));
+}
@@ -0,0 +1,38 @@
+//! Uses the primitives the challenges are built on from outside the crate, as anything else
+//! depending on it would
+
+use cryptopals::ec::{params, Point};
+use cryptopals::hash::md4::md4_hash;
+use cryptopals::stream::{Ctr, StreamCipher};
+use cryptopals::utils::{decode_b64_str, Interval};
+use num_bigint::BigInt;
+
+#[test]
+fn md4() {
+    assert_eq!(md4_hash(b"abc"), "a448017aaf21d8525fc10ae87aa6729d");
+}
+
+#[test]
+fn ctr() {
+    let ciphertext =
+        decode_b64_str("L77na/nrFsKvynd6HzOoG7GHTLXsTVu9qvY/2syLXzhPweyyMTJULu/6/kXX0KSvoOLSFQ==")
+            .unwrap();
+    let plaintext = Ctr::new(b"YELLOW SUBMARINE", 0).apply_keystream(&ciphertext);
+    assert!(plaintext.starts_with(b"Yo, VIP Let's kick it Ice, Ice, baby"));
+}
+
+#[test]
+fn curves() {
+    let n = params::base_order();
+    assert_eq!(params::montgomery().ladder(&BigInt::from(4), n), 0.into());
+    let curve = params::curve();
+    assert_eq!(curve.scale(params::base_point(), n), Point::O);
+}
+
+#[test]
+fn intervals() {
+    let a = Interval::new(&1.into(), &10.into());
+    let b = Interval::new(&5.into(), &20.into());
+    assert_eq!(a.intersect(&b), Some(Interval::new(&5.into(), &10.into())));
+    assert_eq!(a.measure(), 10.into());
+}